@@ -0,0 +1,99 @@
+//! Minimal venue independent traits for trading systems that work across several exchanges.
+//! Markets and assets are identified by the venue's own names; amounts and prices are exact
+//! decimals.
+
+use async_trait::async_trait;
+use bigdecimal::BigDecimal;
+use futures::stream::BoxStream;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Side {
+    Buy,
+    Sell,
+}
+
+/// How long an order stays on the book
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum TimeInForce {
+    GoodTilCancelled,
+    /// Execute what is possible immediately and cancel the rest
+    ImmediateOrCancel,
+    /// Execute completely and immediately, or not at all
+    FillOrKill,
+}
+
+/// Limit order to place on a market
+#[derive(Clone, Debug, PartialEq)]
+pub struct OrderRequest {
+    pub market: String,
+    pub side: Side,
+    /// In the market's base asset
+    pub amount: BigDecimal,
+    /// In the market's quote asset per unit of base asset
+    pub price: BigDecimal,
+    pub time_in_force: TimeInForce,
+    /// Reject the order instead of letting it take liquidity
+    pub post_only: bool,
+}
+
+/// Acknowledgement of a placed order
+#[derive(Clone, Debug, PartialEq)]
+pub struct OrderAck {
+    pub order_id: String,
+}
+
+/// Balance of one asset held on the venue
+#[derive(Clone, Debug, PartialEq)]
+pub struct Balance {
+    pub asset: String,
+    /// Free to trade or withdraw
+    pub available: BigDecimal,
+    /// Reserved by open orders
+    pub in_orders: BigDecimal,
+}
+
+/// Best prices of a market. A side is `None` while the book is empty on it.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TopOfBook {
+    pub market: String,
+    pub best_bid: Option<BigDecimal>,
+    pub best_ask: Option<BigDecimal>,
+    pub last_price: Option<BigDecimal>,
+}
+
+/// A trading venue. The other traits build on this one so that they share an error type.
+pub trait Venue: Send + Sync {
+    type Error: std::error::Error + Send + Sync + 'static;
+
+    /// Short name of the venue, e.g. for logs
+    fn venue_name(&self) -> &str;
+}
+
+#[async_trait]
+pub trait OrderEntry: Venue {
+    async fn place_order(&self, order: &OrderRequest) -> Result<OrderAck, Self::Error>;
+
+    async fn cancel_order(&self, market: &str, order_id: &str) -> Result<(), Self::Error>;
+}
+
+#[async_trait]
+pub trait Balances: Venue {
+    /// Balances of every asset the account holds on the venue
+    async fn balances(&self) -> Result<Vec<Balance>, Self::Error>;
+}
+
+#[async_trait]
+pub trait MarketData: Venue {
+    async fn top_of_book(&self, market: &str) -> Result<TopOfBook, Self::Error>;
+
+    /// Top of book updates of `market` until the stream is dropped
+    async fn subscribe_top_of_book(
+        &self,
+        market: &str,
+    ) -> Result<BoxStream<'static, Result<TopOfBook, Self::Error>>, Self::Error>;
+}
+
+/// Everything a multi-venue system needs from a venue
+pub trait Exchange: OrderEntry + Balances + MarketData {}
+
+impl<T: OrderEntry + Balances + MarketData> Exchange for T {}
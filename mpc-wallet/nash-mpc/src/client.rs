@@ -7,11 +7,10 @@ use crate::common::{
 };
 #[cfg(feature = "secp256k1")]
 use crate::curves::secp256_k1::{Secp256k1Point, Secp256k1Scalar};
-#[cfg(feature = "k256")]
+#[cfg(all(feature = "k256", not(feature = "secp256k1")))]
 use crate::curves::secp256_k1_rust::{Secp256k1Point, Secp256k1Scalar};
 use crate::curves::secp256_r1::{Secp256r1Point, Secp256r1Scalar};
 use crate::curves::traits::{ECPoint, ECScalar};
-use chrono::prelude::{DateTime, Utc};
 use crossbeam_queue::SegQueue;
 use lazy_static::__Deref;
 #[cfg(feature = "num_bigint")]
@@ -28,6 +27,8 @@ use rayon::iter::{IntoParallelIterator, ParallelIterator};
 use rust_bigint::traits::{Converter, Modulo, Samplable, ZeroizeBN};
 use rust_bigint::BigInt;
 use serde::{Deserialize, Serialize};
+#[cfg(not(feature = "wasm"))]
+use std::time::{SystemTime, UNIX_EPOCH};
 use zeroize::Zeroize;
 use zeroize::Zeroizing;
 
@@ -249,11 +250,26 @@ fn compute_presig_curveindependent(
 // two pools of r-values (one for each curve) and one pool of random values for Paillier.
 // mutex is essential for security (helps us ensuring that no value is used twice).
 lazy_static! {
-    static ref RPOOL_SECP256R1: SegQueue<(BigInt, (DateTime<Utc>, BigInt))> = SegQueue::new();
-    static ref RPOOL_SECP256K1: SegQueue<(BigInt, (DateTime<Utc>, BigInt))> = SegQueue::new();
+    static ref RPOOL_SECP256R1: SegQueue<(BigInt, (i64, BigInt))> = SegQueue::new();
+    static ref RPOOL_SECP256K1: SegQueue<(BigInt, (i64, BigInt))> = SegQueue::new();
     static ref POOL_PAILLIER: SegQueue<BigInt> = SegQueue::new();
 }
 
+/// seconds since the epoch at which an r-value was added to a pool.
+/// std has no clock on wasm, so the time comes from javascript through chrono there.
+#[cfg(feature = "wasm")]
+fn now_secs() -> i64 {
+    chrono::Utc::now().timestamp()
+}
+
+#[cfg(not(feature = "wasm"))]
+fn now_secs() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_secs() as i64)
+        .unwrap_or(0)
+}
+
 /// fill pool of random values for Paillier
 fn fill_pool_paillier(n: usize, paillier_pk: &EncryptionKey) {
     // sequentially for wasm, else parallel
@@ -286,13 +302,13 @@ pub fn fill_rpool_secp256r1(
     #[cfg(feature = "wasm")]
     for i in 0..own_dh_secrets.len() {
         if let Ok(r) = other_dh_publics[i].scalar_mul(&own_dh_secrets[i].fe) {
-            RPOOL_SECP256R1.push((r.to_bigint(), (Utc::now(), own_dh_secrets[i].to_bigint())));
+            RPOOL_SECP256R1.push((r.to_bigint(), (now_secs(), own_dh_secrets[i].to_bigint())));
         }
     }
     #[cfg(not(feature = "wasm"))]
     (0..own_dh_secrets.len()).into_par_iter().for_each(|i| {
         if let Ok(r) = other_dh_publics[i].scalar_mul(&own_dh_secrets[i].fe) {
-            RPOOL_SECP256R1.push((r.to_bigint(), (Utc::now(), own_dh_secrets[i].to_bigint())));
+            RPOOL_SECP256R1.push((r.to_bigint(), (now_secs(), own_dh_secrets[i].to_bigint())));
         }
     });
     for i in &mut own_dh_secrets {
@@ -315,13 +331,13 @@ pub fn fill_rpool_secp256k1(
     #[cfg(feature = "wasm")]
     for i in 0..own_dh_secrets.len() {
         if let Ok(r) = other_dh_publics[i].scalar_mul(&own_dh_secrets[i].fe) {
-            RPOOL_SECP256K1.push((r.to_bigint(), (Utc::now(), own_dh_secrets[i].to_bigint())));
+            RPOOL_SECP256K1.push((r.to_bigint(), (now_secs(), own_dh_secrets[i].to_bigint())));
         }
     }
     #[cfg(not(feature = "wasm"))]
     (0..own_dh_secrets.len()).into_par_iter().for_each(|i| {
         if let Ok(r) = other_dh_publics[i].scalar_mul(&own_dh_secrets[i].fe) {
-            RPOOL_SECP256K1.push((r.to_bigint(), (Utc::now(), own_dh_secrets[i].to_bigint())));
+            RPOOL_SECP256K1.push((r.to_bigint(), (now_secs(), own_dh_secrets[i].to_bigint())));
         };
     });
     for i in &mut own_dh_secrets {
@@ -339,7 +355,7 @@ pub fn get_rpool_size(curve: Curve) -> Result<usize, ()> {
         /*RPOOL_SECP256K1
             .lock()
             .unwrap()
-            .retain(|_, v| Utc::now() - v.0 < Duration::hours(48));
+            .retain(|_, v| now_secs() - v.0 < 48 * 60 * 60);
         Ok(RPOOL_SECP256K1.lock().unwrap().len())*/
         Ok(RPOOL_SECP256K1.len())
     } else if curve == Curve::Secp256r1 {
@@ -347,7 +363,7 @@ pub fn get_rpool_size(curve: Curve) -> Result<usize, ()> {
         /*RPOOL_SECP256R1
             .lock()
             .unwrap()
-            .retain(|_, v| Utc::now() - v.0 < Duration::hours(48));
+            .retain(|_, v| now_secs() - v.0 < 48 * 60 * 60);
         Ok(RPOOL_SECP256R1.len())*/
         Ok(RPOOL_SECP256R1.len())
     } else {
@@ -406,7 +422,7 @@ mod tests {
     use crate::common::{CorrectKeyProof, Curve};
     #[cfg(feature = "secp256k1")]
     use crate::curves::secp256_k1::{Secp256k1Point, Secp256k1Scalar};
-    #[cfg(feature = "k256")]
+    #[cfg(all(feature = "k256", not(feature = "secp256k1")))]
     use crate::curves::secp256_k1_rust::{Secp256k1Point, Secp256k1Scalar};
     use crate::curves::secp256_r1::{Secp256r1Point, Secp256r1Scalar};
     use crate::curves::traits::ECScalar;
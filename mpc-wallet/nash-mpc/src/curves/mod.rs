@@ -5,3 +5,73 @@ pub mod secp256_k1_rust;
 
 pub mod secp256_r1;
 pub mod traits;
+
+/// With both backends enabled, the libsecp256k1 bindings are used and the pure Rust
+/// arithmetic is only compiled to be checked against them.
+#[cfg(all(test, feature = "secp256k1", feature = "k256"))]
+mod tests {
+    use super::secp256_k1::{Secp256k1Point as NativePoint, Secp256k1Scalar as NativeScalar};
+    use super::secp256_k1_rust::{Secp256k1Point as RustPoint, Secp256k1Scalar as RustScalar};
+    use super::traits::{ECPoint, ECScalar};
+
+    fn scalar_pair() -> (NativeScalar, RustScalar) {
+        let native = NativeScalar::new_random().unwrap();
+        let rust = RustScalar::from(&native.to_bigint()).unwrap();
+        (native, rust)
+    }
+
+    #[test]
+    fn backends_agree_on_scalar_arithmetic() {
+        for _ in 0..20 {
+            let (a, a_rust) = scalar_pair();
+            let (b, b_rust) = scalar_pair();
+            assert_eq!(
+                a.add(&b.fe).unwrap().to_bigint(),
+                a_rust.add(&b_rust.fe).unwrap().to_bigint()
+            );
+            assert_eq!(
+                a.mul(&b.fe).unwrap().to_bigint(),
+                a_rust.mul(&b_rust.fe).unwrap().to_bigint()
+            );
+            assert_eq!(
+                a.sub(&b.fe).unwrap().to_bigint(),
+                a_rust.sub(&b_rust.fe).unwrap().to_bigint()
+            );
+            assert_eq!(
+                a.invert().unwrap().to_bigint(),
+                a_rust.invert().unwrap().to_bigint()
+            );
+        }
+    }
+
+    #[test]
+    fn backends_agree_on_point_arithmetic() {
+        assert_eq!(
+            NativePoint::generator().to_vec(),
+            RustPoint::generator().to_vec()
+        );
+        for _ in 0..20 {
+            let (a, a_rust) = scalar_pair();
+            let (b, b_rust) = scalar_pair();
+            let p = NativePoint::generator().scalar_mul(&a.fe).unwrap();
+            let p_rust = RustPoint::generator().scalar_mul(&a_rust.fe).unwrap();
+            assert_eq!(p.to_hex(), p_rust.to_hex());
+            let q = NativePoint::generator().scalar_mul(&b.fe).unwrap();
+            let q_rust = RustPoint::generator().scalar_mul(&b_rust.fe).unwrap();
+            assert_eq!(
+                p.add_point(&q.ge).unwrap().to_hex(),
+                p_rust.add_point(&q_rust.ge).unwrap().to_hex()
+            );
+            assert_eq!(
+                p.sub_point(&q.ge).unwrap().to_hex(),
+                p_rust.sub_point(&q_rust.ge).unwrap().to_hex()
+            );
+            assert_eq!(
+                p.scalar_mul(&b.fe).unwrap().to_hex(),
+                p_rust.scalar_mul(&b_rust.fe).unwrap().to_hex()
+            );
+            let decoded = RustPoint::from_bytes(&p.to_vec()).unwrap();
+            assert_eq!(decoded.to_hex(), p.to_hex());
+        }
+    }
+}
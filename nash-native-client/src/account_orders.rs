@@ -0,0 +1,590 @@
+//! Local view of the account's open orders, kept from the account orders subscription and
+//! reconciled with `ListAccountOrdersRequest` snapshots: when it starts, and whenever the
+//! subscription had to be renewed and updates may have been missed. Orders can be looked up
+//! by id and by client order id. A subscription that stays quiet is checked against a
+//! snapshot, and renewed if it missed changes. If the subscription can't be renewed, open
+//! orders are polled instead until it can, so fills are still seen while private streams
+//! are down.
+
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::sync::{Arc, RwLock as SyncRwLock};
+use std::time::Duration;
+
+use tokio::sync::broadcast;
+use tokio_util::sync::CancellationToken;
+use tracing::warn;
+
+use nash_protocol::errors::{ProtocolError, Result};
+use nash_protocol::protocol::get_account_order::GetAccountOrderRequest;
+use nash_protocol::protocol::list_account_orders::ListAccountOrdersRequest;
+use nash_protocol::protocol::subscriptions::updated_account_orders::{
+    AccountOrdersResponse, SubscribeAccountOrders,
+};
+use nash_protocol::types::{Order, OrderStatus};
+
+use crate::tags::OrderTag;
+use crate::{Client, SubscriptionHandle};
+
+const RESUBSCRIBE_ATTEMPTS: u32 = 3;
+const RESUBSCRIBE_BACKOFF: Duration = Duration::from_secs(1);
+const CHANGES_CAPACITY: usize = 1024;
+/// Closed order ids remembered so late updates don't bring them back
+const RECENTLY_CLOSED: usize = 1_000;
+/// Time between snapshots while the subscription is down
+const POLL_INTERVAL: Duration = Duration::from_secs(2);
+/// Polls between attempts to renew the subscription
+const POLLS_PER_RESUBSCRIBE: u32 = 15;
+/// Consecutive failed polls after which the view is no longer kept up to date
+const MAX_FAILED_POLLS: u32 = 30;
+/// Time without updates after which the subscription is checked against a snapshot
+const STALENESS_CHECK_INTERVAL: Duration = Duration::from_secs(60);
+
+fn is_open(order: &Order) -> bool {
+    matches!(order.status, OrderStatus::Open | OrderStatus::Pending)
+}
+
+/// Open orders of the account by id, with an index by client order id
+#[derive(Clone, Debug, Default)]
+pub struct OpenOrders {
+    orders: HashMap<String, Order>,
+    client_order_ids: HashMap<String, String>,
+    closed: VecDeque<String>,
+    closed_ids: HashSet<String>,
+}
+
+impl OpenOrders {
+    /// Apply an update of an order, returning whether it changed the view. Updates of orders
+    /// already closed, or that executed less than what is known, arrived late and are ignored.
+    pub fn apply(&mut self, order: &Order) -> bool {
+        if self.closed_ids.contains(&order.id) {
+            return false;
+        }
+        if !is_open(order) {
+            self.close(&order.id);
+            return true;
+        }
+        if let Some(known) = self.orders.get(&order.id) {
+            if known.amount_executed > order.amount_executed {
+                return false;
+            }
+        }
+        self.insert(order.clone());
+        true
+    }
+
+    /// Replace the view with the open orders of `snapshot`, keeping what is known of an order
+    /// if it is newer than the snapshot. Returns the orders of the view missing from the
+    /// snapshot, i.e. those filled or cancelled while the view was not kept up to date.
+    pub fn reconcile(&mut self, snapshot: Vec<Order>) -> Vec<Order> {
+        let mut known = std::mem::take(&mut self.orders);
+        self.client_order_ids.clear();
+        for order in snapshot {
+            if !is_open(&order) || self.closed_ids.contains(&order.id) {
+                continue;
+            }
+            let order = match known.remove(&order.id) {
+                Some(known) if known.amount_executed > order.amount_executed => known,
+                _ => order,
+            };
+            self.insert(order);
+        }
+        known.into_iter().map(|(_, order)| order).collect()
+    }
+
+    /// Compare a snapshot of the open orders with the view. Returns the orders of the
+    /// snapshot that are new or changed, and the ids of orders missing from it: those were
+    /// closed since, or the snapshot was taken before they were placed.
+    pub fn diff(&self, snapshot: &[Order]) -> (Vec<Order>, Vec<String>) {
+        let mut listed = HashSet::new();
+        let mut changed = Vec::new();
+        for order in snapshot {
+            if !is_open(order) || self.closed_ids.contains(&order.id) {
+                continue;
+            }
+            listed.insert(order.id.as_str());
+            let is_change = match self.orders.get(&order.id) {
+                Some(known) => {
+                    known.amount_executed < order.amount_executed || known.status != order.status
+                }
+                None => true,
+            };
+            if is_change {
+                changed.push(order.clone());
+            }
+        }
+        let missing = self
+            .orders
+            .keys()
+            .filter(|id| !listed.contains(id.as_str()))
+            .cloned()
+            .collect();
+        (changed, missing)
+    }
+
+    fn insert(&mut self, order: Order) {
+        if let Some(client_order_id) = &order.client_order_id {
+            self.client_order_ids
+                .insert(client_order_id.clone(), order.id.clone());
+        }
+        self.orders.insert(order.id.clone(), order);
+    }
+
+    fn close(&mut self, id: &str) {
+        if let Some(order) = self.orders.remove(id) {
+            if let Some(client_order_id) = &order.client_order_id {
+                self.client_order_ids.remove(client_order_id);
+            }
+        }
+        self.closed_ids.insert(id.to_string());
+        self.closed.push_back(id.to_string());
+        if self.closed.len() > RECENTLY_CLOSED {
+            if let Some(id) = self.closed.pop_front() {
+                self.closed_ids.remove(&id);
+            }
+        }
+    }
+
+    pub fn get(&self, id: &str) -> Option<&Order> {
+        self.orders.get(id)
+    }
+
+    pub fn get_by_client_order_id(&self, client_order_id: &str) -> Option<&Order> {
+        self.client_order_ids
+            .get(client_order_id)
+            .and_then(|id| self.orders.get(id))
+    }
+
+    pub fn orders(&self) -> impl Iterator<Item = &Order> {
+        self.orders.values()
+    }
+
+    /// Open orders tagged with `strategy` through their client order id
+    pub fn of_strategy<'a>(&'a self, strategy: &'a str) -> impl Iterator<Item = &'a Order> {
+        self.orders
+            .values()
+            .filter(move |order| OrderTag::of(order).map_or(false, |tag| tag.strategy == strategy))
+    }
+
+    pub fn len(&self) -> usize {
+        self.orders.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.orders.is_empty()
+    }
+}
+
+/// Where a change of `AccountOrderUpdates` was learned from
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ChangeSource {
+    Subscription,
+    /// Found by comparing snapshots of the open orders while the subscription was down.
+    /// Orders may have changed more than once between two polls.
+    Poll,
+}
+
+/// Change to the open orders of `AccountOrderUpdates`
+#[derive(Clone, Debug)]
+pub enum AccountOrderChange {
+    /// An order was placed or partially filled
+    Updated { order: Order, source: ChangeSource },
+    /// An order was filled or cancelled, and is no longer open
+    Closed { order: Order, source: ChangeSource },
+    /// The view was replaced by a snapshot, because updates may have been missed
+    Reconciled { open: usize },
+    /// The subscription could not be renewed, changes are polled until it can
+    Polling(ProtocolError),
+    /// The view is no longer kept up to date
+    Stopped(ProtocolError),
+}
+
+/// Open orders of the account, kept up to date in the background. Dropping it unsubscribes.
+pub struct AccountOrderUpdates {
+    orders: Arc<SyncRwLock<OpenOrders>>,
+    error: Arc<SyncRwLock<Option<ProtocolError>>>,
+    changes: broadcast::Sender<AccountOrderChange>,
+    stop: CancellationToken,
+}
+
+impl AccountOrderUpdates {
+    pub fn open_orders(&self) -> Vec<Order> {
+        self.orders.read().unwrap().orders().cloned().collect()
+    }
+
+    pub fn get(&self, id: &str) -> Option<Order> {
+        self.orders.read().unwrap().get(id).cloned()
+    }
+
+    pub fn get_by_client_order_id(&self, client_order_id: &str) -> Option<Order> {
+        let orders = self.orders.read().unwrap();
+        orders.get_by_client_order_id(client_order_id).cloned()
+    }
+
+    /// Copy of the whole view
+    pub fn snapshot(&self) -> OpenOrders {
+        self.orders.read().unwrap().clone()
+    }
+
+    pub fn changes(&self) -> broadcast::Receiver<AccountOrderChange> {
+        self.changes.subscribe()
+    }
+
+    /// Why the view stopped being kept up to date, if it did
+    pub fn error(&self) -> Option<ProtocolError> {
+        self.error.read().unwrap().clone()
+    }
+}
+
+impl Drop for AccountOrderUpdates {
+    fn drop(&mut self) {
+        self.stop.cancel();
+    }
+}
+
+impl Client {
+    /// Keep a local view of the account's open orders, of every market or only of `market`
+    pub async fn account_order_updates(&self, market: Option<&str>) -> Result<AccountOrderUpdates> {
+        let market = market.map(|market| market.to_string());
+        // subscribed before the snapshot is taken, so no update falls in between
+        let updates = self.subscribe_account_orders(&market).await?;
+        let mut orders = OpenOrders::default();
+        orders.reconcile(self.open_orders_snapshot(&market).await?);
+        let (changes, _) = broadcast::channel(CHANGES_CAPACITY);
+        let handle = AccountOrderUpdates {
+            orders: Arc::new(SyncRwLock::new(orders)),
+            error: Arc::new(SyncRwLock::new(None)),
+            changes,
+            stop: CancellationToken::new(),
+        };
+        let client = self.clone();
+        let orders = handle.orders.clone();
+        let error = handle.error.clone();
+        let changes = handle.changes.clone();
+        let stop = handle.stop.clone();
+        tokio::spawn(async move {
+            let reason = tokio::select! {
+                reason = client.maintain_open_orders(&market, updates, &orders, &changes) => reason,
+                _ = stop.cancelled() => return,
+            };
+            warn!(error = %reason.report(), "open orders are no longer maintained");
+            *error.write().unwrap() = Some(reason.clone());
+            let _ = changes.send(AccountOrderChange::Stopped(reason));
+        });
+        Ok(handle)
+    }
+
+    async fn subscribe_account_orders(
+        &self,
+        market: &Option<String>,
+    ) -> Result<SubscriptionHandle<AccountOrdersResponse>> {
+        self.subscribe_protocol(SubscribeAccountOrders {
+            market: market.clone(),
+            buy_or_sell: None,
+            status: None,
+            order_type: None,
+            range: None,
+        })
+        .await
+    }
+
+    async fn open_orders_snapshot(&self, market: &Option<String>) -> Result<Vec<Order>> {
+        self.pages(ListAccountOrdersRequest {
+            market: market.clone(),
+            ..ListAccountOrdersRequest::default()
+                .with_statuses(&[OrderStatus::Open, OrderStatus::Pending])
+        })
+        .all()
+        .await
+    }
+
+    /// Apply updates to `orders` until they can't be kept up to date anymore, returning why
+    async fn maintain_open_orders(
+        &self,
+        market: &Option<String>,
+        mut updates: SubscriptionHandle<AccountOrdersResponse>,
+        orders: &SyncRwLock<OpenOrders>,
+        changes: &broadcast::Sender<AccountOrderChange>,
+    ) -> ProtocolError {
+        loop {
+            loop {
+                let update = tokio::select! {
+                    update = updates.recv() => update,
+                    _ = tokio::time::sleep(STALENESS_CHECK_INTERVAL) => {
+                        match self.missed_changes(market, orders).await {
+                            Ok(false) => continue,
+                            Ok(true) => {
+                                warn!("account orders subscription missed changes, resubscribing");
+                                break;
+                            }
+                            // checked again after the next quiet interval
+                            Err(e) => {
+                                warn!(error = %e.report(), "could not check account orders subscription");
+                                continue;
+                            }
+                        }
+                    }
+                };
+                let update = match update {
+                    Some(update) => update,
+                    None => {
+                        warn!("account orders subscription ended, resubscribing");
+                        break;
+                    }
+                };
+                let update = match update.and_then(|update| update.response_or_error()) {
+                    Ok(update) => update,
+                    Err(e) => {
+                        warn!(error = %e.report(), "invalid account orders update");
+                        continue;
+                    }
+                };
+                for order in update.orders {
+                    apply_change(order, ChangeSource::Subscription, orders, changes);
+                }
+            }
+            let renewed = match self
+                .renew_account_orders(market, RESUBSCRIBE_ATTEMPTS)
+                .await
+            {
+                Ok(renewed) => renewed,
+                Err(e) => {
+                    warn!(error = %e.report(), "could not resubscribe to account orders, polling");
+                    let _ = changes.send(AccountOrderChange::Polling(e));
+                    match self.poll_open_orders(market, orders, changes).await {
+                        Ok(renewed) => renewed,
+                        Err(e) => return e.context("polling open orders"),
+                    }
+                }
+            };
+            let (renewed, snapshot) = renewed;
+            updates = renewed;
+            let gone = orders.write().unwrap().reconcile(snapshot);
+            self.close_gone_orders(gone, orders, changes).await;
+            let open = orders.read().unwrap().len();
+            let _ = changes.send(AccountOrderChange::Reconciled { open });
+        }
+    }
+
+    /// Send the orders that a reconcile found to be no longer open as closed. Each is fetched
+    /// to tell whether it was filled or cancelled; one that can't be fetched is closed as it
+    /// was last known.
+    async fn close_gone_orders(
+        &self,
+        gone: Vec<Order>,
+        orders: &SyncRwLock<OpenOrders>,
+        changes: &broadcast::Sender<AccountOrderChange>,
+    ) {
+        for order in gone {
+            let fetched = self
+                .run(GetAccountOrderRequest {
+                    order_id: order.id.clone(),
+                })
+                .await
+                .and_then(|response| response.response_or_error());
+            match fetched {
+                Ok(fetched) => apply_change(fetched.into(), ChangeSource::Poll, orders, changes),
+                Err(e) => {
+                    warn!(order_id = %order.id, error = %e.report(), "could not look up order that is no longer open");
+                    orders.write().unwrap().close(&order.id);
+                    let source = ChangeSource::Poll;
+                    let _ = changes.send(AccountOrderChange::Closed { order, source });
+                }
+            }
+        }
+    }
+
+    /// Whether a snapshot of the open orders differs from the view, i.e. the subscription
+    /// missed changes. Only called after a quiet interval, so updates in flight are unlikely.
+    async fn missed_changes(
+        &self,
+        market: &Option<String>,
+        orders: &SyncRwLock<OpenOrders>,
+    ) -> Result<bool> {
+        let snapshot = self.open_orders_snapshot(market).await?;
+        let (changed, missing) = orders.read().unwrap().diff(&snapshot);
+        Ok(!changed.is_empty() || !missing.is_empty())
+    }
+
+    /// Subscribe to account orders again and take a snapshot of the open orders
+    async fn renew_account_orders(
+        &self,
+        market: &Option<String>,
+        attempts: u32,
+    ) -> Result<(SubscriptionHandle<AccountOrdersResponse>, Vec<Order>)> {
+        let mut renewed = Err(ProtocolError("Could not resubscribe to account orders"));
+        for attempt in 1..=attempts {
+            tokio::time::sleep(RESUBSCRIBE_BACKOFF * attempt).await;
+            renewed = match self.subscribe_account_orders(market).await {
+                Ok(renewed) => self
+                    .open_orders_snapshot(market)
+                    .await
+                    .map(|snapshot| (renewed, snapshot)),
+                Err(e) => Err(e),
+            };
+            if renewed.is_ok() {
+                break;
+            }
+        }
+        renewed
+    }
+
+    /// Poll the open orders into `orders` until the subscription is renewed, returning it.
+    /// Fails once polling failed `MAX_FAILED_POLLS` times in a row.
+    async fn poll_open_orders(
+        &self,
+        market: &Option<String>,
+        orders: &SyncRwLock<OpenOrders>,
+        changes: &broadcast::Sender<AccountOrderChange>,
+    ) -> Result<(SubscriptionHandle<AccountOrdersResponse>, Vec<Order>)> {
+        let mut failed = 0;
+        let mut polls = 0;
+        loop {
+            tokio::time::sleep(POLL_INTERVAL).await;
+            match self.poll_open_orders_once(market, orders, changes).await {
+                Ok(()) => failed = 0,
+                Err(e) if failed + 1 < MAX_FAILED_POLLS => {
+                    failed += 1;
+                    warn!(error = %e.report(), %failed, "could not poll open orders");
+                }
+                Err(e) => return Err(e),
+            }
+            polls += 1;
+            if polls % POLLS_PER_RESUBSCRIBE == 0 {
+                if let Ok(renewed) = self.renew_account_orders(market, 1).await {
+                    return Ok(renewed);
+                }
+            }
+        }
+    }
+
+    /// Take a snapshot of the open orders and send the changes since the last one. Orders
+    /// missing from it are fetched to tell whether they were filled or cancelled; one that
+    /// can't be fetched is kept as it is and looked up again on the next poll.
+    async fn poll_open_orders_once(
+        &self,
+        market: &Option<String>,
+        orders: &SyncRwLock<OpenOrders>,
+        changes: &broadcast::Sender<AccountOrderChange>,
+    ) -> Result<()> {
+        let snapshot = self.open_orders_snapshot(market).await?;
+        let (changed, missing) = orders.read().unwrap().diff(&snapshot);
+        for order in changed {
+            apply_change(order, ChangeSource::Poll, orders, changes);
+        }
+        for order_id in missing {
+            let order = self
+                .run(GetAccountOrderRequest {
+                    order_id: order_id.clone(),
+                })
+                .await
+                .and_then(|response| response.response_or_error());
+            match order {
+                Ok(order) => apply_change(order.into(), ChangeSource::Poll, orders, changes),
+                Err(e) => {
+                    warn!(%order_id, error = %e.report(), "could not look up order missing from open orders")
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Apply an update of `order` to `orders`, sending the change if there is one
+fn apply_change(
+    order: Order,
+    source: ChangeSource,
+    orders: &SyncRwLock<OpenOrders>,
+    changes: &broadcast::Sender<AccountOrderChange>,
+) {
+    if !orders.write().unwrap().apply(&order) {
+        return;
+    }
+    let change = if is_open(&order) {
+        AccountOrderChange::Updated { order, source }
+    } else {
+        AccountOrderChange::Closed { order, source }
+    };
+    let _ = changes.send(change);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::OpenOrders;
+    use bigdecimal::BigDecimal;
+    use nash_protocol::types::timestamp::now;
+    use nash_protocol::types::{BuyOrSell, Order, OrderStatus, OrderType};
+
+    fn order(id: &str, executed: u32, status: OrderStatus) -> Order {
+        Order {
+            id: id.to_string(),
+            client_order_id: Some(format!("client-{}", id)),
+            amount_placed: BigDecimal::from(10),
+            amount_remaining: BigDecimal::from(10 - executed),
+            amount_executed: BigDecimal::from(executed),
+            limit_price: Some(BigDecimal::from(100)),
+            stop_price: None,
+            placed_at: now(),
+            buy_or_sell: BuyOrSell::Buy,
+            cancellation_policy: None,
+            cancellation_reason: None,
+            market: "eth_usdc".to_string(),
+            order_type: OrderType::Limit,
+            status,
+            trades: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn updates_and_snapshots_are_reconciled() {
+        let mut orders = OpenOrders::default();
+        orders.reconcile(vec![
+            order("a", 0, OrderStatus::Open),
+            order("b", 0, OrderStatus::Open),
+        ]);
+        assert!(orders.apply(&order("a", 4, OrderStatus::Open)));
+        // arrived after the update that executed 4
+        assert!(!orders.apply(&order("a", 2, OrderStatus::Open)));
+        assert!(orders.apply(&order("b", 10, OrderStatus::Filled)));
+        assert!(!orders.apply(&order("b", 0, OrderStatus::Open)));
+        assert!(orders.get_by_client_order_id("client-b").is_none());
+
+        // a snapshot taken before the fill of a, while c was placed
+        orders.reconcile(vec![
+            order("a", 2, OrderStatus::Open),
+            order("b", 0, OrderStatus::Open),
+            order("c", 0, OrderStatus::Pending),
+        ]);
+        assert_eq!(orders.len(), 2);
+        let a = orders.get_by_client_order_id("client-a").unwrap();
+        assert_eq!(a.amount_executed, BigDecimal::from(4));
+        assert!(orders.get("c").is_some());
+
+        // a was filled and c cancelled while updates were missed
+        let gone = orders.reconcile(vec![order("d", 0, OrderStatus::Open)]);
+        let mut gone: Vec<_> = gone.iter().map(|order| order.id.as_str()).collect();
+        gone.sort_unstable();
+        assert_eq!(gone, vec!["a", "c"]);
+        assert_eq!(orders.len(), 1);
+    }
+
+    #[test]
+    fn polled_snapshots_are_diffed() {
+        let mut orders = OpenOrders::default();
+        orders.reconcile(vec![
+            order("a", 0, OrderStatus::Open),
+            order("b", 0, OrderStatus::Open),
+            order("c", 0, OrderStatus::Open),
+        ]);
+        assert!(orders.apply(&order("d", 0, OrderStatus::Filled)));
+        let (changed, mut missing) = orders.diff(&[
+            order("a", 0, OrderStatus::Open),
+            order("b", 3, OrderStatus::Open),
+            order("d", 0, OrderStatus::Open),
+            order("e", 0, OrderStatus::Pending),
+        ]);
+        let changed: Vec<_> = changed.iter().map(|order| order.id.as_str()).collect();
+        assert_eq!(changed, vec!["b", "e"]);
+        missing.sort();
+        assert_eq!(missing, vec!["c".to_string()]);
+    }
+}
@@ -0,0 +1,284 @@
+//! Execution algorithms that spread a parent order over time. Child orders are placed
+//! through `place_limit_orders`, so they get its pre-trade checks and nonce handling.
+
+use std::time::Duration;
+
+use bigdecimal::{BigDecimal, Signed};
+use tokio::sync::watch;
+use tokio::task::JoinHandle;
+use tokio::time::Instant;
+use tokio_util::sync::CancellationToken;
+use tracing::{info, warn};
+
+use nash_protocol::errors::{ProtocolError, Result};
+use nash_protocol::protocol::place_order::LimitOrderRequest;
+use nash_protocol::protocol::place_orders::LimitOrdersRequest;
+use nash_protocol::types::{BuyOrSell, Market, OrderCancellationPolicy, RoundingMode};
+
+use crate::execution::{ChildExecution, ExecutionStage, ParentExecution};
+use crate::Client;
+
+/// Execute `amount` evenly over `window`. The window is split into `intervals`; at the
+/// start of each one a limit order for an equal share of what is left is placed, and
+/// whatever of it is still open when the interval ends is cancelled and carried over to
+/// the next slice.
+#[derive(Clone, Debug)]
+pub struct Twap {
+    pub market: String,
+    pub buy_or_sell: BuyOrSell,
+    pub amount: BigDecimal,
+    /// Worst price any slice is executed at
+    pub limit_price: BigDecimal,
+    pub window: Duration,
+    pub intervals: u32,
+    /// Let slices take liquidity instead of only adding it
+    pub allow_taker: bool,
+}
+
+impl Twap {
+    fn validate(&self) -> Result<()> {
+        if !self.amount.is_positive() || !self.limit_price.is_positive() {
+            return Err(ProtocolError(
+                "TWAP amount and limit price must be positive",
+            ));
+        }
+        if self.intervals == 0 || self.window.is_zero() {
+            return Err(ProtocolError(
+                "TWAP needs a window of at least one interval",
+            ));
+        }
+        Ok(())
+    }
+
+    fn interval(&self) -> Duration {
+        self.window / self.intervals
+    }
+
+    /// Size of the slice placed with `intervals_left` intervals to go, including this one.
+    /// Rounded down to the market's precision, but never below its minimum size, so small
+    /// remainders are executed early rather than not at all. `None` once what is left is
+    /// below the minimum size.
+    fn slice_amount(
+        &self,
+        remaining: &BigDecimal,
+        intervals_left: u32,
+        market: &Market,
+    ) -> Option<BigDecimal> {
+        let min_size = &market.min_trade_size_a.amount.value;
+        if !remaining.is_positive() || remaining < min_size {
+            return None;
+        }
+        if intervals_left <= 1 {
+            return Some(remaining.clone());
+        }
+        let share = (remaining / BigDecimal::from(intervals_left))
+            .with_scale(market.asset_a.precision as i64);
+        Some(if &share < min_size {
+            min_size.clone()
+        } else {
+            share
+        })
+    }
+
+    fn child_order(&self, amount: &BigDecimal) -> LimitOrderRequest {
+        LimitOrderRequest {
+            market: self.market.clone(),
+            client_order_id: None,
+            buy_or_sell: self.buy_or_sell,
+            amount: amount.to_string(),
+            price: self.limit_price.to_string(),
+            cancellation_policy: OrderCancellationPolicy::GoodTilCancelled,
+            allow_taker: self.allow_taker,
+        }
+    }
+}
+
+/// Handle to a running TWAP. Dropping it leaves the TWAP running.
+pub struct TwapHandle {
+    cancel: CancellationToken,
+    progress: watch::Receiver<ParentExecution>,
+    task: JoinHandle<Result<ParentExecution>>,
+}
+
+impl TwapHandle {
+    /// Stop placing slices and cancel the open one. The rest of the amount is left unexecuted.
+    pub fn cancel_remainder(&self) {
+        self.cancel.cancel();
+    }
+
+    /// Slices settled so far
+    pub fn progress(&self) -> ParentExecution {
+        self.progress.borrow().clone()
+    }
+
+    /// Receiver that is updated every time a slice settles
+    pub fn subscribe(&self) -> watch::Receiver<ParentExecution> {
+        self.progress.clone()
+    }
+
+    /// Wait until the window is over, the amount executed or the remainder cancelled
+    pub async fn finished(self) -> Result<ParentExecution> {
+        self.task
+            .await
+            .map_err(|_| ProtocolError("TWAP task failed"))?
+    }
+}
+
+impl Client {
+    /// Start working `twap` in the background
+    pub async fn start_twap(&self, mut twap: Twap) -> Result<TwapHandle> {
+        twap.validate()?;
+        let market = self.inner.state.read().await.get_market(&twap.market)?;
+        // the exchange rejects prices with more decimals than the market has
+        twap.limit_price = RoundingMode::for_price(twap.buy_or_sell)
+            .round(&twap.limit_price, market.asset_b.precision)?;
+        self.warm_up(&twap.market).await?;
+        let execution = ParentExecution {
+            market: twap.market.clone(),
+            buy_or_sell: twap.buy_or_sell,
+            requested: twap.amount.clone(),
+            children: Vec::new(),
+        };
+        let (progress_sender, progress) = watch::channel(execution.clone());
+        let cancel = CancellationToken::new();
+        let task_cancel = cancel.clone();
+        let client = self.clone();
+        let task = tokio::spawn(async move {
+            client
+                .run_twap(twap, market, execution, progress_sender, task_cancel)
+                .await
+        });
+        Ok(TwapHandle {
+            cancel,
+            progress,
+            task,
+        })
+    }
+
+    async fn run_twap(
+        &self,
+        twap: Twap,
+        market: Market,
+        mut execution: ParentExecution,
+        progress: watch::Sender<ParentExecution>,
+        cancel: CancellationToken,
+    ) -> Result<ParentExecution> {
+        let start = Instant::now();
+        for index in 0..twap.intervals {
+            if cancel.is_cancelled() {
+                break;
+            }
+            let interval_end = start + twap.interval() * (index + 1);
+            let intervals_left = twap.intervals - index;
+            let amount = match twap.slice_amount(&execution.remaining(), intervals_left, &market) {
+                Some(amount) => amount,
+                None => break,
+            };
+            let slice = self
+                .execute_twap_slice(&twap, &amount, interval_end, &cancel)
+                .await
+                .map_err(|e| {
+                    e.context(format!(
+                        "TWAP stopped after {} of {} executed",
+                        execution.executed(),
+                        execution.requested
+                    ))
+                })?;
+            if let Some(slice) = slice {
+                info!(market = %twap.market, interval = index, executed = %slice.executed, "TWAP slice settled");
+                execution.children.push(slice);
+                // nobody watching is fine
+                let _ = progress.send(execution.clone());
+            }
+            tokio::select! {
+                _ = tokio::time::sleep_until(interval_end) => {}
+                _ = cancel.cancelled() => {}
+            }
+        }
+        if !execution.is_complete() {
+            info!(market = %twap.market, remaining = %execution.remaining(), "TWAP ended with a remainder");
+        }
+        Ok(execution)
+    }
+
+    /// Place one slice and wait until it settles or `interval_end`. A rejected slice is
+    /// skipped, leaving its amount to the following ones.
+    async fn execute_twap_slice(
+        &self,
+        twap: &Twap,
+        amount: &BigDecimal,
+        interval_end: Instant,
+        cancel: &CancellationToken,
+    ) -> Result<Option<ChildExecution>> {
+        let request = LimitOrdersRequest::new(vec![twap.child_order(amount)])?;
+        let placement = self.place_limit_orders(request).await?;
+        let placed = match placement.outcomes.into_iter().next() {
+            Some(Ok(placed)) => placed,
+            Some(Err(rejected)) => {
+                warn!(market = %twap.market, %amount, message = %rejected.message, "TWAP slice rejected");
+                return Ok(None);
+            }
+            None => return Ok(None),
+        };
+        let timeout = interval_end.saturating_duration_since(Instant::now());
+        let order = self
+            .await_fill(&placed.order_id, &twap.market, timeout, cancel)
+            .await?;
+        Ok(Some(ChildExecution {
+            stage: ExecutionStage::Interval,
+            order_id: order.id,
+            limit_price: twap.limit_price.clone(),
+            amount: amount.clone(),
+            executed: order.amount_executed,
+            status: order.status,
+            trades: order.trades,
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{BigDecimal, BuyOrSell, Twap};
+    use nash_protocol::types::{Amount, Asset, AssetAmount, Market};
+    use std::str::FromStr;
+    use std::time::Duration;
+
+    fn dec(value: &str) -> BigDecimal {
+        BigDecimal::from_str(value).unwrap()
+    }
+
+    #[test]
+    fn slices_spread_remainder_over_intervals_left() {
+        let eth = Asset::ETH.with_precision(4);
+        let usdc = Asset::USDC.with_precision(2);
+        let min_size = |asset| AssetAmount {
+            asset,
+            amount: Amount::new("0.01", 4).unwrap(),
+        };
+        let market = Market::new(eth.clone(), usdc.clone(), min_size(eth), min_size(usdc));
+        let twap = Twap {
+            market: "eth_usdc".to_string(),
+            buy_or_sell: BuyOrSell::Buy,
+            amount: dec("1"),
+            limit_price: dec("200"),
+            window: Duration::from_secs(60),
+            intervals: 3,
+            allow_taker: true,
+        };
+        assert!(twap.validate().is_ok());
+        assert_eq!(twap.interval(), Duration::from_secs(20));
+        assert_eq!(
+            twap.slice_amount(&dec("1"), 3, &market),
+            Some(dec("0.3333"))
+        );
+        assert_eq!(
+            twap.slice_amount(&dec("0.02"), 3, &market),
+            Some(dec("0.01"))
+        );
+        assert_eq!(
+            twap.slice_amount(&dec("0.6667"), 1, &market),
+            Some(dec("0.6667"))
+        );
+        assert_eq!(twap.slice_amount(&dec("0.005"), 2, &market), None);
+    }
+}
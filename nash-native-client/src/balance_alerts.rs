@@ -0,0 +1,359 @@
+//! Alerts on balances leaving configured bounds, e.g. too little of an asset left in the
+//! state channel to keep quoting, or funds piling up idle. Balances are checked once when the
+//! watch starts and then on every balance update. An alert is sent when a balance crosses a
+//! bound, not again while it stays out of bounds, and once more when it is back within them.
+
+use std::collections::HashMap;
+use std::fmt;
+
+use bigdecimal::BigDecimal;
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+use tokio_util::sync::CancellationToken;
+use tracing::{info, warn};
+
+use nash_protocol::errors::{ProtocolError, Result};
+use nash_protocol::protocol::list_account_balances::ListAccountBalancesRequest;
+use nash_protocol::protocol::subscriptions::updated_account_balances::SubscribeAccountBalances;
+use nash_protocol::types::Asset;
+
+use crate::Client;
+
+/// Part of an asset's balance that is watched
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum BalanceKind {
+    /// Funds in the personal wallet
+    Personal,
+    /// Funds available in the state channel, i.e. what new orders can use
+    Trading,
+}
+
+/// Bounds of one balance. Either may be left out.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Thresholds {
+    pub min: Option<BigDecimal>,
+    pub max: Option<BigDecimal>,
+}
+
+impl Thresholds {
+    pub fn new(min: Option<BigDecimal>, max: Option<BigDecimal>) -> Result<Self> {
+        if let (Some(min), Some(max)) = (&min, &max) {
+            if min > max {
+                return Err(ProtocolError("Minimum balance is above the maximum"));
+            }
+        }
+        Ok(Self { min, max })
+    }
+
+    fn breach(&self, balance: &BigDecimal) -> Option<Breach> {
+        if self.min.as_ref().map_or(false, |min| balance < min) {
+            Some(Breach::Low)
+        } else if self.max.as_ref().map_or(false, |max| balance > max) {
+            Some(Breach::Excess)
+        } else {
+            None
+        }
+    }
+}
+
+/// Thresholds per asset and balance kind
+#[derive(Clone, Debug, Default)]
+pub struct BalanceThresholds {
+    thresholds: HashMap<(Asset, BalanceKind), Thresholds>,
+}
+
+impl BalanceThresholds {
+    pub fn with_personal(self, asset: Asset, thresholds: Thresholds) -> Self {
+        self.with(asset, BalanceKind::Personal, thresholds)
+    }
+
+    pub fn with_trading(self, asset: Asset, thresholds: Thresholds) -> Self {
+        self.with(asset, BalanceKind::Trading, thresholds)
+    }
+
+    pub fn with(mut self, asset: Asset, kind: BalanceKind, thresholds: Thresholds) -> Self {
+        self.thresholds.insert((asset, kind), thresholds);
+        self
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.thresholds.is_empty()
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Breach {
+    Low,
+    Excess,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum BalanceAlert {
+    /// Balance fell below its minimum. For a trading balance: too little left to keep quoting.
+    Low {
+        asset: Asset,
+        kind: BalanceKind,
+        balance: BigDecimal,
+        min: BigDecimal,
+    },
+    /// Balance rose above its maximum, i.e. funds sit idle
+    Excess {
+        asset: Asset,
+        kind: BalanceKind,
+        balance: BigDecimal,
+        max: BigDecimal,
+    },
+    /// Balance is back within its thresholds after a `Low` or `Excess` alert
+    Cleared {
+        asset: Asset,
+        kind: BalanceKind,
+        balance: BigDecimal,
+    },
+}
+
+impl BalanceAlert {
+    pub fn asset(&self) -> Asset {
+        match self {
+            Self::Low { asset, .. } | Self::Excess { asset, .. } | Self::Cleared { asset, .. } => {
+                *asset
+            }
+        }
+    }
+
+    pub fn kind(&self) -> BalanceKind {
+        match self {
+            Self::Low { kind, .. } | Self::Excess { kind, .. } | Self::Cleared { kind, .. } => {
+                *kind
+            }
+        }
+    }
+}
+
+impl fmt::Display for BalanceAlert {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Low {
+                asset,
+                kind,
+                balance,
+                min,
+            } => write!(
+                f,
+                "{:?} {} balance {} is below {}",
+                kind,
+                asset.name(),
+                balance,
+                min
+            ),
+            Self::Excess {
+                asset,
+                kind,
+                balance,
+                max,
+            } => write!(
+                f,
+                "{:?} {} balance {} is above {}",
+                kind,
+                asset.name(),
+                balance,
+                max
+            ),
+            Self::Cleared {
+                asset,
+                kind,
+                balance,
+            } => write!(
+                f,
+                "{:?} {} balance {} is back within bounds",
+                kind,
+                asset.name(),
+                balance
+            ),
+        }
+    }
+}
+
+/// Checks balances against thresholds, remembering which are out of bounds
+#[derive(Debug)]
+struct BalanceChecker {
+    thresholds: BalanceThresholds,
+    breaches: HashMap<(Asset, BalanceKind), Breach>,
+}
+
+impl BalanceChecker {
+    fn new(thresholds: BalanceThresholds) -> Self {
+        Self {
+            thresholds,
+            breaches: HashMap::new(),
+        }
+    }
+
+    /// Alert for a new `balance`, if it crossed a threshold
+    fn check(
+        &mut self,
+        asset: Asset,
+        kind: BalanceKind,
+        balance: &BigDecimal,
+    ) -> Option<BalanceAlert> {
+        let thresholds = self.thresholds.thresholds.get(&(asset, kind))?;
+        let breach = thresholds.breach(balance);
+        let previous = match breach {
+            Some(breach) => self.breaches.insert((asset, kind), breach),
+            None => self.breaches.remove(&(asset, kind)),
+        };
+        if previous == breach {
+            return None;
+        }
+        let balance = balance.clone();
+        Some(match breach {
+            Some(Breach::Low) => BalanceAlert::Low {
+                asset,
+                kind,
+                balance,
+                min: thresholds.min.clone()?,
+            },
+            Some(Breach::Excess) => BalanceAlert::Excess {
+                asset,
+                kind,
+                balance,
+                max: thresholds.max.clone()?,
+            },
+            None => BalanceAlert::Cleared {
+                asset,
+                kind,
+                balance,
+            },
+        })
+    }
+
+    fn check_all(
+        &mut self,
+        kind: BalanceKind,
+        balances: &HashMap<Asset, BigDecimal>,
+    ) -> Vec<BalanceAlert> {
+        balances
+            .iter()
+            .filter_map(|(asset, balance)| self.check(*asset, kind, balance))
+            .collect()
+    }
+}
+
+/// Running balance watch, from `Client::watch_balances`. Dropping it stops the watch.
+pub struct BalanceWatch {
+    cancel: CancellationToken,
+    receiver: mpsc::UnboundedReceiver<BalanceAlert>,
+    task: JoinHandle<Result<()>>,
+}
+
+impl Drop for BalanceWatch {
+    fn drop(&mut self) {
+        self.cancel.cancel();
+    }
+}
+
+impl BalanceWatch {
+    /// Next alert, or `None` once the watch ended, see `stop` for why
+    pub async fn recv(&mut self) -> Option<BalanceAlert> {
+        self.receiver.recv().await
+    }
+
+    /// Stop watching. Returns the error the watch ended with, if it ended by itself.
+    pub async fn stop(mut self) -> Result<()> {
+        self.cancel.cancel();
+        (&mut self.task)
+            .await
+            .map_err(|_| ProtocolError("Balance watch task failed"))?
+    }
+}
+
+impl Client {
+    /// Watch balances against `thresholds`. Alerts are logged and can be received from the
+    /// returned watch. The watch ends if the balance subscription does.
+    pub async fn watch_balances(&self, thresholds: BalanceThresholds) -> Result<BalanceWatch> {
+        if thresholds.is_empty() {
+            return Err(ProtocolError("No balance thresholds to watch"));
+        }
+        // subscribed before listing balances so no update in between is missed
+        let mut updates = self
+            .subscribe_protocol(SubscribeAccountBalances { symbol: None })
+            .await?;
+        let balances = self
+            .run(ListAccountBalancesRequest { filter: None })
+            .await?
+            .response_or_error()?;
+        let (sender, receiver) = mpsc::unbounded_channel();
+        let cancel = CancellationToken::new();
+        let task_cancel = cancel.clone();
+        let task = tokio::spawn(async move {
+            let mut checker = BalanceChecker::new(thresholds);
+            let mut alerts = checker.check_all(BalanceKind::Personal, &balances.personal);
+            alerts.extend(checker.check_all(BalanceKind::Trading, &balances.state_channel));
+            loop {
+                for alert in alerts.drain(..) {
+                    match alert {
+                        BalanceAlert::Cleared { .. } => info!(%alert, "balance alert cleared"),
+                        _ => warn!(%alert, "balance alert"),
+                    }
+                    // a watch nobody receives from still logs its alerts
+                    let _ = sender.send(alert);
+                }
+                let update = tokio::select! {
+                    update = updates.recv() => update,
+                    _ = task_cancel.cancelled() => return Ok(()),
+                };
+                let update = match update {
+                    Some(update) => update?.response_or_error()?,
+                    None => {
+                        warn!("balance subscription of balance watch ended");
+                        return Err(ProtocolError("Balance subscription ended"));
+                    }
+                };
+                alerts = checker.check_all(BalanceKind::Personal, &update.personal);
+                alerts.extend(checker.check_all(BalanceKind::Trading, &update.balances));
+            }
+        });
+        Ok(BalanceWatch {
+            cancel,
+            receiver,
+            task,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{BalanceAlert, BalanceChecker, BalanceKind, BalanceThresholds, Thresholds};
+    use bigdecimal::BigDecimal;
+    use nash_protocol::types::Asset;
+
+    #[test]
+    fn alerts_fire_once_per_crossing() {
+        let thresholds =
+            Thresholds::new(Some(BigDecimal::from(10)), Some(BigDecimal::from(100))).unwrap();
+        let mut checker =
+            BalanceChecker::new(BalanceThresholds::default().with_trading(Asset::ETH, thresholds));
+        let mut check = |balance: i32| {
+            checker.check(Asset::ETH, BalanceKind::Trading, &BigDecimal::from(balance))
+        };
+        assert_eq!(check(50), None);
+        assert_eq!(
+            check(5),
+            Some(BalanceAlert::Low {
+                asset: Asset::ETH,
+                kind: BalanceKind::Trading,
+                balance: BigDecimal::from(5),
+                min: BigDecimal::from(10),
+            })
+        );
+        assert_eq!(check(4), None);
+        assert!(matches!(check(200), Some(BalanceAlert::Excess { .. })));
+        assert!(matches!(check(100), Some(BalanceAlert::Cleared { .. })));
+        assert_eq!(check(100), None);
+
+        assert_eq!(
+            checker.check(Asset::ETH, BalanceKind::Personal, &BigDecimal::from(0)),
+            None
+        );
+        assert!(Thresholds::new(Some(BigDecimal::from(2)), Some(BigDecimal::from(1))).is_err());
+    }
+}
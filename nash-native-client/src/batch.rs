@@ -0,0 +1,542 @@
+//! Batch order placement with per-order outcomes
+
+use std::future::Future;
+
+use tracing::warn;
+
+use nash_protocol::errors::{ProtocolError, Result};
+use nash_protocol::protocol::cancel_order::CancelOrderRequest;
+use nash_protocol::protocol::cancel_orders::{CancelOrdersRequest, CancelOrdersResponse};
+use nash_protocol::protocol::place_order::ChainSigningError;
+use nash_protocol::protocol::place_orders::{
+    LimitOrdersRequest, OrderPlaced, OrderRejected, PlaceOrdersResponse,
+};
+use nash_protocol::protocol::NashProtocol;
+use nash_protocol::types::Blockchain;
+
+use crate::config::BatchLimits;
+use crate::Client;
+
+const NOT_SENT: &str = "not sent, an earlier order of the all or nothing batch was rejected";
+const ROLLED_BACK: &str = "cancelled, another order of the all or nothing batch was rejected";
+
+type Outcome = std::result::Result<OrderPlaced, OrderRejected>;
+
+/// Limit orders to place together, and how to handle orders that can't be placed. A
+/// `LimitOrdersRequest` converts into a batch with every option off.
+#[derive(Clone, Debug)]
+pub struct OrderBatch {
+    pub orders: LimitOrdersRequest,
+    /// If any order is rejected, none should stay on the book. The Nash API places batch
+    /// orders individually, so the orders that were placed are cancelled again.
+    pub all_or_nothing: bool,
+    /// If signing fails for one blockchain (see `ChainSigningError`), reject the orders on
+    /// markets that involve it and place the others, instead of failing the whole batch
+    pub skip_failed_chains: bool,
+}
+
+impl OrderBatch {
+    pub fn new(orders: LimitOrdersRequest) -> Self {
+        Self {
+            orders,
+            all_or_nothing: false,
+            skip_failed_chains: false,
+        }
+    }
+
+    /// Set `all_or_nothing`
+    pub fn all_or_nothing(mut self, all_or_nothing: bool) -> Self {
+        self.all_or_nothing = all_or_nothing;
+        self
+    }
+
+    /// Set `skip_failed_chains`
+    pub fn skip_failed_chains(mut self, skip_failed_chains: bool) -> Self {
+        self.skip_failed_chains = skip_failed_chains;
+        self
+    }
+}
+
+impl From<LimitOrdersRequest> for OrderBatch {
+    fn from(orders: LimitOrdersRequest) -> Self {
+        Self::new(orders)
+    }
+}
+
+/// Result of `Client::place_limit_orders`
+#[derive(Debug)]
+pub struct BatchPlacement {
+    /// Outcome of every order, in request order
+    pub outcomes: Vec<std::result::Result<OrderPlaced, OrderRejected>>,
+    /// Number of orders in each request the batch was sent as, in the order they were sent
+    pub chunks: Vec<usize>,
+}
+
+impl BatchPlacement {
+    pub fn placed(&self) -> impl Iterator<Item = &OrderPlaced> {
+        self.outcomes
+            .iter()
+            .filter_map(|outcome| outcome.as_ref().ok())
+    }
+
+    pub fn rejected(&self) -> impl Iterator<Item = &OrderRejected> {
+        self.outcomes
+            .iter()
+            .filter_map(|outcome| outcome.as_ref().err())
+    }
+}
+
+impl Client {
+    /// Place a batch of limit orders and report the outcome of every order. Batches over the
+    /// `BatchLimits` are split into several requests, sent one after the other so that order
+    /// nonces keep increasing across them. If one of these requests fails, the orders placed
+    /// by the earlier ones are cancelled again and the error is returned, as it would be for
+    /// a batch sent in one request.
+    ///
+    /// If `batch.all_or_nothing` is set and any order is rejected, the placed orders are
+    /// cancelled again and reported as rejected. An order that could not be cancelled is still
+    /// reported as placed, as it may be on the book.
+    ///
+    /// If `batch.skip_failed_chains` is set, orders that can't be signed because signing
+    /// fails for one of their blockchains are rejected, and the others are still placed.
+    pub async fn place_limit_orders(&self, batch: impl Into<OrderBatch>) -> Result<BatchPlacement> {
+        let batch = batch.into();
+        // refuse the whole batch up front if an order lacks approval. Approvals are used up
+        // when the chunk carrying their order is sent, see `Client::run`
+        self.inner
+            .check_approval(batch.orders.limit_prices(), batch.orders.limit_sizes())?;
+        let chunk_size = self.inner.batch_limits.read().unwrap().orders_per_request();
+        let skip_failed_chains = batch.skip_failed_chains;
+        place_batch_with(
+            &batch,
+            chunk_size,
+            |chunk| self.place_chunk(chunk, skip_failed_chains),
+            |cancels| async move { self.run(cancels).await.and_then(|r| r.response_or_error()) },
+        )
+        .await
+    }
+
+    /// Place one chunk of a batch, see `place_chunk_with`
+    async fn place_chunk(
+        &self,
+        chunk: LimitOrdersRequest,
+        skip_failed_chains: bool,
+    ) -> Result<Vec<Outcome>> {
+        let blockchains = if skip_failed_chains {
+            let state = self.inner.state.read().await;
+            chunk
+                .requests
+                .iter()
+                .map(|order| Ok(state.get_market(&order.market)?.blockchains()))
+                .collect::<Result<Vec<_>>>()?
+        } else {
+            Vec::new()
+        };
+        place_chunk_with(&chunk, &blockchains, |request| async move {
+            self.run(request).await.and_then(|r| r.response_or_error())
+        })
+        .await
+    }
+
+    /// Change the limits applied by `place_limit_orders`, see `ClientConfig::batch`
+    pub fn set_batch_limits(&self, limits: BatchLimits) {
+        *self.inner.batch_limits.write().unwrap() = limits;
+    }
+}
+
+/// Place `batch` in chunks of `chunk_size` orders through `place`, cancelling through `cancel`
+/// what has to be rolled back, see `Client::place_limit_orders`
+async fn place_batch_with<P, PF, C, CF>(
+    batch: &OrderBatch,
+    chunk_size: usize,
+    mut place: P,
+    mut cancel: C,
+) -> Result<BatchPlacement>
+where
+    P: FnMut(LimitOrdersRequest) -> PF,
+    PF: Future<Output = Result<Vec<Outcome>>>,
+    C: FnMut(CancelOrdersRequest) -> CF,
+    CF: Future<Output = Result<CancelOrdersResponse>>,
+{
+    let request = &batch.orders;
+    let mut placement = BatchPlacement {
+        outcomes: Vec::with_capacity(request.requests.len()),
+        chunks: Vec::new(),
+    };
+    for chunk in request.chunks(chunk_size) {
+        let offset = placement.outcomes.len();
+        let len = chunk.requests.len();
+        if batch.all_or_nothing && placement.rejected().next().is_some() {
+            // no point sending orders that would be rolled back
+            placement
+                .outcomes
+                .extend((offset..offset + len).map(|index| {
+                    Err(OrderRejected {
+                        index,
+                        message: NOT_SENT.to_string(),
+                    })
+                }));
+            continue;
+        }
+        placement.chunks.push(len);
+        match place(chunk).await {
+            Ok(outcomes) => placement
+                .outcomes
+                .extend(outcomes.into_iter().map(|outcome| {
+                    outcome.map_err(|rejected| OrderRejected {
+                        index: offset + rejected.index,
+                        ..rejected
+                    })
+                })),
+            Err(error) if offset == 0 => return Err(error),
+            Err(error) => {
+                // the failed request may or may not have reached the exchange, but the
+                // earlier ones did
+                warn!(error = %error.report(), %offset, "batch chunk failed, cancelling earlier chunks");
+                let placed = placement.placed().count();
+                let failures = roll_back(request, &mut placement, chunk_size, &mut cancel).await;
+                let mut message = format!(
+                    "Batch request at order {} failed, cancelled {} of the {} orders placed before",
+                    offset,
+                    placed - placement.placed().count(),
+                    placed
+                );
+                for failure in &failures {
+                    message.push_str(&format!(" (could not cancel: {})", failure.report()));
+                }
+                return Err(ProtocolError::with_source(message, error));
+            }
+        }
+    }
+    if batch.all_or_nothing && placement.placed().count() < placement.outcomes.len() {
+        warn!(
+            rejected = placement.rejected().count(),
+            "all or nothing batch partially rejected, cancelling placed orders"
+        );
+        // orders that could not be cancelled are still reported as placed
+        roll_back(request, &mut placement, chunk_size, &mut cancel).await;
+    }
+    Ok(placement)
+}
+
+/// Cancel every placed order of a batch, reporting the cancelled ones as rejected. A failure
+/// to cancel some orders doesn't stop cancelling the others; the failures are logged and
+/// returned.
+async fn roll_back<C, CF>(
+    request: &LimitOrdersRequest,
+    placement: &mut BatchPlacement,
+    chunk_size: usize,
+    cancel: &mut C,
+) -> Vec<ProtocolError>
+where
+    C: FnMut(CancelOrdersRequest) -> CF,
+    CF: Future<Output = Result<CancelOrdersResponse>>,
+{
+    let (placed, cancels): (Vec<usize>, Vec<CancelOrderRequest>) = placement
+        .outcomes
+        .iter()
+        .enumerate()
+        .filter_map(|(index, outcome)| {
+            let order = outcome.as_ref().ok()?;
+            let cancel = CancelOrderRequest {
+                order_id: order.order_id.clone(),
+                market: request.requests[index].market.clone(),
+            };
+            Some((index, cancel))
+        })
+        .unzip();
+    if placed.is_empty() {
+        return Vec::new();
+    }
+    let cancels = match CancelOrdersRequest::new(cancels) {
+        Ok(cancels) => cancels,
+        Err(error) => return vec![error],
+    };
+    let mut failures = Vec::new();
+    let mut placed = placed.into_iter();
+    for chunk in cancels.chunks(chunk_size) {
+        let indices: Vec<usize> = placed.by_ref().take(chunk.requests.len()).collect();
+        let cancelled = match cancel(chunk).await {
+            Ok(cancelled) => cancelled.responses,
+            Err(error) => {
+                warn!(?indices, error = %error.report(), "could not cancel orders of batch");
+                failures.push(error);
+                continue;
+            }
+        };
+        for (index, cancelled) in indices.into_iter().zip(cancelled) {
+            match cancelled {
+                Ok(_) => {
+                    placement.outcomes[index] = Err(OrderRejected {
+                        index,
+                        message: ROLLED_BACK.to_string(),
+                    })
+                }
+                Err(error) => {
+                    warn!(%index, error = %error.report(), "could not cancel order of batch");
+                    failures.push(error);
+                }
+            }
+        }
+    }
+    failures
+}
+
+/// Place one chunk of a batch through `send`. If `blockchains` has the blockchains of each
+/// order's market, orders involving a blockchain that signing failed for are rejected and the
+/// chunk is sent again without them; otherwise the signing error fails the chunk.
+async fn place_chunk_with<S, F>(
+    chunk: &LimitOrdersRequest,
+    blockchains: &[Vec<Blockchain>],
+    mut send: S,
+) -> Result<Vec<Outcome>>
+where
+    S: FnMut(LimitOrdersRequest) -> F,
+    F: Future<Output = Result<PlaceOrdersResponse>>,
+{
+    let mut outcomes: Vec<Option<Outcome>> = vec![None; chunk.requests.len()];
+    let mut pending: Vec<usize> = (0..chunk.requests.len()).collect();
+    while !pending.is_empty() {
+        let request = LimitOrdersRequest {
+            requests: pending
+                .iter()
+                .map(|&index| chunk.requests[index].clone())
+                .collect(),
+            ..chunk.clone()
+        };
+        let error = match send(request).await {
+            Ok(response) => {
+                let sent = response.outcomes();
+                if sent.len() != pending.len() {
+                    return Err(ProtocolError::coerce_static_from_str(&format!(
+                        "Batch response has {} outcomes for {} orders",
+                        sent.len(),
+                        pending.len()
+                    )));
+                }
+                for (&index, outcome) in pending.iter().zip(sent) {
+                    outcomes[index] =
+                        Some(outcome.map_err(|rejected| OrderRejected { index, ..rejected }));
+                }
+                break;
+            }
+            Err(error) => error,
+        };
+        let failed = match error.find_source::<ChainSigningError>() {
+            Some(failed) if !blockchains.is_empty() => failed.blockchain,
+            _ => return Err(error),
+        };
+        let unsignable: Vec<usize> = pending
+            .iter()
+            .copied()
+            .filter(|&index| blockchains[index].contains(&failed))
+            .collect();
+        if unsignable.is_empty() {
+            return Err(error);
+        }
+        warn!(?failed, orders = ?unsignable, error = %error.report(), "could not sign for blockchain, skipping its orders");
+        for &index in &unsignable {
+            outcomes[index] = Some(Err(OrderRejected {
+                index,
+                message: format!("not sent: {}", error.report()),
+            }));
+        }
+        pending.retain(|index| !unsignable.contains(index));
+    }
+    outcomes
+        .into_iter()
+        .collect::<Option<Vec<_>>>()
+        .ok_or(ProtocolError("Batch response is missing orders"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{place_batch_with, place_chunk_with, OrderBatch, NOT_SENT, ROLLED_BACK};
+    use futures::future::ready;
+    use nash_protocol::errors::ProtocolError;
+    use nash_protocol::protocol::cancel_order::CancelOrderResponse;
+    use nash_protocol::protocol::multi_request::{MultiRequest, MultiResponse};
+    use nash_protocol::protocol::place_order::{ChainSigningError, LimitOrderRequest};
+    use nash_protocol::protocol::place_orders::{
+        LimitOrdersRequest, OrderPlaced, OrderRejected, PlaceOrdersResponse,
+    };
+    use nash_protocol::types::{Blockchain, BuyOrSell, OrderCancellationPolicy};
+    use serde_json::json;
+
+    fn orders(markets: &[&str]) -> LimitOrdersRequest {
+        let orders = markets
+            .iter()
+            .map(|market| LimitOrderRequest {
+                market: market.to_string(),
+                client_order_id: None,
+                buy_or_sell: BuyOrSell::Buy,
+                amount: "1".to_string(),
+                price: "1".to_string(),
+                cancellation_policy: OrderCancellationPolicy::GoodTilCancelled,
+                allow_taker: true,
+            })
+            .collect();
+        MultiRequest::new(orders).unwrap()
+    }
+
+    fn signing_failed(blockchain: Blockchain) -> ProtocolError {
+        ChainSigningError {
+            blockchain,
+            payload_index: 0,
+            error: ProtocolError("No child key"),
+        }
+        .into()
+    }
+
+    fn rejected(messages: &[&str]) -> PlaceOrdersResponse {
+        MultiResponse::from(
+            messages
+                .iter()
+                .map(|message| Err(ProtocolError::coerce_static_from_str(message)))
+                .collect::<Vec<_>>(),
+        )
+    }
+
+    fn placed(order_id: &str) -> OrderPlaced {
+        serde_json::from_value(json!({
+            "ordersTillSignState": 10,
+            "id": order_id,
+            "status": "OPEN",
+            "placedAt": "2021-01-01T00:00:00Z",
+            "type": "LIMIT",
+            "buyOrSell": "BUY",
+            "market": { "name": "eth_usdc" }
+        }))
+        .unwrap()
+    }
+
+    fn placed_and_rejected() -> Vec<Result<OrderPlaced, OrderRejected>> {
+        vec![
+            Ok(placed("a")),
+            Err(OrderRejected {
+                index: 1,
+                message: "Insufficient funds".to_string(),
+            }),
+        ]
+    }
+
+    #[tokio::test]
+    async fn all_or_nothing_batches_cancel_what_was_placed() {
+        // sent in chunks of two: the first has a rejected order, so the second isn't sent
+        let batch = OrderBatch::new(orders(&["eth_usdc"; 4])).all_or_nothing(true);
+        let mut sent = Vec::new();
+        let mut cancelled = Vec::new();
+        let placement = place_batch_with(
+            &batch,
+            2,
+            |chunk| {
+                sent.push(chunk.requests.len());
+                ready(Ok(placed_and_rejected()))
+            },
+            |cancels| {
+                cancelled.extend(
+                    cancels
+                        .requests
+                        .iter()
+                        .map(|cancel| cancel.order_id.clone()),
+                );
+                ready(Ok(MultiResponse::from(vec![Ok(CancelOrderResponse {
+                    order_id: "a".to_string(),
+                })])))
+            },
+        )
+        .await
+        .unwrap();
+        assert_eq!(sent, vec![2]);
+        assert_eq!(cancelled, vec!["a"]);
+        assert_eq!(placement.placed().count(), 0);
+        let messages: Vec<&str> = placement
+            .rejected()
+            .map(|rejected| rejected.message.as_str())
+            .collect();
+        assert_eq!(
+            messages,
+            vec![ROLLED_BACK, "Insufficient funds", NOT_SENT, NOT_SENT]
+        );
+
+        // an order that could not be cancelled may be on the book
+        let placement = place_batch_with(
+            &batch,
+            2,
+            |_| ready(Ok(placed_and_rejected())),
+            |_| ready(Err(ProtocolError("Connection lost"))),
+        )
+        .await
+        .unwrap();
+        assert_eq!(placement.placed().count(), 1);
+
+        // without the option nothing is cancelled
+        let mut cancelled = 0;
+        let placement = place_batch_with(
+            &OrderBatch::new(orders(&["eth_usdc"; 2])),
+            2,
+            |_| ready(Ok(placed_and_rejected())),
+            |_| {
+                cancelled += 1;
+                ready(Err(ProtocolError("Nothing to cancel")))
+            },
+        )
+        .await
+        .unwrap();
+        assert_eq!((placement.placed().count(), cancelled), (1, 0));
+    }
+
+    #[tokio::test]
+    async fn signing_failures_skip_only_the_orders_of_that_chain() {
+        let chunk = orders(&["eth_usdc", "neo_eth", "btc_usdc"]);
+        let blockchains = vec![
+            vec![Blockchain::Ethereum],
+            vec![Blockchain::NEO, Blockchain::Ethereum],
+            vec![Blockchain::Bitcoin, Blockchain::Ethereum],
+        ];
+        let mut responses = vec![
+            Err(signing_failed(Blockchain::NEO)),
+            Ok(rejected(&["first", "third"])),
+        ]
+        .into_iter();
+        let mut sent = Vec::new();
+        let outcomes = place_chunk_with(&chunk, &blockchains, |request| {
+            sent.push(request.requests.len());
+            ready(responses.next().unwrap())
+        })
+        .await
+        .unwrap();
+        assert_eq!(sent, vec![3, 2]);
+        let rejected: Vec<_> = outcomes
+            .iter()
+            .map(|outcome| outcome.as_ref().unwrap_err())
+            .collect();
+        assert_eq!(
+            (rejected[0].index, rejected[0].message.as_str()),
+            (0, "first")
+        );
+        assert_eq!(rejected[1].index, 1);
+        assert!(rejected[1].message.starts_with("not sent"));
+        assert_eq!(
+            (rejected[2].index, rejected[2].message.as_str()),
+            (2, "third")
+        );
+    }
+
+    #[tokio::test]
+    async fn chunk_fails_on_signing_errors_unless_skipping_and_on_short_responses() {
+        let chunk = orders(&["eth_usdc", "neo_eth"]);
+        let error = place_chunk_with(&chunk, &[], |_| ready(Err(signing_failed(Blockchain::NEO))))
+            .await
+            .unwrap_err();
+        assert!(error.find_source::<ChainSigningError>().is_some());
+
+        let error = place_chunk_with(&chunk, &[], |_| ready(Ok(rejected(&["only one"]))))
+            .await
+            .unwrap_err();
+        assert_eq!(
+            error.message(),
+            "Batch response has 1 outcomes for 2 orders"
+        );
+    }
+}
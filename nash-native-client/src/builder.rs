@@ -0,0 +1,151 @@
+//! Fluent alternative to filling in a `ClientConfig` by hand
+
+use std::time::Duration;
+
+use nash_protocol::errors::Result;
+use tokio::sync::broadcast;
+
+use crate::config::{ClientConfig, EnvironmentConfig};
+use crate::ws_client::ConnectionEvents;
+use crate::{Client, ConnectionEvent};
+
+/// Builds a `Client`, starting from `ClientConfig::default()`
+///
+/// ```no_run
+/// # async fn connect() -> nash_protocol::errors::Result<()> {
+/// use std::time::Duration;
+/// use nash_native_client::ClientBuilder;
+///
+/// let client = ClientBuilder::new()
+///     .keys_path("keys.json")
+///     .tcp_nodelay(true)
+///     .ping_interval(Duration::from_secs(2))
+///     .build()
+///     .await?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct ClientBuilder {
+    config: ClientConfig,
+    events: ConnectionEvents,
+}
+
+impl ClientBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Start from an existing config, e.g. one loaded from a file
+    pub fn from_config(config: ClientConfig) -> Self {
+        Self {
+            config,
+            events: ConnectionEvents::default(),
+        }
+    }
+
+    pub fn environment(mut self, environment: EnvironmentConfig) -> Self {
+        self.config.environment = environment;
+        self
+    }
+
+    pub fn keys_path(mut self, keys_path: &str) -> Self {
+        self.config.keys_path = Some(keys_path.to_string());
+        self
+    }
+
+    pub fn affiliate_code(mut self, affiliate_code: &str) -> Self {
+        self.config.affiliate_code = Some(affiliate_code.to_string());
+        self
+    }
+
+    pub fn client_id(mut self, client_id: u64) -> Self {
+        self.config.client_id = client_id;
+        self
+    }
+
+    /// Timeout for requests
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.config.timeout_ms = timeout.as_millis() as u64;
+        self
+    }
+
+    /// Timeout for establishing the websocket connection
+    pub fn connect_timeout(mut self, timeout: Duration) -> Self {
+        self.config.websocket.connect_timeout_ms = timeout.as_millis() as u64;
+        self
+    }
+
+    /// Largest websocket frame accepted from the server
+    pub fn max_frame_size(mut self, bytes: usize) -> Self {
+        self.config.websocket.max_frame_size = Some(bytes);
+        self
+    }
+
+    /// Websocket messages buffered for sending before sends fail
+    pub fn send_queue_depth(mut self, messages: usize) -> Self {
+        self.config.websocket.send_queue_depth = Some(messages);
+        self
+    }
+
+    /// How often heartbeats are sent over the websocket
+    pub fn ping_interval(mut self, interval: Duration) -> Self {
+        self.config.websocket.ping_interval_ms = Some(interval.as_millis() as u64);
+        self
+    }
+
+    /// Disable Nagle's algorithm on the websocket's TCP connection
+    pub fn tcp_nodelay(mut self, nodelay: bool) -> Self {
+        self.config.websocket.tcp_nodelay = nodelay;
+        self
+    }
+
+    /// How often the session token is refreshed, `None` to let it expire when idle
+    pub fn session_refresh(mut self, interval: Option<Duration>) -> Self {
+        self.config.websocket.session_refresh_ms = interval.map(|i| i.as_millis() as u64);
+        self
+    }
+
+    /// Cancel all open orders unless `Client::refresh_dead_man_switch` is called at least
+    /// every `timeout`, or once the connection drops and isn't back within `timeout`
+    pub fn dead_man_switch(mut self, timeout: Duration) -> Self {
+        self.config.risk.dead_man_switch_ms = Some(timeout.as_millis() as u64);
+        self
+    }
+
+    /// Replaces the default `nash-native-client/<version>` user agent
+    pub fn user_agent(mut self, user_agent: &str) -> Self {
+        self.config.headers.user_agent = Some(user_agent.to_string());
+        self
+    }
+
+    /// Identify the application to the exchange, sent as `X-App-Id`
+    pub fn app_id(mut self, app_id: &str) -> Self {
+        self.config.headers.app_id = Some(app_id.to_string());
+        self
+    }
+
+    /// Send a header with every HTTP request and on websocket handshakes
+    pub fn header(mut self, name: &str, value: &str) -> Self {
+        self.config
+            .headers
+            .extra
+            .insert(name.to_string(), value.to_string());
+        self
+    }
+
+    pub fn config(&self) -> &ClientConfig {
+        &self.config
+    }
+
+    /// Listen to connection lifecycle events, including those emitted while `build` connects.
+    /// The receiver keeps working for the lifetime of the built client.
+    pub fn connection_events(&self) -> broadcast::Receiver<ConnectionEvent> {
+        self.events.subscribe()
+    }
+
+    /// Validate the config and connect
+    pub async fn build(self) -> Result<Client> {
+        Client::from_config_with_events(&self.config, self.events).await
+    }
+}
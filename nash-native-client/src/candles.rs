@@ -0,0 +1,372 @@
+//! OHLCV candles of any interval built from the trades subscription, for strategies that need
+//! finer or live candles than the fixed intervals of `ListCandlesRequest`. Candles are aligned
+//! to the unix epoch, so a 5 second candle starts at a multiple of 5 seconds.
+
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use bigdecimal::BigDecimal;
+use futures::stream::Stream;
+use tokio::sync::{mpsc, watch};
+use tokio_util::sync::CancellationToken;
+
+use nash_protocol::errors::{ProtocolError, Result};
+use nash_protocol::protocol::subscriptions::trades::{SubscribeTrades, TradesResponse};
+use nash_protocol::types::timestamp::unix_millis;
+use nash_protocol::types::{MarketSymbol, Trade};
+
+use crate::{Client, MarketEvent, MarketSubscriptionHandle};
+
+/// How long after its interval ends a candle is kept open for trades that arrive late
+const CLOSE_GRACE: Duration = Duration::from_millis(500);
+
+/// Candle of trades executed in `[start_ms, start_ms + interval)`
+#[derive(Clone, Debug, PartialEq)]
+pub struct Ohlcv {
+    pub market: String,
+    /// Milliseconds since the unix epoch
+    pub start_ms: i64,
+    pub interval: Duration,
+    pub open: BigDecimal,
+    pub high: BigDecimal,
+    pub low: BigDecimal,
+    pub close: BigDecimal,
+    /// Traded amount of A
+    pub a_volume: BigDecimal,
+    /// Traded amount of B
+    pub b_volume: BigDecimal,
+    pub trades: u32,
+    /// Execution times of the first and last trade, to order trades that arrive out of order
+    open_ms: i64,
+    close_ms: i64,
+}
+
+impl Ohlcv {
+    fn new(
+        market: &str,
+        start_ms: i64,
+        interval: Duration,
+        price: &BigDecimal,
+        at_ms: i64,
+    ) -> Self {
+        Self {
+            market: market.to_string(),
+            start_ms,
+            interval,
+            open: price.clone(),
+            high: price.clone(),
+            low: price.clone(),
+            close: price.clone(),
+            a_volume: BigDecimal::from(0),
+            b_volume: BigDecimal::from(0),
+            trades: 0,
+            open_ms: at_ms,
+            close_ms: at_ms,
+        }
+    }
+
+    pub fn end_ms(&self) -> i64 {
+        self.start_ms + self.interval.as_millis() as i64
+    }
+
+    fn add(&mut self, price: &BigDecimal, amount: &BigDecimal, at_ms: i64) {
+        if price > &self.high {
+            self.high = price.clone();
+        }
+        if price < &self.low {
+            self.low = price.clone();
+        }
+        if at_ms < self.open_ms {
+            self.open = price.clone();
+            self.open_ms = at_ms;
+        }
+        if at_ms >= self.close_ms {
+            self.close = price.clone();
+            self.close_ms = at_ms;
+        }
+        self.a_volume += amount;
+        self.b_volume += amount * price;
+        self.trades += 1;
+    }
+}
+
+/// Builds candles of one market from its trades
+#[derive(Clone, Debug)]
+pub struct CandleAggregator {
+    market: String,
+    interval: Duration,
+    /// Emit a candle without trades, at the previous close, for intervals nothing traded in
+    fill_gaps: bool,
+    current: Option<Ohlcv>,
+    last_close: Option<(i64, BigDecimal)>,
+    late_trades: u64,
+}
+
+impl CandleAggregator {
+    pub fn new(market: &str, interval: Duration, fill_gaps: bool) -> Result<Self> {
+        if interval.as_millis() == 0 {
+            return Err(ProtocolError(
+                "Candle interval must be at least a millisecond",
+            ));
+        }
+        Ok(Self {
+            market: market.to_string(),
+            interval,
+            fill_gaps,
+            current: None,
+            last_close: None,
+            late_trades: 0,
+        })
+    }
+
+    fn start_of(&self, at_ms: i64) -> i64 {
+        let interval = self.interval.as_millis() as i64;
+        at_ms - at_ms.rem_euclid(interval)
+    }
+
+    /// Add a trade, returning the candles it closed, oldest first. Trades of a candle that
+    /// was already closed are dropped and counted in `late_trades`.
+    pub fn push(&mut self, trade: &Trade) -> Vec<Ohlcv> {
+        let at_ms = unix_millis(&trade.executed_at);
+        let start_ms = self.start_of(at_ms);
+        let late = match (&self.current, &self.last_close) {
+            (Some(current), _) => start_ms < current.start_ms,
+            (None, Some((end_ms, _))) => start_ms < *end_ms,
+            (None, None) => false,
+        };
+        if late {
+            self.late_trades += 1;
+            return Vec::new();
+        }
+        let closed = self.close_until(start_ms);
+        let (market, interval) = (&self.market, self.interval);
+        self.current
+            .get_or_insert_with(|| {
+                Ohlcv::new(market, start_ms, interval, &trade.limit_price, at_ms)
+            })
+            .add(&trade.limit_price, &trade.amount, at_ms);
+        closed
+    }
+
+    /// Close every candle that ends by `now_ms`, e.g. when no trade came in for a while
+    pub fn close_until(&mut self, now_ms: i64) -> Vec<Ohlcv> {
+        let mut closed = Vec::new();
+        if let Some(current) = self.current.take() {
+            if current.end_ms() > now_ms {
+                self.current = Some(current);
+                return closed;
+            }
+            self.last_close = Some((current.end_ms(), current.close.clone()));
+            closed.push(current);
+        }
+        if self.fill_gaps {
+            while let Some((end_ms, close)) = self.last_close.clone() {
+                if end_ms + (self.interval.as_millis() as i64) > now_ms {
+                    break;
+                }
+                let empty = Ohlcv::new(&self.market, end_ms, self.interval, &close, end_ms);
+                self.last_close = Some((empty.end_ms(), close));
+                closed.push(empty);
+            }
+        }
+        closed
+    }
+
+    /// Candle being built, if a trade came in since the last one closed
+    pub fn current(&self) -> Option<&Ohlcv> {
+        self.current.as_ref()
+    }
+
+    /// Trades dropped because their candle had already closed
+    pub fn late_trades(&self) -> u64 {
+        self.late_trades
+    }
+}
+
+/// Closed candles of a market, from `Client::subscribe_candles`. Dropping the handle stops
+/// building them and unsubscribes.
+pub struct CandleHandle {
+    receiver: mpsc::UnboundedReceiver<Result<Ohlcv>>,
+    current: watch::Receiver<Option<Ohlcv>>,
+    stop: CancellationToken,
+}
+
+impl CandleHandle {
+    /// Next closed candle, or `None` once the trades subscription ended
+    pub async fn recv(&mut self) -> Option<Result<Ohlcv>> {
+        self.receiver.recv().await
+    }
+
+    /// Candle being built, updated with every trade
+    pub fn current(&self) -> Option<Ohlcv> {
+        self.current.borrow().clone()
+    }
+}
+
+impl Drop for CandleHandle {
+    fn drop(&mut self) {
+        self.stop.cancel();
+    }
+}
+
+impl Stream for CandleHandle {
+    type Item = Result<Ohlcv>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.receiver.poll_recv(cx)
+    }
+}
+
+fn now_ms() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |since| since.as_millis() as i64)
+}
+
+impl Client {
+    /// Build `interval` candles of `market` from its trades. Candles are sent once their
+    /// interval is over, also when nothing traded in the next one; with `fill_gaps` intervals
+    /// without trades are sent as candles at the previous close.
+    pub async fn subscribe_candles(
+        &self,
+        market: &str,
+        interval: Duration,
+        fill_gaps: bool,
+    ) -> Result<CandleHandle> {
+        let market: MarketSymbol = market.parse()?;
+        let aggregator = CandleAggregator::new(market.as_str(), interval, fill_gaps)?;
+        let trades = self
+            .subscribe_markets(&[market.as_str()], |market| SubscribeTrades { market })
+            .await?;
+        let (sender, receiver) = mpsc::unbounded_channel();
+        let (current_sender, current) = watch::channel(None);
+        let stop = CancellationToken::new();
+        tokio::spawn(build_candles(
+            aggregator,
+            trades,
+            sender,
+            current_sender,
+            stop.clone(),
+        ));
+        Ok(CandleHandle {
+            receiver,
+            current,
+            stop,
+        })
+    }
+}
+
+async fn build_candles(
+    mut aggregator: CandleAggregator,
+    mut trades: MarketSubscriptionHandle<TradesResponse>,
+    sender: mpsc::UnboundedSender<Result<Ohlcv>>,
+    current: watch::Sender<Option<Ohlcv>>,
+    stop: CancellationToken,
+) {
+    loop {
+        // wake up to close the current candle even if no trade comes in
+        let close_in = aggregator.current().map_or(aggregator.interval, |candle| {
+            Duration::from_millis((candle.end_ms() - now_ms()).max(0) as u64) + CLOSE_GRACE
+        });
+        let event = tokio::select! {
+            event = trades.recv() => event,
+            _ = tokio::time::sleep(close_in) => {
+                for candle in aggregator.close_until(now_ms() - CLOSE_GRACE.as_millis() as i64) {
+                    if sender.send(Ok(candle)).is_err() {
+                        return;
+                    }
+                }
+                let _ = current.send(aggregator.current().cloned());
+                continue;
+            }
+            _ = stop.cancelled() => return,
+        };
+        let response = match event {
+            Some(MarketEvent { event, .. }) => event.and_then(|e| e.response_or_error()),
+            None => return,
+        };
+        let closed: Vec<Ohlcv> = match response {
+            Ok(response) => response
+                .trades
+                .iter()
+                .flat_map(|trade| aggregator.push(trade))
+                .collect(),
+            Err(e) => {
+                if sender.send(Err(e)).is_err() {
+                    return;
+                }
+                continue;
+            }
+        };
+        for candle in closed {
+            if sender.send(Ok(candle)).is_err() {
+                return;
+            }
+        }
+        let _ = current.send(aggregator.current().cloned());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::CandleAggregator;
+    use bigdecimal::BigDecimal;
+    use nash_protocol::types::timestamp::parse_timestamp;
+    use nash_protocol::types::{AccountTradeSide, BuyOrSell, Trade};
+    use std::str::FromStr;
+    use std::time::Duration;
+
+    fn dec(value: &str) -> BigDecimal {
+        BigDecimal::from_str(value).unwrap()
+    }
+
+    fn trade(at: &str, price: &str, amount: &str) -> Trade {
+        Trade {
+            id: at.to_string(),
+            taker_order_id: "taker".to_string(),
+            maker_order_id: "maker".to_string(),
+            amount: dec(amount),
+            executed_at: parse_timestamp(&format!("1970-01-01T00:00:{}Z", at)).unwrap(),
+            account_side: AccountTradeSide::None,
+            maker_fee: dec("0"),
+            taker_fee: dec("0"),
+            maker_recieved: dec("0"),
+            taker_recieved: dec("0"),
+            market: "eth_usdc".to_string(),
+            direction: BuyOrSell::Buy,
+            limit_price: dec(price),
+        }
+    }
+
+    #[test]
+    fn trades_are_aggregated_into_candles() {
+        let mut candles = CandleAggregator::new("eth_usdc", Duration::from_secs(1), true).unwrap();
+        assert!(candles.push(&trade("00.200", "10", "1")).is_empty());
+        assert!(candles.push(&trade("00.700", "12", "2")).is_empty());
+        // arrives last but executed before the 12, so it doesn't become the close
+        assert!(candles.push(&trade("00.500", "9", "1")).is_empty());
+
+        let closed = candles.push(&trade("03.100", "11", "1"));
+        assert_eq!(closed.len(), 3);
+        let first = &closed[0];
+        assert_eq!(
+            (&first.open, &first.high, &first.low, &first.close),
+            (&dec("10"), &dec("12"), &dec("9"), &dec("12"))
+        );
+        assert_eq!(
+            (&first.a_volume, &first.b_volume, first.trades),
+            (&dec("4"), &dec("43"), 3)
+        );
+        // nothing traded in the next two seconds
+        assert_eq!(
+            (closed[2].start_ms, &closed[2].open, closed[2].trades),
+            (2000, &dec("12"), 0)
+        );
+
+        assert!(candles.push(&trade("00.900", "10", "1")).is_empty());
+        assert_eq!(candles.late_trades(), 1);
+        assert!(candles.close_until(3999).is_empty());
+        assert_eq!(candles.close_until(4000)[0].close, dec("11"));
+    }
+}
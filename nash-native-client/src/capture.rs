@@ -0,0 +1,261 @@
+//! Capture of the traffic between the client and the exchange over a window of time, to
+//! attach to a support ticket. The bundle holds every request with its response or error and
+//! how long it took, the connection events and the client config. Signatures, keys, tokens
+//! and similar fields are replaced before anything is recorded, as are custom headers.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex as SyncMutex, RwLock as SyncRwLock};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use serde::Serialize;
+use tokio::sync::broadcast;
+use tokio_util::sync::CancellationToken;
+use tracing::info;
+
+use nash_protocol::errors::{ProtocolError, Result};
+
+use crate::config::ClientConfig;
+use crate::{Client, ConnectionEvent};
+
+/// Requests kept per capture; later ones are only counted
+const MAX_CAPTURED_EXCHANGES: usize = 10_000;
+/// Fields whose name contains any of these (ignoring case) are redacted
+const SECRET_FIELDS: &[&str] = &["secret", "signature", "signed", "token", "password", "key"];
+const REDACTED: &str = "<redacted>";
+
+/// A request and what came back for it
+#[derive(Clone, Debug, Serialize)]
+pub struct CapturedExchange {
+    pub at_ms: u64,
+    /// `ws` or `http`
+    pub transport: &'static str,
+    pub request: serde_json::Value,
+    pub response: Option<serde_json::Value>,
+    pub error: Option<String>,
+    pub elapsed_us: u64,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct CapturedEvent {
+    pub at_ms: u64,
+    pub event: String,
+}
+
+/// Everything captured between `Client::start_capture` and `Client::stop_capture`
+#[derive(Clone, Debug, Serialize)]
+pub struct CaptureBundle {
+    pub client_version: &'static str,
+    pub started_at_ms: u64,
+    pub ended_at_ms: u64,
+    /// Config the client was built from, if it was built from one
+    pub config: Option<String>,
+    pub exchanges: Vec<CapturedExchange>,
+    /// Requests not kept because the capture was full
+    pub dropped_exchanges: usize,
+    pub connection_events: Vec<CapturedEvent>,
+    /// Connection events not kept because they came faster than they were recorded
+    pub missed_connection_events: u64,
+}
+
+impl CaptureBundle {
+    pub fn to_json(&self) -> Result<String> {
+        serde_json::to_string_pretty(self)
+            .map_err(|e| ProtocolError::with_source("Could not serialize capture", e))
+    }
+}
+
+/// Capture state of a client
+#[derive(Debug, Default)]
+pub(crate) struct WireCapture {
+    active: AtomicBool,
+    bundle: Arc<SyncMutex<Option<CaptureBundle>>>,
+    stop_events: SyncMutex<Option<CancellationToken>>,
+    config: SyncRwLock<Option<String>>,
+}
+
+impl WireCapture {
+    pub fn is_active(&self) -> bool {
+        self.active.load(Ordering::Acquire)
+    }
+
+    /// Remember the config for later captures, without custom header values
+    pub fn set_config(&self, config: &ClientConfig) {
+        let mut config = config.clone();
+        for value in config.headers.extra.values_mut() {
+            *value = REDACTED.to_string();
+        }
+        *self.config.write().unwrap() = Some(format!("{:#?}", config));
+    }
+
+    /// Record a request sent while capturing
+    pub fn record(
+        &self,
+        transport: &'static str,
+        request: &serde_json::Value,
+        result: &Result<serde_json::Value>,
+        elapsed: Duration,
+    ) {
+        let mut bundle = self.bundle.lock().unwrap();
+        let bundle = match bundle.as_mut() {
+            Some(bundle) => bundle,
+            None => return,
+        };
+        if bundle.exchanges.len() >= MAX_CAPTURED_EXCHANGES {
+            bundle.dropped_exchanges += 1;
+            return;
+        }
+        let (response, error) = match result {
+            Ok(response) => (Some(sanitized(response)), None),
+            Err(e) => (None, Some(e.report())),
+        };
+        bundle.exchanges.push(CapturedExchange {
+            at_ms: now_ms(),
+            transport,
+            request: sanitized(request),
+            response,
+            error,
+            elapsed_us: elapsed.as_micros() as u64,
+        });
+    }
+}
+
+/// `value` with the values of secret fields replaced
+fn sanitized(value: &serde_json::Value) -> serde_json::Value {
+    match value {
+        serde_json::Value::Object(fields) => fields
+            .iter()
+            .map(|(name, value)| {
+                let name_lower = name.to_lowercase();
+                let value = if SECRET_FIELDS.iter().any(|field| name_lower.contains(field)) {
+                    serde_json::Value::String(REDACTED.to_string())
+                } else {
+                    sanitized(value)
+                };
+                (name.clone(), value)
+            })
+            .collect(),
+        serde_json::Value::Array(values) => values.iter().map(sanitized).collect(),
+        value => value.clone(),
+    }
+}
+
+fn now_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |since| since.as_millis() as u64)
+}
+
+impl Client {
+    /// Start recording requests, responses and connection events for a support ticket
+    pub fn start_capture(&self) -> Result<()> {
+        let capture = &self.inner.capture;
+        let mut bundle = capture.bundle.lock().unwrap();
+        if bundle.is_some() {
+            return Err(ProtocolError("A capture is already running"));
+        }
+        *bundle = Some(CaptureBundle {
+            client_version: env!("CARGO_PKG_VERSION"),
+            started_at_ms: now_ms(),
+            ended_at_ms: 0,
+            config: capture.config.read().unwrap().clone(),
+            exchanges: Vec::new(),
+            dropped_exchanges: 0,
+            connection_events: Vec::new(),
+            missed_connection_events: 0,
+        });
+        let stop = CancellationToken::new();
+        tokio::spawn(record_events(
+            self.inner.connection_events.subscribe(),
+            capture.bundle.clone(),
+            stop.clone(),
+        ));
+        *capture.stop_events.lock().unwrap() = Some(stop);
+        capture.active.store(true, Ordering::Release);
+        info!("wire capture started");
+        Ok(())
+    }
+
+    /// Stop recording and return what was captured
+    pub fn stop_capture(&self) -> Result<CaptureBundle> {
+        let capture = &self.inner.capture;
+        capture.active.store(false, Ordering::Release);
+        if let Some(stop) = capture.stop_events.lock().unwrap().take() {
+            stop.cancel();
+        }
+        let mut bundle = capture
+            .bundle
+            .lock()
+            .unwrap()
+            .take()
+            .ok_or(ProtocolError("No capture is running"))?;
+        bundle.ended_at_ms = now_ms();
+        info!(exchanges = bundle.exchanges.len(), "wire capture stopped");
+        Ok(bundle)
+    }
+
+    /// Capture everything the client does over the next `window`
+    pub async fn capture_for(&self, window: Duration) -> Result<CaptureBundle> {
+        self.start_capture()?;
+        tokio::time::sleep(window).await;
+        self.stop_capture()
+    }
+}
+
+async fn record_events(
+    mut events: broadcast::Receiver<ConnectionEvent>,
+    bundle: Arc<SyncMutex<Option<CaptureBundle>>>,
+    stop: CancellationToken,
+) {
+    loop {
+        let event = tokio::select! {
+            event = events.recv() => event,
+            _ = stop.cancelled() => return,
+        };
+        let mut bundle = bundle.lock().unwrap();
+        let bundle = match bundle.as_mut() {
+            Some(bundle) => bundle,
+            None => return,
+        };
+        match event {
+            Ok(event) => bundle.connection_events.push(CapturedEvent {
+                at_ms: now_ms(),
+                event: format!("{:?}", event),
+            }),
+            Err(broadcast::error::RecvError::Lagged(missed)) => {
+                bundle.missed_connection_events += missed
+            }
+            Err(broadcast::error::RecvError::Closed) => return,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::sanitized;
+    use serde_json::json;
+
+    #[test]
+    fn secrets_are_redacted() {
+        let request = json!({
+            "query": "mutation placeLimitOrder",
+            "variables": {
+                "payload": { "amount": "1.5", "marketName": "eth_usdc" },
+                "signature": { "publicKey": "02ab", "signedDigest": "ff" },
+                "blockchainSignatures": [{ "r": "1" }],
+                "fills": [{ "nonce": 3, "authToken": "abc" }]
+            }
+        });
+        assert_eq!(
+            sanitized(&request),
+            json!({
+                "query": "mutation placeLimitOrder",
+                "variables": {
+                    "payload": { "amount": "1.5", "marketName": "eth_usdc" },
+                    "signature": "<redacted>",
+                    "blockchainSignatures": "<redacted>",
+                    "fills": [{ "nonce": 3, "authToken": "<redacted>" }]
+                }
+            })
+        );
+    }
+}
@@ -0,0 +1,267 @@
+//! Orders held locally until a market or account condition holds. Conditions are evaluated
+//! on subscription updates as they arrive and the orders go out as one batch. Arming only
+//! fills the r-value pools and refreshes asset nonces; the orders are built and signed once
+//! the condition holds, so signing adds to the time between the trigger and the placement.
+
+use std::cmp::Ordering;
+
+use bigdecimal::BigDecimal;
+use tokio::task::JoinHandle;
+use tokio_util::sync::CancellationToken;
+use tracing::{info, warn};
+
+use nash_protocol::errors::{ProtocolError, Result};
+use nash_protocol::protocol::get_ticker::TickerRequest;
+use nash_protocol::protocol::list_account_balances::ListAccountBalancesRequest;
+use nash_protocol::protocol::place_orders::LimitOrdersRequest;
+use nash_protocol::protocol::subscriptions::updated_account_balances::SubscribeAccountBalances;
+use nash_protocol::protocol::subscriptions::updated_ticker::SubscribeTicker;
+use nash_protocol::types::Asset;
+
+use crate::batch::BatchPlacement;
+use crate::Client;
+
+/// What has to happen on the exchange for armed orders to be placed
+#[derive(Clone, Debug, PartialEq)]
+pub enum Condition {
+    /// Last trade price of `market` reaches or passes `price`, from whichever side it was on
+    /// when the condition was armed
+    PriceCrosses { market: String, price: BigDecimal },
+    /// Best ask minus best bid of `market` is below `spread`
+    SpreadBelow { market: String, spread: BigDecimal },
+    /// Available balance of `asset` is above `amount`
+    PositionAbove { asset: Asset, amount: BigDecimal },
+}
+
+/// Latest known top of book of a market
+#[derive(Clone, Debug, Default)]
+struct Quote {
+    last_price: Option<BigDecimal>,
+    best_bid: Option<BigDecimal>,
+    best_ask: Option<BigDecimal>,
+}
+
+/// Evaluates a condition against successive observations
+#[derive(Debug)]
+struct Trigger {
+    condition: Condition,
+    /// Side of the crossing price the last price was on when first observed
+    armed_side: Option<Ordering>,
+}
+
+impl Trigger {
+    fn new(condition: Condition) -> Self {
+        Self {
+            condition,
+            armed_side: None,
+        }
+    }
+
+    fn on_quote(&mut self, quote: &Quote) -> bool {
+        match &self.condition {
+            Condition::PriceCrosses { price, .. } => {
+                let side = match &quote.last_price {
+                    Some(last) => last.cmp(price),
+                    None => return false,
+                };
+                match self.armed_side {
+                    None => {
+                        self.armed_side = Some(side);
+                        side == Ordering::Equal
+                    }
+                    Some(armed) => side != armed,
+                }
+            }
+            Condition::SpreadBelow { spread, .. } => match (&quote.best_bid, &quote.best_ask) {
+                (Some(bid), Some(ask)) => &(ask - bid) < spread,
+                _ => false,
+            },
+            Condition::PositionAbove { .. } => false,
+        }
+    }
+
+    fn on_balance(&mut self, balance: &BigDecimal) -> bool {
+        match &self.condition {
+            Condition::PositionAbove { amount, .. } => balance > amount,
+            _ => false,
+        }
+    }
+}
+
+/// Handle to armed orders. Dropping it leaves them armed.
+pub struct ConditionalHandle {
+    cancel: CancellationToken,
+    task: JoinHandle<Result<BatchPlacement>>,
+}
+
+impl ConditionalHandle {
+    /// Disarm the orders, unless they have already been sent
+    pub fn cancel(&self) {
+        self.cancel.cancel();
+    }
+
+    /// Wait for the condition to hold and the orders to be placed
+    pub async fn fired(self) -> Result<BatchPlacement> {
+        self.task
+            .await
+            .map_err(|_| ProtocolError("Conditional order task failed"))?
+    }
+}
+
+impl Client {
+    /// Place `orders` as one batch as soon as `condition` holds. Orders are signed and go
+    /// through the same pre-trade checks as `place_limit_orders` when they fire, not when
+    /// armed.
+    pub async fn arm_conditional(
+        &self,
+        condition: Condition,
+        orders: LimitOrdersRequest,
+    ) -> Result<ConditionalHandle> {
+        if orders.requests.is_empty() {
+            return Err(ProtocolError("No orders to arm"));
+        }
+        let mut markets: Vec<&str> = orders.requests.iter().map(|o| o.market.as_str()).collect();
+        markets.sort_unstable();
+        markets.dedup();
+        for market in markets {
+            self.warm_up(market).await?;
+        }
+        let cancel = CancellationToken::new();
+        let task_cancel = cancel.clone();
+        let client = self.clone();
+        let task = tokio::spawn(async move {
+            client.await_condition(condition, &task_cancel).await?;
+            let placement = client.place_limit_orders(orders).await?;
+            info!(
+                placed = placement.placed().count(),
+                rejected = placement.rejected().count(),
+                "conditional orders fired"
+            );
+            Ok(placement)
+        });
+        Ok(ConditionalHandle { cancel, task })
+    }
+
+    /// Return once `condition` holds, checking the current state first and then every update
+    async fn await_condition(
+        &self,
+        condition: Condition,
+        cancel: &CancellationToken,
+    ) -> Result<()> {
+        let mut trigger = Trigger::new(condition.clone());
+        match condition {
+            Condition::PriceCrosses { market, .. } | Condition::SpreadBelow { market, .. } => {
+                let mut updates = self
+                    .subscribe_protocol(SubscribeTicker {
+                        market: market.clone(),
+                    })
+                    .await?;
+                let ticker = self
+                    .run(TickerRequest { market })
+                    .await?
+                    .response_or_error()?;
+                let mut quote = Quote {
+                    last_price: ticker.last_price,
+                    best_bid: ticker.best_bid_price,
+                    best_ask: ticker.best_ask_price,
+                };
+                while !trigger.on_quote(&quote) {
+                    let update = tokio::select! {
+                        update = updates.recv() => update,
+                        _ = cancel.cancelled() => {
+                            return Err(ProtocolError("Conditional orders were cancelled"));
+                        }
+                    };
+                    let ticker = match update {
+                        Some(update) => update?.response_or_error()?,
+                        None => return Err(ProtocolError("Ticker subscription ended")),
+                    };
+                    // an update without a price keeps the last known one
+                    quote.last_price = ticker.last_price.or(quote.last_price);
+                    quote.best_bid = ticker.best_bid_price.or(quote.best_bid);
+                    quote.best_ask = ticker.best_ask_price.or(quote.best_ask);
+                }
+            }
+            Condition::PositionAbove { asset, .. } => {
+                let mut updates = self
+                    .subscribe_protocol(SubscribeAccountBalances {
+                        symbol: Some(asset.name().to_string()),
+                    })
+                    .await?;
+                let balances = self
+                    .run(ListAccountBalancesRequest { filter: None })
+                    .await?
+                    .response_or_error()?;
+                let mut balance = balances.state_channel.get(&asset).cloned();
+                while !balance.as_ref().map_or(false, |b| trigger.on_balance(b)) {
+                    let update = tokio::select! {
+                        update = updates.recv() => update,
+                        _ = cancel.cancelled() => {
+                            return Err(ProtocolError("Conditional orders were cancelled"));
+                        }
+                    };
+                    match update {
+                        Some(update) => {
+                            let update = update?.response_or_error()?;
+                            if let Some(new) = update.balances.get(&asset) {
+                                balance = Some(new.clone());
+                            }
+                        }
+                        None => {
+                            warn!(asset = asset.name(), "balance subscription ended");
+                            return Err(ProtocolError("Balance subscription ended"));
+                        }
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{BigDecimal, Condition, Quote, Trigger};
+    use std::str::FromStr;
+
+    fn last(price: &str) -> Quote {
+        Quote {
+            last_price: Some(BigDecimal::from_str(price).unwrap()),
+            ..Quote::default()
+        }
+    }
+
+    #[test]
+    fn price_crossing_fires_from_either_side() {
+        let condition = Condition::PriceCrosses {
+            market: "eth_usdc".to_string(),
+            price: BigDecimal::from(200),
+        };
+        let mut from_below = Trigger::new(condition.clone());
+        assert!(!from_below.on_quote(&last("190")));
+        assert!(!from_below.on_quote(&last("199.9")));
+        assert!(from_below.on_quote(&last("200")));
+
+        let mut from_above = Trigger::new(condition);
+        assert!(!from_above.on_quote(&Quote::default()));
+        assert!(!from_above.on_quote(&last("210")));
+        assert!(from_above.on_quote(&last("195")));
+    }
+
+    #[test]
+    fn spread_needs_both_sides_of_the_book() {
+        let mut trigger = Trigger::new(Condition::SpreadBelow {
+            market: "eth_usdc".to_string(),
+            spread: BigDecimal::from_str("0.5").unwrap(),
+        });
+        let mut quote = Quote {
+            best_bid: Some(BigDecimal::from(200)),
+            ..Quote::default()
+        };
+        assert!(!trigger.on_quote(&quote));
+        quote.best_ask = Some(BigDecimal::from_str("200.5").unwrap());
+        assert!(!trigger.on_quote(&quote));
+        quote.best_ask = Some(BigDecimal::from_str("200.4").unwrap());
+        assert!(trigger.on_quote(&quote));
+    }
+}
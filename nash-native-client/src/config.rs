@@ -0,0 +1,704 @@
+//! Declarative client configuration. A `ClientConfig` can be deserialized from TOML or YAML
+//! (behind the `toml` and `yaml` features) or read from `NASH_*` environment variables, and is
+//! validated before a client is built from it.
+
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+use serde::Deserialize;
+
+use nash_protocol::errors::{ProtocolError, Result, ResultExt};
+use nash_protocol::protocol::refresh_token::SESSION_TOKEN_LIFETIME;
+use nash_protocol::protocol::State;
+
+use crate::http_extension::{header_map, HttpOptions, HTTP_SHARDS};
+use crate::types::Environment;
+use crate::ws_client::{TransportOptions, WsOptions};
+
+/// Exchange endpoint to connect to
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum EnvironmentConfig {
+    Production,
+    Sandbox,
+    /// Custom host, e.g. a local deployment
+    Dev(String),
+}
+
+impl Default for EnvironmentConfig {
+    fn default() -> Self {
+        Self::Production
+    }
+}
+
+impl EnvironmentConfig {
+    pub fn to_environment(&self) -> Environment {
+        match self {
+            Self::Production => Environment::Production,
+            Self::Sandbox => Environment::Sandbox,
+            // Environment only holds static hosts. Configs are loaded once per process.
+            Self::Dev(host) => Environment::Dev(Box::leak(host.clone().into_boxed_str())),
+        }
+    }
+}
+
+/// How often to retry establishing the initial connection before giving up
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct RetryPolicy {
+    pub max_attempts: u32,
+    pub backoff_ms: u64,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 1,
+            backoff_ms: 500,
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Delay before attempt number `attempt` (starting at 1 for the first retry), doubling each time
+    pub fn backoff(&self, attempt: u32) -> Duration {
+        Duration::from_millis(self.backoff_ms.saturating_mul(1 << attempt.saturating_sub(1).min(16)))
+    }
+}
+
+/// Limits on how much load the client puts on the exchange
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct RateLimits {
+    /// Order placements allowed in flight at the same time
+    pub max_concurrent_orders: usize,
+    /// Time all requests wait after the exchange throttled one without saying how long to
+    /// wait
+    pub throttle_backoff_ms: u64,
+}
+
+impl Default for RateLimits {
+    fn default() -> Self {
+        Self {
+            max_concurrent_orders: 1,
+            throttle_backoff_ms: 1_000,
+        }
+    }
+}
+
+impl RateLimits {
+    pub fn throttle_backoff(&self) -> Duration {
+        Duration::from_millis(self.throttle_backoff_ms)
+    }
+}
+
+/// Connection pool sizes
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct PoolConfig {
+    /// Number of HTTP connections requests are sharded over (by market)
+    pub http_shards: usize,
+}
+
+impl Default for PoolConfig {
+    fn default() -> Self {
+        Self {
+            http_shards: HTTP_SHARDS,
+        }
+    }
+}
+
+/// Transport compression. Only HTTP responses can be compressed: the websocket
+/// implementation does not support permessage-deflate.
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct CompressionConfig {
+    /// Accept gzip and deflate encoded HTTP responses
+    pub http: bool,
+}
+
+impl Default for CompressionConfig {
+    fn default() -> Self {
+        Self { http: true }
+    }
+}
+
+/// Websocket connection tunables. The defaults suit dashboards and bots alike; latency
+/// sensitive traders usually want `tcp_nodelay` and a shorter `ping_interval_ms`.
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct WebSocketTunables {
+    pub connect_timeout_ms: u64,
+    /// Largest accepted frame, unlimited if not set
+    pub max_frame_size: Option<usize>,
+    /// Messages buffered for sending before sends fail, unlimited if not set
+    pub send_queue_depth: Option<usize>,
+    /// Heartbeat period, defaults to `timeout_ms`
+    pub ping_interval_ms: Option<u64>,
+    /// Disable Nagle's algorithm on the underlying TCP connection
+    pub tcp_nodelay: bool,
+    /// How often the session token of an authenticated client is refreshed, so it never
+    /// expires under open subscriptions. Never refreshed if not set.
+    pub session_refresh_ms: Option<u64>,
+}
+
+impl Default for WebSocketTunables {
+    fn default() -> Self {
+        Self {
+            connect_timeout_ms: 10_000,
+            max_frame_size: None,
+            send_queue_depth: None,
+            ping_interval_ms: None,
+            tcp_nodelay: false,
+            session_refresh_ms: Some(DEFAULT_SESSION_REFRESH.as_millis() as u64),
+        }
+    }
+}
+
+/// A third of the session token lifetime, leaving room for a couple of failed refreshes
+pub(crate) const DEFAULT_SESSION_REFRESH: Duration =
+    Duration::from_secs(SESSION_TOKEN_LIFETIME.as_secs() / 3);
+
+/// User agent sent unless `HeadersConfig::user_agent` replaces it
+pub const DEFAULT_USER_AGENT: &str = concat!("nash-native-client/", env!("CARGO_PKG_VERSION"));
+
+/// Headers sent with every HTTP request and on websocket handshakes, e.g. for gateway
+/// routing. Single requests can add or override headers with `Client::run_http_with_headers`.
+#[derive(Clone, Debug, Default, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct HeadersConfig {
+    /// Replaces `DEFAULT_USER_AGENT`
+    pub user_agent: Option<String>,
+    /// Identifies the application to the exchange, sent as `X-App-Id`
+    pub app_id: Option<String>,
+    pub extra: BTreeMap<String, String>,
+}
+
+impl HeadersConfig {
+    /// All headers as name and value pairs, user agent first
+    pub(crate) fn to_list(&self) -> Vec<(String, String)> {
+        let user_agent = self.user_agent.as_deref().unwrap_or(DEFAULT_USER_AGENT);
+        let mut headers = vec![("User-Agent".to_string(), user_agent.to_string())];
+        if let Some(app_id) = &self.app_id {
+            headers.push(("X-App-Id".to_string(), app_id.clone()));
+        }
+        headers.extend(self.extra.clone());
+        headers
+    }
+}
+
+/// Per market limits on cancel-replace cycles done through `Client::requote`
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct RequoteLimits {
+    /// Minimum time between two requotes on the same market
+    pub min_interval_ms: u64,
+    /// Requotes allowed per market in any 60 second window, 0 for no limit
+    pub max_per_minute: u32,
+}
+
+impl Default for RequoteLimits {
+    fn default() -> Self {
+        Self {
+            min_interval_ms: 200,
+            max_per_minute: 120,
+        }
+    }
+}
+
+impl RequoteLimits {
+    pub fn min_interval(&self) -> Duration {
+        Duration::from_millis(self.min_interval_ms)
+    }
+}
+
+/// Size limits for a single batch order request. Larger batches placed with
+/// `Client::place_limit_orders` are split into several requests. Nash doesn't publish limits
+/// for batch requests, so the defaults are conservative guesses. Set `ClientConfig::batch`
+/// or use `Client::set_batch_limits` if the exchange accepts larger batches or rejects
+/// smaller ones.
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct BatchLimits {
+    /// Most orders in one request
+    pub max_orders: usize,
+    /// Limit on the request body. Orders are only signed when a request is sent, so this is
+    /// enforced using an upper estimate of the size of one signed order.
+    pub max_payload_bytes: usize,
+}
+
+/// Upper estimate of the size of one signed order in a batch request, signatures for every
+/// blockchain included
+pub(crate) const SIGNED_ORDER_BYTES: usize = 4096;
+
+impl Default for BatchLimits {
+    fn default() -> Self {
+        Self {
+            max_orders: 20,
+            max_payload_bytes: 128 * 1024,
+        }
+    }
+}
+
+impl BatchLimits {
+    /// Most orders that fit in one request
+    pub fn orders_per_request(&self) -> usize {
+        self.max_orders
+            .min(self.max_payload_bytes / SIGNED_ORDER_BYTES)
+            .max(1)
+    }
+}
+
+/// Client side pre-trade checks
+#[derive(Clone, Debug, Default, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct RiskConfig {
+    /// Reject limit orders priced further than this fraction (e.g. 0.1 for 10%) away from
+    /// the orderbook mid. Use `Client::set_price_guard` to also check against a reference
+    /// price source, and `Client::run_unguarded` to bypass the check for a single order.
+    pub max_price_deviation: Option<f64>,
+    pub requote: RequoteLimits,
+    /// Arm a dead man's switch with this timeout once connected, see
+    /// `Client::arm_dead_man_switch`
+    pub dead_man_switch_ms: Option<u64>,
+}
+
+impl RiskConfig {
+    pub fn dead_man_switch(&self) -> Option<Duration> {
+        self.dead_man_switch_ms.map(Duration::from_millis)
+    }
+}
+
+/// Everything needed to build a `Client`
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct ClientConfig {
+    pub environment: EnvironmentConfig,
+    /// Path to a Nash keyfile. Without keys only public requests are possible
+    pub keys_path: Option<String>,
+    pub affiliate_code: Option<String>,
+    pub turn_off_sign_states: bool,
+    pub client_id: u64,
+    pub timeout_ms: u64,
+    pub retry: RetryPolicy,
+    pub rate_limits: RateLimits,
+    pub pools: PoolConfig,
+    pub compression: CompressionConfig,
+    pub websocket: WebSocketTunables,
+    pub risk: RiskConfig,
+    pub batch: BatchLimits,
+    pub headers: HeadersConfig,
+    /// Send query hashes instead of full documents (APQ). Requires server support.
+    pub persisted_queries: bool,
+}
+
+impl Default for ClientConfig {
+    fn default() -> Self {
+        Self {
+            environment: EnvironmentConfig::default(),
+            keys_path: None,
+            affiliate_code: None,
+            turn_off_sign_states: false,
+            client_id: 1,
+            timeout_ms: 10_000,
+            retry: RetryPolicy::default(),
+            rate_limits: RateLimits::default(),
+            pools: PoolConfig::default(),
+            compression: CompressionConfig::default(),
+            websocket: WebSocketTunables::default(),
+            risk: RiskConfig::default(),
+            batch: BatchLimits::default(),
+            headers: HeadersConfig::default(),
+            persisted_queries: false,
+        }
+    }
+}
+
+impl ClientConfig {
+    pub fn timeout(&self) -> Duration {
+        Duration::from_millis(self.timeout_ms)
+    }
+
+    pub(crate) fn transport_options(&self) -> TransportOptions {
+        TransportOptions {
+            http: HttpOptions {
+                shards: self.pools.http_shards,
+                compression: self.compression.http,
+                headers: self.headers.to_list(),
+            },
+            ws: WsOptions {
+                connect_timeout: Duration::from_millis(self.websocket.connect_timeout_ms),
+                max_frame_size: self.websocket.max_frame_size,
+                send_queue_depth: self.websocket.send_queue_depth,
+                ping_interval: self.websocket.ping_interval_ms.map(Duration::from_millis),
+                tcp_nodelay: self.websocket.tcp_nodelay,
+                session_refresh: self.websocket.session_refresh_ms.map(Duration::from_millis),
+                headers: self.headers.to_list(),
+            },
+        }
+    }
+
+    /// Check values that would otherwise only fail once the client is running
+    pub fn validate(&self) -> Result<()> {
+        if self.timeout_ms == 0 {
+            return Err(ProtocolError("Config: timeout_ms must be greater than 0"));
+        }
+        if self.retry.max_attempts == 0 {
+            return Err(ProtocolError("Config: retry.max_attempts must be at least 1"));
+        }
+        if self.rate_limits.max_concurrent_orders == 0 {
+            return Err(ProtocolError(
+                "Config: rate_limits.max_concurrent_orders must be at least 1",
+            ));
+        }
+        if self.pools.http_shards == 0 {
+            return Err(ProtocolError("Config: pools.http_shards must be at least 1"));
+        }
+        if self.websocket.connect_timeout_ms == 0 {
+            return Err(ProtocolError(
+                "Config: websocket.connect_timeout_ms must be greater than 0",
+            ));
+        }
+        if self.websocket.ping_interval_ms == Some(0) {
+            return Err(ProtocolError(
+                "Config: websocket.ping_interval_ms must be greater than 0",
+            ));
+        }
+        if let Some(refresh) = self.websocket.session_refresh_ms {
+            if refresh == 0 || refresh >= SESSION_TOKEN_LIFETIME.as_millis() as u64 {
+                return Err(ProtocolError::coerce_static_from_str(&format!(
+                    "Config: websocket.session_refresh_ms must be greater than 0 and less than {}",
+                    SESSION_TOKEN_LIFETIME.as_millis()
+                )));
+            }
+        }
+        if let Some(deviation) = self.risk.max_price_deviation {
+            if deviation.is_nan() || deviation <= 0.0 {
+                return Err(ProtocolError(
+                    "Config: risk.max_price_deviation must be greater than 0",
+                ));
+            }
+        }
+        if self.risk.dead_man_switch_ms == Some(0) {
+            return Err(ProtocolError(
+                "Config: risk.dead_man_switch_ms must be greater than 0",
+            ));
+        }
+        if self.batch.max_orders == 0 {
+            return Err(ProtocolError("Config: batch.max_orders must be at least 1"));
+        }
+        if self.batch.max_payload_bytes < SIGNED_ORDER_BYTES {
+            return Err(ProtocolError::coerce_static_from_str(&format!(
+                "Config: batch.max_payload_bytes must be at least {}",
+                SIGNED_ORDER_BYTES
+            )));
+        }
+        header_map(&self.headers.to_list()).context("Config")?;
+        if let EnvironmentConfig::Dev(host) = &self.environment {
+            if host.is_empty() || host.contains("://") {
+                return Err(ProtocolError(
+                    "Config: dev environment must be a bare host name without scheme",
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// Parse and validate a TOML config
+    #[cfg(feature = "toml")]
+    pub fn from_toml_str(config: &str) -> Result<Self> {
+        let config: Self = toml::from_str(config)
+            .map_err(|e| ProtocolError::with_source("Could not parse TOML config", e))?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    /// Parse and validate a YAML config
+    #[cfg(feature = "yaml")]
+    pub fn from_yaml_str(config: &str) -> Result<Self> {
+        let config: Self = serde_yaml::from_str(config)
+            .map_err(|e| ProtocolError::with_source("Could not parse YAML config", e))?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    /// Load a config file, picking the format from its extension
+    pub fn from_file(path: &str) -> Result<Self> {
+        let contents = std::fs::read_to_string(path).map_err(|e| {
+            ProtocolError::with_source(format!("Could not read config {}", path), e)
+        })?;
+        match path.rsplit('.').next() {
+            #[cfg(feature = "toml")]
+            Some("toml") => Self::from_toml_str(&contents),
+            #[cfg(feature = "yaml")]
+            Some("yaml") | Some("yml") => Self::from_yaml_str(&contents),
+            _ => Err(ProtocolError::coerce_static_from_str(&format!(
+                "Unsupported config format for {} (enable the `toml` or `yaml` feature)",
+                path
+            ))),
+        }
+    }
+
+    /// Defaults overridden by `NASH_*` environment variables:
+    /// `NASH_ENV` (production, sandbox or a dev host), `NASH_KEYS_PATH`, `NASH_AFFILIATE_CODE`,
+    /// `NASH_TURN_OFF_SIGN_STATES`, `NASH_CLIENT_ID`, `NASH_TIMEOUT_MS`, `NASH_RETRY_ATTEMPTS`,
+    /// `NASH_RETRY_BACKOFF_MS`, `NASH_MAX_CONCURRENT_ORDERS`, `NASH_HTTP_SHARDS`,
+    /// `NASH_HTTP_COMPRESSION`, `NASH_WS_CONNECT_TIMEOUT_MS`, `NASH_WS_PING_INTERVAL_MS`,
+    /// `NASH_WS_SESSION_REFRESH_MS`, `NASH_WS_TCP_NODELAY`, `NASH_PERSISTED_QUERIES`,
+    /// `NASH_MAX_PRICE_DEVIATION`, `NASH_REQUOTE_MIN_INTERVAL_MS`, `NASH_REQUOTE_MAX_PER_MINUTE`,
+    /// `NASH_DEAD_MAN_SWITCH_MS`, `NASH_BATCH_MAX_ORDERS`, `NASH_BATCH_MAX_PAYLOAD_BYTES`,
+    /// `NASH_USER_AGENT` and `NASH_APP_ID`
+    pub fn from_env() -> Result<Self> {
+        Self::from_vars(env_var)
+    }
+
+    /// Defaults overridden by the `NASH_*` variables `var` looks up, as in `from_env`
+    fn from_vars(var: impl Fn(&str) -> Option<String>) -> Result<Self> {
+        let mut config = Self::default();
+        if let Some(env) = var("NASH_ENV") {
+            config.environment = match env.to_lowercase().as_str() {
+                "production" => EnvironmentConfig::Production,
+                "sandbox" => EnvironmentConfig::Sandbox,
+                _ => EnvironmentConfig::Dev(env),
+            };
+        }
+        if let Some(path) = var("NASH_KEYS_PATH") {
+            config.keys_path = Some(path);
+        }
+        if let Some(code) = var("NASH_AFFILIATE_CODE") {
+            config.affiliate_code = Some(code);
+        }
+        if let Some(flag) = parse_var(&var, "NASH_TURN_OFF_SIGN_STATES")? {
+            config.turn_off_sign_states = flag;
+        }
+        if let Some(id) = parse_var(&var, "NASH_CLIENT_ID")? {
+            config.client_id = id;
+        }
+        if let Some(timeout) = parse_var(&var, "NASH_TIMEOUT_MS")? {
+            config.timeout_ms = timeout;
+        }
+        if let Some(attempts) = parse_var(&var, "NASH_RETRY_ATTEMPTS")? {
+            config.retry.max_attempts = attempts;
+        }
+        if let Some(backoff) = parse_var(&var, "NASH_RETRY_BACKOFF_MS")? {
+            config.retry.backoff_ms = backoff;
+        }
+        if let Some(orders) = parse_var(&var, "NASH_MAX_CONCURRENT_ORDERS")? {
+            config.rate_limits.max_concurrent_orders = orders;
+        }
+        if let Some(shards) = parse_var(&var, "NASH_HTTP_SHARDS")? {
+            config.pools.http_shards = shards;
+        }
+        if let Some(compression) = parse_var(&var, "NASH_HTTP_COMPRESSION")? {
+            config.compression.http = compression;
+        }
+        if let Some(timeout) = parse_var(&var, "NASH_WS_CONNECT_TIMEOUT_MS")? {
+            config.websocket.connect_timeout_ms = timeout;
+        }
+        if let Some(interval) = parse_var(&var, "NASH_WS_PING_INTERVAL_MS")? {
+            config.websocket.ping_interval_ms = Some(interval);
+        }
+        if let Some(refresh) = parse_var(&var, "NASH_WS_SESSION_REFRESH_MS")? {
+            config.websocket.session_refresh_ms = Some(refresh);
+        }
+        if let Some(nodelay) = parse_var(&var, "NASH_WS_TCP_NODELAY")? {
+            config.websocket.tcp_nodelay = nodelay;
+        }
+        if let Some(persisted_queries) = parse_var(&var, "NASH_PERSISTED_QUERIES")? {
+            config.persisted_queries = persisted_queries;
+        }
+        if let Some(deviation) = parse_var(&var, "NASH_MAX_PRICE_DEVIATION")? {
+            config.risk.max_price_deviation = Some(deviation);
+        }
+        if let Some(interval) = parse_var(&var, "NASH_REQUOTE_MIN_INTERVAL_MS")? {
+            config.risk.requote.min_interval_ms = interval;
+        }
+        if let Some(requotes) = parse_var(&var, "NASH_REQUOTE_MAX_PER_MINUTE")? {
+            config.risk.requote.max_per_minute = requotes;
+        }
+        if let Some(timeout) = parse_var(&var, "NASH_DEAD_MAN_SWITCH_MS")? {
+            config.risk.dead_man_switch_ms = Some(timeout);
+        }
+        if let Some(orders) = parse_var(&var, "NASH_BATCH_MAX_ORDERS")? {
+            config.batch.max_orders = orders;
+        }
+        if let Some(bytes) = parse_var(&var, "NASH_BATCH_MAX_PAYLOAD_BYTES")? {
+            config.batch.max_payload_bytes = bytes;
+        }
+        if let Some(user_agent) = var("NASH_USER_AGENT") {
+            config.headers.user_agent = Some(user_agent);
+        }
+        if let Some(app_id) = var("NASH_APP_ID") {
+            config.headers.app_id = Some(app_id);
+        }
+        config.validate()?;
+        Ok(config)
+    }
+}
+
+/// Build protocol state from credentials in the environment, falling back to `keys_path`
+pub(crate) fn state_from_env(config: &ClientConfig) -> Result<State> {
+    if let (Some(secret), Some(session)) = (env_var("NASH_API_SECRET"), env_var("NASH_API_KEY")) {
+        return State::from_keys(&secret, &session);
+    }
+    if let Some(keyfile) = env_var("NASH_KEYFILE") {
+        return State::from_keyfile_json(&keyfile);
+    }
+    if let Some(encoded) = env_var("NASH_KEYFILE_BASE64") {
+        // tolerate line wrapping as produced by `base64` on most systems
+        let encoded: String = encoded.split_whitespace().collect();
+        let decoded = base64::decode(&encoded)
+            .map_err(|_| ProtocolError("NASH_KEYFILE_BASE64 is not valid base64"))?;
+        let keyfile = String::from_utf8(decoded)
+            .map_err(|_| ProtocolError("NASH_KEYFILE_BASE64 does not decode to UTF-8"))?;
+        return State::from_keyfile_json(&keyfile);
+    }
+    State::from_keys_path(config.keys_path.as_deref())
+}
+
+fn env_var(name: &str) -> Option<String> {
+    std::env::var(name).ok().filter(|value| !value.is_empty())
+}
+
+fn parse_var<T: std::str::FromStr>(
+    var: &impl Fn(&str) -> Option<String>,
+    name: &str,
+) -> Result<Option<T>> {
+    match var(name) {
+        Some(value) => value.parse().map(Some).map_err(|_| {
+            ProtocolError::coerce_static_from_str(&format!("Invalid value for {}: {}", name, value))
+        }),
+        None => Ok(None),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ClientConfig, EnvironmentConfig, SIGNED_ORDER_BYTES};
+    use std::collections::HashMap;
+
+    fn from_vars(vars: &[(&str, &str)]) -> Result<ClientConfig, String> {
+        let vars: HashMap<String, String> = vars
+            .iter()
+            .map(|(name, value)| (name.to_string(), value.to_string()))
+            .collect();
+        ClientConfig::from_vars(|name| vars.get(name).cloned()).map_err(|e| e.report())
+    }
+
+    #[test]
+    fn environment_variables_override_defaults() {
+        assert_eq!(from_vars(&[]).unwrap(), ClientConfig::default());
+        let config = from_vars(&[
+            ("NASH_ENV", "Sandbox"),
+            ("NASH_KEYS_PATH", "/keys/nash.json"),
+            ("NASH_TIMEOUT_MS", "2500"),
+            ("NASH_RETRY_ATTEMPTS", "3"),
+            ("NASH_HTTP_COMPRESSION", "false"),
+            ("NASH_WS_PING_INTERVAL_MS", "1000"),
+            ("NASH_MAX_PRICE_DEVIATION", "0.05"),
+            ("NASH_DEAD_MAN_SWITCH_MS", "30000"),
+            ("NASH_APP_ID", "desk-7"),
+        ])
+        .unwrap();
+        assert_eq!(config.environment, EnvironmentConfig::Sandbox);
+        assert_eq!(config.keys_path.as_deref(), Some("/keys/nash.json"));
+        assert_eq!(config.timeout_ms, 2500);
+        assert_eq!(config.retry.max_attempts, 3);
+        assert!(!config.compression.http);
+        assert_eq!(config.websocket.ping_interval_ms, Some(1000));
+        assert_eq!(config.risk.max_price_deviation, Some(0.05));
+        assert_eq!(config.risk.dead_man_switch_ms, Some(30_000));
+        assert_eq!(config.headers.app_id.as_deref(), Some("desk-7"));
+        // untouched values keep their defaults
+        assert_eq!(config.batch, ClientConfig::default().batch);
+
+        let config = from_vars(&[("NASH_ENV", "localhost:4000")]).unwrap();
+        assert_eq!(
+            config.environment,
+            EnvironmentConfig::Dev("localhost:4000".to_string())
+        );
+    }
+
+    #[test]
+    fn invalid_environment_variables_are_rejected() {
+        let error = from_vars(&[("NASH_TIMEOUT_MS", "soon")]).unwrap_err();
+        assert!(error.contains("NASH_TIMEOUT_MS"), "{}", error);
+        assert!(from_vars(&[("NASH_WS_TCP_NODELAY", "yes")]).is_err());
+        // parsed values are validated as well
+        let error = from_vars(&[("NASH_HTTP_SHARDS", "0")]).unwrap_err();
+        assert!(error.contains("pools.http_shards"), "{}", error);
+        assert!(from_vars(&[("NASH_ENV", "https://localhost")]).is_err());
+    }
+
+    #[test]
+    fn values_that_would_fail_at_runtime_are_rejected() {
+        let mut config = ClientConfig::default();
+        assert!(config.validate().is_ok());
+        config.websocket.session_refresh_ms = None;
+        assert!(config.validate().is_ok());
+
+        let changes: &[fn(&mut ClientConfig)] = &[
+            |config| config.timeout_ms = 0,
+            |config| config.retry.max_attempts = 0,
+            |config| config.rate_limits.max_concurrent_orders = 0,
+            |config| config.websocket.connect_timeout_ms = 0,
+            |config| config.websocket.ping_interval_ms = Some(0),
+            |config| config.websocket.session_refresh_ms = Some(0),
+            |config| config.websocket.session_refresh_ms = Some(u64::MAX),
+            |config| config.risk.max_price_deviation = Some(f64::NAN),
+            |config| config.risk.max_price_deviation = Some(-0.1),
+            |config| config.risk.dead_man_switch_ms = Some(0),
+            |config| config.batch.max_orders = 0,
+            |config| config.batch.max_payload_bytes = SIGNED_ORDER_BYTES - 1,
+            |config| config.environment = EnvironmentConfig::Dev(String::new()),
+            |config| {
+                config
+                    .headers
+                    .extra
+                    .insert("Bad Header".to_string(), "value".to_string());
+            },
+        ];
+        for (i, change) in changes.iter().enumerate() {
+            let mut config = ClientConfig::default();
+            change(&mut config);
+            assert!(config.validate().is_err(), "change {} was accepted", i);
+        }
+    }
+
+    #[test]
+    fn config_files_need_a_supported_format() {
+        let path = std::env::temp_dir().join(format!("nash-config-{}.ini", std::process::id()));
+        std::fs::write(&path, "timeout_ms = 1").unwrap();
+        let result = ClientConfig::from_file(path.to_str().unwrap());
+        std::fs::remove_file(&path).unwrap();
+        assert!(result.is_err());
+        assert!(ClientConfig::from_file("/does/not/exist.toml").is_err());
+    }
+
+    #[cfg(feature = "toml")]
+    #[test]
+    fn toml_configs_are_parsed_and_validated() {
+        let config = ClientConfig::from_toml_str(
+            r#"
+            timeout_ms = 5000
+
+            [environment]
+            dev = "localhost:4000"
+
+            [websocket]
+            tcp_nodelay = true
+            "#,
+        )
+        .unwrap();
+        assert_eq!(config.timeout_ms, 5000);
+        assert_eq!(
+            config.environment,
+            EnvironmentConfig::Dev("localhost:4000".to_string())
+        );
+        assert!(config.websocket.tcp_nodelay);
+        assert_eq!(config.retry, ClientConfig::default().retry);
+
+        assert!(ClientConfig::from_toml_str("timeout_ms = 0").is_err());
+        assert!(ClientConfig::from_toml_str("timeout_ms = \"soon\"").is_err());
+    }
+}
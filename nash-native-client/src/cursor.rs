@@ -0,0 +1,340 @@
+//! Incremental syncs: a `CursorStore` remembers, per account and stream (e.g. `trades`), how
+//! far a sync got, so the next one only fetches what is new. Cursors are kept in memory, in
+//! JSON files, or in SQLite behind the `sqlite` feature.
+
+use std::collections::{BTreeMap, HashMap};
+use std::path::PathBuf;
+use std::sync::RwLock as SyncRwLock;
+
+use futures::StreamExt;
+use serde::{Deserialize, Serialize};
+
+use nash_protocol::errors::{ProtocolError, Result};
+use nash_protocol::protocol::list_account_movements::{ListAccountMovementsRequest, Movement};
+use nash_protocol::protocol::list_account_trades::ListAccountTradesRequest;
+use nash_protocol::types::timestamp::{self, Timestamp};
+use nash_protocol::types::{DateTimeRange, Trade};
+
+use crate::Client;
+
+/// Stream name trades are synced under
+pub const TRADES_STREAM: &str = "trades";
+
+/// Stream name deposits, withdrawals and transfers are synced under
+pub const MOVEMENTS_STREAM: &str = "movements";
+
+/// How far a sync got: the newest item seen, and the ids of every item at that time, since
+/// the next sync starts at that time again
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct SyncCursor {
+    #[serde(with = "timestamp::rfc3339")]
+    pub synced_until: Timestamp,
+    pub ids_at_cursor: Vec<String>,
+}
+
+impl SyncCursor {
+    /// Cursor after `items`, given as `(id, time)`, starting from `previous`
+    fn advance<'a>(
+        previous: Option<SyncCursor>,
+        items: impl Iterator<Item = (&'a str, Timestamp)>,
+    ) -> Option<SyncCursor> {
+        let mut cursor = previous;
+        for (id, at) in items {
+            match &mut cursor {
+                Some(cursor) if at < cursor.synced_until => {}
+                Some(cursor) if at == cursor.synced_until => {
+                    cursor.ids_at_cursor.push(id.to_string())
+                }
+                _ => {
+                    cursor = Some(SyncCursor {
+                        synced_until: at,
+                        ids_at_cursor: vec![id.to_string()],
+                    })
+                }
+            }
+        }
+        cursor
+    }
+
+    fn has_seen(&self, id: &str, at: &Timestamp) -> bool {
+        at < &self.synced_until
+            || (at == &self.synced_until && self.ids_at_cursor.iter().any(|seen| seen == id))
+    }
+}
+
+/// Where sync cursors are kept
+pub trait CursorStore: Send + Sync {
+    fn load(&self, account: &str, stream: &str) -> Result<Option<SyncCursor>>;
+
+    fn save(&self, account: &str, stream: &str, cursor: &SyncCursor) -> Result<()>;
+}
+
+/// Cursors kept for the lifetime of the process
+#[derive(Debug, Default)]
+pub struct MemoryCursorStore {
+    cursors: SyncRwLock<HashMap<(String, String), SyncCursor>>,
+}
+
+impl CursorStore for MemoryCursorStore {
+    fn load(&self, account: &str, stream: &str) -> Result<Option<SyncCursor>> {
+        let key = (account.to_string(), stream.to_string());
+        Ok(self.cursors.read().unwrap().get(&key).cloned())
+    }
+
+    fn save(&self, account: &str, stream: &str, cursor: &SyncCursor) -> Result<()> {
+        let key = (account.to_string(), stream.to_string());
+        self.cursors.write().unwrap().insert(key, cursor.clone());
+        Ok(())
+    }
+}
+
+/// Cursors of each account in a JSON file `<account>.json` in `dir`, keyed by stream
+#[derive(Debug)]
+pub struct FileCursorStore {
+    dir: PathBuf,
+}
+
+impl FileCursorStore {
+    pub fn new(dir: impl Into<PathBuf>) -> Result<Self> {
+        let dir = dir.into();
+        std::fs::create_dir_all(&dir).map_err(|e| {
+            ProtocolError::with_source(
+                format!("Could not create cursor store {}", dir.display()),
+                e,
+            )
+        })?;
+        Ok(Self { dir })
+    }
+
+    fn path(&self, account: &str) -> PathBuf {
+        self.dir.join(format!("{}.json", account))
+    }
+
+    fn read(&self, account: &str) -> Result<BTreeMap<String, SyncCursor>> {
+        let path = self.path(account);
+        let contents = match std::fs::read_to_string(&path) {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(BTreeMap::new()),
+            Err(e) => {
+                return Err(ProtocolError::with_source(
+                    format!("Could not read cursors {}", path.display()),
+                    e,
+                ))
+            }
+        };
+        serde_json::from_str(&contents).map_err(|e| {
+            ProtocolError::with_source(format!("Could not parse cursors {}", path.display()), e)
+        })
+    }
+}
+
+impl CursorStore for FileCursorStore {
+    fn load(&self, account: &str, stream: &str) -> Result<Option<SyncCursor>> {
+        Ok(self.read(account)?.remove(stream))
+    }
+
+    fn save(&self, account: &str, stream: &str, cursor: &SyncCursor) -> Result<()> {
+        let mut cursors = self.read(account)?;
+        cursors.insert(stream.to_string(), cursor.clone());
+        let contents = serde_json::to_string_pretty(&cursors)
+            .map_err(|e| ProtocolError::with_source("Could not serialize cursors", e))?;
+        // written next to the file and renamed over it, so a crash doesn't lose every cursor
+        let path = self.path(account);
+        let partial = path.with_extension("json.partial");
+        std::fs::write(&partial, contents)
+            .and_then(|_| std::fs::rename(&partial, &path))
+            .map_err(|e| {
+                ProtocolError::with_source(format!("Could not store cursors {}", path.display()), e)
+            })
+    }
+}
+
+/// Cursors in a `sync_cursors` table of a SQLite database
+#[cfg(feature = "sqlite")]
+pub struct SqliteCursorStore {
+    connection: std::sync::Mutex<rusqlite::Connection>,
+}
+
+#[cfg(feature = "sqlite")]
+impl SqliteCursorStore {
+    pub fn open(path: impl AsRef<std::path::Path>) -> Result<Self> {
+        let connection = rusqlite::Connection::open(path).map_err(sqlite_error)?;
+        connection
+            .execute(
+                "CREATE TABLE IF NOT EXISTS sync_cursors (
+                    account TEXT NOT NULL,
+                    stream TEXT NOT NULL,
+                    cursor TEXT NOT NULL,
+                    PRIMARY KEY (account, stream)
+                )",
+                [],
+            )
+            .map_err(sqlite_error)?;
+        Ok(Self {
+            connection: std::sync::Mutex::new(connection),
+        })
+    }
+}
+
+#[cfg(feature = "sqlite")]
+fn sqlite_error(e: rusqlite::Error) -> ProtocolError {
+    ProtocolError::with_source("Cursor store error", e)
+}
+
+#[cfg(feature = "sqlite")]
+impl CursorStore for SqliteCursorStore {
+    fn load(&self, account: &str, stream: &str) -> Result<Option<SyncCursor>> {
+        use rusqlite::OptionalExtension;
+        let cursor: Option<String> = self
+            .connection
+            .lock()
+            .unwrap()
+            .query_row(
+                "SELECT cursor FROM sync_cursors WHERE account = ?1 AND stream = ?2",
+                rusqlite::params![account, stream],
+                |row| row.get(0),
+            )
+            .optional()
+            .map_err(sqlite_error)?;
+        cursor
+            .map(|cursor| {
+                serde_json::from_str(&cursor)
+                    .map_err(|e| ProtocolError::with_source("Could not parse cursor", e))
+            })
+            .transpose()
+    }
+
+    fn save(&self, account: &str, stream: &str, cursor: &SyncCursor) -> Result<()> {
+        let cursor = serde_json::to_string(cursor)
+            .map_err(|e| ProtocolError::with_source("Could not serialize cursor", e))?;
+        self.connection
+            .lock()
+            .unwrap()
+            .execute(
+                "INSERT INTO sync_cursors (account, stream, cursor) VALUES (?1, ?2, ?3)
+                 ON CONFLICT (account, stream) DO UPDATE SET cursor = excluded.cursor",
+                rusqlite::params![account, stream, cursor],
+            )
+            .map_err(sqlite_error)?;
+        Ok(())
+    }
+}
+
+impl Client {
+    /// Key cursors of the current account are stored under
+    async fn cursor_account(&self) -> Result<String> {
+        Ok(self
+            .inner
+            .state
+            .read()
+            .await
+            .signer()?
+            .request_payload_public_key())
+    }
+
+    /// Trades of the account since the last sync recorded in `store`, oldest first. The first
+    /// sync returns the whole history. The cursor is only saved once every page was fetched,
+    /// so a failed sync is repeated in full the next time.
+    pub async fn sync_account_trades(&self, store: &dyn CursorStore) -> Result<Vec<Trade>> {
+        let account = self.cursor_account().await?;
+        let cursor = store.load(&account, TRADES_STREAM)?;
+        let range = cursor.as_ref().map(|cursor| DateTimeRange {
+            start: cursor.synced_until,
+            stop: timestamp::now(),
+        });
+        let mut trades = self
+            .pages(ListAccountTradesRequest {
+                market: None,
+                before: None,
+                limit: None,
+                range,
+            })
+            .all()
+            .await?;
+        trades.retain(|trade| {
+            !cursor.as_ref().map_or(false, |cursor| {
+                cursor.has_seen(&trade.id, &trade.executed_at)
+            })
+        });
+        trades.sort_by_key(|trade| trade.executed_at);
+        let items = trades
+            .iter()
+            .map(|trade| (trade.id.as_str(), trade.executed_at));
+        if let Some(advanced) = SyncCursor::advance(cursor, items) {
+            store.save(&account, TRADES_STREAM, &advanced)?;
+        }
+        Ok(trades)
+    }
+
+    /// Movements of the account received since the last sync recorded in `store`, oldest
+    /// first. Movements the exchange has not received yet are left for a later sync, and a
+    /// synced movement is not returned again when its status changes. The exchange can't
+    /// filter movements by time, so pages are followed until one reaches the cursor.
+    pub async fn sync_account_movements(&self, store: &dyn CursorStore) -> Result<Vec<Movement>> {
+        let account = self.cursor_account().await?;
+        let cursor = store.load(&account, MOVEMENTS_STREAM)?;
+        let mut pages = self.pages(ListAccountMovementsRequest::default()).stream();
+        let mut movements = Vec::new();
+        while let Some(page) = pages.next().await {
+            let page = page?;
+            let reached_cursor = cursor.as_ref().map_or(false, |cursor| {
+                page.items.iter().any(|movement| {
+                    movement
+                        .received_at
+                        .map_or(false, |at| at < cursor.synced_until)
+                })
+            });
+            movements.extend(page.items);
+            if reached_cursor {
+                break;
+            }
+        }
+        let mut movements: Vec<(Timestamp, Movement)> = movements
+            .into_iter()
+            .filter_map(|movement| movement.received_at.map(|at| (at, movement)))
+            .filter(|(at, movement)| {
+                !cursor
+                    .as_ref()
+                    .map_or(false, |cursor| cursor.has_seen(&movement.id, at))
+            })
+            .collect();
+        movements.sort_by_key(|(at, _)| *at);
+        let items = movements
+            .iter()
+            .map(|(at, movement)| (movement.id.as_str(), *at));
+        if let Some(advanced) = SyncCursor::advance(cursor, items) {
+            store.save(&account, MOVEMENTS_STREAM, &advanced)?;
+        }
+        Ok(movements
+            .into_iter()
+            .map(|(_, movement)| movement)
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{CursorStore, FileCursorStore, SyncCursor};
+    use nash_protocol::types::timestamp::parse_timestamp;
+
+    #[test]
+    fn cursors_advance_and_persist() {
+        let at = |time: &str| parse_timestamp(&format!("2021-01-01T00:00:{}Z", time)).unwrap();
+        let items = vec![("a", at("01")), ("b", at("02")), ("c", at("02"))];
+        let cursor = SyncCursor::advance(None, items.into_iter()).unwrap();
+        assert_eq!(cursor.synced_until, at("02"));
+        assert_eq!(cursor.ids_at_cursor, vec!["b", "c"]);
+        assert!(cursor.has_seen("c", &at("02")) && cursor.has_seen("z", &at("01")));
+        assert!(!cursor.has_seen("d", &at("02")));
+        let advanced = SyncCursor::advance(Some(cursor.clone()), vec![("d", at("02"))].into_iter());
+        assert_eq!(advanced.unwrap().ids_at_cursor, vec!["b", "c", "d"]);
+
+        let dir = std::env::temp_dir().join(format!("nash-cursors-{}", std::process::id()));
+        let store = FileCursorStore::new(&dir).unwrap();
+        assert_eq!(store.load("account", "trades").unwrap(), None);
+        store.save("account", "trades", &cursor).unwrap();
+        let reopened = FileCursorStore::new(&dir).unwrap();
+        assert_eq!(reopened.load("account", "trades").unwrap(), Some(cursor));
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,495 @@
+//! Execution tactics that work an order through several child orders and report the result
+//! as a single parent execution
+
+use std::time::Duration;
+
+use bigdecimal::{BigDecimal, Signed, Zero};
+use tokio::time::Instant;
+use tokio_util::sync::CancellationToken;
+use tracing::{info, warn};
+
+use nash_protocol::errors::{ProtocolError, Result};
+use nash_protocol::protocol::cancel_order::CancelOrderRequest;
+use nash_protocol::protocol::get_account_order::GetAccountOrderRequest;
+use nash_protocol::protocol::place_order::LimitOrderRequest;
+use nash_protocol::types::{
+    BuyOrSell, Order, OrderCancellationPolicy, OrderStatus, RoundingMode, Trade,
+};
+
+use crate::Client;
+
+/// How often order status is polled while waiting for a child order to settle
+const SETTLE_POLL_INTERVAL: Duration = Duration::from_millis(100);
+/// Consecutive iceberg slices that may settle without any fill before the iceberg gives up
+const MAX_IDLE_SLICES: u32 = 5;
+/// Pause after the first iceberg slice without fills, doubled for every further one
+const IDLE_SLICE_BACKOFF: Duration = Duration::from_secs(1);
+
+/// Buy or sell `amount` with an immediate-or-cancel limit order at `protective_price`. If
+/// that doesn't fill completely within `fill_timeout`, the remainder is sent as a marketable
+/// order capped at `max_slippage` (e.g. 0.01 for 1%) beyond the protective price. Nash market
+/// orders take no price, so the capped order is an immediate-or-cancel limit order at the
+/// cap price: it takes whatever liquidity is available up to the cap and never worse.
+#[derive(Clone, Debug)]
+pub struct IocWithFallback {
+    pub market: String,
+    pub buy_or_sell: BuyOrSell,
+    pub amount: BigDecimal,
+    pub protective_price: BigDecimal,
+    pub max_slippage: BigDecimal,
+    pub fill_timeout: Duration,
+}
+
+impl IocWithFallback {
+    /// Worst price the remainder may be executed at, rounded towards the protective price
+    /// to `price_precision` decimals
+    pub fn cap_price(&self, price_precision: u32) -> Result<BigDecimal> {
+        let one = BigDecimal::from(1);
+        let cap = match self.buy_or_sell {
+            BuyOrSell::Buy => &self.protective_price * (one + &self.max_slippage),
+            BuyOrSell::Sell => &self.protective_price * (one - &self.max_slippage),
+        };
+        RoundingMode::for_price(self.buy_or_sell).round(&cap, price_precision)
+    }
+
+    fn validate(&self) -> Result<()> {
+        if !self.amount.is_positive() || !self.protective_price.is_positive() {
+            return Err(ProtocolError(
+                "Execution amount and protective price must be positive",
+            ));
+        }
+        if self.max_slippage.is_negative() || self.max_slippage >= BigDecimal::from(1) {
+            return Err(ProtocolError("Execution max_slippage must be in [0, 1)"));
+        }
+        Ok(())
+    }
+
+    fn child_order(&self, amount: &BigDecimal, price: &BigDecimal) -> LimitOrderRequest {
+        LimitOrderRequest {
+            market: self.market.clone(),
+            client_order_id: None,
+            buy_or_sell: self.buy_or_sell,
+            amount: amount.to_string(),
+            price: price.to_string(),
+            cancellation_policy: OrderCancellationPolicy::ImmediateOrCancel,
+            allow_taker: true,
+        }
+    }
+}
+
+/// Work a large limit order while only showing `display_amount` of it on the book. The
+/// exchange has no hidden size, so the order is sent as good-til-cancelled slices of at
+/// most `display_amount`, each placed once the previous one has settled. A slice still
+/// resting after `slice_timeout` is cancelled and the remainder re-sliced, so the visible
+/// size never goes stale. Slices that settle without any fill are re-posted with a growing
+/// pause, and the iceberg stops once `MAX_IDLE_SLICES` of them settled in a row.
+#[derive(Clone, Debug)]
+pub struct Iceberg {
+    pub market: String,
+    pub buy_or_sell: BuyOrSell,
+    /// Total amount to execute
+    pub amount: BigDecimal,
+    /// Largest amount on the book at any time
+    pub display_amount: BigDecimal,
+    pub price: BigDecimal,
+    /// Let slices take liquidity instead of only adding it
+    pub allow_taker: bool,
+    pub slice_timeout: Duration,
+}
+
+impl Iceberg {
+    fn validate(&self) -> Result<()> {
+        if !self.amount.is_positive() || !self.price.is_positive() {
+            return Err(ProtocolError("Iceberg amount and price must be positive"));
+        }
+        if !self.display_amount.is_positive() || self.display_amount > self.amount {
+            return Err(ProtocolError(
+                "Iceberg display amount must be positive and at most the amount",
+            ));
+        }
+        Ok(())
+    }
+
+    /// Size of the next slice once `executed` has been executed
+    fn next_slice(&self, executed: &BigDecimal) -> BigDecimal {
+        let remaining = &self.amount - executed;
+        if remaining > self.display_amount {
+            self.display_amount.clone()
+        } else {
+            remaining
+        }
+    }
+
+    /// Pause before re-posting after `idle` consecutive slices without fills
+    fn idle_backoff(idle: u32) -> Duration {
+        match idle {
+            0 => Duration::from_secs(0),
+            idle => IDLE_SLICE_BACKOFF * 2u32.pow(idle - 1),
+        }
+    }
+
+    fn child_order(&self, amount: &BigDecimal) -> LimitOrderRequest {
+        LimitOrderRequest {
+            market: self.market.clone(),
+            client_order_id: None,
+            buy_or_sell: self.buy_or_sell,
+            amount: amount.to_string(),
+            price: self.price.to_string(),
+            cancellation_policy: OrderCancellationPolicy::GoodTilCancelled,
+            allow_taker: self.allow_taker,
+        }
+    }
+}
+
+/// Which step of a tactic a child order belongs to
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ExecutionStage {
+    Protective,
+    Escalation,
+    /// One visible slice of an `Iceberg`
+    Slice,
+    /// The slice of one interval of a `Twap`
+    Interval,
+}
+
+/// One child order of a parent execution, as settled on the exchange
+#[derive(Clone, Debug)]
+pub struct ChildExecution {
+    pub stage: ExecutionStage,
+    pub order_id: String,
+    pub limit_price: BigDecimal,
+    pub amount: BigDecimal,
+    pub executed: BigDecimal,
+    pub status: OrderStatus,
+    pub trades: Vec<Trade>,
+}
+
+/// Result of an execution tactic across all its child orders
+#[derive(Clone, Debug)]
+pub struct ParentExecution {
+    pub market: String,
+    pub buy_or_sell: BuyOrSell,
+    pub requested: BigDecimal,
+    pub children: Vec<ChildExecution>,
+}
+
+impl ParentExecution {
+    pub fn executed(&self) -> BigDecimal {
+        self.children
+            .iter()
+            .fold(BigDecimal::zero(), |total, child| total + &child.executed)
+    }
+
+    pub fn remaining(&self) -> BigDecimal {
+        &self.requested - self.executed()
+    }
+
+    pub fn is_complete(&self) -> bool {
+        !self.remaining().is_positive()
+    }
+
+    /// Volume weighted price over all trades of all child orders
+    pub fn average_price(&self) -> Option<BigDecimal> {
+        let trades = self.children.iter().flat_map(|child| child.trades.iter());
+        let (volume, notional) = trades.fold(
+            (BigDecimal::zero(), BigDecimal::zero()),
+            |(volume, notional), trade| {
+                (
+                    volume + &trade.amount,
+                    notional + &trade.amount * &trade.limit_price,
+                )
+            },
+        );
+        if volume.is_zero() {
+            None
+        } else {
+            Some(notional / volume)
+        }
+    }
+}
+
+impl Client {
+    /// Run an `IocWithFallback` tactic. Both child orders go through the price guard.
+    pub async fn execute_ioc_with_fallback(
+        &self,
+        tactic: IocWithFallback,
+    ) -> Result<ParentExecution> {
+        self.execute_ioc_with_fallback_cancellable(tactic, CancellationToken::new())
+            .await
+    }
+
+    /// Same as `execute_ioc_with_fallback`, stopping early once `cancel` is triggered: an
+    /// open child order is cancelled and no escalation is placed. The returned execution
+    /// covers what was executed until then.
+    pub async fn execute_ioc_with_fallback_cancellable(
+        &self,
+        tactic: IocWithFallback,
+        cancel: CancellationToken,
+    ) -> Result<ParentExecution> {
+        tactic.validate()?;
+        let market = self.inner.state.read().await.get_market(&tactic.market)?;
+        let mut execution = ParentExecution {
+            market: tactic.market.clone(),
+            buy_or_sell: tactic.buy_or_sell,
+            requested: tactic.amount.clone(),
+            children: Vec::new(),
+        };
+        if cancel.is_cancelled() {
+            return Ok(execution);
+        }
+        let protective = self
+            .execute_child(
+                &tactic,
+                ExecutionStage::Protective,
+                &tactic.amount,
+                &tactic.protective_price,
+                &cancel,
+            )
+            .await?;
+        execution.children.push(protective);
+        if execution.is_complete() || cancel.is_cancelled() {
+            return Ok(execution);
+        }
+        let remaining = execution.remaining();
+        let cap_price = tactic.cap_price(market.asset_b.precision)?;
+        info!(market = %tactic.market, %remaining, "IOC not filled, escalating remainder");
+        let escalation = self
+            .execute_child(
+                &tactic,
+                ExecutionStage::Escalation,
+                &remaining,
+                &cap_price,
+                &cancel,
+            )
+            .await
+            .map_err(|e| {
+                e.context(format!(
+                    "Escalation failed after {} of {} executed",
+                    execution.executed(),
+                    execution.requested
+                ))
+            })?;
+        execution.children.push(escalation);
+        Ok(execution)
+    }
+
+    /// Run an `Iceberg` tactic until its amount is executed or `cancel` is triggered, in
+    /// which case the open slice is cancelled. Every slice goes through the price guard.
+    /// Fails once `MAX_IDLE_SLICES` slices in a row settled without any fill.
+    pub async fn execute_iceberg(
+        &self,
+        tactic: Iceberg,
+        cancel: CancellationToken,
+    ) -> Result<ParentExecution> {
+        tactic.validate()?;
+        let mut execution = ParentExecution {
+            market: tactic.market.clone(),
+            buy_or_sell: tactic.buy_or_sell,
+            requested: tactic.amount.clone(),
+            children: Vec::new(),
+        };
+        let mut idle = 0;
+        while !execution.is_complete() && !cancel.is_cancelled() {
+            let amount = tactic.next_slice(&execution.executed());
+            let slice = self
+                .execute_slice(&tactic, &amount, &cancel)
+                .await
+                .map_err(|e| {
+                    e.context(format!(
+                        "Iceberg stopped after {} of {} executed",
+                        execution.executed(),
+                        execution.requested
+                    ))
+                })?;
+            info!(market = %tactic.market, executed = %slice.executed, status = ?slice.status, "iceberg slice settled");
+            idle = if slice.executed.is_zero() { idle + 1 } else { 0 };
+            execution.children.push(slice);
+            if idle >= MAX_IDLE_SLICES {
+                return Err(ProtocolError::coerce_static_from_str(&format!(
+                    "Iceberg stopped after {} of {} executed: {} slices in a row without fills",
+                    execution.executed(),
+                    execution.requested,
+                    idle
+                )));
+            }
+            if idle > 0 && !execution.is_complete() {
+                tokio::select! {
+                    _ = tokio::time::sleep(Iceberg::idle_backoff(idle)) => {}
+                    _ = cancel.cancelled() => {}
+                }
+            }
+        }
+        Ok(execution)
+    }
+
+    async fn execute_slice(
+        &self,
+        tactic: &Iceberg,
+        amount: &BigDecimal,
+        cancel: &CancellationToken,
+    ) -> Result<ChildExecution> {
+        let placed = self
+            .run(tactic.child_order(amount))
+            .await?
+            .response_or_error()?;
+        let order = self
+            .await_fill(
+                &placed.order_id,
+                &tactic.market,
+                tactic.slice_timeout,
+                cancel,
+            )
+            .await?;
+        Ok(ChildExecution {
+            stage: ExecutionStage::Slice,
+            order_id: order.id,
+            limit_price: tactic.price.clone(),
+            amount: amount.clone(),
+            executed: order.amount_executed,
+            status: order.status,
+            trades: order.trades,
+        })
+    }
+
+    async fn execute_child(
+        &self,
+        tactic: &IocWithFallback,
+        stage: ExecutionStage,
+        amount: &BigDecimal,
+        price: &BigDecimal,
+        cancel: &CancellationToken,
+    ) -> Result<ChildExecution> {
+        let placed = self
+            .run(tactic.child_order(amount, price))
+            .await?
+            .response_or_error()?;
+        let order = self
+            .await_fill(
+                &placed.order_id,
+                &tactic.market,
+                tactic.fill_timeout,
+                cancel,
+            )
+            .await?;
+        Ok(ChildExecution {
+            stage,
+            order_id: order.id,
+            limit_price: price.clone(),
+            amount: amount.clone(),
+            executed: order.amount_executed,
+            status: order.status,
+            trades: order.trades,
+        })
+    }
+
+    /// Wait until an order is filled or canceled. An order still open after `timeout`, or
+    /// once `cancel` is triggered, is cancelled on the exchange and given another `timeout`
+    /// for the cancellation to show up. Returns the order as last seen.
+    pub async fn await_fill(
+        &self,
+        order_id: &str,
+        market: &str,
+        timeout: Duration,
+        cancel: &CancellationToken,
+    ) -> Result<Order> {
+        let mut deadline = Instant::now() + timeout;
+        let mut cancelled = false;
+        loop {
+            let order = self.fetch_order(order_id).await?;
+            if matches!(order.status, OrderStatus::Filled | OrderStatus::Canceled) {
+                return Ok(order);
+            }
+            if cancelled && Instant::now() >= deadline {
+                return Err(ProtocolError::coerce_static_from_str(&format!(
+                    "Order {} did not settle after cancellation",
+                    order_id
+                )));
+            }
+            if !cancelled && (Instant::now() >= deadline || cancel.is_cancelled()) {
+                warn!(%order_id, "order not settled in time or wait cancelled, cancelling");
+                let request = CancelOrderRequest {
+                    order_id: order_id.to_string(),
+                    market: market.to_string(),
+                };
+                // an error response usually means the order settled in the meantime
+                self.run(request).await?;
+                cancelled = true;
+                deadline = Instant::now() + timeout;
+                continue;
+            }
+            if cancelled {
+                tokio::time::sleep(SETTLE_POLL_INTERVAL).await;
+            } else {
+                tokio::select! {
+                    _ = tokio::time::sleep(SETTLE_POLL_INTERVAL) => {}
+                    _ = cancel.cancelled() => {}
+                }
+            }
+        }
+    }
+
+    async fn fetch_order(&self, order_id: &str) -> Result<Order> {
+        let request = GetAccountOrderRequest {
+            order_id: order_id.to_string(),
+        };
+        Ok(self.run(request).await?.response_or_error()?.order)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{BigDecimal, BuyOrSell, Iceberg, IocWithFallback};
+    use std::str::FromStr;
+    use std::time::Duration;
+
+    #[test]
+    fn cap_price_is_beyond_protective_price() {
+        let mut tactic = IocWithFallback {
+            market: "eth_usdc".to_string(),
+            buy_or_sell: BuyOrSell::Buy,
+            amount: BigDecimal::from_str("1.5").unwrap(),
+            protective_price: BigDecimal::from_str("200.05").unwrap(),
+            max_slippage: BigDecimal::from_str("0.01").unwrap(),
+            fill_timeout: Duration::from_secs(1),
+        };
+        // 202.0505 and 198.0495, rounded towards the protective price
+        assert_eq!(
+            tactic.cap_price(2).unwrap(),
+            BigDecimal::from_str("202.05").unwrap()
+        );
+        tactic.buy_or_sell = BuyOrSell::Sell;
+        assert_eq!(
+            tactic.cap_price(2).unwrap(),
+            BigDecimal::from_str("198.05").unwrap()
+        );
+        assert!(tactic.validate().is_ok());
+        tactic.max_slippage = BigDecimal::from(1);
+        assert!(tactic.validate().is_err());
+    }
+
+    #[test]
+    fn iceberg_slices_never_exceed_display_amount() {
+        let dec = |value: &str| BigDecimal::from_str(value).unwrap();
+        let mut tactic = Iceberg {
+            market: "eth_usdc".to_string(),
+            buy_or_sell: BuyOrSell::Sell,
+            amount: dec("2.5"),
+            display_amount: dec("1"),
+            price: dec("200"),
+            allow_taker: false,
+            slice_timeout: Duration::from_secs(60),
+        };
+        assert!(tactic.validate().is_ok());
+        assert_eq!(tactic.next_slice(&dec("0")), dec("1"));
+        assert_eq!(tactic.next_slice(&dec("1.8")), dec("0.7"));
+        tactic.display_amount = dec("3");
+        assert!(tactic.validate().is_err());
+    }
+
+    #[test]
+    fn idle_slices_back_off_exponentially() {
+        assert_eq!(Iceberg::idle_backoff(0), Duration::from_secs(0));
+        assert_eq!(Iceberg::idle_backoff(1), Duration::from_secs(1));
+        assert_eq!(Iceberg::idle_backoff(3), Duration::from_secs(4));
+    }
+}
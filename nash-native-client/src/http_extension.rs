@@ -1,76 +1,205 @@
 //! Client implementation of Nash API over http
 
 use std::any::type_name;
-use std::time::Duration;
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
 
 use async_recursion::async_recursion;
-use rand::Rng;
-use reqwest::header::AUTHORIZATION;
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue, AUTHORIZATION};
+use tokio::sync::Mutex;
 use tracing::{error, info_span, Instrument};
 
 use nash_protocol::errors::{ProtocolError, Result};
-use nash_protocol::protocol::{NashProtocol, NashProtocolPipeline, ResponseOrError, State};
+use nash_protocol::protocol::{
+    is_persisted_query_not_found, LatencyBudget, NashProtocol, NashProtocolPipeline,
+    ResponseOrError, ResponseTiming, ServerTimingMetric, StageTimings, State, TimedNashProtocol,
+    TimedResponse,
+};
 
+use crate::config::HeadersConfig;
 use crate::types::Environment;
-use crate::ws_client::{Client, InnerClient};
+use crate::ws_client::{warn_if_over_budget, Client, InnerClient};
 
-pub(crate) struct HttpClientState {
+/// Default number of independent HTTP connections kept towards the API
+pub(crate) const HTTP_SHARDS: usize = 4;
+
+tokio::task_local! {
+    /// Headers added to the HTTP requests of the pipeline run by `Client::run_http_with_headers`
+    static REQUEST_HEADERS: Vec<(String, String)>;
+}
+
+/// Settings for the HTTP connection pool
+#[derive(Clone, Debug)]
+pub(crate) struct HttpOptions {
+    pub shards: usize,
+    /// Accept gzip and deflate encoded responses
+    pub compression: bool,
+    /// Sent with every request
+    pub headers: Vec<(String, String)>,
+}
+
+impl Default for HttpOptions {
+    fn default() -> Self {
+        Self {
+            shards: HTTP_SHARDS,
+            compression: true,
+            headers: HeadersConfig::default().to_list(),
+        }
+    }
+}
+
+/// Check and convert header name and value pairs
+pub(crate) fn header_map(headers: &[(String, String)]) -> Result<HeaderMap> {
+    let mut map = HeaderMap::with_capacity(headers.len());
+    for (name, value) in headers {
+        let name = HeaderName::from_bytes(name.as_bytes()).map_err(|_| {
+            ProtocolError::coerce_static_from_str(&format!("Invalid header name {:?}", name))
+        })?;
+        let value = HeaderValue::from_str(value).map_err(|_| {
+            ProtocolError::coerce_static_from_str(&format!("Invalid value for header {}", name))
+        })?;
+        map.insert(name, value);
+    }
+    Ok(map)
+}
+
+/// A single pooled connection. Requests bound to a market hold `order_lock` while in flight
+/// so that e.g. a place followed by a cancel on the same market can't overtake each other
+struct HttpShard {
     client: reqwest::Client,
+    order_lock: Mutex<()>,
+}
+
+pub(crate) struct HttpClientState {
+    shards: Vec<HttpShard>,
+    next_shard: AtomicUsize,
     api_url: String,
     auth_token: Option<String>,
 }
 
+impl HttpClientState {
+    /// Requests on the same market always map to the same shard, anything else is spread round robin
+    fn shard_index(&self, market: Option<&str>) -> usize {
+        match market {
+            Some(market) => {
+                let mut hasher = DefaultHasher::new();
+                market.hash(&mut hasher);
+                (hasher.finish() % self.shards.len() as u64) as usize
+            }
+            None => self.next_shard.fetch_add(1, Ordering::Relaxed) % self.shards.len(),
+        }
+    }
+}
+
 impl InnerClient {
     /// Init internal http client
     pub(crate) async fn setup_http(
         state: &mut State,
         env: Environment,
         timeout: Duration,
+        options: HttpOptions,
     ) -> Result<HttpClientState> {
-        let client = reqwest::Client::builder()
-            .timeout(timeout)
-            .build()
-            .map_err(|_| ProtocolError("Could not initialize reqwest client"))?;
+        let mut shards = Vec::with_capacity(options.shards);
+        let headers = header_map(&options.headers)?;
+        for _ in 0..options.shards.max(1) {
+            // One idle connection per shard, so a shard maps onto a single keep-alive connection
+            let client = reqwest::Client::builder()
+                .timeout(timeout)
+                .pool_max_idle_per_host(1)
+                .gzip(options.compression)
+                .deflate(options.compression)
+                .default_headers(headers.clone())
+                .build()
+                .map_err(|e| ProtocolError::with_source("Could not initialize reqwest client", e))?;
+            shards.push(HttpShard {
+                client,
+                order_lock: Mutex::new(()),
+            });
+        }
         let api_url = format!("https://{}/api/graphql", env.url());
         let auth_token = state
             .signer
             .as_ref()
             .map(|s| format!("Token {}", s.api_keys.session_id));
         Ok(HttpClientState {
-            client,
+            shards,
+            next_shard: AtomicUsize::new(0),
             api_url,
             auth_token,
         })
     }
 
-    /// Execute a serialized NashProtocol request via http
-    async fn request_http(&self, request: &serde_json::Value) -> Result<serde_json::Value> {
+    /// Execute a serialized NashProtocol request via http. Requests with a market affinity
+    /// are sent in order over the shard that market maps to
+    async fn request_http(
+        &self,
+        request: &serde_json::Value,
+        market: Option<&str>,
+    ) -> Result<(serde_json::Value, ResponseTiming)> {
+        let shard = &self.http_state.shards[self.http_state.shard_index(market)];
+        let _order_guard = match market {
+            Some(_) => Some(shard.order_lock.lock().await),
+            None => None,
+        };
         // Do simple request/response...
-        let mut request = self
-            .http_state
-            .client
-            .post(&self.http_state.api_url)
-            .json(request);
+        let started = Instant::now();
+        let result = async {
+            let response = self.send_http(shard, request).await?;
+            let server: Vec<ServerTimingMetric> = response
+                .headers()
+                .get_all("server-timing")
+                .iter()
+                .filter_map(|value| value.to_str().ok())
+                .flat_map(ServerTimingMetric::parse_header)
+                .collect();
+            let json: serde_json::Value = response
+                .json()
+                .await
+                .map_err(|e| ProtocolError::with_source("Could not parse response as JSON", e))?;
+            Ok::<_, ProtocolError>((json, server))
+        }
+        .await;
+        let round_trip = started.elapsed();
+        if self.capture.is_active() {
+            let json = result.as_ref().map(|(json, _)| json.clone()).map_err(Clone::clone);
+            self.capture.record("http", request, &json, round_trip);
+        }
+        let (json, server) = result?;
+        Ok((json, ResponseTiming { round_trip, server }))
+    }
+
+    /// Send a serialized request on a round robin shard without waiting for the response body,
+    /// for responses that are read incrementally
+    pub(crate) async fn send_http_unordered(
+        &self,
+        request: &serde_json::Value,
+    ) -> Result<reqwest::Response> {
+        let shard = &self.http_state.shards[self.http_state.shard_index(None)];
+        self.send_http(shard, request).await
+    }
+
+    async fn send_http(
+        &self,
+        shard: &HttpShard,
+        request: &serde_json::Value,
+    ) -> Result<reqwest::Response> {
+        let mut request = shard.client.post(&self.http_state.api_url).json(request);
         if let Some(auth_token) = &self.http_state.auth_token {
             request = request.header(AUTHORIZATION, auth_token)
         }
-        let response = request.send().await;
-        response
-            .map_err(|e| {
-                if e.is_timeout() {
-                    ProtocolError("Request timeout")
-                } else {
-                    ProtocolError::coerce_static_from_str(&format!("Failed HTTP request: {}", e))
-                }
-            })?
-            .json()
-            .await
-            .map_err(|e| {
-                ProtocolError::coerce_static_from_str(&format!(
-                    "Could not parse response as JSON: {}",
-                    e
-                ))
-            })
+        // headers set on the request take precedence over the client's defaults
+        if let Ok(headers) = REQUEST_HEADERS.try_with(|headers| header_map(headers)) {
+            request = request.headers(headers?);
+        }
+        request.send().await.map_err(|e| {
+            if e.is_timeout() {
+                ProtocolError::with_source("Request timeout", e)
+            } else {
+                ProtocolError::with_source("Failed HTTP request", e)
+            }
+        })
     }
 
     /// Execute a NashProtocol request. Query will be created, executed over network, response will
@@ -79,9 +208,36 @@ impl InnerClient {
     async fn execute_protocol_http<T: NashProtocol + Sync>(
         &self,
         request: T,
-    ) -> Result<ResponseOrError<T::Response>> {
+    ) -> Result<(ResponseOrError<T::Response>, ResponseTiming)> {
         let graphql_request = request.graphql(self.state.clone()).await?;
-        let graphql_response = self.request_http(&graphql_request).await?;
+        self.execute_graphql_http(&request, graphql_request).await
+    }
+
+    /// Submit an already constructed query for `request` via http and run the protocol's response handling.
+    /// The round trip of the timing includes registering a persisted query the server did not know.
+    async fn execute_graphql_http<T: NashProtocol + Sync>(
+        &self,
+        request: &T,
+        graphql_request: serde_json::Value,
+    ) -> Result<(ResponseOrError<T::Response>, ResponseTiming)> {
+        let market = request.market_affinity();
+        self.exchange_throttle.wait().await;
+        let (graphql_response, timing) = match self.persisted_queries.prepare(&graphql_request) {
+            None => self.request_http(&graphql_request, market).await?,
+            Some((body, hash)) => {
+                let (mut response, mut timing) = self.request_http(&body, market).await?;
+                if is_persisted_query_not_found(&response) {
+                    let body = self.persisted_queries.register(&graphql_request, &hash);
+                    let first_round_trip = timing.round_trip;
+                    let (retried, retry_timing) = self.request_http(&body, market).await?;
+                    response = retried;
+                    timing = retry_timing;
+                    timing.round_trip += first_round_trip;
+                }
+                self.persisted_queries.record_response(hash, &response);
+                (response, timing)
+            }
+        };
         let protocol_response = request
             .response_from_json(graphql_response, self.state.clone())
             .await?;
@@ -92,12 +248,13 @@ impl InnerClient {
                     .await?;
             }
             ResponseOrError::Error(ref error_response) => {
+                self.back_off_if_throttled(error_response);
                 request
                     .process_error(error_response, Some(&graphql_request), self.state.clone())
                     .await?;
             }
         }
-        Ok(protocol_response)
+        Ok((protocol_response, timing))
     }
 
     #[async_recursion]
@@ -105,6 +262,16 @@ impl InnerClient {
         &self,
         request: T,
     ) -> Result<ResponseOrError<<T::ActionType as NashProtocol>::Response>> {
+        self.run_http_timed(request)
+            .await
+            .map(|timed| timed.response)
+    }
+
+    /// Same as `run_http`, also returning the timing of the last request the pipeline sent
+    pub async fn run_http_timed<T: NashProtocolPipeline + Clone>(
+        &self,
+        request: T,
+    ) -> Result<TimedResponse<<T::ActionType as NashProtocol>::Response>> {
         async {
             let response = {
                 if let Some(_permit) = request.acquire_permit(self.state.clone()).await {
@@ -114,14 +281,14 @@ impl InnerClient {
                 }
             };
             if let Err(ref e) = response {
-                error!(error = %e, "request error");
+                error!(error = %e.report(), "request error");
             }
             response
         }
         .instrument(info_span!(
                 "RUN (http)",
                 request = type_name::<T>(),
-                id = %rand::thread_rng().gen::<u32>()))
+                id = %self.rng.next_u32()))
         .await
     }
 
@@ -133,22 +300,53 @@ impl InnerClient {
         async {
             let response = self.run_helper_http(request).await;
             if let Err(ref e) = response {
-                error!(error = %e, "request error");
+                error!(error = %e.report(), "request error");
             }
-            response
+            response.map(|timed| timed.response)
         }
         .instrument(info_span!(
                 "RUN (http)",
                 request = type_name::<T>(),
-                id = %rand::thread_rng().gen::<u32>()))
+                id = %self.rng.next_u32()))
         .await
     }
 
-    /// Does the main work of running a pipeline via http
+    /// Run an order request under a latency budget via http. The budget covers payload
+    /// construction, signing and transport; hooks run before the request are not counted.
+    pub async fn run_http_with_latency_budget<T: TimedNashProtocol + Clone>(
+        &self,
+        request: T,
+        budget: LatencyBudget,
+    ) -> Result<(ResponseOrError<T::Response>, StageTimings)> {
+        let _permit = NashProtocol::acquire_permit(&request, self.state.clone()).await;
+        if let Some(actions) = NashProtocol::run_before(&request, self.state.clone()).await? {
+            for action in actions {
+                self.run_http(action).await?;
+            }
+        }
+        let (graphql_request, mut timings) = request.graphql_timed(self.state.clone()).await?;
+        budget.check_before_submit(&timings)?;
+        let started = Instant::now();
+        let (response, _) = self.execute_graphql_http(&request, graphql_request).await?;
+        timings.transport = started.elapsed();
+        warn_if_over_budget::<T>(&budget, &timings);
+        if let Some(error) = response.error() {
+            Self::manage_client_error(self.state.clone(), error).await;
+        }
+        if let Some(actions) = NashProtocol::run_after(&request, self.state.clone()).await? {
+            for action in actions {
+                self.run_http(action).await?;
+            }
+        }
+        Ok((response, timings))
+    }
+
+    /// Does the main work of running a pipeline via http. Only the pipeline's own requests
+    /// are timed, not those of its hooks.
     async fn run_helper_http<T: NashProtocolPipeline + Clone>(
         &self,
         request: T,
-    ) -> Result<ResponseOrError<<T::ActionType as NashProtocol>::Response>> {
+    ) -> Result<TimedResponse<<T::ActionType as NashProtocol>::Response>> {
         // First run any dependencies of the request/pipeline
         let before_actions = request.run_before(self.state.clone()).await?;
         if let Some(actions) = before_actions {
@@ -158,13 +356,16 @@ impl InnerClient {
         }
         // Now run the pipeline
         let mut protocol_state = request.init_state(self.state.clone()).await;
+        let mut timing = None;
         // While pipeline contains more actions for client to take, execute them
         loop {
             if let Some(protocol_request) = request
                 .next_step(&protocol_state, self.state.clone())
                 .await?
             {
-                let protocol_response = self.execute_protocol_http(protocol_request).await?;
+                let (protocol_response, step_timing) =
+                    self.execute_protocol_http(protocol_request).await?;
+                timing = Some(step_timing);
                 // If error, end pipeline early and return GraphQL/network error data
                 if protocol_response.is_error() {
                     Self::manage_client_error(
@@ -173,11 +374,14 @@ impl InnerClient {
                     )
                     .await;
 
-                    return Ok(ResponseOrError::Error(
-                        protocol_response
-                            .consume_error()
-                            .expect("Destructure error after check. Impossible to fail."),
-                    ));
+                    return Ok(TimedResponse {
+                        response: ResponseOrError::Error(
+                            protocol_response
+                                .consume_error()
+                                .expect("Destructure error after check. Impossible to fail."),
+                        ),
+                        timing,
+                    });
                 }
                 // Otherwise update the pipeline and continue
                 request
@@ -202,7 +406,10 @@ impl InnerClient {
             }
         }
         // Return the pipeline output
-        request.output(protocol_state)
+        Ok(TimedResponse {
+            response: request.output(protocol_state)?,
+            timing,
+        })
     }
 }
 
@@ -216,6 +423,79 @@ impl Client {
         &self,
         request: T,
     ) -> Result<ResponseOrError<<T::ActionType as NashProtocol>::Response>> {
-        self.inner.run_http(request).await
+        self.inner.check_price_guard(request.limit_prices()).await?;
+        let approved = self
+            .inner
+            .check_approval(request.limit_prices(), request.limit_sizes())?;
+        let response = self.inner.run_http(request).await;
+        let submitted = matches!(&response, Ok(response) if !response.is_error());
+        self.inner.consume_approvals(&approved, submitted);
+        response
+    }
+
+    /// Same as `run_http`, also returning how long the exchange round trip took and the
+    /// `Server-Timing` metrics the exchange reported for it, to tell exchange side latency
+    /// apart from network latency
+    pub async fn run_http_timed<T: NashProtocolPipeline + Clone>(
+        &self,
+        request: T,
+    ) -> Result<TimedResponse<<T::ActionType as NashProtocol>::Response>> {
+        self.inner.check_price_guard(request.limit_prices()).await?;
+        let approved = self
+            .inner
+            .check_approval(request.limit_prices(), request.limit_sizes())?;
+        let response = self.inner.run_http_timed(request).await;
+        let submitted = matches!(&response, Ok(timed) if !timed.response.is_error());
+        self.inner.consume_approvals(&approved, submitted);
+        response
+    }
+
+    /// Same as `run_http`, sending `headers` in addition to the configured ones with every
+    /// HTTP request the pipeline makes. A header configured for the client is replaced.
+    pub async fn run_http_with_headers<T: NashProtocolPipeline + Clone>(
+        &self,
+        request: T,
+        headers: Vec<(String, String)>,
+    ) -> Result<ResponseOrError<<T::ActionType as NashProtocol>::Response>> {
+        header_map(&headers)?;
+        REQUEST_HEADERS.scope(headers, self.run_http(request)).await
+    }
+
+    /// Same as `run_http`, but skips the price guard
+    #[inline]
+    pub async fn run_http_unguarded<T: NashProtocolPipeline + Clone>(
+        &self,
+        request: T,
+    ) -> Result<ResponseOrError<<T::ActionType as NashProtocol>::Response>> {
+        let approved = self
+            .inner
+            .check_approval(request.limit_prices(), request.limit_sizes())?;
+        let response = self.inner.run_http(request).await;
+        let submitted = matches!(&response, Ok(response) if !response.is_error());
+        self.inner.consume_approvals(&approved, submitted);
+        response
+    }
+    /// Run an order request via HTTP, recording construction, signing and transport timings.
+    /// Logs a warning when `budget` is exceeded, or aborts before submission if the budget asks for it
+    #[inline]
+    pub async fn run_http_with_latency_budget<T: TimedNashProtocol + Clone>(
+        &self,
+        request: T,
+        budget: LatencyBudget,
+    ) -> Result<(ResponseOrError<T::Response>, StageTimings)> {
+        self.inner
+            .check_price_guard(NashProtocol::limit_prices(&request))
+            .await?;
+        let approved = self.inner.check_approval(
+            NashProtocol::limit_prices(&request),
+            NashProtocol::limit_sizes(&request),
+        )?;
+        let response = self
+            .inner
+            .run_http_with_latency_budget(request, budget)
+            .await;
+        let submitted = matches!(&response, Ok((response, _)) if !response.is_error());
+        self.inner.consume_approvals(&approved, submitted);
+        response
     }
 }
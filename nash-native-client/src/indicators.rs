@@ -0,0 +1,315 @@
+//! Indicators for signal code, updated incrementally in O(1) from the candles of
+//! `Client::subscribe_candles` or from trades. Prices stay `BigDecimal`, kept to `SCALE`
+//! decimal places so they don't grow with every update; volatility needs logarithms and is an
+//! `f64`.
+
+use std::collections::VecDeque;
+use std::time::Duration;
+
+use bigdecimal::{BigDecimal, Signed, ToPrimitive, Zero};
+
+use nash_protocol::errors::{ProtocolError, Result};
+use nash_protocol::types::timestamp::unix_millis;
+use nash_protocol::types::Trade;
+
+use crate::candles::Ohlcv;
+
+/// Decimal places indicator values are truncated to
+const SCALE: i64 = 18;
+
+fn check_period(period: usize) -> Result<()> {
+    if period == 0 {
+        return Err(ProtocolError("Indicator period must be at least 1"));
+    }
+    Ok(())
+}
+
+/// Exponential moving average with smoothing `2 / (period + 1)`, starting at the first value
+#[derive(Clone, Debug)]
+pub struct Ema {
+    alpha: BigDecimal,
+    value: Option<BigDecimal>,
+}
+
+impl Ema {
+    pub fn new(period: usize) -> Result<Self> {
+        check_period(period)?;
+        Ok(Self {
+            alpha: (BigDecimal::from(2) / BigDecimal::from(period as u64 + 1)).with_scale(SCALE),
+            value: None,
+        })
+    }
+
+    pub fn update(&mut self, value: &BigDecimal) -> &BigDecimal {
+        let next = match self.value.take() {
+            Some(previous) => (&previous + &self.alpha * (value - &previous)).with_scale(SCALE),
+            None => value.clone(),
+        };
+        self.value.get_or_insert(next)
+    }
+
+    pub fn value(&self) -> Option<&BigDecimal> {
+        self.value.as_ref()
+    }
+}
+
+/// Average true range with Wilder's smoothing, seeded with the mean of the first `period`
+/// true ranges
+#[derive(Clone, Debug)]
+pub struct Atr {
+    period: usize,
+    previous_close: Option<BigDecimal>,
+    seen: usize,
+    value: Option<BigDecimal>,
+}
+
+impl Atr {
+    pub fn new(period: usize) -> Result<Self> {
+        check_period(period)?;
+        Ok(Self {
+            period,
+            previous_close: None,
+            seen: 0,
+            value: None,
+        })
+    }
+
+    /// Add a closed candle; the ATR is known once `period` candles were added
+    pub fn update(&mut self, candle: &Ohlcv) -> Option<&BigDecimal> {
+        let mut true_range = &candle.high - &candle.low;
+        if let Some(close) = self.previous_close.replace(candle.close.clone()) {
+            for range in &[(&candle.high - &close).abs(), (&candle.low - &close).abs()] {
+                if range > &true_range {
+                    true_range = range.clone();
+                }
+            }
+        }
+        let period = BigDecimal::from(self.period as u64);
+        self.seen += 1;
+        let next = match self.value.take() {
+            // sum of the true ranges until the period is complete
+            Some(sum) if self.seen <= self.period => sum + true_range,
+            Some(atr) => (atr * (&period - BigDecimal::from(1)) + true_range) / &period,
+            None => true_range,
+        };
+        let next = if self.seen == self.period {
+            next / &period
+        } else {
+            next
+        };
+        self.value = Some(next.with_scale(SCALE));
+        self.value()
+    }
+
+    pub fn value(&self) -> Option<&BigDecimal> {
+        self.value.as_ref().filter(|_| self.seen >= self.period)
+    }
+}
+
+/// Standard deviation of the log returns between the last `window + 1` closes, per candle.
+/// Multiply by the square root of the candles per year to annualize it.
+#[derive(Clone, Debug)]
+pub struct RealizedVolatility {
+    window: usize,
+    previous: Option<f64>,
+    returns: VecDeque<f64>,
+    sum: f64,
+    sum_of_squares: f64,
+}
+
+impl RealizedVolatility {
+    pub fn new(window: usize) -> Result<Self> {
+        if window < 2 {
+            return Err(ProtocolError(
+                "Volatility needs a window of at least 2 returns",
+            ));
+        }
+        Ok(Self {
+            window,
+            previous: None,
+            returns: VecDeque::with_capacity(window + 1),
+            sum: 0.0,
+            sum_of_squares: 0.0,
+        })
+    }
+
+    /// Add a close; prices that aren't positive are ignored
+    pub fn update(&mut self, close: &BigDecimal) -> Option<f64> {
+        let close = close.to_f64().filter(|close| *close > 0.0)?;
+        if let Some(previous) = self.previous.replace(close) {
+            let log_return = (close / previous).ln();
+            self.returns.push_back(log_return);
+            self.sum += log_return;
+            self.sum_of_squares += log_return * log_return;
+            if self.returns.len() > self.window {
+                let dropped = self.returns.pop_front().unwrap_or_default();
+                self.sum -= dropped;
+                self.sum_of_squares -= dropped * dropped;
+            }
+        }
+        self.value()
+    }
+
+    pub fn value(&self) -> Option<f64> {
+        if self.returns.len() < self.window {
+            return None;
+        }
+        let n = self.window as f64;
+        let variance = (self.sum_of_squares - self.sum * self.sum / n) / (n - 1.0);
+        // running sums can leave a tiny negative variance for constant prices
+        Some(variance.max(0.0).sqrt())
+    }
+}
+
+/// Volume weighted average price over a sliding time window, from trades or candles
+#[derive(Clone, Debug)]
+pub struct RollingVwap {
+    window_ms: i64,
+    /// Time in ms, A amount and B amount of what is in the window
+    entries: VecDeque<(i64, BigDecimal, BigDecimal)>,
+    /// Time of the newest entry, so entries arriving out of order don't evict others
+    newest_ms: i64,
+    a_volume: BigDecimal,
+    b_volume: BigDecimal,
+}
+
+impl RollingVwap {
+    pub fn new(window: Duration) -> Result<Self> {
+        if window.as_millis() == 0 {
+            return Err(ProtocolError("VWAP window must be at least a millisecond"));
+        }
+        Ok(Self {
+            window_ms: window.as_millis() as i64,
+            entries: VecDeque::new(),
+            newest_ms: i64::MIN,
+            a_volume: BigDecimal::zero(),
+            b_volume: BigDecimal::zero(),
+        })
+    }
+
+    pub fn update_trade(&mut self, trade: &Trade) -> Option<BigDecimal> {
+        let b_amount = &trade.amount * &trade.limit_price;
+        self.add(
+            unix_millis(&trade.executed_at),
+            trade.amount.clone(),
+            b_amount,
+        )
+    }
+
+    /// Add a closed candle, counted at its end
+    pub fn update_candle(&mut self, candle: &Ohlcv) -> Option<BigDecimal> {
+        self.add(
+            candle.end_ms(),
+            candle.a_volume.clone(),
+            candle.b_volume.clone(),
+        )
+    }
+
+    fn add(
+        &mut self,
+        at_ms: i64,
+        a_amount: BigDecimal,
+        b_amount: BigDecimal,
+    ) -> Option<BigDecimal> {
+        self.a_volume += &a_amount;
+        self.b_volume += &b_amount;
+        self.entries.push_back((at_ms, a_amount, b_amount));
+        self.newest_ms = self.newest_ms.max(at_ms);
+        while let Some((at, ..)) = self.entries.front() {
+            if *at > self.newest_ms - self.window_ms {
+                break;
+            }
+            if let Some((_, a_amount, b_amount)) = self.entries.pop_front() {
+                self.a_volume -= a_amount;
+                self.b_volume -= b_amount;
+            }
+        }
+        self.value()
+    }
+
+    /// VWAP of the window, if anything traded in it
+    pub fn value(&self) -> Option<BigDecimal> {
+        if self.a_volume.is_zero() {
+            return None;
+        }
+        Some((&self.b_volume / &self.a_volume).with_scale(SCALE))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Atr, Ema, RealizedVolatility, RollingVwap};
+    use crate::candles::CandleAggregator;
+    use bigdecimal::BigDecimal;
+    use nash_protocol::types::timestamp::parse_timestamp;
+    use nash_protocol::types::{AccountTradeSide, BuyOrSell, Trade};
+    use std::str::FromStr;
+    use std::time::Duration;
+
+    fn dec(value: &str) -> BigDecimal {
+        BigDecimal::from_str(value).unwrap()
+    }
+
+    fn trade(second: u32, price: &str, amount: &str) -> Trade {
+        Trade {
+            id: second.to_string(),
+            taker_order_id: "taker".to_string(),
+            maker_order_id: "maker".to_string(),
+            amount: dec(amount),
+            executed_at: parse_timestamp(&format!("1970-01-01T00:00:{:02}Z", second)).unwrap(),
+            account_side: AccountTradeSide::None,
+            maker_fee: dec("0"),
+            taker_fee: dec("0"),
+            maker_recieved: dec("0"),
+            taker_recieved: dec("0"),
+            market: "eth_usdc".to_string(),
+            direction: BuyOrSell::Buy,
+            limit_price: dec(price),
+        }
+    }
+
+    #[test]
+    fn indicators_update_incrementally() {
+        let mut ema = Ema::new(3).unwrap();
+        assert_eq!(ema.update(&dec("10")), &dec("10"));
+        assert_eq!(ema.update(&dec("20")), &dec("15"));
+        assert_eq!(ema.update(&dec("15")), &dec("15"));
+
+        // one candle per second: 10-12 close 11, 11-15 close 14, 13-14 close 13
+        let mut candles = CandleAggregator::new("eth_usdc", Duration::from_secs(1), false).unwrap();
+        let trades = [
+            (0, "10"),
+            (0, "12"),
+            (0, "11"),
+            (1, "11"),
+            (1, "15"),
+            (1, "14"),
+            (2, "13"),
+            (2, "14"),
+            (2, "13"),
+            (3, "13"),
+        ];
+        let closed: Vec<_> = trades
+            .iter()
+            .flat_map(|(second, price)| candles.push(&trade(*second, price, "1")))
+            .collect();
+        let mut atr = Atr::new(2).unwrap();
+        assert_eq!(atr.update(&closed[0]), None);
+        // true ranges 2 and 4, then 1 after the seed of 3
+        assert_eq!(atr.update(&closed[1]), Some(&dec("3")));
+        assert_eq!(atr.update(&closed[2]), Some(&dec("2")));
+
+        let mut volatility = RealizedVolatility::new(2).unwrap();
+        assert_eq!(volatility.update(&dec("100")), None);
+        assert_eq!(volatility.update(&dec("100")), None);
+        assert_eq!(volatility.update(&dec("100")), Some(0.0));
+        let moved = volatility.update(&dec("110")).unwrap();
+        assert!((moved - (1.1f64).ln() / 2f64.sqrt()).abs() < 1e-12);
+
+        let mut vwap = RollingVwap::new(Duration::from_secs(2)).unwrap();
+        assert_eq!(vwap.update_trade(&trade(0, "10", "1")), Some(dec("10")));
+        assert_eq!(vwap.update_trade(&trade(1, "13", "2")), Some(dec("12")));
+        // the first trade is out of the window
+        assert_eq!(vwap.update_trade(&trade(2, "16", "2")), Some(dec("14.5")));
+    }
+}
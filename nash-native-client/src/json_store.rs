@@ -0,0 +1,154 @@
+//! Directory of JSON files, one per entry, for what a client has to pick up again after a
+//! restart: scheduled orders, trailing stops and queued cancellations
+
+use std::fmt;
+use std::marker::PhantomData;
+use std::path::PathBuf;
+use std::sync::RwLock as SyncRwLock;
+
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use tracing::warn;
+
+use nash_protocol::errors::{ProtocolError, Result};
+
+/// Something kept in a `JsonStore`
+pub(crate) trait Stored: Serialize + DeserializeOwned {
+    /// What an entry is, for messages, e.g. "scheduled order"
+    const KIND: &'static str;
+
+    /// Name of the entry's file, without extension
+    fn id(&self) -> &str;
+}
+
+/// Entries stored as `<id>.json` in a directory. Until a directory is set nothing is stored.
+pub(crate) struct JsonStore<T> {
+    dir: SyncRwLock<Option<PathBuf>>,
+    entries: PhantomData<fn() -> T>,
+}
+
+impl<T> Default for JsonStore<T> {
+    fn default() -> Self {
+        Self {
+            dir: SyncRwLock::new(None),
+            entries: PhantomData,
+        }
+    }
+}
+
+impl<T> fmt::Debug for JsonStore<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("JsonStore")
+            .field("dir", &*self.dir.read().unwrap())
+            .finish()
+    }
+}
+
+impl<T: Stored> JsonStore<T> {
+    /// Store entries in `dir` from now on, creating it if needed, and return the entries
+    /// found there. Unreadable files are skipped.
+    pub(crate) fn open(&self, dir: PathBuf) -> Result<Vec<T>> {
+        let read_error = |e: std::io::Error| {
+            ProtocolError::with_source(
+                format!("Could not read {} store {}", T::KIND, dir.display()),
+                e,
+            )
+        };
+        std::fs::create_dir_all(&dir).map_err(read_error)?;
+        let mut entries = Vec::new();
+        for file in std::fs::read_dir(&dir).map_err(read_error)?.flatten() {
+            match std::fs::read_to_string(file.path())
+                .ok()
+                .and_then(|contents| serde_json::from_str::<T>(&contents).ok())
+            {
+                Some(entry) => entries.push(entry),
+                None => {
+                    warn!(path = %file.path().display(), kind = T::KIND, "ignoring unreadable entry")
+                }
+            }
+        }
+        *self.dir.write().unwrap() = Some(dir);
+        Ok(entries)
+    }
+
+    /// Write `entry`, replacing what was stored under its id
+    pub(crate) fn persist(&self, entry: &T) -> Result<()> {
+        let dir = match self.dir.read().unwrap().clone() {
+            Some(dir) => dir,
+            None => return Ok(()),
+        };
+        let contents = serde_json::to_string_pretty(entry).map_err(|e| {
+            ProtocolError::with_source(format!("Could not serialize {}", T::KIND), e)
+        })?;
+        std::fs::write(dir.join(format!("{}.json", entry.id())), contents)
+            .map_err(|e| ProtocolError::with_source(format!("Could not store {}", T::KIND), e))
+    }
+
+    /// Delete the entry stored under `id`
+    pub(crate) fn remove(&self, id: &str) {
+        if let Some(dir) = self.dir.read().unwrap().as_ref() {
+            let path = dir.join(format!("{}.json", id));
+            if let Err(e) = std::fs::remove_file(&path) {
+                warn!(path = %path.display(), kind = T::KIND, error = %e, "could not remove entry");
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{JsonStore, Stored};
+    use serde::{Deserialize, Serialize};
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Entry {
+        id: String,
+        value: u32,
+    }
+
+    impl Stored for Entry {
+        const KIND: &'static str = "entry";
+
+        fn id(&self) -> &str {
+            &self.id
+        }
+    }
+
+    #[test]
+    fn entries_survive_reopening() {
+        let dir = std::env::temp_dir().join(format!("nash-json-store-{}", std::process::id()));
+        let store = JsonStore::<Entry>::default();
+        // nothing is written before a directory is set
+        store
+            .persist(&Entry {
+                id: "early".to_string(),
+                value: 0,
+            })
+            .unwrap();
+        assert!(store.open(dir.clone()).unwrap().is_empty());
+        store
+            .persist(&Entry {
+                id: "kept".to_string(),
+                value: 1,
+            })
+            .unwrap();
+        store
+            .persist(&Entry {
+                id: "removed".to_string(),
+                value: 2,
+            })
+            .unwrap();
+        store.remove("removed");
+        std::fs::write(dir.join("garbage.json"), "{").unwrap();
+
+        let reopened = JsonStore::<Entry>::default();
+        assert_eq!(
+            reopened.open(dir.clone()).unwrap(),
+            vec![Entry {
+                id: "kept".to_string(),
+                value: 1
+            }]
+        );
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
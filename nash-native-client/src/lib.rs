@@ -1,6 +1,47 @@
+pub use builder::ClientBuilder;
+pub use config::ClientConfig;
+pub use exchange_traits;
+pub use tokio_util::sync::CancellationToken;
 pub use types::Environment;
-pub use ws_client::Client;
+pub use ws_client::{
+    Client, ConnectionEvent, MarketEvent, MarketSubscriptionHandle, SubscriptionHandle, Watchlist,
+    WatchlistChannels, WatchlistEvent,
+};
 
+pub mod account_orders;
+pub mod algos;
+pub mod balance_alerts;
+pub mod batch;
+mod builder;
+pub mod candles;
+pub mod capture;
+pub mod conditional;
+pub mod config;
+pub mod cursor;
+pub mod execution;
 pub mod http_extension;
+pub mod indicators;
+mod json_store;
+pub mod liquidity;
+pub mod orderbook;
+pub mod outbox;
+pub mod pagination;
+pub mod paper;
+pub mod prelude;
+pub mod prepared;
+mod quoting;
+mod random;
+pub mod rebalance;
+pub mod reconcile;
+pub mod risk;
+pub mod schedule;
+pub mod statement;
+pub mod streaming;
+pub mod sweep;
+pub mod tags;
+pub mod trade_stream;
+pub mod trailing;
 mod types;
+mod venue;
+pub mod withdraw;
 mod ws_client;
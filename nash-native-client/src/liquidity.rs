@@ -0,0 +1,480 @@
+//! Reporting for market maker incentive and liquidity programs. Programs score makers on
+//! maker volume, on the share of time they quoted both sides of a market, and on how tight
+//! those quotes were. Maker volume comes from the account's trades; the exchange keeps no
+//! history of quotes, so quoted time and spreads are measured locally by a `QuoteTracker`
+//! fed with the account's open orders while the strategy runs.
+
+use std::collections::{BTreeMap, HashMap};
+use std::convert::TryFrom;
+use std::time::{Duration, Instant};
+
+use bigdecimal::{BigDecimal, ToPrimitive, Zero};
+use futures::StreamExt;
+use serde::{Deserialize, Serialize};
+
+use nash_protocol::errors::{ProtocolError, Result};
+use nash_protocol::protocol::list_account_trades::ListAccountTradesRequest;
+use nash_protocol::types::timestamp::format_timestamp;
+use nash_protocol::types::{AccountTradeSide, BuyOrSell, DateTimeRange, Fill, OrderType};
+
+use crate::account_orders::OpenOrders;
+use crate::Client;
+
+/// Best own prices on each side of a market
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct OwnQuote {
+    pub best_bid: Option<BigDecimal>,
+    pub best_ask: Option<BigDecimal>,
+}
+
+impl OwnQuote {
+    /// Best bid and ask of the open limit orders in `market`. Stop orders aren't on the book
+    /// and don't count.
+    pub fn of_orders(orders: &OpenOrders, market: &str) -> Self {
+        let mut quote = Self::default();
+        let limit_orders = orders
+            .orders()
+            .filter(|order| order.market == market && order.order_type == OrderType::Limit);
+        for order in limit_orders {
+            let price = match &order.limit_price {
+                Some(price) => price,
+                None => continue,
+            };
+            let best = match order.buy_or_sell {
+                BuyOrSell::Buy => &mut quote.best_bid,
+                BuyOrSell::Sell => &mut quote.best_ask,
+            };
+            let better = match (order.buy_or_sell, best.as_ref()) {
+                (_, None) => true,
+                (BuyOrSell::Buy, Some(best)) => price > best,
+                (BuyOrSell::Sell, Some(best)) => price < best,
+            };
+            if better {
+                *best = Some(price.clone());
+            }
+        }
+        quote
+    }
+
+    /// Spread relative to the mid price, e.g. 0.002 for 20 bps, if both sides are quoted
+    pub fn spread(&self) -> Option<BigDecimal> {
+        let (bid, ask) = (self.best_bid.as_ref()?, self.best_ask.as_ref()?);
+        let mid = (bid + ask) / BigDecimal::from(2);
+        if mid.is_zero() {
+            return None;
+        }
+        Some((ask - bid) / mid)
+    }
+}
+
+/// Quoting of one market measured so far
+#[derive(Clone, Debug, Default)]
+struct MarketQuoting {
+    /// Quote in force since the last observation
+    last: Option<(Instant, OwnQuote)>,
+    observed: Duration,
+    /// Time both sides were quoted
+    two_sided: Duration,
+    /// Time both sides were quoted within the tracker's maximum spread
+    qualifying: Duration,
+    /// Sum of spread times seconds it was quoted, for the time weighted average
+    weighted_spread: f64,
+    min_spread: Option<BigDecimal>,
+    max_spread: Option<BigDecimal>,
+}
+
+impl MarketQuoting {
+    fn observe(&mut self, quote: OwnQuote, at: Instant, max_spread: Option<&BigDecimal>) {
+        if let Some((since, previous)) = self.last.take() {
+            let held = at.saturating_duration_since(since);
+            self.observed += held;
+            if let Some(spread) = previous.spread() {
+                self.two_sided += held;
+                if max_spread.map_or(true, |max| &spread <= max) {
+                    self.qualifying += held;
+                }
+                self.weighted_spread += spread.to_f64().unwrap_or(0.0) * held.as_secs_f64();
+            }
+        }
+        if let Some(spread) = quote.spread() {
+            if self.min_spread.as_ref().map_or(true, |min| &spread < min) {
+                self.min_spread = Some(spread.clone());
+            }
+            if self.max_spread.as_ref().map_or(true, |max| &spread > max) {
+                self.max_spread = Some(spread);
+            }
+        }
+        self.last = Some((at, quote));
+    }
+}
+
+/// Measures how long and how tightly the account quoted each of a set of markets. Record
+/// the open orders whenever they change, e.g. on every `AccountOrderChange`; each record
+/// counts for the time until the next one.
+#[derive(Clone, Debug)]
+pub struct QuoteTracker {
+    /// Widest relative spread a program still rewards, if it sets one
+    max_spread: Option<BigDecimal>,
+    markets: BTreeMap<String, MarketQuoting>,
+}
+
+impl QuoteTracker {
+    pub fn new<S: Into<String>>(markets: impl IntoIterator<Item = S>) -> Self {
+        Self {
+            max_spread: None,
+            markets: markets
+                .into_iter()
+                .map(|market| (market.into(), MarketQuoting::default()))
+                .collect(),
+        }
+    }
+
+    /// Only count two-sided quotes at most `max_spread` wide (relative to mid) as qualifying
+    pub fn with_max_spread(mut self, max_spread: BigDecimal) -> Self {
+        self.max_spread = Some(max_spread);
+        self
+    }
+
+    /// Record the account's own quote in every tracked market from its open orders
+    pub fn record_orders(&mut self, orders: &OpenOrders) {
+        let now = Instant::now();
+        let markets: Vec<String> = self.markets.keys().cloned().collect();
+        for market in markets {
+            let quote = OwnQuote::of_orders(orders, &market);
+            self.record_at(&market, quote, now);
+        }
+    }
+
+    /// Record the account's own quote in `market`. Markets that aren't tracked are ignored.
+    pub fn record(&mut self, market: &str, quote: OwnQuote) {
+        self.record_at(market, quote, Instant::now());
+    }
+
+    fn record_at(&mut self, market: &str, quote: OwnQuote, at: Instant) {
+        let max_spread = self.max_spread.as_ref();
+        if let Some(quoting) = self.markets.get_mut(market) {
+            quoting.observe(quote, at, max_spread);
+        }
+    }
+
+    /// Quoting statistics per market, up to now
+    pub fn stats(&self) -> BTreeMap<String, QuoteStats> {
+        self.stats_at(Instant::now())
+    }
+
+    fn stats_at(&self, now: Instant) -> BTreeMap<String, QuoteStats> {
+        let max_spread = self.max_spread.as_ref();
+        self.markets
+            .iter()
+            .map(|(market, quoting)| {
+                // the last quote holds until now
+                let mut quoting = quoting.clone();
+                if let Some((_, quote)) = quoting.last.clone() {
+                    quoting.observe(quote, now, max_spread);
+                }
+                (market.clone(), QuoteStats::of(&quoting))
+            })
+            .collect()
+    }
+}
+
+/// How a market was quoted while it was tracked. Decimals are kept as strings, as in
+/// account statements.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct QuoteStats {
+    pub observed_seconds: f64,
+    pub two_sided_seconds: f64,
+    pub qualifying_seconds: f64,
+    /// Share of the observed time the market was quoted on both sides within the max spread
+    pub uptime: f64,
+    /// Time weighted average of the relative spread while quoted on both sides
+    pub average_spread: Option<f64>,
+    pub min_spread: Option<String>,
+    pub max_spread: Option<String>,
+}
+
+impl QuoteStats {
+    fn of(quoting: &MarketQuoting) -> Self {
+        let observed = quoting.observed.as_secs_f64();
+        let two_sided = quoting.two_sided.as_secs_f64();
+        let qualifying = quoting.qualifying.as_secs_f64();
+        Self {
+            observed_seconds: observed,
+            two_sided_seconds: two_sided,
+            qualifying_seconds: qualifying,
+            uptime: if observed > 0.0 {
+                qualifying / observed
+            } else {
+                0.0
+            },
+            average_spread: if two_sided > 0.0 {
+                Some(quoting.weighted_spread / two_sided)
+            } else {
+                None
+            },
+            min_spread: quoting.min_spread.as_ref().map(|spread| spread.to_string()),
+            max_spread: quoting.max_spread.as_ref().map(|spread| spread.to_string()),
+        }
+    }
+}
+
+/// Liquidity program metrics of one market over a period
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct MarketLiquidity {
+    pub market: String,
+    /// Amount of the market's A asset traded as maker
+    pub maker_volume: String,
+    /// Maker volume in the B asset, at the prices it traded at
+    pub maker_notional: String,
+    pub maker_fills: usize,
+    pub taker_volume: String,
+    #[serde(flatten)]
+    pub quoting: QuoteStats,
+}
+
+/// Liquidity program report of the account, from `Client::liquidity_report`
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct LiquidityReport {
+    pub period_start: String,
+    pub period_end: String,
+    /// Sorted by market
+    pub markets: Vec<MarketLiquidity>,
+}
+
+/// Columns of `LiquidityReport::to_csv`
+const CSV_HEADER: &str = "market,maker_volume,maker_notional,maker_fills,taker_volume,\
+observed_seconds,two_sided_seconds,qualifying_seconds,uptime,average_spread,min_spread,max_spread";
+
+impl LiquidityReport {
+    /// Report on `fills` of the period and the quoting `quotes` measured
+    pub fn new(
+        period: &DateTimeRange,
+        fills: &[Fill],
+        quotes: BTreeMap<String, QuoteStats>,
+    ) -> Self {
+        #[derive(Default)]
+        struct Volumes {
+            maker: BigDecimal,
+            maker_notional: BigDecimal,
+            maker_fills: usize,
+            taker: BigDecimal,
+        }
+        let mut volumes: HashMap<&str, Volumes> = HashMap::new();
+        for fill in fills {
+            let market = volumes.entry(fill.market.as_str()).or_default();
+            match fill.liquidity {
+                AccountTradeSide::Maker => {
+                    market.maker += &fill.amount;
+                    market.maker_notional += &fill.amount * &fill.price;
+                    market.maker_fills += 1;
+                }
+                AccountTradeSide::Taker => market.taker += &fill.amount,
+                AccountTradeSide::None => {}
+            }
+        }
+        let mut names: Vec<String> = volumes.keys().map(|market| market.to_string()).collect();
+        names.extend(quotes.keys().cloned());
+        names.sort();
+        names.dedup();
+        let no_volume = Volumes::default();
+        let markets = names
+            .into_iter()
+            .map(|market| {
+                let volumes = volumes.get(market.as_str()).unwrap_or(&no_volume);
+                MarketLiquidity {
+                    maker_volume: volumes.maker.to_string(),
+                    maker_notional: volumes.maker_notional.to_string(),
+                    maker_fills: volumes.maker_fills,
+                    taker_volume: volumes.taker.to_string(),
+                    quoting: quotes.get(&market).cloned().unwrap_or_default(),
+                    market,
+                }
+            })
+            .collect();
+        Self {
+            period_start: format_timestamp(&period.start),
+            period_end: format_timestamp(&period.stop),
+            markets,
+        }
+    }
+
+    pub fn to_json(&self) -> Result<String> {
+        serde_json::to_string_pretty(self)
+            .map_err(|e| ProtocolError::with_source("Could not serialize liquidity report", e))
+    }
+
+    /// One row per market, with a header row. Missing values are empty.
+    pub fn to_csv(&self) -> String {
+        let optional = |value: &Option<String>| value.clone().unwrap_or_default();
+        let mut csv = String::from(CSV_HEADER);
+        csv.push('\n');
+        for entry in &self.markets {
+            let quoting = &entry.quoting;
+            let row = [
+                entry.market.clone(),
+                entry.maker_volume.clone(),
+                entry.maker_notional.clone(),
+                entry.maker_fills.to_string(),
+                entry.taker_volume.clone(),
+                quoting.observed_seconds.to_string(),
+                quoting.two_sided_seconds.to_string(),
+                quoting.qualifying_seconds.to_string(),
+                quoting.uptime.to_string(),
+                quoting
+                    .average_spread
+                    .map_or_else(String::new, |spread| spread.to_string()),
+                optional(&quoting.min_spread),
+                optional(&quoting.max_spread),
+            ];
+            csv.push_str(&row.join(","));
+            csv.push('\n');
+        }
+        csv
+    }
+}
+
+impl Client {
+    /// Liquidity program metrics of the connected account over `period`: maker and taker
+    /// volume from the account's trades, and quoting as measured by `quotes`. The tracker
+    /// should have run over the same period; its figures are taken as they are now.
+    pub async fn liquidity_report(
+        &self,
+        period: DateTimeRange,
+        quotes: &QuoteTracker,
+    ) -> Result<LiquidityReport> {
+        let mut trades = self
+            .pages(ListAccountTradesRequest {
+                market: None,
+                before: None,
+                limit: None,
+                range: Some(period),
+            })
+            .items();
+        let mut fills = Vec::new();
+        while let Some(trade) = trades.next().await {
+            let trade = trade?;
+            if trade.account_side != AccountTradeSide::None {
+                fills.push(Fill::try_from(&trade)?);
+            }
+        }
+        Ok(LiquidityReport::new(&period, &fills, quotes.stats()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{LiquidityReport, OwnQuote, QuoteStats, QuoteTracker, CSV_HEADER};
+    use bigdecimal::BigDecimal;
+    use nash_protocol::types::timestamp::{format_timestamp, now, parse_timestamp};
+    use nash_protocol::types::{AccountTradeSide, BuyOrSell, DateTimeRange, Fill};
+    use std::collections::BTreeMap;
+    use std::str::FromStr;
+    use std::time::{Duration, Instant};
+
+    fn quote(bid: Option<&str>, ask: Option<&str>) -> OwnQuote {
+        OwnQuote {
+            best_bid: bid.map(|price| BigDecimal::from_str(price).unwrap()),
+            best_ask: ask.map(|price| BigDecimal::from_str(price).unwrap()),
+        }
+    }
+
+    #[test]
+    fn quoted_time_counts_two_sided_quotes_within_the_max_spread() {
+        let mut tracker = QuoteTracker::new(vec!["eth_usdc"])
+            .with_max_spread(BigDecimal::from_str("0.01").unwrap());
+        let start = Instant::now();
+        let at = |secs: u64| start + Duration::from_secs(secs);
+        // 1% wide for 10s, 4% wide for 10s, one-sided for 20s
+        tracker.record_at("eth_usdc", quote(Some("99.5"), Some("100.5")), at(0));
+        tracker.record_at("eth_usdc", quote(Some("98"), Some("102")), at(10));
+        tracker.record_at("eth_usdc", quote(Some("99"), None), at(20));
+        tracker.record_at("not_tracked", quote(Some("1"), Some("2")), at(20));
+        let stats = tracker.stats_at(at(40));
+        assert_eq!(stats.len(), 1);
+        let stats = &stats["eth_usdc"];
+        assert_eq!(stats.observed_seconds, 40.0);
+        assert_eq!(stats.two_sided_seconds, 20.0);
+        assert_eq!(stats.qualifying_seconds, 10.0);
+        assert_eq!(stats.uptime, 0.25);
+        assert!((stats.average_spread.unwrap() - 0.025).abs() < 1e-9);
+        let spread =
+            |spread: &Option<String>| BigDecimal::from_str(spread.as_ref().unwrap()).unwrap();
+        assert_eq!(
+            spread(&stats.min_spread),
+            BigDecimal::from_str("0.01").unwrap()
+        );
+        assert_eq!(
+            spread(&stats.max_spread),
+            BigDecimal::from_str("0.04").unwrap()
+        );
+    }
+
+    fn fill(market: &str, liquidity: AccountTradeSide, amount: &str, price: u32) -> Fill {
+        Fill {
+            trade_id: "1".to_string(),
+            order_id: "2".to_string(),
+            market: market.to_string(),
+            buy_or_sell: BuyOrSell::Buy,
+            liquidity,
+            amount: BigDecimal::from_str(amount).unwrap(),
+            price: BigDecimal::from(price),
+            fee: BigDecimal::from(0),
+            received: BigDecimal::from(0),
+            executed_at: now(),
+        }
+    }
+
+    #[test]
+    fn reports_volume_per_market_with_the_quoting_measured() {
+        let period = DateTimeRange {
+            start: parse_timestamp("2021-01-01T00:00:00Z").unwrap(),
+            stop: parse_timestamp("2021-02-01T00:00:00Z").unwrap(),
+        };
+        let fills = [
+            fill("eth_usdc", AccountTradeSide::Maker, "2", 100),
+            fill("eth_usdc", AccountTradeSide::Maker, "1", 110),
+            fill("eth_usdc", AccountTradeSide::Taker, "0.5", 105),
+            fill("neo_usdc", AccountTradeSide::Taker, "3", 10),
+        ];
+        let mut quotes = BTreeMap::new();
+        quotes.insert(
+            "btc_usdc".to_string(),
+            QuoteStats {
+                observed_seconds: 40.0,
+                two_sided_seconds: 20.0,
+                qualifying_seconds: 10.0,
+                uptime: 0.25,
+                average_spread: Some(0.025),
+                min_spread: Some("0.01".to_string()),
+                max_spread: Some("0.04".to_string()),
+            },
+        );
+        let report = LiquidityReport::new(&period, &fills, quotes);
+        assert_eq!(report.period_start, format_timestamp(&period.start));
+        assert_eq!(report.period_end, format_timestamp(&period.stop));
+        let markets: Vec<&str> = report
+            .markets
+            .iter()
+            .map(|market| market.market.as_str())
+            .collect();
+        assert_eq!(markets, vec!["btc_usdc", "eth_usdc", "neo_usdc"]);
+        let eth = &report.markets[1];
+        assert_eq!(eth.maker_volume, "3");
+        assert_eq!(eth.maker_notional, "310");
+        assert_eq!(eth.maker_fills, 2);
+        assert_eq!(eth.taker_volume, "0.5");
+        assert_eq!(eth.quoting, QuoteStats::default());
+
+        let csv = report.to_csv();
+        let rows: Vec<&str> = csv.lines().collect();
+        assert_eq!(
+            rows,
+            vec![
+                CSV_HEADER,
+                "btc_usdc,0,0,0,0,40,20,10,0.25,0.025,0.01,0.04",
+                "eth_usdc,3,310,2,0.5,0,0,0,0,,,",
+                "neo_usdc,0,0,0,3,0,0,0,0,,,",
+            ]
+        );
+    }
+}
@@ -0,0 +1,482 @@
+//! Local L2 orderbook of a market, kept up to date from the orderbook subscription. Every
+//! update carries the id of the update before it, so a missed update shows as a gap in the
+//! sequence. The exchange doesn't send checksums of the book, so an update that would leave
+//! it crossed is taken as a sign of corruption as well. Either way the book is reported as
+//! out of sync and replaced by a new snapshot, rather than served as it is.
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::str::FromStr;
+use std::sync::{Arc, RwLock as SyncRwLock};
+use std::time::Duration;
+
+use bigdecimal::{BigDecimal, Signed, Zero};
+use tokio::sync::broadcast;
+use tokio_util::sync::CancellationToken;
+use tracing::warn;
+
+use nash_protocol::errors::{ProtocolError, Result, ResultExt};
+use nash_protocol::protocol::orderbook::OrderbookRequest;
+use nash_protocol::protocol::subscriptions::updated_orderbook::SubscribeOrderbookResponse;
+use nash_protocol::types::{BuyOrSell, MarketSymbol, OrderbookOrder};
+
+use crate::{Client, MarketEvent, MarketSubscriptionHandle};
+
+/// Attempts to fetch a new snapshot after a desync before giving up on the book
+const RESNAPSHOT_ATTEMPTS: u32 = 3;
+const RESNAPSHOT_BACKOFF: Duration = Duration::from_secs(1);
+/// Changes buffered for a slow reader of `OrderbookManager::changes` before it lags
+const CHANGES_CAPACITY: usize = 1024;
+
+/// Price level of the book
+#[derive(Clone, Debug, PartialEq)]
+pub struct Level {
+    pub price: BigDecimal,
+    pub amount: BigDecimal,
+}
+
+/// How an update related to the book it was applied to
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Sequence {
+    Applied,
+    /// Already part of the book, e.g. an update sent before the snapshot was taken
+    Stale,
+}
+
+/// Why the local book no longer matches the exchange's. The update is not applied.
+#[derive(Clone, Debug, PartialEq)]
+pub enum OrderbookDesync {
+    /// Updates were missed: the update didn't follow `expected`, the last one applied
+    Gap { expected: i64, received: i64 },
+    /// The update would leave the best bid at or above the best ask
+    Crossed {
+        update_id: i64,
+        best_bid: BigDecimal,
+        best_ask: BigDecimal,
+    },
+    /// The update could not be read
+    InvalidUpdate { reason: String },
+}
+
+impl fmt::Display for OrderbookDesync {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Gap { expected, received } => write!(
+                f,
+                "Orderbook update follows update {} instead of {}",
+                received, expected
+            ),
+            Self::Crossed {
+                update_id,
+                best_bid,
+                best_ask,
+            } => write!(
+                f,
+                "Orderbook update {} crosses the book: best bid {} >= best ask {}",
+                update_id, best_bid, best_ask
+            ),
+            Self::InvalidUpdate { reason } => write!(f, "Invalid orderbook update: {}", reason),
+        }
+    }
+}
+
+impl std::error::Error for OrderbookDesync {}
+
+/// Bids and asks of a market as of `update_id`
+#[derive(Clone, Debug, Default)]
+pub struct L2Book {
+    update_id: i64,
+    bids: BTreeMap<BigDecimal, BigDecimal>,
+    asks: BTreeMap<BigDecimal, BigDecimal>,
+}
+
+impl L2Book {
+    pub(crate) fn from_orders(
+        update_id: i64,
+        bids: &[OrderbookOrder],
+        asks: &[OrderbookOrder],
+    ) -> Result<Self> {
+        let mut book = Self {
+            update_id,
+            ..Self::default()
+        };
+        set_levels(&mut book.bids, bids)?;
+        set_levels(&mut book.asks, asks)?;
+        Ok(book)
+    }
+
+    /// Apply an update from the orderbook subscription. Levels with a zero amount are removed.
+    /// On a desync the book is left as it was.
+    pub fn apply(
+        &mut self,
+        update: &SubscribeOrderbookResponse,
+    ) -> std::result::Result<Sequence, OrderbookDesync> {
+        if update.update_id <= self.update_id {
+            return Ok(Sequence::Stale);
+        }
+        if update.last_update_id != self.update_id {
+            return Err(OrderbookDesync::Gap {
+                expected: self.update_id,
+                received: update.last_update_id,
+            });
+        }
+        let invalid = |e: ProtocolError| OrderbookDesync::InvalidUpdate { reason: e.report() };
+        // applied to copies, so a bad update doesn't leave the book half updated
+        let mut bids = self.bids.clone();
+        let mut asks = self.asks.clone();
+        set_levels(&mut bids, &update.bids).map_err(invalid)?;
+        set_levels(&mut asks, &update.asks).map_err(invalid)?;
+        if let (Some((best_bid, _)), Some((best_ask, _))) =
+            (bids.iter().next_back(), asks.iter().next())
+        {
+            if best_bid >= best_ask {
+                return Err(OrderbookDesync::Crossed {
+                    update_id: update.update_id,
+                    best_bid: best_bid.clone(),
+                    best_ask: best_ask.clone(),
+                });
+            }
+        }
+        self.bids = bids;
+        self.asks = asks;
+        self.update_id = update.update_id;
+        Ok(Sequence::Applied)
+    }
+
+    pub fn update_id(&self) -> i64 {
+        self.update_id
+    }
+
+    pub fn best_bid(&self) -> Option<Level> {
+        self.bids.iter().next_back().map(level)
+    }
+
+    pub fn best_ask(&self) -> Option<Level> {
+        self.asks.iter().next().map(level)
+    }
+
+    /// Up to `levels` bids, best first
+    pub fn bids(&self, levels: usize) -> Vec<Level> {
+        self.bids.iter().rev().take(levels).map(level).collect()
+    }
+
+    /// Up to `levels` asks, best first
+    pub fn asks(&self, levels: usize) -> Vec<Level> {
+        self.asks.iter().take(levels).map(level).collect()
+    }
+
+    /// Amount resting at `price` on the bids (`Buy`) or asks (`Sell`)
+    pub fn amount_at(&self, buy_or_sell: BuyOrSell, price: &BigDecimal) -> BigDecimal {
+        let side = match buy_or_sell {
+            BuyOrSell::Buy => &self.bids,
+            BuyOrSell::Sell => &self.asks,
+        };
+        side.get(price).cloned().unwrap_or_else(BigDecimal::zero)
+    }
+
+    /// Amount of A on the book that a `buy_or_sell` order limited to `limit_price` could
+    /// fill: asks at or below the price for a buy, bids at or above it for a sell
+    pub fn liquidity(&self, buy_or_sell: BuyOrSell, limit_price: &BigDecimal) -> BigDecimal {
+        match buy_or_sell {
+            BuyOrSell::Buy => self
+                .asks
+                .range(..=limit_price.clone())
+                .map(|(_, a)| a)
+                .sum(),
+            BuyOrSell::Sell => self.bids.range(limit_price.clone()..).map(|(_, a)| a).sum(),
+        }
+    }
+}
+
+fn level((price, amount): (&BigDecimal, &BigDecimal)) -> Level {
+    Level {
+        price: price.clone(),
+        amount: amount.clone(),
+    }
+}
+
+fn set_levels(
+    side: &mut BTreeMap<BigDecimal, BigDecimal>,
+    orders: &[OrderbookOrder],
+) -> Result<()> {
+    for order in orders {
+        let price = BigDecimal::from_str(&order.price)
+            .with_context(|| format!("parsing orderbook price {}", order.price))?;
+        if order.amount.is_zero() {
+            side.remove(&price);
+        } else if order.amount.is_positive() {
+            side.insert(price, order.amount.clone());
+        } else {
+            return Err(ProtocolError::coerce_static_from_str(&format!(
+                "Negative orderbook amount {} at {}",
+                order.amount, price
+            )));
+        }
+    }
+    Ok(())
+}
+
+fn to_levels(orders: &[OrderbookOrder]) -> Result<Vec<Level>> {
+    orders
+        .iter()
+        .map(|order| {
+            Ok(Level {
+                price: BigDecimal::from_str(&order.price)?,
+                amount: order.amount.clone(),
+            })
+        })
+        .collect()
+}
+
+/// Change to the book maintained by an `OrderbookManager`
+#[derive(Clone, Debug)]
+pub enum OrderbookChange {
+    /// Levels changed by an update, with their new amount; zero if the level was removed
+    Update {
+        update_id: i64,
+        bids: Vec<Level>,
+        asks: Vec<Level>,
+    },
+    /// The book went out of sync; a new snapshot is being fetched
+    Desync(OrderbookDesync),
+    /// The book was replaced by a new snapshot after a desync
+    Resnapshot { update_id: i64 },
+    /// The book is no longer maintained, see `OrderbookManager::error`
+    Stopped(ProtocolError),
+}
+
+/// Local orderbook of one market, maintained in the background. Dropping the manager stops
+/// maintaining the book and unsubscribes.
+pub struct OrderbookManager {
+    market: MarketSymbol,
+    book: Arc<SyncRwLock<L2Book>>,
+    error: Arc<SyncRwLock<Option<ProtocolError>>>,
+    changes: broadcast::Sender<OrderbookChange>,
+    stop: CancellationToken,
+}
+
+impl OrderbookManager {
+    pub fn market(&self) -> &MarketSymbol {
+        &self.market
+    }
+
+    pub fn best_bid(&self) -> Option<Level> {
+        self.book.read().unwrap().best_bid()
+    }
+
+    pub fn best_ask(&self) -> Option<Level> {
+        self.book.read().unwrap().best_ask()
+    }
+
+    /// Up to `levels` bids, best first
+    pub fn bids(&self, levels: usize) -> Vec<Level> {
+        self.book.read().unwrap().bids(levels)
+    }
+
+    /// Up to `levels` asks, best first
+    pub fn asks(&self, levels: usize) -> Vec<Level> {
+        self.book.read().unwrap().asks(levels)
+    }
+
+    /// See `L2Book::liquidity`
+    pub fn liquidity(&self, buy_or_sell: BuyOrSell, limit_price: &BigDecimal) -> BigDecimal {
+        self.book
+            .read()
+            .unwrap()
+            .liquidity(buy_or_sell, limit_price)
+    }
+
+    /// Copy of the whole book
+    pub fn book(&self) -> L2Book {
+        self.book.read().unwrap().clone()
+    }
+
+    /// Changes applied to the book from now on
+    pub fn changes(&self) -> broadcast::Receiver<OrderbookChange> {
+        self.changes.subscribe()
+    }
+
+    /// Why the book stopped being maintained, if it did. The book is stale from then on.
+    pub fn error(&self) -> Option<ProtocolError> {
+        self.error.read().unwrap().clone()
+    }
+}
+
+impl Drop for OrderbookManager {
+    fn drop(&mut self) {
+        self.stop.cancel();
+    }
+}
+
+impl Client {
+    /// Maintain a local orderbook of `market` from its orderbook subscription, fetching a new
+    /// snapshot whenever it goes out of sync (see `OrderbookDesync`)
+    pub async fn orderbook_manager(&self, market: &str) -> Result<OrderbookManager> {
+        let market: MarketSymbol = market.parse()?;
+        // subscribed before the snapshot is taken, so no update falls in between
+        let updates = self.subscribe_orderbooks(&[market.as_str()]).await?;
+        let book = Arc::new(SyncRwLock::new(self.orderbook_snapshot(&market).await?));
+        let (changes, _) = broadcast::channel(CHANGES_CAPACITY);
+        let manager = OrderbookManager {
+            market,
+            book,
+            error: Arc::new(SyncRwLock::new(None)),
+            changes,
+            stop: CancellationToken::new(),
+        };
+        let client = self.clone();
+        let market = manager.market.clone();
+        let book = manager.book.clone();
+        let error = manager.error.clone();
+        let changes = manager.changes.clone();
+        let stop = manager.stop.clone();
+        tokio::spawn(async move {
+            let reason = tokio::select! {
+                reason = client.maintain_orderbook(&market, updates, &book, &changes) => reason,
+                _ = stop.cancelled() => return,
+            };
+            warn!(%market, error = %reason.report(), "orderbook is no longer maintained");
+            *error.write().unwrap() = Some(reason.clone());
+            let _ = changes.send(OrderbookChange::Stopped(reason));
+        });
+        Ok(manager)
+    }
+
+    async fn orderbook_snapshot(&self, market: &MarketSymbol) -> Result<L2Book> {
+        let snapshot = self
+            .run(OrderbookRequest::from(market.clone()))
+            .await?
+            .response_or_error()?;
+        L2Book::from_orders(snapshot.update_id, &snapshot.bids, &snapshot.asks)
+    }
+
+    /// Apply updates to `book` until it can't be maintained anymore, returning why
+    async fn maintain_orderbook(
+        &self,
+        market: &MarketSymbol,
+        mut updates: MarketSubscriptionHandle<SubscribeOrderbookResponse>,
+        book: &SyncRwLock<L2Book>,
+        changes: &broadcast::Sender<OrderbookChange>,
+    ) -> ProtocolError {
+        loop {
+            let update = match updates.recv().await {
+                Some(MarketEvent { event, .. }) => {
+                    event.and_then(|response| response.response_or_error())
+                }
+                None => return ProtocolError("Orderbook subscription ended"),
+            };
+            let invalid = |e: ProtocolError| OrderbookDesync::InvalidUpdate { reason: e.report() };
+            let applied = update.map_err(invalid).and_then(|update| {
+                if book.write().unwrap().apply(&update)? == Sequence::Applied {
+                    let _ = changes.send(OrderbookChange::Update {
+                        update_id: update.update_id,
+                        bids: to_levels(&update.bids).map_err(invalid)?,
+                        asks: to_levels(&update.asks).map_err(invalid)?,
+                    });
+                }
+                Ok(())
+            });
+            let desync = match applied {
+                Ok(()) => continue,
+                Err(desync) => desync,
+            };
+            warn!(%market, %desync, "orderbook out of sync, resnapshotting");
+            let _ = changes.send(OrderbookChange::Desync(desync.clone()));
+            let mut snapshot = Err(ProtocolError("Could not fetch orderbook snapshot"));
+            for attempt in 1..=RESNAPSHOT_ATTEMPTS {
+                snapshot = self.orderbook_snapshot(market).await;
+                if snapshot.is_ok() {
+                    break;
+                }
+                tokio::time::sleep(RESNAPSHOT_BACKOFF * attempt).await;
+            }
+            match snapshot {
+                Ok(snapshot) => {
+                    let update_id = snapshot.update_id;
+                    *book.write().unwrap() = snapshot;
+                    let _ = changes.send(OrderbookChange::Resnapshot { update_id });
+                }
+                Err(e) => return e.context(format!("resnapshotting orderbook after: {}", desync)),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{L2Book, Level, OrderbookDesync, Sequence};
+    use bigdecimal::BigDecimal;
+    use nash_protocol::protocol::subscriptions::updated_orderbook::SubscribeOrderbookResponse;
+    use nash_protocol::types::{BuyOrSell, OrderbookOrder};
+    use std::str::FromStr;
+
+    fn dec(value: &str) -> BigDecimal {
+        BigDecimal::from_str(value).unwrap()
+    }
+
+    fn orders(levels: &[(&str, &str)]) -> Vec<OrderbookOrder> {
+        levels
+            .iter()
+            .map(|(price, amount)| OrderbookOrder {
+                price: price.to_string(),
+                amount: dec(amount),
+            })
+            .collect()
+    }
+
+    fn update(
+        last_update_id: i64,
+        update_id: i64,
+        bids: &[(&str, &str)],
+    ) -> SubscribeOrderbookResponse {
+        SubscribeOrderbookResponse {
+            last_update_id,
+            update_id,
+            bids: orders(bids),
+            asks: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn updates_are_applied_in_sequence() {
+        let bids = orders(&[("99", "1"), ("100", "2")]);
+        let asks = orders(&[("102", "1"), ("101", "3")]);
+        let mut book = L2Book::from_orders(10, &bids, &asks).unwrap();
+        assert_eq!(
+            book.best_ask(),
+            Some(Level {
+                price: dec("101"),
+                amount: dec("3")
+            })
+        );
+        assert_eq!(book.liquidity(BuyOrSell::Buy, &dec("101.5")), dec("3"));
+        assert_eq!(book.liquidity(BuyOrSell::Sell, &dec("99")), dec("3"));
+
+        let next = update(10, 11, &[("100", "0"), ("99.5", "4")]);
+        assert_eq!(book.apply(&next).unwrap(), Sequence::Applied);
+        assert_eq!(book.apply(&next).unwrap(), Sequence::Stale);
+        assert_eq!(book.best_bid().unwrap().price, dec("99.5"));
+        assert_eq!(book.bids(5).len(), 2);
+
+        assert_eq!(
+            book.apply(&update(12, 13, &[("98", "1")])),
+            Err(OrderbookDesync::Gap {
+                expected: 11,
+                received: 12
+            })
+        );
+        assert_eq!(
+            book.apply(&update(11, 12, &[("101", "1")])),
+            Err(OrderbookDesync::Crossed {
+                update_id: 12,
+                best_bid: dec("101"),
+                best_ask: dec("101")
+            })
+        );
+        assert!(matches!(
+            book.apply(&update(11, 12, &[("98", "-1")])),
+            Err(OrderbookDesync::InvalidUpdate { .. })
+        ));
+        assert_eq!((book.update_id(), book.bids(5).len()), (11, 2));
+    }
+}
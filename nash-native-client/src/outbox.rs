@@ -0,0 +1,431 @@
+//! Outbox for cancellations that can't reach the exchange because the connection is down.
+//! Cancelling reduces risk, so instead of failing, `Client::cancel_or_queue` queues the
+//! cancellation and it is sent as soon as the exchange can be reached again, unless it
+//! expired in the meantime. New orders are never queued: placed late, into a market that
+//! moved on, they would add risk instead.
+//!
+//! Queued cancellations are signed when they are sent, as the exchange rejects requests
+//! signed too long ago. With a store set, they are kept as JSON files and sent by the next
+//! client using the store, e.g. after a restart.
+
+use std::future::Future;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex as SyncMutex, RwLock as SyncRwLock, Weak};
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use tokio::sync::broadcast;
+use tokio_util::sync::CancellationToken;
+use tracing::{info, warn};
+
+use nash_protocol::errors::{ProtocolError, Result};
+use nash_protocol::protocol::cancel_all_orders::CancelAllOrders;
+use nash_protocol::protocol::cancel_order::CancelOrderRequest;
+use nash_protocol::types::timestamp::{self, Timestamp};
+use nash_protocol::utils::current_time_as_i64;
+
+use crate::json_store::{JsonStore, Stored};
+use crate::ws_client::InnerClient;
+use crate::{Client, ConnectionEvent};
+
+/// How often sending queued cancellations is retried while no reconnect is seen. Over HTTP
+/// they may go through before the websocket is back.
+const FLUSH_RETRY_INTERVAL: Duration = Duration::from_secs(5);
+/// Messages of the errors the websocket client fails a request with when it can't reach
+/// the exchange. Errors of HTTP requests carry the `reqwest::Error` as their source.
+const UNREACHABLE_MESSAGES: &[&str] = &[
+    "Request timeout",
+    "Request failed to send over channel",
+    "Failed to receive response from return channel",
+    "Could not register request with broker",
+    "Disconnected.",
+];
+
+/// A cancellation, which reduces risk and so may be queued
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Cancellation {
+    Order { order_id: String, market: String },
+    AllOrders { market: String },
+}
+
+/// A cancellation waiting in the outbox
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct QueuedCancellation {
+    pub id: String,
+    pub cancellation: Cancellation,
+    #[serde(with = "timestamp::rfc3339")]
+    pub queued_at: Timestamp,
+    /// The cancellation is dropped instead of sent if still queued this long after
+    /// `queued_at`, as what it was meant to cancel may have been replaced by then
+    pub max_age: Duration,
+}
+
+impl QueuedCancellation {
+    /// Whether the cancellation is older than `max_age` at `now`, in unix millis
+    fn is_expired(&self, now: i64) -> bool {
+        now - timestamp::unix_millis(&self.queued_at) > self.max_age.as_millis() as i64
+    }
+}
+
+impl Stored for QueuedCancellation {
+    const KIND: &'static str = "queued cancellation";
+
+    fn id(&self) -> &str {
+        &self.id
+    }
+}
+
+/// Whether a request failed with `error` because the exchange couldn't be reached, as
+/// opposed to e.g. a request that couldn't be built or signed, which would fail again
+fn is_unreachable(error: &ProtocolError) -> bool {
+    error.find_source::<reqwest::Error>().is_some()
+        || UNREACHABLE_MESSAGES.contains(&error.message())
+        || error.message().ends_with("likely disconnected")
+}
+
+/// What `Client::cancel_or_queue` did with a cancellation
+#[derive(Clone, Debug, PartialEq)]
+pub enum CancelOutcome {
+    Sent,
+    /// Queued under this id until the exchange can be reached
+    Queued(String),
+}
+
+/// Queued cancellations and where they are persisted
+#[derive(Debug, Default)]
+pub(crate) struct Outbox {
+    /// How long cancellations stay queued, `None` while the outbox is disabled
+    max_age: SyncRwLock<Option<Duration>>,
+    /// Set from a disconnect until the next connect
+    disconnected: AtomicBool,
+    queue: SyncMutex<Vec<QueuedCancellation>>,
+    store: JsonStore<QueuedCancellation>,
+    /// Stops watching the connection once the outbox is disabled
+    watch: SyncMutex<Option<CancellationToken>>,
+    /// Held while sending queued cancellations, so none is sent twice
+    flushing: tokio::sync::Mutex<()>,
+}
+
+impl Outbox {
+    fn push(&self, entry: QueuedCancellation) -> Result<()> {
+        self.store.persist(&entry)?;
+        self.queue.lock().unwrap().push(entry);
+        Ok(())
+    }
+
+    fn remove(&self, id: &str) {
+        self.queue.lock().unwrap().retain(|entry| entry.id != id);
+        self.store.remove(id);
+    }
+
+    fn is_empty(&self) -> bool {
+        self.queue.lock().unwrap().is_empty()
+    }
+
+    /// Send queued cancellations with `send`, oldest first. Expired ones, and ones the
+    /// exchange rejects or that fail for another reason than the exchange being unreachable,
+    /// are dropped. Ones that can't reach the exchange stay queued, and the next one is
+    /// tried. Returns how many were sent.
+    async fn flush<F, Fut>(&self, mut send: F) -> usize
+    where
+        F: FnMut(Cancellation) -> Fut,
+        Fut: Future<Output = Result<Result<()>>>,
+    {
+        let _flushing = self.flushing.lock().await;
+        let queued = self.queue.lock().unwrap().clone();
+        let mut sent = 0;
+        for entry in queued {
+            if entry.is_expired(current_time_as_i64()) {
+                warn!(id = %entry.id, cancellation = ?entry.cancellation, "queued cancellation expired, dropping it");
+                self.remove(&entry.id);
+                continue;
+            }
+            match send(entry.cancellation.clone()).await {
+                Ok(Ok(())) => {
+                    sent += 1;
+                    self.remove(&entry.id);
+                }
+                Ok(Err(e)) => {
+                    warn!(id = %entry.id, error = %e.report(), "queued cancellation rejected, dropping it");
+                    self.remove(&entry.id);
+                }
+                Err(e) if is_unreachable(&e) => {
+                    warn!(id = %entry.id, error = %e.report(), "exchange still unreachable, cancellation stays queued");
+                }
+                Err(e) => {
+                    warn!(id = %entry.id, error = %e.report(), "queued cancellation failed, dropping it");
+                    self.remove(&entry.id);
+                }
+            }
+        }
+        if sent > 0 {
+            info!(%sent, "queued cancellations sent");
+        }
+        sent
+    }
+}
+
+impl Client {
+    /// Queue cancellations passed to `cancel_or_queue` for up to `max_age` when they can't
+    /// reach the exchange. Replaces the max age set before; cancellations already queued
+    /// keep theirs.
+    pub fn enable_outbox(&self, max_age: Duration) -> Result<()> {
+        if max_age.is_zero() {
+            return Err(ProtocolError("Outbox max age must be greater than 0"));
+        }
+        // subscribed before spawning, so a disconnect right after enabling isn't missed
+        let events = self.inner.connection_events.subscribe();
+        let stop = CancellationToken::new();
+        if let Some(previous) = self
+            .inner
+            .outbox
+            .watch
+            .lock()
+            .unwrap()
+            .replace(stop.clone())
+        {
+            previous.cancel();
+        }
+        *self.inner.outbox.max_age.write().unwrap() = Some(max_age);
+        tokio::spawn(watch_connection(Arc::downgrade(&self.inner), events, stop));
+        info!(max_age_ms = max_age.as_millis() as u64, "outbox enabled");
+        Ok(())
+    }
+
+    /// Stop queueing cancellations. Those already queued stay queued until `flush_outbox`.
+    pub fn disable_outbox(&self) {
+        if let Some(watch) = self.inner.outbox.watch.lock().unwrap().take() {
+            watch.cancel();
+            info!("outbox disabled");
+        }
+        *self.inner.outbox.max_age.write().unwrap() = None;
+    }
+
+    /// Persist queued cancellations as JSON files in `dir`. Cancellations found there are
+    /// queued again and sent with the next flush; returns how many there were.
+    pub fn set_outbox_store(&self, dir: impl Into<PathBuf>) -> Result<usize> {
+        let outbox = &self.inner.outbox;
+        let found = outbox.store.open(dir.into())?;
+        let count = found.len();
+        let mut queue = outbox.queue.lock().unwrap();
+        for queued in found {
+            if !queue.iter().any(|entry| entry.id == queued.id) {
+                queue.push(queued);
+            }
+        }
+        queue.sort_by_key(|entry| timestamp::unix_millis(&entry.queued_at));
+        for entry in queue.iter() {
+            outbox.store.persist(entry)?;
+        }
+        Ok(count)
+    }
+
+    /// Cancellations waiting in the outbox, oldest first
+    pub fn queued_cancellations(&self) -> Vec<QueuedCancellation> {
+        self.inner.outbox.queue.lock().unwrap().clone()
+    }
+
+    /// Send `cancellation`. If the outbox is enabled and the exchange can't be reached, the
+    /// cancellation is queued instead, see `enable_outbox`. A cancellation the exchange
+    /// rejects, or that fails for another reason such as an unknown market, is returned as
+    /// an error and never queued. While the websocket is down, cancellations are tried over
+    /// HTTP before being queued.
+    pub async fn cancel_or_queue(&self, cancellation: Cancellation) -> Result<CancelOutcome> {
+        let max_age = *self.inner.outbox.max_age.read().unwrap();
+        let max_age = match max_age {
+            Some(max_age) => max_age,
+            None => {
+                return self
+                    .inner
+                    .send_cancellation(&cancellation)
+                    .await?
+                    .map(|_| CancelOutcome::Sent)
+            }
+        };
+        match self.inner.send_cancellation(&cancellation).await {
+            Ok(sent) => sent.map(|_| CancelOutcome::Sent),
+            Err(e) if !is_unreachable(&e) => Err(e),
+            Err(e) => {
+                let queued = QueuedCancellation {
+                    id: self.inner.rng.id(),
+                    cancellation,
+                    queued_at: timestamp::now(),
+                    max_age,
+                };
+                warn!(id = %queued.id, cancellation = ?queued.cancellation, error = %e.report(), "exchange unreachable, cancellation queued");
+                let id = queued.id.clone();
+                self.inner.outbox.push(queued)?;
+                Ok(CancelOutcome::Queued(id))
+            }
+        }
+    }
+
+    /// Send queued cancellations now, oldest first. Expired ones, and ones that fail for
+    /// another reason than the exchange being unreachable, are dropped. While the outbox is
+    /// enabled this happens by itself on every reconnect. Returns how many were sent.
+    pub async fn flush_outbox(&self) -> usize {
+        self.inner.flush_outbox().await
+    }
+}
+
+impl InnerClient {
+    /// Send a cancellation, over HTTP if the websocket is down. The outer error is a failure
+    /// to send it, e.g. because the exchange can't be reached, the inner one a rejection by
+    /// the exchange.
+    async fn send_cancellation(&self, cancellation: &Cancellation) -> Result<Result<()>> {
+        let over_http = self.outbox.disconnected.load(Ordering::SeqCst);
+        match cancellation {
+            Cancellation::Order { order_id, market } => {
+                let request = CancelOrderRequest {
+                    order_id: order_id.clone(),
+                    market: market.clone(),
+                };
+                let response = if over_http {
+                    self.run_http(request).await?
+                } else {
+                    self.run(request).await?
+                };
+                Ok(response.response_or_error().map(|_| ()))
+            }
+            Cancellation::AllOrders { market } => {
+                let request = CancelAllOrders {
+                    market: market.clone(),
+                };
+                let response = if over_http {
+                    self.run_http(request).await?
+                } else {
+                    self.run(request).await?
+                };
+                Ok(response.response_or_error().and_then(|response| {
+                    if response.accepted {
+                        Ok(())
+                    } else {
+                        Err(ProtocolError("Cancelling all orders was not accepted"))
+                    }
+                }))
+            }
+        }
+    }
+
+    /// Send queued cancellations, see `Outbox::flush`
+    pub(crate) async fn flush_outbox(&self) -> usize {
+        self.outbox
+            .flush(|cancellation| async move { self.send_cancellation(&cancellation).await })
+            .await
+    }
+}
+
+/// Follow the connection until `stop`, sending queued cancellations on every reconnect and
+/// retrying them every `FLUSH_RETRY_INTERVAL` in between
+async fn watch_connection(
+    inner: Weak<InnerClient>,
+    mut events: broadcast::Receiver<ConnectionEvent>,
+    stop: CancellationToken,
+) {
+    loop {
+        let event = tokio::select! {
+            _ = stop.cancelled() => return,
+            _ = tokio::time::sleep(FLUSH_RETRY_INTERVAL) => None,
+            event = events.recv() => Some(event),
+        };
+        let inner = match inner.upgrade() {
+            Some(inner) => inner,
+            None => return,
+        };
+        match event {
+            None => {
+                if !inner.outbox.is_empty() {
+                    inner.flush_outbox().await;
+                }
+            }
+            Some(Ok(ConnectionEvent::Disconnected))
+            | Some(Ok(ConnectionEvent::Reconnecting { .. })) => {
+                inner.outbox.disconnected.store(true, Ordering::SeqCst);
+            }
+            Some(Ok(ConnectionEvent::Connected { .. })) => {
+                inner.outbox.disconnected.store(false, Ordering::SeqCst);
+                inner.flush_outbox().await;
+            }
+            Some(Ok(_)) | Some(Err(broadcast::error::RecvError::Lagged(_))) => {}
+            Some(Err(broadcast::error::RecvError::Closed)) => return,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Cancellation, Outbox, QueuedCancellation};
+    use nash_protocol::errors::ProtocolError;
+    use nash_protocol::types::timestamp::{self, parse_timestamp};
+    use std::time::Duration;
+
+    fn queued(id: &str, market: &str) -> QueuedCancellation {
+        QueuedCancellation {
+            id: id.to_string(),
+            cancellation: Cancellation::AllOrders {
+                market: market.to_string(),
+            },
+            queued_at: timestamp::now(),
+            max_age: Duration::from_secs(60),
+        }
+    }
+
+    #[test]
+    fn queued_cancellations_expire_and_round_trip() {
+        let queued = QueuedCancellation {
+            id: "queued".to_string(),
+            cancellation: Cancellation::Order {
+                order_id: "order".to_string(),
+                market: "eth_usdc".to_string(),
+            },
+            queued_at: parse_timestamp("2021-01-01T00:00:00Z").unwrap(),
+            max_age: Duration::from_secs(30),
+        };
+        let queued_at = timestamp::unix_millis(&queued.queued_at);
+        assert!(!queued.is_expired(queued_at + 30_000));
+        assert!(queued.is_expired(queued_at + 30_001));
+
+        let json = serde_json::to_string(&queued).unwrap();
+        let restored: QueuedCancellation = serde_json::from_str(&json).unwrap();
+        assert_eq!(restored.cancellation, queued.cancellation);
+        assert_eq!(restored.queued_at, queued.queued_at);
+        assert_eq!(restored.max_age, queued.max_age);
+    }
+
+    #[tokio::test]
+    async fn flush_keeps_only_what_could_not_reach_the_exchange() {
+        let outbox = Outbox::default();
+        let mut expired = queued("expired", "eth_usdc");
+        expired.queued_at = parse_timestamp("2021-01-01T00:00:00Z").unwrap();
+        outbox.push(expired).unwrap();
+        outbox.push(queued("unreachable", "eth_usdc")).unwrap();
+        outbox.push(queued("unknown market", "foo_bar")).unwrap();
+        outbox.push(queued("rejected", "btc_usdc")).unwrap();
+        outbox.push(queued("sent", "eth_btc")).unwrap();
+
+        let sent = outbox
+            .flush(|cancellation| async move {
+                let market = match cancellation {
+                    Cancellation::AllOrders { market } => market,
+                    Cancellation::Order { market, .. } => market,
+                };
+                match market.as_str() {
+                    "eth_usdc" => Err(ProtocolError("Request timeout")),
+                    "foo_bar" => Err(ProtocolError("Market not found")),
+                    "btc_usdc" => Ok(Err(ProtocolError("Order not found"))),
+                    _ => Ok(Ok(())),
+                }
+            })
+            .await;
+        assert_eq!(sent, 1);
+        let left: Vec<_> = outbox
+            .queue
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|entry| entry.id.clone())
+            .collect();
+        assert_eq!(left, vec!["unreachable".to_string()]);
+    }
+}
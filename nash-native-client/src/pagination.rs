@@ -0,0 +1,296 @@
+//! `Paginated` list requests followed page by page until exhausted, as a `Stream`. Pages are
+//! requested one after the other with a delay in between to stay within the exchange's rate
+//! limits, and a page that fails, e.g. because the limit was hit anyway, is retried with
+//! backoff.
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use futures::future::{BoxFuture, FutureExt};
+use futures::stream::{self, BoxStream, StreamExt};
+
+use nash_protocol::errors::Result;
+use nash_protocol::protocol::{Page, Paginated};
+
+use crate::config::RetryPolicy;
+use crate::Client;
+
+const DEFAULT_PAGE_SIZE: i64 = 100;
+const DEFAULT_PAGE_DELAY: Duration = Duration::from_millis(100);
+
+/// Runs the request for one page
+type FetchPage<T> =
+    Arc<dyn Fn(T) -> BoxFuture<'static, Result<Page<<T as Paginated>::Item>>> + Send + Sync>;
+
+/// Pages of a list request, from `Client::pages`. The request's `before` is replaced by the
+/// cursor of each page and its `limit` by the page size.
+#[derive(Clone)]
+pub struct Pages<T: Paginated> {
+    fetch_page: FetchPage<T>,
+    request: T,
+    page_size: i64,
+    delay: Duration,
+    retry: RetryPolicy,
+}
+
+impl<T> Pages<T>
+where
+    T: Paginated + Send + Sync + 'static,
+    T::Item: Send + 'static,
+{
+    fn new(request: T, fetch_page: FetchPage<T>) -> Self {
+        Self {
+            fetch_page,
+            request,
+            page_size: DEFAULT_PAGE_SIZE,
+            delay: DEFAULT_PAGE_DELAY,
+            retry: RetryPolicy {
+                max_attempts: 3,
+                backoff_ms: 500,
+            },
+        }
+    }
+
+    /// Items per page, 100 by default
+    pub fn page_size(mut self, page_size: i64) -> Self {
+        self.page_size = page_size.max(1);
+        self
+    }
+
+    /// Wait between pages, 100ms by default
+    pub fn delay(mut self, delay: Duration) -> Self {
+        self.delay = delay;
+        self
+    }
+
+    /// Attempts per page and the backoff between them, 3 attempts from 500ms by default
+    pub fn retry(mut self, retry: RetryPolicy) -> Self {
+        self.retry = retry;
+        self
+    }
+
+    async fn fetch(&self, request: &T) -> Result<Page<T::Item>> {
+        let mut attempt = 1;
+        loop {
+            match (self.fetch_page)(request.clone()).await {
+                Ok(page) => return Ok(page),
+                Err(e) if attempt >= self.retry.max_attempts => {
+                    let before = request.before().unwrap_or("the start");
+                    return Err(e.context(format!("fetching the page from {}", before)));
+                }
+                Err(_) => {
+                    tokio::time::sleep(self.retry.backoff(attempt)).await;
+                    attempt += 1;
+                }
+            }
+        }
+    }
+
+    /// Every page, newest first, until one comes back empty or without a next page. A page
+    /// that fails after all attempts ends the stream with its error.
+    pub fn stream(self) -> BoxStream<'static, Result<Page<T::Item>>> {
+        let first = self.request.page(None, Some(self.page_size));
+        stream::unfold(
+            (self, Some(first), true),
+            |(pages, request, first)| async move {
+                let request = request?;
+                if !first {
+                    tokio::time::sleep(pages.delay).await;
+                }
+                match pages.fetch(&request).await {
+                    Ok(page) => {
+                        let next = request.next_page(&page);
+                        Some((Ok(page), (pages, next, false)))
+                    }
+                    Err(e) => Some((Err(e), (pages, None, false))),
+                }
+            },
+        )
+        .boxed()
+    }
+
+    /// Items of every page, in page order
+    pub fn items(self) -> BoxStream<'static, Result<T::Item>> {
+        self.stream()
+            .flat_map(|page| {
+                stream::iter(match page {
+                    Ok(page) => page.items.into_iter().map(Ok).collect(),
+                    Err(e) => vec![Err(e)],
+                })
+            })
+            .boxed()
+    }
+
+    /// Items of every page, or the error of the first page that failed
+    pub async fn all(self) -> Result<Vec<T::Item>> {
+        let mut pages = self.stream();
+        let mut items = Vec::new();
+        while let Some(page) = pages.next().await {
+            items.extend(page?.items);
+        }
+        Ok(items)
+    }
+}
+
+impl Client {
+    /// Follow the pages of `request` until exhausted, e.g.
+    /// `client.pages(ListAccountTradesRequest { .. }).page_size(50).all().await`
+    pub fn pages<T>(&self, request: T) -> Pages<T>
+    where
+        T: Paginated + Send + Sync + 'static,
+        T::Item: Send + 'static,
+    {
+        let client = self.clone();
+        Pages::new(
+            request,
+            Arc::new(move |request: T| {
+                let client = client.clone();
+                async move {
+                    client
+                        .run(request)
+                        .await
+                        .and_then(|r| r.response_or_error())
+                        .map(T::into_page)
+                }
+                .boxed()
+            }),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{FetchPage, Pages};
+    use crate::config::RetryPolicy;
+    use bigdecimal::BigDecimal;
+    use futures::future::{self, FutureExt};
+    use futures::StreamExt;
+    use nash_protocol::errors::ProtocolError;
+    use nash_protocol::protocol::list_account_movements::{
+        ListAccountMovementsRequest, Movement, MovementType,
+    };
+    use nash_protocol::protocol::withdraw::MovementStatus;
+    use nash_protocol::protocol::Page;
+    use nash_protocol::types::Blockchain;
+    use std::sync::atomic::{AtomicU32, Ordering};
+    use std::sync::{Arc, Mutex};
+    use std::time::{Duration, Instant};
+
+    fn movement(id: &str) -> Movement {
+        Movement {
+            id: id.to_string(),
+            movement_type: MovementType::Deposit,
+            status: MovementStatus::Completed,
+            currency: "eth".to_string(),
+            quantity: BigDecimal::from(1),
+            fee: None,
+            blockchain: Blockchain::Ethereum,
+            address: None,
+            target_address: None,
+            transaction_hash: None,
+            confirmations: None,
+            received_at: None,
+        }
+    }
+
+    /// Two pages, "a" and "b" then "c". The first `failures` requests fail.
+    fn two_pages(
+        failures: u32,
+        requests: Arc<Mutex<Vec<ListAccountMovementsRequest>>>,
+    ) -> FetchPage<ListAccountMovementsRequest> {
+        let calls = AtomicU32::new(0);
+        Arc::new(move |request: ListAccountMovementsRequest| {
+            requests.lock().unwrap().push(request.clone());
+            if calls.fetch_add(1, Ordering::SeqCst) < failures {
+                return future::ready(Err(ProtocolError("Rate limited"))).boxed();
+            }
+            let page = match request.before.as_deref() {
+                None => Page {
+                    items: vec![movement("a"), movement("b")],
+                    next_page: Some("b".to_string()),
+                },
+                Some(_) => Page {
+                    items: vec![movement("c")],
+                    next_page: None,
+                },
+            };
+            future::ready(Ok(page)).boxed()
+        })
+    }
+
+    fn ids(movements: &[Movement]) -> Vec<&str> {
+        movements
+            .iter()
+            .map(|movement| movement.id.as_str())
+            .collect()
+    }
+
+    fn retry(max_attempts: u32) -> RetryPolicy {
+        RetryPolicy {
+            max_attempts,
+            backoff_ms: 1,
+        }
+    }
+
+    #[tokio::test]
+    async fn pages_are_followed_with_a_delay_in_between() {
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        let pages = Pages::new(
+            ListAccountMovementsRequest::default(),
+            two_pages(0, requests.clone()),
+        )
+        .page_size(2)
+        .delay(Duration::from_millis(50));
+        let started = Instant::now();
+        let pages: Vec<_> = pages.stream().collect().await;
+        assert!(started.elapsed() >= Duration::from_millis(50));
+        let pages: Vec<Vec<Movement>> = pages.into_iter().map(|page| page.unwrap().items).collect();
+        assert_eq!(pages.len(), 2);
+        assert_eq!(ids(&pages[0]), vec!["a", "b"]);
+        assert_eq!(ids(&pages[1]), vec!["c"]);
+        let requests = requests.lock().unwrap();
+        let cursors: Vec<_> = requests
+            .iter()
+            .map(|request| request.before.clone())
+            .collect();
+        assert_eq!(cursors, vec![None, Some("b".to_string())]);
+        assert!(requests.iter().all(|request| request.limit == Some(2)));
+    }
+
+    #[tokio::test]
+    async fn failed_pages_are_retried() {
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        let pages = Pages::new(
+            ListAccountMovementsRequest::default(),
+            two_pages(2, requests.clone()),
+        )
+        .delay(Duration::from_millis(0))
+        .retry(retry(3));
+        assert_eq!(ids(&pages.all().await.unwrap()), vec!["a", "b", "c"]);
+        assert_eq!(requests.lock().unwrap().len(), 4);
+
+        let failing = || {
+            Pages::new(
+                ListAccountMovementsRequest::default(),
+                two_pages(2, Arc::new(Mutex::new(Vec::new()))),
+            )
+            .retry(retry(2))
+        };
+        assert!(failing().all().await.is_err());
+        // the error ends the stream
+        let items: Vec<_> = failing().items().collect().await;
+        assert_eq!(items.len(), 1);
+        assert!(items[0].is_err());
+    }
+
+    #[tokio::test]
+    async fn items_are_in_page_order() {
+        let pages = Pages::new(
+            ListAccountMovementsRequest::default(),
+            two_pages(0, Arc::new(Mutex::new(Vec::new()))),
+        )
+        .delay(Duration::from_millis(0));
+        let items: Vec<Movement> = pages.items().map(|item| item.unwrap()).collect().await;
+        assert_eq!(ids(&items), vec!["a", "b", "c"]);
+    }
+}
@@ -0,0 +1,727 @@
+//! Paper trading: a venue that takes orders like the exchange, but fills them against the
+//! live orderbook instead of sending them. A `FillModel` and a latency decide how fills are
+//! simulated, so strategies see delays and partial fills rather than instant all-or-nothing
+//! fills. Simulated orders don't move the real book and fees are not charged.
+
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::sync::{Arc, Mutex as SyncMutex};
+use std::time::Duration;
+
+use async_trait::async_trait;
+use bigdecimal::{BigDecimal, Signed, Zero};
+use exchange_traits::{
+    Balance, Balances, MarketData, OrderAck, OrderEntry, OrderRequest, Side, TimeInForce,
+    TopOfBook, Venue,
+};
+use futures::stream::BoxStream;
+use tokio::sync::{broadcast, Mutex};
+use tokio::time::Instant;
+use tokio_util::sync::CancellationToken;
+use tracing::{info, warn};
+
+use nash_protocol::errors::{ProtocolError, Result};
+use nash_protocol::types::{BuyOrSell, MarketSymbol};
+
+use crate::orderbook::{L2Book, Level, OrderbookChange, OrderbookManager};
+use crate::Client;
+
+/// Closed orders kept to be looked up; older ones are forgotten
+const RECENTLY_CLOSED: usize = 1_000;
+/// Fills kept by the engine; older ones are dropped
+const RECENT_FILLS: usize = 10_000;
+
+/// How orders are filled from the book
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum FillModel {
+    /// Orders fill once the other side of the book reaches their price, taking at most the
+    /// size of its best level on each book update
+    TopOfBook,
+    /// Like `TopOfBook`, and resting orders join the back of the queue at their price: once
+    /// the amount that was ahead of them has left the level, whatever leaves it next fills
+    /// them
+    QueuePosition,
+}
+
+#[derive(Clone, Debug)]
+pub struct PaperConfig {
+    pub fill_model: FillModel,
+    /// Delay before orders and cancellations take effect, as sending them would take. Orders
+    /// can still fill while a cancellation is on its way.
+    pub latency: Duration,
+    /// Available balance of each asset at the start
+    pub balances: HashMap<String, BigDecimal>,
+}
+
+impl Default for PaperConfig {
+    fn default() -> Self {
+        Self {
+            fill_model: FillModel::TopOfBook,
+            latency: Duration::from_secs(0),
+            balances: HashMap::new(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct PaperFill {
+    pub order_id: String,
+    pub market: String,
+    pub side: Side,
+    pub price: BigDecimal,
+    pub amount: BigDecimal,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum PaperOrderStatus {
+    /// Placed, but the latency hasn't passed yet
+    Pending,
+    Open,
+    Filled,
+    Cancelled,
+    /// Not accepted once it took effect, e.g. a post-only order that would have crossed
+    Rejected(String),
+}
+
+#[derive(Clone, Debug)]
+pub struct PaperOrder {
+    pub id: String,
+    pub market: MarketSymbol,
+    pub request: OrderRequest,
+    pub remaining: BigDecimal,
+    pub status: PaperOrderStatus,
+    active_at: Instant,
+    /// Estimated amount ahead of the order at its price, with `FillModel::QueuePosition`
+    queue_ahead: BigDecimal,
+    /// Amount at the order's price on the last book update
+    last_level: BigDecimal,
+}
+
+impl PaperOrder {
+    pub fn is_live(&self) -> bool {
+        matches!(
+            self.status,
+            PaperOrderStatus::Pending | PaperOrderStatus::Open
+        )
+    }
+
+    fn buy_or_sell(&self) -> BuyOrSell {
+        match self.request.side {
+            Side::Buy => BuyOrSell::Buy,
+            Side::Sell => BuyOrSell::Sell,
+        }
+    }
+
+    /// Asset and amount held back for what is left of the order
+    fn reserved(&self) -> (&str, BigDecimal) {
+        match self.request.side {
+            Side::Buy => (self.market.quote(), &self.remaining * &self.request.price),
+            Side::Sell => (self.market.base(), self.remaining.clone()),
+        }
+    }
+
+    /// Levels on the other side of `book` the order can take, best first
+    fn crossing(&self, book: &L2Book) -> Vec<Level> {
+        let price = &self.request.price;
+        match self.request.side {
+            Side::Buy => book
+                .asks(usize::MAX)
+                .into_iter()
+                .take_while(|ask| &ask.price <= price)
+                .collect(),
+            Side::Sell => book
+                .bids(usize::MAX)
+                .into_iter()
+                .take_while(|bid| &bid.price >= price)
+                .collect(),
+        }
+    }
+}
+
+/// Matching and balances of a paper venue, driven by book updates
+#[derive(Debug)]
+pub struct PaperEngine {
+    config: PaperConfig,
+    orders: BTreeMap<u64, PaperOrder>,
+    balances: BTreeMap<String, Balance>,
+    fills: VecDeque<PaperFill>,
+    next_id: u64,
+}
+
+impl PaperEngine {
+    pub fn new(config: PaperConfig) -> Self {
+        let mut balances = BTreeMap::new();
+        for (asset, available) in &config.balances {
+            balance_mut(&mut balances, &asset.to_lowercase()).available += available;
+        }
+        Self {
+            config,
+            orders: BTreeMap::new(),
+            balances,
+            fills: VecDeque::new(),
+            next_id: 0,
+        }
+    }
+
+    /// Accept an order, holding back the balance it needs. It takes effect after the latency.
+    pub fn place(&mut self, request: &OrderRequest, now: Instant) -> Result<OrderAck> {
+        let market: MarketSymbol = request.market.parse()?;
+        if !request.amount.is_positive() || !request.price.is_positive() {
+            return Err(ProtocolError(
+                "Paper order amount and price must be positive",
+            ));
+        }
+        self.next_id += 1;
+        let order = PaperOrder {
+            id: format!("paper-{}", self.next_id),
+            market,
+            request: request.clone(),
+            remaining: request.amount.clone(),
+            status: PaperOrderStatus::Pending,
+            active_at: now + self.config.latency,
+            queue_ahead: BigDecimal::zero(),
+            last_level: BigDecimal::zero(),
+        };
+        let (asset, reserved) = order.reserved();
+        let balance = balance_mut(&mut self.balances, asset);
+        if balance.available < reserved {
+            return Err(ProtocolError::coerce_static_from_str(&format!(
+                "Insufficient {} for paper order: {} available, {} needed",
+                asset, balance.available, reserved
+            )));
+        }
+        balance.available -= &reserved;
+        balance.in_orders += &reserved;
+        let ack = OrderAck {
+            order_id: order.id.clone(),
+        };
+        self.orders.insert(self.next_id, order);
+        self.forget_closed();
+        Ok(ack)
+    }
+
+    /// Cancel an order right away, releasing what it holds back. `PaperVenue` waits for the
+    /// latency before cancelling.
+    pub fn cancel(&mut self, order_id: &str) -> Result<()> {
+        let order = self
+            .orders
+            .values_mut()
+            .find(|order| order.id == order_id)
+            .ok_or(ProtocolError("Unknown paper order"))?;
+        if !order.is_live() {
+            return Err(ProtocolError("Paper order is no longer open"));
+        }
+        close(&mut self.balances, order, PaperOrderStatus::Cancelled);
+        self.forget_closed();
+        Ok(())
+    }
+
+    /// Match the live orders of `market` against `book`, returning the fills
+    pub fn on_book(
+        &mut self,
+        market: &MarketSymbol,
+        book: &L2Book,
+        now: Instant,
+    ) -> Vec<PaperFill> {
+        let fill_model = self.config.fill_model;
+        let balances = &mut self.balances;
+        // what orders took from the best level of each side during this update
+        let mut taken_asks = BigDecimal::zero();
+        let mut taken_bids = BigDecimal::zero();
+        let mut fills = Vec::new();
+        let live = self
+            .orders
+            .values_mut()
+            .filter(|order| &order.market == market && order.is_live());
+        for order in live {
+            if order.active_at > now {
+                continue;
+            }
+            let crossing = order.crossing(book);
+            if order.status == PaperOrderStatus::Pending {
+                order.status = PaperOrderStatus::Open;
+                if order.request.post_only && !crossing.is_empty() {
+                    let reason = "post-only order would have taken liquidity".to_string();
+                    close(balances, order, PaperOrderStatus::Rejected(reason));
+                    continue;
+                }
+                if order.request.time_in_force == TimeInForce::FillOrKill {
+                    let available: BigDecimal = crossing.iter().map(|level| &level.amount).sum();
+                    if available < order.remaining {
+                        close(balances, order, PaperOrderStatus::Cancelled);
+                        continue;
+                    }
+                    for level in crossing {
+                        if !order.is_live() {
+                            break;
+                        }
+                        let amount = level.amount.min(order.remaining.clone());
+                        fills.push(fill(balances, order, level.price, amount));
+                    }
+                    continue;
+                }
+                order.queue_ahead = book.amount_at(order.buy_or_sell(), &order.request.price);
+                order.last_level = order.queue_ahead.clone();
+            }
+            if let Some(best) = crossing.into_iter().next() {
+                let taken = match order.request.side {
+                    Side::Buy => &mut taken_asks,
+                    Side::Sell => &mut taken_bids,
+                };
+                let amount = (&best.amount - &*taken).min(order.remaining.clone());
+                if amount.is_positive() {
+                    *taken += &amount;
+                    fills.push(fill(balances, order, best.price, amount));
+                }
+            } else if fill_model == FillModel::QueuePosition {
+                let level = book.amount_at(order.buy_or_sell(), &order.request.price);
+                if level < order.last_level {
+                    order.queue_ahead -= &order.last_level - &level;
+                    if order.queue_ahead.is_negative() {
+                        let amount = (-&order.queue_ahead).min(order.remaining.clone());
+                        order.queue_ahead = BigDecimal::zero();
+                        let price = order.request.price.clone();
+                        fills.push(fill(balances, order, price, amount));
+                    }
+                }
+                order.last_level = level;
+            }
+            if order.request.time_in_force == TimeInForce::ImmediateOrCancel && order.is_live() {
+                close(balances, order, PaperOrderStatus::Cancelled);
+            }
+        }
+        self.fills.extend(fills.iter().cloned());
+        while self.fills.len() > RECENT_FILLS {
+            self.fills.pop_front();
+        }
+        self.forget_closed();
+        fills
+    }
+
+    /// Drop the oldest closed orders beyond `RECENTLY_CLOSED`. Live orders are always kept.
+    fn forget_closed(&mut self) {
+        let closed = self
+            .orders
+            .values()
+            .filter(|order| !order.is_live())
+            .count();
+        if closed <= RECENTLY_CLOSED {
+            return;
+        }
+        let forgotten: Vec<u64> = self
+            .orders
+            .iter()
+            .filter(|(_, order)| !order.is_live())
+            .map(|(id, _)| *id)
+            .take(closed - RECENTLY_CLOSED)
+            .collect();
+        for id in forgotten {
+            self.orders.remove(&id);
+        }
+    }
+
+    /// A live order, or one of the last `RECENTLY_CLOSED` closed ones
+    pub fn order(&self, order_id: &str) -> Option<&PaperOrder> {
+        self.orders.values().find(|order| order.id == order_id)
+    }
+
+    /// The last `RECENT_FILLS` fills, oldest first
+    pub fn fills(&self) -> impl Iterator<Item = &PaperFill> {
+        self.fills.iter()
+    }
+
+    pub fn balances(&self) -> Vec<Balance> {
+        self.balances.values().cloned().collect()
+    }
+}
+
+fn balance_mut<'a>(balances: &'a mut BTreeMap<String, Balance>, asset: &str) -> &'a mut Balance {
+    balances
+        .entry(asset.to_string())
+        .or_insert_with(|| Balance {
+            asset: asset.to_string(),
+            available: BigDecimal::zero(),
+            in_orders: BigDecimal::zero(),
+        })
+}
+
+/// Fill `amount` of `order` at `price`, settling balances. Buys hold back the limit price, so
+/// the difference to a better fill price is released.
+fn fill(
+    balances: &mut BTreeMap<String, Balance>,
+    order: &mut PaperOrder,
+    price: BigDecimal,
+    amount: BigDecimal,
+) -> PaperFill {
+    let (base, quote) = (order.market.base(), order.market.quote());
+    match order.request.side {
+        Side::Buy => {
+            let held = balance_mut(balances, quote);
+            held.in_orders -= &amount * &order.request.price;
+            held.available += &amount * (&order.request.price - &price);
+            balance_mut(balances, base).available += &amount;
+        }
+        Side::Sell => {
+            balance_mut(balances, base).in_orders -= &amount;
+            balance_mut(balances, quote).available += &amount * &price;
+        }
+    }
+    order.remaining -= &amount;
+    if order.remaining.is_zero() {
+        order.status = PaperOrderStatus::Filled;
+    }
+    PaperFill {
+        order_id: order.id.clone(),
+        market: order.market.to_string(),
+        side: order.request.side,
+        price,
+        amount,
+    }
+}
+
+/// End a live order, releasing what it still holds back
+fn close(
+    balances: &mut BTreeMap<String, Balance>,
+    order: &mut PaperOrder,
+    status: PaperOrderStatus,
+) {
+    if order.is_live() {
+        let (asset, reserved) = order.reserved();
+        let balance = balance_mut(balances, asset);
+        balance.in_orders -= &reserved;
+        balance.available += &reserved;
+    }
+    order.status = status;
+}
+
+/// Venue that fills orders against the live books of a `Client` instead of sending them.
+/// A market's book is maintained (see `OrderbookManager`) from the first order placed on it,
+/// and orders are matched on every update. Market data comes from the client.
+pub struct PaperVenue {
+    client: Client,
+    engine: Arc<SyncMutex<PaperEngine>>,
+    books: Mutex<HashMap<MarketSymbol, Arc<OrderbookManager>>>,
+    stop: CancellationToken,
+}
+
+impl PaperVenue {
+    pub fn new(client: Client, config: PaperConfig) -> Self {
+        Self {
+            client,
+            engine: Arc::new(SyncMutex::new(PaperEngine::new(config))),
+            books: Mutex::new(HashMap::new()),
+            stop: CancellationToken::new(),
+        }
+    }
+
+    pub fn order(&self, order_id: &str) -> Option<PaperOrder> {
+        self.engine.lock().unwrap().order(order_id).cloned()
+    }
+
+    /// Recent fills, oldest first
+    pub fn fills(&self) -> Vec<PaperFill> {
+        self.engine.lock().unwrap().fills().cloned().collect()
+    }
+
+    /// Book of `market`, maintained and matched against from the first call on
+    async fn book(&self, market: &MarketSymbol) -> Result<Arc<OrderbookManager>> {
+        let mut books = self.books.lock().await;
+        if let Some(book) = books.get(market) {
+            return Ok(book.clone());
+        }
+        let book = Arc::new(self.client.orderbook_manager(market.as_str()).await?);
+        tokio::spawn(match_updates(
+            book.clone(),
+            self.engine.clone(),
+            self.stop.clone(),
+        ));
+        books.insert(market.clone(), book.clone());
+        Ok(book)
+    }
+}
+
+impl Drop for PaperVenue {
+    fn drop(&mut self) {
+        self.stop.cancel();
+    }
+}
+
+async fn match_updates(
+    book: Arc<OrderbookManager>,
+    engine: Arc<SyncMutex<PaperEngine>>,
+    stop: CancellationToken,
+) {
+    let mut changes = book.changes();
+    loop {
+        let change = tokio::select! {
+            change = changes.recv() => change,
+            _ = stop.cancelled() => return,
+        };
+        match change {
+            Ok(OrderbookChange::Update { .. })
+            | Ok(OrderbookChange::Resnapshot { .. })
+            | Err(broadcast::error::RecvError::Lagged(_)) => {
+                let fills =
+                    engine
+                        .lock()
+                        .unwrap()
+                        .on_book(book.market(), &book.book(), Instant::now());
+                log_fills(&fills);
+            }
+            Ok(OrderbookChange::Desync(_)) => {}
+            Ok(OrderbookChange::Stopped(e)) => {
+                warn!(market = %book.market(), error = %e, "paper orders are no longer matched");
+                return;
+            }
+            Err(broadcast::error::RecvError::Closed) => return,
+        }
+    }
+}
+
+fn log_fills(fills: &[PaperFill]) {
+    for fill in fills {
+        info!(order_id = %fill.order_id, price = %fill.price, amount = %fill.amount, "paper fill");
+    }
+}
+
+impl Venue for PaperVenue {
+    type Error = ProtocolError;
+
+    fn venue_name(&self) -> &str {
+        "nash-paper"
+    }
+}
+
+#[async_trait]
+impl OrderEntry for PaperVenue {
+    async fn place_order(&self, order: &OrderRequest) -> Result<OrderAck> {
+        let market: MarketSymbol = order.market.parse()?;
+        let book = self.book(&market).await?;
+        let now = Instant::now();
+        let (ack, latency) = {
+            let mut engine = self.engine.lock().unwrap();
+            let ack = engine.place(order, now)?;
+            // without latency the order can fill right away
+            engine.on_book(&market, &book.book(), now);
+            (ack, engine.config.latency)
+        };
+        if latency > Duration::from_secs(0) {
+            // activate the order when the latency has passed, even if the book is quiet
+            let (engine, stop) = (self.engine.clone(), self.stop.clone());
+            tokio::spawn(async move {
+                tokio::select! {
+                    _ = tokio::time::sleep_until(now + latency) => {}
+                    _ = stop.cancelled() => return,
+                }
+                let fills = engine
+                    .lock()
+                    .unwrap()
+                    .on_book(&market, &book.book(), Instant::now());
+                log_fills(&fills);
+            });
+        }
+        Ok(ack)
+    }
+
+    /// Orders can still fill during the latency, after which the cancellation is applied
+    async fn cancel_order(&self, _market: &str, order_id: &str) -> Result<()> {
+        let latency = self.engine.lock().unwrap().config.latency;
+        tokio::time::sleep(latency).await;
+        self.engine.lock().unwrap().cancel(order_id)
+    }
+}
+
+#[async_trait]
+impl Balances for PaperVenue {
+    async fn balances(&self) -> Result<Vec<Balance>> {
+        Ok(self.engine.lock().unwrap().balances())
+    }
+}
+
+#[async_trait]
+impl MarketData for PaperVenue {
+    async fn top_of_book(&self, market: &str) -> Result<TopOfBook> {
+        self.client.top_of_book(market).await
+    }
+
+    async fn subscribe_top_of_book(
+        &self,
+        market: &str,
+    ) -> Result<BoxStream<'static, Result<TopOfBook>>> {
+        self.client.subscribe_top_of_book(market).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{FillModel, PaperConfig, PaperEngine, PaperOrderStatus, RECENTLY_CLOSED};
+    use crate::orderbook::L2Book;
+    use bigdecimal::BigDecimal;
+    use exchange_traits::{OrderRequest, Side, TimeInForce};
+    use nash_protocol::types::{MarketSymbol, OrderbookOrder};
+    use std::str::FromStr;
+    use std::time::Duration;
+    use tokio::time::Instant;
+
+    fn dec(value: &str) -> BigDecimal {
+        BigDecimal::from_str(value).unwrap()
+    }
+
+    fn book(update_id: i64, bids: &[(&str, &str)], asks: &[(&str, &str)]) -> L2Book {
+        let orders = |levels: &[(&str, &str)]| -> Vec<OrderbookOrder> {
+            levels
+                .iter()
+                .map(|(price, amount)| OrderbookOrder {
+                    price: price.to_string(),
+                    amount: dec(amount),
+                })
+                .collect()
+        };
+        L2Book::from_orders(update_id, &orders(bids), &orders(asks)).unwrap()
+    }
+
+    fn order(side: Side, amount: &str, price: &str, time_in_force: TimeInForce) -> OrderRequest {
+        OrderRequest {
+            market: "eth_usdc".to_string(),
+            side,
+            amount: dec(amount),
+            price: dec(price),
+            time_in_force,
+            post_only: false,
+        }
+    }
+
+    fn engine(fill_model: FillModel, latency: Duration) -> PaperEngine {
+        PaperEngine::new(PaperConfig {
+            fill_model,
+            latency,
+            balances: vec![
+                ("USDC".to_string(), dec("1000")),
+                ("eth".to_string(), dec("5")),
+            ]
+            .into_iter()
+            .collect(),
+        })
+    }
+
+    #[test]
+    fn crossing_orders_share_the_top_level() {
+        let market: MarketSymbol = "eth_usdc".parse().unwrap();
+        let mut engine = engine(FillModel::TopOfBook, Duration::from_secs(0));
+        let now = Instant::now();
+        let ack = engine
+            .place(
+                &order(Side::Buy, "1", "102", TimeInForce::GoodTilCancelled),
+                now,
+            )
+            .unwrap();
+        let ioc = engine
+            .place(
+                &order(Side::Buy, "3", "102", TimeInForce::ImmediateOrCancel),
+                now,
+            )
+            .unwrap();
+        let fills = engine.on_book(
+            &market,
+            &book(1, &[("99", "2")], &[("101", "1.5"), ("102", "5")]),
+            now,
+        );
+        // the first order takes 1 of the best level, leaving 0.5 to the second
+        assert_eq!(
+            fills
+                .iter()
+                .map(|fill| (fill.price.clone(), fill.amount.clone()))
+                .collect::<Vec<_>>(),
+            vec![(dec("101"), dec("1")), (dec("101"), dec("0.5"))]
+        );
+        assert_eq!(
+            engine.order(&ack.order_id).unwrap().status,
+            PaperOrderStatus::Filled
+        );
+        assert_eq!(
+            engine.order(&ioc.order_id).unwrap().status,
+            PaperOrderStatus::Cancelled
+        );
+        let usdc = engine
+            .balances()
+            .into_iter()
+            .find(|b| b.asset == "usdc")
+            .unwrap();
+        // fills pay the level's price, and what the limit price held back beyond it is released
+        assert_eq!((usdc.available, usdc.in_orders), (dec("848.5"), dec("0")));
+    }
+
+    #[test]
+    fn resting_orders_fill_once_the_queue_ahead_is_gone() {
+        let market: MarketSymbol = "eth_usdc".parse().unwrap();
+        let mut engine = engine(FillModel::QueuePosition, Duration::from_millis(100));
+        let now = Instant::now();
+        let ack = engine
+            .place(
+                &order(Side::Sell, "2", "101", TimeInForce::GoodTilCancelled),
+                now,
+            )
+            .unwrap();
+        let status = |engine: &PaperEngine| engine.order(&ack.order_id).unwrap().status.clone();
+        engine.on_book(&market, &book(1, &[("99", "1")], &[("101", "1")]), now);
+        assert_eq!(status(&engine), PaperOrderStatus::Pending);
+
+        let later = now + Duration::from_millis(100);
+        engine.on_book(&market, &book(2, &[("99", "1")], &[("101", "1")]), later);
+        assert_eq!(status(&engine), PaperOrderStatus::Open);
+        // orders joining behind don't change the queue; 1.5 leaving takes the 1 ahead first
+        assert!(engine
+            .on_book(&market, &book(3, &[("99", "1")], &[("101", "3")]), later)
+            .is_empty());
+        let fills = engine.on_book(&market, &book(4, &[("99", "1")], &[("101", "1.5")]), later);
+        assert_eq!(
+            (fills[0].price.clone(), fills[0].amount.clone()),
+            (dec("101"), dec("0.5"))
+        );
+        let fills = engine.on_book(&market, &book(5, &[("101", "4")], &[("102", "1")]), later);
+        assert_eq!(fills[0].amount, dec("1.5"));
+        assert_eq!(status(&engine), PaperOrderStatus::Filled);
+    }
+
+    #[test]
+    fn cancels_apply_at_once_and_old_closed_orders_are_forgotten() {
+        let mut engine = engine(FillModel::TopOfBook, Duration::from_secs(60));
+        let now = Instant::now();
+        let live = engine
+            .place(
+                &order(Side::Sell, "1", "150", TimeInForce::GoodTilCancelled),
+                now,
+            )
+            .unwrap();
+        let cancelled = engine
+            .place(
+                &order(Side::Buy, "2", "100", TimeInForce::GoodTilCancelled),
+                now,
+            )
+            .unwrap();
+        // no book update is needed for the cancel to take effect
+        engine.cancel(&cancelled.order_id).unwrap();
+        assert_eq!(
+            engine.order(&cancelled.order_id).unwrap().status,
+            PaperOrderStatus::Cancelled
+        );
+        assert!(engine.cancel(&cancelled.order_id).is_err());
+        let usdc = engine
+            .balances()
+            .into_iter()
+            .find(|b| b.asset == "usdc")
+            .unwrap();
+        assert_eq!((usdc.available, usdc.in_orders), (dec("1000"), dec("0")));
+
+        for _ in 0..RECENTLY_CLOSED {
+            let ack = engine
+                .place(
+                    &order(Side::Buy, "1", "100", TimeInForce::GoodTilCancelled),
+                    now,
+                )
+                .unwrap();
+            engine.cancel(&ack.order_id).unwrap();
+        }
+        assert!(engine.order(&cancelled.order_id).is_none());
+        assert!(engine.order(&live.order_id).unwrap().is_live());
+    }
+}
@@ -0,0 +1,50 @@
+//! The supported public API: the client, the requests it runs, the types they are built from
+//! and the events it reports. `use nash_native_client::prelude::*;` is all most programs
+//! need.
+//!
+//! Items are only removed from or changed in the prelude in a breaking release. Everything
+//! else is reachable through the modules of this crate and of `nash_protocol`, but may
+//! change between minor releases. That includes `nash_protocol::graphql`, which is
+//! generated from the exchange's GraphQL schema and changes whenever it is regenerated.
+
+pub use crate::batch::BatchPlacement;
+pub use crate::capture::CaptureBundle;
+pub use crate::config::ClientConfig;
+pub use crate::orderbook::{L2Book, Level, OrderbookChange, OrderbookDesync, OrderbookManager};
+pub use crate::prepared::PreparedOrder;
+pub use crate::{
+    CancellationToken, Client, ClientBuilder, ConnectionEvent, Environment, MarketEvent,
+    MarketSubscriptionHandle, SubscriptionHandle, Watchlist, WatchlistEvent,
+};
+
+pub use nash_protocol::errors::{ProtocolError, ResultExt};
+pub use nash_protocol::protocol::cancel_all_orders::CancelAllOrders;
+pub use nash_protocol::protocol::cancel_order::CancelOrderRequest;
+pub use nash_protocol::protocol::cancel_orders::CancelOrdersRequest;
+pub use nash_protocol::protocol::get_account_order::GetAccountOrderRequest;
+pub use nash_protocol::protocol::get_deposit_address::GetDepositAddressRequest;
+pub use nash_protocol::protocol::get_ticker::TickerRequest;
+pub use nash_protocol::protocol::list_account_activity::ListAccountActivityRequest;
+pub use nash_protocol::protocol::list_account_balances::ListAccountBalancesRequest;
+pub use nash_protocol::protocol::list_account_movements::ListAccountMovementsRequest;
+pub use nash_protocol::protocol::list_account_orders::ListAccountOrdersRequest;
+pub use nash_protocol::protocol::list_account_trades::ListAccountTradesRequest;
+pub use nash_protocol::protocol::list_candles::ListCandlesRequest;
+pub use nash_protocol::protocol::list_markets::ListMarketsRequest;
+pub use nash_protocol::protocol::list_tickers::ListTickersRequest;
+pub use nash_protocol::protocol::list_trades::ListTradesRequest;
+pub use nash_protocol::protocol::orderbook::OrderbookRequest;
+pub use nash_protocol::protocol::place_order::{
+    ChainSigningError, LimitOrderRequest, MarketOrderRequest, PlaceOrderResponse, RateBounds,
+    StopLimitOrderRequest,
+};
+pub use nash_protocol::protocol::place_orders::{
+    AmendOrderRequest, LimitOrdersRequest, MarketOrdersRequest, MixedOrder, MixedOrdersRequest,
+    OcoOrderRequest, OrderPlaced, OrderRejected,
+};
+pub use nash_protocol::protocol::withdraw::{WithdrawRequest, WithdrawResponse};
+pub use nash_protocol::protocol::ResponseOrError;
+pub use nash_protocol::types::{
+    Asset, Blockchain, BuyOrSell, Market, MarketSymbol, Order, OrderCancellationPolicy,
+    OrderStatus, OrderType, RoundingMode, Trade,
+};
@@ -0,0 +1,93 @@
+//! Limit orders prepared ahead of a signal, so placing them is one round trip. Everything
+//! that can be done early is: markets, fee rates, asset nonces and r values are fetched,
+//! states are signed if they are due, and the order is validated and fully signed.
+
+use std::time::Duration;
+
+use nash_protocol::errors::Result;
+use nash_protocol::protocol::place_order::{
+    LimitOrderRequest, PlaceOrderResponse, PreparedLimitOrder,
+};
+use nash_protocol::protocol::{NashProtocol, NonceBlock};
+
+use crate::Client;
+
+/// A signed limit order waiting to be placed, from `Client::prepare_limit_order`
+pub struct PreparedOrder {
+    client: Client,
+    order: PreparedLimitOrder,
+}
+
+impl PreparedOrder {
+    pub fn request(&self) -> &LimitOrderRequest {
+        self.order.request()
+    }
+
+    /// Time since the order was signed
+    pub fn age(&self) -> Duration {
+        self.order.age()
+    }
+
+    /// Whether `submit` can send the order as signed. If asset nonces changed in the
+    /// meantime, e.g. because states were signed, or the order got too old, `submit` signs
+    /// it again first.
+    pub async fn is_current(&self) -> bool {
+        self.order
+            .is_current(&*self.client.inner.state.read().await)
+    }
+
+    /// Place the order. It still goes through the price guard and approval checks, against
+    /// the market as it is now.
+    pub async fn submit(self) -> Result<PlaceOrderResponse> {
+        self.client
+            .run(self.order.submission())
+            .await?
+            .response_or_error()
+    }
+}
+
+impl Client {
+    /// Do all the work of placing `request` except sending it, so that `submit` on the result
+    /// only has to send the signed order. Prepare a fresh order for every placement: the
+    /// same prepared order can't be placed twice.
+    pub async fn prepare_limit_order(&self, request: LimitOrderRequest) -> Result<PreparedOrder> {
+        self.run_order_hooks(&request).await?;
+        let order = request.prepare(self.inner.state.clone()).await?;
+        Ok(PreparedOrder {
+            client: self.clone(),
+            order,
+        })
+    }
+
+    /// Same as `prepare_limit_order`, signing the order with the next nonce of `nonces`
+    pub async fn prepare_limit_order_with_nonce(
+        &self,
+        request: LimitOrderRequest,
+        nonces: &NonceBlock,
+    ) -> Result<PreparedOrder> {
+        self.run_order_hooks(&request).await?;
+        let order = request
+            .prepare_with_nonce(self.inner.state.clone(), nonces)
+            .await?;
+        Ok(PreparedOrder {
+            client: self.clone(),
+            order,
+        })
+    }
+
+    /// Reserve `n` order nonces for a planned burst of orders, e.g. for
+    /// `prepare_limit_order_with_nonce`. Orders placed by other tasks in the meantime get
+    /// nonces after the block.
+    pub async fn reserve_order_nonces(&self, n: usize) -> NonceBlock {
+        self.inner.state.read().await.reserve_order_nonce_block(n)
+    }
+
+    async fn run_order_hooks(&self, request: &LimitOrderRequest) -> Result<()> {
+        if let Some(hooks) = request.run_before(self.inner.state.clone()).await? {
+            for hook in hooks {
+                self.run(hook).await?;
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,66 @@
+//! Helpers for market making loops that keep replacing their orders
+
+use tracing::trace;
+
+use nash_protocol::errors::{ProtocolError, Result};
+use nash_protocol::protocol::cancel_order::CancelOrderRequest;
+use nash_protocol::protocol::place_order::{LimitOrderRequest, PlaceOrderResponse};
+use nash_protocol::protocol::place_orders::{AmendOrderRequest, AmendOrderResponse};
+use nash_protocol::protocol::ResponseOrError;
+
+use crate::config::RequoteLimits;
+use crate::Client;
+
+impl Client {
+    /// Replace order `replaces` (if any) with `order`, waiting first until the requote limits
+    /// of the order's market allow another cancel-replace cycle. The new order is only placed
+    /// once the cancellation has been acknowledged, and goes through the price guard.
+    pub async fn requote(
+        &self,
+        replaces: Option<String>,
+        order: LimitOrderRequest,
+    ) -> Result<ResponseOrError<PlaceOrderResponse>> {
+        self.inner.requote_throttle.acquire(&order.market).await;
+        if let Some(order_id) = replaces {
+            let cancel = CancelOrderRequest {
+                order_id: order_id.clone(),
+                market: order.market.clone(),
+            };
+            if let ResponseOrError::Error(error) = self.run(cancel).await? {
+                let message = error
+                    .errors
+                    .first()
+                    .map(|error| error.message.as_str())
+                    .unwrap_or("unknown error");
+                return Err(ProtocolError::coerce_static_from_str(&format!(
+                    "Requote: could not cancel order {}: {}",
+                    order_id, message
+                )));
+            }
+            trace!(%order_id, market = %order.market, "requote cancelled");
+        }
+        self.run(order).await
+    }
+
+    /// Cancel an order and place its replacement, see `AmendOrderRequest`. The replacement
+    /// is only placed once the cancellation is confirmed, and goes through the price guard.
+    pub async fn amend_order(&self, request: AmendOrderRequest) -> Result<AmendOrderResponse> {
+        let cancelled = self.run(request.cancel.clone()).await?.response_or_error();
+        let replacement = match request.replacement_after(&cancelled) {
+            Some(replacement) => self.run(replacement.clone()).await?.response_or_error(),
+            None => Err(ProtocolError(
+                "Replacement not placed, as the order could not be cancelled",
+            )),
+        };
+        trace!(order_id = %request.cancel.order_id, replaced = replacement.is_ok(), "amended order");
+        Ok(AmendOrderResponse {
+            cancelled,
+            replacement,
+        })
+    }
+
+    /// Change the limits applied by `requote`, see `RiskConfig::requote`
+    pub fn set_requote_limits(&self, limits: RequoteLimits) {
+        self.inner.requote_throttle.set_limits(limits);
+    }
+}
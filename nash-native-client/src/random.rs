@@ -0,0 +1,42 @@
+//! Randomness the client draws on for ids, replaceable so that test runs are reproducible
+
+use std::sync::Mutex as SyncMutex;
+
+use rand::rngs::StdRng;
+use rand::{RngCore, SeedableRng};
+
+use crate::Client;
+
+/// Random number generator shared by a client
+pub(crate) struct ClientRng {
+    rng: SyncMutex<Box<dyn RngCore + Send>>,
+}
+
+impl Default for ClientRng {
+    fn default() -> Self {
+        Self {
+            rng: SyncMutex::new(Box::new(StdRng::from_entropy())),
+        }
+    }
+}
+
+impl ClientRng {
+    pub(crate) fn next_u32(&self) -> u32 {
+        self.rng.lock().unwrap().next_u32()
+    }
+
+    /// Id for something the client keeps track of, e.g. a scheduled order
+    pub(crate) fn id(&self) -> String {
+        format!("{:016x}", self.rng.lock().unwrap().next_u64())
+    }
+}
+
+impl Client {
+    /// Replace the random number generator behind the ids of scheduled orders, trailing
+    /// stops and approvals and of request log spans, e.g. with a seeded `StdRng` in tests.
+    /// Signing is not affected: r-values are always generated from the operating system's
+    /// randomness, as reusing or predicting them would leak the signing key.
+    pub fn set_rng(&self, rng: impl RngCore + Send + 'static) {
+        *self.inner.rng.rng.lock().unwrap() = Box::new(rng);
+    }
+}
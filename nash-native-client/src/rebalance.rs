@@ -0,0 +1,302 @@
+//! Rebalancing of state channel balances towards target weights. Balances are valued in a
+//! quote asset at the mid price of each asset's market against it, and the trades needed to
+//! get back within tolerance are executed with `IocWithFallback`.
+
+use std::collections::HashMap;
+use std::time::Duration;
+
+use bigdecimal::{BigDecimal, Signed, Zero};
+use tracing::info;
+
+use nash_protocol::errors::{ProtocolError, Result};
+use nash_protocol::protocol::get_ticker::TickerRequest;
+use nash_protocol::protocol::list_account_balances::ListAccountBalancesRequest;
+use nash_protocol::types::{Asset, BuyOrSell, Market};
+
+use crate::execution::{IocWithFallback, ParentExecution};
+use crate::Client;
+
+/// Target portfolio and how closely it has to be tracked
+#[derive(Clone, Debug)]
+pub struct Rebalancer {
+    /// Asset every other asset is valued and traded against, e.g. USDC
+    pub quote: Asset,
+    /// Target share of the portfolio value per asset. Weights may add up to less than one;
+    /// the rest stays in the quote asset.
+    pub targets: HashMap<Asset, BigDecimal>,
+    /// How far (in weight, e.g. 0.02) an asset may drift from its target before it is traded
+    pub tolerance: BigDecimal,
+    /// Passed on to each `IocWithFallback`
+    pub max_slippage: BigDecimal,
+    pub fill_timeout: Duration,
+}
+
+/// Best prices of an asset's market against the quote asset
+#[derive(Clone, Debug)]
+pub struct MarketQuote {
+    pub market: Market,
+    pub best_bid: BigDecimal,
+    pub best_ask: BigDecimal,
+}
+
+impl MarketQuote {
+    fn mid(&self) -> BigDecimal {
+        (&self.best_bid + &self.best_ask) / BigDecimal::from(2)
+    }
+}
+
+/// One trade of a rebalance
+#[derive(Clone, Debug, PartialEq)]
+pub struct RebalanceTrade {
+    pub market: String,
+    pub buy_or_sell: BuyOrSell,
+    pub amount: BigDecimal,
+    /// Best price on the side the trade takes, used as the protective price
+    pub price: BigDecimal,
+    pub current_weight: BigDecimal,
+    pub target_weight: BigDecimal,
+}
+
+/// Trades a rebalance would make, sells first so their proceeds can fund the buys
+#[derive(Clone, Debug)]
+pub struct RebalancePlan {
+    /// Portfolio value in the quote asset
+    pub total_value: BigDecimal,
+    pub trades: Vec<RebalanceTrade>,
+}
+
+/// A plan and the executions of its trades, in plan order. Stops at the first trade that
+/// fails, so executions may be fewer than trades.
+#[derive(Clone, Debug)]
+pub struct Rebalance {
+    pub plan: RebalancePlan,
+    pub executions: Vec<ParentExecution>,
+}
+
+impl Rebalancer {
+    fn validate(&self) -> Result<()> {
+        let one = BigDecimal::from(1);
+        let mut total = BigDecimal::zero();
+        for weight in self.targets.values() {
+            if weight.is_negative() || weight > &one {
+                return Err(ProtocolError("Rebalance weights must be in [0, 1]"));
+            }
+            total += weight;
+        }
+        if total > one {
+            return Err(ProtocolError("Rebalance weights add up to more than one"));
+        }
+        if self.tolerance.is_negative() {
+            return Err(ProtocolError("Rebalance tolerance must not be negative"));
+        }
+        Ok(())
+    }
+
+    /// Trades that bring `balances` back within tolerance of the targets. `quotes` needs an
+    /// entry for every asset other than the quote asset with a non-zero balance or a target.
+    pub fn plan(
+        &self,
+        balances: &HashMap<Asset, BigDecimal>,
+        quotes: &HashMap<Asset, MarketQuote>,
+    ) -> Result<RebalancePlan> {
+        self.validate()?;
+        let zero = BigDecimal::zero();
+        let held = |asset: &Asset| balances.get(asset).map_or(false, |b| !b.is_zero());
+        let mut assets: Vec<Asset> = balances
+            .keys()
+            .chain(self.targets.keys())
+            .copied()
+            .filter(|asset| *asset != self.quote)
+            .filter(|asset| held(asset) || self.targets.contains_key(asset))
+            .collect();
+        assets.sort_by_key(|asset| asset.name());
+        assets.dedup();
+
+        let mut values = Vec::with_capacity(assets.len());
+        let mut total_value = balances.get(&self.quote).cloned().unwrap_or_default();
+        for asset in assets {
+            let quote = quotes.get(&asset).ok_or_else(|| {
+                ProtocolError::coerce_static_from_str(&format!(
+                    "No market quote for {} against {}",
+                    asset.name(),
+                    self.quote.name()
+                ))
+            })?;
+            let value = balances.get(&asset).unwrap_or(&zero) * quote.mid();
+            total_value += &value;
+            values.push((asset, quote, value));
+        }
+        if !total_value.is_positive() {
+            return Err(ProtocolError("Nothing to rebalance"));
+        }
+
+        let mut trades = Vec::new();
+        for (asset, quote, value) in values {
+            let current_weight = &value / &total_value;
+            let target_weight = self.targets.get(&asset).cloned().unwrap_or_default();
+            if (&current_weight - &target_weight).abs() <= self.tolerance {
+                continue;
+            }
+            let difference = &target_weight * &total_value - &value;
+            let precision = quote.market.asset_a.precision as i64;
+            let amount = (difference.abs() / quote.mid()).with_scale(precision);
+            if amount < quote.market.min_trade_size_a.amount.value || amount.is_zero() {
+                continue;
+            }
+            let (buy_or_sell, price) = if difference.is_positive() {
+                (BuyOrSell::Buy, quote.best_ask.clone())
+            } else {
+                (BuyOrSell::Sell, quote.best_bid.clone())
+            };
+            trades.push(RebalanceTrade {
+                market: quote.market.market_name(),
+                buy_or_sell,
+                amount,
+                price,
+                current_weight,
+                target_weight,
+            });
+        }
+        trades.sort_by_key(|trade| trade.buy_or_sell == BuyOrSell::Buy);
+        Ok(RebalancePlan {
+            total_value,
+            trades,
+        })
+    }
+}
+
+impl Client {
+    /// Work out the trades `rebalancer` would make now, without placing anything
+    pub async fn preview_rebalance(&self, rebalancer: &Rebalancer) -> Result<RebalancePlan> {
+        rebalancer.validate()?;
+        let balances = self
+            .run(ListAccountBalancesRequest { filter: None })
+            .await?
+            .response_or_error()?
+            .state_channel;
+        let mut quotes = HashMap::new();
+        for asset in balances.keys().chain(rebalancer.targets.keys()) {
+            let not_needed = !rebalancer.targets.contains_key(asset)
+                && balances.get(asset).map_or(true, BigDecimal::is_zero);
+            if *asset == rebalancer.quote || quotes.contains_key(asset) || not_needed {
+                continue;
+            }
+            // held assets without a market against the quote asset surface as a plan error
+            if let Some(quote) = self.market_quote(*asset, rebalancer.quote).await? {
+                quotes.insert(*asset, quote);
+            }
+        }
+        rebalancer.plan(&balances, &quotes)
+    }
+
+    /// Plan and execute a rebalance. Every trade goes through the price guard.
+    pub async fn rebalance(&self, rebalancer: &Rebalancer) -> Result<Rebalance> {
+        let plan = self.preview_rebalance(rebalancer).await?;
+        let mut executions = Vec::with_capacity(plan.trades.len());
+        for trade in &plan.trades {
+            info!(market = %trade.market, side = ?trade.buy_or_sell, amount = %trade.amount, "rebalancing");
+            let tactic = IocWithFallback {
+                market: trade.market.clone(),
+                buy_or_sell: trade.buy_or_sell,
+                amount: trade.amount.clone(),
+                protective_price: trade.price.clone(),
+                max_slippage: rebalancer.max_slippage.clone(),
+                fill_timeout: rebalancer.fill_timeout,
+            };
+            match self.execute_ioc_with_fallback(tactic).await {
+                Ok(execution) => executions.push(execution),
+                Err(e) => {
+                    return Err(e.context(format!(
+                        "Rebalance stopped after {} of {} trades",
+                        executions.len(),
+                        plan.trades.len()
+                    )))
+                }
+            }
+        }
+        Ok(Rebalance { plan, executions })
+    }
+
+    /// Best prices of `asset` against `quote`, or `None` if there is no such market or its
+    /// book is empty on either side
+    async fn market_quote(&self, asset: Asset, quote: Asset) -> Result<Option<MarketQuote>> {
+        let name = format!("{}_{}", asset.name(), quote.name());
+        let market = match self.inner.state.read().await.get_market(&name) {
+            Ok(market) => market,
+            Err(_) => return Ok(None),
+        };
+        let ticker = self
+            .run(TickerRequest { market: name })
+            .await?
+            .response_or_error()?;
+        Ok(match (ticker.best_bid_price, ticker.best_ask_price) {
+            (Some(best_bid), Some(best_ask)) => Some(MarketQuote {
+                market,
+                best_bid,
+                best_ask,
+            }),
+            _ => None,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Asset, BigDecimal, BuyOrSell, HashMap, MarketQuote, Rebalancer};
+    use nash_protocol::types::{Amount, AssetAmount, Market};
+    use std::str::FromStr;
+    use std::time::Duration;
+
+    fn dec(value: &str) -> BigDecimal {
+        BigDecimal::from_str(value).unwrap()
+    }
+
+    fn eth_usdc(bid: &str, ask: &str) -> MarketQuote {
+        let eth = Asset::ETH.with_precision(4);
+        let usdc = Asset::USDC.with_precision(2);
+        let min_size = |asset| AssetAmount {
+            asset,
+            amount: Amount::new("0.01", 4).unwrap(),
+        };
+        MarketQuote {
+            market: Market::new(eth.clone(), usdc.clone(), min_size(eth), min_size(usdc)),
+            best_bid: dec(bid),
+            best_ask: dec(ask),
+        }
+    }
+
+    #[test]
+    fn plans_trades_outside_tolerance() {
+        let mut rebalancer = Rebalancer {
+            quote: Asset::USDC,
+            targets: vec![(Asset::ETH, dec("0.5"))].into_iter().collect(),
+            tolerance: dec("0.05"),
+            max_slippage: dec("0.01"),
+            fill_timeout: Duration::from_secs(1),
+        };
+        let quotes: HashMap<_, _> = vec![(Asset::ETH, eth_usdc("199", "201"))]
+            .into_iter()
+            .collect();
+        let balances: HashMap<_, _> = vec![(Asset::ETH, dec("1")), (Asset::USDC, dec("600"))]
+            .into_iter()
+            .collect();
+
+        let plan = rebalancer.plan(&balances, &quotes).unwrap();
+        assert_eq!(plan.total_value, dec("800"));
+        assert_eq!(plan.trades.len(), 1);
+        let trade = &plan.trades[0];
+        assert_eq!(trade.buy_or_sell, BuyOrSell::Buy);
+        assert_eq!(trade.amount, dec("1"));
+        assert_eq!(trade.price, dec("201"));
+
+        rebalancer.tolerance = dec("0.3");
+        assert!(rebalancer
+            .plan(&balances, &quotes)
+            .unwrap()
+            .trades
+            .is_empty());
+
+        rebalancer.targets.insert(Asset::BTC, dec("0.6"));
+        assert!(rebalancer.plan(&balances, &quotes).is_err());
+    }
+}
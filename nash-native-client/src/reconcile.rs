@@ -0,0 +1,412 @@
+//! Cross-checks of the account's deposits and withdrawals against blockchain data from
+//! pluggable providers. The exchange API exposes movements but not state channel settlement
+//! events, so settlements are only covered through the movements that carry them.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::time::Duration;
+
+use async_trait::async_trait;
+use bigdecimal::num_bigint::BigInt;
+use bigdecimal::BigDecimal;
+use serde_json::json;
+
+use nash_protocol::errors::{ProtocolError, Result};
+use nash_protocol::protocol::list_account_movements::{
+    ListAccountMovementsRequest, Movement, MovementType,
+};
+use nash_protocol::protocol::withdraw::MovementStatus;
+use nash_protocol::types::Blockchain;
+
+use crate::Client;
+
+/// A transaction as seen on its blockchain
+#[derive(Clone, Debug, PartialEq)]
+pub struct ChainTransaction {
+    /// Blocks on top of and including the one with the transaction
+    pub confirmations: u64,
+    /// Whether the chain executed the transaction, e.g. the receipt status on Ethereum
+    pub succeeded: bool,
+    /// Amount moved in the movement's asset, if the provider can tell
+    pub quantity: Option<BigDecimal>,
+}
+
+/// Source of blockchain data for one chain
+#[async_trait]
+pub trait ChainProvider: Send + Sync {
+    /// Transaction with hash `hash`, or `None` if the chain doesn't have it (yet)
+    async fn transaction(&self, hash: &str) -> Result<Option<ChainTransaction>>;
+}
+
+/// Providers by blockchain. Movements on chains without a provider are reported as unchecked.
+#[derive(Default)]
+pub struct ChainProviders {
+    providers: HashMap<Blockchain, Box<dyn ChainProvider>>,
+}
+
+impl ChainProviders {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with(mut self, blockchain: Blockchain, provider: impl ChainProvider + 'static) -> Self {
+        self.providers.insert(blockchain, Box::new(provider));
+        self
+    }
+}
+
+/// What doesn't match between a movement and the chain
+#[derive(Clone, Debug, PartialEq)]
+pub enum Discrepancy {
+    /// The exchange says the movement completed but it has no transaction
+    CompletedWithoutTransaction,
+    /// The movement completed but the chain doesn't have its transaction
+    TransactionNotFound { hash: String },
+    /// The chain reverted a transaction the exchange doesn't consider failed
+    TransactionFailed { hash: String },
+    /// The exchange marked the movement failed but the chain executed its transaction
+    FailedButExecuted { hash: String },
+    /// The chain moved a different amount than recorded
+    QuantityMismatch {
+        recorded: BigDecimal,
+        on_chain: BigDecimal,
+    },
+    /// The exchange counted more confirmations than the chain has, e.g. after a reorg
+    ConfirmationsAhead { recorded: u64, on_chain: u64 },
+}
+
+/// A movement that doesn't match the chain
+#[derive(Clone, Debug, PartialEq)]
+pub struct MovementDiscrepancy {
+    pub movement_id: String,
+    pub discrepancy: Discrepancy,
+}
+
+/// A movement that could not be checked, e.g. for lack of a provider for its chain
+#[derive(Clone, Debug, PartialEq)]
+pub struct UncheckedMovement {
+    pub movement_id: String,
+    pub reason: String,
+}
+
+/// Outcome of `Client::reconcile_movements`
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ReconciliationReport {
+    /// Movements checked against the chain, with or without discrepancies
+    pub checked: usize,
+    pub discrepancies: Vec<MovementDiscrepancy>,
+    pub unchecked: Vec<UncheckedMovement>,
+}
+
+impl ReconciliationReport {
+    pub fn is_clean(&self) -> bool {
+        self.discrepancies.is_empty()
+    }
+}
+
+/// Compare `movement` with its transaction as found on chain, `None` if the chain doesn't have
+/// it. Transfers stay within the exchange and movements still being created have nothing on
+/// chain yet. A withdrawal may move its quantity with or without the fee deducted.
+pub fn reconcile_movement(
+    movement: &Movement,
+    on_chain: Option<&ChainTransaction>,
+) -> Vec<Discrepancy> {
+    if movement.movement_type == MovementType::Transfer {
+        return Vec::new();
+    }
+    let hash = match &movement.transaction_hash {
+        Some(hash) => hash,
+        None if movement.status == MovementStatus::Completed => {
+            return vec![Discrepancy::CompletedWithoutTransaction]
+        }
+        None => return Vec::new(),
+    };
+    let transaction = match on_chain {
+        Some(transaction) => transaction,
+        // a pending transaction may not be mined yet
+        None if movement.status == MovementStatus::Completed => {
+            return vec![Discrepancy::TransactionNotFound { hash: hash.clone() }]
+        }
+        None => return Vec::new(),
+    };
+    let mut discrepancies = Vec::new();
+    match (movement.status, transaction.succeeded) {
+        (MovementStatus::Failed, true) => {
+            discrepancies.push(Discrepancy::FailedButExecuted { hash: hash.clone() })
+        }
+        (MovementStatus::Failed, false) => {}
+        (_, false) => discrepancies.push(Discrepancy::TransactionFailed { hash: hash.clone() }),
+        _ => {}
+    }
+    if let Some(on_chain) = &transaction.quantity {
+        let net = movement.fee.as_ref().map(|fee| &movement.quantity - fee);
+        if on_chain != &movement.quantity && net.as_ref() != Some(on_chain) {
+            discrepancies.push(Discrepancy::QuantityMismatch {
+                recorded: movement.quantity.clone(),
+                on_chain: on_chain.clone(),
+            });
+        }
+    }
+    if let Some(recorded) = movement.confirmations {
+        let recorded = recorded.max(0) as u64;
+        if recorded > transaction.confirmations {
+            discrepancies.push(Discrepancy::ConfirmationsAhead {
+                recorded,
+                on_chain: transaction.confirmations,
+            });
+        }
+    }
+    discrepancies
+}
+
+impl Client {
+    /// Check every movement matching `request` against the chain it happened on
+    pub async fn reconcile_movements(
+        &self,
+        request: ListAccountMovementsRequest,
+        providers: &ChainProviders,
+    ) -> Result<ReconciliationReport> {
+        let movements = self.pages(request).all().await?;
+        let mut report = ReconciliationReport::default();
+        for movement in movements {
+            // transfers stay within the exchange
+            if movement.movement_type == MovementType::Transfer {
+                continue;
+            }
+            let provider = match providers.providers.get(&movement.blockchain) {
+                Some(provider) => provider,
+                None => {
+                    report.unchecked.push(UncheckedMovement {
+                        movement_id: movement.id.clone(),
+                        reason: format!("no provider for {:?}", movement.blockchain),
+                    });
+                    continue;
+                }
+            };
+            let on_chain = match &movement.transaction_hash {
+                Some(hash) => match provider.transaction(hash).await {
+                    Ok(transaction) => transaction,
+                    Err(e) => {
+                        report.unchecked.push(UncheckedMovement {
+                            movement_id: movement.id.clone(),
+                            reason: e.report(),
+                        });
+                        continue;
+                    }
+                },
+                None => None,
+            };
+            report.checked += 1;
+            for discrepancy in reconcile_movement(&movement, on_chain.as_ref()) {
+                report.discrepancies.push(MovementDiscrepancy {
+                    movement_id: movement.id.clone(),
+                    discrepancy,
+                });
+            }
+        }
+        Ok(report)
+    }
+}
+
+/// Ethereum transactions from a JSON-RPC node. Amounts are only known for plain ether
+/// transfers; token transfers are checked for status and confirmations only.
+pub struct EthereumRpc {
+    client: reqwest::Client,
+    url: String,
+}
+
+impl EthereumRpc {
+    pub fn new(url: &str, timeout: Duration) -> Result<Self> {
+        let client = reqwest::Client::builder()
+            .timeout(timeout)
+            .build()
+            .map_err(|e| ProtocolError::with_source("Could not initialize reqwest client", e))?;
+        Ok(Self {
+            client,
+            url: url.to_string(),
+        })
+    }
+
+    async fn call(&self, method: &str, params: serde_json::Value) -> Result<serde_json::Value> {
+        let mut response: serde_json::Value = self
+            .client
+            .post(&self.url)
+            .json(&json!({"jsonrpc": "2.0", "id": 1, "method": method, "params": params}))
+            .send()
+            .await
+            .map_err(|e| ProtocolError::with_source(format!("Could not call {}", method), e))?
+            .json()
+            .await
+            .map_err(|e| {
+                ProtocolError::with_source(format!("Could not parse {} response", method), e)
+            })?;
+        if let Some(error) = response.get_mut("error") {
+            let error = JsonRpcError(error.take());
+            return Err(ProtocolError::with_source(
+                format!("{} failed", method),
+                error,
+            ));
+        }
+        Ok(response["result"].take())
+    }
+}
+
+/// Error object of a JSON-RPC response
+#[derive(Debug)]
+struct JsonRpcError(serde_json::Value);
+
+impl fmt::Display for JsonRpcError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "JSON-RPC error {}", self.0)
+    }
+}
+
+impl std::error::Error for JsonRpcError {}
+
+/// Value of a JSON-RPC quantity such as `"0x1b4"`
+fn parse_quantity(value: &serde_json::Value) -> Result<u128> {
+    value
+        .as_str()
+        .and_then(|hex| hex.strip_prefix("0x"))
+        .and_then(|hex| u128::from_str_radix(hex, 16).ok())
+        .ok_or_else(|| {
+            ProtocolError::coerce_static_from_str(&format!("Invalid JSON-RPC quantity {}", value))
+        })
+}
+
+/// Ether amount of `wei`
+fn wei_to_ether(wei: u128) -> BigDecimal {
+    BigDecimal::new(BigInt::from(wei), 18)
+}
+
+#[async_trait]
+impl ChainProvider for EthereumRpc {
+    async fn transaction(&self, hash: &str) -> Result<Option<ChainTransaction>> {
+        let receipt = self
+            .call("eth_getTransactionReceipt", json!([hash]))
+            .await?;
+        if receipt.is_null() {
+            return Ok(None);
+        }
+        let block = parse_quantity(&receipt["blockNumber"])?;
+        let head = parse_quantity(&self.call("eth_blockNumber", json!([])).await?)?;
+        let transaction = self.call("eth_getTransactionByHash", json!([hash])).await?;
+        let quantity = match transaction["input"].as_str() {
+            Some("0x") | Some("") => Some(wei_to_ether(parse_quantity(&transaction["value"])?)),
+            _ => None,
+        };
+        Ok(Some(ChainTransaction {
+            confirmations: (head + 1).saturating_sub(block) as u64,
+            succeeded: parse_quantity(&receipt["status"])? == 1,
+            quantity,
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_quantity, reconcile_movement, wei_to_ether, ChainTransaction, Discrepancy};
+    use bigdecimal::BigDecimal;
+    use nash_protocol::protocol::list_account_movements::{Movement, MovementType};
+    use nash_protocol::protocol::withdraw::MovementStatus;
+    use nash_protocol::types::Blockchain;
+    use std::str::FromStr;
+
+    fn withdrawal(status: MovementStatus, hash: Option<&str>) -> Movement {
+        Movement {
+            id: "m1".to_string(),
+            movement_type: MovementType::Withdrawal,
+            status,
+            currency: "eth".to_string(),
+            quantity: BigDecimal::from_str("1.5").unwrap(),
+            fee: Some(BigDecimal::from_str("0.01").unwrap()),
+            blockchain: Blockchain::Ethereum,
+            address: None,
+            target_address: Some("0xabc".to_string()),
+            transaction_hash: hash.map(str::to_string),
+            confirmations: Some(12),
+            received_at: None,
+        }
+    }
+
+    fn mined(quantity: &str) -> ChainTransaction {
+        ChainTransaction {
+            confirmations: 20,
+            succeeded: true,
+            quantity: Some(BigDecimal::from_str(quantity).unwrap()),
+        }
+    }
+
+    #[test]
+    fn matching_movements_have_no_discrepancies() {
+        let movement = withdrawal(MovementStatus::Completed, Some("0x1"));
+        assert!(reconcile_movement(&movement, Some(&mined("1.5"))).is_empty());
+        // the fee may be deducted from what is sent
+        assert!(reconcile_movement(&movement, Some(&mined("1.49"))).is_empty());
+        // nothing on chain yet for a pending withdrawal
+        let pending = withdrawal(MovementStatus::Pending, Some("0x1"));
+        assert!(reconcile_movement(&pending, None).is_empty());
+        let mut transfer = withdrawal(MovementStatus::Completed, None);
+        transfer.movement_type = MovementType::Transfer;
+        assert!(reconcile_movement(&transfer, None).is_empty());
+    }
+
+    #[test]
+    fn mismatches_are_reported() {
+        let completed = withdrawal(MovementStatus::Completed, Some("0x1"));
+        assert_eq!(
+            reconcile_movement(&withdrawal(MovementStatus::Completed, None), None),
+            vec![Discrepancy::CompletedWithoutTransaction]
+        );
+        assert_eq!(
+            reconcile_movement(&completed, None),
+            vec![Discrepancy::TransactionNotFound {
+                hash: "0x1".to_string()
+            }]
+        );
+        assert_eq!(
+            reconcile_movement(&completed, Some(&mined("1.4"))),
+            vec![Discrepancy::QuantityMismatch {
+                recorded: BigDecimal::from_str("1.5").unwrap(),
+                on_chain: BigDecimal::from_str("1.4").unwrap(),
+            }]
+        );
+        let reverted = ChainTransaction {
+            confirmations: 3,
+            succeeded: false,
+            quantity: None,
+        };
+        assert_eq!(
+            reconcile_movement(&completed, Some(&reverted)),
+            vec![
+                Discrepancy::TransactionFailed {
+                    hash: "0x1".to_string()
+                },
+                Discrepancy::ConfirmationsAhead {
+                    recorded: 12,
+                    on_chain: 3
+                }
+            ]
+        );
+        assert_eq!(
+            reconcile_movement(
+                &withdrawal(MovementStatus::Failed, Some("0x1")),
+                Some(&mined("1.5"))
+            ),
+            vec![Discrepancy::FailedButExecuted {
+                hash: "0x1".to_string()
+            }]
+        );
+    }
+
+    #[test]
+    fn json_rpc_quantities_are_parsed() {
+        assert_eq!(parse_quantity(&serde_json::json!("0x1b4")).unwrap(), 436);
+        assert!(parse_quantity(&serde_json::json!("1b4")).is_err());
+        assert!(parse_quantity(&serde_json::Value::Null).is_err());
+        assert_eq!(
+            wei_to_ether(1_500_000_000_000_000_000),
+            BigDecimal::from_str("1.5").unwrap()
+        );
+    }
+}
@@ -0,0 +1,462 @@
+//! Multi-party approval of large limit orders. Above a notional threshold an order is only
+//! submitted once enough approvers have signed off on it, with the same kind of payload
+//! signature the client puts on its own requests.
+
+use std::collections::{BTreeMap, HashMap};
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use bigdecimal::BigDecimal;
+use serde::{Deserialize, Serialize};
+use tracing::warn;
+
+use nash_protocol::errors::{ProtocolError, Result};
+use nash_protocol::protocol::place_order::LimitOrderRequest;
+use nash_protocol::protocol::{general_canonical_string, verify_canonical_string};
+use nash_protocol::types::BuyOrSell;
+
+use crate::ws_client::InnerClient;
+
+/// Orders whose notional (amount times price, in the quote asset) reaches `threshold` need
+/// `required` distinct approvals out of `approvers` before they are submitted
+#[derive(Clone, Debug)]
+pub struct ApprovalPolicy {
+    threshold: BigDecimal,
+    approvers: Vec<String>,
+    required: usize,
+    ttl: Duration,
+    store: Option<PathBuf>,
+}
+
+impl ApprovalPolicy {
+    /// `approvers` are hex encoded secp256k1 public keys. Approval requests expire after `ttl`.
+    pub fn new(
+        threshold: BigDecimal,
+        approvers: Vec<String>,
+        required: usize,
+        ttl: Duration,
+    ) -> Result<Self> {
+        if required == 0 || required > approvers.len() {
+            return Err(ProtocolError(
+                "Approval policy needs between 1 and the number of approvers approvals",
+            ));
+        }
+        Ok(Self {
+            threshold,
+            approvers: approvers
+                .into_iter()
+                .map(|key| key.to_lowercase())
+                .collect(),
+            required,
+            ttl,
+            store: None,
+        })
+    }
+
+    /// Keep pending approvals as JSON files in `dir`, so they survive restarts
+    pub fn with_store(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.store = Some(dir.into());
+        self
+    }
+
+    pub fn requires_approval(&self, amount: &BigDecimal, price: &BigDecimal) -> bool {
+        amount * price >= self.threshold
+    }
+}
+
+/// A large order waiting for approvals. Approvers sign `canonical_string` with their key.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct PendingApproval {
+    pub id: String,
+    pub market: String,
+    pub buy_or_sell: BuyOrSell,
+    pub amount: String,
+    pub price: String,
+    /// Unix seconds
+    pub expires_at: u64,
+    /// DER encoded signatures over `canonical_string`, by approver public key
+    pub approvals: BTreeMap<String, String>,
+}
+
+impl PendingApproval {
+    pub fn canonical_string(&self) -> String {
+        let payload = serde_json::json!({
+            "payload": {
+                "id": self.id,
+                "market": self.market,
+                "buy_or_sell": self.buy_or_sell,
+                "amount": self.amount,
+                "price": self.price,
+                "expires_at": self.expires_at,
+            }
+        });
+        general_canonical_string("approve_limit_order".to_string(), payload, vec![])
+    }
+
+    pub fn is_expired(&self) -> bool {
+        unix_now() >= self.expires_at
+    }
+
+    fn matches(&self, market: &str, buy_or_sell: BuyOrSell, amount: &str, price: &str) -> bool {
+        self.market == market
+            && self.buy_or_sell == buy_or_sell
+            && same_decimal(&self.amount, amount)
+            && same_decimal(&self.price, price)
+    }
+}
+
+/// Approval policy with its pending approvals
+pub(crate) struct Approvals {
+    policy: ApprovalPolicy,
+    pending: Mutex<HashMap<String, PendingApproval>>,
+}
+
+impl Approvals {
+    /// Set up `policy`, picking up approvals left pending in its store
+    pub(crate) fn new(policy: ApprovalPolicy) -> Result<Self> {
+        let mut pending = HashMap::new();
+        if let Some(dir) = &policy.store {
+            std::fs::create_dir_all(dir).map_err(|e| {
+                ProtocolError::with_source(
+                    format!("Could not create approval store {}", dir.display()),
+                    e,
+                )
+            })?;
+            let entries = std::fs::read_dir(dir).map_err(|e| {
+                ProtocolError::with_source(
+                    format!("Could not read approval store {}", dir.display()),
+                    e,
+                )
+            })?;
+            for entry in entries.flatten() {
+                let approval: Option<PendingApproval> = std::fs::read_to_string(entry.path())
+                    .ok()
+                    .and_then(|contents| serde_json::from_str(&contents).ok());
+                match approval {
+                    Some(approval) if !approval.is_expired() => {
+                        pending.insert(approval.id.clone(), approval);
+                    }
+                    Some(_) => remove_file(&entry.path()),
+                    None => warn!(path = %entry.path().display(), "ignoring unreadable approval"),
+                }
+            }
+        }
+        Ok(Self {
+            policy,
+            pending: Mutex::new(pending),
+        })
+    }
+
+    fn request(&self, id: String, order: &LimitOrderRequest) -> Result<PendingApproval> {
+        let approval = PendingApproval {
+            id,
+            market: order.market.clone(),
+            buy_or_sell: order.buy_or_sell,
+            amount: order.amount.clone(),
+            price: order.price.clone(),
+            expires_at: unix_now() + self.policy.ttl.as_secs(),
+            approvals: BTreeMap::new(),
+        };
+        self.persist(&approval)?;
+        self.pending
+            .lock()
+            .unwrap()
+            .insert(approval.id.clone(), approval.clone());
+        Ok(approval)
+    }
+
+    fn approve(&self, id: &str, approver: &str, signature: &str) -> Result<PendingApproval> {
+        let approver = approver.to_lowercase();
+        if !self.policy.approvers.contains(&approver) {
+            return Err(ProtocolError("Not an approver of this policy"));
+        }
+        let mut pending = self.pending.lock().unwrap();
+        let approval = pending
+            .get_mut(id)
+            .ok_or(ProtocolError("No pending approval with this id"))?;
+        if approval.is_expired() {
+            return Err(ProtocolError("Approval request has expired"));
+        }
+        if !verify_canonical_string(&approver, &approval.canonical_string(), signature) {
+            return Err(ProtocolError("Invalid approval signature"));
+        }
+        approval.approvals.insert(approver, signature.to_string());
+        let approval = approval.clone();
+        drop(pending);
+        self.persist(&approval)?;
+        Ok(approval)
+    }
+
+    /// Let an order through if it is below the threshold or fully approved, returning the id
+    /// of the approval it needs, if any. Approvals in `taken` are already used by other
+    /// orders of the same request. Nothing is consumed here, see `consume`.
+    fn check(
+        &self,
+        market: &str,
+        buy_or_sell: BuyOrSell,
+        amount: &str,
+        price: &str,
+        taken: &[String],
+    ) -> Result<Option<String>> {
+        let (amount_value, price_value) =
+            match (BigDecimal::from_str(amount), BigDecimal::from_str(price)) {
+                (Ok(amount), Ok(price)) => (amount, price),
+                _ => return Err(ProtocolError("Could not parse order amount or price")),
+            };
+        if !self.policy.requires_approval(&amount_value, &price_value) {
+            return Ok(None);
+        }
+        let pending = self.pending.lock().unwrap();
+        let approved = pending
+            .values()
+            .find(|approval| {
+                approval.matches(market, buy_or_sell, amount, price)
+                    && !approval.is_expired()
+                    && approval.approvals.len() >= self.policy.required
+                    && !taken.contains(&approval.id)
+            })
+            .map(|approval| approval.id.clone());
+        match approved {
+            Some(id) => Ok(Some(id)),
+            None => Err(ProtocolError::coerce_static_from_str(&format!(
+                "{} order of {} at {} on {} needs {} approvals, see Client::request_approval",
+                buy_or_sell_name(buy_or_sell),
+                amount,
+                price,
+                market,
+                self.policy.required
+            ))),
+        }
+    }
+
+    /// Approvals are single use: drop them once their orders were submitted
+    fn consume(&self, ids: &[String]) {
+        let mut pending = self.pending.lock().unwrap();
+        for id in ids {
+            if pending.remove(id).is_some() {
+                self.unpersist(id);
+            }
+        }
+    }
+
+    fn persist(&self, approval: &PendingApproval) -> Result<()> {
+        let dir = match &self.policy.store {
+            Some(dir) => dir,
+            None => return Ok(()),
+        };
+        let contents = serde_json::to_string_pretty(approval)
+            .map_err(|e| ProtocolError::with_source("Could not serialize pending approval", e))?;
+        std::fs::write(dir.join(format!("{}.json", approval.id)), contents)
+            .map_err(|e| ProtocolError::with_source("Could not store approval", e))
+    }
+
+    fn unpersist(&self, id: &str) {
+        if let Some(dir) = &self.policy.store {
+            remove_file(&dir.join(format!("{}.json", id)));
+        }
+    }
+}
+
+impl InnerClient {
+    /// Run the approval policy, if one is set, against the limit orders of a request. Returns
+    /// the approvals to hand to `consume_approvals` once the request was submitted.
+    pub(crate) fn check_approval(
+        &self,
+        limit_prices: Vec<(&str, &str)>,
+        limit_sizes: Vec<(BuyOrSell, &str)>,
+    ) -> Result<Vec<String>> {
+        let approvals = match self.approvals.read().unwrap().clone() {
+            Some(approvals) => approvals,
+            None => return Ok(Vec::new()),
+        };
+        let mut approved = Vec::new();
+        for ((market, price), (buy_or_sell, amount)) in limit_prices.into_iter().zip(limit_sizes) {
+            if let Some(id) = approvals.check(market, buy_or_sell, amount, price, &approved)? {
+                approved.push(id);
+            }
+        }
+        Ok(approved)
+    }
+
+    /// Use up the approvals `check_approval` returned, if the request they were for was
+    /// `submitted`. Otherwise they stay valid for another attempt.
+    pub(crate) fn consume_approvals(&self, approved: &[String], submitted: bool) {
+        if approved.is_empty() || !submitted {
+            return;
+        }
+        if let Some(approvals) = self.approvals.read().unwrap().clone() {
+            approvals.consume(approved);
+        }
+    }
+
+    pub(crate) fn approvals(&self) -> Result<std::sync::Arc<Approvals>> {
+        self.approvals
+            .read()
+            .unwrap()
+            .clone()
+            .ok_or(ProtocolError("No approval policy set"))
+    }
+}
+
+impl crate::Client {
+    /// Require approvals for large limit orders (`None` disables the policy). Applies to all
+    /// ways of placing limit orders, including the unguarded ones.
+    pub fn set_approval_policy(&self, policy: Option<ApprovalPolicy>) -> Result<()> {
+        let approvals = match policy {
+            Some(policy) => Some(std::sync::Arc::new(Approvals::new(policy)?)),
+            None => None,
+        };
+        *self.inner.approvals.write().unwrap() = approvals;
+        Ok(())
+    }
+
+    /// Open an approval request for `order`. Once enough approvers signed its canonical
+    /// string through `approve_order`, the order can be placed as usual.
+    pub fn request_approval(&self, order: &LimitOrderRequest) -> Result<PendingApproval> {
+        self.inner.approvals()?.request(self.inner.rng.id(), order)
+    }
+
+    /// Add an approver's signature to a pending approval
+    pub fn approve_order(
+        &self,
+        id: &str,
+        approver: &str,
+        signature: &str,
+    ) -> Result<PendingApproval> {
+        self.inner.approvals()?.approve(id, approver, signature)
+    }
+
+    /// Approval requests that are still open
+    pub fn pending_approvals(&self) -> Result<Vec<PendingApproval>> {
+        let approvals = self.inner.approvals()?;
+        let pending = approvals.pending.lock().unwrap();
+        Ok(pending
+            .values()
+            .filter(|approval| !approval.is_expired())
+            .cloned()
+            .collect())
+    }
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|since| since.as_secs())
+        .unwrap_or(0)
+}
+
+fn same_decimal(a: &str, b: &str) -> bool {
+    match (BigDecimal::from_str(a), BigDecimal::from_str(b)) {
+        (Ok(a), Ok(b)) => a == b,
+        _ => false,
+    }
+}
+
+fn buy_or_sell_name(buy_or_sell: BuyOrSell) -> &'static str {
+    match buy_or_sell {
+        BuyOrSell::Buy => "Buy",
+        BuyOrSell::Sell => "Sell",
+    }
+}
+
+fn remove_file(path: &std::path::Path) {
+    if let Err(e) = std::fs::remove_file(path) {
+        warn!(path = %path.display(), error = %e, "could not remove approval file");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ApprovalPolicy, Approvals, BigDecimal, BuyOrSell};
+    use nash_protocol::protocol::place_order::LimitOrderRequest;
+    use nash_protocol::protocol::Signer;
+    use nash_protocol::types::OrderCancellationPolicy;
+    use std::time::Duration;
+
+    const KEY: &str = "eyJjaGlsZF9rZXlzIjp7fSwKICAgICAgICAicGFpbGxpZXJfcGsiOnsibiI6IjU5ODdlNjIyMjYxY2FmOTZlMjU4MjZjNzBjZjMyM2IyNjE5NGZmOWNmZTY5ZTNmNDBmMzBkMzA2NTcxNjQyY2FlYThhMzE0M2QxMWZmOTRjMTM4ODM2MDQ4NjczNTdhZThjMGU2NjNiZjAzZDAwOTMwMTZkN2Y0ZDc5MGFlMjRlMjkxNzgwM2Q4MTJiNjQxYWYyZDZjMDk1NzNkMTEyZWI3Njg2NDY1MjkxY2QxNDZmZDY2MmY3N2Y1OTVlZjgzMjc3YmUxNjgwZDA0MGIxZjNjNDk5YzgxOTE3NTcyMDZlNTEwYWU1NDcyNGQ2NjdmYzA0MWEyYzdjMmZmM2QzYjY2YzM3MjlkYzI1ZTAyYzQwMTllZDNhMDEyZmQ3NWVjMGUwMzk0OGNmNzgzYWQzOTAyY2U1ZTVlNzIyMjljM2RkM2ExNGI5MzRkNjAyNjlhY2I3YmEwYmQ0MTVkMmRlMTI4ZWYxODcyMjQwMGJhZWEyZTg1MGU2ZDFmZDg3ODdhMDEzMGQ1MTYyMDZkNzE4YTQ5ZDdhMjFkNDI4YjBmYTM3NzMwNzliNjQ4NjE4MTExOTFiNTUwMDFkNGMyYzI5ZjYzMDMxNGJlMTkxY2YzY2EzZjBmOGUwOWVlMDk1NDNmZmRkYTNmOTdjZjE2OWQ1MmUwNjdjZmQ0MGNiMzAzOTQxIn0sCiAgICAgICAgInBheWxvYWRfcHVibGljX2tleSI6IjA0NjE2NDZmZGM0NTQ0ZjEwMjk0ZTIwZTk5NGNlNTZkOGMwZmY4NTI1OTZlYjZiM2FhMGJhOWQ0YjIwNzlkODZkNDJiM2I1ZTg0OTFhNDhmZjZlMTYyMDczMjU3OTgwNzkxNmVlYjA3YmViNmY5OTcwZGM1OTUyYmQ0NDQ0MDRmNzQiLAogICAgICAgICJwYXlsb2FkX3NpZ25pbmdfa2V5IjoiYmI4YmNmNTJhNWY5NDRmMzUxYzViYzg1NmI3YTRjNDFhNWYzNzBmNWNlOTlkY2UwYzhkNmYxZDQ5MWNkMzRiZiIsCiAgICAgICAgInZlcnNpb24iOjB9";
+
+    fn large_order() -> LimitOrderRequest {
+        LimitOrderRequest {
+            market: "eth_usdc".to_string(),
+            client_order_id: None,
+            buy_or_sell: BuyOrSell::Buy,
+            amount: "100".to_string(),
+            price: "200".to_string(),
+            cancellation_policy: OrderCancellationPolicy::GoodTilCancelled,
+            allow_taker: true,
+        }
+    }
+
+    #[test]
+    fn large_orders_need_approval() {
+        let policy = ApprovalPolicy::new(
+            BigDecimal::from(10_000),
+            vec!["02ab".to_string(), "03cd".to_string()],
+            2,
+            Duration::from_secs(600),
+        )
+        .unwrap();
+        assert!(
+            ApprovalPolicy::new(BigDecimal::from(1), vec![], 1, Duration::from_secs(1)).is_err()
+        );
+        let approvals = Approvals::new(policy).unwrap();
+        assert_eq!(
+            approvals
+                .check("eth_usdc", BuyOrSell::Buy, "1", "200", &[])
+                .unwrap(),
+            None
+        );
+        assert!(approvals
+            .check("eth_usdc", BuyOrSell::Buy, "100", "200", &[])
+            .is_err());
+
+        let pending = approvals.request("1".to_string(), &large_order()).unwrap();
+        assert!(approvals.approve(&pending.id, "04ef", "3006").is_err());
+        assert!(approvals.approve(&pending.id, "02ab", "3006").is_err());
+        // not approved yet
+        assert!(approvals
+            .check("eth_usdc", BuyOrSell::Buy, "100.0", "200", &[])
+            .is_err());
+        assert!(pending
+            .canonical_string()
+            .starts_with("approve_limit_order,"));
+    }
+
+    #[test]
+    fn approvals_are_used_up_once_submitted() {
+        let approver = Signer::from_data(KEY, "").unwrap();
+        let public_key = approver.request_payload_public_key();
+        let policy = ApprovalPolicy::new(
+            BigDecimal::from(10_000),
+            vec![public_key.clone()],
+            1,
+            Duration::from_secs(600),
+        )
+        .unwrap();
+        let approvals = Approvals::new(policy).unwrap();
+        let pending = approvals.request("1".to_string(), &large_order()).unwrap();
+        let signature = approver
+            .sign_canonical_string(&pending.canonical_string())
+            .unwrap();
+        let approved = approvals
+            .approve(
+                &pending.id,
+                &public_key.to_uppercase(),
+                &signature.signed_digest,
+            )
+            .unwrap();
+        assert_eq!(approved.approvals.len(), 1);
+
+        let check =
+            |taken: &[String]| approvals.check("eth_usdc", BuyOrSell::Buy, "100", "200.0", taken);
+        assert_eq!(check(&[]).unwrap(), Some(pending.id.clone()));
+        // another order of the same request can't use it as well
+        assert!(check(&[pending.id.clone()]).is_err());
+        // checking doesn't use it up, a failed send can be retried
+        assert!(check(&[]).is_ok());
+        approvals.consume(&[pending.id.clone()]);
+        assert!(check(&[]).is_err());
+    }
+}
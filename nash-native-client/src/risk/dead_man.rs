@@ -0,0 +1,274 @@
+//! Dead man's switch: once armed, all open orders are cancelled if the switch isn't refreshed
+//! in time or the connection to the exchange drops and isn't back within the timeout.
+//!
+//! Nash has no server side cancel-on-disconnect, so the switch is run by the client. It
+//! covers a stalled strategy and a lost connection, but a process that is killed can't cancel
+//! anything: its orders stay on the book until something calls `Client::cancel_everything`,
+//! e.g. the process itself when it is restarted.
+
+use std::sync::{Arc, Mutex, Weak};
+use std::time::Duration;
+
+use futures::future::join_all;
+use tokio::sync::{broadcast, watch};
+use tokio::time::Instant;
+use tokio_util::sync::CancellationToken;
+use tracing::{error, info, warn};
+
+use nash_protocol::errors::{ProtocolError, Result};
+use nash_protocol::protocol::cancel_all_orders::CancelAllOrders;
+
+use crate::ws_client::InnerClient;
+use crate::{Client, ConnectionEvent};
+
+/// An armed switch. Dropping it disarms the switch.
+pub(crate) struct DeadManSwitch {
+    timeout: Duration,
+    deadline: watch::Sender<Instant>,
+    disarm: CancellationToken,
+    /// Why the switch tripped, once it did
+    tripped: Arc<Mutex<Option<String>>>,
+}
+
+impl Drop for DeadManSwitch {
+    fn drop(&mut self) {
+        self.disarm.cancel();
+    }
+}
+
+impl Client {
+    /// Cancel all open orders unless `refresh_dead_man_switch` is called at least every
+    /// `timeout`, or when the connection drops and isn't back within `timeout`. Replaces a
+    /// switch armed before.
+    /// Orders are cancelled over HTTP on every market, as the websocket may be gone.
+    pub fn arm_dead_man_switch(&self, timeout: Duration) -> Result<()> {
+        if timeout.is_zero() {
+            return Err(ProtocolError(
+                "Dead man's switch timeout must be greater than 0",
+            ));
+        }
+        let (deadline, deadline_receiver) = watch::channel(Instant::now() + timeout);
+        let switch = DeadManSwitch {
+            timeout,
+            deadline,
+            disarm: CancellationToken::new(),
+            tripped: Arc::new(Mutex::new(None)),
+        };
+        // subscribed before spawning, so a disconnect right after arming isn't missed
+        let events = self.inner.connection_events.subscribe();
+        // the client holds the switch, so the task only holds on to the client weakly
+        let inner = Arc::downgrade(&self.inner);
+        let disarm = switch.disarm.clone();
+        let tripped = switch.tripped.clone();
+        tokio::spawn(async move {
+            let reason = watch_switch(deadline_receiver, events, timeout, &disarm).await;
+            if let Some(reason) = reason {
+                trip(inner, reason, &tripped).await;
+            }
+        });
+        info!(
+            timeout_ms = timeout.as_millis() as u64,
+            "dead man's switch armed"
+        );
+        *self.inner.dead_man_switch.write().unwrap() = Some(switch);
+        Ok(())
+    }
+
+    /// Push the deadline of the dead man's switch back by its timeout. Fails if no switch is
+    /// armed or it has tripped, in which case orders have been cancelled and the switch has
+    /// to be armed again.
+    pub fn refresh_dead_man_switch(&self) -> Result<()> {
+        let switch = self.inner.dead_man_switch.read().unwrap();
+        let switch = switch
+            .as_ref()
+            .ok_or(ProtocolError("Dead man's switch is not armed"))?;
+        if let Some(reason) = switch.tripped.lock().unwrap().as_ref() {
+            return Err(ProtocolError::coerce_static_from_str(&format!(
+                "Dead man's switch tripped: {}",
+                reason
+            )));
+        }
+        // the watching task only goes away once the switch tripped or was disarmed
+        let _ = switch.deadline.send(Instant::now() + switch.timeout);
+        Ok(())
+    }
+
+    /// Stop watching, leaving open orders alone
+    pub fn disarm_dead_man_switch(&self) {
+        if self.inner.dead_man_switch.write().unwrap().take().is_some() {
+            info!("dead man's switch disarmed");
+        }
+    }
+
+    /// Cancel all open orders on every market, over HTTP. Failures are logged, so that one
+    /// market failing doesn't keep the others from being cancelled.
+    pub async fn cancel_everything(&self) {
+        self.inner.cancel_everything().await
+    }
+}
+
+impl InnerClient {
+    /// See `Client::cancel_everything`
+    pub(crate) async fn cancel_everything(&self) {
+        let markets: Vec<String> = match &self.state.read().await.markets {
+            Some(markets) => markets.keys().cloned().collect(),
+            None => {
+                warn!("markets unknown, no orders cancelled");
+                return;
+            }
+        };
+        let cancellations = markets.into_iter().map(|market| async move {
+            let result = self
+                .run_http(CancelAllOrders {
+                    market: market.clone(),
+                })
+                .await
+                .and_then(|response| response.response_or_error());
+            if let Err(e) = result {
+                error!(%market, error = %e.report(), "could not cancel orders");
+            }
+        });
+        join_all(cancellations).await;
+    }
+}
+
+/// Record why the switch tripped and cancel all orders of the client, if it is still around
+async fn trip(inner: Weak<InnerClient>, reason: String, tripped: &Mutex<Option<String>>) {
+    // a client that is gone has no switch left to trip
+    let inner = match inner.upgrade() {
+        Some(inner) => inner,
+        None => return,
+    };
+    error!(%reason, "dead man's switch tripped, cancelling all orders");
+    *tripped.lock().unwrap() = Some(reason);
+    inner.cancel_everything().await;
+}
+
+/// Wait for the switch to trip and return why, or `None` once it is disarmed. A dropped
+/// connection trips the switch if it isn't back within `timeout`.
+async fn watch_switch(
+    mut deadline: watch::Receiver<Instant>,
+    mut events: broadcast::Receiver<ConnectionEvent>,
+    timeout: Duration,
+    disarm: &CancellationToken,
+) -> Option<String> {
+    // when the connection has to be back by, while it is down
+    let mut reconnect_by: Option<Instant> = None;
+    loop {
+        let current = *deadline.borrow();
+        let wake = reconnect_by.map_or(current, |by| by.min(current));
+        tokio::select! {
+            _ = disarm.cancelled() => return None,
+            _ = tokio::time::sleep_until(wake) => {
+                let now = Instant::now();
+                if reconnect_by.map_or(false, |by| by <= now) {
+                    return Some("connection dropped and was not back in time".to_string());
+                }
+                if *deadline.borrow() <= now {
+                    return Some("not refreshed in time".to_string());
+                }
+            }
+            changed = deadline.changed() => {
+                if changed.is_err() {
+                    return None;
+                }
+            }
+            event = events.recv() => match event {
+                Ok(ConnectionEvent::Disconnected) => {
+                    reconnect_by.get_or_insert_with(|| Instant::now() + timeout);
+                }
+                Ok(ConnectionEvent::Connected { .. }) => reconnect_by = None,
+                Ok(ConnectionEvent::GaveUp { error, .. }) => {
+                    return Some(format!("could not reconnect: {}", error));
+                }
+                Ok(_) | Err(broadcast::error::RecvError::Lagged(_)) => {}
+                Err(broadcast::error::RecvError::Closed) => {
+                    return Some("connection events closed".to_string());
+                }
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::watch_switch;
+    use crate::ConnectionEvent;
+    use std::time::Duration;
+    use tokio::sync::{broadcast, watch};
+    use tokio::time::Instant;
+    use tokio_util::sync::CancellationToken;
+
+    const TIMEOUT: Duration = Duration::from_millis(50);
+
+    #[tokio::test]
+    async fn trips_when_not_refreshed_in_time() {
+        let start = Instant::now();
+        let (deadline, receiver) = watch::channel(start + TIMEOUT);
+        let (_events, events_receiver) = broadcast::channel(4);
+        let refresh = async {
+            tokio::time::sleep(TIMEOUT / 2).await;
+            deadline.send(Instant::now() + TIMEOUT).unwrap();
+        };
+        let disarm = CancellationToken::new();
+        let (reason, ()) = tokio::join!(
+            watch_switch(receiver, events_receiver, TIMEOUT, &disarm),
+            refresh
+        );
+        assert_eq!(reason.as_deref(), Some("not refreshed in time"));
+        // the refresh pushed the deadline back
+        assert!(start.elapsed() >= TIMEOUT * 3 / 2);
+    }
+
+    #[tokio::test]
+    async fn tolerates_a_reconnect_within_the_timeout() {
+        let deadline = Instant::now() + Duration::from_secs(60);
+        let (_deadline, receiver) = watch::channel(deadline);
+        let (events, events_receiver) = broadcast::channel(4);
+        events.send(ConnectionEvent::Disconnected).unwrap();
+        events
+            .send(ConnectionEvent::Connected {
+                endpoint: "wss://app.nash.io".to_string(),
+                rtt: Duration::from_millis(10),
+            })
+            .unwrap();
+        let disarm = CancellationToken::new();
+        let watching = watch_switch(receiver, events_receiver, TIMEOUT, &disarm);
+        assert!(tokio::time::timeout(TIMEOUT * 2, watching).await.is_err());
+
+        let (_deadline, receiver) = watch::channel(deadline);
+        let events_receiver = events.subscribe();
+        events.send(ConnectionEvent::Disconnected).unwrap();
+        let start = Instant::now();
+        let reason = watch_switch(receiver, events_receiver, TIMEOUT, &disarm).await;
+        assert_eq!(
+            reason.as_deref(),
+            Some("connection dropped and was not back in time")
+        );
+        assert!(start.elapsed() >= TIMEOUT);
+    }
+
+    #[tokio::test]
+    async fn gives_up_and_disarms() {
+        let deadline = Instant::now() + Duration::from_secs(60);
+        let (_deadline, receiver) = watch::channel(deadline);
+        let (events, events_receiver) = broadcast::channel(4);
+        events
+            .send(ConnectionEvent::GaveUp {
+                attempts: 3,
+                error: "refused".to_string(),
+            })
+            .unwrap();
+        let disarm = CancellationToken::new();
+        let reason = watch_switch(receiver, events_receiver, TIMEOUT, &disarm).await;
+        assert_eq!(reason.as_deref(), Some("could not reconnect: refused"));
+
+        let (_deadline, receiver) = watch::channel(Instant::now() + TIMEOUT);
+        let disarm = CancellationToken::new();
+        disarm.cancel();
+        assert_eq!(
+            watch_switch(receiver, events.subscribe(), TIMEOUT, &disarm).await,
+            None
+        );
+    }
+}
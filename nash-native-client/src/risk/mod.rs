@@ -0,0 +1,17 @@
+//! Pre-trade checks run on the client before orders reach the exchange
+
+mod approval;
+mod dead_man;
+mod price_guard;
+mod reference_price;
+mod throttle;
+
+pub use approval::{ApprovalPolicy, PendingApproval};
+pub(crate) use approval::Approvals;
+pub(crate) use dead_man::DeadManSwitch;
+pub use price_guard::PriceGuard;
+pub use reference_price::{
+    price_deviation, HttpReferencePrice, ReferencePrice, ReferencePriceSource,
+    StaticReferencePrices,
+};
+pub use throttle::{ExchangeThrottle, RequoteThrottle};
@@ -0,0 +1,174 @@
+//! Fat-finger guard: rejects limit orders priced too far away from the market before they
+//! are signed and submitted
+
+use std::collections::HashMap;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use bigdecimal::BigDecimal;
+use tracing::warn;
+
+use nash_protocol::errors::{ProtocolError, Result};
+use nash_protocol::protocol::orderbook::OrderbookRequest;
+use nash_protocol::protocol::ResponseOrError;
+
+use super::reference_price::{price_deviation, ReferencePriceSource};
+use crate::ws_client::InnerClient;
+
+/// Limit on how far a limit order price may be from the fair price of its market. The fair
+/// price is taken from the reference source if one is set and has a fresh price for the
+/// market, otherwise it is the mid of the Nash orderbook. Fair prices are reused for
+/// `cache_ttl`, so a burst of orders doesn't fetch the orderbook before each of them.
+#[derive(Clone)]
+pub struct PriceGuard {
+    max_deviation: BigDecimal,
+    reference: Option<Arc<dyn ReferencePriceSource>>,
+    max_reference_age: Duration,
+    cache_ttl: Duration,
+    fair_prices: Arc<Mutex<HashMap<String, (Instant, Option<BigDecimal>)>>>,
+}
+
+impl PriceGuard {
+    /// Reject orders priced more than `max_deviation` (e.g. 0.1 for 10%) away from the fair price
+    pub fn new(max_deviation: f64) -> Result<Self> {
+        if max_deviation.is_nan() || max_deviation <= 0.0 {
+            return Err(ProtocolError("Price guard deviation must be greater than 0"));
+        }
+        let max_deviation = BigDecimal::from_str(&max_deviation.to_string())
+            .map_err(|_| ProtocolError("Price guard deviation is not a valid decimal"))?;
+        Ok(Self {
+            max_deviation,
+            reference: None,
+            max_reference_age: Duration::from_secs(60),
+            cache_ttl: Duration::from_secs(1),
+            fair_prices: Arc::new(Mutex::new(HashMap::new())),
+        })
+    }
+
+    /// Prefer prices from `source` over the Nash orderbook, as long as they are younger than `max_age`
+    pub fn with_reference(
+        mut self,
+        source: Arc<dyn ReferencePriceSource>,
+        max_age: Duration,
+    ) -> Self {
+        self.reference = Some(source);
+        self.max_reference_age = max_age;
+        self
+    }
+
+    /// Reuse a market's fair price for `ttl` (1 second by default). Zero fetches it for
+    /// every order.
+    pub fn with_cache_ttl(mut self, ttl: Duration) -> Self {
+        self.cache_ttl = ttl;
+        self
+    }
+
+    pub fn max_deviation(&self) -> &BigDecimal {
+        &self.max_deviation
+    }
+
+    /// Check `price` against an already known fair price
+    pub fn check(&self, market: &str, price: &BigDecimal, fair_price: &BigDecimal) -> Result<()> {
+        let deviation = price_deviation(price, fair_price)?;
+        if deviation > self.max_deviation {
+            return Err(ProtocolError::coerce_static_from_str(&format!(
+                "Price guard: {} price {} is {}% away from fair price {} (limit {}%)",
+                market,
+                price,
+                (deviation * BigDecimal::from(100)).round(2),
+                fair_price,
+                &self.max_deviation * BigDecimal::from(100)
+            )));
+        }
+        Ok(())
+    }
+
+    async fn fair_price(&self, client: &InnerClient, market: &str) -> Result<Option<BigDecimal>> {
+        if let Some((at, fair_price)) = self.fair_prices.lock().unwrap().get(market) {
+            if at.elapsed() < self.cache_ttl {
+                return Ok(fair_price.clone());
+            }
+        }
+        let fair_price = self.fetch_fair_price(client, market).await?;
+        self.fair_prices
+            .lock()
+            .unwrap()
+            .insert(market.to_string(), (Instant::now(), fair_price.clone()));
+        Ok(fair_price)
+    }
+
+    async fn fetch_fair_price(
+        &self,
+        client: &InnerClient,
+        market: &str,
+    ) -> Result<Option<BigDecimal>> {
+        if let Some(reference) = &self.reference {
+            match reference.reference_price(market).await {
+                Ok(Some(reference)) if !reference.is_stale(self.max_reference_age) => {
+                    return Ok(Some(reference.price))
+                }
+                Ok(_) => {}
+                Err(e) => warn!(error = %e, %market, "reference price unavailable"),
+            }
+        }
+        let request = OrderbookRequest {
+            market: market.to_string(),
+        };
+        let book = match client.run(request).await? {
+            ResponseOrError::Response(book) => book.data,
+            ResponseOrError::Error(_) => {
+                return Err(ProtocolError("Price guard could not fetch orderbook"))
+            }
+        };
+        match (book.bids.first(), book.asks.first()) {
+            (Some(bid), Some(ask)) => {
+                let bid = BigDecimal::from_str(&bid.price)
+                    .map_err(|_| ProtocolError("Could not parse orderbook price"))?;
+                let ask = BigDecimal::from_str(&ask.price)
+                    .map_err(|_| ProtocolError("Could not parse orderbook price"))?;
+                Ok(Some((bid + ask) / BigDecimal::from(2)))
+            }
+            _ => Ok(None),
+        }
+    }
+}
+
+impl InnerClient {
+    /// Run the price guard, if one is set, against the limit prices of a request
+    pub(crate) async fn check_price_guard(&self, limit_prices: Vec<(&str, &str)>) -> Result<()> {
+        let guard = match self.price_guard.read().unwrap().clone() {
+            Some(guard) => guard,
+            None => return Ok(()),
+        };
+        for (market, price) in limit_prices {
+            let price = BigDecimal::from_str(price)
+                .map_err(|_| ProtocolError("Could not parse limit price"))?;
+            match guard.fair_price(self, market).await? {
+                Some(fair_price) => guard.check(market, &price, &fair_price)?,
+                None => {
+                    // one sided or empty book and no reference: nothing to compare against
+                    warn!(%market, "price guard has no fair price, order not checked");
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::PriceGuard;
+    use bigdecimal::BigDecimal;
+    use std::str::FromStr;
+
+    #[test]
+    fn rejects_prices_outside_deviation() {
+        let guard = PriceGuard::new(0.1).unwrap();
+        let fair = BigDecimal::from_str("200").unwrap();
+        assert!(guard.check("eth_usdc", &BigDecimal::from_str("215").unwrap(), &fair).is_ok());
+        assert!(guard.check("eth_usdc", &BigDecimal::from_str("2150").unwrap(), &fair).is_err());
+        assert!(guard.check("eth_usdc", &BigDecimal::from_str("21.5").unwrap(), &fair).is_err());
+        assert!(PriceGuard::new(0.0).is_err());
+    }
+}
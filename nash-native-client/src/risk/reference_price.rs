@@ -0,0 +1,164 @@
+//! External reference prices (e.g. an index price from another venue) used to sanity check
+//! Nash prices before orders are placed
+
+use std::collections::HashMap;
+use std::sync::RwLock;
+use std::time::{Duration, Instant};
+
+use async_trait::async_trait;
+use bigdecimal::{BigDecimal, Signed};
+
+use nash_protocol::errors::{ProtocolError, Result};
+
+/// Fair price of a market's base asset in its quote asset
+#[derive(Clone, Debug, PartialEq)]
+pub struct ReferencePrice {
+    pub price: BigDecimal,
+    /// When the source observed the price
+    pub observed_at: Instant,
+}
+
+impl ReferencePrice {
+    pub fn new(price: BigDecimal) -> Self {
+        Self {
+            price,
+            observed_at: Instant::now(),
+        }
+    }
+
+    pub fn is_stale(&self, max_age: Duration) -> bool {
+        self.observed_at.elapsed() > max_age
+    }
+}
+
+/// Source of reference prices by Nash market name (e.g. `eth_usdc`)
+#[async_trait]
+pub trait ReferencePriceSource: Send + Sync {
+    /// Current reference price, or `None` if the source doesn't cover `market`
+    async fn reference_price(&self, market: &str) -> Result<Option<ReferencePrice>>;
+}
+
+/// Relative distance of `price` from `reference`, e.g. 0.05 for 5% away
+pub fn price_deviation(price: &BigDecimal, reference: &BigDecimal) -> Result<BigDecimal> {
+    if !reference.is_positive() {
+        return Err(ProtocolError("Reference price must be positive"));
+    }
+    Ok((price - reference).abs() / reference)
+}
+
+/// Reference prices set by the application, e.g. from its own market data feed
+#[derive(Debug, Default)]
+pub struct StaticReferencePrices {
+    prices: RwLock<HashMap<String, ReferencePrice>>,
+}
+
+impl StaticReferencePrices {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn set(&self, market: &str, price: BigDecimal) {
+        self.prices
+            .write()
+            .unwrap()
+            .insert(market.to_string(), ReferencePrice::new(price));
+    }
+
+    pub fn remove(&self, market: &str) {
+        self.prices.write().unwrap().remove(market);
+    }
+}
+
+#[async_trait]
+impl ReferencePriceSource for StaticReferencePrices {
+    async fn reference_price(&self, market: &str) -> Result<Option<ReferencePrice>> {
+        Ok(self.prices.read().unwrap().get(market).cloned())
+    }
+}
+
+/// Reference prices fetched from a JSON HTTP endpoint. `url_template` may contain `{market}`
+/// and `{symbol}` (mapped name, see `with_symbol`) placeholders; `pointer` is a JSON pointer
+/// to the price in the response, which may be a string or a number.
+pub struct HttpReferencePrice {
+    client: reqwest::Client,
+    url_template: String,
+    pointer: String,
+    symbols: HashMap<String, String>,
+}
+
+impl HttpReferencePrice {
+    pub fn new(url_template: &str, pointer: &str, timeout: Duration) -> Result<Self> {
+        let client = reqwest::Client::builder()
+            .timeout(timeout)
+            .build()
+            .map_err(|e| ProtocolError::with_source("Could not initialize reqwest client", e))?;
+        Ok(Self {
+            client,
+            url_template: url_template.to_string(),
+            pointer: pointer.to_string(),
+            symbols: HashMap::new(),
+        })
+    }
+
+    /// Name the endpoint uses for `market`, e.g. `ETHUSDC` for `eth_usdc`. Markets without a
+    /// mapping are not covered by this source.
+    pub fn with_symbol(mut self, market: &str, symbol: &str) -> Self {
+        self.symbols.insert(market.to_string(), symbol.to_string());
+        self
+    }
+}
+
+#[async_trait]
+impl ReferencePriceSource for HttpReferencePrice {
+    async fn reference_price(&self, market: &str) -> Result<Option<ReferencePrice>> {
+        let symbol = match self.symbols.get(market) {
+            Some(symbol) => symbol,
+            None => return Ok(None),
+        };
+        let url = self
+            .url_template
+            .replace("{market}", market)
+            .replace("{symbol}", symbol);
+        let response: serde_json::Value = self
+            .client
+            .get(&url)
+            .send()
+            .await
+            .map_err(|e| ProtocolError::with_source("Could not fetch reference price", e))?
+            .json()
+            .await
+            .map_err(|e| {
+                ProtocolError::with_source("Could not parse reference price response as JSON", e)
+            })?;
+        let price: Option<BigDecimal> = match response.pointer(&self.pointer) {
+            Some(serde_json::Value::String(price)) => price.parse().ok(),
+            Some(serde_json::Value::Number(price)) => price.to_string().parse().ok(),
+            _ => None,
+        };
+        match price {
+            Some(price) if price.is_positive() => Ok(Some(ReferencePrice::new(price))),
+            _ => Err(ProtocolError::coerce_static_from_str(&format!(
+                "Reference price response has no price at {}",
+                self.pointer
+            ))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::price_deviation;
+    use bigdecimal::BigDecimal;
+    use std::str::FromStr;
+
+    #[test]
+    fn deviation_is_relative_to_reference() {
+        let reference = BigDecimal::from_str("2000").unwrap();
+        let price = BigDecimal::from_str("1900").unwrap();
+        assert_eq!(
+            price_deviation(&price, &reference).unwrap(),
+            BigDecimal::from_str("0.05").unwrap()
+        );
+        assert!(price_deviation(&price, &BigDecimal::from(0)).is_err());
+    }
+}
@@ -0,0 +1,191 @@
+//! Per market throttling of cancel-replace cycles, so quoting loops stay within the exchange
+//! rate limits and don't spend more time signing than trading, and the backoff the exchange
+//! asks for when it throttles requests anyway
+
+use std::collections::{HashMap, VecDeque};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use crate::config::RequoteLimits;
+
+const WINDOW: Duration = Duration::from_secs(60);
+
+/// Tracks recent requotes per market and decides when the next one may go out
+#[derive(Debug)]
+pub struct RequoteThrottle {
+    limits: Mutex<RequoteLimits>,
+    history: Mutex<HashMap<String, VecDeque<Instant>>>,
+}
+
+impl RequoteThrottle {
+    pub fn new(limits: RequoteLimits) -> Self {
+        Self {
+            limits: Mutex::new(limits),
+            history: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn limits(&self) -> RequoteLimits {
+        self.limits.lock().unwrap().clone()
+    }
+
+    pub fn set_limits(&self, limits: RequoteLimits) {
+        *self.limits.lock().unwrap() = limits;
+    }
+
+    /// Record a requote on `market` if allowed now, otherwise return how long to wait
+    pub fn try_acquire(&self, market: &str) -> std::result::Result<(), Duration> {
+        self.try_acquire_at(market, Instant::now())
+    }
+
+    /// Wait until a requote on `market` is allowed and record it
+    pub async fn acquire(&self, market: &str) {
+        while let Err(wait) = self.try_acquire(market) {
+            tokio::time::sleep(wait).await;
+        }
+    }
+
+    fn try_acquire_at(&self, market: &str, now: Instant) -> std::result::Result<(), Duration> {
+        let limits = self.limits();
+        let mut history = self.history.lock().unwrap();
+        let recent = history.entry(market.to_string()).or_default();
+        while let Some(oldest) = recent.front() {
+            if now.duration_since(*oldest) >= WINDOW {
+                recent.pop_front();
+            } else {
+                break;
+            }
+        }
+        if let Some(last) = recent.back() {
+            let since_last = now.duration_since(*last);
+            if since_last < limits.min_interval() {
+                return Err(limits.min_interval() - since_last);
+            }
+        }
+        if limits.max_per_minute > 0 && recent.len() >= limits.max_per_minute as usize {
+            // the oldest requote in the window has to age out first
+            let oldest = recent[recent.len() - limits.max_per_minute as usize];
+            return Err(WINDOW - now.duration_since(oldest));
+        }
+        recent.push_back(now);
+        Ok(())
+    }
+}
+
+/// Backoff requested by the exchange when it throttled a request. Every request of the
+/// client waits it out before it is sent, so the client doesn't keep hitting the exchange
+/// while it enforces its limits.
+#[derive(Debug)]
+pub struct ExchangeThrottle {
+    /// Backoff when the exchange didn't say how long to wait
+    default_backoff: Mutex<Duration>,
+    until: Mutex<Option<Instant>>,
+}
+
+impl ExchangeThrottle {
+    pub fn new(default_backoff: Duration) -> Self {
+        Self {
+            default_backoff: Mutex::new(default_backoff),
+            until: Mutex::new(None),
+        }
+    }
+
+    pub fn set_default_backoff(&self, backoff: Duration) {
+        *self.default_backoff.lock().unwrap() = backoff;
+    }
+
+    /// Back off for `retry_after`, or the default backoff if the exchange didn't say how
+    /// long. A backoff already in place is only ever extended. Returns the time to wait.
+    pub fn throttled(&self, retry_after: Option<Duration>) -> Duration {
+        self.throttled_at(retry_after, Instant::now())
+    }
+
+    /// Time left to wait before sending requests, if throttled
+    pub fn remaining(&self) -> Option<Duration> {
+        self.remaining_at(Instant::now())
+    }
+
+    /// Wait until the exchange's backoff is over
+    pub async fn wait(&self) {
+        while let Some(wait) = self.remaining() {
+            tokio::time::sleep(wait).await;
+        }
+    }
+
+    fn throttled_at(&self, retry_after: Option<Duration>, now: Instant) -> Duration {
+        let backoff = retry_after.unwrap_or_else(|| *self.default_backoff.lock().unwrap());
+        let mut until = self.until.lock().unwrap();
+        let next = now + backoff;
+        let until = until.get_or_insert(next);
+        if next > *until {
+            *until = next;
+        }
+        until.saturating_duration_since(now)
+    }
+
+    fn remaining_at(&self, now: Instant) -> Option<Duration> {
+        let mut until = self.until.lock().unwrap();
+        match *until {
+            Some(at) if at > now => Some(at - now),
+            Some(_) => {
+                *until = None;
+                None
+            }
+            None => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ExchangeThrottle, RequoteThrottle, WINDOW};
+    use crate::config::RequoteLimits;
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn enforces_interval_and_rate_per_market() {
+        let throttle = RequoteThrottle::new(RequoteLimits {
+            min_interval_ms: 100,
+            max_per_minute: 2,
+        });
+        let start = Instant::now();
+        assert!(throttle.try_acquire_at("eth_usdc", start).is_ok());
+        // other markets are throttled independently
+        assert!(throttle.try_acquire_at("btc_usdc", start).is_ok());
+        assert_eq!(
+            throttle.try_acquire_at("eth_usdc", start + Duration::from_millis(40)),
+            Err(Duration::from_millis(60))
+        );
+        assert!(throttle
+            .try_acquire_at("eth_usdc", start + Duration::from_millis(100))
+            .is_ok());
+        let later = start + Duration::from_secs(1);
+        assert_eq!(
+            throttle.try_acquire_at("eth_usdc", later),
+            Err(WINDOW - Duration::from_secs(1))
+        );
+        assert!(throttle.try_acquire_at("eth_usdc", start + WINDOW).is_ok());
+    }
+
+    #[test]
+    fn exchange_backoff_is_only_extended() {
+        let throttle = ExchangeThrottle::new(Duration::from_secs(1));
+        let start = Instant::now();
+        assert_eq!(throttle.remaining_at(start), None);
+        assert_eq!(throttle.throttled_at(None, start), Duration::from_secs(1));
+        // a shorter retry after doesn't cut the backoff short
+        assert_eq!(
+            throttle.throttled_at(Some(Duration::from_millis(200)), start),
+            Duration::from_secs(1)
+        );
+        assert_eq!(
+            throttle.throttled_at(Some(Duration::from_secs(3)), start),
+            Duration::from_secs(3)
+        );
+        assert_eq!(
+            throttle.remaining_at(start + Duration::from_secs(2)),
+            Some(Duration::from_secs(1))
+        );
+        assert_eq!(throttle.remaining_at(start + Duration::from_secs(3)), None);
+    }
+}
@@ -0,0 +1,289 @@
+//! One-shot scheduling of limit orders at a point in time. Signing material is prepared
+//! shortly before the order fires, so it goes out without the usual cold start latency.
+//! An order is stored as fired before it is sent, and fired orders are never resumed, so a
+//! crash can't place it twice.
+
+use std::collections::HashSet;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicI64, Ordering};
+use std::sync::Mutex as SyncMutex;
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use tokio::task::JoinHandle;
+use tokio_util::sync::CancellationToken;
+use tracing::{info, warn};
+
+use nash_protocol::errors::{ProtocolError, Result};
+use nash_protocol::protocol::asset_nonces::AssetNoncesRequest;
+use nash_protocol::protocol::place_order::{LimitOrderRequest, PlaceOrderResponse};
+use nash_protocol::types::timestamp::{self, Timestamp};
+use nash_protocol::utils::current_time_as_i64;
+
+use crate::json_store::{JsonStore, Stored};
+use crate::Client;
+
+/// How long before its time a scheduled order prepares r-values and asset nonces
+const WARM_UP_LEAD: Duration = Duration::from_secs(5);
+
+/// A limit order to be placed once, when the exchange clock reaches `at`
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ScheduledOrder {
+    pub id: String,
+    pub request: LimitOrderRequest,
+    #[serde(with = "timestamp::rfc3339")]
+    pub at: Timestamp,
+    /// The order is dropped instead of placed if it can't go out within this long after
+    /// `at`, e.g. because the client was down at the time
+    pub max_delay: Duration,
+    /// Set right before the order is sent
+    #[serde(default)]
+    pub fired: bool,
+}
+
+impl Stored for ScheduledOrder {
+    const KIND: &'static str = "scheduled order";
+
+    fn id(&self) -> &str {
+        &self.id
+    }
+}
+
+/// Handle to a scheduled order. Dropping it leaves the order scheduled.
+pub struct ScheduleHandle {
+    pub id: String,
+    cancel: CancellationToken,
+    task: JoinHandle<Result<PlaceOrderResponse>>,
+}
+
+impl ScheduleHandle {
+    /// Unschedule the order, unless it has already been sent
+    pub fn cancel(&self) {
+        self.cancel.cancel();
+    }
+
+    /// Wait for the order to be placed
+    pub async fn placed(self) -> Result<PlaceOrderResponse> {
+        self.task
+            .await
+            .map_err(|_| ProtocolError("Scheduled order task failed"))?
+    }
+}
+
+/// Where schedules are persisted and how far the local clock is behind the exchange
+#[derive(Debug, Default)]
+pub(crate) struct Schedules {
+    store: JsonStore<ScheduledOrder>,
+    clock_offset_ms: AtomicI64,
+    /// Ids of the orders waiting in a task, so resuming a store twice doesn't fire them twice
+    pending: SyncMutex<HashSet<String>>,
+}
+
+impl Schedules {
+    /// Exchange time in unix millis, as far as we know it
+    fn server_now(&self) -> i64 {
+        current_time_as_i64() + self.clock_offset_ms.load(Ordering::Relaxed)
+    }
+
+    fn remove(&self, id: &str) {
+        self.pending.lock().unwrap().remove(id);
+        self.store.remove(id);
+    }
+
+    /// Register a task for `id`. Returns false if one is already waiting for it.
+    fn claim(&self, id: &str) -> bool {
+        self.pending.lock().unwrap().insert(id.to_string())
+    }
+
+    /// Store schedules in `dir` and return the orders stored there. Orders that fired
+    /// before, whether or not they went through, are removed instead of returned.
+    fn open_store(&self, dir: PathBuf) -> Result<Vec<ScheduledOrder>> {
+        let mut orders = self.store.open(dir)?;
+        orders.retain(|order| {
+            if order.fired {
+                warn!(id = %order.id, "scheduled order fired before, not resuming it");
+                self.store.remove(&order.id);
+            }
+            !order.fired
+        });
+        Ok(orders)
+    }
+}
+
+impl Client {
+    /// Place `request` once the exchange clock reaches `at`. The order is dropped if it
+    /// can't be sent within `max_delay` after that.
+    pub fn schedule_order(
+        &self,
+        request: LimitOrderRequest,
+        at: Timestamp,
+        max_delay: Duration,
+    ) -> Result<ScheduleHandle> {
+        let order = ScheduledOrder {
+            id: self.inner.rng.id(),
+            request,
+            at,
+            max_delay,
+            fired: false,
+        };
+        self.inner.schedules.store.persist(&order)?;
+        self.inner.schedules.claim(&order.id);
+        Ok(self.spawn_scheduled(order))
+    }
+
+    /// Persist pending schedules as JSON files in `dir`. Schedules found there are resumed
+    /// and returned; those that are too late by now are dropped when they would fire. Orders
+    /// this client already waits for are not resumed again, so calling this twice is safe.
+    pub fn set_schedule_store(&self, dir: impl Into<PathBuf>) -> Result<Vec<ScheduleHandle>> {
+        let orders = self.inner.schedules.open_store(dir.into())?;
+        Ok(orders
+            .into_iter()
+            .filter(|order| self.inner.schedules.claim(&order.id))
+            .map(|order| self.spawn_scheduled(order))
+            .collect())
+    }
+
+    /// Tell the scheduler how far the exchange clock is ahead of the local one (negative if
+    /// behind). The offset is also estimated from every scheduled order that is placed.
+    pub fn set_clock_offset(&self, offset_ms: i64) {
+        self.inner
+            .schedules
+            .clock_offset_ms
+            .store(offset_ms, Ordering::Relaxed);
+    }
+
+    fn spawn_scheduled(&self, order: ScheduledOrder) -> ScheduleHandle {
+        let cancel = CancellationToken::new();
+        let client = self.clone();
+        let id = order.id.clone();
+        let task_cancel = cancel.clone();
+        let task = tokio::spawn(async move {
+            let result = client.fire_scheduled(order.clone(), &task_cancel).await;
+            client.inner.schedules.remove(&order.id);
+            if let Err(e) = &result {
+                warn!(id = %order.id, error = %e, "scheduled order not placed");
+            }
+            result
+        });
+        ScheduleHandle { id, cancel, task }
+    }
+
+    async fn fire_scheduled(
+        &self,
+        mut order: ScheduledOrder,
+        cancel: &CancellationToken,
+    ) -> Result<PlaceOrderResponse> {
+        let target = timestamp::unix_millis(&order.at);
+        let warm_up_at = target - WARM_UP_LEAD.as_millis() as i64;
+        self.sleep_until(warm_up_at, cancel).await?;
+        if let Err(e) = self.warm_up(&order.request.market).await {
+            warn!(id = %order.id, error = %e, "could not prepare scheduled order");
+        }
+        self.sleep_until(target, cancel).await?;
+        let late = self.inner.schedules.server_now() - target;
+        if late > order.max_delay.as_millis() as i64 {
+            return Err(ProtocolError::coerce_static_from_str(&format!(
+                "Scheduled order {} is {} ms late, more than allowed",
+                order.id, late
+            )));
+        }
+        order.fired = true;
+        self.inner.schedules.store.persist(&order)?;
+        let sent = current_time_as_i64();
+        let response = self.run(order.request.clone()).await?.response_or_error()?;
+        // the exchange stamps the order about halfway through the round trip
+        let local_mid = (sent + current_time_as_i64()) / 2;
+        let offset = timestamp::unix_millis(&response.placed_at) - local_mid;
+        self.set_clock_offset(offset);
+        info!(id = %order.id, order_id = %response.order_id, late, offset, "scheduled order placed");
+        Ok(response)
+    }
+
+    /// Sleep until the exchange clock reaches `server_millis`
+    async fn sleep_until(&self, server_millis: i64, cancel: &CancellationToken) -> Result<()> {
+        loop {
+            let remaining = server_millis - self.inner.schedules.server_now();
+            if remaining <= 0 {
+                return Ok(());
+            }
+            // wake up at least every minute so clock offset updates are picked up
+            let wait = Duration::from_millis(remaining.min(60_000) as u64);
+            tokio::select! {
+                _ = tokio::time::sleep(wait) => {}
+                _ = cancel.cancelled() => {
+                    return Err(ProtocolError("Scheduled order was cancelled"));
+                }
+            }
+        }
+    }
+
+    /// Fill r-value pools and refresh asset nonces ahead of placing an order on `market`
+    pub(crate) async fn warm_up(&self, market: &str) -> Result<()> {
+        let fill_pools = {
+            let state = self.inner.state.read().await;
+            let chains = state.get_market(market)?.blockchains();
+            state
+                .acquire_fill_pool_schedules(Some(&chains), Some(10))
+                .await?
+        };
+        for (request, permit) in fill_pools {
+            self.run(request).await?.response_or_error()?;
+            drop(permit);
+        }
+        self.run(AssetNoncesRequest::new())
+            .await?
+            .response_or_error()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ScheduledOrder, Schedules};
+    use nash_protocol::protocol::place_order::LimitOrderRequest;
+    use nash_protocol::types::timestamp;
+    use nash_protocol::types::{BuyOrSell, OrderCancellationPolicy};
+    use std::time::Duration;
+
+    fn order(id: &str, fired: bool) -> ScheduledOrder {
+        ScheduledOrder {
+            id: id.to_string(),
+            request: LimitOrderRequest {
+                market: "eth_usdc".to_string(),
+                client_order_id: None,
+                buy_or_sell: BuyOrSell::Buy,
+                amount: "1".to_string(),
+                price: "200".to_string(),
+                cancellation_policy: OrderCancellationPolicy::GoodTilCancelled,
+                allow_taker: true,
+            },
+            at: timestamp::now(),
+            max_delay: Duration::from_secs(1),
+            fired,
+        }
+    }
+
+    #[test]
+    fn fired_orders_are_not_resumed() {
+        let dir = std::env::temp_dir().join(format!("nash-schedules-{}", std::process::id()));
+        let schedules = Schedules::default();
+        schedules.store.open(dir.clone()).unwrap();
+        schedules.store.persist(&order("pending", false)).unwrap();
+        schedules.store.persist(&order("fired", true)).unwrap();
+
+        let orders = Schedules::default().open_store(dir.clone()).unwrap();
+        assert_eq!(orders.len(), 1);
+        assert_eq!(orders[0].id, "pending");
+        assert!(!dir.join("fired.json").exists());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn an_order_is_claimed_by_one_task_at_a_time() {
+        let schedules = Schedules::default();
+        assert!(schedules.claim("order"));
+        assert!(!schedules.claim("order"));
+        schedules.remove("order");
+        assert!(schedules.claim("order"));
+    }
+}
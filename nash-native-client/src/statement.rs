@@ -0,0 +1,529 @@
+//! Periodic account statements, signed with the account's payload signing key so auditors
+//! can check they were produced by the key holder and not altered afterwards
+
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::convert::TryFrom;
+use std::str::FromStr;
+
+use bigdecimal::{BigDecimal, Zero};
+use futures::stream::BoxStream;
+use futures::{Future, StreamExt};
+use serde::{Deserialize, Serialize};
+use tracing::warn;
+
+use nash_protocol::errors::{ProtocolError, Result};
+use nash_protocol::protocol::get_account_order::GetAccountOrderRequest;
+use nash_protocol::protocol::list_account_balances::ListAccountBalancesRequest;
+use nash_protocol::protocol::list_account_movements::{ListAccountMovementsRequest, Movement};
+use nash_protocol::protocol::list_account_orders::ListAccountOrdersRequest;
+use nash_protocol::protocol::list_account_trades::ListAccountTradesRequest;
+use nash_protocol::protocol::verify_canonical_string;
+use nash_protocol::types::timestamp::{format_timestamp, now};
+use nash_protocol::types::{
+    AccountTradeSide, Asset, BuyOrSell, DateTimeRange, Fill, MarketSymbol, Order,
+};
+
+use crate::tags::OrderTag;
+use crate::Client;
+
+/// Balances of one asset at the time the statement was generated
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct StatementBalance {
+    pub state_channel: String,
+    pub pending: String,
+    pub personal: String,
+    pub in_orders: String,
+}
+
+/// One execution of an order of the account within the statement period
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct StatementFill {
+    pub trade_id: String,
+    pub order_id: String,
+    pub market: String,
+    pub side: String,
+    pub liquidity: String,
+    pub amount: String,
+    pub price: String,
+    pub fee: String,
+    /// Asset the fee was paid in: the one received, base on buys and quote on sells
+    pub fee_asset: String,
+    pub received: String,
+    pub executed_at: String,
+    /// Strategy and tags of the order, if it was placed with a tagged client order id
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub strategy: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+}
+
+/// A deposit, withdrawal or transfer the exchange received within the statement period
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct StatementMovement {
+    pub id: String,
+    pub movement_type: String,
+    pub status: String,
+    pub asset: String,
+    pub quantity: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fee: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub transaction_hash: Option<String>,
+    pub received_at: String,
+}
+
+/// Balances, fills, fees and movements of an account over a period. Decimals are kept as
+/// strings so the serialized form is exact.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Statement {
+    /// Payload signing public key of the account, hex encoded
+    pub account: String,
+    pub period_start: String,
+    pub period_end: String,
+    pub generated_at: String,
+    /// Keyed by asset symbol
+    pub balances: BTreeMap<String, StatementBalance>,
+    /// Oldest first
+    pub fills: Vec<StatementFill>,
+    /// Trading fees paid over the period, keyed by the asset they were paid in
+    pub fees: BTreeMap<String, String>,
+    /// Oldest first
+    pub movements: Vec<StatementMovement>,
+    /// Orders with fills in the period that could not be looked up, so their fills have no
+    /// strategy even if they were tagged
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub unattributed_orders: Vec<String>,
+}
+
+impl Statement {
+    /// The exact bytes that are signed: the statement as compact JSON
+    pub fn signed_content(&self) -> Result<String> {
+        serde_json::to_string(self).map_err(|_| ProtocolError("Could not serialize statement"))
+    }
+
+    /// Fills of orders tagged with `strategy`, to attribute PnL per strategy
+    pub fn fills_of<'a>(&'a self, strategy: &'a str) -> impl Iterator<Item = &'a StatementFill> {
+        self.fills
+            .iter()
+            .filter(move |fill| fill.strategy.as_deref() == Some(strategy))
+    }
+}
+
+/// A `Statement` with a DER encoded secp256k1 ECDSA signature over the SHA-256 digest of
+/// `Statement::signed_content`, made with the key `statement.account` identifies
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct SignedStatement {
+    pub statement: Statement,
+    pub signature: String,
+}
+
+impl SignedStatement {
+    pub fn to_json(&self) -> Result<String> {
+        serde_json::to_string_pretty(self)
+            .map_err(|_| ProtocolError("Could not serialize statement"))
+    }
+
+    /// Whether `signature` was made over the statement by the key in `statement.account`.
+    /// Statements read back with `from_json` verify as long as they were not altered.
+    pub fn verify(&self) -> Result<bool> {
+        Ok(verify_canonical_string(
+            &self.statement.account,
+            &self.statement.signed_content()?,
+            &self.signature,
+        ))
+    }
+
+    pub fn from_json(json: &str) -> Result<Self> {
+        serde_json::from_str(json)
+            .map_err(|e| ProtocolError::with_source("Could not parse statement", e))
+    }
+}
+
+impl Client {
+    /// Build and sign the statement of the connected account for `period`. Balances are
+    /// the current ones, fills and fees are those executed within the period. Fills are
+    /// attributed to the strategy their order was tagged with (see `OrderTag`). The orders
+    /// are found by listing the account's orders; an order that can't be looked up is listed
+    /// in `unattributed_orders` instead of failing the statement.
+    pub async fn account_statement(&self, period: DateTimeRange) -> Result<SignedStatement> {
+        let balances = self
+            .run(ListAccountBalancesRequest { filter: None })
+            .await?
+            .response_or_error()?;
+        let mut assets: Vec<&Asset> = balances
+            .state_channel
+            .keys()
+            .chain(balances.pending.keys())
+            .chain(balances.personal.keys())
+            .chain(balances.in_orders.keys())
+            .collect();
+        assets.sort_by_key(|asset| asset.name());
+        assets.dedup();
+        let zero = BigDecimal::zero();
+        let balance = |amounts: &HashMap<Asset, BigDecimal>, asset: &Asset| {
+            amounts.get(asset).unwrap_or(&zero).to_string()
+        };
+        let balances = assets
+            .into_iter()
+            .map(|asset| {
+                let entry = StatementBalance {
+                    state_channel: balance(&balances.state_channel, asset),
+                    pending: balance(&balances.pending, asset),
+                    personal: balance(&balances.personal, asset),
+                    in_orders: balance(&balances.in_orders, asset),
+                };
+                (asset.name().to_string(), entry)
+            })
+            .collect();
+
+        let mut fills = self.fills_in(&period).await?;
+        fills.sort_by_key(|fill| fill.executed_at);
+        let fees = fees_by_asset(&fills)?;
+        let movements = self.movements_in(&period).await?;
+
+        let order_ids: BTreeSet<&str> = fills.iter().map(|fill| fill.order_id.as_str()).collect();
+        let (tags, unattributed_orders) = order_tags(
+            self.pages(ListAccountOrdersRequest::default()).items(),
+            order_ids,
+            |order_id| async move {
+                self.run(GetAccountOrderRequest { order_id })
+                    .await
+                    .and_then(|response| response.response_or_error())
+                    .map(|response| response.order)
+            },
+        )
+        .await;
+
+        let state = self.inner.state.read().await;
+        let signer = state.signer()?;
+        let statement = Statement {
+            account: signer.request_payload_public_key(),
+            period_start: format_timestamp(&period.start),
+            period_end: format_timestamp(&period.stop),
+            generated_at: format_timestamp(&now()),
+            balances,
+            fills: fills
+                .iter()
+                .map(|fill| statement_fill(fill, tags.get(&fill.order_id)))
+                .collect::<Result<_>>()?,
+            fees: fees
+                .into_iter()
+                .map(|(asset, fee)| (asset, fee.to_string()))
+                .collect(),
+            movements: movements.iter().map(statement_movement).collect(),
+            unattributed_orders,
+        };
+        let signature = signer
+            .sign_canonical_string(&statement.signed_content()?)?
+            .signed_digest;
+        Ok(SignedStatement {
+            statement,
+            signature,
+        })
+    }
+
+    /// Fills of the account executed within `period`, following trade pagination
+    async fn fills_in(&self, period: &DateTimeRange) -> Result<Vec<Fill>> {
+        let trades = self
+            .pages(ListAccountTradesRequest {
+                market: None,
+                before: None,
+                limit: None,
+                range: Some(*period),
+            })
+            .all()
+            .await?;
+        trades
+            .iter()
+            .filter(|trade| trade.account_side != AccountTradeSide::None)
+            .map(Fill::try_from)
+            .collect()
+    }
+
+    /// Movements the exchange received within `period`, oldest first. Movements can't be
+    /// filtered by time, so pages are followed, newest first, until one reaches the start.
+    async fn movements_in(&self, period: &DateTimeRange) -> Result<Vec<Movement>> {
+        let mut pages = self.pages(ListAccountMovementsRequest::default()).stream();
+        let mut movements = Vec::new();
+        while let Some(page) = pages.next().await {
+            let page = page?;
+            let reached_start = page
+                .items
+                .iter()
+                .any(|movement| movement.received_at.map_or(false, |at| at < period.start));
+            movements.extend(page.items.into_iter().filter(|movement| {
+                movement
+                    .received_at
+                    .map_or(false, |at| at >= period.start && at < period.stop)
+            }));
+            if reached_start {
+                break;
+            }
+        }
+        movements.sort_by_key(|movement| movement.received_at);
+        Ok(movements)
+    }
+}
+
+/// Asset the fee of `fill` was paid in. Fees are taken out of what the order received:
+/// the base asset on a buy and the quote asset on a sell.
+fn fee_asset(fill: &Fill) -> Result<String> {
+    let market = MarketSymbol::from_str(&fill.market)?;
+    Ok(match fill.buy_or_sell {
+        BuyOrSell::Buy => market.base(),
+        BuyOrSell::Sell => market.quote(),
+    }
+    .to_string())
+}
+
+/// Total fees of `fills` per asset they were paid in
+fn fees_by_asset(fills: &[Fill]) -> Result<BTreeMap<String, BigDecimal>> {
+    let mut fees: BTreeMap<String, BigDecimal> = BTreeMap::new();
+    for fill in fills {
+        *fees
+            .entry(fee_asset(fill)?)
+            .or_insert_with(BigDecimal::zero) += &fill.fee;
+    }
+    Ok(fees)
+}
+
+/// Tags of the orders `order_ids`. `orders` are the account's orders, newest first, and are
+/// read until all of them are found; orders it doesn't reach are fetched with `lookup`, one
+/// request each. Returns the tags and the orders that couldn't be looked up.
+async fn order_tags<F, Fut>(
+    mut orders: BoxStream<'_, Result<Order>>,
+    order_ids: BTreeSet<&str>,
+    lookup: F,
+) -> (HashMap<String, OrderTag>, Vec<String>)
+where
+    F: Fn(String) -> Fut,
+    Fut: Future<Output = Result<Order>>,
+{
+    let mut missing = order_ids;
+    let mut tags = HashMap::new();
+    while !missing.is_empty() {
+        match orders.next().await {
+            Some(Ok(order)) => {
+                if missing.remove(order.id.as_str()) {
+                    if let Some(tag) = OrderTag::of(&order) {
+                        tags.insert(order.id, tag);
+                    }
+                }
+            }
+            Some(Err(e)) => {
+                warn!(error = %e.report(), "could not list orders to attribute fills");
+                break;
+            }
+            None => break,
+        }
+    }
+    let mut failed = Vec::new();
+    for order_id in missing {
+        match lookup(order_id.to_string()).await {
+            Ok(order) => {
+                if let Some(tag) = OrderTag::of(&order) {
+                    tags.insert(order.id, tag);
+                }
+            }
+            Err(e) => {
+                warn!(order_id, error = %e.report(), "could not look up order to attribute fills");
+                failed.push(order_id.to_string());
+            }
+        }
+    }
+    (tags, failed)
+}
+
+fn statement_fill(fill: &Fill, tag: Option<&OrderTag>) -> Result<StatementFill> {
+    Ok(StatementFill {
+        trade_id: fill.trade_id.clone(),
+        order_id: fill.order_id.clone(),
+        market: fill.market.clone(),
+        side: match fill.buy_or_sell {
+            BuyOrSell::Buy => "buy",
+            BuyOrSell::Sell => "sell",
+        }
+        .to_string(),
+        liquidity: match fill.liquidity {
+            AccountTradeSide::Maker => "maker",
+            AccountTradeSide::Taker => "taker",
+            AccountTradeSide::None => "none",
+        }
+        .to_string(),
+        amount: fill.amount.to_string(),
+        price: fill.price.to_string(),
+        fee: fill.fee.to_string(),
+        fee_asset: fee_asset(fill)?,
+        received: fill.received.to_string(),
+        executed_at: format_timestamp(&fill.executed_at),
+        strategy: tag.map(|tag| tag.strategy.clone()),
+        tags: tag.map_or_else(Vec::new, |tag| tag.tags.clone()),
+    })
+}
+
+fn statement_movement(movement: &Movement) -> StatementMovement {
+    StatementMovement {
+        id: movement.id.clone(),
+        movement_type: format!("{:?}", movement.movement_type).to_lowercase(),
+        status: format!("{:?}", movement.status).to_lowercase(),
+        asset: movement.currency.clone(),
+        quantity: movement.quantity.to_string(),
+        fee: movement.fee.as_ref().map(|fee| fee.to_string()),
+        transaction_hash: movement.transaction_hash.clone(),
+        // only movements with a received time are in the period
+        received_at: movement
+            .received_at
+            .as_ref()
+            .map(format_timestamp)
+            .unwrap_or_default(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{fees_by_asset, order_tags, SignedStatement, Statement};
+    use crate::tags::OrderTag;
+    use bigdecimal::BigDecimal;
+    use futures::stream::{self, StreamExt};
+    use nash_protocol::errors::ProtocolError;
+    use nash_protocol::protocol::Signer;
+    use nash_protocol::types::timestamp::{format_timestamp, now};
+    use nash_protocol::types::{AccountTradeSide, BuyOrSell, Fill, Order, OrderStatus, OrderType};
+    use std::collections::BTreeMap;
+    use std::str::FromStr;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    const KEY: &str = "eyJjaGlsZF9rZXlzIjp7fSwKICAgICAgICAicGFpbGxpZXJfcGsiOnsibiI6IjU5ODdlNjIyMjYxY2FmOTZlMjU4MjZjNzBjZjMyM2IyNjE5NGZmOWNmZTY5ZTNmNDBmMzBkMzA2NTcxNjQyY2FlYThhMzE0M2QxMWZmOTRjMTM4ODM2MDQ4NjczNTdhZThjMGU2NjNiZjAzZDAwOTMwMTZkN2Y0ZDc5MGFlMjRlMjkxNzgwM2Q4MTJiNjQxYWYyZDZjMDk1NzNkMTEyZWI3Njg2NDY1MjkxY2QxNDZmZDY2MmY3N2Y1OTVlZjgzMjc3YmUxNjgwZDA0MGIxZjNjNDk5YzgxOTE3NTcyMDZlNTEwYWU1NDcyNGQ2NjdmYzA0MWEyYzdjMmZmM2QzYjY2YzM3MjlkYzI1ZTAyYzQwMTllZDNhMDEyZmQ3NWVjMGUwMzk0OGNmNzgzYWQzOTAyY2U1ZTVlNzIyMjljM2RkM2ExNGI5MzRkNjAyNjlhY2I3YmEwYmQ0MTVkMmRlMTI4ZWYxODcyMjQwMGJhZWEyZTg1MGU2ZDFmZDg3ODdhMDEzMGQ1MTYyMDZkNzE4YTQ5ZDdhMjFkNDI4YjBmYTM3NzMwNzliNjQ4NjE4MTExOTFiNTUwMDFkNGMyYzI5ZjYzMDMxNGJlMTkxY2YzY2EzZjBmOGUwOWVlMDk1NDNmZmRkYTNmOTdjZjE2OWQ1MmUwNjdjZmQ0MGNiMzAzOTQxIn0sCiAgICAgICAgInBheWxvYWRfcHVibGljX2tleSI6IjA0NjE2NDZmZGM0NTQ0ZjEwMjk0ZTIwZTk5NGNlNTZkOGMwZmY4NTI1OTZlYjZiM2FhMGJhOWQ0YjIwNzlkODZkNDJiM2I1ZTg0OTFhNDhmZjZlMTYyMDczMjU3OTgwNzkxNmVlYjA3YmViNmY5OTcwZGM1OTUyYmQ0NDQ0MDRmNzQiLAogICAgICAgICJwYXlsb2FkX3NpZ25pbmdfa2V5IjoiYmI4YmNmNTJhNWY5NDRmMzUxYzViYzg1NmI3YTRjNDFhNWYzNzBmNWNlOTlkY2UwYzhkNmYxZDQ5MWNkMzRiZiIsCiAgICAgICAgInZlcnNpb24iOjB9";
+
+    fn fill(market: &str, buy_or_sell: BuyOrSell, fee: &str) -> Fill {
+        Fill {
+            trade_id: "1".to_string(),
+            order_id: "2".to_string(),
+            market: market.to_string(),
+            buy_or_sell,
+            liquidity: AccountTradeSide::Taker,
+            amount: BigDecimal::from(1),
+            price: BigDecimal::from(1),
+            fee: BigDecimal::from_str(fee).unwrap(),
+            received: BigDecimal::from(1),
+            executed_at: now(),
+        }
+    }
+
+    fn order(id: &str, client_order_id: Option<&str>) -> Order {
+        Order {
+            id: id.to_string(),
+            client_order_id: client_order_id.map(str::to_string),
+            amount_placed: BigDecimal::from(1),
+            amount_remaining: BigDecimal::from(0),
+            amount_executed: BigDecimal::from(1),
+            limit_price: Some(BigDecimal::from(100)),
+            stop_price: None,
+            placed_at: now(),
+            buy_or_sell: BuyOrSell::Buy,
+            cancellation_policy: None,
+            cancellation_reason: None,
+            market: "eth_usdc".to_string(),
+            order_type: OrderType::Limit,
+            status: OrderStatus::Filled,
+            trades: Vec::new(),
+        }
+    }
+
+    #[tokio::test]
+    async fn orders_are_tagged_from_the_listing_and_failed_lookups_reported() {
+        let lookups = AtomicUsize::new(0);
+        let lookup = |order_id: String| {
+            lookups.fetch_add(1, Ordering::SeqCst);
+            async move {
+                match order_id.as_str() {
+                    "b" => Ok(order("b", Some("t:arb::2"))),
+                    _ => Err(ProtocolError("no such order")),
+                }
+            }
+        };
+        let listing = || {
+            stream::iter(vec![
+                Ok(order("a", Some("t:mm:hedge:1"))),
+                Ok(order("c", Some("not a tag"))),
+                Err(ProtocolError("page failed")),
+            ])
+            .boxed()
+        };
+
+        let (tags, failed) = order_tags(
+            listing(),
+            vec!["a", "b", "c", "d"].into_iter().collect(),
+            lookup,
+        )
+        .await;
+        assert_eq!(tags.len(), 2);
+        assert_eq!(
+            tags["a"],
+            OrderTag::new("mm").unwrap().with_tag("hedge").unwrap()
+        );
+        assert_eq!(tags["b"], OrderTag::new("arb").unwrap());
+        assert_eq!(failed, vec!["d".to_string()]);
+        assert_eq!(lookups.load(Ordering::SeqCst), 2);
+
+        // orders found in the listing take no lookups, and the failing page isn't reached
+        let (tags, failed) =
+            order_tags(listing(), vec!["a", "c"].into_iter().collect(), lookup).await;
+        assert_eq!(tags.len(), 1);
+        assert!(failed.is_empty());
+        assert_eq!(lookups.load(Ordering::SeqCst), 2);
+    }
+
+    #[test]
+    fn fees_are_summed_per_asset_paid_in() {
+        let fees = fees_by_asset(&[
+            fill("eth_usdc", BuyOrSell::Buy, "0.01"),
+            fill("eth_usdc", BuyOrSell::Sell, "2.5"),
+            fill("eth_btc", BuyOrSell::Buy, "0.02"),
+            fill("neo_usdc", BuyOrSell::Sell, "1"),
+        ])
+        .unwrap();
+        let expected: BTreeMap<String, BigDecimal> = vec![("eth", "0.03"), ("usdc", "3.5")]
+            .into_iter()
+            .map(|(asset, fee)| (asset.to_string(), BigDecimal::from_str(fee).unwrap()))
+            .collect();
+        assert_eq!(fees, expected);
+        assert!(fees_by_asset(&[fill("ethusdc", BuyOrSell::Buy, "1")]).is_err());
+    }
+
+    #[test]
+    fn signed_statements_verify_until_altered() {
+        let signer = Signer::from_data(KEY, "").unwrap();
+        let statement = Statement {
+            account: signer.request_payload_public_key(),
+            period_start: format_timestamp(&now()),
+            period_end: format_timestamp(&now()),
+            generated_at: format_timestamp(&now()),
+            balances: BTreeMap::new(),
+            fills: Vec::new(),
+            fees: vec![("eth".to_string(), "0.03".to_string())]
+                .into_iter()
+                .collect(),
+            movements: Vec::new(),
+            unattributed_orders: Vec::new(),
+        };
+        let signature = signer
+            .sign_canonical_string(&statement.signed_content().unwrap())
+            .unwrap()
+            .signed_digest;
+        let signed = SignedStatement {
+            statement,
+            signature,
+        };
+        let read_back = SignedStatement::from_json(&signed.to_json().unwrap()).unwrap();
+        assert_eq!(read_back, signed);
+        assert!(read_back.verify().unwrap());
+
+        let mut altered = read_back;
+        altered
+            .statement
+            .fees
+            .insert("eth".to_string(), "0".to_string());
+        assert!(!altered.verify().unwrap());
+    }
+}
@@ -0,0 +1,122 @@
+//! List requests whose items are received while the response is still being downloaded and
+//! decoded, so a very large page never has to be held in memory at once
+
+use std::io::{self, Read};
+
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+
+use nash_protocol::errors::{ProtocolError, Result};
+use nash_protocol::protocol::{decode_list, ListEnvelope, NashProtocol, StreamableList};
+
+use crate::Client;
+
+/// Response body chunks buffered ahead of the decoder
+const CHUNK_BUFFER: usize = 16;
+/// Decoded items buffered ahead of the receiver
+const ITEM_BUFFER: usize = 256;
+
+/// Items of a list response, in response order
+pub struct ListStream<T> {
+    items: mpsc::Receiver<T>,
+    decoder: JoinHandle<Result<ListEnvelope>>,
+}
+
+impl<T> ListStream<T> {
+    /// Next item, or `None` once the list is exhausted or decoding failed
+    pub async fn next(&mut self) -> Option<T> {
+        self.items.recv().await
+    }
+
+    /// Fields of the response besides the list, e.g. the next page cursor. Call once `next`
+    /// returned `None`; called earlier, the rest of the list is abandoned and this fails.
+    /// Also fails if decoding failed or the server reported an error.
+    pub async fn finish(self) -> Result<ListEnvelope> {
+        drop(self.items);
+        let envelope = self
+            .decoder
+            .await
+            .map_err(|e| ProtocolError::with_source("List decoding task failed", e))??;
+        match envelope.errors.first() {
+            Some(error) => Err(ProtocolError::coerce_static_from_str(error)),
+            None => Ok(envelope),
+        }
+    }
+}
+
+/// Blocking reader over response body chunks received from the download task
+struct ChunkReader {
+    chunks: mpsc::Receiver<io::Result<Vec<u8>>>,
+    current: Vec<u8>,
+    position: usize,
+}
+
+impl Read for ChunkReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        while self.position == self.current.len() {
+            match self.chunks.blocking_recv() {
+                Some(chunk) => {
+                    self.current = chunk?;
+                    self.position = 0;
+                }
+                None => return Ok(0),
+            }
+        }
+        let n = buf.len().min(self.current.len() - self.position);
+        buf[..n].copy_from_slice(&self.current[self.position..self.position + n]);
+        self.position += n;
+        Ok(n)
+    }
+}
+
+impl Client {
+    /// Run a list request via HTTP and receive its items while the response is decoded,
+    /// instead of once it has been parsed as a whole. Decoding pauses while a few hundred
+    /// items are waiting to be received, so memory stays bounded however large the page is.
+    pub async fn stream_list<T>(&self, request: T) -> Result<ListStream<T::Item>>
+    where
+        T: StreamableList + 'static,
+        T::Item: 'static,
+    {
+        let state = self.inner.state.clone();
+        if let Some(actions) = NashProtocol::run_before(&request, state.clone()).await? {
+            for action in actions {
+                self.inner.run_http(action).await?;
+            }
+        }
+        let graphql_request = request.graphql(state).await?;
+        let mut response = self.inner.send_http_unordered(&graphql_request).await?;
+
+        let (chunk_sender, chunks) = mpsc::channel(CHUNK_BUFFER);
+        tokio::spawn(async move {
+            loop {
+                let chunk = match response.chunk().await {
+                    Ok(Some(chunk)) => Ok(chunk.to_vec()),
+                    Ok(None) => break,
+                    Err(e) => Err(io::Error::new(io::ErrorKind::Other, e)),
+                };
+                let failed = chunk.is_err();
+                // the decoder is gone once it failed or the stream was dropped
+                if chunk_sender.send(chunk).await.is_err() || failed {
+                    break;
+                }
+            }
+        });
+
+        let (item_sender, items) = mpsc::channel(ITEM_BUFFER);
+        let path = request.list_path();
+        let decoder = tokio::task::spawn_blocking(move || {
+            let reader = ChunkReader {
+                chunks,
+                current: Vec::new(),
+                position: 0,
+            };
+            decode_list(reader, path, |raw: T::Raw| {
+                item_sender
+                    .blocking_send(T::convert(raw)?)
+                    .map_err(|_| ProtocolError("List stream was dropped"))
+            })
+        });
+        Ok(ListStream { items, decoder })
+    }
+}
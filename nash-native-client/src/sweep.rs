@@ -0,0 +1,390 @@
+//! Sweeps of trading balance above a threshold to whitelisted cold addresses. On every tick the
+//! state channel balances are listed, whatever exceeds an asset's threshold is withdrawn, and
+//! the withdrawal is followed in the account's movements until it completes or fails. An asset
+//! is not swept again while its last sweep is in flight.
+
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::time::Duration;
+
+use bigdecimal::{BigDecimal, Zero};
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+use tokio_util::sync::CancellationToken;
+use tracing::{info, warn};
+
+use nash_protocol::errors::{ProtocolError, Result};
+use nash_protocol::protocol::list_account_balances::ListAccountBalancesRequest;
+use nash_protocol::protocol::list_account_movements::{ListAccountMovementsRequest, MovementType};
+use nash_protocol::protocol::withdraw::{MovementStatus, WithdrawRequest};
+use nash_protocol::types::{Address, Asset};
+
+use crate::Client;
+
+/// Decimals the state channel keeps amounts at
+const SWEEP_SCALE: i64 = 8;
+
+/// Withdrawals looked at when following in-flight sweeps, newest first
+const MOVEMENTS_CHECKED: i64 = 50;
+
+/// How much of an asset to keep in the state channel and where to send the rest
+#[derive(Clone, Debug, PartialEq)]
+pub struct SweepRule {
+    /// Trading balance left in place
+    pub keep: BigDecimal,
+    /// Smallest amount worth a withdrawal and its fee
+    pub min_sweep: BigDecimal,
+    pub address: String,
+}
+
+impl SweepRule {
+    /// Amount to sweep out of `balance`, if any
+    fn amount(&self, balance: &BigDecimal) -> Option<BigDecimal> {
+        let amount = (balance - &self.keep).with_scale(SWEEP_SCALE);
+        if amount <= BigDecimal::zero() || amount < self.min_sweep {
+            None
+        } else {
+            Some(amount)
+        }
+    }
+}
+
+/// Sweep rules per asset, limited to whitelisted addresses
+#[derive(Clone, Debug)]
+pub struct SweepConfig {
+    interval: Duration,
+    whitelist: HashSet<String>,
+    rules: HashMap<Asset, SweepRule>,
+}
+
+impl SweepConfig {
+    /// Check balances every `interval`
+    pub fn new(interval: Duration) -> Self {
+        Self {
+            interval,
+            whitelist: HashSet::new(),
+            rules: HashMap::new(),
+        }
+    }
+
+    /// Allow sweeps to `address`
+    pub fn whitelist(mut self, address: &str) -> Self {
+        self.whitelist.insert(address.to_string());
+        self
+    }
+
+    /// Sweep `asset` above `keep` to `address`, which must be whitelisted and on the asset's
+    /// blockchain
+    pub fn with_rule(
+        mut self,
+        asset: Asset,
+        keep: BigDecimal,
+        min_sweep: BigDecimal,
+        address: &str,
+    ) -> Result<Self> {
+        if !self.whitelist.contains(address) {
+            return Err(ProtocolError::coerce_static_from_str(&format!(
+                "Sweep address {} is not whitelisted",
+                address
+            )));
+        }
+        Address::parse(asset.blockchain(), address)?;
+        if keep < BigDecimal::zero() {
+            return Err(ProtocolError("Balance to keep can't be negative"));
+        }
+        self.rules.insert(
+            asset,
+            SweepRule {
+                keep,
+                min_sweep,
+                address: address.to_string(),
+            },
+        );
+        Ok(self)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rules.is_empty()
+    }
+}
+
+/// What happened to a sweep
+#[derive(Clone, Debug, PartialEq)]
+pub enum SweepEvent {
+    /// A withdrawal was submitted
+    Started {
+        asset: Asset,
+        amount: BigDecimal,
+        movement_id: String,
+    },
+    /// The withdrawal completed
+    Completed { asset: Asset, movement_id: String },
+    /// The sweep could not be submitted, or its withdrawal failed
+    Failed { asset: Asset, reason: String },
+    /// Balances could not be listed, so nothing was swept this tick
+    BalancesUnavailable { reason: String },
+}
+
+impl SweepEvent {
+    pub fn is_failure(&self) -> bool {
+        matches!(self, Self::Failed { .. } | Self::BalancesUnavailable { .. })
+    }
+}
+
+impl fmt::Display for SweepEvent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Started {
+                asset,
+                amount,
+                movement_id,
+            } => write!(
+                f,
+                "sweeping {} {} in movement {}",
+                amount,
+                asset.name(),
+                movement_id
+            ),
+            Self::Completed { asset, movement_id } => {
+                write!(f, "{} sweep {} completed", asset.name(), movement_id)
+            }
+            Self::Failed { asset, reason } => {
+                write!(f, "{} sweep failed: {}", asset.name(), reason)
+            }
+            Self::BalancesUnavailable { reason } => {
+                write!(f, "could not list balances to sweep: {}", reason)
+            }
+        }
+    }
+}
+
+/// Running sweeps, from `Client::start_sweeps`. Dropping it stops sweeping; withdrawals already
+/// submitted go ahead.
+pub struct Sweeper {
+    cancel: CancellationToken,
+    receiver: mpsc::UnboundedReceiver<SweepEvent>,
+    task: JoinHandle<()>,
+}
+
+impl Drop for Sweeper {
+    fn drop(&mut self) {
+        self.cancel.cancel();
+    }
+}
+
+impl Sweeper {
+    /// Next event. Failures are the alerts to act on.
+    pub async fn recv(&mut self) -> Option<SweepEvent> {
+        self.receiver.recv().await
+    }
+
+    pub async fn stop(mut self) -> Result<()> {
+        self.cancel.cancel();
+        (&mut self.task)
+            .await
+            .map_err(|_| ProtocolError("Sweep task failed"))
+    }
+}
+
+impl Client {
+    /// Sweep balances according to `config` until the returned sweeper is stopped. Events are
+    /// logged and can be received from the sweeper.
+    pub fn start_sweeps(&self, config: SweepConfig) -> Result<Sweeper> {
+        if config.is_empty() {
+            return Err(ProtocolError("No sweep rules"));
+        }
+        let client = self.clone();
+        let (sender, receiver) = mpsc::unbounded_channel();
+        let cancel = CancellationToken::new();
+        let task_cancel = cancel.clone();
+        let task = tokio::spawn(async move {
+            let mut in_flight: HashMap<Asset, String> = HashMap::new();
+            let mut interval = tokio::time::interval(config.interval);
+            loop {
+                tokio::select! {
+                    _ = interval.tick() => {}
+                    _ = task_cancel.cancelled() => return,
+                }
+                let mut events = client.follow_sweeps(&mut in_flight).await;
+                events.extend(client.sweep_once(&config, &mut in_flight).await);
+                for event in events {
+                    if event.is_failure() {
+                        warn!(%event, "sweep alert");
+                    } else {
+                        info!(%event, "sweep");
+                    }
+                    // a sweeper nobody receives from still logs its events
+                    let _ = sender.send(event);
+                }
+            }
+        });
+        Ok(Sweeper {
+            cancel,
+            receiver,
+            task,
+        })
+    }
+
+    /// Withdraw what exceeds each rule's threshold, for assets without a sweep in flight
+    async fn sweep_once(
+        &self,
+        config: &SweepConfig,
+        in_flight: &mut HashMap<Asset, String>,
+    ) -> Vec<SweepEvent> {
+        let balances = match self
+            .run(ListAccountBalancesRequest { filter: None })
+            .await
+            .and_then(|response| response.response_or_error())
+        {
+            Ok(balances) => balances,
+            Err(e) => return vec![SweepEvent::BalancesUnavailable { reason: e.report() }],
+        };
+        let mut events = Vec::new();
+        for (asset, rule) in &config.rules {
+            if in_flight.contains_key(asset) {
+                continue;
+            }
+            let amount = match balances
+                .state_channel
+                .get(asset)
+                .and_then(|balance| rule.amount(balance))
+            {
+                Some(amount) => amount,
+                None => continue,
+            };
+            let request =
+                match WithdrawRequest::new(*asset, amount.clone()).to_address(&rule.address) {
+                    Ok(request) => request,
+                    Err(e) => {
+                        events.push(SweepEvent::Failed {
+                            asset: *asset,
+                            reason: e.report(),
+                        });
+                        continue;
+                    }
+                };
+            match self.withdraw(request).await {
+                Ok(response) => {
+                    in_flight.insert(*asset, response.movement_id.clone());
+                    events.push(SweepEvent::Started {
+                        asset: *asset,
+                        amount,
+                        movement_id: response.movement_id,
+                    });
+                }
+                Err(e) => events.push(SweepEvent::Failed {
+                    asset: *asset,
+                    reason: e.report(),
+                }),
+            }
+        }
+        events
+    }
+
+    /// Look up sweeps in flight and report those that completed or failed
+    async fn follow_sweeps(&self, in_flight: &mut HashMap<Asset, String>) -> Vec<SweepEvent> {
+        let mut events = Vec::new();
+        for (asset, movement_id) in in_flight.clone() {
+            let movements = self
+                .run(ListAccountMovementsRequest {
+                    asset: Some(asset),
+                    movement_type: Some(MovementType::Withdrawal),
+                    limit: Some(MOVEMENTS_CHECKED),
+                    ..Default::default()
+                })
+                .await
+                .and_then(|response| response.response_or_error());
+            let movements = match movements {
+                Ok(response) => response.movements,
+                // tried again next tick
+                Err(e) => {
+                    warn!(asset = asset.name(), error = %e.report(), "could not follow sweep");
+                    continue;
+                }
+            };
+            let status = movements
+                .iter()
+                .find(|movement| movement.id == movement_id)
+                .map(|movement| movement.status);
+            match status {
+                Some(MovementStatus::Completed) => {
+                    in_flight.remove(&asset);
+                    events.push(SweepEvent::Completed { asset, movement_id });
+                }
+                Some(MovementStatus::Failed) => {
+                    in_flight.remove(&asset);
+                    events.push(SweepEvent::Failed {
+                        asset,
+                        reason: format!("withdrawal {} failed", movement_id),
+                    });
+                }
+                Some(_) => {}
+                None => {
+                    in_flight.remove(&asset);
+                    events.push(SweepEvent::Failed {
+                        asset,
+                        reason: format!(
+                            "withdrawal {} is not in the account's movements",
+                            movement_id
+                        ),
+                    });
+                }
+            }
+        }
+        events
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{SweepConfig, SweepRule};
+    use bigdecimal::BigDecimal;
+    use nash_protocol::types::Asset;
+    use std::str::FromStr;
+    use std::time::Duration;
+
+    const COLD: &str = "0x5b1c8ab7e9e7e9e6e7e6e7e6e7e6e7e6e7e6e7e6";
+
+    #[test]
+    fn only_the_excess_above_the_threshold_is_swept() {
+        let rule = SweepRule {
+            keep: BigDecimal::from(10),
+            min_sweep: BigDecimal::from_str("0.5").unwrap(),
+            address: COLD.to_string(),
+        };
+        assert_eq!(rule.amount(&BigDecimal::from(9)), None);
+        assert_eq!(rule.amount(&BigDecimal::from(10)), None);
+        assert_eq!(rule.amount(&BigDecimal::from_str("10.4").unwrap()), None);
+        assert_eq!(
+            rule.amount(&BigDecimal::from_str("12.123456789").unwrap()),
+            Some(BigDecimal::from_str("2.12345678").unwrap())
+        );
+    }
+
+    #[test]
+    fn rules_only_sweep_to_whitelisted_addresses() {
+        let config = SweepConfig::new(Duration::from_secs(60));
+        assert!(config
+            .clone()
+            .with_rule(Asset::ETH, BigDecimal::from(1), BigDecimal::from(1), COLD)
+            .is_err());
+        let config = config.whitelist(COLD).whitelist("not an address");
+        assert!(config
+            .clone()
+            .with_rule(
+                Asset::ETH,
+                BigDecimal::from(1),
+                BigDecimal::from(1),
+                "not an address"
+            )
+            .is_err());
+        assert!(config
+            .clone()
+            .with_rule(Asset::ETH, BigDecimal::from(-1), BigDecimal::from(1), COLD)
+            .is_err());
+        assert!(!config
+            .with_rule(Asset::ETH, BigDecimal::from(1), BigDecimal::from(1), COLD)
+            .unwrap()
+            .is_empty());
+    }
+}
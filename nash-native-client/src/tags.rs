@@ -0,0 +1,113 @@
+//! Strategy attribution for orders sharing an account. Orders have no metadata field, so the
+//! strategy and tags of an order are encoded in its client order id as
+//! `t:<strategy>:<tag>,<tag>:<unique>`. The exchange returns the client order id with the
+//! order, so the attribution survives restarts and shows up in `OpenOrders` and statements.
+
+use nash_protocol::errors::{ProtocolError, Result};
+use nash_protocol::types::Order;
+
+use crate::Client;
+
+const TAG_PREFIX: &str = "t";
+const MAX_NAME_LENGTH: usize = 24;
+
+fn check_name(name: &str) -> Result<()> {
+    let valid = !name.is_empty()
+        && name.len() <= MAX_NAME_LENGTH
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-');
+    if !valid {
+        return Err(ProtocolError::coerce_static_from_str(&format!(
+            "Invalid order tag {:?}: use up to {} letters, digits, '_' or '-'",
+            name, MAX_NAME_LENGTH
+        )));
+    }
+    Ok(())
+}
+
+/// Strategy an order belongs to, with optional free-form tags
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct OrderTag {
+    pub strategy: String,
+    pub tags: Vec<String>,
+}
+
+impl OrderTag {
+    pub fn new(strategy: &str) -> Result<Self> {
+        check_name(strategy)?;
+        Ok(Self {
+            strategy: strategy.to_string(),
+            tags: Vec::new(),
+        })
+    }
+
+    pub fn with_tag(mut self, tag: &str) -> Result<Self> {
+        check_name(tag)?;
+        self.tags.push(tag.to_string());
+        Ok(self)
+    }
+
+    /// Client order id carrying this tag, made unique by `unique`
+    pub fn client_order_id(&self, unique: &str) -> String {
+        format!(
+            "{}:{}:{}:{}",
+            TAG_PREFIX,
+            self.strategy,
+            self.tags.join(","),
+            unique
+        )
+    }
+
+    /// Tag of a client order id made by `client_order_id`, or `None` for any other id
+    pub fn parse(client_order_id: &str) -> Option<Self> {
+        let mut parts = client_order_id.splitn(4, ':');
+        if parts.next()? != TAG_PREFIX {
+            return None;
+        }
+        let strategy = parts.next()?;
+        let tags = parts.next()?;
+        parts.next()?;
+        let mut tag = Self::new(strategy).ok()?;
+        for name in tags.split(',').filter(|name| !name.is_empty()) {
+            tag = tag.with_tag(name).ok()?;
+        }
+        Some(tag)
+    }
+
+    /// Tag of `order`, if it was placed with a tagged client order id
+    pub fn of(order: &Order) -> Option<Self> {
+        order.client_order_id.as_deref().and_then(Self::parse)
+    }
+}
+
+impl Client {
+    /// New client order id for an order of `tag`, e.g. for `LimitOrderRequest::client_order_id`
+    pub fn tagged_client_order_id(&self, tag: &OrderTag) -> String {
+        tag.client_order_id(&self.inner.rng.id())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::OrderTag;
+
+    #[test]
+    fn tags_round_trip_through_client_order_ids() {
+        let tag = OrderTag::new("mm-eth")
+            .and_then(|tag| tag.with_tag("hedge"))
+            .and_then(|tag| tag.with_tag("v2"))
+            .unwrap();
+        let id = tag.client_order_id("00c0ffee");
+        assert_eq!(id, "t:mm-eth:hedge,v2:00c0ffee");
+        assert_eq!(OrderTag::parse(&id), Some(tag));
+
+        let untagged = OrderTag::new("arb").unwrap();
+        assert_eq!(
+            OrderTag::parse(&untagged.client_order_id("1")),
+            Some(untagged)
+        );
+        assert_eq!(OrderTag::parse("my-own-id"), None);
+        assert!(OrderTag::new("has:colon").is_err());
+    }
+}
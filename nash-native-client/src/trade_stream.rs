@@ -0,0 +1,319 @@
+//! Public trades of several markets as one `Stream` of trades, with a per-market count of
+//! what was received. The exchange doesn't number trades, so after a reconnect the latest
+//! trades of each market are fetched and those missed in between are sent before anything
+//! new; trades received twice are dropped.
+
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::pin::Pin;
+use std::sync::{Arc, Mutex as SyncMutex};
+use std::task::{Context, Poll};
+
+use futures::stream::Stream;
+use tokio::sync::{broadcast, mpsc};
+use tokio_util::sync::CancellationToken;
+use tracing::warn;
+
+use nash_protocol::errors::Result;
+use nash_protocol::protocol::list_trades::ListTradesRequest;
+use nash_protocol::protocol::subscriptions::trades::{SubscribeTrades, TradesResponse};
+use nash_protocol::types::timestamp::Timestamp;
+use nash_protocol::types::Trade;
+
+use crate::{Client, ConnectionEvent, MarketEvent, MarketSubscriptionHandle};
+
+/// Trade ids remembered per market to drop trades received twice
+const RECENT_TRADE_IDS: usize = 1_000;
+/// Trades fetched per market to find the ones missed during a reconnect
+const RESYNC_PAGE_SIZE: i64 = 100;
+
+/// What was received of a market
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct TradeSequence {
+    /// Trades sent to the stream, including recovered ones
+    pub received: u64,
+    pub last_trade_id: Option<String>,
+    pub last_executed_at: Option<Timestamp>,
+}
+
+/// Outcome of recovering a market's trades after a reconnect
+#[derive(Clone, Debug)]
+pub struct TradeResync {
+    pub market: String,
+    /// Execution time of the last trade received before the reconnect
+    pub since: Option<Timestamp>,
+    /// Missed trades that were recovered and sent
+    pub recovered: usize,
+    /// False if the fetched trades didn't reach back to `since`, so more may have been missed
+    pub complete: bool,
+}
+
+/// Received trades of one market
+#[derive(Debug, Default)]
+struct TradeTracker {
+    sequence: TradeSequence,
+    recent: VecDeque<String>,
+    recent_ids: HashSet<String>,
+}
+
+impl TradeTracker {
+    /// Count `trade`, or return false if it was already received
+    fn accept(&mut self, trade: &Trade) -> bool {
+        if !self.recent_ids.insert(trade.id.clone()) {
+            return false;
+        }
+        self.recent.push_back(trade.id.clone());
+        if self.recent.len() > RECENT_TRADE_IDS {
+            if let Some(id) = self.recent.pop_front() {
+                self.recent_ids.remove(&id);
+            }
+        }
+        let sequence = &mut self.sequence;
+        sequence.received += 1;
+        if sequence
+            .last_executed_at
+            .map_or(true, |last| trade.executed_at >= last)
+        {
+            sequence.last_trade_id = Some(trade.id.clone());
+            sequence.last_executed_at = Some(trade.executed_at);
+        }
+        true
+    }
+
+    /// Trades of `latest` not received yet, oldest first, and whether `latest` reaches back
+    /// to the last trade received
+    fn missed(&self, mut latest: Vec<Trade>) -> (Vec<Trade>, bool) {
+        let since = match self.sequence.last_executed_at {
+            Some(since) => since,
+            // nothing to recover from before the first trade
+            None => return (Vec::new(), true),
+        };
+        let complete = latest.iter().any(|trade| trade.executed_at <= since);
+        latest.retain(|trade| trade.executed_at >= since && !self.recent_ids.contains(&trade.id));
+        latest.sort_by_key(|trade| trade.executed_at);
+        (latest, complete)
+    }
+}
+
+/// Trades of the markets of `Client::subscribe_trade_stream`, in the order received. Dropping
+/// the stream unsubscribes.
+pub struct TradeStream {
+    receiver: mpsc::UnboundedReceiver<Result<Trade>>,
+    trackers: Arc<SyncMutex<HashMap<String, TradeTracker>>>,
+    stop: CancellationToken,
+}
+
+impl TradeStream {
+    /// Next trade, or `None` once the subscriptions ended
+    pub async fn recv(&mut self) -> Option<Result<Trade>> {
+        self.receiver.recv().await
+    }
+
+    /// What was received of `market` so far
+    pub fn sequence(&self, market: &str) -> Option<TradeSequence> {
+        let trackers = self.trackers.lock().unwrap();
+        trackers.get(market).map(|tracker| tracker.sequence.clone())
+    }
+}
+
+impl Drop for TradeStream {
+    fn drop(&mut self) {
+        self.stop.cancel();
+    }
+}
+
+impl Stream for TradeStream {
+    type Item = Result<Trade>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.receiver.poll_recv(cx)
+    }
+}
+
+impl Client {
+    /// Subscribe to the trades of `markets` as one stream. After every reconnect the trades
+    /// missed in between are recovered and `on_resync` is called with the outcome for each
+    /// market, e.g. to rebuild state when a resync was incomplete.
+    pub async fn subscribe_trade_stream<F>(
+        &self,
+        markets: &[&str],
+        on_resync: F,
+    ) -> Result<TradeStream>
+    where
+        F: Fn(&TradeResync) + Send + Sync + 'static,
+    {
+        // listen before subscribing, so no reconnect is missed in between
+        let connection_events = self.connection_events();
+        let trades = self
+            .subscribe_markets(markets, |market| SubscribeTrades { market })
+            .await?;
+        let trackers: HashMap<_, _> = markets
+            .iter()
+            .map(|market| (market.to_string(), TradeTracker::default()))
+            .collect();
+        let trackers = Arc::new(SyncMutex::new(trackers));
+        let (sender, receiver) = mpsc::unbounded_channel();
+        let stop = CancellationToken::new();
+        tokio::spawn(forward_trades(
+            self.clone(),
+            trades,
+            connection_events,
+            trackers.clone(),
+            sender,
+            on_resync,
+            stop.clone(),
+        ));
+        Ok(TradeStream {
+            receiver,
+            trackers,
+            stop,
+        })
+    }
+
+    /// Send the trades of `market` missed since the last one received
+    async fn resync_trades(
+        &self,
+        market: &str,
+        trackers: &SyncMutex<HashMap<String, TradeTracker>>,
+        sender: &mpsc::UnboundedSender<Result<Trade>>,
+    ) -> Result<TradeResync> {
+        let latest = self
+            .run(ListTradesRequest {
+                market: market.to_string(),
+                limit: Some(RESYNC_PAGE_SIZE),
+                before: None,
+            })
+            .await?
+            .response_or_error()?
+            .trades;
+        let mut trackers = trackers.lock().unwrap();
+        let tracker = trackers.entry(market.to_string()).or_default();
+        let since = tracker.sequence.last_executed_at;
+        let (missed, complete) = tracker.missed(latest);
+        let mut recovered = 0;
+        for trade in missed {
+            if tracker.accept(&trade) {
+                recovered += 1;
+                let _ = sender.send(Ok(trade));
+            }
+        }
+        Ok(TradeResync {
+            market: market.to_string(),
+            since,
+            recovered,
+            complete,
+        })
+    }
+}
+
+async fn forward_trades<F>(
+    client: Client,
+    mut trades: MarketSubscriptionHandle<TradesResponse>,
+    mut connection_events: broadcast::Receiver<ConnectionEvent>,
+    trackers: Arc<SyncMutex<HashMap<String, TradeTracker>>>,
+    sender: mpsc::UnboundedSender<Result<Trade>>,
+    on_resync: F,
+    stop: CancellationToken,
+) where
+    F: Fn(&TradeResync),
+{
+    let mut interrupted = false;
+    loop {
+        tokio::select! {
+            event = trades.recv() => {
+                let response = match event {
+                    Some(MarketEvent { event, .. }) => event.and_then(|e| e.response_or_error()),
+                    None => return,
+                };
+                let response = match response {
+                    Ok(response) => response,
+                    Err(e) => {
+                        if sender.send(Err(e)).is_err() {
+                            return;
+                        }
+                        continue;
+                    }
+                };
+                let mut trackers = trackers.lock().unwrap();
+                let tracker = trackers.entry(response.market.clone()).or_default();
+                for trade in response.trades {
+                    if tracker.accept(&trade) && sender.send(Ok(trade)).is_err() {
+                        return;
+                    }
+                }
+            }
+            event = connection_events.recv() => match event {
+                Ok(ConnectionEvent::Connected { .. }) if interrupted => {
+                    interrupted = false;
+                    for market in trades.markets().to_vec() {
+                        match client.resync_trades(&market, &trackers, &sender).await {
+                            Ok(resync) => on_resync(&resync),
+                            Err(e) => {
+                                warn!(%market, error = %e, "could not recover missed trades");
+                                if sender.send(Err(e)).is_err() {
+                                    return;
+                                }
+                            }
+                        }
+                    }
+                }
+                Ok(ConnectionEvent::Disconnected) | Ok(ConnectionEvent::Reconnecting { .. }) => {
+                    interrupted = true
+                }
+                // missed connection events may have hidden a reconnect
+                Err(broadcast::error::RecvError::Lagged(_)) => interrupted = true,
+                _ => {}
+            },
+            _ = stop.cancelled() => return,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::TradeTracker;
+    use bigdecimal::BigDecimal;
+    use nash_protocol::types::timestamp::parse_timestamp;
+    use nash_protocol::types::{AccountTradeSide, BuyOrSell, Trade};
+
+    fn trade(id: &str, second: u32) -> Trade {
+        Trade {
+            id: id.to_string(),
+            taker_order_id: "taker".to_string(),
+            maker_order_id: "maker".to_string(),
+            amount: BigDecimal::from(1),
+            executed_at: parse_timestamp(&format!("2021-01-01T00:00:{:02}Z", second)).unwrap(),
+            account_side: AccountTradeSide::None,
+            maker_fee: BigDecimal::from(0),
+            taker_fee: BigDecimal::from(0),
+            maker_recieved: BigDecimal::from(0),
+            taker_recieved: BigDecimal::from(0),
+            market: "eth_usdc".to_string(),
+            direction: BuyOrSell::Buy,
+            limit_price: BigDecimal::from(100),
+        }
+    }
+
+    #[test]
+    fn missed_trades_are_found_once() {
+        let mut tracker = TradeTracker::default();
+        assert!(tracker.accept(&trade("a", 1)));
+        assert!(tracker.accept(&trade("b", 2)));
+        assert!(!tracker.accept(&trade("b", 2)));
+        assert_eq!(tracker.sequence.received, 2);
+        assert_eq!(tracker.sequence.last_trade_id.as_deref(), Some("b"));
+
+        // newest first, as the exchange lists them
+        let latest = vec![trade("d", 4), trade("c", 2), trade("b", 2), trade("a", 1)];
+        let (missed, complete) = tracker.missed(latest);
+        let ids: Vec<_> = missed.iter().map(|trade| trade.id.as_str()).collect();
+        assert_eq!(ids, vec!["c", "d"]);
+        assert!(complete);
+
+        for trade in &missed {
+            assert!(tracker.accept(trade));
+        }
+        let (missed, complete) = tracker.missed(vec![trade("f", 6), trade("e", 5)]);
+        assert_eq!(missed.len(), 2);
+        assert!(!complete);
+    }
+}
@@ -0,0 +1,293 @@
+//! Trailing stops held by the client. The stop follows the last price of the market from the
+//! ticker subscription at a fixed distance, and a market order is placed once the price
+//! turns back by that distance. Trigger state is persisted, so a restarted client resumes
+//! a stop from the best price seen so far. A stop is stored as fired before its market
+//! order is sent, and fired stops are never resumed, so a crash can't place it twice.
+
+use std::path::PathBuf;
+
+use bigdecimal::{BigDecimal, Signed};
+use serde::{Deserialize, Serialize};
+use tokio::task::JoinHandle;
+use tokio_util::sync::CancellationToken;
+use tracing::{info, warn};
+
+use nash_protocol::errors::{ProtocolError, Result};
+use nash_protocol::protocol::get_ticker::TickerRequest;
+use nash_protocol::protocol::place_order::{MarketOrderRequest, PlaceOrderResponse};
+use nash_protocol::protocol::subscriptions::updated_ticker::SubscribeTicker;
+use nash_protocol::types::{BuyOrSell, MarketSymbol};
+
+use crate::json_store::{JsonStore, Stored};
+use crate::Client;
+
+/// Distance the stop keeps from the best price seen
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum TrailingOffset {
+    /// In the quote asset of the market
+    Absolute(BigDecimal),
+    /// Fraction of the best price, e.g. 0.02 for 2%
+    Relative(BigDecimal),
+}
+
+/// A trailing stop on an A_B market and how far it has trailed
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct TrailingStop {
+    pub id: String,
+    pub market: String,
+    /// `Sell` protects a long position: the stop trails below the highest price and sells
+    /// `amount` of A. `Buy` protects a short one: the stop trails above the lowest price and
+    /// spends `amount` of B.
+    pub buy_or_sell: BuyOrSell,
+    pub amount: String,
+    pub offset: TrailingOffset,
+    /// Highest (selling) or lowest (buying) last price seen since the stop was placed
+    pub best_price: Option<BigDecimal>,
+    /// Set once the stop triggered, before its market order is sent
+    #[serde(default)]
+    pub fired: bool,
+}
+
+impl Stored for TrailingStop {
+    const KIND: &'static str = "trailing stop";
+
+    fn id(&self) -> &str {
+        &self.id
+    }
+}
+
+impl TrailingStop {
+    fn validate(&self) -> Result<()> {
+        let positive = match &self.offset {
+            TrailingOffset::Absolute(offset) => offset.is_positive(),
+            TrailingOffset::Relative(offset) => {
+                offset.is_positive() && offset < &BigDecimal::from(1)
+            }
+        };
+        if !positive {
+            return Err(ProtocolError(
+                "Trailing offset must be positive, and below one if relative",
+            ));
+        }
+        Ok(())
+    }
+
+    /// Price at which the stop triggers, once a price has been seen
+    pub fn stop_price(&self) -> Option<BigDecimal> {
+        let best = self.best_price.as_ref()?;
+        let offset = match &self.offset {
+            TrailingOffset::Absolute(offset) => offset.clone(),
+            TrailingOffset::Relative(fraction) => best * fraction,
+        };
+        Some(match self.buy_or_sell {
+            BuyOrSell::Sell => best - offset,
+            BuyOrSell::Buy => best + offset,
+        })
+    }
+
+    /// Account for a new last price. Returns whether the best price moved, so the stop
+    /// needs to be stored again, and whether the stop triggered.
+    fn on_price(&mut self, price: &BigDecimal) -> (bool, bool) {
+        let improved = match (&self.best_price, self.buy_or_sell) {
+            (None, _) => true,
+            (Some(best), BuyOrSell::Sell) => price > best,
+            (Some(best), BuyOrSell::Buy) => price < best,
+        };
+        if improved {
+            self.best_price = Some(price.clone());
+        }
+        let triggered = match (self.stop_price(), self.buy_or_sell) {
+            (Some(stop), BuyOrSell::Sell) => price <= &stop,
+            (Some(stop), BuyOrSell::Buy) => price >= &stop,
+            (None, _) => false,
+        };
+        (improved, triggered)
+    }
+
+    /// Market order closing the position. Nash market orders sell their amount of A, so a
+    /// buy is sent on the reversed market.
+    fn market_order(&self) -> Result<MarketOrderRequest> {
+        let market: MarketSymbol = self.market.parse()?;
+        let market = match self.buy_or_sell {
+            BuyOrSell::Sell => market,
+            BuyOrSell::Buy => market.reversed(),
+        };
+        MarketOrderRequest::new(market, &self.amount, None)
+    }
+}
+
+/// Handle to a trailing stop. Dropping it leaves the stop in place.
+pub struct TrailingStopHandle {
+    pub id: String,
+    cancel: CancellationToken,
+    task: JoinHandle<Result<PlaceOrderResponse>>,
+}
+
+impl TrailingStopHandle {
+    /// Remove the stop, unless it has already triggered
+    pub fn cancel(&self) {
+        self.cancel.cancel();
+    }
+
+    /// Wait for the stop to trigger and its market order to be placed
+    pub async fn triggered(self) -> Result<PlaceOrderResponse> {
+        self.task
+            .await
+            .map_err(|_| ProtocolError("Trailing stop task failed"))?
+    }
+}
+
+impl Client {
+    /// Place a trailing stop closing `amount` on `market`, see `TrailingStop`. The market
+    /// order goes through the same pre-trade checks as `run` when the stop triggers.
+    pub async fn trailing_stop(
+        &self,
+        market: &str,
+        buy_or_sell: BuyOrSell,
+        amount: &str,
+        offset: TrailingOffset,
+    ) -> Result<TrailingStopHandle> {
+        let stop = TrailingStop {
+            id: self.inner.rng.id(),
+            market: market.to_string(),
+            buy_or_sell,
+            amount: amount.to_string(),
+            offset,
+            best_price: None,
+            fired: false,
+        };
+        stop.validate()?;
+        self.warm_up(market).await?;
+        self.inner.trailing_stops.persist(&stop)?;
+        Ok(self.spawn_trailing_stop(stop))
+    }
+
+    /// Persist trailing stops as JSON files in `dir`, updated whenever a stop trails further.
+    /// Stops found there are resumed and returned. Stops that fired before, whether or not
+    /// their market order went through, are removed instead.
+    pub fn set_trailing_stop_store(
+        &self,
+        dir: impl Into<PathBuf>,
+    ) -> Result<Vec<TrailingStopHandle>> {
+        let mut stops = self.inner.trailing_stops.open(dir.into())?;
+        stops.retain(|stop| {
+            if stop.fired {
+                warn!(id = %stop.id, market = %stop.market, "trailing stop fired before, not resuming it");
+                self.inner.trailing_stops.remove(&stop.id);
+            }
+            !stop.fired
+        });
+        Ok(stops
+            .into_iter()
+            .map(|stop| self.spawn_trailing_stop(stop))
+            .collect())
+    }
+
+    fn spawn_trailing_stop(&self, stop: TrailingStop) -> TrailingStopHandle {
+        let cancel = CancellationToken::new();
+        let client = self.clone();
+        let id = stop.id.clone();
+        let task_cancel = cancel.clone();
+        let task = tokio::spawn(async move {
+            let id = stop.id.clone();
+            let result = client.run_trailing_stop(stop, &task_cancel).await;
+            match &result {
+                Ok(_) => client.inner.trailing_stops.remove(&id),
+                Err(_) if task_cancel.is_cancelled() => client.inner.trailing_stops.remove(&id),
+                // kept in the store, so a restart tries again unless it fired already
+                Err(e) => warn!(%id, error = %e, "trailing stop failed"),
+            }
+            result
+        });
+        TrailingStopHandle { id, cancel, task }
+    }
+
+    async fn run_trailing_stop(
+        &self,
+        mut stop: TrailingStop,
+        cancel: &CancellationToken,
+    ) -> Result<PlaceOrderResponse> {
+        let request = stop.market_order()?;
+        let mut updates = self
+            .subscribe_protocol(SubscribeTicker {
+                market: stop.market.clone(),
+            })
+            .await?;
+        let ticker = self
+            .run(TickerRequest {
+                market: stop.market.clone(),
+            })
+            .await?
+            .response_or_error()?;
+        let mut last_price = ticker.last_price;
+        loop {
+            if let Some(price) = &last_price {
+                let (improved, triggered) = stop.on_price(price);
+                if triggered {
+                    break;
+                }
+                if improved {
+                    self.inner.trailing_stops.persist(&stop)?;
+                }
+            }
+            let update = tokio::select! {
+                update = updates.recv() => update,
+                _ = cancel.cancelled() => {
+                    return Err(ProtocolError("Trailing stop was cancelled"));
+                }
+            };
+            last_price = match update {
+                Some(update) => update?.response_or_error()?.last_price,
+                None => return Err(ProtocolError("Ticker subscription ended")),
+            };
+        }
+        info!(id = %stop.id, market = %stop.market, stop_price = ?stop.stop_price(), "trailing stop triggered");
+        stop.fired = true;
+        self.inner.trailing_stops.persist(&stop)?;
+        self.run(request).await?.response_or_error()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{BigDecimal, BuyOrSell, TrailingOffset, TrailingStop};
+    use std::str::FromStr;
+
+    fn dec(value: &str) -> BigDecimal {
+        BigDecimal::from_str(value).unwrap()
+    }
+
+    #[test]
+    fn stop_trails_best_price() {
+        let mut stop = TrailingStop {
+            id: "stop".to_string(),
+            market: "eth_usdc".to_string(),
+            buy_or_sell: BuyOrSell::Sell,
+            amount: "1".to_string(),
+            offset: TrailingOffset::Absolute(dec("10")),
+            best_price: None,
+            fired: false,
+        };
+        assert_eq!(stop.on_price(&dec("200")), (true, false));
+        assert_eq!(stop.on_price(&dec("195")), (false, false));
+        assert_eq!(stop.on_price(&dec("220")), (true, false));
+        assert_eq!(stop.stop_price(), Some(dec("210")));
+        assert_eq!(stop.on_price(&dec("210")), (false, true));
+
+        stop.buy_or_sell = BuyOrSell::Buy;
+        stop.offset = TrailingOffset::Relative(dec("0.1"));
+        stop.best_price = None;
+        assert_eq!(stop.on_price(&dec("100")), (true, false));
+        assert_eq!(stop.on_price(&dec("90")), (true, false));
+        assert_eq!(stop.on_price(&dec("99")), (false, true));
+        assert_eq!(stop.market_order().unwrap().market, "usdc_eth");
+    }
+
+    #[test]
+    fn stops_stored_before_fired_marker_load_as_not_fired() {
+        let stored = r#"{"id":"stop","market":"eth_usdc","buy_or_sell":"SELL","amount":"1","offset":{"Absolute":"10"},"best_price":"200"}"#;
+        let stop: TrailingStop = serde_json::from_str(stored).unwrap();
+        assert!(!stop.fired);
+        assert_eq!(stop.best_price, Some(dec("200")));
+    }
+}
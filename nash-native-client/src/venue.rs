@@ -0,0 +1,131 @@
+//! `exchange_traits` implementation for `Client`, so multi-venue systems can trade on Nash
+//! through the same traits as on other venues
+
+use async_trait::async_trait;
+use bigdecimal::BigDecimal;
+use exchange_traits::{
+    Balance, Balances, MarketData, OrderAck, OrderEntry, OrderRequest, Side, TimeInForce,
+    TopOfBook, Venue,
+};
+use futures::stream::{BoxStream, StreamExt};
+
+use nash_protocol::errors::{ProtocolError, Result};
+use nash_protocol::protocol::cancel_order::CancelOrderRequest;
+use nash_protocol::protocol::get_ticker::TickerRequest;
+use nash_protocol::protocol::list_account_balances::ListAccountBalancesRequest;
+use nash_protocol::protocol::place_order::LimitOrderRequest;
+use nash_protocol::protocol::subscriptions::updated_ticker::SubscribeTicker;
+use nash_protocol::types::{BuyOrSell, OrderCancellationPolicy};
+
+use crate::Client;
+
+impl Venue for Client {
+    type Error = ProtocolError;
+
+    fn venue_name(&self) -> &str {
+        "nash"
+    }
+}
+
+#[async_trait]
+impl OrderEntry for Client {
+    /// Places the order through `run`, so the client's pre-trade checks apply
+    async fn place_order(&self, order: &OrderRequest) -> Result<OrderAck> {
+        let request = LimitOrderRequest {
+            market: order.market.clone(),
+            client_order_id: None,
+            buy_or_sell: match order.side {
+                Side::Buy => BuyOrSell::Buy,
+                Side::Sell => BuyOrSell::Sell,
+            },
+            amount: order.amount.to_string(),
+            price: order.price.to_string(),
+            cancellation_policy: match order.time_in_force {
+                TimeInForce::GoodTilCancelled => OrderCancellationPolicy::GoodTilCancelled,
+                TimeInForce::ImmediateOrCancel => OrderCancellationPolicy::ImmediateOrCancel,
+                TimeInForce::FillOrKill => OrderCancellationPolicy::FillOrKill,
+            },
+            allow_taker: !order.post_only,
+        };
+        let placed = self.run(request).await?.response_or_error()?;
+        Ok(OrderAck {
+            order_id: placed.order_id,
+        })
+    }
+
+    async fn cancel_order(&self, market: &str, order_id: &str) -> Result<()> {
+        let request = CancelOrderRequest {
+            order_id: order_id.to_string(),
+            market: market.to_string(),
+        };
+        self.run(request).await?.response_or_error()?;
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl Balances for Client {
+    /// State channel balances; funds in the personal wallet are not tradable and left out
+    async fn balances(&self) -> Result<Vec<Balance>> {
+        let response = self
+            .run(ListAccountBalancesRequest { filter: None })
+            .await?
+            .response_or_error()?;
+        let mut assets: Vec<_> = response
+            .state_channel
+            .keys()
+            .chain(response.in_orders.keys())
+            .collect();
+        assets.sort_by_key(|asset| asset.name());
+        assets.dedup();
+        let zero = BigDecimal::from(0);
+        Ok(assets
+            .into_iter()
+            .map(|asset| Balance {
+                asset: asset.name().to_string(),
+                available: response.state_channel.get(asset).unwrap_or(&zero).clone(),
+                in_orders: response.in_orders.get(asset).unwrap_or(&zero).clone(),
+            })
+            .collect())
+    }
+}
+
+#[async_trait]
+impl MarketData for Client {
+    async fn top_of_book(&self, market: &str) -> Result<TopOfBook> {
+        let ticker = self
+            .run(TickerRequest {
+                market: market.to_string(),
+            })
+            .await?
+            .response_or_error()?;
+        Ok(TopOfBook {
+            market: ticker.market_name,
+            best_bid: ticker.best_bid_price,
+            best_ask: ticker.best_ask_price,
+            last_price: ticker.last_price,
+        })
+    }
+
+    async fn subscribe_top_of_book(
+        &self,
+        market: &str,
+    ) -> Result<BoxStream<'static, Result<TopOfBook>>> {
+        let updates = self
+            .subscribe_protocol(SubscribeTicker {
+                market: market.to_string(),
+            })
+            .await?;
+        Ok(updates
+            .map(|update| {
+                let ticker = update?.response_or_error()?;
+                Ok(TopOfBook {
+                    market: ticker.market_name,
+                    best_bid: ticker.best_bid_price,
+                    best_ask: ticker.best_ask_price,
+                    last_price: ticker.last_price,
+                })
+            })
+            .boxed())
+    }
+}
@@ -0,0 +1,16 @@
+//! Withdrawals to addresses on the asset's blockchain
+
+use nash_protocol::errors::Result;
+use nash_protocol::protocol::withdraw::{WithdrawRequest, WithdrawResponse};
+
+use crate::Client;
+
+impl Client {
+    /// Withdraw funds from the state channel: have the exchange prepare the withdrawal, then
+    /// check and sign its blockchain payloads and submit it. Signing takes one r value per
+    /// payload, from the pools `start_background_fill_pool_loop` keeps filled.
+    pub async fn withdraw(&self, request: WithdrawRequest) -> Result<WithdrawResponse> {
+        let prepared = self.run(request).await?.response_or_error()?;
+        self.run(prepared).await?.response_or_error()
+    }
+}
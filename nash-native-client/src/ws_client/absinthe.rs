@@ -123,6 +123,7 @@ pub enum AbsintheEvent {
     SubscriptionData,
     Heartbeat, // to maintain connection
     Error,
+    Unsubscribe,
 }
 
 #[derive(Debug, Clone)]
@@ -277,6 +278,7 @@ impl Serialize for AbsintheEvent {
             Self::SubscriptionData => serializer.serialize_str(&"subscription:data".to_string()),
             Self::Heartbeat => serializer.serialize_str(&"heartbeat".to_string()),
             Self::Error => serializer.serialize_str(&"phx_error".to_string()),
+            Self::Unsubscribe => serializer.serialize_str(&"unsubscribe".to_string()),
         }
     }
 }
@@ -307,6 +309,7 @@ impl<'de> Deserialize<'de> for AbsintheEvent {
                     "heartbeat" => Ok(AbsintheEvent::Heartbeat),
                     "subscription:data" => Ok(AbsintheEvent::SubscriptionData),
                     "phx_error" => Ok(AbsintheEvent::Error),
+                    "unsubscribe" => Ok(AbsintheEvent::Unsubscribe),
                     _ => Err(de::Error::custom("Bad AbsintheEvent field value")),
                 }
             }
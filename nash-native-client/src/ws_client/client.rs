@@ -1,37 +1,136 @@
 //! Client implementation of Nash API over websockets using channels and message brokers
 
 use std::any::type_name;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::sync::atomic::{AtomicU64, Ordering};
-use std::sync::Arc;
+use std::sync::{Arc, Mutex as SyncMutex, RwLock as SyncRwLock};
+use std::time::Instant;
 
 use async_recursion::async_recursion;
 use futures::{FutureExt, SinkExt, StreamExt};
 use futures_util::future::{select, Either};
-use rand::Rng;
-use tokio::{net::TcpStream, sync::mpsc, sync::oneshot, sync::RwLock, time::Duration};
-use tokio_tungstenite::tungstenite::protocol::Message;
-use tokio_tungstenite::{connect_async, MaybeTlsStream, WebSocketStream};
+use tokio::{
+    net::TcpStream, sync::broadcast, sync::mpsc, sync::oneshot, sync::watch, sync::RwLock,
+    time::Duration,
+};
+use tokio_tungstenite::tungstenite::client::IntoClientRequest;
+use tokio_tungstenite::tungstenite::protocol::{Message, WebSocketConfig};
+use tokio_util::sync::CancellationToken;
+use tokio_tungstenite::{client_async_tls_with_config, MaybeTlsStream, WebSocketStream};
 use tracing::{error, info_span, trace, warn, Instrument};
 
 use nash_protocol::errors::{ProtocolError, Result};
 use nash_protocol::protocol::subscriptions::SubscriptionResponse;
 use nash_protocol::protocol::{
-    ErrorResponse, NashProtocol, NashProtocolPipeline, NashProtocolSubscription, ResponseOrError,
-    State,
+    is_persisted_query_not_found, ErrorResponse, LatencyBudget, NashProtocol,
+    NashProtocolPipeline, NashProtocolSubscription, PersistedQueries, ResponseOrError,
+    StageTimings, State, TimedNashProtocol,
 };
 use nash_protocol::types::Blockchain;
 
-use crate::http_extension::HttpClientState;
+use crate::capture::WireCapture;
+use crate::config::{
+    state_from_env, BatchLimits, ClientConfig, HeadersConfig, RateLimits, RequoteLimits,
+    DEFAULT_SESSION_REFRESH,
+};
+use crate::http_extension::{header_map, HttpClientState, HttpOptions};
+use crate::json_store::JsonStore;
+use crate::outbox::Outbox;
+use crate::random::ClientRng;
+use crate::risk::{Approvals, DeadManSwitch, ExchangeThrottle, PriceGuard, RequoteThrottle};
+use crate::schedule::Schedules;
+use crate::trailing::TrailingStop;
 use crate::Environment;
 
 use super::absinthe::{AbsintheEvent, AbsintheTopic, AbsintheWSRequest, AbsintheWSResponse};
+use super::events::{display_endpoint, ConnectionEvent, ConnectionEvents};
+use super::longpoll::{spawn_longpoll_loop, LongPollSession};
+use super::subscription::{SubscriptionControl, SubscriptionHandle, SubscriptionLink};
 use nash_protocol::protocol::dh_fill_pool::DhFillPoolRequest;
+use nash_protocol::protocol::refresh_token::RefreshTokenRequest;
 use nash_protocol::protocol::sign_all_states::SignAllStates;
 
 type WebSocket = WebSocketStream<MaybeTlsStream<TcpStream>>;
 
+/// Connection Absinthe messages are exchanged over
+enum Transport {
+    WebSocket(WebSocket),
+    LongPoll(LongPollSession),
+}
+
 const HEARTBEAT_MESSAGE_ID: u64 = 0;
+/// Websocket connection attempts before falling back to long polling
+const WS_CONNECT_ATTEMPTS: u32 = 3;
+
+/// Settings for the websocket connection
+#[derive(Clone, Debug)]
+pub(crate) struct WsOptions {
+    pub connect_timeout: Duration,
+    pub max_frame_size: Option<usize>,
+    pub send_queue_depth: Option<usize>,
+    /// Heartbeat period, the request timeout if not set
+    pub ping_interval: Option<Duration>,
+    pub tcp_nodelay: bool,
+    /// Session token refresh period of authenticated clients, never refreshed if not set
+    pub session_refresh: Option<Duration>,
+    /// Sent on the handshake, and with every request when falling back to long polling
+    pub headers: Vec<(String, String)>,
+}
+
+impl Default for WsOptions {
+    fn default() -> Self {
+        Self {
+            connect_timeout: Duration::from_secs(10),
+            max_frame_size: None,
+            send_queue_depth: None,
+            ping_interval: None,
+            tcp_nodelay: false,
+            session_refresh: Some(DEFAULT_SESSION_REFRESH),
+            headers: HeadersConfig::default().to_list(),
+        }
+    }
+}
+
+/// Settings for all transports of a client
+#[derive(Clone, Debug, Default)]
+pub(crate) struct TransportOptions {
+    pub http: HttpOptions,
+    pub ws: WsOptions,
+    pub events: ConnectionEvents,
+}
+
+/// Open a websocket to `url` on `domain` (a host, optionally with port) applying `options`
+async fn connect_websocket(url: &str, domain: &str, options: &WsOptions) -> Result<WebSocket> {
+    let address = if domain.contains(':') {
+        domain.to_string()
+    } else {
+        format!("{}:443", domain)
+    };
+    let config = WebSocketConfig {
+        max_send_queue: options.send_queue_depth,
+        max_frame_size: options.max_frame_size,
+        ..WebSocketConfig::default()
+    };
+    let mut request = url
+        .into_client_request()
+        .map_err(|e| ProtocolError::with_source(e.to_string(), e))?;
+    request.headers_mut().extend(header_map(&options.headers)?);
+    let connect = async {
+        let stream = TcpStream::connect(&address)
+            .await
+            .map_err(|e| ProtocolError::with_source(e.to_string(), e).context("connecting to WS"))?;
+        stream
+            .set_nodelay(options.tcp_nodelay)
+            .map_err(|e| ProtocolError::with_source(e.to_string(), e))?;
+        client_async_tls_with_config(request, stream, Some(config), None)
+            .await
+            .map(|(socket, _response)| socket)
+            .map_err(|e| ProtocolError::with_source(e.to_string(), e).context("connecting to WS"))
+    };
+    tokio::time::timeout(options.connect_timeout, connect)
+        .await
+        .map_err(|_| ProtocolError("Timed out connecting to WS"))?
+}
 // this will add heartbeat (keep alive) messages to the channel for ws to send out every 15s
 pub fn spawn_heartbeat_loop(
     period: Duration,
@@ -67,6 +166,7 @@ pub fn spawn_sender_loop(
     )>,
     mut ws_disconnect_receiver: mpsc::UnboundedReceiver<()>,
     message_broker_link: mpsc::UnboundedSender<BrokerAction>,
+    events: ConnectionEvents,
 ) {
     tokio::spawn(async move {
         // The idea is that try_recv will only work when it receives a disconnect signal
@@ -109,11 +209,11 @@ pub fn spawn_sender_loop(
                     if let Ok(incoming) = incoming {
                         if let Some(Ok(message)) = incoming {
                             let raw_response = message.into_text().map_err(|e| {
-                                ProtocolError::coerce_static_from_str(e.to_string().as_str())
+                                ProtocolError::with_source(e.to_string(), e)
                             });
                             let response: Result<AbsintheWSResponse> = raw_response.and_then(|r| {
                                 serde_json::from_str(&r).map_err(|e| {
-                                    ProtocolError::coerce_static_from_str(e.to_string().as_str())
+                                    ProtocolError::with_source(e.to_string(), e)
                                 })
                             });
                             match response {
@@ -123,7 +223,7 @@ pub fn spawn_sender_loop(
                                         .send(BrokerAction::Message(Ok(response)));
                                 }
                                 Err(e) => {
-                                    error!(error = %e, "RECV invalid response message");
+                                    error!(error = %e.report(), "RECV invalid response message");
                                     let _ = message_broker_link.send(BrokerAction::Message(Err(e)));
                                     break;
                                 }
@@ -146,6 +246,7 @@ pub fn spawn_sender_loop(
             };
         }
         error!("DISCONNECT");
+        events.emit(ConnectionEvent::Disconnected);
         let error = ProtocolError("Disconnected.");
         message_broker_link
             .send(BrokerAction::Message(Err(error)))
@@ -163,6 +264,7 @@ fn global_subscription_loop<T: NashProtocolSubscription + Send + Sync + 'static>
     >,
     request: T,
     state: Arc<RwLock<State>>,
+    control: Arc<SubscriptionControl>,
 ) {
     tokio::spawn(async move {
         loop {
@@ -200,10 +302,12 @@ fn global_subscription_loop<T: NashProtocolSubscription + Send + Sync + 'static>
                             }
                             Err(e) => Err(e),
                         };
-                        // If callback_channel fails, kill process
-                        if let Err(_e) = user_callback_sender.send(output) {
-                            // Note: we do not want to kill the process in this case! User could just have destroyed the individual callback stream
-                            // and we still want to send to the global stream! maybe add a log here in the future
+                        // Paused handles miss events, everything else still sees them
+                        if !control.is_paused() {
+                            if let Err(_e) = user_callback_sender.send(output) {
+                                // Note: we do not want to kill the process in this case! User could just have destroyed the individual callback stream
+                                // and we still want to send to the global stream! maybe add a log here in the future
+                            }
                         }
 
                         // Now do global subscription logic. If global channel fails, also kill process
@@ -221,14 +325,18 @@ fn global_subscription_loop<T: NashProtocolSubscription + Send + Sync + 'static>
                 }
                 Some(Err(e)) => {
                     // kill process due to closed channel
+                    control.kill();
                     let _ = global_subscription_sender.send(Err(e));
                     // if for some reason the global subscription doesn't exist anymore (likely because client doesn't exist!) then just ignore
                     // and close out the process loop
                     break;
                 }
                 None => {
-                    let _ = global_subscription_sender
-                        .send(Err(ProtocolError("channel returned None. dead?")));
+                    // the broker drops the channel when the subscription was closed on purpose
+                    if control.kill() {
+                        let _ = global_subscription_sender
+                            .send(Err(ProtocolError("channel returned None. dead?")));
+                    }
                     break;
                 }
             }
@@ -244,6 +352,8 @@ pub enum BrokerAction {
         oneshot::Sender<bool>,
     ),
     RegisterSubscription(String, mpsc::UnboundedSender<Result<AbsintheWSResponse>>),
+    /// Forget a subscription, and silently drop the reply to the unsubscribe message with given id
+    UnregisterSubscription(String, u64),
     Message(Result<AbsintheWSResponse>),
 }
 
@@ -257,6 +367,7 @@ impl MessageBroker {
         tokio::spawn(async move {
             let mut request_map = HashMap::new();
             let mut subscription_map = HashMap::new();
+            let mut ignored_replies = HashSet::new();
             loop {
                 if let Some(next_incoming) = internal_receiver.recv().await {
                     match next_incoming {
@@ -270,6 +381,11 @@ impl MessageBroker {
                             trace!(%id, "BROKER subscription");
                             subscription_map.insert(id, channel);
                         }
+                        BrokerAction::UnregisterSubscription(id, reply_id) => {
+                            trace!(%id, "BROKER unsubscribe");
+                            subscription_map.remove(&id);
+                            ignored_replies.insert(reply_id);
+                        }
                         // When message comes in, if id is registered with channel, send there
                         BrokerAction::Message(Ok(response)) => {
                             // if message has subscription id, send it to subscription
@@ -292,6 +408,8 @@ impl MessageBroker {
                                         // Kill process on error
                                         break;
                                     }
+                                } else if ignored_replies.remove(&id) {
+                                    trace!(id, "BROKER unsubscribe reply");
                                 } else {
                                     if id != HEARTBEAT_MESSAGE_ID {
                                         warn!(
@@ -327,6 +445,20 @@ impl MessageBroker {
     }
 }
 
+/// Emit a structured warning with per stage timings when a request took longer than its budget
+pub(crate) fn warn_if_over_budget<T>(budget: &LatencyBudget, timings: &StageTimings) {
+    if budget.is_exceeded(timings) {
+        warn!(
+            request = type_name::<T>(),
+            budget_us = budget.budget.as_micros() as u64,
+            construction_us = timings.construction.as_micros() as u64,
+            signing_us = timings.signing.as_micros() as u64,
+            transport_us = timings.transport.as_micros() as u64,
+            "latency budget exceeded"
+        );
+    }
+}
+
 pub struct WsClientState {
     ws_outgoing_sender: mpsc::UnboundedSender<(AbsintheWSRequest, Option<oneshot::Receiver<bool>>)>,
     ws_disconnect_sender: mpsc::UnboundedSender<()>,
@@ -348,6 +480,19 @@ impl WsClientState {
 pub struct InnerClient {
     pub(crate) ws_state: WsClientState,
     pub(crate) http_state: HttpClientState,
+    pub(crate) persisted_queries: PersistedQueries,
+    pub(crate) price_guard: SyncRwLock<Option<PriceGuard>>,
+    pub(crate) approvals: SyncRwLock<Option<Arc<Approvals>>>,
+    pub(crate) requote_throttle: RequoteThrottle,
+    pub(crate) exchange_throttle: ExchangeThrottle,
+    pub(crate) batch_limits: SyncRwLock<BatchLimits>,
+    pub(crate) connection_events: ConnectionEvents,
+    pub(crate) schedules: Schedules,
+    pub(crate) trailing_stops: JsonStore<TrailingStop>,
+    pub(crate) outbox: Outbox,
+    pub(crate) dead_man_switch: SyncRwLock<Option<DeadManSwitch>>,
+    pub(crate) capture: WireCapture,
+    pub(crate) rng: ClientRng,
     pub state: Arc<RwLock<State>>,
 }
 
@@ -359,18 +504,39 @@ impl InnerClient {
         timeout: Duration,
         affiliate_code: Option<String>,
         turn_off_sign_states: bool,
+        options: TransportOptions,
     ) -> Result<(
         Self,
         mpsc::UnboundedReceiver<Result<ResponseOrError<SubscriptionResponse>>>,
     )> {
         state.affiliate_code = affiliate_code;
         state.dont_sign_states = turn_off_sign_states;
-        let (ws_state, global_subscription_receiver) =
-            Self::setup_ws(&mut state, client_id, env, timeout).await?;
-        let http_state = Self::setup_http(&mut state, env, timeout).await?;
+        let (ws_state, global_subscription_receiver) = Self::setup_ws(
+            &mut state,
+            client_id,
+            env,
+            timeout,
+            options.ws,
+            options.events.clone(),
+        )
+        .await?;
+        let http_state = Self::setup_http(&mut state, env, timeout, options.http).await?;
         let client = InnerClient {
             ws_state,
             http_state,
+            connection_events: options.events,
+            persisted_queries: PersistedQueries::default(),
+            price_guard: SyncRwLock::new(None),
+            approvals: SyncRwLock::new(None),
+            requote_throttle: RequoteThrottle::new(RequoteLimits::default()),
+            exchange_throttle: ExchangeThrottle::new(RateLimits::default().throttle_backoff()),
+            batch_limits: SyncRwLock::new(BatchLimits::default()),
+            schedules: Schedules::default(),
+            trailing_stops: JsonStore::default(),
+            outbox: Outbox::default(),
+            dead_man_switch: SyncRwLock::new(None),
+            capture: WireCapture::default(),
+            rng: ClientRng::default(),
             state: Arc::new(RwLock::new(state)),
         };
         Ok((client, global_subscription_receiver))
@@ -381,25 +547,76 @@ impl InnerClient {
         client_id: u64,
         env: Environment,
         timeout: Duration,
+        options: WsOptions,
+        events: ConnectionEvents,
     ) -> Result<(
         WsClientState,
         mpsc::UnboundedReceiver<Result<ResponseOrError<SubscriptionResponse>>>,
     )> {
         let version = "2.0.0";
         let domain = env.url();
+        let auth_token = state.signer.as_ref().map(|s| s.api_keys.session_id.clone());
         // Setup authenticated or unauthenticated connection
-        let conn_path = match &state.signer {
-            Some(signer) => format!(
+        let conn_path = match &auth_token {
+            Some(token) => format!(
                 "wss://{}/api/socket/websocket?token={}&vsn={}",
-                domain, signer.api_keys.session_id, version
+                domain, token, version
             ),
             None => format!("wss://{}/api/socket/websocket?vsn={}", domain, version),
         };
 
-        // create connection
-        let (socket, _response) = connect_async(&conn_path).await.map_err(|error| {
-            ProtocolError::coerce_static_from_str(&format!("Could not connect to WS: {}", error))
-        })?;
+        // create connection, falling back to long polling if websockets keep failing
+        let endpoint = display_endpoint(&conn_path);
+        let mut attempt = 0;
+        let socket = loop {
+            attempt += 1;
+            events.emit(ConnectionEvent::Connecting {
+                endpoint: endpoint.clone(),
+            });
+            let started = Instant::now();
+            match connect_websocket(&conn_path, domain, &options).await {
+                Ok(socket) => {
+                    events.emit(ConnectionEvent::Connected {
+                        endpoint,
+                        rtt: started.elapsed(),
+                    });
+                    break Some(socket);
+                }
+                Err(error) if attempt < WS_CONNECT_ATTEMPTS => {
+                    warn!(%error, %attempt, "could not connect to WS, retrying");
+                    events.emit(ConnectionEvent::Reconnecting { attempt });
+                }
+                Err(error) => {
+                    warn!(%error, "could not connect to WS, falling back to long polling");
+                    events.emit(ConnectionEvent::Degraded {
+                        reason: format!("websocket unavailable ({}), using long polling", error),
+                    });
+                    break None;
+                }
+            }
+        };
+        let transport = match socket {
+            Some(socket) => Transport::WebSocket(socket),
+            None => {
+                let endpoint = format!("https://{}/api/socket/longpoll?vsn={}", domain, version);
+                events.emit(ConnectionEvent::Connecting {
+                    endpoint: endpoint.clone(),
+                });
+                let started = Instant::now();
+                let session = LongPollSession::open(
+                    endpoint.clone(),
+                    auth_token.as_deref(),
+                    timeout,
+                    &options.headers,
+                )
+                .await?;
+                events.emit(ConnectionEvent::Connected {
+                    endpoint,
+                    rtt: started.elapsed(),
+                });
+                Transport::LongPoll(session)
+            }
+        };
 
         // channels to pass messages between threads. bounded at 100 unprocessed
         let (ws_outgoing_sender, ws_outgoing_receiver) = mpsc::unbounded_channel();
@@ -409,13 +626,23 @@ impl InnerClient {
         let message_broker = MessageBroker::new();
 
         // This will loop over WS connection, send things out, and route things in
-        spawn_sender_loop(
-            timeout,
-            socket,
-            ws_outgoing_receiver,
-            ws_disconnect_receiver,
-            message_broker.link.clone(),
-        );
+        match transport {
+            Transport::WebSocket(socket) => spawn_sender_loop(
+                timeout,
+                socket,
+                ws_outgoing_receiver,
+                ws_disconnect_receiver,
+                message_broker.link.clone(),
+                events,
+            ),
+            Transport::LongPoll(session) => spawn_longpoll_loop(
+                session,
+                ws_outgoing_receiver,
+                ws_disconnect_receiver,
+                message_broker.link.clone(),
+                events,
+            ),
+        }
 
         // initialize the connection (first message id, 1)
         let message_id = 1;
@@ -424,7 +651,8 @@ impl InnerClient {
             .map_err(|_| ProtocolError("Could not initialize connection with Nash"))?;
 
         // start a heartbeat loop
-        spawn_heartbeat_loop(timeout, client_id, ws_outgoing_sender.clone());
+        let ping_interval = options.ping_interval.unwrap_or(timeout);
+        spawn_heartbeat_loop(ping_interval, client_id, ws_outgoing_sender.clone());
 
         let client_state = WsClientState {
             ws_outgoing_sender,
@@ -471,6 +699,50 @@ impl InnerClient {
         Ok(callback_channel)
     }
 
+    /// Send a GraphQL request over websockets and wait for the response payload
+    async fn request_graphql(&self, graphql_request: serde_json::Value) -> Result<serde_json::Value> {
+        let captured = if self.capture.is_active() {
+            Some(graphql_request.clone())
+        } else {
+            None
+        };
+        let started = Instant::now();
+        let response = async {
+            let ws_response =
+                tokio::time::timeout(self.ws_state.timeout, self.request(graphql_request).await?)
+                    .await
+                    .map_err(|_| ProtocolError("Request timeout"))?
+                    .map_err(|_| ProtocolError("Failed to receive response from return channel"))??;
+            ws_response.json_payload()
+        }
+        .await;
+        if let Some(request) = captured {
+            self.capture
+                .record("ws", &request, &response, started.elapsed());
+        }
+        response
+    }
+
+    /// Same as `request_graphql`, but only sends the query hash once the server has registered
+    /// the query if persisted queries are enabled
+    async fn request_graphql_persisted(
+        &self,
+        graphql_request: serde_json::Value,
+    ) -> Result<serde_json::Value> {
+        match self.persisted_queries.prepare(&graphql_request) {
+            None => self.request_graphql(graphql_request).await,
+            Some((body, hash)) => {
+                let mut response = self.request_graphql(body).await?;
+                if is_persisted_query_not_found(&response) {
+                    let body = self.persisted_queries.register(&graphql_request, &hash);
+                    response = self.request_graphql(body).await?;
+                }
+                self.persisted_queries.record_response(hash, &response);
+                Ok(response)
+            }
+        }
+    }
+
     /// Execute a NashProtocol request. Query will be created, executed over network, response will
     /// be passed to the protocol's state update hook, and response will be returned. Used by the even
     /// more generic `run(..)`.
@@ -479,12 +751,17 @@ impl InnerClient {
         request: T,
     ) -> Result<ResponseOrError<T::Response>> {
         let graphql_request = request.graphql(self.state.clone()).await?;
-        let ws_response =
-            tokio::time::timeout(self.ws_state.timeout, self.request(graphql_request).await?)
-                .await
-                .map_err(|_| ProtocolError("Request timeout"))?
-                .map_err(|_| ProtocolError("Failed to receive response from return channel"))??;
-        let graphql_response = ws_response.json_payload()?;
+        self.execute_graphql(&request, graphql_request).await
+    }
+
+    /// Submit an already constructed query for `request` and run the protocol's response handling
+    async fn execute_graphql<T: NashProtocol>(
+        &self,
+        request: &T,
+        graphql_request: serde_json::Value,
+    ) -> Result<ResponseOrError<T::Response>> {
+        self.exchange_throttle.wait().await;
+        let graphql_response = self.request_graphql_persisted(graphql_request).await?;
         let protocol_response = request
             .response_from_json(graphql_response, self.state.clone())
             .await?;
@@ -495,6 +772,7 @@ impl InnerClient {
                     .await?;
             }
             ResponseOrError::Error(ref error_response) => {
+                self.back_off_if_throttled(error_response);
                 request
                     .process_error(error_response, None, self.state.clone())
                     .await?;
@@ -503,28 +781,39 @@ impl InnerClient {
         Ok(protocol_response)
     }
 
-    #[async_recursion]
     pub async fn run<T: NashProtocolPipeline + Clone>(
         &self,
         request: T,
+    ) -> Result<ResponseOrError<<T::ActionType as NashProtocol>::Response>> {
+        self.run_cancellable(request, None).await
+    }
+
+    /// Same as `run`, but stops before the next pipeline step once `cancel` is triggered. The
+    /// step in flight is completed, hooks after the pipeline still run and the output reflects
+    /// the steps taken so far.
+    #[async_recursion]
+    pub async fn run_cancellable<T: NashProtocolPipeline + Clone>(
+        &self,
+        request: T,
+        cancel: Option<CancellationToken>,
     ) -> Result<ResponseOrError<<T::ActionType as NashProtocol>::Response>> {
         async {
             let response = {
                 if let Some(_permit) = request.acquire_permit(self.state.clone()).await {
-                    self.run_helper(request).await
+                    self.run_helper(request, cancel).await
                 } else {
-                    self.run_helper(request).await
+                    self.run_helper(request, cancel).await
                 }
             };
             if let Err(ref e) = response {
-                error!(error = %e, "request error");
+                error!(error = %e.report(), "request error");
             }
             response
         }
         .instrument(info_span!(
                 "RUN (ws)",
                 request = type_name::<T>(),
-                id = %rand::thread_rng().gen::<u32>()))
+                id = %self.rng.next_u32()))
         .await
     }
 
@@ -532,6 +821,7 @@ impl InnerClient {
     async fn run_helper<T: NashProtocolPipeline + Clone>(
         &self,
         request: T,
+        cancel: Option<CancellationToken>,
     ) -> Result<ResponseOrError<<T::ActionType as NashProtocol>::Response>> {
         // First run any dependencies of the request/pipeline
         let before_actions = request.run_before(self.state.clone()).await?;
@@ -544,6 +834,10 @@ impl InnerClient {
         let mut protocol_state = request.init_state(self.state.clone()).await;
         // While pipeline contains more actions for client to take, execute them
         loop {
+            if cancel.as_ref().map_or(false, |cancel| cancel.is_cancelled()) {
+                warn!(request = type_name::<T>(), "pipeline cancelled");
+                break;
+            }
             if let Some(protocol_request) = request
                 .next_step(&protocol_state, self.state.clone())
                 .await?
@@ -589,15 +883,41 @@ impl InnerClient {
         request.output(protocol_state)
     }
 
+    /// Run an order request under a latency budget via websockets. The budget covers payload
+    /// construction, signing and transport; hooks run before the request are not counted.
+    pub async fn run_with_latency_budget<T: TimedNashProtocol + Clone>(
+        &self,
+        request: T,
+        budget: LatencyBudget,
+    ) -> Result<(ResponseOrError<T::Response>, StageTimings)> {
+        let _permit = NashProtocol::acquire_permit(&request, self.state.clone()).await;
+        if let Some(actions) = NashProtocol::run_before(&request, self.state.clone()).await? {
+            for action in actions {
+                self.run(action).await?;
+            }
+        }
+        let (graphql_request, mut timings) = request.graphql_timed(self.state.clone()).await?;
+        budget.check_before_submit(&timings)?;
+        let started = Instant::now();
+        let response = self.execute_graphql(&request, graphql_request).await?;
+        timings.transport = started.elapsed();
+        warn_if_over_budget::<T>(&budget, &timings);
+        if let Some(error) = response.error() {
+            Self::manage_client_error(self.state.clone(), error).await;
+        }
+        if let Some(actions) = NashProtocol::run_after(&request, self.state.clone()).await? {
+            for action in actions {
+                self.run(action).await?;
+            }
+        }
+        Ok((response, timings))
+    }
+
     /// Entry point for running Nash protocol subscriptions
     pub async fn subscribe_protocol<T: NashProtocolSubscription + Send + Sync + 'static>(
         &self,
         request: T,
-    ) -> Result<
-        mpsc::UnboundedReceiver<
-            Result<ResponseOrError<<T as NashProtocolSubscription>::SubscriptionResponse>>,
-        >,
-    > {
+    ) -> Result<SubscriptionHandle<<T as NashProtocolSubscription>::SubscriptionResponse>> {
         let query = request.graphql(self.state.clone()).await?;
         // a subscription starts with a normal request
         let subscription_response = self
@@ -615,12 +935,13 @@ impl InnerClient {
             .ok_or(ProtocolError("Response does not include subscription id"))?;
         broker_link
             .send(BrokerAction::RegisterSubscription(
-                subscription_id,
+                subscription_id.clone(),
                 for_broker,
             ))
             .map_err(|_| ProtocolError("Could not register subscription with broker"))?;
 
         let (user_callback_sender, user_callback_receiver) = mpsc::unbounded_channel();
+        let control = SubscriptionControl::new();
 
         global_subscription_loop(
             callback_channel,
@@ -628,24 +949,51 @@ impl InnerClient {
             self.ws_state.global_subscription_sender.clone(),
             request.clone(),
             self.state.clone(),
+            control.clone(),
         );
 
-        Ok(user_callback_receiver)
+        let link = SubscriptionLink {
+            outgoing: self.ws_state.ws_outgoing_sender.clone(),
+            broker: broker_link,
+            next_message_id: self.ws_state.next_message_id.clone(),
+            client_id: self.ws_state.client_id,
+        };
+        Ok(SubscriptionHandle::new(
+            user_callback_receiver,
+            control,
+            subscription_id,
+            link,
+        ))
     }
 
     pub async fn disconnect(&self) {
         self.ws_state.ws_disconnect_sender.send(()).ok();
     }
 
+    /// Hold back all requests for as long as the exchange asks when it throttled one
+    pub(crate) fn back_off_if_throttled(&self, response: &ErrorResponse) {
+        if response.is_throttled() {
+            let wait = self.exchange_throttle.throttled(response.retry_after());
+            warn!(?wait, "throttled by the exchange, holding back requests");
+        }
+    }
+
     pub async fn manage_client_error(_state: Arc<RwLock<State>>, response: &ErrorResponse) {
         error!(?response, "client error response");
     }
 }
 
+/// Handle to a connection with the exchange. Cloning is cheap: clones share the transports,
+/// `State` and signer, and can be moved to other tasks and used concurrently without any
+/// extra locking. Requests from different clones only wait on each other where the protocol
+/// requires it (e.g. order placement is limited by `rate_limits.max_concurrent_orders`).
+/// Clones also share the global subscription stream, so each event is received by only one
+/// of the clones polling it.
+#[derive(Clone)]
 pub struct Client {
     pub inner: Arc<InnerClient>,
     pub(crate) global_subscription_receiver:
-        mpsc::UnboundedReceiver<Result<ResponseOrError<SubscriptionResponse>>>,
+        Arc<SyncMutex<mpsc::UnboundedReceiver<Result<ResponseOrError<SubscriptionResponse>>>>>,
 }
 
 impl Client {
@@ -667,6 +1015,7 @@ impl Client {
             client_id,
             env,
             timeout,
+            TransportOptions::default(),
         )
         .await
     }
@@ -689,10 +1038,137 @@ impl Client {
             client_id,
             env,
             timeout,
+            TransportOptions::default(),
+        )
+        .await
+    }
+
+    /// Create a client from an existing `State`, e.g. one sharing a `MarketMetadataCache` with
+    /// other clients or restored from a `StateSnapshot`. If the state already holds market data
+    /// the initial `ListMarkets` request is skipped.
+    pub async fn from_state(
+        state: State,
+        affiliate_code: Option<String>,
+        turn_off_sign_states: bool,
+        client_id: u64,
+        env: Environment,
+        timeout: Duration,
+    ) -> Result<Self> {
+        Self::setup(
+            state,
+            affiliate_code,
+            turn_off_sign_states,
+            client_id,
+            env,
+            timeout,
+            TransportOptions::default(),
+        )
+        .await
+    }
+
+    /// Create a client from a validated `ClientConfig`. Connecting is retried according to
+    /// the config's retry policy.
+    pub async fn from_config(config: &ClientConfig) -> Result<Self> {
+        Self::from_config_with_events(config, ConnectionEvents::default()).await
+    }
+
+    /// Same as `from_config`, publishing connection events on `events`
+    pub(crate) async fn from_config_with_events(
+        config: &ClientConfig,
+        events: ConnectionEvents,
+    ) -> Result<Self> {
+        Self::connect_with_events(
+            config,
+            |config| State::from_keys_path(config.keys_path.as_deref()),
+            events,
         )
         .await
     }
 
+    /// Create a client from `NASH_*` environment variables (see `ClientConfig::from_env`).
+    /// Credentials are taken from the first of these that is set: `NASH_API_SECRET` together
+    /// with `NASH_API_KEY`, `NASH_KEYFILE` (keyfile contents), `NASH_KEYFILE_BASE64` (base64
+    /// encoded keyfile, e.g. mounted from a container secret) or `NASH_KEYS_PATH`.
+    pub async fn from_env() -> Result<Self> {
+        let config = ClientConfig::from_env()?;
+        Self::connect(&config, state_from_env).await
+    }
+
+    /// Set up a client from `config`, creating fresh state for every connection attempt
+    async fn connect<F>(config: &ClientConfig, make_state: F) -> Result<Self>
+    where
+        F: Fn(&ClientConfig) -> Result<State>,
+    {
+        Self::connect_with_events(config, make_state, ConnectionEvents::default()).await
+    }
+
+    /// Same as `connect`, publishing connection events on `events`
+    async fn connect_with_events<F>(
+        config: &ClientConfig,
+        make_state: F,
+        events: ConnectionEvents,
+    ) -> Result<Self>
+    where
+        F: Fn(&ClientConfig) -> Result<State>,
+    {
+        config.validate()?;
+        let mut options = config.transport_options();
+        options.events = events;
+        let mut attempt = 0;
+        loop {
+            attempt += 1;
+            let mut state = make_state(config)?;
+            state.place_order_semaphore = Arc::new(tokio::sync::Semaphore::new(
+                config.rate_limits.max_concurrent_orders,
+            ));
+            let client = Self::setup(
+                state,
+                config.affiliate_code.clone(),
+                config.turn_off_sign_states,
+                config.client_id,
+                config.environment.to_environment(),
+                config.timeout(),
+                options.clone(),
+            )
+            .await;
+            match client {
+                Err(e) if attempt < config.retry.max_attempts => {
+                    warn!(error = %e, %attempt, "could not set up client, retrying");
+                    options
+                        .events
+                        .emit(ConnectionEvent::Reconnecting { attempt });
+                    tokio::time::sleep(config.retry.backoff(attempt)).await;
+                }
+                Ok(client) => {
+                    client.inner.capture.set_config(config);
+                    if config.persisted_queries {
+                        client.enable_persisted_queries();
+                    }
+                    if let Some(deviation) = config.risk.max_price_deviation {
+                        client.set_price_guard(Some(PriceGuard::new(deviation)?));
+                    }
+                    client.set_requote_limits(config.risk.requote.clone());
+                    client
+                        .inner
+                        .exchange_throttle
+                        .set_default_backoff(config.rate_limits.throttle_backoff());
+                    client.set_batch_limits(config.batch.clone());
+                    if let Some(timeout) = config.risk.dead_man_switch() {
+                        client.arm_dead_man_switch(timeout)?;
+                    }
+                    return Ok(client);
+                }
+                Err(e) => {
+                    options.events.emit(ConnectionEvent::GaveUp {
+                        attempts: attempt,
+                        error: e.to_string(),
+                    });
+                    return Err(e);
+                }
+            }
+        }
+    }
+
     async fn setup(
         state: State,
         affiliate_code: Option<String>,
@@ -700,7 +1176,9 @@ impl Client {
         client_id: u64,
         env: Environment,
         timeout: Duration,
+        options: TransportOptions,
     ) -> Result<Self> {
+        let session_refresh = options.ws.session_refresh;
         let (inner, global_subscription_receiver) = InnerClient::setup(
             state,
             client_id,
@@ -708,16 +1186,33 @@ impl Client {
             timeout,
             affiliate_code,
             turn_off_sign_states,
+            options,
         )
         .await?;
         let client = Self {
             inner: Arc::new(inner),
-            global_subscription_receiver,
+            global_subscription_receiver: Arc::new(SyncMutex::new(global_subscription_receiver)),
         };
-        // Grab market data upon initial setup
-        let _ = client
-            .run(nash_protocol::protocol::list_markets::ListMarketsRequest)
-            .await?;
+        // Grab market data upon initial setup, unless the state came with it
+        let has_markets = client.inner.state.read().await.markets.is_some();
+        if !has_markets {
+            let _ = client
+                .run(nash_protocol::protocol::list_markets::ListMarketsRequest)
+                .await?;
+        }
+        // Orders are signed with the account's fee rate. Orders fetch it again when it is
+        // missing or stale and fail if it can't be had, so a failure here doesn't prevent
+        // connecting
+        let has_signer = client.inner.state.read().await.signer.is_some();
+        if has_signer {
+            let request = nash_protocol::protocol::get_account_fee_rates::GetAccountFeeRatesRequest;
+            if let Err(e) = client.run(request).await.and_then(|r| r.response_or_error()) {
+                warn!(error = %e, "could not fetch account fee rates");
+            }
+            if let Some(interval) = session_refresh {
+                client.start_background_session_refresh_loop(interval);
+            }
+        }
         Ok(client)
     }
 
@@ -725,24 +1220,87 @@ impl Client {
     /// All `NashProtocol` requests automatically do. Other more complex multi-stage interactions like `SignAllStates`
     /// implement the trait manually. This will optionally run before and after hooks if those are defined for the pipeline
     /// or request (e.g., get asset nonces if they don't exist)
+    /// Limit orders are checked by the price guard first, if one is set.
     #[inline]
     pub async fn run<T: NashProtocolPipeline + Clone>(
         &self,
         request: T,
     ) -> Result<ResponseOrError<<T::ActionType as NashProtocol>::Response>> {
-        self.inner.run(request).await
+        self.inner.check_price_guard(request.limit_prices()).await?;
+        let approved = self
+            .inner
+            .check_approval(request.limit_prices(), request.limit_sizes())?;
+        let response = self.inner.run(request).await;
+        let submitted = matches!(&response, Ok(response) if !response.is_error());
+        self.inner.consume_approvals(&approved, submitted);
+        response
     }
 
-    /// Entry point for running Nash protocol subscriptions
+    /// Same as `run`, but a pipeline (e.g. `SignAllStates`) stops before its next step once
+    /// `cancel` is triggered. Hooks after the pipeline still run, so client state stays
+    /// consistent, and the response covers the steps completed so far. A pipeline cancelled
+    /// before its first step returns an error.
+    #[inline]
+    pub async fn run_cancellable<T: NashProtocolPipeline + Clone>(
+        &self,
+        request: T,
+        cancel: CancellationToken,
+    ) -> Result<ResponseOrError<<T::ActionType as NashProtocol>::Response>> {
+        self.inner.check_price_guard(request.limit_prices()).await?;
+        let approved = self
+            .inner
+            .check_approval(request.limit_prices(), request.limit_sizes())?;
+        let response = self.inner.run_cancellable(request, Some(cancel)).await;
+        let submitted = matches!(&response, Ok(response) if !response.is_error());
+        self.inner.consume_approvals(&approved, submitted);
+        response
+    }
+
+    /// Same as `run`, but skips the price guard. Use this to deliberately place an order
+    /// far away from the market.
+    #[inline]
+    pub async fn run_unguarded<T: NashProtocolPipeline + Clone>(
+        &self,
+        request: T,
+    ) -> Result<ResponseOrError<<T::ActionType as NashProtocol>::Response>> {
+        let approved = self
+            .inner
+            .check_approval(request.limit_prices(), request.limit_sizes())?;
+        let response = self.inner.run(request).await;
+        let submitted = matches!(&response, Ok(response) if !response.is_error());
+        self.inner.consume_approvals(&approved, submitted);
+        response
+    }
+
+    /// Run an order request via websockets, recording construction, signing and transport
+    /// timings. Logs a warning when `budget` is exceeded, or aborts before submission if the
+    /// budget asks for it
+    #[inline]
+    pub async fn run_with_latency_budget<T: TimedNashProtocol + Clone>(
+        &self,
+        request: T,
+        budget: LatencyBudget,
+    ) -> Result<(ResponseOrError<T::Response>, StageTimings)> {
+        self.inner
+            .check_price_guard(NashProtocol::limit_prices(&request))
+            .await?;
+        let approved = self.inner.check_approval(
+            NashProtocol::limit_prices(&request),
+            NashProtocol::limit_sizes(&request),
+        )?;
+        let response = self.inner.run_with_latency_budget(request, budget).await;
+        let submitted = matches!(&response, Ok((response, _)) if !response.is_error());
+        self.inner.consume_approvals(&approved, submitted);
+        response
+    }
+
+    /// Entry point for running Nash protocol subscriptions. The subscription lives until the
+    /// returned handle is closed or dropped.
     #[inline]
     pub async fn subscribe_protocol<T: NashProtocolSubscription + Send + Sync + 'static>(
         &self,
         request: T,
-    ) -> Result<
-        mpsc::UnboundedReceiver<
-            Result<ResponseOrError<<T as NashProtocolSubscription>::SubscriptionResponse>>,
-        >,
-    > {
+    ) -> Result<SubscriptionHandle<<T as NashProtocolSubscription>::SubscriptionResponse>> {
         self.inner.subscribe_protocol(request).await
     }
 
@@ -752,6 +1310,57 @@ impl Client {
         self.inner.disconnect().await;
     }
 
+    /// Send only hashes of query documents the server has already seen (automatic persisted
+    /// queries). Only enable this against servers that support APQ.
+    pub fn enable_persisted_queries(&self) {
+        self.inner.persisted_queries.enable();
+    }
+
+    /// Check limit order prices against the market before submitting them (`None` disables
+    /// the check). Applies to `run`, `run_http` and their latency budget variants.
+    pub fn set_price_guard(&self, guard: Option<PriceGuard>) {
+        *self.inner.price_guard.write().unwrap() = guard;
+    }
+
+    /// Watch the number of orders left before states must be signed, as reported by the
+    /// exchange on every order response. `None` while unknown: before the first order
+    /// and right after states were signed.
+    pub async fn signing_capacity(&self) -> watch::Receiver<Option<u64>> {
+        self.inner.state.read().await.signing_capacity()
+    }
+
+    /// Wait until at least `min_orders` can be placed before states must be signed, so fast
+    /// senders can slow down instead of running into signing errors. Capacity is restored
+    /// by signing states, e.g. with `start_background_sign_states_loop`. Returns the current
+    /// capacity, or `None` right away if it is unknown.
+    pub async fn wait_for_signing_capacity(&self, min_orders: u64) -> Option<u64> {
+        let mut capacity = self.signing_capacity().await;
+        loop {
+            let current = *capacity.borrow();
+            match current {
+                Some(remaining) if remaining < min_orders => {}
+                current => return current,
+            }
+            if capacity.changed().await.is_err() {
+                return None;
+            }
+        }
+    }
+
+    /// Listen to connection lifecycle events: connecting, connected, degraded to long polling,
+    /// reconnecting, disconnected and giving up. Only events after this call are received; use
+    /// `ClientBuilder::connection_events` to also see the ones from connecting.
+    pub fn connection_events(&self) -> broadcast::Receiver<ConnectionEvent> {
+        self.inner.connection_events.subscribe()
+    }
+
+    /// Time left before requests are sent again, if the exchange throttled one recently.
+    /// Requests made meanwhile wait it out; a throttled response itself is returned as an
+    /// error with a `Throttled` source, see `ResponseOrError::response_or_error`.
+    pub fn exchange_backoff(&self) -> Option<Duration> {
+        self.inner.exchange_throttle.remaining()
+    }
+
     pub async fn turn_off_sign_states(&self) {
         let mut state = self.inner.state.write().await;
         state.dont_sign_states = true;
@@ -776,6 +1385,34 @@ impl Client {
         });
     }
 
+    /// Refresh the session token every `interval`. The exchange expires the token some time
+    /// after its last request, after which private subscriptions stop receiving updates.
+    /// Refreshing happens over the open connection, so subscriptions are never interrupted.
+    /// Clients built with a signer run this loop from the start, see
+    /// `WebSocketTunables::session_refresh_ms`. A failed refresh is retried after a tenth of
+    /// `interval`.
+    pub fn start_background_session_refresh_loop(&self, interval: Duration) {
+        let weak_inner = Arc::downgrade(&self.inner);
+        tokio::spawn(async move {
+            tokio::time::sleep(interval).await;
+            while let Some(inner) = weak_inner.upgrade() {
+                let tick_start = tokio::time::Instant::now();
+                let response = inner
+                    .run(RefreshTokenRequest)
+                    .await
+                    .and_then(|r| r.response_or_error());
+                let wait = match response {
+                    Ok(_) => interval,
+                    Err(e) => {
+                        warn!(request = type_name::<RefreshTokenRequest>(), error = %e, "could not refresh session token");
+                        interval / 10
+                    }
+                };
+                tokio::time::sleep_until(tick_start + wait).await;
+            }
+        });
+    }
+
     pub fn start_background_fill_pool_loop(
         &self,
         interval: Duration,
@@ -0,0 +1,63 @@
+//! Connection lifecycle events, published separately from request and subscription data so
+//! operators can alert on flapping connections
+
+use std::time::Duration;
+
+use tokio::sync::broadcast;
+
+/// Events buffered per listener before the slowest listener starts missing events
+const CONNECTION_EVENT_BUFFER: usize = 64;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum ConnectionEvent {
+    Connecting {
+        endpoint: String,
+    },
+    /// `rtt` is the time the handshake took
+    Connected {
+        endpoint: String,
+        rtt: Duration,
+    },
+    /// Connected, but not the preferred way (e.g. long polling instead of websockets)
+    Degraded {
+        reason: String,
+    },
+    Reconnecting {
+        attempt: u32,
+    },
+    Disconnected,
+    GaveUp {
+        attempts: u32,
+        error: String,
+    },
+}
+
+/// Sending side of the connection event stream, shared by everything that sets up or runs
+/// a connection
+#[derive(Clone, Debug)]
+pub(crate) struct ConnectionEvents {
+    sender: broadcast::Sender<ConnectionEvent>,
+}
+
+impl Default for ConnectionEvents {
+    fn default() -> Self {
+        let (sender, _) = broadcast::channel(CONNECTION_EVENT_BUFFER);
+        Self { sender }
+    }
+}
+
+impl ConnectionEvents {
+    pub fn emit(&self, event: ConnectionEvent) {
+        // nobody listening is fine
+        let _ = self.sender.send(event);
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<ConnectionEvent> {
+        self.sender.subscribe()
+    }
+}
+
+/// Endpoint without its query string, which may carry the session token
+pub(crate) fn display_endpoint(url: &str) -> String {
+    url.split('?').next().unwrap_or(url).to_string()
+}
@@ -0,0 +1,208 @@
+//! Fallback transport for environments where websockets are blocked (e.g. by corporate proxies).
+//! Speaks Phoenix's long poll transport, which carries the same Absinthe messages as the
+//! websocket: outgoing messages are POSTed, incoming messages are collected by a GET loop.
+//! The rest of the client (broker, subscriptions, heartbeat) is unaware of the difference.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, RwLock as SyncRwLock};
+
+use serde::Deserialize;
+use tokio::{sync::mpsc, sync::oneshot, time::Duration};
+use tracing::{error, trace};
+
+use nash_protocol::errors::{ProtocolError, Result};
+
+use crate::http_extension::header_map;
+
+use super::absinthe::{AbsintheWSRequest, AbsintheWSResponse};
+use super::client::BrokerAction;
+use super::events::{ConnectionEvent, ConnectionEvents};
+
+/// How long the server holds a poll open before answering with no messages
+const LONGPOLL_WINDOW: Duration = Duration::from_secs(10);
+
+#[derive(Deserialize, Debug)]
+struct LongPollResponse {
+    status: u16,
+    token: Option<String>,
+    #[serde(default)]
+    messages: Vec<serde_json::Value>,
+}
+
+/// Long poll session as established with the server
+pub(crate) struct LongPollSession {
+    client: reqwest::Client,
+    endpoint: String,
+    token: Arc<SyncRwLock<String>>,
+}
+
+impl LongPollSession {
+    /// Open a session. `endpoint` is the long poll url including `vsn`, `auth_token` the Nash
+    /// session used to authenticate the underlying socket. `headers` are sent with every request
+    pub(crate) async fn open(
+        endpoint: String,
+        auth_token: Option<&str>,
+        timeout: Duration,
+        headers: &[(String, String)],
+    ) -> Result<Self> {
+        let client = reqwest::Client::builder()
+            .timeout(timeout + LONGPOLL_WINDOW)
+            .default_headers(header_map(headers)?)
+            .build()
+            .map_err(|e| ProtocolError::with_source("Could not initialize reqwest client", e))?;
+        let mut request = client.get(&endpoint);
+        if let Some(auth_token) = auth_token {
+            request = request.query(&[("token", auth_token)]);
+        }
+        let response: LongPollResponse = request
+            .send()
+            .await
+            .map_err(|e| ProtocolError::with_source("Could not open long poll", e))?
+            .json()
+            .await
+            .map_err(|e| ProtocolError::with_source("Could not parse long poll response", e))?;
+        // a new session is answered with 410 (gone) and the token to resume it with
+        match (response.status, response.token) {
+            (410, Some(token)) => Ok(Self {
+                client,
+                endpoint,
+                token: Arc::new(SyncRwLock::new(token)),
+            }),
+            (status, _) => Err(ProtocolError::coerce_static_from_str(&format!(
+                "Could not open long poll session (status {})",
+                status
+            ))),
+        }
+    }
+
+    fn token(&self) -> String {
+        self.token.read().unwrap().clone()
+    }
+
+    async fn send(&self, request: &AbsintheWSRequest) -> Result<()> {
+        let body = serde_json::to_string(request)
+            .map_err(|_| ProtocolError("Could not serialize long poll message"))?;
+        let response: LongPollResponse = self
+            .client
+            .post(&self.endpoint)
+            .query(&[("token", self.token())])
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .body(body)
+            .send()
+            .await
+            .map_err(|_| ProtocolError("failed to send message on long poll, likely disconnected"))?
+            .json()
+            .await
+            .map_err(|_| ProtocolError("Could not parse long poll response"))?;
+        match response.status {
+            200 => Ok(()),
+            _ => Err(ProtocolError("long poll session closed by server")),
+        }
+    }
+
+    async fn poll(&self) -> Result<Vec<AbsintheWSResponse>> {
+        let response: LongPollResponse = self
+            .client
+            .get(&self.endpoint)
+            .query(&[("token", self.token())])
+            .send()
+            .await
+            .map_err(|_| ProtocolError("long poll failed, likely disconnected"))?
+            .json()
+            .await
+            .map_err(|_| ProtocolError("Could not parse long poll response"))?;
+        if let Some(token) = response.token {
+            *self.token.write().unwrap() = token;
+        }
+        match response.status {
+            // 204 means the poll window elapsed without messages
+            204 => Ok(Vec::new()),
+            200 => response
+                .messages
+                .into_iter()
+                .map(|message| {
+                    // messages arrive as encoded frames, same as the websocket text frames
+                    let parsed = match message {
+                        serde_json::Value::String(frame) => serde_json::from_str(&frame),
+                        message => serde_json::from_value(message),
+                    };
+                    parsed.map_err(|e| {
+                        ProtocolError::with_source("Could not parse long poll message", e)
+                    })
+                })
+                .collect(),
+            _ => Err(ProtocolError("long poll session closed by server")),
+        }
+    }
+}
+
+/// Long poll counterpart of `spawn_sender_loop`: sends outgoing messages and routes incoming
+/// messages to the broker until disconnected or the session fails
+pub(crate) fn spawn_longpoll_loop(
+    session: LongPollSession,
+    mut outgoing_receiver: mpsc::UnboundedReceiver<(
+        AbsintheWSRequest,
+        Option<oneshot::Receiver<bool>>,
+    )>,
+    mut disconnect_receiver: mpsc::UnboundedReceiver<()>,
+    message_broker_link: mpsc::UnboundedSender<BrokerAction>,
+    events: ConnectionEvents,
+) {
+    let session = Arc::new(session);
+    let stopped = Arc::new(AtomicBool::new(false));
+
+    let poll_session = session.clone();
+    let poll_stopped = stopped.clone();
+    let poll_broker_link = message_broker_link.clone();
+    tokio::spawn(async move {
+        while !poll_stopped.load(Ordering::Acquire) {
+            match poll_session.poll().await {
+                Ok(responses) => {
+                    for response in responses {
+                        trace!(id = ?response.message_id(), "RECV success (long poll)");
+                        let _ = poll_broker_link.send(BrokerAction::Message(Ok(response)));
+                    }
+                }
+                Err(e) => {
+                    error!(error = %e, "RECV long poll error");
+                    poll_stopped.store(true, Ordering::Release);
+                    let _ = poll_broker_link.send(BrokerAction::Message(Err(e)));
+                    break;
+                }
+            }
+        }
+    });
+
+    tokio::spawn(async move {
+        while !stopped.load(Ordering::Acquire) {
+            tokio::select! {
+                outgoing = outgoing_receiver.recv() => {
+                    match outgoing {
+                        Some((request, _ready_rx)) => match session.send(&request).await {
+                            Ok(_) => trace!(id = ?request.message_id(), "SEND (long poll)"),
+                            Err(e) => {
+                                error!(error = %e, "SEND long poll error");
+                                let _ = message_broker_link.send(BrokerAction::Message(Err(e)));
+                                break;
+                            }
+                        },
+                        None => {
+                            error!("SEND channel error");
+                            let error = ProtocolError("outgoing channel died or errored, likely disconnected");
+                            let _ = message_broker_link.send(BrokerAction::Message(Err(error)));
+                            break;
+                        }
+                    }
+                }
+                _ = disconnect_receiver.recv() => break,
+            }
+        }
+        stopped.store(true, Ordering::Release);
+        error!("DISCONNECT");
+        events.emit(ConnectionEvent::Disconnected);
+        let error = ProtocolError("Disconnected.");
+        message_broker_link
+            .send(BrokerAction::Message(Err(error)))
+            .ok();
+    });
+}
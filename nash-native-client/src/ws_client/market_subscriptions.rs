@@ -0,0 +1,199 @@
+//! One handle over the same subscription on many markets. Events are tagged with their market
+//! and merged into one stream. A market whose subscription ends unexpectedly is resubscribed.
+
+use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::task::{Context, Poll};
+
+use futures::stream::Stream;
+use tokio::sync::{mpsc, watch};
+use tokio::time::Duration;
+use tracing::warn;
+
+use nash_protocol::errors::{ProtocolError, Result};
+use nash_protocol::protocol::subscriptions::updated_orderbook::{
+    SubscribeOrderbook, SubscribeOrderbookResponse,
+};
+use nash_protocol::protocol::{NashProtocolSubscription, ResponseOrError};
+
+use super::{Client, InnerClient, SubscriptionHandle};
+
+/// Attempts to resubscribe a market whose subscription ended before giving up on it
+const RESUBSCRIBE_ATTEMPTS: u32 = 3;
+const RESUBSCRIBE_BACKOFF: Duration = Duration::from_secs(1);
+
+/// Subscription event tagged with the market it belongs to
+#[derive(Debug)]
+pub struct MarketEvent<R> {
+    pub market: String,
+    pub event: Result<ResponseOrError<R>>,
+}
+
+/// Handle over one subscription per market. Closing or dropping it unsubscribes all markets.
+pub struct MarketSubscriptionHandle<R> {
+    receiver: mpsc::UnboundedReceiver<MarketEvent<R>>,
+    markets: Vec<String>,
+    paused: Arc<AtomicBool>,
+    close_sender: watch::Sender<bool>,
+}
+
+impl<R> MarketSubscriptionHandle<R> {
+    /// Next event from any market, or `None` once every market subscription ended
+    pub async fn recv(&mut self) -> Option<MarketEvent<R>> {
+        self.receiver.recv().await
+    }
+
+    pub fn markets(&self) -> &[String] {
+        &self.markets
+    }
+
+    /// Drop events of all markets until resumed
+    pub fn pause(&self) {
+        self.paused.store(true, Ordering::Release);
+    }
+
+    pub fn resume(&self) {
+        self.paused.store(false, Ordering::Release);
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused.load(Ordering::Acquire)
+    }
+
+    /// Unsubscribe every market. Called automatically when the handle is dropped.
+    pub fn close(&self) {
+        let _ = self.close_sender.send(true);
+    }
+}
+
+impl<R> Drop for MarketSubscriptionHandle<R> {
+    fn drop(&mut self) {
+        self.close();
+    }
+}
+
+impl<R> Stream for MarketSubscriptionHandle<R> {
+    type Item = MarketEvent<R>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.receiver.poll_recv(cx)
+    }
+}
+
+/// Forward events of one market to `emit` until closed, resubscribing when the subscription
+/// ends. `emit` returns false once nobody listens anymore. Closing is signalled through
+/// `close_receiver`, either by sending `true` or by dropping the sender.
+pub(crate) async fn forward_market<T, F, E>(
+    client: Arc<InnerClient>,
+    market: String,
+    make_request: Arc<F>,
+    mut handle: SubscriptionHandle<T::SubscriptionResponse>,
+    emit: E,
+    paused: Arc<AtomicBool>,
+    mut close_receiver: watch::Receiver<bool>,
+) where
+    T: NashProtocolSubscription + Send + Sync + 'static,
+    F: Fn(String) -> T + Send + Sync + 'static,
+    E: Fn(MarketEvent<T::SubscriptionResponse>) -> bool,
+{
+    loop {
+        let next = tokio::select! {
+            next = handle.recv() => next,
+            _ = close_receiver.changed() => return,
+        };
+        match next {
+            Some(event) => {
+                if paused.load(Ordering::Acquire) {
+                    continue;
+                }
+                let event = MarketEvent {
+                    market: market.clone(),
+                    event,
+                };
+                if !emit(event) {
+                    return;
+                }
+            }
+            None => {
+                let mut resubscribed = None;
+                for attempt in 1..=RESUBSCRIBE_ATTEMPTS {
+                    warn!(%market, %attempt, "subscription ended, resubscribing");
+                    tokio::select! {
+                        _ = tokio::time::sleep(RESUBSCRIBE_BACKOFF * attempt) => {}
+                        _ = close_receiver.changed() => return,
+                    }
+                    let request = make_request(market.clone());
+                    if let Ok(new_handle) = client.subscribe_protocol(request).await {
+                        resubscribed = Some(new_handle);
+                        break;
+                    }
+                }
+                match resubscribed {
+                    Some(new_handle) => handle = new_handle,
+                    None => {
+                        emit(MarketEvent {
+                            market,
+                            event: Err(ProtocolError("Could not resubscribe to market")),
+                        });
+                        return;
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl Client {
+    /// Subscribe to `make_request(market)` for every market, merging events under one handle
+    pub async fn subscribe_markets<T, F>(
+        &self,
+        markets: &[&str],
+        make_request: F,
+    ) -> Result<MarketSubscriptionHandle<T::SubscriptionResponse>>
+    where
+        T: NashProtocolSubscription + Send + Sync + 'static,
+        F: Fn(String) -> T + Send + Sync + 'static,
+    {
+        // subscribe everything up front so a bad market fails the whole call
+        let mut handles = Vec::with_capacity(markets.len());
+        for market in markets {
+            let handle = self
+                .inner
+                .subscribe_protocol(make_request(market.to_string()))
+                .await?;
+            handles.push((market.to_string(), handle));
+        }
+        let (sender, receiver) = mpsc::unbounded_channel();
+        let (close_sender, close_receiver) = watch::channel(false);
+        let paused = Arc::new(AtomicBool::new(false));
+        let make_request = Arc::new(make_request);
+        for (market, handle) in handles {
+            let sender = sender.clone();
+            tokio::spawn(forward_market(
+                self.inner.clone(),
+                market,
+                make_request.clone(),
+                handle,
+                move |event| sender.send(event).is_ok(),
+                paused.clone(),
+                close_receiver.clone(),
+            ));
+        }
+        Ok(MarketSubscriptionHandle {
+            receiver,
+            markets: markets.iter().map(|m| m.to_string()).collect(),
+            paused,
+            close_sender,
+        })
+    }
+
+    /// Subscribe to orderbook updates of several markets under one handle
+    pub async fn subscribe_orderbooks(
+        &self,
+        markets: &[&str],
+    ) -> Result<MarketSubscriptionHandle<SubscribeOrderbookResponse>> {
+        self.subscribe_markets(markets, |market| SubscribeOrderbook { market })
+            .await
+    }
+}
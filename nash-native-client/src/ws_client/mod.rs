@@ -2,7 +2,17 @@
 
 mod absinthe;
 mod client;
+mod events;
+mod longpoll;
+mod market_subscriptions;
 pub mod stream;
+mod subscription;
+mod watchlist;
 
 pub use client::Client;
-pub(crate) use client::InnerClient;
+pub use events::ConnectionEvent;
+pub use market_subscriptions::{MarketEvent, MarketSubscriptionHandle};
+pub use subscription::SubscriptionHandle;
+pub use watchlist::{Watchlist, WatchlistChannels, WatchlistEvent};
+pub(crate) use client::{warn_if_over_budget, InnerClient, TransportOptions, WsOptions};
+pub(crate) use events::ConnectionEvents;
@@ -11,7 +11,10 @@ use crate::Client;
 impl Stream for Client {
     type Item = Result<ResponseOrError<SubscriptionResponse>>;
 
-    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
-        self.global_subscription_receiver.poll_recv(cx)
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.global_subscription_receiver
+            .lock()
+            .unwrap()
+            .poll_recv(cx)
     }
 }
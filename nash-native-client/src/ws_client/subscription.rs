@@ -0,0 +1,144 @@
+//! Handles returned for subscriptions. A handle delivers the subscription's events and owns
+//! its server side state: closing or dropping the handle unsubscribes on the server.
+
+use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Arc;
+use std::task::{Context, Poll};
+
+use futures::stream::Stream;
+use serde_json::json;
+use tokio::sync::{mpsc, oneshot};
+
+use nash_protocol::errors::Result;
+use nash_protocol::protocol::ResponseOrError;
+
+use super::absinthe::{AbsintheEvent, AbsintheTopic, AbsintheWSRequest};
+use super::client::BrokerAction;
+
+/// Flags shared between a handle and the loop feeding it
+#[derive(Debug)]
+pub(crate) struct SubscriptionControl {
+    paused: AtomicBool,
+    alive: AtomicBool,
+}
+
+impl SubscriptionControl {
+    pub(crate) fn new() -> Arc<Self> {
+        Arc::new(Self {
+            paused: AtomicBool::new(false),
+            alive: AtomicBool::new(true),
+        })
+    }
+
+    pub(crate) fn is_paused(&self) -> bool {
+        self.paused.load(Ordering::Acquire)
+    }
+
+    pub(crate) fn is_alive(&self) -> bool {
+        self.alive.load(Ordering::Acquire)
+    }
+
+    /// Mark the subscription dead, returning whether it was alive before
+    pub(crate) fn kill(&self) -> bool {
+        self.alive.swap(false, Ordering::AcqRel)
+    }
+}
+
+/// Connection details needed to unsubscribe
+pub(crate) struct SubscriptionLink {
+    pub(crate) outgoing: mpsc::UnboundedSender<(AbsintheWSRequest, Option<oneshot::Receiver<bool>>)>,
+    pub(crate) broker: mpsc::UnboundedSender<BrokerAction>,
+    pub(crate) next_message_id: Arc<AtomicU64>,
+    pub(crate) client_id: u64,
+}
+
+/// Events of a single subscription. Read them with `recv()` or as a `Stream`.
+pub struct SubscriptionHandle<R> {
+    receiver: mpsc::UnboundedReceiver<Result<ResponseOrError<R>>>,
+    control: Arc<SubscriptionControl>,
+    subscription_id: String,
+    link: SubscriptionLink,
+}
+
+impl<R> SubscriptionHandle<R> {
+    pub(crate) fn new(
+        receiver: mpsc::UnboundedReceiver<Result<ResponseOrError<R>>>,
+        control: Arc<SubscriptionControl>,
+        subscription_id: String,
+        link: SubscriptionLink,
+    ) -> Self {
+        Self {
+            receiver,
+            control,
+            subscription_id,
+            link,
+        }
+    }
+
+    /// Next event, or `None` once the subscription is closed
+    pub async fn recv(&mut self) -> Option<Result<ResponseOrError<R>>> {
+        self.receiver.recv().await
+    }
+
+    /// Stop delivering events to this handle. Events arriving while paused are dropped, but
+    /// still update client state and reach the client wide subscription stream.
+    pub fn pause(&self) {
+        self.control.paused.store(true, Ordering::Release);
+    }
+
+    pub fn resume(&self) {
+        self.control.paused.store(false, Ordering::Release);
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.control.is_paused()
+    }
+
+    /// False once closed or after the connection carrying the subscription failed
+    pub fn is_alive(&self) -> bool {
+        self.control.is_alive()
+    }
+
+    pub fn subscription_id(&self) -> &str {
+        &self.subscription_id
+    }
+
+    /// Unsubscribe on the server. Called automatically when the handle is dropped.
+    pub fn close(&self) {
+        if !self.control.kill() {
+            return;
+        }
+        let message_id = self.link.next_message_id.fetch_add(1, Ordering::SeqCst);
+        let unsubscribe = AbsintheWSRequest::new(
+            self.link.client_id,
+            message_id,
+            AbsintheTopic::Control,
+            AbsintheEvent::Unsubscribe,
+            Some(json!({ "subscriptionId": self.subscription_id })),
+        );
+        // failures mean the connection is gone already, and the server state with it
+        let _ = self
+            .link
+            .broker
+            .send(BrokerAction::UnregisterSubscription(
+                self.subscription_id.clone(),
+                message_id,
+            ));
+        let _ = self.link.outgoing.send((unsubscribe, None));
+    }
+}
+
+impl<R> Drop for SubscriptionHandle<R> {
+    fn drop(&mut self) {
+        self.close();
+    }
+}
+
+impl<R> Stream for SubscriptionHandle<R> {
+    type Item = Result<ResponseOrError<R>>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.receiver.poll_recv(cx)
+    }
+}
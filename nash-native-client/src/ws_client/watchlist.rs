@@ -0,0 +1,146 @@
+//! Watchlist of markets that can change at runtime. For every watched market the watchlist
+//! keeps ticker and/or orderbook subscriptions open and merges their events into one stream.
+
+use std::collections::HashMap;
+use std::sync::atomic::AtomicBool;
+use std::sync::Arc;
+
+use tokio::sync::{mpsc, watch};
+
+use nash_protocol::errors::Result;
+use nash_protocol::protocol::subscriptions::updated_orderbook::{
+    SubscribeOrderbook, SubscribeOrderbookResponse,
+};
+use nash_protocol::protocol::subscriptions::updated_ticker::{
+    SubscribeTicker, SubscribeTickerResponse,
+};
+
+use super::market_subscriptions::forward_market;
+use super::{Client, InnerClient, MarketEvent};
+
+/// Subscriptions kept open for every watched market
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct WatchlistChannels {
+    pub ticker: bool,
+    pub orderbook: bool,
+}
+
+impl Default for WatchlistChannels {
+    fn default() -> Self {
+        Self {
+            ticker: true,
+            orderbook: false,
+        }
+    }
+}
+
+/// Event of any watched market
+#[derive(Debug)]
+pub enum WatchlistEvent {
+    Ticker(MarketEvent<SubscribeTickerResponse>),
+    Orderbook(MarketEvent<SubscribeOrderbookResponse>),
+}
+
+impl WatchlistEvent {
+    pub fn market(&self) -> &str {
+        match self {
+            Self::Ticker(event) => &event.market,
+            Self::Orderbook(event) => &event.market,
+        }
+    }
+}
+
+pub struct Watchlist {
+    client: Arc<InnerClient>,
+    channels: WatchlistChannels,
+    /// Dropping a market's sender ends (and unsubscribes) its subscriptions
+    markets: HashMap<String, watch::Sender<bool>>,
+    sender: mpsc::UnboundedSender<WatchlistEvent>,
+    receiver: mpsc::UnboundedReceiver<WatchlistEvent>,
+}
+
+impl Watchlist {
+    /// Start watching `market`. Does nothing if it is watched already.
+    pub async fn add(&mut self, market: &str) -> Result<()> {
+        if self.markets.contains_key(market) {
+            return Ok(());
+        }
+        let (close_sender, close_receiver) = watch::channel(false);
+        let not_paused = Arc::new(AtomicBool::new(false));
+        // subscribe before spawning anything so a failed add leaves no subscription behind
+        let ticker = if self.channels.ticker {
+            let request = SubscribeTicker {
+                market: market.to_string(),
+            };
+            Some(self.client.subscribe_protocol(request).await?)
+        } else {
+            None
+        };
+        let orderbook = if self.channels.orderbook {
+            let request = SubscribeOrderbook {
+                market: market.to_string(),
+            };
+            Some(self.client.subscribe_protocol(request).await?)
+        } else {
+            None
+        };
+        if let Some(handle) = ticker {
+            let sender = self.sender.clone();
+            tokio::spawn(forward_market(
+                self.client.clone(),
+                market.to_string(),
+                Arc::new(|market: String| SubscribeTicker { market }),
+                handle,
+                move |event| sender.send(WatchlistEvent::Ticker(event)).is_ok(),
+                not_paused.clone(),
+                close_receiver.clone(),
+            ));
+        }
+        if let Some(handle) = orderbook {
+            let sender = self.sender.clone();
+            tokio::spawn(forward_market(
+                self.client.clone(),
+                market.to_string(),
+                Arc::new(|market: String| SubscribeOrderbook { market }),
+                handle,
+                move |event| sender.send(WatchlistEvent::Orderbook(event)).is_ok(),
+                not_paused,
+                close_receiver,
+            ));
+        }
+        self.markets.insert(market.to_string(), close_sender);
+        Ok(())
+    }
+
+    /// Stop watching `market` and unsubscribe. Returns whether it was watched.
+    pub fn remove(&mut self, market: &str) -> bool {
+        self.markets.remove(market).is_some()
+    }
+
+    pub fn contains(&self, market: &str) -> bool {
+        self.markets.contains_key(market)
+    }
+
+    pub fn markets(&self) -> impl Iterator<Item = &str> {
+        self.markets.keys().map(|market| market.as_str())
+    }
+
+    /// Next event of any watched market. Waits while the watchlist is empty.
+    pub async fn recv(&mut self) -> Option<WatchlistEvent> {
+        self.receiver.recv().await
+    }
+}
+
+impl Client {
+    /// Create an empty watchlist keeping `channels` subscribed for every market added to it
+    pub fn watchlist(&self, channels: WatchlistChannels) -> Watchlist {
+        let (sender, receiver) = mpsc::unbounded_channel();
+        Watchlist {
+            client: self.inner.clone(),
+            channels,
+            markets: HashMap::new(),
+            sender,
+            receiver,
+        }
+    }
+}
@@ -2,8 +2,6 @@ use std::collections::HashMap;
 use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::Arc;
 
-use chrono::offset::TimeZone;
-use chrono::Utc;
 use dotenv::dotenv;
 use tokio::time::Duration;
 
@@ -15,12 +13,16 @@ use nash_protocol::protocol::cancel_order::CancelOrderRequest;
 use nash_protocol::protocol::cancel_orders::CancelOrdersRequest;
 use nash_protocol::protocol::dh_fill_pool::DhFillPoolRequest;
 use nash_protocol::protocol::get_account_order::{GetAccountOrderRequest};
+use nash_protocol::protocol::get_deposit_address::GetDepositAddressRequest;
 use nash_protocol::protocol::get_ticker::TickerRequest;
+use nash_protocol::protocol::list_account_activity::ListAccountActivityRequest;
 use nash_protocol::protocol::list_account_balances::ListAccountBalancesRequest;
+use nash_protocol::protocol::list_account_movements::ListAccountMovementsRequest;
 use nash_protocol::protocol::list_account_orders::ListAccountOrdersRequest;
 use nash_protocol::protocol::list_account_trades::ListAccountTradesRequest;
 use nash_protocol::protocol::list_candles::ListCandlesRequest;
 use nash_protocol::protocol::list_markets::ListMarketsRequest;
+use nash_protocol::protocol::list_tickers::ListTickersRequest;
 use nash_protocol::protocol::list_trades::ListTradesRequest;
 use nash_protocol::protocol::orderbook::OrderbookRequest;
 use nash_protocol::protocol::place_order::{LimitOrderRequest, MarketOrderRequest};
@@ -32,8 +34,9 @@ use nash_protocol::protocol::subscriptions::trades::SubscribeTrades;
 use nash_protocol::protocol::subscriptions::updated_account_orders::SubscribeAccountOrders;
 use nash_protocol::protocol::subscriptions::updated_orderbook::SubscribeOrderbook;
 use nash_protocol::types::{
-    Blockchain, BuyOrSell, DateTimeRange, OrderCancellationPolicy, OrderStatus, OrderType,
+    Asset, Blockchain, BuyOrSell, DateTimeRange, OrderCancellationPolicy, OrderStatus, OrderType,
 };
+use nash_protocol::types::timestamp::parse_timestamp;
 
 async fn init_client() -> Client {
     dotenv().ok();
@@ -334,9 +337,9 @@ async fn two_loops_concurrent_request() {
 
 #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
 async fn multiple_concurrent_requests() {
+    // clones share the same connection and state
     let client = init_client().await;
-    let share_client = Arc::new(client);
-    async fn make_long_request(client: Arc<Client>, i: u64) {
+    async fn make_long_request(client: Client, i: u64) {
         println!("started long {}", i);
         let req = client.run(SignAllStates::new()).await;
         if !req.is_err() {
@@ -345,7 +348,7 @@ async fn multiple_concurrent_requests() {
             println!("error (long) {}: {}", i, req.unwrap_err());
         }
     }
-    async fn make_short_request(client: Arc<Client>, i: u64) {
+    async fn make_short_request(client: Client, i: u64) {
         println!("started short2 {}", i);
         let req = client.run(ListMarketsRequest).await;
         if !req.is_err() {
@@ -357,12 +360,9 @@ async fn multiple_concurrent_requests() {
     let mut handles = Vec::new();
     let mut count = 0;
     for _ in 0..10 {
-        handles.push(tokio::spawn(make_long_request(share_client.clone(), count)));
+        handles.push(tokio::spawn(make_long_request(client.clone(), count)));
         count += 1;
-        handles.push(tokio::spawn(make_short_request(
-            share_client.clone(),
-            count,
-        )));
+        handles.push(tokio::spawn(make_short_request(client.clone(), count)));
         count += 1;
     }
     futures::future::join_all(handles).await;
@@ -448,8 +448,8 @@ fn list_account_orders() {
                 ]),
                 order_type: Some(vec![OrderType::Limit]),
                 range: Some(DateTimeRange {
-                    start: Utc.ymd(2020, 9, 12).and_hms(0, 0, 0),
-                    stop: Utc.ymd(2020, 10, 16).and_hms(0, 10, 0),
+                    start: parse_timestamp("2020-09-12T00:00:00Z").unwrap(),
+                    stop: parse_timestamp("2020-10-16T00:10:00Z").unwrap(),
                 }),
             })
             .await
@@ -478,6 +478,24 @@ pub fn list_account_trades() {
     runtime.block_on(async_block);
 }
 
+#[test]
+pub fn list_account_movements() {
+    let runtime = tokio::runtime::Runtime::new().unwrap();
+    let async_block = async {
+        let client = init_client().await;
+        let response = client
+            .run(ListAccountMovementsRequest {
+                asset: Some(Asset::ETH),
+                limit: Some(10),
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+        println!("{:?}", response);
+    };
+    runtime.block_on(async_block);
+}
+
 #[test]
 pub fn list_candles() {
     let runtime = tokio::runtime::Runtime::new().unwrap();
@@ -491,8 +509,8 @@ pub fn list_candles() {
                 chronological: None,
                 interval: None,
                 range: Some(DateTimeRange {
-                    start: Utc.ymd(2020, 8, 1).and_hms(0, 0, 0),
-                    stop: Utc.ymd(2020, 8, 1).and_hms(0, 10, 0),
+                    start: parse_timestamp("2020-08-01T00:00:00Z").unwrap(),
+                    stop: parse_timestamp("2020-08-01T00:10:00Z").unwrap(),
                 }),
             })
             .await
@@ -533,6 +551,20 @@ fn test_list_account_balances() {
     runtime.block_on(async_block);
 }
 
+#[test]
+fn test_get_deposit_address() {
+    let runtime = tokio::runtime::Runtime::new().unwrap();
+    let async_block = async {
+        let client = init_client().await;
+        let response = client
+            .run(GetDepositAddressRequest::from(Asset::ETH))
+            .await
+            .unwrap();
+        println!("{:#?}", response);
+    };
+    runtime.block_on(async_block);
+}
+
 #[test]
 fn test_list_markets() {
     let runtime = tokio::runtime::Runtime::new().unwrap();
@@ -687,6 +719,35 @@ fn end_to_end_ticker() {
     runtime.block_on(async_block);
 }
 
+#[test]
+fn end_to_end_list_account_activity() {
+    let runtime = tokio::runtime::Runtime::new().unwrap();
+    let async_block = async {
+        let client = init_client().await;
+        let response = client
+            .run(ListAccountActivityRequest {
+                page: Some(1),
+                page_size: Some(10),
+            })
+            .await
+            .unwrap();
+        println!("{:?}", response.response_or_error());
+    };
+    runtime.block_on(async_block);
+}
+
+#[test]
+fn end_to_end_list_tickers() {
+    let runtime = tokio::runtime::Runtime::new().unwrap();
+    let async_block = async {
+        let client = init_client().await;
+        let response = client.run(ListTickersRequest).await.unwrap();
+        let tickers = response.response_or_error().unwrap();
+        println!("{:?}", tickers.ticker("btc_usdc"));
+    };
+    runtime.block_on(async_block);
+}
+
 #[test]
 fn end_to_end_list_trades() {
     let runtime = tokio::runtime::Runtime::new().unwrap();
@@ -1013,16 +1074,19 @@ fn multi_market_order() {
                     client_order_id: None,
                     market: "eth_btc".to_string(),
                     amount: "0.2".to_string(),
+                    rate_bounds: Default::default(),
                 },
                 MarketOrderRequest {
                     client_order_id: None,
                     market: "eth_btc".to_string(),
                     amount: "0.1".to_string(),
+                    rate_bounds: Default::default(),
                 },
                 MarketOrderRequest {
                     client_order_id: None,
                     market: "eth_btc".to_string(),
                     amount: "0.05".to_string(),
+                    rate_bounds: Default::default(),
                 },
             ],
         };
@@ -1101,7 +1165,7 @@ fn limit_order_nonce_recovery() {
             amount: "0.02".to_string(),
             price: "900".to_string(),
             cancellation_policy: OrderCancellationPolicy::GoodTilTime(
-                Utc.ymd(2020, 12, 16).and_hms(0, 0, 0),
+                parse_timestamp("2020-12-16T00:00:00Z").unwrap(),
             ),
             allow_taker: true,
         };
@@ -1153,6 +1217,7 @@ fn end_to_end_market_order() {
                 client_order_id: None,
                 market: "usdc_eth".to_string(),
                 amount: "10".to_string(),
+                rate_bounds: Default::default(),
             })
             .await;
         println!("{:?}", response);
@@ -1217,6 +1282,37 @@ fn end_to_end_sell_limit_order() {
     runtime.block_on(async_block);
 }
 
+#[test]
+fn end_to_end_prepared_limit_order() {
+    let runtime = tokio::runtime::Runtime::new().unwrap();
+    let async_block = async {
+        let client = init_client().await;
+        let prepared = client
+            .prepare_limit_order(LimitOrderRequest {
+                client_order_id: None,
+                market: "eth_usdc".to_string(),
+                buy_or_sell: BuyOrSell::Sell,
+                amount: "0.004".to_string(),
+                price: "1500".to_string(),
+                cancellation_policy: OrderCancellationPolicy::GoodTilCancelled,
+                allow_taker: true,
+            })
+            .await
+            .unwrap();
+        assert!(prepared.is_current().await);
+        let response = prepared.submit().await.unwrap();
+        println!("{:?}", response);
+        let response = client
+            .run(CancelAllOrders {
+                market: "eth_usdc".to_string(),
+            })
+            .await
+            .unwrap();
+        assert_eq!(response.response().unwrap().accepted, true);
+    };
+    runtime.block_on(async_block);
+}
+
 #[test]
 fn list_markets_test() {
     let runtime = tokio::runtime::Runtime::new().unwrap();
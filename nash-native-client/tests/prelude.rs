@@ -0,0 +1,74 @@
+//! The prelude is the semver-guarded surface of the crate: removing or renaming any of its
+//! items breaks this test, and has to wait for a breaking release.
+
+use nash_native_client::prelude::*;
+
+#[test]
+fn prelude_surface_is_stable() {
+    fn exported<T: ?Sized>() {}
+    exported::<Client>();
+    exported::<ClientBuilder>();
+    exported::<ClientConfig>();
+    exported::<Environment>();
+    exported::<ConnectionEvent>();
+    exported::<MarketEvent<()>>();
+    exported::<WatchlistEvent>();
+    exported::<Watchlist>();
+    exported::<SubscriptionHandle<()>>();
+    exported::<MarketSubscriptionHandle<()>>();
+    exported::<CancellationToken>();
+    exported::<BatchPlacement>();
+    exported::<CaptureBundle>();
+    exported::<OrderbookManager>();
+    exported::<OrderbookChange>();
+    exported::<OrderbookDesync>();
+    exported::<L2Book>();
+    exported::<Level>();
+    exported::<PreparedOrder>();
+    let error = Err::<(), _>(ProtocolError("rejected")).context("placing order");
+    assert_eq!(error.unwrap_err().to_string(), "placing order: rejected");
+    exported::<ChainSigningError>();
+    exported::<LimitOrderRequest>();
+    exported::<StopLimitOrderRequest>();
+    exported::<MarketOrderRequest>();
+    exported::<RateBounds>();
+    exported::<PlaceOrderResponse>();
+    exported::<LimitOrdersRequest>();
+    exported::<MarketOrdersRequest>();
+    exported::<MixedOrder>();
+    exported::<MixedOrdersRequest>();
+    exported::<OcoOrderRequest>();
+    exported::<AmendOrderRequest>();
+    exported::<OrderPlaced>();
+    exported::<OrderRejected>();
+    exported::<CancelOrderRequest>();
+    exported::<CancelOrdersRequest>();
+    exported::<CancelAllOrders>();
+    exported::<GetAccountOrderRequest>();
+    exported::<GetDepositAddressRequest>();
+    exported::<WithdrawRequest>();
+    exported::<WithdrawResponse>();
+    exported::<TickerRequest>();
+    exported::<OrderbookRequest>();
+    exported::<ListMarketsRequest>();
+    exported::<ListTickersRequest>();
+    exported::<ListAccountActivityRequest>();
+    exported::<ListAccountBalancesRequest>();
+    exported::<ListAccountMovementsRequest>();
+    exported::<ListAccountOrdersRequest>();
+    exported::<ListAccountTradesRequest>();
+    exported::<ListCandlesRequest>();
+    exported::<ListTradesRequest>();
+    exported::<ResponseOrError<()>>();
+    exported::<Asset>();
+    exported::<Blockchain>();
+    exported::<BuyOrSell>();
+    exported::<Market>();
+    exported::<MarketSymbol>();
+    exported::<Order>();
+    exported::<OrderCancellationPolicy>();
+    exported::<OrderStatus>();
+    exported::<OrderType>();
+    exported::<RoundingMode>();
+    exported::<Trade>();
+}
@@ -0,0 +1,593 @@
+//! Integration tests against the Nash sandbox, enabled with the `integration-tests` feature:
+//!
+//! ```text
+//! NASH_KEYFILE_BASE64=... cargo test -p nash-native-client --features integration-tests --test sandbox
+//! ```
+//!
+//! Credentials are read like `Client::from_env` does (`NASH_API_SECRET` + `NASH_API_KEY`,
+//! `NASH_KEYFILE`, `NASH_KEYFILE_BASE64` or `NASH_KEYS_PATH`). `NASH_ENV` defaults to the
+//! sandbox here and the harness refuses to run against production.
+//!
+//! Every test is tagged with a capability and prefixed with its name, so a subset can be
+//! selected either with `NASH_TEST_CAPABILITIES` (comma separated, default `public,account`)
+//! or with the usual test name filter, e.g. `-- trading_`:
+//! * `public`: market data, no keys needed
+//! * `account`: read only account queries
+//! * `trading`: places and cancels orders on `NASH_TEST_MARKET` (default `eth_usdc`)
+//! * `subscriptions`: websocket subscriptions
+//!
+//! Runs can be recorded and replayed without a connection or credentials, e.g. to check a
+//! change to response parsing against what the sandbox returned before:
+//! * `NASH_TEST_RECORD=<dir>` runs against the sandbox and saves the responses of every test
+//!   to `<dir>/<test>.json`, in the redacted form of `Client::start_capture`
+//! * `NASH_TEST_REPLAY=<dir>` answers every request of a test from its recording and runs
+//!   the same assertions. Only `public` and `account` tests can be replayed; the others are
+//!   skipped, as orders need keys to be signed and subscriptions are not recorded.
+#![cfg(feature = "integration-tests")]
+
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use bigdecimal::{BigDecimal, Zero};
+use tokio::sync::RwLock;
+
+use nash_native_client::Client;
+
+use nash_protocol::protocol::asset_nonces::AssetNoncesRequest;
+use nash_protocol::protocol::cancel_all_orders::CancelAllOrders;
+use nash_protocol::protocol::cancel_order::CancelOrderRequest;
+use nash_protocol::protocol::get_account_fee_rates::GetAccountFeeRatesRequest;
+use nash_protocol::protocol::get_account_order::GetAccountOrderRequest;
+use nash_protocol::protocol::get_deposit_address::GetDepositAddressRequest;
+use nash_protocol::protocol::get_ticker::TickerRequest;
+use nash_protocol::protocol::list_account_activity::ListAccountActivityRequest;
+use nash_protocol::protocol::list_account_balances::ListAccountBalancesRequest;
+use nash_protocol::protocol::list_account_movements::ListAccountMovementsRequest;
+use nash_protocol::protocol::list_account_orders::ListAccountOrdersRequest;
+use nash_protocol::protocol::list_account_trades::ListAccountTradesRequest;
+use nash_protocol::protocol::list_candles::ListCandlesRequest;
+use nash_protocol::protocol::list_markets::ListMarketsRequest;
+use nash_protocol::protocol::list_tickers::ListTickersRequest;
+use nash_protocol::protocol::list_trades::ListTradesRequest;
+use nash_protocol::protocol::orderbook::OrderbookRequest;
+use nash_protocol::protocol::place_order::LimitOrderRequest;
+use nash_protocol::protocol::subscriptions::trades::SubscribeTrades;
+use nash_protocol::protocol::subscriptions::updated_orderbook::SubscribeOrderbook;
+use nash_protocol::protocol::{NashProtocol, State};
+use nash_protocol::types::{
+    AccountTradeSide, Asset, Blockchain, BuyOrSell, OrderCancellationPolicy, OrderStatus,
+    OrderbookOrder,
+};
+
+#[derive(Clone, Copy, Debug)]
+enum Capability {
+    Public,
+    Account,
+    Trading,
+    Subscriptions,
+}
+
+impl Capability {
+    fn name(self) -> &'static str {
+        match self {
+            Self::Public => "public",
+            Self::Account => "account",
+            Self::Trading => "trading",
+            Self::Subscriptions => "subscriptions",
+        }
+    }
+
+    fn enabled(self) -> bool {
+        let capabilities = std::env::var("NASH_TEST_CAPABILITIES")
+            .unwrap_or_else(|_| "public,account".to_string());
+        capabilities
+            .split(',')
+            .any(|c| c.trim() == "all" || c.trim() == self.name())
+    }
+
+    /// Whether tests of this capability can run from a recording
+    fn replayable(self) -> bool {
+        matches!(self, Self::Public | Self::Account)
+    }
+}
+
+fn test_market() -> String {
+    std::env::var("NASH_TEST_MARKET").unwrap_or_else(|_| "eth_usdc".to_string())
+}
+
+/// Where the responses of a test come from
+enum Mode {
+    /// The sandbox, saving the responses to the directory if one is given
+    Live(Option<PathBuf>),
+    /// Responses saved by an earlier live run
+    Replay(PathBuf),
+}
+
+impl Mode {
+    fn from_env() -> Self {
+        dotenv::dotenv().ok();
+        match std::env::var("NASH_TEST_REPLAY") {
+            Ok(dir) => Self::Replay(dir.into()),
+            Err(_) => Self::Live(std::env::var("NASH_TEST_RECORD").ok().map(PathBuf::from)),
+        }
+    }
+}
+
+async fn sandbox_client() -> Client {
+    match std::env::var("NASH_ENV") {
+        Ok(env) if env.eq_ignore_ascii_case("production") => {
+            panic!("Refusing to run integration tests against production")
+        }
+        Ok(_) => {}
+        Err(_) => std::env::set_var("NASH_ENV", "sandbox"),
+    }
+    Client::from_env()
+        .await
+        .expect("Could not connect to sandbox")
+}
+
+/// Runs the requests of one test, against the sandbox or from its recording
+struct Sandbox {
+    test: &'static str,
+    client: Option<Client>,
+    record: Option<PathBuf>,
+    /// Recorded GraphQL queries with their responses, in the order they were sent
+    recording: Mutex<Vec<(serde_json::Value, serde_json::Value)>>,
+    /// Protocol state responses are replayed against
+    state: Arc<RwLock<State>>,
+}
+
+impl Sandbox {
+    async fn start(test: &'static str) -> Self {
+        let sandbox = match Mode::from_env() {
+            Mode::Live(record) => {
+                let client = sandbox_client().await;
+                if record.is_some() {
+                    client.start_capture().unwrap();
+                }
+                Self {
+                    test,
+                    client: Some(client),
+                    record,
+                    recording: Mutex::new(Vec::new()),
+                    state: Arc::new(RwLock::new(State::new(None))),
+                }
+            }
+            Mode::Replay(dir) => Self {
+                test,
+                client: None,
+                record: None,
+                recording: Mutex::new(read_recording(&dir, test)),
+                state: Arc::new(RwLock::new(State::new(None))),
+            },
+        };
+        // responses are parsed with the market list, so it is part of every recording
+        sandbox.run(ListMarketsRequest).await;
+        sandbox
+    }
+
+    /// Client connected to the sandbox. Not available when replaying.
+    fn client(&self) -> &Client {
+        self.client
+            .as_ref()
+            .expect("test needs a sandbox connection and can't be replayed")
+    }
+
+    async fn run<T: NashProtocol + Clone + Send + Sync>(&self, request: T) -> T::Response {
+        match &self.client {
+            Some(client) => client
+                .run(request)
+                .await
+                .unwrap()
+                .response_or_error()
+                .unwrap(),
+            None => self.replay(request).await,
+        }
+    }
+
+    /// Answer `request` with the first recorded response to the same query
+    async fn replay<T: NashProtocol>(&self, request: T) -> T::Response {
+        let query = request.graphql(self.state.clone()).await.unwrap()["query"].clone();
+        let response = {
+            let mut recording = self.recording.lock().unwrap();
+            let position = recording
+                .iter()
+                .position(|(recorded, _)| recorded == &query)
+                .unwrap_or_else(|| {
+                    panic!("{} sent a request that is not in its recording", self.test)
+                });
+            recording.remove(position).1
+        };
+        let response = request
+            .response_from_json(response, self.state.clone())
+            .await
+            .unwrap()
+            .response_or_error()
+            .unwrap();
+        request
+            .process_response(&response, self.state.clone())
+            .await
+            .unwrap();
+        response
+    }
+
+    /// Save the recording of a recorded run
+    fn finish(self) {
+        if let (Some(dir), Some(client)) = (&self.record, &self.client) {
+            let bundle = client.stop_capture().unwrap();
+            std::fs::create_dir_all(dir).unwrap();
+            std::fs::write(
+                dir.join(format!("{}.json", self.test)),
+                bundle.to_json().unwrap(),
+            )
+            .unwrap();
+        }
+    }
+}
+
+/// Queries and responses recorded for `test` in `dir`
+fn read_recording(dir: &Path, test: &str) -> Vec<(serde_json::Value, serde_json::Value)> {
+    let path = dir.join(format!("{}.json", test));
+    let contents = std::fs::read_to_string(&path).unwrap_or_else(|e| {
+        panic!(
+            "no recording for {} at {} ({}), record one with NASH_TEST_RECORD",
+            test,
+            path.display(),
+            e
+        )
+    });
+    let bundle: serde_json::Value = serde_json::from_str(&contents).unwrap();
+    bundle["exchanges"]
+        .as_array()
+        .expect("recording has no exchanges")
+        .iter()
+        .filter(|exchange| !exchange["response"].is_null())
+        .map(|exchange| {
+            (
+                exchange["request"]["query"].clone(),
+                exchange["response"].clone(),
+            )
+        })
+        .collect()
+}
+
+/// Declare a test that only runs when its capability is enabled
+macro_rules! sandbox_test {
+    ($capability:ident, $name:ident, |$sandbox:ident| $body:block) => {
+        #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+        async fn $name() {
+            let capability = Capability::$capability;
+            if !capability.enabled() {
+                eprintln!(
+                    "skipping {}: capability {} not enabled",
+                    stringify!($name),
+                    capability.name()
+                );
+                return;
+            }
+            if matches!(Mode::from_env(), Mode::Replay(_)) && !capability.replayable() {
+                eprintln!(
+                    "skipping {}: {} tests can't be replayed",
+                    stringify!($name),
+                    capability.name()
+                );
+                return;
+            }
+            let $sandbox = Sandbox::start(stringify!($name)).await;
+            $body
+            $sandbox.finish();
+        }
+    };
+}
+
+fn price(order: &OrderbookOrder) -> BigDecimal {
+    order.price.parse().unwrap()
+}
+
+/// Bids must be best (highest) first, asks lowest first, and the book must not be crossed
+fn assert_book(bids: &[OrderbookOrder], asks: &[OrderbookOrder]) {
+    assert!(bids
+        .windows(2)
+        .all(|pair| price(&pair[0]) >= price(&pair[1])));
+    assert!(asks
+        .windows(2)
+        .all(|pair| price(&pair[0]) <= price(&pair[1])));
+    if let (Some(bid), Some(ask)) = (bids.first(), asks.first()) {
+        assert!(price(bid) < price(ask), "crossed book");
+    }
+    assert!(bids
+        .iter()
+        .chain(asks)
+        .all(|order| order.amount > BigDecimal::zero()));
+}
+
+sandbox_test!(Public, public_list_markets, |sandbox| {
+    let markets = sandbox.run(ListMarketsRequest).await;
+    assert!(markets.markets.contains_key(&test_market()));
+    for (name, market) in &markets.markets {
+        assert_eq!(name, &market.market_name());
+    }
+});
+
+sandbox_test!(Public, public_ticker, |sandbox| {
+    let ticker = sandbox
+        .run(TickerRequest {
+            market: test_market(),
+        })
+        .await;
+    assert_eq!(ticker.market_name, test_market());
+    if let (Some(bid), Some(ask)) = (&ticker.best_bid_price, &ticker.best_ask_price) {
+        assert!(bid < ask, "crossed ticker");
+    }
+    if let (Some(low), Some(high)) = (&ticker.low_price_24h, &ticker.high_price_24h) {
+        assert!(low <= high);
+    }
+});
+
+sandbox_test!(Public, public_list_tickers, |sandbox| {
+    let tickers = sandbox.run(ListTickersRequest).await;
+    let ticker = tickers
+        .ticker(&test_market())
+        .expect("no ticker for the test market");
+    assert_eq!(ticker.market_name, test_market());
+});
+
+sandbox_test!(Public, public_orderbook, |sandbox| {
+    let book = sandbox
+        .run(OrderbookRequest {
+            market: test_market(),
+        })
+        .await;
+    assert_book(&book.bids, &book.asks);
+});
+
+sandbox_test!(Public, public_list_trades, |sandbox| {
+    let response = sandbox
+        .run(ListTradesRequest {
+            market: test_market(),
+            limit: Some(10),
+            before: None,
+        })
+        .await;
+    assert!(response.trades.len() <= 10);
+    assert!(response
+        .trades
+        .iter()
+        .all(|trade| trade.market == test_market() && trade.amount > BigDecimal::zero()));
+    // newest first
+    assert!(response
+        .trades
+        .windows(2)
+        .all(|pair| pair[0].executed_at >= pair[1].executed_at));
+});
+
+sandbox_test!(Public, public_list_candles, |sandbox| {
+    let response = sandbox
+        .run(ListCandlesRequest {
+            before: None,
+            market: test_market(),
+            limit: Some(10),
+            chronological: None,
+            interval: None,
+            range: None,
+        })
+        .await;
+    assert!(response.candles.len() <= 10);
+    for candle in &response.candles {
+        assert!(candle.low_price <= candle.high_price);
+        for value in &[&candle.open_price, &candle.close_price] {
+            assert!(&candle.low_price <= *value && *value <= &candle.high_price);
+        }
+    }
+});
+
+sandbox_test!(Account, account_balances, |sandbox| {
+    let balances = sandbox
+        .run(ListAccountBalancesRequest { filter: None })
+        .await;
+    let amounts = balances
+        .state_channel
+        .values()
+        .chain(balances.pending.values())
+        .chain(balances.personal.values())
+        .chain(balances.in_orders.values());
+    for amount in amounts {
+        assert!(amount >= &BigDecimal::zero(), "negative balance {}", amount);
+    }
+});
+
+sandbox_test!(Account, account_orders, |sandbox| {
+    let response = sandbox
+        .run(ListAccountOrdersRequest {
+            before: None,
+            market: Some(test_market()),
+            buy_or_sell: None,
+            limit: Some(10),
+            status: None,
+            order_type: None,
+            range: None,
+        })
+        .await;
+    assert!(response.orders.len() <= 10);
+    for order in &response.orders {
+        assert_eq!(order.market, test_market());
+        assert_eq!(
+            &order.amount_executed + &order.amount_remaining,
+            order.amount_placed,
+            "order {} amounts don't add up",
+            order.id
+        );
+    }
+});
+
+sandbox_test!(Account, account_trades, |sandbox| {
+    let response = sandbox
+        .run(ListAccountTradesRequest {
+            before: None,
+            market: Some(test_market()),
+            limit: Some(10),
+            range: None,
+        })
+        .await;
+    assert!(response.trades.len() <= 10);
+    for trade in &response.trades {
+        assert_eq!(trade.market, test_market());
+        assert_ne!(trade.account_side, AccountTradeSide::None);
+    }
+});
+
+sandbox_test!(Account, account_asset_nonces, |sandbox| {
+    let response = sandbox.run(AssetNoncesRequest::new()).await;
+    assert!(!response.nonces.is_empty());
+});
+
+sandbox_test!(Account, account_fee_rates, |sandbox| {
+    let response = sandbox.run(GetAccountFeeRatesRequest).await;
+    assert!(response.fee_rates.maker >= BigDecimal::zero());
+    assert!(response.fee_rates.taker >= BigDecimal::zero());
+});
+
+sandbox_test!(Account, account_movements, |sandbox| {
+    let response = sandbox
+        .run(ListAccountMovementsRequest {
+            limit: Some(10),
+            ..Default::default()
+        })
+        .await;
+    assert!(response.movements.len() <= 10);
+    assert!(response
+        .movements
+        .iter()
+        .all(|movement| movement.quantity > BigDecimal::zero()));
+});
+
+sandbox_test!(Account, account_activity, |sandbox| {
+    let response = sandbox
+        .run(ListAccountActivityRequest {
+            page: Some(1),
+            page_size: Some(10),
+        })
+        .await;
+    assert!(response.events.len() <= 10);
+});
+
+sandbox_test!(Account, account_deposit_address, |sandbox| {
+    let response = sandbox
+        .run(GetDepositAddressRequest { asset: Asset::ETH })
+        .await;
+    assert_eq!(response.asset, Asset::ETH);
+    assert_eq!(response.blockchain, Blockchain::Ethereum);
+    assert!(!response.address_string.is_empty());
+});
+
+sandbox_test!(Trading, trading_place_and_cancel, |sandbox| {
+    // far from the market so the order rests on the book until cancelled
+    let placed = sandbox
+        .run(LimitOrderRequest {
+            client_order_id: None,
+            market: test_market(),
+            buy_or_sell: BuyOrSell::Buy,
+            amount: "0.01".to_string(),
+            price: "1".to_string(),
+            cancellation_policy: OrderCancellationPolicy::GoodTilCancelled,
+            allow_taker: false,
+        })
+        .await;
+    assert_eq!(placed.market.name, test_market());
+    assert_eq!(placed.buy_or_sell, BuyOrSell::Buy);
+    assert!(matches!(
+        placed.status,
+        OrderStatus::Pending | OrderStatus::Open
+    ));
+
+    let order = sandbox
+        .run(GetAccountOrderRequest {
+            order_id: placed.order_id.clone(),
+        })
+        .await
+        .order;
+    assert_eq!(order.id, placed.order_id);
+    assert_eq!(order.limit_price, Some(BigDecimal::from(1)));
+
+    let cancelled = sandbox
+        .run(CancelOrderRequest {
+            order_id: placed.order_id.clone(),
+            market: test_market(),
+        })
+        .await;
+    assert_eq!(cancelled.order_id, placed.order_id);
+    let mut status = OrderStatus::Open;
+    for _ in 0..10 {
+        status = sandbox
+            .run(GetAccountOrderRequest {
+                order_id: placed.order_id.clone(),
+            })
+            .await
+            .order
+            .status;
+        if status == OrderStatus::Canceled {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(500)).await;
+    }
+    assert_eq!(status, OrderStatus::Canceled);
+
+    let response = sandbox
+        .run(CancelAllOrders {
+            market: test_market(),
+        })
+        .await;
+    assert!(response.accepted);
+});
+
+sandbox_test!(Subscriptions, subscriptions_orderbook, |sandbox| {
+    let mut updates = sandbox
+        .client()
+        .subscribe_protocol(SubscribeOrderbook {
+            market: test_market(),
+        })
+        .await
+        .unwrap();
+    let update = updates
+        .recv()
+        .await
+        .unwrap()
+        .unwrap()
+        .response_or_error()
+        .unwrap();
+    assert_book(&update.bids, &update.asks);
+});
+
+sandbox_test!(Subscriptions, subscriptions_trades, |sandbox| {
+    let mut trades = sandbox
+        .client()
+        .subscribe_protocol(SubscribeTrades {
+            market: test_market(),
+        })
+        .await
+        .unwrap();
+    // the sandbox may see no trades at all, but whatever arrives must be for the market
+    let mut received = 0;
+    let deadline = tokio::time::sleep(Duration::from_secs(30));
+    tokio::pin!(deadline);
+    loop {
+        tokio::select! {
+            update = trades.recv() => {
+                let update = update
+                    .expect("trade subscription closed")
+                    .unwrap()
+                    .response_or_error()
+                    .unwrap();
+                assert_eq!(update.market, test_market());
+                for trade in &update.trades {
+                    assert_eq!(trade.market, test_market());
+                    assert!(trade.amount > BigDecimal::zero());
+                    received += 1;
+                }
+            }
+            _ = &mut deadline => break,
+        }
+    }
+    eprintln!("received {} trades", received);
+});
@@ -0,0 +1,51 @@
+//! With the `custom-queries` feature, every `*.graphql` file in the directory named by
+//! `NASH_QUERY_OVERRIDES` replaces the built in document of the operation it defines. This
+//! lets partners running an exchange with an extended schema select extra fields or pass
+//! extra arguments, as long as the operation name, variables and response shape stay
+//! compatible with the built in query.
+
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+
+#[path = "src/protocol/operation_name.rs"]
+mod operation_name;
+
+use operation_name::operation_name;
+
+fn main() {
+    println!("cargo:rerun-if-changed=build.rs");
+    println!("cargo:rerun-if-env-changed=NASH_QUERY_OVERRIDES");
+    let mut overrides = Vec::new();
+    let dir = env::var_os("NASH_QUERY_OVERRIDES").map(PathBuf::from);
+    if let (Some(dir), true) = (dir, env::var_os("CARGO_FEATURE_CUSTOM_QUERIES").is_some()) {
+        println!("cargo:rerun-if-changed={}", dir.display());
+        let entries = fs::read_dir(&dir)
+            .unwrap_or_else(|e| panic!("NASH_QUERY_OVERRIDES {}: {}", dir.display(), e));
+        let mut paths: Vec<PathBuf> = entries
+            .map(|entry| entry.expect("reading NASH_QUERY_OVERRIDES").path())
+            .filter(|path| path.extension().map_or(false, |ext| ext == "graphql"))
+            .collect();
+        paths.sort();
+        for path in paths {
+            println!("cargo:rerun-if-changed={}", path.display());
+            let document = fs::read_to_string(&path)
+                .unwrap_or_else(|e| panic!("query override {}: {}", path.display(), e));
+            let name = operation_name(&document).unwrap_or_else(|| {
+                panic!(
+                    "query override {} defines no named operation",
+                    path.display()
+                )
+            });
+            let path = path.canonicalize().expect("query override path");
+            overrides.push((name, path));
+        }
+    }
+    let mut generated = String::from("pub(crate) static QUERY_OVERRIDES: &[(&str, &str)] = &[\n");
+    for (name, path) in overrides {
+        generated += &format!("    ({:?}, include_str!({:?})),\n", name, path);
+    }
+    generated += "];\n";
+    let out = PathBuf::from(env::var_os("OUT_DIR").expect("OUT_DIR")).join("query_overrides.rs");
+    fs::write(out, generated).expect("writing query overrides");
+}
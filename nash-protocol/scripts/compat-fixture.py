@@ -0,0 +1,64 @@
+"""Write a compatibility fixture for the keyfile of an existing fixture, computed with the
+`cryptography` package instead of this crate, e.g.
+
+    python3 scripts/compat-fixture.py test_data/compat/python-cryptography.json
+
+Signatures are deterministic (RFC 6979) with the low-S normalization Nash SDKs apply, so
+this crate must reproduce them byte for byte.
+"""
+
+import base64
+import json
+import sys
+
+import cryptography
+from cryptography.hazmat.primitives import hashes, serialization
+from cryptography.hazmat.primitives.asymmetric import ec, utils
+
+# order of secp256k1
+N = 0xFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFEBAAEDCE6AF48A03BBFD25E8CD0364141
+
+CANONICAL_STRINGS = [
+    "hello, world!",
+    "get_assets_nonces,{\"timestamp\":1565361133707}",
+    "place_limit_order,{\"allow_taker\":true,\"amount\":{\"amount\":\"10.000000\",\"currency\":\"eth\"},"
+    "\"buy_or_sell\":\"buy\",\"cancellation_policy\":\"good_til_cancelled\",\"market_name\":\"eth_usdc\","
+    "\"limit_price\":{\"amount\":\"200.00\",\"currency_a\":\"usdc\",\"currency_b\":\"eth\"},"
+    "\"nonce_from\":1,\"nonce_to\":1,\"nonce_order\":1565361133707,\"timestamp\":1565361133707}",
+    "cancel_all_orders,{\"market_name\":\"eth_usdc\",\"timestamp\":1565361133707}",
+    "",
+]
+
+
+def sign(key, message):
+    der = key.sign(message.encode(), ec.ECDSA(hashes.SHA256(), deterministic_signing=True))
+    r, s = utils.decode_dss_signature(der)
+    if s > N // 2:
+        s = N - s
+    return utils.encode_dss_signature(r, s).hex()
+
+
+def main(path):
+    with open(path) as f:
+        keyfile = json.load(f)["keyfile"]
+    keys = json.loads(base64.b64decode(keyfile["secret"]))
+    key = ec.derive_private_key(int(keys["payload_signing_key"], 16), ec.SECP256K1())
+    public_key = key.public_key().public_bytes(
+        serialization.Encoding.X962, serialization.PublicFormat.UncompressedPoint
+    )
+    fixture = {
+        "sdk": "python cryptography " + cryptography.__version__,
+        "keyfile": keyfile,
+        "payload_public_key": public_key.hex(),
+        "signatures": [
+            {"canonical_string": message, "signed_digest": sign(key, message)}
+            for message in CANONICAL_STRINGS
+        ],
+    }
+    with open(path, "w") as f:
+        json.dump(fixture, f, indent=2)
+        f.write("\n")
+
+
+if __name__ == "__main__":
+    main(sys.argv[1])
@@ -0,0 +1,5 @@
+//! Market analytics computed from public market data. Nothing in here places orders.
+
+mod triangular;
+
+pub use triangular::{ArbitrageLeg, ArbitrageOpportunity, TriangularArbitrage};
@@ -0,0 +1,191 @@
+//! Triangular arbitrage detection. Given best bid/ask quotes for a set of markets, every
+//! cycle of three assets (e.g. usdc -> eth -> btc -> usdc) is evaluated by chaining the
+//! quoted rates, net of taker fees. Cycles whose implied edge exceeds a threshold are
+//! reported as `ArbitrageOpportunity` events; acting on them is left to the caller.
+
+use std::collections::HashMap;
+
+use bigdecimal::{BigDecimal, ToPrimitive};
+
+use crate::protocol::subscriptions::updated_ticker::SubscribeTickerResponse;
+use crate::types::BuyOrSell;
+
+/// Best prices of a market, in quote asset per base asset
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+struct Quote {
+    bid: Option<f64>,
+    ask: Option<f64>,
+}
+
+/// One conversion within a cycle
+#[derive(Clone, Debug, PartialEq)]
+pub struct ArbitrageLeg {
+    pub market: String,
+    /// Side of the order on `market` that performs the conversion
+    pub side: BuyOrSell,
+    pub from: String,
+    pub to: String,
+    /// Units of `to` received per unit of `from`, before fees
+    pub rate: f64,
+}
+
+/// Cycle of three conversions that ends with more of the starting asset than it began with
+#[derive(Clone, Debug, PartialEq)]
+pub struct ArbitrageOpportunity {
+    pub legs: [ArbitrageLeg; 3],
+    /// Units of the starting asset after the cycle per unit put in, net of fees
+    pub net_rate: f64,
+    /// `net_rate - 1`
+    pub edge: f64,
+}
+
+impl ArbitrageOpportunity {
+    pub fn start_asset(&self) -> &str {
+        &self.legs[0].from
+    }
+}
+
+/// Keeps the latest quote for every market it is fed and evaluates the cycles touching a
+/// market whenever its quote changes
+#[derive(Clone, Debug)]
+pub struct TriangularArbitrage {
+    /// Taker fee charged on each leg, e.g. 0.0025 for 0.25%
+    pub fee_rate: f64,
+    /// Minimum net edge for a cycle to be reported
+    pub min_edge: f64,
+    quotes: HashMap<String, Quote>,
+}
+
+impl TriangularArbitrage {
+    pub fn new(fee_rate: f64, min_edge: f64) -> Self {
+        Self {
+            fee_rate,
+            min_edge,
+            quotes: HashMap::new(),
+        }
+    }
+
+    /// Record the best bid and ask of `market` (named `base_quote`) and return the
+    /// opportunities among the cycles that include it
+    pub fn update_quote(
+        &mut self,
+        market: &str,
+        bid: Option<f64>,
+        ask: Option<f64>,
+    ) -> Vec<ArbitrageOpportunity> {
+        self.quotes.insert(market.to_string(), Quote { bid, ask });
+        self.opportunities_for(market)
+    }
+
+    /// Same as `update_quote`, taking prices from a ticker update
+    pub fn update_ticker(&mut self, ticker: &SubscribeTickerResponse) -> Vec<ArbitrageOpportunity> {
+        let bid = ticker.best_bid_price.as_ref().and_then(BigDecimal::to_f64);
+        let ask = ticker.best_ask_price.as_ref().and_then(BigDecimal::to_f64);
+        self.update_quote(&ticker.market_name, bid, ask)
+    }
+
+    /// Forget a market, e.g. once it is no longer watched
+    pub fn remove_market(&mut self, market: &str) {
+        self.quotes.remove(market);
+    }
+
+    fn opportunities_for(&self, market: &str) -> Vec<ArbitrageOpportunity> {
+        let (a, b) = match split_market(market) {
+            Some(assets) => assets,
+            None => return Vec::new(),
+        };
+        let mut opportunities = Vec::new();
+        // every third asset connected to both a and b closes a triangle
+        let mut thirds: Vec<&str> = self
+            .quotes
+            .keys()
+            .filter_map(|other| split_market(other))
+            .flat_map(|(x, y)| vec![x, y])
+            .filter(|c| *c != a && *c != b)
+            .collect();
+        thirds.sort_unstable();
+        thirds.dedup();
+        for c in thirds {
+            for path in &[[a, b, c], [a, c, b]] {
+                if let Some(opportunity) = self.evaluate(path) {
+                    if opportunity.edge >= self.min_edge {
+                        opportunities.push(opportunity);
+                    }
+                }
+            }
+        }
+        opportunities
+    }
+
+    /// Evaluate the cycle path[0] -> path[1] -> path[2] -> path[0]
+    fn evaluate(&self, path: &[&str; 3]) -> Option<ArbitrageOpportunity> {
+        let first = self.leg(path[0], path[1])?;
+        let second = self.leg(path[1], path[2])?;
+        let third = self.leg(path[2], path[0])?;
+        let fee_factor = (1.0 - self.fee_rate).powi(3);
+        let net_rate = first.rate * second.rate * third.rate * fee_factor;
+        Some(ArbitrageOpportunity {
+            legs: [first, second, third],
+            net_rate,
+            edge: net_rate - 1.0,
+        })
+    }
+
+    /// Conversion of `from` into `to`, selling on `from_to` at the bid or buying on `to_from`
+    /// at the ask, whichever market exists
+    fn leg(&self, from: &str, to: &str) -> Option<ArbitrageLeg> {
+        let sell_market = format!("{}_{}", from, to);
+        if let Some(quote) = self.quotes.get(&sell_market) {
+            return quote.bid.filter(|bid| *bid > 0.0).map(|bid| ArbitrageLeg {
+                market: sell_market,
+                side: BuyOrSell::Sell,
+                from: from.to_string(),
+                to: to.to_string(),
+                rate: bid,
+            });
+        }
+        let buy_market = format!("{}_{}", to, from);
+        let quote = self.quotes.get(&buy_market)?;
+        quote.ask.filter(|ask| *ask > 0.0).map(|ask| ArbitrageLeg {
+            market: buy_market,
+            side: BuyOrSell::Buy,
+            from: from.to_string(),
+            to: to.to_string(),
+            rate: 1.0 / ask,
+        })
+    }
+}
+
+fn split_market(market: &str) -> Option<(&str, &str)> {
+    let mut assets = market.splitn(2, '_');
+    match (assets.next(), assets.next()) {
+        (Some(base), Some(quote)) if !base.is_empty() && !quote.is_empty() => Some((base, quote)),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::TriangularArbitrage;
+    use crate::types::BuyOrSell;
+
+    #[test]
+    fn detects_mispriced_cycle_net_of_fees() {
+        let mut detector = TriangularArbitrage::new(0.001, 0.0);
+        assert!(detector.update_quote("eth_usdc", Some(2000.0), Some(2001.0)).is_empty());
+        assert!(detector.update_quote("btc_usdc", Some(40000.0), Some(40010.0)).is_empty());
+        // eth_btc should trade around 0.05, so eth is overpriced in btc: buying eth for usdc,
+        // selling it for btc and selling the btc for usdc again yields ~3.9% before fees
+        let opportunities = detector.update_quote("eth_btc", Some(0.0520), Some(0.0521));
+        assert_eq!(opportunities.len(), 1);
+        let opportunity = &opportunities[0];
+        assert!(opportunity.edge > 0.0);
+        let sides: Vec<_> = opportunity.legs.iter().map(|leg| leg.side).collect();
+        assert_eq!(opportunity.legs[0].market, "eth_btc");
+        assert_eq!(sides, vec![BuyOrSell::Sell, BuyOrSell::Sell, BuyOrSell::Buy]);
+
+        // the same mispricing disappears once fees eat the edge
+        detector.fee_rate = 0.02;
+        assert!(detector.update_quote("eth_btc", Some(0.0520), Some(0.0521)).is_empty());
+    }
+}
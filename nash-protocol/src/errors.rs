@@ -1,33 +1,181 @@
 //! Error types defined for nash_protocol
 
-use thiserror::Error;
+use std::borrow::Cow;
+use std::fmt;
+use std::sync::Arc;
 
 /// Expose custom Result type that wraps ProtocolError
 pub type Result<T> = std::result::Result<T, ProtocolError>;
 
-/// Wrapper over string that describes error condition
-#[derive(Debug, Error, Clone)]
-pub struct ProtocolError(pub &'static str);
+type Source = Arc<dyn std::error::Error + Send + Sync + 'static>;
+
+/// Describes an error condition, along with what was being done when it occurred (e.g.
+/// which order was being signed) and the error that caused it, if any. The cause is
+/// available through `std::error::Error::source`.
+#[derive(Clone)]
+pub struct ProtocolError {
+    message: Cow<'static, str>,
+    /// Innermost first
+    context: Vec<String>,
+    source: Option<Source>,
+}
+
+/// Error with a fixed message. `ProtocolError` used to be a tuple struct wrapping the
+/// message, so errors are still created as `ProtocolError("...")`. The message is no longer
+/// a public field: read `error.0` as `error.message()`.
+#[allow(non_snake_case)]
+pub fn ProtocolError(message: &'static str) -> ProtocolError {
+    ProtocolError::new(message)
+}
 
 impl ProtocolError {
-    // FIXME: this is a terrible hack. Added temporarily because so much code was already relying
-    // upon &'static str creation of protocol errors, but migrate everything to String and allow
-    // construction of error messages dynamically
+    pub fn new(message: impl Into<Cow<'static, str>>) -> Self {
+        Self {
+            message: message.into(),
+            context: Vec::new(),
+            source: None,
+        }
+    }
+
+    /// Error with a message built at runtime
     pub fn coerce_static_from_str(error_str: &str) -> Self {
-        let coerce_static = Box::leak(error_str.to_string().into_boxed_str());
-        ProtocolError(coerce_static)
+        Self::new(error_str.to_string())
+    }
+
+    /// Error caused by `source`. The message should say what failed; the source is not
+    /// repeated in it.
+    pub fn with_source(
+        message: impl Into<Cow<'static, str>>,
+        source: impl std::error::Error + Send + Sync + 'static,
+    ) -> Self {
+        Self {
+            source: Some(Arc::new(source)),
+            ..Self::new(message)
+        }
+    }
+
+    /// Add what was being done when the error occurred, e.g. `placing order on eth_usdc`
+    pub fn context(mut self, context: impl Into<String>) -> Self {
+        self.context.push(context.into());
+        self
+    }
+
+    /// Message of the error, without context
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+
+    /// Context added to the error, outermost first
+    pub fn contexts(&self) -> impl Iterator<Item = &str> {
+        self.context.iter().rev().map(String::as_str)
+    }
+
+    /// The error followed by each error in its chain of sources, e.g. for logs
+    pub fn report(&self) -> String {
+        let mut report = self.to_string();
+        let mut source = std::error::Error::source(self);
+        while let Some(error) = source {
+            report.push_str(&format!(": {}", error));
+            source = error.source();
+        }
+        report
+    }
+
+    /// First error of type `E` in the chain of sources, e.g. a `ChainSigningError` telling
+    /// which blockchain an order could not be signed for
+    pub fn find_source<E: std::error::Error + 'static>(&self) -> Option<&E> {
+        let mut source = std::error::Error::source(self);
+        while let Some(error) = source {
+            if let Some(found) = error.downcast_ref::<E>() {
+                return Some(found);
+            }
+            source = error.source();
+        }
+        None
     }
 }
 
-impl std::fmt::Display for ProtocolError {
-    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
-        write!(f, "{}", self.0)
+impl fmt::Display for ProtocolError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for context in self.contexts() {
+            write!(f, "{}: ", context)?;
+        }
+        write!(f, "{}", self.message)
+    }
+}
+
+impl fmt::Debug for ProtocolError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut debug = f.debug_struct("ProtocolError");
+        debug.field("message", &self.message);
+        if !self.context.is_empty() {
+            debug.field("context", &self.contexts().collect::<Vec<_>>());
+        }
+        if let Some(source) = &self.source {
+            debug.field("source", source);
+        }
+        debug.finish()
+    }
+}
+
+impl std::error::Error for ProtocolError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        self.source
+            .as_ref()
+            .map(|source| source.as_ref() as &(dyn std::error::Error + 'static))
+    }
+}
+
+/// Adds context to the error of a `Result`
+pub trait ResultExt<T> {
+    fn context(self, context: impl Into<String>) -> Result<T>;
+
+    /// Like `context`, building the context only if there is an error
+    fn with_context<C: Into<String>, F: FnOnce() -> C>(self, context: F) -> Result<T>;
+}
+
+impl<T> ResultExt<T> for Result<T> {
+    fn context(self, context: impl Into<String>) -> Result<T> {
+        self.map_err(|error| error.context(context))
+    }
+
+    fn with_context<C: Into<String>, F: FnOnce() -> C>(self, context: F) -> Result<T> {
+        self.map_err(|error| error.context(context()))
     }
 }
 
 use bigdecimal::ParseBigDecimalError;
 impl From<ParseBigDecimalError> for ProtocolError {
-    fn from(_err: ParseBigDecimalError) -> Self {
-        ProtocolError("Error converting to BigDecimal")
+    fn from(err: ParseBigDecimalError) -> Self {
+        ProtocolError::with_source("Error converting to BigDecimal", err)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ProtocolError, Result, ResultExt};
+    use std::error::Error;
+
+    #[test]
+    fn context_and_source_are_kept() {
+        let parse_error = serde_json::from_str::<u32>("x").unwrap_err();
+        let result: Result<()> = Err(ProtocolError::with_source(
+            "Couldn't parse nonce",
+            parse_error,
+        ));
+        let error = result
+            .context("signing payload for eth")
+            .with_context(|| format!("placing order on {}", "eth_usdc"))
+            .unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "placing order on eth_usdc: signing payload for eth: Couldn't parse nonce"
+        );
+        assert_eq!(error.message(), "Couldn't parse nonce");
+        assert!(error.source().unwrap().is::<serde_json::Error>());
+        assert!(error.find_source::<serde_json::Error>().is_some());
+        assert!(error.find_source::<std::fmt::Error>().is_none());
+        assert!(error.report().starts_with(&format!("{}: ", error)));
+        assert!(ProtocolError("plain").source().is_none());
     }
 }
@@ -12,6 +12,8 @@ type DateTime = String;
 type Base16 = String;
 type PaginationCursor = String;
 type AffiliateDeveloperCode = String;
+#[allow(non_camel_case_types)]
+type ip_address = String;
 
 // But otherwise these macros will generate type constructor code
 // inside a new module, grounded on the associated structs
@@ -34,6 +36,24 @@ pub struct GetOrderbook;
 )]
 pub struct GetTicker;
 
+/// Rust constructor for ListTickers query
+#[derive(GraphQLQuery)]
+#[graphql(
+    schema_path = "src/graphql/schema.json",
+    query_path = "src/graphql/queries/list_tickers.graphql",
+    response_derives = "Debug"
+)]
+pub struct ListTickers;
+
+/// Rust constructor for ListAccountActivity query
+#[derive(GraphQLQuery)]
+#[graphql(
+    schema_path = "src/graphql/schema.json",
+    query_path = "src/graphql/queries/list_account_activity.graphql",
+    response_derives = "Debug"
+)]
+pub struct ListAccountActivity;
+
 #[derive(GraphQLQuery)]
 #[graphql(
     schema_path = "src/graphql/schema.json",
@@ -128,6 +148,15 @@ pub struct PlaceLimitOrder;
 )]
 pub struct PlaceMarketOrder;
 
+/// Rust type for PlaceStopLimitOrder mutation constructor
+#[derive(GraphQLQuery)]
+#[graphql(
+    schema_path = "src/graphql/schema.json",
+    query_path = "src/graphql/mutations/stop_limit_order.graphql",
+    response_derives = "Debug"
+)]
+pub struct PlaceStopLimitOrder;
+
 #[derive(GraphQLQuery)]
 #[graphql(
     schema_path = "src/graphql/schema.json",
@@ -136,6 +165,30 @@ pub struct PlaceMarketOrder;
 )]
 pub struct SignStates;
 
+#[derive(GraphQLQuery)]
+#[graphql(
+    schema_path = "src/graphql/schema.json",
+    query_path = "src/graphql/mutations/prepare_movement.graphql",
+    response_derives = "Debug"
+)]
+pub struct PrepareMovement;
+
+#[derive(GraphQLQuery)]
+#[graphql(
+    schema_path = "src/graphql/schema.json",
+    query_path = "src/graphql/mutations/add_movement.graphql",
+    response_derives = "Debug"
+)]
+pub struct AddMovement;
+
+#[derive(GraphQLQuery)]
+#[graphql(
+    schema_path = "src/graphql/schema.json",
+    query_path = "src/graphql/mutations/refresh_token.graphql",
+    response_derives = "Debug"
+)]
+pub struct RefreshToken;
+
 #[derive(GraphQLQuery)]
 #[graphql(
     schema_path = "src/graphql/schema.json",
@@ -152,6 +205,22 @@ pub struct ListAccountBalances;
 )]
 pub struct GetAccountOrder;
 
+#[derive(GraphQLQuery)]
+#[graphql(
+    schema_path = "src/graphql/schema.json",
+    query_path = "src/graphql/queries/get_account_address.graphql",
+    response_derives = "Debug"
+)]
+pub struct GetAccountAddress;
+
+#[derive(GraphQLQuery)]
+#[graphql(
+    schema_path = "src/graphql/schema.json",
+    query_path = "src/graphql/queries/get_account_volumes.graphql",
+    response_derives = "Debug"
+)]
+pub struct GetAccountVolumes;
+
 #[derive(GraphQLQuery)]
 #[graphql(
     schema_path = "src/graphql/schema.json",
@@ -199,3 +268,11 @@ pub struct ListCandles;
 )]
 pub struct ListTrades;
 
+
+#[derive(GraphQLQuery)]
+#[graphql(
+    schema_path = "src/graphql/schema.json",
+    query_path = "src/graphql/queries/list_movements.graphql",
+    response_derives = "Debug"
+)]
+pub struct ListMovements;
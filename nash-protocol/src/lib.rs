@@ -3,7 +3,11 @@
 //! For an example of how to use this library to construct network requests, see an [example client](https://github.com/nash-io/nash-rust/tree/master/nash-native-client)
 
 // FIXME: not all of these should be exposed
+pub mod analytics;
 pub mod errors;
+// Generated from the exchange's schema: an implementation detail that changes whenever the
+// schema is regenerated
+#[doc(hidden)]
 pub mod graphql;
 pub mod protocol;
 pub mod types;
@@ -8,7 +8,7 @@ use crate::utils::current_time_as_i64;
 use super::super::signer::Signer;
 
 use graphql::get_assets_nonces;
-use graphql_client::GraphQLQuery;
+use crate::protocol::query_overrides::build_query;
 
 impl AssetNoncesRequest {
     pub fn make_query(
@@ -34,9 +34,9 @@ impl AssetNoncesRequest {
             signature: RequestPayloadSignature::empty().into(),
         };
         let sig_payload = asset_nonces_canonical_string(&asset_nonce_args)?;
-        let sig = signer.sign_canonical_string(&sig_payload);
+        let sig = signer.sign_canonical_string(&sig_payload)?;
         asset_nonce_args.signature = sig.into();
-        Ok(graphql::GetAssetsNonces::build_query(asset_nonce_args))
+        Ok(build_query::<graphql::GetAssetsNonces>(asset_nonce_args))
     }
 }
 
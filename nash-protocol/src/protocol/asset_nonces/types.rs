@@ -40,7 +40,7 @@ impl NashProtocol for AssetNoncesRequest {
         // A bit of a hack, but if the client has a list of known assets acquired from
         // doing a ListMarkets request, we will extract that and use it in the query.
         // If not, request generation will fail
-        let assets = state.assets.clone();
+        let assets = state.assets.as_deref().cloned();
         let signer = state.signer()?;
         let query = self.make_query(signer, assets)?;
         serializable_to_json(&query)
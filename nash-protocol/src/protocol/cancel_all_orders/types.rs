@@ -7,6 +7,7 @@ use crate::graphql::cancel_all_orders;
 use async_trait::async_trait;
 use tokio::sync::RwLock;
 use std::sync::Arc;
+use crate::types::MarketSymbol;
 
 /// Request to cancel all orders in a given market. To prove orders have been canceled,
 /// the client must sign and sync nonces on the assets in question.
@@ -15,6 +16,14 @@ pub struct CancelAllOrders {
     pub market: String,
 }
 
+impl From<MarketSymbol> for CancelAllOrders {
+    fn from(market: MarketSymbol) -> Self {
+        Self {
+            market: market.into(),
+        }
+    }
+}
+
 /// Response indicates whether the ME accepted the request to cancel all orders
 #[derive(Clone, Copy, Debug)]
 pub struct CancelAllOrdersResponse {
@@ -26,10 +35,14 @@ pub struct CancelAllOrdersResponse {
 impl NashProtocol for CancelAllOrders {
     type Response = CancelAllOrdersResponse;
 
+    fn market_affinity(&self) -> Option<&str> {
+        Some(&self.market)
+    }
+
     async fn graphql(&self, state: Arc<RwLock<State>>) -> Result<serde_json::Value> {
         let state = state.read().await;
         let signer = state.signer()?;
-        let query = self.make_query(signer);
+        let query = self.make_query(signer)?;
         serializable_to_json(&query)
     }
 
@@ -1,15 +1,16 @@
 use super::super::{general_canonical_string, RequestPayloadSignature};
 use super::types::CancelOrderRequest;
+use crate::errors::{ProtocolError, Result};
 use crate::graphql;
 use crate::graphql::cancel_order;
 use crate::utils::current_time_as_i64;
 
 use super::super::signer::Signer;
 
-use graphql_client::GraphQLQuery;
+use crate::protocol::query_overrides::build_query;
 
 impl CancelOrderRequest {
-    pub fn make_variables(&self, signer: &Signer) -> cancel_order::Variables {
+    pub fn make_variables(&self, signer: &Signer) -> Result<cancel_order::Variables> {
         let mut cancel_args = cancel_order::Variables {
             payload: cancel_order::CancelOrderParams {
                 market_name: self.market.clone(),
@@ -18,28 +19,30 @@ impl CancelOrderRequest {
             },
             signature: RequestPayloadSignature::empty().into(),
         };
-        let sig_payload = cancel_all_canonical_string(&cancel_args);
-        let sig = signer.sign_canonical_string(&sig_payload);
+        let sig_payload = cancel_all_canonical_string(&cancel_args)?;
+        let sig = signer.sign_canonical_string(&sig_payload)?;
         cancel_args.signature = sig.into();
-        cancel_args
+        Ok(cancel_args)
     }
 
     pub fn make_query(
         &self,
         signer: &Signer,
-    ) -> graphql_client::QueryBody<cancel_order::Variables> {
-        let variables = self.make_variables(signer);
-        graphql::CancelOrder::build_query(variables)
+    ) -> Result<graphql_client::QueryBody<cancel_order::Variables>> {
+        let variables = self.make_variables(signer)?;
+        Ok(build_query::<graphql::CancelOrder>(variables))
     }
 }
 
-fn cancel_all_canonical_string(variables: &cancel_order::Variables) -> String {
-    let serialized_all = serde_json::to_string(variables).unwrap();
-    general_canonical_string(
+fn cancel_all_canonical_string(variables: &cancel_order::Variables) -> Result<String> {
+    let serialized_all = serde_json::to_string(variables)
+        .map_err(|_| ProtocolError("Failed to serialize variables"))?;
+    Ok(general_canonical_string(
         "cancel_order".to_string(),
-        serde_json::from_str(&serialized_all).unwrap(),
+        serde_json::from_str(&serialized_all)
+            .map_err(|_| ProtocolError("Failed to deserialize variables"))?,
         vec![],
-    )
+    ))
 }
 
 impl From<RequestPayloadSignature> for cancel_order::Signature {
@@ -29,10 +29,14 @@ pub struct CancelOrderResponse {
 impl NashProtocol for CancelOrderRequest {
     type Response = CancelOrderResponse;
 
+    fn market_affinity(&self) -> Option<&str> {
+        Some(&self.market)
+    }
+
     async fn graphql(&self, state: Arc<RwLock<State>>) -> Result<serde_json::Value> {
         let state = state.read().await;
         let signer = state.signer()?;
-        let query = self.make_query(signer);
+        let query = self.make_query(signer)?;
         serializable_to_json(&query)
     }
 
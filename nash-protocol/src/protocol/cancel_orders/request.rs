@@ -1,18 +1,20 @@
 use super::types::CancelOrdersRequest;
 use super::super::signer::Signer;
+use crate::errors::Result;
 use crate::protocol::multi_request::DynamicQueryBody;
+use crate::protocol::serializable_to_json;
 use std::collections::HashMap;
 
 impl CancelOrdersRequest {
     pub fn make_query(
         &self,
         signer: &Signer,
-    ) -> DynamicQueryBody {
+    ) -> Result<DynamicQueryBody> {
         let mut variables = HashMap::new();
         let mut params = String::new();
         let mut calls = String::new();
         for (index, variable) in self.requests.iter().enumerate() {
-            let variable = variable.make_variables(signer);
+            let variable = variable.make_variables(signer)?;
 
             // FIXME: This is also replicated in MarketOrdersConstructor::signed_graphql_request
             let payload = format!("payload{}", index);
@@ -25,10 +27,10 @@ impl CancelOrdersRequest {
                     orderId
                 }}
                 "#, calls, index, payload, signature);
-            variables.insert(payload, serde_json::to_value(variable.payload).unwrap());
-            variables.insert(signature, serde_json::to_value(variable.signature).unwrap());
+            variables.insert(payload, serializable_to_json(&variable.payload)?);
+            variables.insert(signature, serializable_to_json(&variable.signature)?);
         }
-        DynamicQueryBody {
+        Ok(DynamicQueryBody {
             variables,
             operation_name: "CancelOrder",
             query: format!(r#"
@@ -36,6 +38,6 @@ impl CancelOrdersRequest {
                     {}
                 }}
             "#, params, calls)
-        }
+        })
     }
 }
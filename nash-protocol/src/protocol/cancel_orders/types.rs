@@ -6,7 +6,6 @@ use crate::errors::Result;
 use async_trait::async_trait;
 use tokio::sync::RwLock;
 use std::sync::Arc;
-use std::convert::TryInto;
 use crate::protocol::multi_request::{MultiRequest, MultiResponse};
 use crate::protocol::cancel_order::{CancelOrderRequest, CancelOrderResponse};
 
@@ -18,10 +17,14 @@ pub type CancelOrdersResponse = MultiResponse<CancelOrderResponse>;
 impl NashProtocol for CancelOrdersRequest {
     type Response = CancelOrdersResponse;
 
+    fn market_affinity(&self) -> Option<&str> {
+        self.requests.first().map(|request| request.market.as_str())
+    }
+
     async fn graphql(&self, state: Arc<RwLock<State>>) -> Result<serde_json::Value> {
         let state = state.read().await;
         let signer = state.signer()?;
-        let query = self.make_query(signer);
+        let query = self.make_query(signer)?;
         serializable_to_json(&query)
     }
 
@@ -30,6 +33,6 @@ impl NashProtocol for CancelOrdersRequest {
         response: serde_json::Value,
         _state: Arc<RwLock<State>>
     ) -> Result<ResponseOrError<Self::Response>> {
-        Ok(ResponseOrError::from_data(response.try_into()?))
+        Ok(ResponseOrError::from_data(MultiResponse::from_graphql(response, self.requests.len())?))
     }
 }
@@ -0,0 +1,127 @@
+//! Compatibility checks for keys exported from other Nash SDKs. A fixture records what
+//! another SDK derived from a keyfile (payload public key, child key addresses and request
+//! signatures); checking it against this crate verifies that the keys load and sign
+//! identically before switching a live deployment over.
+
+use std::collections::HashMap;
+
+use serde::Deserialize;
+
+use super::Signer;
+use crate::errors::{ProtocolError, Result};
+
+/// Canonical string and the DER encoded hex signature another SDK produced for it
+#[derive(Clone, Debug, Deserialize)]
+pub struct SignatureVector {
+    pub canonical_string: String,
+    pub signed_digest: String,
+}
+
+/// Values exported by another SDK. See `test_data/compat/README.md` for the file format.
+#[derive(Clone, Debug, Deserialize)]
+pub struct CompatibilityFixture {
+    /// Name and version of the SDK the values were exported from
+    pub sdk: String,
+    /// Keyfile the values were computed with, if it is shipped with the fixture
+    #[serde(default)]
+    pub keyfile: Option<serde_json::Value>,
+    #[serde(default)]
+    pub payload_public_key: Option<String>,
+    /// Child key addresses by derivation path, e.g. `m/44'/60'/0'/0/0`
+    #[serde(default)]
+    pub addresses: HashMap<String, String>,
+    #[serde(default)]
+    pub signatures: Vec<SignatureVector>,
+}
+
+/// Outcome of a compatibility check. Every differing value is listed.
+#[derive(Clone, Debug)]
+pub struct CompatibilityReport {
+    pub sdk: String,
+    pub checked: usize,
+    pub mismatches: Vec<String>,
+}
+
+impl CompatibilityReport {
+    pub fn is_compatible(&self) -> bool {
+        self.mismatches.is_empty()
+    }
+}
+
+impl CompatibilityFixture {
+    pub fn from_json(fixture: &str) -> Result<Self> {
+        serde_json::from_str(fixture)
+            .map_err(|e| ProtocolError::with_source("Could not parse compatibility fixture", e))
+    }
+
+    /// Check the fixture against the keyfile it ships with
+    pub fn check(&self) -> Result<CompatibilityReport> {
+        let keyfile = self
+            .keyfile
+            .as_ref()
+            .ok_or(ProtocolError("Compatibility fixture does not include a keyfile"))?;
+        self.check_keyfile(&keyfile.to_string())
+    }
+
+    /// Check the fixture against a keyfile, e.g. the one the fixture was exported with in another SDK
+    pub fn check_keyfile(&self, keyfile: &str) -> Result<CompatibilityReport> {
+        let signer = Signer::from_keyfile_json(keyfile)?;
+        let mut checked = 0;
+        let mut mismatches = Vec::new();
+        if let Some(expected) = &self.payload_public_key {
+            checked += 1;
+            let actual = signer.request_payload_public_key();
+            if !actual.eq_ignore_ascii_case(expected) {
+                mismatches.push(format!(
+                    "payload public key: expected {}, got {}",
+                    expected, actual
+                ));
+            }
+        }
+        for (path, expected) in &self.addresses {
+            checked += 1;
+            match signer.api_keys.keys.child_keys.get(path) {
+                Some(key) if key.address.eq_ignore_ascii_case(expected) => {}
+                Some(key) => mismatches.push(format!(
+                    "address for {}: expected {}, got {}",
+                    path, expected, key.address
+                )),
+                None => mismatches.push(format!("address for {}: no child key", path)),
+            }
+        }
+        for vector in &self.signatures {
+            checked += 1;
+            let actual = signer.sign_canonical_string(&vector.canonical_string)?.signed_digest;
+            if !actual.eq_ignore_ascii_case(&vector.signed_digest) {
+                mismatches.push(format!(
+                    "signature of {:?}: expected {}, got {}",
+                    vector.canonical_string, vector.signed_digest, actual
+                ));
+            }
+        }
+        Ok(CompatibilityReport {
+            sdk: self.sdk.clone(),
+            checked,
+            mismatches,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::CompatibilityFixture;
+
+    // computed by an independent ECDSA implementation, see `scripts/compat-fixture.py`
+    #[test]
+    fn keys_sign_like_python_cryptography() {
+        let fixture = include_str!("../../test_data/compat/python-cryptography.json");
+        let mut fixture = CompatibilityFixture::from_json(fixture).unwrap();
+        let report = fixture.check().unwrap();
+        assert_eq!(report.checked, 6);
+        assert!(report.is_compatible(), "{:?}", report.mismatches);
+
+        fixture.signatures[0].canonical_string.push('!');
+        fixture.payload_public_key = Some("04".to_string());
+        assert_eq!(fixture.check().unwrap().mismatches.len(), 2);
+    }
+}
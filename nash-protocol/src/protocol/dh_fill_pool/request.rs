@@ -1,6 +1,6 @@
 use crate::graphql;
 use graphql::dh_fill_pool;
-use graphql_client::GraphQLQuery;
+use crate::protocol::query_overrides::build_query;
 use nash_mpc::curves::traits::ECPoint;
 
 use super::{DhFillPoolRequest, K1FillPool, R1FillPool};
@@ -31,7 +31,7 @@ impl DhFillPoolRequest {
             dh_publics,
             blockchain: self.blockchain().into(),
         };
-        graphql::DhFillPool::build_query(dh_args)
+        build_query::<graphql::DhFillPool>(dh_args)
     }
 }
 
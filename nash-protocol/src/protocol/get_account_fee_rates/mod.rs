@@ -0,0 +1,8 @@
+//! Get the current maker and taker fee rates of the account. The rates are kept in client
+//! state and signed into the fill payloads of subsequent orders.
+
+mod request;
+mod response;
+mod types;
+
+pub use types::{GetAccountFeeRatesRequest, GetAccountFeeRatesResponse};
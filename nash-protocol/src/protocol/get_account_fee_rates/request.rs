@@ -0,0 +1,14 @@
+use super::types::GetAccountFeeRatesRequest;
+use crate::graphql;
+use crate::graphql::get_account_volumes;
+
+use crate::protocol::query_overrides::build_query;
+
+impl GetAccountFeeRatesRequest {
+    pub fn make_query(&self) -> graphql_client::QueryBody<get_account_volumes::Variables> {
+        let get_volumes = get_account_volumes::Variables {
+            payload: get_account_volumes::GetAccountVolumesParams { timestamp: None },
+        };
+        build_query::<graphql::GetAccountVolumes>(get_volumes)
+    }
+}
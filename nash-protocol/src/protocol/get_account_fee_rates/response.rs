@@ -0,0 +1,25 @@
+use super::types::GetAccountFeeRatesResponse;
+use crate::errors::{ProtocolError, Result};
+use crate::graphql::get_account_volumes;
+use crate::types::AccountFeeRates;
+use bigdecimal::BigDecimal;
+use std::convert::TryFrom;
+use std::str::FromStr;
+
+impl TryFrom<get_account_volumes::ResponseData> for GetAccountFeeRatesResponse {
+    type Error = ProtocolError;
+    fn try_from(response: get_account_volumes::ResponseData) -> Result<Self> {
+        let volumes = response.get_account_volumes;
+        let maker = fee_rate(volumes.maker_fee_rate)?;
+        let taker = fee_rate(volumes.taker_fee_rate)?;
+        Ok(Self {
+            fee_rates: AccountFeeRates::new(maker, taker)?,
+        })
+    }
+}
+
+// Floats go through their shortest string form, so 0.0025 doesn't turn into 0.00250000000000000005
+fn fee_rate(rate: Option<f64>) -> Result<BigDecimal> {
+    let rate = rate.ok_or(ProtocolError("Fee rate missing in account volumes"))?;
+    BigDecimal::from_str(&rate.to_string()).map_err(|_| ProtocolError("Could not parse fee rate"))
+}
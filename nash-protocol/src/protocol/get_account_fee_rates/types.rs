@@ -0,0 +1,47 @@
+use super::super::{
+    serializable_to_json, try_response_from_json, NashProtocol, ResponseOrError, State,
+};
+use crate::errors::Result;
+use crate::graphql::get_account_volumes;
+use crate::types::AccountFeeRates;
+use async_trait::async_trait;
+use std::sync::Arc;
+use tokio::sync::RwLock;
+
+/// Request the fee rates Nash currently charges the account
+#[derive(Clone, Debug)]
+pub struct GetAccountFeeRatesRequest;
+
+#[derive(Clone, Debug)]
+pub struct GetAccountFeeRatesResponse {
+    pub fee_rates: AccountFeeRates,
+}
+
+#[async_trait]
+impl NashProtocol for GetAccountFeeRatesRequest {
+    type Response = GetAccountFeeRatesResponse;
+
+    async fn graphql(&self, _state: Arc<RwLock<State>>) -> Result<serde_json::Value> {
+        let query = self.make_query();
+        serializable_to_json(&query)
+    }
+
+    async fn process_response(
+        &self,
+        response: &Self::Response,
+        state: Arc<RwLock<State>>,
+    ) -> Result<()> {
+        state.write().await.set_fee_rates(response.fee_rates.clone());
+        Ok(())
+    }
+
+    async fn response_from_json(
+        &self,
+        response: serde_json::Value,
+        _state: Arc<RwLock<State>>,
+    ) -> Result<ResponseOrError<Self::Response>> {
+        try_response_from_json::<GetAccountFeeRatesResponse, get_account_volumes::ResponseData>(
+            response,
+        )
+    }
+}
@@ -2,7 +2,7 @@ use super::types::GetAccountOrderRequest;
 use crate::graphql;
 use crate::graphql::get_account_order;
 
-use graphql_client::GraphQLQuery;
+use crate::protocol::query_overrides::build_query;
 
 impl GetAccountOrderRequest {
     pub fn make_query(&self) -> graphql_client::QueryBody<get_account_order::Variables> {
@@ -11,6 +11,6 @@ impl GetAccountOrderRequest {
                 order_id: self.order_id.clone(),
             },
         };
-        graphql::GetAccountOrder::build_query(get_order)
+        build_query::<graphql::GetAccountOrder>(get_order)
     }
 }
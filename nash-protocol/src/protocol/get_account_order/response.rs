@@ -1,12 +1,13 @@
 use super::types::GetAccountOrderResponse;
 use crate::errors::{ProtocolError, Result};
+use std::convert::{TryFrom, TryInto};
 use crate::graphql::get_account_order;
 use crate::types::{
     AccountTradeSide, BuyOrSell, Order, OrderCancellationPolicy, OrderCancellationReason,
     OrderStatus, OrderType, Trade,
 };
 use crate::protocol::{State, traits::TryFromState};
-use chrono::{DateTime, Utc};
+use crate::types::timestamp::parse_timestamp;
 use std::str::FromStr;
 use bigdecimal::BigDecimal;
 use std::sync::Arc;
@@ -29,8 +30,7 @@ impl TryFromState<get_account_order::ResponseData> for GetAccountOrderResponse {
             Some(price) => Some(BigDecimal::from_str(&price.amount)?),
             None => None,
         };
-        let placed_at = DateTime::<Utc>::from_str(&order_data.placed_at)
-            .map_err(|_| ProtocolError("Could not convert value to DateTime"))?;
+        let placed_at = parse_timestamp(&order_data.placed_at)?;
         let mut trades = Vec::new();
         // These wraps are safe. ME_FIXME
         for trade_data in order_data.trades.as_ref().unwrap() {
@@ -53,8 +53,7 @@ impl TryFromState<get_account_order::ResponseData> for GetAccountOrderResponse {
                 maker_order_id: trade_data.maker_order_id.clone(),
                 account_side: (&trade_data.account_side).into(),
                 id: trade_data.id.clone(),
-                executed_at: DateTime::<Utc>::from_str(&trade_data.executed_at)
-                    .map_err(|_| ProtocolError("Could not convert value to DateTime"))?,
+                executed_at: parse_timestamp(&trade_data.executed_at)?,
                 limit_price,
                 direction: (&trade_data.direction).into(),
             })
@@ -71,7 +70,7 @@ impl TryFromState<get_account_order::ResponseData> for GetAccountOrderResponse {
                 stop_price,
                 placed_at,
                 trades,
-                cancellation_policy: (&order_data).into(),
+                cancellation_policy: (&order_data).try_into()?,
                 cancellation_reason: order_data.cancellation_reason.as_ref().map(|x| x.into()),
                 buy_or_sell: order_data.buy_or_sell.into(),
                 order_type: order_data.type_.into(),
@@ -134,10 +133,12 @@ impl From<&get_account_order::OrderCancellationReason> for OrderCancellationReas
     }
 }
 
-impl From<&get_account_order::GetAccountOrderGetAccountOrder> for Option<OrderCancellationPolicy> {
-    fn from(order: &get_account_order::GetAccountOrderGetAccountOrder) -> Self {
+impl TryFrom<&get_account_order::GetAccountOrderGetAccountOrder> for Option<OrderCancellationPolicy> {
+    type Error = ProtocolError;
+
+    fn try_from(order: &get_account_order::GetAccountOrderGetAccountOrder) -> Result<Self> {
         if let Some(cancellation_policy) = &order.cancellation_policy {
-            Some(match cancellation_policy {
+            Ok(Some(match cancellation_policy {
                 get_account_order::OrderCancellationPolicy::FILL_OR_KILL => {
                     OrderCancellationPolicy::FillOrKill
                 }
@@ -145,18 +146,20 @@ impl From<&get_account_order::GetAccountOrderGetAccountOrder> for Option<OrderCa
                     OrderCancellationPolicy::GoodTilCancelled
                 }
                 get_account_order::OrderCancellationPolicy::GOOD_TIL_TIME => {
-                    // This unwrap is safe here. GoodTilTime must have an associated time
-                    let cancel_at =
-                        DateTime::<Utc>::from_str(order.cancel_at.as_ref().unwrap()).unwrap();
+                    let cancel_at = order
+                        .cancel_at
+                        .as_ref()
+                        .ok_or(ProtocolError("GoodTilTime order has no cancellation time"))?;
+                    let cancel_at = parse_timestamp(cancel_at)?;
                     OrderCancellationPolicy::GoodTilTime(cancel_at)
                 }
                 get_account_order::OrderCancellationPolicy::IMMEDIATE_OR_CANCEL => {
                     OrderCancellationPolicy::ImmediateOrCancel
                 }
                 _ => panic!("Unsupported OrderCancellationPolicy"),
-            })
+            }))
         } else {
-            None
+            Ok(None)
         }
     }
 }
@@ -22,6 +22,12 @@ pub struct GetAccountOrderResponse {
     pub order: Order,
 }
 
+impl From<GetAccountOrderResponse> for Order {
+    fn from(response: GetAccountOrderResponse) -> Self {
+        response.order
+    }
+}
+
 /// Implement protocol bindings for GetAccountOrderRequest
 #[async_trait]
 impl NashProtocol for GetAccountOrderRequest {
@@ -0,0 +1,9 @@
+//! Get the address to deposit an asset to. Deposits to it are credited to the account's
+//! personal balance. None of the blockchains the exchange supports needs a memo or tag
+//! next to the address, so the address alone identifies the account.
+
+mod request;
+mod response;
+mod types;
+
+pub use types::{DepositAddressResponse, GetDepositAddressRequest};
@@ -0,0 +1,16 @@
+use super::types::GetDepositAddressRequest;
+use crate::graphql;
+use crate::graphql::get_account_address;
+
+use crate::protocol::query_overrides::build_query;
+
+impl GetDepositAddressRequest {
+    pub fn make_query(&self) -> graphql_client::QueryBody<get_account_address::Variables> {
+        let get_address = get_account_address::Variables {
+            payload: get_account_address::GetAccountAddressParams {
+                currency: self.asset.name().to_string(),
+            },
+        };
+        build_query::<graphql::GetAccountAddress>(get_address)
+    }
+}
@@ -0,0 +1,66 @@
+use super::super::ResponseOrError;
+use super::types::{DepositAddressResponse, GetDepositAddressRequest};
+use crate::errors::{ProtocolError, Result};
+use crate::graphql::get_account_address;
+use crate::types::Address;
+
+impl GetDepositAddressRequest {
+    pub fn response_from_graphql(
+        &self,
+        response: ResponseOrError<get_account_address::ResponseData>,
+    ) -> Result<ResponseOrError<DepositAddressResponse>> {
+        match response {
+            ResponseOrError::Response(data) => {
+                let address_string = data
+                    .data
+                    .get_account_address
+                    .address
+                    .ok_or(ProtocolError("Exchange has no deposit address for asset"))?;
+                let blockchain = self.asset.blockchain();
+                let address = Address::parse(blockchain, &address_string)?;
+                Ok(ResponseOrError::from_data(DepositAddressResponse {
+                    asset: self.asset,
+                    blockchain,
+                    address,
+                    address_string,
+                }))
+            }
+            ResponseOrError::Error(error) => Ok(ResponseOrError::Error(error)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::GetDepositAddressRequest;
+    use crate::graphql::get_account_address;
+    use crate::protocol::ResponseOrError;
+    use crate::types::{Address, Asset, Blockchain};
+
+    fn response(address: Option<&str>) -> ResponseOrError<get_account_address::ResponseData> {
+        let data = serde_json::from_value(serde_json::json!({
+            "getAccountAddress": { "address": address, "currency": "eth" }
+        }))
+        .unwrap();
+        ResponseOrError::from_data(data)
+    }
+
+    #[test]
+    fn deposit_addresses_are_parsed_for_their_blockchain() {
+        let request = GetDepositAddressRequest::from(Asset::ETH);
+        let address = "0xd58547f100b67bb99bbe8e94523b6bb4fda76954";
+        let deposit = request
+            .response_from_graphql(response(Some(address)))
+            .unwrap()
+            .response_or_error()
+            .unwrap();
+        assert_eq!(deposit.blockchain, Blockchain::Ethereum);
+        assert_eq!(
+            deposit.address,
+            Address::new(Blockchain::Ethereum, &address[2..]).unwrap()
+        );
+        assert_eq!(deposit.address_string, address);
+
+        assert!(request.response_from_graphql(response(None)).is_err());
+    }
+}
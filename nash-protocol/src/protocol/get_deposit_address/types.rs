@@ -0,0 +1,50 @@
+use super::super::{
+    json_to_type_or_error, serializable_to_json, NashProtocol, ResponseOrError, State,
+};
+use crate::errors::Result;
+use crate::types::{Address, Asset, Blockchain};
+use async_trait::async_trait;
+use std::sync::Arc;
+use tokio::sync::RwLock;
+
+/// Look up the address to deposit `asset` to. Assets of the same blockchain share an address.
+#[derive(Clone, Debug)]
+pub struct GetDepositAddressRequest {
+    pub asset: Asset,
+}
+
+impl From<Asset> for GetDepositAddressRequest {
+    fn from(asset: Asset) -> Self {
+        Self { asset }
+    }
+}
+
+/// Deposit address of an asset, parsed for its blockchain
+#[derive(Clone, Debug)]
+pub struct DepositAddressResponse {
+    pub asset: Asset,
+    pub blockchain: Blockchain,
+    pub address: Address,
+    /// The address as the exchange returned it, e.g. to show or encode in a QR code
+    pub address_string: String,
+}
+
+/// Implement protocol bindings for GetDepositAddressRequest
+#[async_trait]
+impl NashProtocol for GetDepositAddressRequest {
+    type Response = DepositAddressResponse;
+
+    async fn graphql(&self, _state: Arc<RwLock<State>>) -> Result<serde_json::Value> {
+        let query = self.make_query();
+        serializable_to_json(&query)
+    }
+
+    async fn response_from_json(
+        &self,
+        response: serde_json::Value,
+        _state: Arc<RwLock<State>>,
+    ) -> Result<ResponseOrError<Self::Response>> {
+        let as_graphql = json_to_type_or_error(response)?;
+        self.response_from_graphql(as_graphql)
+    }
+}
@@ -7,6 +7,7 @@ use bigdecimal::BigDecimal;
 use super::super::{
     json_to_type_or_error, serializable_to_json, NashProtocol, ResponseOrError, State,
 };
+use crate::types::MarketSymbol;
 
 /// Get ticker associated with market
 #[derive(Clone, Debug)]
@@ -14,6 +15,14 @@ pub struct TickerRequest {
     pub market: String,
 }
 
+impl From<MarketSymbol> for TickerRequest {
+    fn from(market: MarketSymbol) -> Self {
+        Self {
+            market: market.into(),
+        }
+    }
+}
+
 /// Ticker response information
 #[derive(Clone, Debug)]
 pub struct TickerResponse {
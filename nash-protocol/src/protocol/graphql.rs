@@ -7,6 +7,7 @@ use std::convert::{TryFrom, TryInto};
 use std::fmt::Display;
 use super::traits::TryFromState;
 use super::state::State;
+use super::throttled::Throttled;
 use std::sync::Arc;
 use tokio::sync::RwLock;
 use std::collections::HashMap;
@@ -21,15 +22,15 @@ pub fn json_to_type_or_error<T: DeserializeOwned>(
 ) -> Result<ResponseOrError<T>> {
     Ok(serde_json::from_value(response)
         .map_err(|x| {
-            ProtocolError::coerce_static_from_str(&format!("{:#?}", x))
+            ProtocolError::with_source("Could not parse response", x)
         })?)
 }
 
 pub fn serializable_to_json<T: Serialize>(obj: &T) -> Result<serde_json::Value> {
     let str_val = serde_json::to_string(obj)
-        .map_err(|_| ProtocolError("Unexpected problem serializing T to string"))?;
-    Ok(serde_json::from_str(&str_val)
-        .expect("Transforming to JSON failed. This should never happen"))
+        .map_err(|e| ProtocolError::with_source("Unexpected problem serializing T to string", e))?;
+    serde_json::from_str(&str_val)
+        .map_err(|e| ProtocolError::with_source("Unexpected problem transforming T to JSON", e))
 }
 
 /// Helper to convert data corresponding to raw GraphQL types (B) into
@@ -70,7 +71,7 @@ where
             let conversion = TryFromState::from(data, state.clone()).await;
             match conversion {
                 Ok(data) => Ok(ResponseOrError::from_data(data)),
-                Err(err) => Err(ProtocolError::coerce_static_from_str(&format!("{}", err))),
+                Err(err) => Err(err),
             }
         },
         ResponseOrError::Error(e) => Ok(ResponseOrError::Error(e)),
@@ -124,11 +125,17 @@ impl<T> ResponseOrError<T> {
             Self::Error(e) => Some(e),
         }
     }
-    /// Get response or else error
+    /// Get response or else error. The GraphQL errors are kept as the source of the error,
+    /// wrapped in `Throttled` if the exchange refused the request because too many were sent.
     pub fn response_or_error(self) -> Result<T> {
         match self {
             Self::Response(DataResponse { data}) => Ok(data),
-            Self::Error(e) => Err(ProtocolError::coerce_static_from_str(&format!("{:?}", e)))
+            Self::Error(e) => Err(match Throttled::from_response(e) {
+                Ok(throttled) => {
+                    ProtocolError::with_source(format!("{:?}", throttled.response), throttled)
+                }
+                Err(e) => ProtocolError::with_source(format!("{:?}", e), e),
+            })
         }
     }
     /// Get error from wrapper if it exists
@@ -178,9 +185,7 @@ impl TryFrom<serde_json::Value> for GraphQLResponse {
     type Error = ProtocolError;
     fn try_from(response: serde_json::Value) -> Result<Self> {
         serde_json::from_value(response).map_err(|e|
-            ProtocolError::coerce_static_from_str(
-                &format!("Couldn't parse response: {:#?}", e)
-            )
+            ProtocolError::with_source("Couldn't parse response", e)
         )
     }
 }
@@ -191,10 +196,20 @@ pub struct ErrorResponse {
     pub errors: Vec<Error>,
 }
 
+impl std::fmt::Display for ErrorResponse {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let messages: Vec<_> = self.errors.iter().map(|error| error.message.as_str()).collect();
+        write!(f, "{}", messages.join("; "))
+    }
+}
+
+impl std::error::Error for ErrorResponse {}
+
 /// Inner wrapper on error GraphQL response data
 #[derive(Deserialize, Serialize, Debug)]
 pub struct Error {
     pub message: String,
+    #[serde(default)]
     pub path: Vec<String>
 }
 
@@ -13,6 +13,7 @@ use crate::protocol::ErrorResponse;
 use super::asset_nonces::{AssetNoncesRequest, AssetNoncesResponse};
 use super::cancel_all_orders::{CancelAllOrders, CancelAllOrdersResponse};
 use super::dh_fill_pool::{DhFillPoolRequest, DhFillPoolResponse};
+use super::get_account_fee_rates::{GetAccountFeeRatesRequest, GetAccountFeeRatesResponse};
 use super::list_markets::{ListMarketsRequest, ListMarketsResponse};
 use super::orderbook::{OrderbookRequest, OrderbookResponse};
 use super::place_order::{LimitOrderRequest, PlaceOrderResponse};
@@ -34,6 +35,7 @@ pub enum NashProtocolRequest {
     CancelOrders(CancelAllOrders),
     SignState(SignStatesRequest),
     ListMarkets(ListMarketsRequest),
+    FeeRates(GetAccountFeeRatesRequest),
 }
 
 /// An enum wrapping all the different protocol responses
@@ -46,6 +48,7 @@ pub enum NashProtocolResponse {
     CancelOrders(CancelAllOrdersResponse),
     SignState(SignStatesResponse),
     ListMarkets(ListMarketsResponse),
+    FeeRates(GetAccountFeeRatesResponse),
 }
 
 /// Implement NashProtocol for the enum, threading through to the base implementation
@@ -71,6 +74,15 @@ impl NashProtocol for NashProtocolRequest {
             Self::ListMarkets(list_markets) => {
                 NashProtocol::acquire_permit(list_markets, state).await
             }
+            Self::FeeRates(fee_rates) => NashProtocol::acquire_permit(fee_rates, state).await,
+        }
+    }
+
+    fn market_affinity(&self) -> Option<&str> {
+        match self {
+            Self::LimitOrder(limit_order) => limit_order.market_affinity(),
+            Self::CancelOrders(cancel_all) => cancel_all.market_affinity(),
+            _ => None,
         }
     }
 
@@ -83,6 +95,7 @@ impl NashProtocol for NashProtocolRequest {
             Self::SignState(sign_state) => sign_state.graphql(state).await,
             Self::CancelOrders(cancel_all) => cancel_all.graphql(state).await,
             Self::ListMarkets(list_markets) => list_markets.graphql(state).await,
+            Self::FeeRates(fee_rates) => fee_rates.graphql(state).await,
         }
     }
 
@@ -120,6 +133,10 @@ impl NashProtocol for NashProtocolRequest {
                 .response_from_json(response, state)
                 .await?
                 .map(Box::new(|res| NashProtocolResponse::ListMarkets(res)))),
+            Self::FeeRates(fee_rates) => Ok(fee_rates
+                .response_from_json(response, state)
+                .await?
+                .map(Box::new(|res| NashProtocolResponse::FeeRates(res)))),
         }
     }
 
@@ -147,6 +164,9 @@ impl NashProtocol for NashProtocolRequest {
             (Self::ListMarkets(list_markets), NashProtocolResponse::ListMarkets(response)) => {
                 list_markets.process_response(response, state).await?
             }
+            (Self::FeeRates(fee_rates), NashProtocolResponse::FeeRates(response)) => {
+                fee_rates.process_response(response, state).await?
+            }
             _ => {
                 return Err(ProtocolError(
                     "Attempting to process a differently typed response. This should never happen.
@@ -172,6 +192,7 @@ impl NashProtocol for NashProtocolRequest {
             Self::SignState(sign_state) => sign_state.process_error(response, graphql_request, state).await,
             Self::CancelOrders(cancel_all) => cancel_all.process_error(response, graphql_request, state).await,
             Self::ListMarkets(list_markets) => list_markets.process_error(response, graphql_request, state).await,
+            Self::FeeRates(fee_rates) => fee_rates.process_error(response, graphql_request, state).await,
         }
     }
 
@@ -184,6 +205,7 @@ impl NashProtocol for NashProtocolRequest {
             Self::SignState(sign_state) => NashProtocol::run_before(sign_state, state).await,
             Self::CancelOrders(cancel_all) => NashProtocol::run_before(cancel_all, state).await,
             Self::ListMarkets(list_markets) => NashProtocol::run_before(list_markets, state).await,
+            Self::FeeRates(fee_rates) => NashProtocol::run_before(fee_rates, state).await,
         }
     }
 
@@ -196,6 +218,7 @@ impl NashProtocol for NashProtocolRequest {
             Self::SignState(sign_state) => NashProtocol::run_after(sign_state, state).await,
             Self::CancelOrders(cancel_all) => NashProtocol::run_after(cancel_all, state).await,
             Self::ListMarkets(list_markets) => NashProtocol::run_after(list_markets, state).await,
+            Self::FeeRates(fee_rates) => NashProtocol::run_after(fee_rates, state).await,
         }
     }
 }
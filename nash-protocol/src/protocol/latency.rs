@@ -0,0 +1,197 @@
+//! Latency budgets for order requests. A budget is attached by the caller when running
+//! a request, and the client records how long each stage took so that slow paths can be
+//! reported (or the order dropped before it ever reaches the exchange).
+
+use crate::errors::{ProtocolError, Result};
+use crate::protocol::ResponseOrError;
+use std::time::Duration;
+
+/// Maximum time a caller is willing to spend on an order, from construction until the
+/// exchange response has been processed
+#[derive(Clone, Copy, Debug)]
+pub struct LatencyBudget {
+    pub budget: Duration,
+    /// If set, orders whose construction and signing already blew the budget are never submitted
+    pub abort_before_submit: bool,
+}
+
+impl LatencyBudget {
+    pub fn new(budget: Duration) -> Self {
+        Self {
+            budget,
+            abort_before_submit: false,
+        }
+    }
+
+    pub fn abort_before_submit(mut self) -> Self {
+        self.abort_before_submit = true;
+        self
+    }
+
+    /// Called once the payload is signed and before it is handed to the transport
+    pub fn check_before_submit(&self, timings: &StageTimings) -> Result<()> {
+        if self.abort_before_submit && timings.before_submit() > self.budget {
+            return Err(ProtocolError::coerce_static_from_str(&format!(
+                "Latency budget of {:?} exceeded before submission (construction {:?}, signing {:?})",
+                self.budget, timings.construction, timings.signing
+            )));
+        }
+        Ok(())
+    }
+
+    pub fn is_exceeded(&self, timings: &StageTimings) -> bool {
+        timings.total() > self.budget
+    }
+}
+
+/// Time spent in each stage of running an order request
+#[derive(Clone, Copy, Debug, Default)]
+pub struct StageTimings {
+    /// Market lookup, amount/rate conversion and payload nonces
+    pub construction: Duration,
+    /// Blockchain payload and request signing
+    pub signing: Duration,
+    /// Round trip to the exchange, including decoding of the response
+    pub transport: Duration,
+}
+
+impl StageTimings {
+    pub fn before_submit(&self) -> Duration {
+        self.construction + self.signing
+    }
+
+    pub fn total(&self) -> Duration {
+        self.construction + self.signing + self.transport
+    }
+}
+
+/// One metric of a `Server-Timing` response header, e.g. `match;dur=1.2;desc="Matching"`
+#[derive(Clone, Debug, PartialEq)]
+pub struct ServerTimingMetric {
+    pub name: String,
+    pub duration: Option<Duration>,
+    pub description: Option<String>,
+}
+
+impl ServerTimingMetric {
+    /// Parse the metrics of a `Server-Timing` header value. Malformed parameters are ignored.
+    pub fn parse_header(value: &str) -> Vec<Self> {
+        split_unquoted(value, ',')
+            .into_iter()
+            .filter_map(|metric| {
+                let mut parts = split_unquoted(metric, ';').into_iter();
+                let name = parts.next()?.trim();
+                if name.is_empty() {
+                    return None;
+                }
+                let mut parsed = Self {
+                    name: name.to_string(),
+                    duration: None,
+                    description: None,
+                };
+                for param in parts {
+                    let (key, value) = match param.find('=') {
+                        Some(i) => (param[..i].trim(), param[i + 1..].trim().trim_matches('"')),
+                        None => continue,
+                    };
+                    match key.to_ascii_lowercase().as_str() {
+                        "dur" => {
+                            parsed.duration = value
+                                .parse::<f64>()
+                                .ok()
+                                .filter(|ms| ms.is_finite() && *ms >= 0.0)
+                                .map(|ms| Duration::from_secs_f64(ms / 1000.0))
+                        }
+                        "desc" => parsed.description = Some(value.to_string()),
+                        _ => {}
+                    }
+                }
+                Some(parsed)
+            })
+            .collect()
+    }
+}
+
+/// Split on `separator` outside of double quoted strings
+fn split_unquoted(value: &str, separator: char) -> Vec<&str> {
+    let mut parts = Vec::new();
+    let mut quoted = false;
+    let mut start = 0;
+    for (i, c) in value.char_indices() {
+        if c == '"' {
+            quoted = !quoted;
+        } else if c == separator && !quoted {
+            parts.push(&value[start..i]);
+            start = i + c.len_utf8();
+        }
+    }
+    parts.push(&value[start..]);
+    parts
+}
+
+/// Timing of one round trip to the exchange
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ResponseTiming {
+    /// From sending the request until its response was decoded
+    pub round_trip: Duration,
+    /// Metrics the exchange reported for the request, if any
+    pub server: Vec<ServerTimingMetric>,
+}
+
+impl ResponseTiming {
+    /// Time spent on the exchange: the longest reported metric, as metrics may nest
+    pub fn server_time(&self) -> Option<Duration> {
+        self.server.iter().filter_map(|metric| metric.duration).max()
+    }
+
+    /// Round trip time not accounted for by the exchange, i.e. network and client side decoding
+    pub fn network_time(&self) -> Option<Duration> {
+        self.server_time()
+            .map(|server| self.round_trip.checked_sub(server).unwrap_or_default())
+    }
+}
+
+/// A response together with the timing of the exchange round trip that produced it
+#[derive(Debug)]
+pub struct TimedResponse<T> {
+    pub response: ResponseOrError<T>,
+    /// Timing of the last request the pipeline sent, `None` if it sent nothing
+    pub timing: Option<ResponseTiming>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{LatencyBudget, ResponseTiming, ServerTimingMetric, StageTimings};
+    use std::time::Duration;
+
+    #[test]
+    fn abort_only_when_requested() {
+        let timings = StageTimings {
+            construction: Duration::from_millis(3),
+            signing: Duration::from_millis(4),
+            transport: Duration::from_millis(0),
+        };
+        let budget = LatencyBudget::new(Duration::from_millis(5));
+        assert!(budget.check_before_submit(&timings).is_ok());
+        assert!(budget.is_exceeded(&timings));
+        assert!(budget.abort_before_submit().check_before_submit(&timings).is_err());
+    }
+
+    #[test]
+    fn parse_server_timing() {
+        let metrics =
+            ServerTimingMetric::parse_header(r#"total;dur=12.5, match;desc="a, b";dur=2, cache"#);
+        assert_eq!(metrics.len(), 3);
+        assert_eq!(metrics[0].duration, Some(Duration::from_micros(12_500)));
+        assert_eq!(metrics[1].name, "match");
+        assert_eq!(metrics[1].description.as_deref(), Some("a, b"));
+        assert_eq!(metrics[2].duration, None);
+
+        let timing = ResponseTiming {
+            round_trip: Duration::from_millis(20),
+            server: metrics,
+        };
+        assert_eq!(timing.server_time(), Some(Duration::from_micros(12_500)));
+        assert_eq!(timing.network_time(), Some(Duration::from_micros(7_500)));
+    }
+}
@@ -0,0 +1,10 @@
+//! List security relevant activity of the account, like logins, API key usage and withdrawal
+//! attempts, so programmatic access can be monitored from the same client
+
+mod request;
+mod response;
+mod types;
+
+pub use types::{
+    AccountActivity, ActivityKind, ListAccountActivityRequest, ListAccountActivityResponse,
+};
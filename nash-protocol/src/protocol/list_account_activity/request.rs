@@ -0,0 +1,15 @@
+use super::types::ListAccountActivityRequest;
+use crate::graphql;
+use crate::graphql::list_account_activity;
+use crate::protocol::query_overrides::build_query;
+
+impl ListAccountActivityRequest {
+    pub fn make_query(&self) -> graphql_client::QueryBody<list_account_activity::Variables> {
+        build_query::<graphql::ListAccountActivity>(list_account_activity::Variables {
+            pagination_options: Some(list_account_activity::PaginationOptions {
+                page: self.page,
+                page_size: self.page_size,
+            }),
+        })
+    }
+}
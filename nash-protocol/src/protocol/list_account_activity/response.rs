@@ -0,0 +1,66 @@
+use super::types::{AccountActivity, ActivityKind, ListAccountActivityResponse};
+use crate::errors::{ProtocolError, Result};
+use crate::graphql::list_account_activity;
+use crate::types::timestamp::parse_timestamp;
+use std::convert::TryFrom;
+
+impl From<&str> for ActivityKind {
+    fn from(kind: &str) -> Self {
+        let lower = kind.to_lowercase();
+        if lower.contains("login") || lower.contains("sign_in") {
+            Self::Login
+        } else if lower.contains("api") {
+            Self::ApiKey
+        } else if lower.contains("withdraw") {
+            Self::Withdrawal
+        } else {
+            Self::Other(kind.to_string())
+        }
+    }
+}
+
+impl TryFrom<list_account_activity::ResponseData> for ListAccountActivityResponse {
+    type Error = ProtocolError;
+
+    fn try_from(response: list_account_activity::ResponseData) -> Result<Self> {
+        let activity = response.list_activity;
+        let events = activity
+            .entries
+            .into_iter()
+            .map(|entry| {
+                Ok(AccountActivity {
+                    kind: ActivityKind::from(entry.type_.as_str()),
+                    status: entry.status,
+                    at: parse_timestamp(&entry.inserted_at)?,
+                    ip_address: entry.ip_address,
+                    browser: entry.browser,
+                    platform: entry.platform,
+                    city: entry.city,
+                    country_code: entry.country_code,
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+        Ok(Self {
+            events,
+            page: activity.metadata.page_number,
+            total_pages: activity.metadata.total_pages,
+            total_events: activity.metadata.total_entries,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::types::ActivityKind;
+
+    #[test]
+    fn activity_kinds_are_classified() {
+        assert_eq!(ActivityKind::from("LOGIN_SUCCESS"), ActivityKind::Login);
+        assert_eq!(ActivityKind::from("api_key_used"), ActivityKind::ApiKey);
+        assert_eq!(ActivityKind::from("WITHDRAWAL"), ActivityKind::Withdrawal);
+        assert_eq!(
+            ActivityKind::from("PASSWORD_CHANGE"),
+            ActivityKind::Other("PASSWORD_CHANGE".to_string())
+        );
+    }
+}
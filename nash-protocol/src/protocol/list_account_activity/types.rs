@@ -0,0 +1,69 @@
+use super::super::{
+    serializable_to_json, try_response_from_json, NashProtocol, ResponseOrError, State,
+};
+use crate::errors::Result;
+use crate::graphql::list_account_activity;
+use crate::types::timestamp::Timestamp;
+use async_trait::async_trait;
+use std::sync::Arc;
+use tokio::sync::RwLock;
+
+/// List activity of the account, newest first. Pages start at 1; without a page the exchange
+/// returns its first page at its default size.
+#[derive(Clone, Debug, Default)]
+pub struct ListAccountActivityRequest {
+    pub page: Option<i64>,
+    pub page_size: Option<i64>,
+}
+
+/// What kind of activity an event is. Kinds the client doesn't know keep the name the
+/// exchange gave them.
+#[derive(Clone, Debug, PartialEq)]
+pub enum ActivityKind {
+    Login,
+    ApiKey,
+    Withdrawal,
+    Other(String),
+}
+
+/// An access to the account, with where it came from
+#[derive(Clone, Debug)]
+pub struct AccountActivity {
+    pub kind: ActivityKind,
+    /// e.g. whether a login succeeded, as given by the exchange
+    pub status: String,
+    pub at: Timestamp,
+    pub ip_address: String,
+    pub browser: String,
+    pub platform: String,
+    pub city: Option<String>,
+    pub country_code: String,
+}
+
+#[derive(Clone, Debug)]
+pub struct ListAccountActivityResponse {
+    pub events: Vec<AccountActivity>,
+    pub page: Option<i64>,
+    pub total_pages: Option<i64>,
+    pub total_events: Option<i64>,
+}
+
+#[async_trait]
+impl NashProtocol for ListAccountActivityRequest {
+    type Response = ListAccountActivityResponse;
+
+    async fn graphql(&self, _state: Arc<RwLock<State>>) -> Result<serde_json::Value> {
+        let query = self.make_query();
+        serializable_to_json(&query)
+    }
+
+    async fn response_from_json(
+        &self,
+        response: serde_json::Value,
+        _state: Arc<RwLock<State>>,
+    ) -> Result<ResponseOrError<Self::Response>> {
+        try_response_from_json::<ListAccountActivityResponse, list_account_activity::ResponseData>(
+            response,
+        )
+    }
+}
@@ -2,7 +2,7 @@ use super::types::ListAccountBalancesRequest;
 use crate::graphql;
 use crate::graphql::list_account_balances;
 
-use graphql_client::GraphQLQuery;
+use crate::protocol::query_overrides::build_query;
 
 impl ListAccountBalancesRequest {
     pub fn make_query(&self) -> graphql_client::QueryBody<list_account_balances::Variables> {
@@ -12,6 +12,6 @@ impl ListAccountBalancesRequest {
                 filter: None,
             },
         };
-        graphql::ListAccountBalances::build_query(list_balances)
+        build_query::<graphql::ListAccountBalances>(list_balances)
     }
 }
@@ -0,0 +1,10 @@
+//! List deposits, withdrawals and transfers of the current account, e.g. to reconstruct its
+//! ledger together with its trades
+
+mod request;
+mod response;
+mod types;
+
+pub use types::{
+    ListAccountMovementsRequest, ListAccountMovementsResponse, Movement, MovementType,
+};
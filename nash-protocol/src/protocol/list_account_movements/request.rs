@@ -0,0 +1,73 @@
+use super::super::withdraw::MovementStatus;
+use super::super::{general_canonical_string, RequestPayloadSignature};
+use super::types::{ListAccountMovementsRequest, MovementType};
+use crate::errors::{ProtocolError, Result};
+use crate::graphql;
+use crate::graphql::list_movements;
+use crate::utils::current_time_as_i64;
+use crate::protocol::query_overrides::build_query;
+
+use super::super::signer::Signer;
+
+impl ListAccountMovementsRequest {
+    /// Create ListMovements GraphQL request
+    pub fn make_query(
+        &self,
+        signer: &Signer,
+    ) -> Result<graphql_client::QueryBody<list_movements::Variables>> {
+        let mut params = list_movements::Variables {
+            payload: list_movements::ListMovementsParams {
+                atomic: None,
+                currency: self.asset.map(|asset| asset.name().to_string()),
+                status: self.status.map(Into::into),
+                timestamp: current_time_as_i64(),
+                type_: self.movement_type.map(Into::into),
+            },
+            signature: RequestPayloadSignature::empty().into(),
+        };
+        let sig_payload = list_movements_canonical_string(&params)?;
+        params.signature = signer.sign_canonical_string(&sig_payload)?.into();
+        Ok(build_query::<graphql::ListMovements>(params))
+    }
+}
+
+impl From<RequestPayloadSignature> for list_movements::Signature {
+    fn from(sig: RequestPayloadSignature) -> Self {
+        list_movements::Signature {
+            signed_digest: sig.signed_digest,
+            public_key: sig.public_key,
+        }
+    }
+}
+
+impl From<MovementStatus> for list_movements::MovementStatus {
+    fn from(status: MovementStatus) -> Self {
+        match status {
+            MovementStatus::Created => Self::CREATED,
+            MovementStatus::Pending => Self::PENDING,
+            MovementStatus::Completed => Self::COMPLETED,
+            MovementStatus::Failed => Self::FAILED,
+        }
+    }
+}
+
+impl From<MovementType> for list_movements::MovementType {
+    fn from(movement_type: MovementType) -> Self {
+        match movement_type {
+            MovementType::Deposit => Self::DEPOSIT,
+            MovementType::Withdrawal => Self::WITHDRAWAL,
+            MovementType::Transfer => Self::TRANSFER,
+        }
+    }
+}
+
+/// Generate canonical payload string for list movements GraphQL request
+pub fn list_movements_canonical_string(variables: &list_movements::Variables) -> Result<String> {
+    let serialized = serde_json::to_value(variables)
+        .map_err(|_| ProtocolError("Failed to serialize movements query into canonical string"))?;
+    Ok(general_canonical_string(
+        "list_movements".to_string(),
+        serialized,
+        vec![],
+    ))
+}
@@ -0,0 +1,198 @@
+use super::super::withdraw::MovementStatus;
+use super::super::ResponseOrError;
+use super::types::{
+    ListAccountMovementsRequest, ListAccountMovementsResponse, Movement, MovementType,
+};
+use crate::errors::{ProtocolError, Result};
+use crate::graphql::list_movements;
+use crate::types::timestamp::parse_timestamp;
+use crate::types::Blockchain;
+use bigdecimal::BigDecimal;
+use std::convert::{TryFrom, TryInto};
+use std::str::FromStr;
+
+impl ListAccountMovementsRequest {
+    pub fn response_from_graphql(
+        &self,
+        response: ResponseOrError<list_movements::ResponseData>,
+    ) -> Result<ResponseOrError<ListAccountMovementsResponse>> {
+        match response {
+            ResponseOrError::Response(data) => {
+                let movements = data
+                    .data
+                    .list_movements
+                    .into_iter()
+                    .map(movement_from_data)
+                    .collect::<Result<Vec<Movement>>>()?;
+                Ok(ResponseOrError::from_data(self.page_of(movements)?))
+            }
+            ResponseOrError::Error(error) => Ok(ResponseOrError::Error(error)),
+        }
+    }
+
+    /// Cut the page this request asks for out of all matching `movements`
+    fn page_of(&self, mut movements: Vec<Movement>) -> Result<ListAccountMovementsResponse> {
+        // Newest first, movements the exchange hasn't received yet before all others
+        movements.sort_by(|a, b| {
+            (b.received_at.is_none(), b.received_at, &b.id).cmp(&(
+                a.received_at.is_none(),
+                a.received_at,
+                &a.id,
+            ))
+        });
+        let start = match &self.before {
+            Some(before) => movements
+                .iter()
+                .position(|movement| &movement.id == before)
+                .map(|position| position + 1)
+                .ok_or(ProtocolError(
+                    "Movement to list movements before was not found",
+                ))?,
+            None => 0,
+        };
+        let mut movements = movements.split_off(start);
+        let mut next_page = None;
+        if let Some(limit) = self.limit {
+            let limit = limit.max(0) as usize;
+            if movements.len() > limit {
+                movements.truncate(limit);
+                next_page = movements.last().map(|movement| movement.id.clone());
+            }
+        }
+        Ok(ListAccountMovementsResponse {
+            movements,
+            next_page,
+        })
+    }
+}
+
+fn movement_from_data(data: list_movements::ListMovementsListMovements) -> Result<Movement> {
+    let fee = match &data.fee {
+        Some(fee) => Some(BigDecimal::from_str(fee)?),
+        None => None,
+    };
+    let received_at = match &data.received_at {
+        Some(received_at) => Some(parse_timestamp(received_at)?),
+        None => None,
+    };
+    Ok(Movement {
+        movement_type: (&data.type_).try_into()?,
+        status: (&data.status).try_into()?,
+        quantity: BigDecimal::from_str(&data.quantity.amount)?,
+        blockchain: (&data.blockchain).into(),
+        id: data.id,
+        currency: data.currency,
+        fee,
+        address: data.address,
+        target_address: data.target_address,
+        transaction_hash: data.transaction_hash,
+        confirmations: data.confirmations,
+        received_at,
+    })
+}
+
+impl From<&list_movements::Blockchain> for Blockchain {
+    fn from(chain: &list_movements::Blockchain) -> Self {
+        match chain {
+            list_movements::Blockchain::ETH => Self::Ethereum,
+            list_movements::Blockchain::NEO => Self::NEO,
+            list_movements::Blockchain::BTC => Self::Bitcoin,
+            list_movements::Blockchain::Other(_) => {
+                panic!("Nash API is serving non-supported blockchain")
+            }
+        }
+    }
+}
+
+impl TryFrom<&list_movements::MovementStatus> for MovementStatus {
+    type Error = ProtocolError;
+
+    fn try_from(status: &list_movements::MovementStatus) -> Result<Self> {
+        match status {
+            list_movements::MovementStatus::CREATED => Ok(Self::Created),
+            list_movements::MovementStatus::PENDING => Ok(Self::Pending),
+            list_movements::MovementStatus::COMPLETED => Ok(Self::Completed),
+            list_movements::MovementStatus::FAILED => Ok(Self::Failed),
+            list_movements::MovementStatus::Other(_) => {
+                Err(ProtocolError("Unknown movement status"))
+            }
+        }
+    }
+}
+
+impl TryFrom<&list_movements::MovementType> for MovementType {
+    type Error = ProtocolError;
+
+    fn try_from(movement_type: &list_movements::MovementType) -> Result<Self> {
+        match movement_type {
+            list_movements::MovementType::DEPOSIT => Ok(Self::Deposit),
+            list_movements::MovementType::WITHDRAWAL => Ok(Self::Withdrawal),
+            list_movements::MovementType::TRANSFER => Ok(Self::Transfer),
+            list_movements::MovementType::Other(_) => Err(ProtocolError("Unknown movement type")),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::types::{ListAccountMovementsRequest, Movement, MovementType};
+    use super::MovementStatus;
+    use crate::types::timestamp::parse_timestamp;
+    use crate::types::Blockchain;
+    use bigdecimal::BigDecimal;
+
+    fn movement(id: &str, received_at: Option<&str>) -> Movement {
+        Movement {
+            id: id.to_string(),
+            movement_type: MovementType::Deposit,
+            status: MovementStatus::Completed,
+            currency: "eth".to_string(),
+            quantity: BigDecimal::from(1),
+            fee: None,
+            blockchain: Blockchain::Ethereum,
+            address: None,
+            target_address: None,
+            transaction_hash: None,
+            confirmations: None,
+            received_at: received_at.map(|time| parse_timestamp(time).unwrap()),
+        }
+    }
+
+    fn ids(movements: &[Movement]) -> Vec<&str> {
+        movements
+            .iter()
+            .map(|movement| movement.id.as_str())
+            .collect()
+    }
+
+    #[test]
+    fn pages_are_cut_newest_first() {
+        let movements = vec![
+            movement("old", Some("2020-01-01T00:00:00Z")),
+            movement("new", Some("2020-03-01T00:00:00Z")),
+            movement("creating", None),
+            movement("mid", Some("2020-02-01T00:00:00Z")),
+        ];
+        let request = ListAccountMovementsRequest {
+            limit: Some(2),
+            ..Default::default()
+        };
+        let first = request.page_of(movements.clone()).unwrap();
+        assert_eq!(ids(&first.movements), vec!["creating", "new"]);
+        assert_eq!(first.next_page.as_deref(), Some("new"));
+
+        let request = ListAccountMovementsRequest {
+            before: first.next_page,
+            ..request
+        };
+        let last = request.page_of(movements.clone()).unwrap();
+        assert_eq!(ids(&last.movements), vec!["mid", "old"]);
+        assert_eq!(last.next_page, None);
+
+        let request = ListAccountMovementsRequest {
+            before: Some("gone".to_string()),
+            ..request
+        };
+        assert!(request.page_of(movements).is_err());
+    }
+}
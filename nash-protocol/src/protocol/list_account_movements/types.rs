@@ -0,0 +1,114 @@
+use super::super::withdraw::MovementStatus;
+use super::super::{
+    json_to_type_or_error, serializable_to_json, NashProtocol, Page, Paginated, ResponseOrError,
+    State,
+};
+use crate::errors::Result;
+use crate::types::{Asset, Blockchain, Timestamp};
+use async_trait::async_trait;
+use bigdecimal::BigDecimal;
+use std::sync::Arc;
+use tokio::sync::RwLock;
+
+/// List movements of the current account, newest first, filtered by several optional fields.
+/// The exchange returns all matching movements at once, so pages are cut from that list:
+/// `before` is the id of the last movement of the previous page.
+#[derive(Clone, Debug, Default)]
+pub struct ListAccountMovementsRequest {
+    pub asset: Option<Asset>,
+    pub status: Option<MovementStatus>,
+    pub movement_type: Option<MovementType>,
+    /// page before if using pagination
+    pub before: Option<String>,
+    /// max movements to return
+    pub limit: Option<i64>,
+}
+
+/// Kind of movement of funds in or out of the account
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MovementType {
+    Deposit,
+    Withdrawal,
+    Transfer,
+}
+
+/// A movement of funds in or out of the account
+#[derive(Clone, Debug)]
+pub struct Movement {
+    pub id: String,
+    pub movement_type: MovementType,
+    pub status: MovementStatus,
+    /// Symbol of the moved asset, kept as is so assets unknown to the client still show up
+    pub currency: String,
+    pub quantity: BigDecimal,
+    pub fee: Option<BigDecimal>,
+    pub blockchain: Blockchain,
+    pub address: Option<String>,
+    pub target_address: Option<String>,
+    /// Hash of the blockchain transaction, once there is one
+    pub transaction_hash: Option<String>,
+    pub confirmations: Option<i64>,
+    /// When the exchange saw the movement, not set for movements still being created
+    pub received_at: Option<Timestamp>,
+}
+
+impl Movement {
+    pub fn asset(&self) -> Result<Asset> {
+        Asset::from_str(&self.currency)
+    }
+}
+
+/// List of movements and optional link to the next page of data
+#[derive(Clone, Debug)]
+pub struct ListAccountMovementsResponse {
+    pub movements: Vec<Movement>,
+    pub next_page: Option<String>,
+}
+
+impl Paginated for ListAccountMovementsRequest {
+    type Item = Movement;
+
+    fn before(&self) -> Option<&str> {
+        self.before.as_deref()
+    }
+
+    fn limit(&self) -> Option<i64> {
+        self.limit
+    }
+
+    fn page(&self, before: Option<String>, limit: Option<i64>) -> Self {
+        Self {
+            before,
+            limit,
+            ..self.clone()
+        }
+    }
+
+    fn into_page(response: ListAccountMovementsResponse) -> Page<Movement> {
+        Page {
+            items: response.movements,
+            next_page: response.next_page,
+        }
+    }
+}
+
+/// Implement protocol bindings for ListAccountMovementsRequest
+#[async_trait]
+impl NashProtocol for ListAccountMovementsRequest {
+    type Response = ListAccountMovementsResponse;
+
+    async fn graphql(&self, state: Arc<RwLock<State>>) -> Result<serde_json::Value> {
+        let state = state.read().await;
+        let query = self.make_query(state.signer()?)?;
+        serializable_to_json(&query)
+    }
+
+    async fn response_from_json(
+        &self,
+        response: serde_json::Value,
+        _state: Arc<RwLock<State>>,
+    ) -> Result<ResponseOrError<Self::Response>> {
+        let as_graphql = json_to_type_or_error(response)?;
+        self.response_from_graphql(as_graphql)
+    }
+}
@@ -1,9 +1,10 @@
+use crate::types::timestamp::format_timestamp;
 use super::types::ListAccountOrdersRequest;
 use crate::graphql;
 use crate::graphql::list_account_orders;
 use crate::types::{BuyOrSell, OrderStatus, OrderType};
 
-use graphql_client::GraphQLQuery;
+use crate::protocol::query_overrides::build_query;
 
 impl ListAccountOrdersRequest {
     pub fn make_query(&self) -> graphql_client::QueryBody<list_account_orders::Variables> {
@@ -21,11 +22,11 @@ impl ListAccountOrdersRequest {
                     .order_type
                     .as_ref()
                     .map(|x| x.iter().map(|x| Some(x.clone().into())).collect()),
-                range_start: self.range.as_ref().map(|x| format!("{:?}", x.start)),
-                range_stop: self.range.as_ref().map(|x| format!("{:?}", x.stop)),
+                range_start: self.range.as_ref().map(|x| format_timestamp(&x.start)),
+                range_stop: self.range.as_ref().map(|x| format_timestamp(&x.stop)),
             },
         };
-        graphql::ListAccountOrders::build_query(get_order)
+        build_query::<graphql::ListAccountOrders>(get_order)
     }
 }
 
@@ -1,11 +1,12 @@
 use super::types::ListAccountOrdersResponse;
 use crate::errors::{ProtocolError, Result};
+use std::convert::{TryFrom, TryInto};
 use crate::graphql::list_account_orders;
 use crate::types::{
     AccountTradeSide, BuyOrSell, Order, OrderCancellationPolicy, OrderCancellationReason,
     OrderStatus, OrderType, Trade,
 };
-use chrono::{DateTime, Utc};
+use crate::types::timestamp::parse_timestamp;
 use bigdecimal::BigDecimal;
 use std::str::FromStr;
 use crate::protocol::traits::TryFromState;
@@ -34,8 +35,7 @@ impl TryFromState<list_account_orders::ResponseData> for ListAccountOrdersRespon
                 Some(price) => Some(BigDecimal::from_str(&price.amount)?),
                 None => None,
             };
-            let placed_at = DateTime::<Utc>::from_str(&order_data.placed_at)
-                .map_err(|_| ProtocolError("Could not convert value to DateTime"))?;
+            let placed_at = parse_timestamp(&order_data.placed_at)?;
             let mut trades = Vec::new();
             for trade_data in order_data.trades.as_ref().unwrap() {
                 let trade_data = trade_data.as_ref().unwrap();
@@ -57,8 +57,7 @@ impl TryFromState<list_account_orders::ResponseData> for ListAccountOrdersRespon
                     maker_order_id: trade_data.maker_order_id.clone(),
                     account_side: (&trade_data.account_side).into(),
                     id: trade_data.id.clone(),
-                    executed_at: DateTime::<Utc>::from_str(&trade_data.executed_at)
-                        .map_err(|_| ProtocolError("Could not convert value to DateTime"))?,
+                    executed_at: parse_timestamp(&trade_data.executed_at)?,
                     limit_price,
                     direction: (&trade_data.direction).into(),
                 })
@@ -74,7 +73,7 @@ impl TryFromState<list_account_orders::ResponseData> for ListAccountOrdersRespon
                 stop_price,
                 placed_at,
                 trades,
-                cancellation_policy: (&order_data).into(),
+                cancellation_policy: (&order_data).try_into()?,
                 cancellation_reason: order_data.cancellation_reason.as_ref().map(|x| x.into()),
                 buy_or_sell: order_data.buy_or_sell.into(),
                 order_type: order_data.type_.into(),
@@ -143,12 +142,14 @@ impl From<&list_account_orders::OrderCancellationReason> for OrderCancellationRe
     }
 }
 
-impl From<&list_account_orders::ListAccountOrdersListAccountOrdersOrders>
+impl TryFrom<&list_account_orders::ListAccountOrdersListAccountOrdersOrders>
     for Option<OrderCancellationPolicy>
 {
-    fn from(order: &list_account_orders::ListAccountOrdersListAccountOrdersOrders) -> Self {
+    type Error = ProtocolError;
+
+    fn try_from(order: &list_account_orders::ListAccountOrdersListAccountOrdersOrders) -> Result<Self> {
         if let Some(cancellation_policy) = &order.cancellation_policy {
-            Some(match cancellation_policy {
+            Ok(Some(match cancellation_policy {
                 list_account_orders::OrderCancellationPolicy::FILL_OR_KILL => {
                     OrderCancellationPolicy::FillOrKill
                 }
@@ -156,18 +157,20 @@ impl From<&list_account_orders::ListAccountOrdersListAccountOrdersOrders>
                     OrderCancellationPolicy::GoodTilCancelled
                 }
                 list_account_orders::OrderCancellationPolicy::GOOD_TIL_TIME => {
-                    // This unwrap is safe
-                    let cancel_at =
-                        DateTime::<Utc>::from_str(&order.cancel_at.as_ref().unwrap()).unwrap();
+                    let cancel_at = order
+                        .cancel_at
+                        .as_ref()
+                        .ok_or(ProtocolError("GoodTilTime order has no cancellation time"))?;
+                    let cancel_at = parse_timestamp(cancel_at)?;
                     OrderCancellationPolicy::GoodTilTime(cancel_at)
                 }
                 list_account_orders::OrderCancellationPolicy::IMMEDIATE_OR_CANCEL => {
                     OrderCancellationPolicy::ImmediateOrCancel
                 }
                 _ => panic!("Unsupported OrderCancellationPolicy"),
-            })
+            }))
         } else {
-            None
+            Ok(None)
         }
     }
 }
@@ -1,8 +1,9 @@
 use super::super::{
-    serializable_to_json, try_response_with_state_from_json, NashProtocol, ResponseOrError, State,
+    Page, Paginated, serializable_to_json, try_response_with_state_from_json, NashProtocol, ResponseOrError, State,
 };
-use crate::errors::Result;
+use crate::errors::{ProtocolError, Result};
 use crate::graphql::list_account_orders;
+use crate::types::timestamp::Timestamp;
 use crate::types::{BuyOrSell, DateTimeRange, Order, OrderStatus, OrderType};
 use super::super::list_markets::ListMarketsRequest;
 use super::super::hooks::{ProtocolHook, NashProtocolRequest};
@@ -28,7 +29,17 @@ use std::sync::Arc;
 ///   }) 
 /// };
 /// ```
-#[derive(Clone, Debug)]
+/// Or built from the default request, which lists all orders, with the filter methods:
+/// ```
+/// use nash_protocol::protocol::list_account_orders::ListAccountOrdersRequest;
+/// use nash_protocol::types::{BuyOrSell, OrderStatus, OrderType};
+/// let request = ListAccountOrdersRequest::default()
+///     .with_market("eth_usdc")
+///     .with_statuses(&[OrderStatus::Open, OrderStatus::Pending])
+///     .with_side(BuyOrSell::Sell)
+///     .with_order_types(&[OrderType::Limit, OrderType::StopLimit]);
+/// ```
+#[derive(Clone, Debug, Default)]
 pub struct ListAccountOrdersRequest {
     pub market: Option<String>,
     /// page before if using pagination
@@ -41,6 +52,67 @@ pub struct ListAccountOrdersRequest {
     pub range: Option<DateTimeRange>,
 }
 
+impl ListAccountOrdersRequest {
+    pub fn with_market(mut self, market: &str) -> Self {
+        self.market = Some(market.to_string());
+        self
+    }
+
+    /// Orders in any of `statuses`. An empty set removes the filter.
+    pub fn with_statuses(mut self, statuses: &[OrderStatus]) -> Self {
+        self.status = unique(statuses);
+        self
+    }
+
+    pub fn with_side(mut self, buy_or_sell: BuyOrSell) -> Self {
+        self.buy_or_sell = Some(buy_or_sell);
+        self
+    }
+
+    /// Orders of any of `order_types`. An empty set removes the filter.
+    pub fn with_order_types(mut self, order_types: &[OrderType]) -> Self {
+        self.order_type = unique(order_types);
+        self
+    }
+
+    /// Orders placed from `start` up to `stop`
+    pub fn placed_between(mut self, start: Timestamp, stop: Timestamp) -> Result<Self> {
+        if start > stop {
+            return Err(ProtocolError("Order time range starts after it stops"));
+        }
+        self.range = Some(DateTimeRange { start, stop });
+        Ok(self)
+    }
+
+    /// Whether `order` passes the filters of this request, e.g. to apply them to order
+    /// updates from a subscription
+    pub fn matches(&self, order: &Order) -> bool {
+        self.market.as_ref().map_or(true, |market| market == &order.market)
+            && self.buy_or_sell.map_or(true, |side| side == order.buy_or_sell)
+            && self
+                .status
+                .as_ref()
+                .map_or(true, |statuses| statuses.contains(&order.status))
+            && self
+                .order_type
+                .as_ref()
+                .map_or(true, |types| types.contains(&order.order_type))
+            && self.range.as_ref().map_or(true, |range| {
+                range.start <= order.placed_at && order.placed_at <= range.stop
+            })
+    }
+}
+
+fn unique<T: Copy + PartialEq>(values: &[T]) -> Option<Vec<T>> {
+    let mut unique = Vec::new();
+    for value in values {
+        if !unique.contains(value) {
+            unique.push(*value);
+        }
+    }
+    Some(unique).filter(|unique| !unique.is_empty())
+}
+
 /// List of orders that meet critera of the request. Includes an optional paging field
 /// if more orders exist.
 #[derive(Debug)]
@@ -49,6 +121,39 @@ pub struct ListAccountOrdersResponse {
     pub next_page: Option<String>,
 }
 
+impl Paginated for ListAccountOrdersRequest {
+    type Item = Order;
+
+    fn before(&self) -> Option<&str> {
+        self.before.as_deref()
+    }
+
+    fn limit(&self) -> Option<i64> {
+        self.limit
+    }
+
+    fn page(&self, before: Option<String>, limit: Option<i64>) -> Self {
+        Self {
+            before,
+            limit,
+            ..self.clone()
+        }
+    }
+
+    fn into_page(response: ListAccountOrdersResponse) -> Page<Order> {
+        Page {
+            items: response.orders,
+            next_page: response.next_page,
+        }
+    }
+}
+
+impl From<ListAccountOrdersResponse> for Vec<Order> {
+    fn from(response: ListAccountOrdersResponse) -> Self {
+        response.orders
+    }
+}
+
 #[async_trait]
 impl NashProtocol for ListAccountOrdersRequest {
     type Response = ListAccountOrdersResponse;
@@ -79,3 +184,60 @@ impl NashProtocol for ListAccountOrdersRequest {
         Ok(Some(hooks))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::ListAccountOrdersRequest;
+    use crate::types::timestamp::parse_timestamp;
+    use crate::types::{BuyOrSell, Order, OrderStatus, OrderType};
+    use bigdecimal::BigDecimal;
+
+    fn order(status: OrderStatus, placed_at: &str) -> Order {
+        Order {
+            id: "1".to_string(),
+            client_order_id: None,
+            amount_placed: BigDecimal::from(1),
+            amount_remaining: BigDecimal::from(1),
+            amount_executed: BigDecimal::from(0),
+            limit_price: Some(BigDecimal::from(100)),
+            stop_price: None,
+            placed_at: parse_timestamp(placed_at).unwrap(),
+            buy_or_sell: BuyOrSell::Sell,
+            cancellation_policy: None,
+            cancellation_reason: None,
+            market: "eth_usdc".to_string(),
+            order_type: OrderType::Limit,
+            status,
+            trades: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn filters_match_orders_like_the_exchange() {
+        let start = parse_timestamp("2021-01-01T00:00:00Z").unwrap();
+        let stop = parse_timestamp("2021-01-02T00:00:00Z").unwrap();
+        let request = ListAccountOrdersRequest::default()
+            .with_market("eth_usdc")
+            .with_statuses(&[OrderStatus::Open, OrderStatus::Open, OrderStatus::Pending])
+            .with_side(BuyOrSell::Sell)
+            .with_order_types(&[])
+            .placed_between(start, stop)
+            .unwrap();
+        assert_eq!(
+            request.status,
+            Some(vec![OrderStatus::Open, OrderStatus::Pending])
+        );
+        assert_eq!(request.order_type, None);
+
+        assert!(request.matches(&order(OrderStatus::Open, "2021-01-01T12:00:00Z")));
+        assert!(!request.matches(&order(OrderStatus::Filled, "2021-01-01T12:00:00Z")));
+        assert!(!request.matches(&order(OrderStatus::Open, "2021-01-03T00:00:00Z")));
+        assert!(!request
+            .clone()
+            .with_side(BuyOrSell::Buy)
+            .matches(&order(OrderStatus::Open, "2021-01-01T12:00:00Z")));
+        assert!(ListAccountOrdersRequest::default()
+            .placed_between(stop, start)
+            .is_err());
+    }
+}
@@ -1,8 +1,9 @@
+use crate::types::timestamp::format_timestamp;
 use super::types::ListAccountTradesRequest;
 use crate::graphql;
 use crate::graphql::list_account_trades;
 
-use graphql_client::GraphQLQuery;
+use crate::protocol::query_overrides::build_query;
 
 impl ListAccountTradesRequest {
     pub fn make_query(&self) -> graphql_client::QueryBody<list_account_trades::Variables> {
@@ -11,10 +12,10 @@ impl ListAccountTradesRequest {
                 before: self.before.clone(),
                 limit: self.limit,
                 market_name: self.market.clone(),
-                range_start: self.range.as_ref().map(|x| format!("{:?}", x.start)),
-                range_stop: self.range.as_ref().map(|x| format!("{:?}", x.stop)),
+                range_start: self.range.as_ref().map(|x| format_timestamp(&x.start)),
+                range_stop: self.range.as_ref().map(|x| format_timestamp(&x.stop)),
             },
         };
-        graphql::ListAccountTrades::build_query(list_account_trades)
+        build_query::<graphql::ListAccountTrades>(list_account_trades)
     }
 }
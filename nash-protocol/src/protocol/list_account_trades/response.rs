@@ -1,12 +1,13 @@
-use super::types::ListAccountTradesResponse;
-use crate::errors::{ProtocolError, Result};
+use super::types::{ListAccountTradesRequest, ListAccountTradesResponse};
+use crate::errors::Result;
 use crate::graphql::list_account_trades;
 use crate::types::{AccountTradeSide, BuyOrSell, Trade};
-use chrono::{DateTime, Utc};
+use crate::types::timestamp::parse_timestamp;
 use std::str::FromStr;
 use bigdecimal::BigDecimal;
 use crate::protocol::traits::TryFromState;
 use crate::protocol::state::State;
+use crate::protocol::StreamableList;
 use std::sync::Arc;
 use tokio::sync::RwLock;
 use async_trait::async_trait;
@@ -14,33 +15,12 @@ use async_trait::async_trait;
 #[async_trait]
 impl TryFromState<list_account_trades::ResponseData> for ListAccountTradesResponse {
     async fn from(response: list_account_trades::ResponseData, _state: Arc<RwLock<State>>) -> Result<ListAccountTradesResponse> {
-        let mut trades = Vec::new();
-        for trade_data in response.list_account_trades.trades {
-            let market = trade_data.market.name.clone();
-            let taker_fee = BigDecimal::from_str(&trade_data.taker_fee.amount)?;
-            let maker_fee = BigDecimal::from_str(&trade_data.maker_fee.amount)?;
-            let amount = BigDecimal::from_str(&trade_data.amount.amount)?;
-            let maker_recieved = BigDecimal::from_str(&trade_data.maker_received.amount)?;
-            let taker_recieved = BigDecimal::from_str(&trade_data.taker_received.amount)?;
-            let limit_price = BigDecimal::from_str(&trade_data.limit_price.amount)?;
-            
-            trades.push(Trade {
-                market,
-                amount,
-                taker_fee,
-                maker_fee,
-                maker_recieved,
-                taker_recieved,
-                taker_order_id: trade_data.taker_order_id.clone(),
-                maker_order_id: trade_data.maker_order_id.clone(),
-                account_side: trade_data.account_side.into(),
-                id: trade_data.id,
-                executed_at: DateTime::<Utc>::from_str(&trade_data.executed_at)
-                    .map_err(|_| ProtocolError("Could not convert value to DateTime"))?,
-                limit_price,
-                direction: trade_data.direction.into(),
-            })
-        }
+        let trades = response
+            .list_account_trades
+            .trades
+            .into_iter()
+            .map(trade_from_data)
+            .collect::<Result<Vec<Trade>>>()?;
         Ok(ListAccountTradesResponse {
             trades,
             next_page: response.list_account_trades.next.clone(),
@@ -48,6 +28,37 @@ impl TryFromState<list_account_trades::ResponseData> for ListAccountTradesRespon
     }
 }
 
+fn trade_from_data(trade_data: list_account_trades::ListAccountTradesListAccountTradesTrades) -> Result<Trade> {
+    Ok(Trade {
+        market: trade_data.market.name,
+        amount: BigDecimal::from_str(&trade_data.amount.amount)?,
+        taker_fee: BigDecimal::from_str(&trade_data.taker_fee.amount)?,
+        maker_fee: BigDecimal::from_str(&trade_data.maker_fee.amount)?,
+        maker_recieved: BigDecimal::from_str(&trade_data.maker_received.amount)?,
+        taker_recieved: BigDecimal::from_str(&trade_data.taker_received.amount)?,
+        taker_order_id: trade_data.taker_order_id,
+        maker_order_id: trade_data.maker_order_id,
+        account_side: trade_data.account_side.into(),
+        id: trade_data.id,
+        executed_at: parse_timestamp(&trade_data.executed_at)?,
+        limit_price: BigDecimal::from_str(&trade_data.limit_price.amount)?,
+        direction: trade_data.direction.into(),
+    })
+}
+
+impl StreamableList for ListAccountTradesRequest {
+    type Raw = list_account_trades::ListAccountTradesListAccountTradesTrades;
+    type Item = Trade;
+
+    fn list_path(&self) -> &'static [&'static str] {
+        &["listAccountTrades", "trades"]
+    }
+
+    fn convert(raw: Self::Raw) -> Result<Trade> {
+        trade_from_data(raw)
+    }
+}
+
 impl From<list_account_trades::Direction> for BuyOrSell {
     fn from(response: list_account_trades::Direction) -> Self {
         match response {
@@ -1,5 +1,5 @@
 use super::super::{
-    serializable_to_json, try_response_with_state_from_json, NashProtocol, ResponseOrError, State,
+    Page, Paginated, serializable_to_json, try_response_with_state_from_json, NashProtocol, ResponseOrError, State,
 };
 use crate::errors::Result;
 use crate::graphql::list_account_trades;
@@ -28,6 +28,33 @@ pub struct ListAccountTradesResponse {
     pub next_page: Option<String>,
 }
 
+impl Paginated for ListAccountTradesRequest {
+    type Item = Trade;
+
+    fn before(&self) -> Option<&str> {
+        self.before.as_deref()
+    }
+
+    fn limit(&self) -> Option<i64> {
+        self.limit
+    }
+
+    fn page(&self, before: Option<String>, limit: Option<i64>) -> Self {
+        Self {
+            before,
+            limit,
+            ..self.clone()
+        }
+    }
+
+    fn into_page(response: ListAccountTradesResponse) -> Page<Trade> {
+        Page {
+            items: response.trades,
+            next_page: response.next_page,
+        }
+    }
+}
+
 #[async_trait]
 impl NashProtocol for ListAccountTradesRequest {
     type Response = ListAccountTradesResponse;
@@ -1,9 +1,10 @@
+use crate::types::timestamp::format_timestamp;
 use super::types::ListCandlesRequest;
 use crate::graphql;
 use crate::graphql::list_candles;
 use crate::types::CandleInterval;
 
-use graphql_client::GraphQLQuery;
+use crate::protocol::query_overrides::build_query;
 
 impl ListCandlesRequest {
     pub fn make_query(&self) -> graphql_client::QueryBody<list_candles::Variables> {
@@ -13,10 +14,10 @@ impl ListCandlesRequest {
             market_name: self.market.clone(),
             chronological: self.chronological,
             interval: self.interval.as_ref().map(|x| x.into()),
-            range_start: self.range.as_ref().map(|x| format!("{:?}", x.start)),
-            range_stop: self.range.as_ref().map(|x| format!("{:?}", x.stop)),
+            range_start: self.range.as_ref().map(|x| format_timestamp(&x.start)),
+            range_stop: self.range.as_ref().map(|x| format_timestamp(&x.stop)),
         };
-        graphql::ListCandles::build_query(list_candles)
+        build_query::<graphql::ListCandles>(list_candles)
     }
 }
 
@@ -1,8 +1,8 @@
 use super::types::ListCandlesResponse;
-use crate::errors::{ProtocolError, Result};
+use crate::errors::Result;
 use crate::graphql::list_candles;
 use crate::types::{Candle, CandleInterval};
-use chrono::{DateTime, Utc};
+use crate::types::timestamp::parse_timestamp;
 use bigdecimal::BigDecimal;
 use std::str::FromStr;
 use crate::protocol::traits::TryFromState;
@@ -31,8 +31,7 @@ impl TryFromState<list_candles::ResponseData> for ListCandlesResponse {
                 open_price,
                 close_price,
                 interval: candle_data.interval.into(),
-                interval_start: DateTime::<Utc>::from_str(&candle_data.interval_starting_at)
-                    .map_err(|_| ProtocolError("Could not convert value to DateTime"))?,
+                interval_start: parse_timestamp(&candle_data.interval_starting_at)?,
             })
         }
         Ok(ListCandlesResponse {
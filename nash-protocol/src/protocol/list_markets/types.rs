@@ -44,10 +44,16 @@ impl NashProtocol for ListMarketsRequest {
             assets.insert(market.asset_b.asset);
             market_map.insert(market.market_name(), market.clone());
         }
+        let market_map = Arc::new(market_map);
+        let assets: Arc<Vec<_>> = Arc::new(assets.into_iter().collect());
         // store market and asset list in the client
         let mut state = state.write().await;
+        // and in the shared cache so other clients can pick them up
+        if let Some(cache) = &state.market_cache {
+            cache.set(market_map.clone(), assets.clone());
+        }
         state.markets = Some(market_map);
-        state.assets = Some(assets.into_iter().collect());
+        state.assets = Some(assets);
         Ok(())
     }
 
@@ -0,0 +1,7 @@
+//! Get the tickers of every market in one query, instead of a `TickerRequest` per market
+
+mod request;
+mod response;
+mod types;
+
+pub use types::{ListTickersRequest, ListTickersResponse};
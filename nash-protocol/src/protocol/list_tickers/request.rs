@@ -0,0 +1,10 @@
+use super::types::ListTickersRequest;
+use crate::graphql;
+use crate::graphql::list_tickers;
+use crate::protocol::query_overrides::build_query;
+
+impl ListTickersRequest {
+    pub fn make_query(&self) -> graphql_client::QueryBody<list_tickers::Variables> {
+        build_query::<graphql::ListTickers>(list_tickers::Variables {})
+    }
+}
@@ -0,0 +1,40 @@
+use super::types::ListTickersResponse;
+use crate::errors::{ProtocolError, Result};
+use crate::graphql::list_tickers;
+use crate::protocol::get_ticker::TickerResponse;
+use bigdecimal::BigDecimal;
+use std::convert::TryFrom;
+use std::str::FromStr;
+
+fn optional_amount(amount: Option<&String>) -> Result<Option<BigDecimal>> {
+    amount
+        .map(|amount| Ok(BigDecimal::from_str(amount)?))
+        .transpose()
+}
+
+impl TryFrom<list_tickers::ResponseData> for ListTickersResponse {
+    type Error = ProtocolError;
+
+    fn try_from(response: list_tickers::ResponseData) -> Result<Self> {
+        let mut tickers = Vec::new();
+        for ticker in response.list_tickers {
+            tickers.push(TickerResponse {
+                id: ticker.id,
+                market_name: ticker.market_name,
+                a_volume_24h: BigDecimal::from_str(&ticker.a_volume24h.amount)?,
+                b_volume_24h: BigDecimal::from_str(&ticker.b_volume24h.amount)?,
+                high_price_24h: optional_amount(ticker.high_price24h.as_ref().map(|p| &p.amount))?,
+                low_price_24h: optional_amount(ticker.low_price24h.as_ref().map(|p| &p.amount))?,
+                last_price: optional_amount(ticker.last_price.as_ref().map(|p| &p.amount))?,
+                price_change_24h: optional_amount(
+                    ticker.price_change24h.as_ref().map(|p| &p.amount),
+                )?,
+                best_ask_amount: optional_amount(ticker.best_ask_size.as_ref().map(|s| &s.amount))?,
+                best_ask_price: optional_amount(ticker.best_ask_price.as_ref().map(|p| &p.amount))?,
+                best_bid_amount: optional_amount(ticker.best_bid_size.as_ref().map(|s| &s.amount))?,
+                best_bid_price: optional_amount(ticker.best_bid_price.as_ref().map(|p| &p.amount))?,
+            });
+        }
+        Ok(Self { tickers })
+    }
+}
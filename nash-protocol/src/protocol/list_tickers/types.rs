@@ -0,0 +1,49 @@
+use super::super::{
+    serializable_to_json, try_response_from_json, NashProtocol, ResponseOrError, State,
+};
+use crate::errors::Result;
+use crate::graphql::list_tickers;
+use crate::protocol::get_ticker::TickerResponse;
+use async_trait::async_trait;
+use std::sync::Arc;
+use tokio::sync::RwLock;
+
+/// Get the 24h ticker of every market
+#[derive(Clone, Debug)]
+pub struct ListTickersRequest;
+
+/// Ticker of every market, in the order the exchange returns them
+#[derive(Clone, Debug)]
+pub struct ListTickersResponse {
+    pub tickers: Vec<TickerResponse>,
+}
+
+impl ListTickersResponse {
+    /// Ticker of `market`, if the exchange returned one
+    pub fn ticker(&self, market: &str) -> Option<&TickerResponse> {
+        self.tickers
+            .iter()
+            .find(|ticker| ticker.market_name == market)
+    }
+}
+
+#[async_trait]
+impl NashProtocol for ListTickersRequest {
+    type Response = ListTickersResponse;
+
+    async fn graphql(&self, _state: Arc<RwLock<State>>) -> Result<serde_json::Value> {
+        let query = self.make_query();
+        let mut out = serializable_to_json(&query)?;
+        // the query takes no variables and ME rejects null for them
+        *out.get_mut("variables").unwrap() = serde_json::json!({});
+        Ok(out)
+    }
+
+    async fn response_from_json(
+        &self,
+        response: serde_json::Value,
+        _state: Arc<RwLock<State>>,
+    ) -> Result<ResponseOrError<Self::Response>> {
+        try_response_from_json::<ListTickersResponse, list_tickers::ResponseData>(response)
+    }
+}
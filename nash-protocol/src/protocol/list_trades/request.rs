@@ -1,11 +1,11 @@
-use graphql_client::GraphQLQuery;
+use crate::protocol::query_overrides::build_query;
 use crate::graphql;
 use crate::graphql::list_trades;
 use super::types::ListTradesRequest;
 
 impl ListTradesRequest {
     pub fn make_query(&self) -> graphql_client::QueryBody<list_trades::Variables> {
-        graphql::ListTrades::build_query(list_trades::Variables {
+        build_query::<graphql::ListTrades>(list_trades::Variables {
             market_name: self.market.clone(),
             limit: self.limit,
             before: self.before.clone(),
@@ -1,12 +1,13 @@
 use std::str::FromStr;
-use chrono::{DateTime, Utc};
-use crate::errors::{ProtocolError, Result};
+use crate::types::timestamp::parse_timestamp;
+use crate::errors::Result;
 use crate::graphql::list_trades;
 use crate::types::{AccountTradeSide, BuyOrSell, Trade};
-use super::types::ListTradesResponse;
+use super::types::{ListTradesRequest, ListTradesResponse};
 use bigdecimal::BigDecimal;
 use crate::protocol::traits::TryFromState;
 use crate::protocol::state::State;
+use crate::protocol::StreamableList;
 use std::sync::Arc;
 use tokio::sync::RwLock;
 use async_trait::async_trait;
@@ -15,35 +16,12 @@ use async_trait::async_trait;
 impl TryFromState<list_trades::ResponseData> for ListTradesResponse {
 
     async fn from(response: list_trades::ResponseData, _state: Arc<RwLock<State>>) -> Result<ListTradesResponse> {
-        let mut trades = Vec::new();
-
-        for trade_data in response.list_trades.trades {
-            let market = trade_data.market.name.clone();
-            let taker_fee = BigDecimal::from_str(&trade_data.taker_fee.amount)?;
-            let maker_fee = BigDecimal::from_str(&trade_data.maker_fee.amount)?;
-            let amount = BigDecimal::from_str(&trade_data.amount.amount)?;
-            let maker_recieved = BigDecimal::from_str(&trade_data.maker_received.amount)?;
-            let taker_recieved = BigDecimal::from_str(&trade_data.taker_received.amount)?;
-            let limit_price = BigDecimal::from_str(&trade_data.limit_price.amount)?;
-
-            trades.push(Trade {
-                id: trade_data.id,
-                taker_order_id: trade_data.taker_order_id.clone(),
-                maker_order_id: trade_data.maker_order_id.clone(),
-                amount,
-                executed_at: DateTime::<Utc>::from_str(&trade_data.executed_at)
-                    .map_err(|_| ProtocolError("Could not convert value to DateTime"))?,
-                account_side: trade_data.account_side.into(),
-                taker_fee,
-                maker_fee,
-                maker_recieved,
-                taker_recieved,
-                market,
-                direction: trade_data.direction.into(),
-                limit_price,
-            })
-        }
-
+        let trades = response
+            .list_trades
+            .trades
+            .into_iter()
+            .map(trade_from_data)
+            .collect::<Result<Vec<Trade>>>()?;
         Ok(ListTradesResponse {
             trades,
             next_page: response.list_trades.next.clone(),
@@ -51,6 +29,37 @@ impl TryFromState<list_trades::ResponseData> for ListTradesResponse {
     }
 }
 
+fn trade_from_data(trade_data: list_trades::ListTradesListTradesTrades) -> Result<Trade> {
+    Ok(Trade {
+        market: trade_data.market.name,
+        amount: BigDecimal::from_str(&trade_data.amount.amount)?,
+        taker_fee: BigDecimal::from_str(&trade_data.taker_fee.amount)?,
+        maker_fee: BigDecimal::from_str(&trade_data.maker_fee.amount)?,
+        maker_recieved: BigDecimal::from_str(&trade_data.maker_received.amount)?,
+        taker_recieved: BigDecimal::from_str(&trade_data.taker_received.amount)?,
+        taker_order_id: trade_data.taker_order_id,
+        maker_order_id: trade_data.maker_order_id,
+        account_side: trade_data.account_side.into(),
+        id: trade_data.id,
+        executed_at: parse_timestamp(&trade_data.executed_at)?,
+        limit_price: BigDecimal::from_str(&trade_data.limit_price.amount)?,
+        direction: trade_data.direction.into(),
+    })
+}
+
+impl StreamableList for ListTradesRequest {
+    type Raw = list_trades::ListTradesListTradesTrades;
+    type Item = Trade;
+
+    fn list_path(&self) -> &'static [&'static str] {
+        &["listTrades", "trades"]
+    }
+
+    fn convert(raw: Self::Raw) -> Result<Trade> {
+        trade_from_data(raw)
+    }
+}
+
 impl From<list_trades::Direction> for BuyOrSell {
     fn from(response: list_trades::Direction) -> Self {
         match response {
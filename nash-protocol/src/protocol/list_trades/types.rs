@@ -7,7 +7,7 @@ use crate::types::Trade;
 use super::super::list_markets::ListMarketsRequest;
 use super::super::hooks::{ProtocolHook, NashProtocolRequest};
 use super::super::{
-    NashProtocol, ResponseOrError, serializable_to_json, State, try_response_with_state_from_json,
+    NashProtocol, Page, Paginated, ResponseOrError, serializable_to_json, State, try_response_with_state_from_json,
 };
 
 /// Get trades associated with market, filtering on several optional fields.
@@ -27,6 +27,33 @@ pub struct ListTradesResponse {
     pub next_page: Option<String>,
 }
 
+impl Paginated for ListTradesRequest {
+    type Item = Trade;
+
+    fn before(&self) -> Option<&str> {
+        self.before.as_deref()
+    }
+
+    fn limit(&self) -> Option<i64> {
+        self.limit
+    }
+
+    fn page(&self, before: Option<String>, limit: Option<i64>) -> Self {
+        Self {
+            before,
+            limit,
+            ..self.clone()
+        }
+    }
+
+    fn into_page(response: ListTradesResponse) -> Page<Trade> {
+        Page {
+            items: response.trades,
+            next_page: response.next_page,
+        }
+    }
+}
+
 #[async_trait]
 impl NashProtocol for ListTradesRequest {
     type Response = ListTradesResponse;
@@ -8,32 +8,63 @@ pub mod cancel_all_orders;
 pub mod cancel_order;
 pub mod cancel_orders;
 pub mod dh_fill_pool;
+pub mod get_account_fee_rates;
 pub mod get_account_order;
+pub mod get_deposit_address;
 pub mod get_ticker;
+pub mod list_account_activity;
 pub mod list_account_balances;
+pub mod list_account_movements;
 pub mod list_account_orders;
 pub mod list_account_trades;
 pub mod list_candles;
 pub mod list_markets;
+pub mod list_tickers;
 pub mod list_trades;
 pub mod orderbook;
 pub mod place_order;
 pub mod place_orders;
+pub mod refresh_token;
 pub mod sign_all_states;
 pub mod sign_states;
 pub mod subscriptions;
+pub mod withdraw;
 pub mod multi_request;
 
 mod canonical_string;
+mod compat;
 mod graphql;
 mod hooks;
+mod latency;
+#[cfg(test)]
+mod operation_name;
+mod order_nonces;
+mod pagination;
+mod persisted_query;
+mod query_overrides;
 mod signer;
+mod snapshot;
 mod state;
+mod streaming;
+mod throttled;
 mod traits;
 
 pub use canonical_string::general_canonical_string;
+pub use compat::{CompatibilityFixture, CompatibilityReport, SignatureVector};
 pub use graphql::*;
 pub use hooks::{NashProtocolRequest, ProtocolHook};
-pub use signer::Signer;
+pub use latency::{
+    LatencyBudget, ResponseTiming, ServerTimingMetric, StageTimings, TimedResponse,
+};
+pub use order_nonces::{NonceBlock, NonceProvider};
+pub use pagination::{Page, Paginated};
+pub use persisted_query::{
+    is_persisted_query_not_found, query_hash, PersistedQueries, PERSISTED_QUERY_NOT_FOUND,
+};
+pub use query_overrides::{overridden_operations, query_override};
+pub use signer::{verify_canonical_string, Signer};
+pub use snapshot::{StateSnapshot, STATE_SNAPSHOT_SCHEMA};
 pub use state::*;
+pub use streaming::{decode_list, ListEnvelope, StreamableList};
+pub use throttled::Throttled;
 pub use traits::*;
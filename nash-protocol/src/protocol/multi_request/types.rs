@@ -2,6 +2,7 @@ use crate::errors::{Result, ProtocolError};
 use std::convert::{TryFrom, TryInto};
 use crate::protocol::GraphQLResponse;
 use serde::Deserialize;
+use std::collections::HashMap;
 
 #[derive(Clone, Debug)]
 pub struct MultiRequest<T> {
@@ -12,6 +13,16 @@ impl<T> MultiRequest<T> {
     pub fn new(requests: Vec<T>) -> Result<Self> { Ok(Self { requests })}
 }
 
+impl<T: Clone> MultiRequest<T> {
+    /// Split into requests of at most `size` requests each, keeping their order
+    pub fn chunks(&self, size: usize) -> Vec<Self> {
+        self.requests
+            .chunks(size.max(1))
+            .map(|requests| Self { requests: requests.to_vec() })
+            .collect()
+    }
+}
+
 /// A helper type for constructing blockchain payloads and GraphQL requests
 pub struct MultiRequestConstructor<T> {
     pub constructors: Vec<T>
@@ -28,51 +39,106 @@ impl<T> From<Vec<Result<T>>> for MultiResponse<T> {
     }
 }
 
-impl<T: for<'de> Deserialize<'de>> TryFrom<serde_json::Value> for MultiResponse<T> {
-    type Error = ProtocolError;
-    fn try_from(response: serde_json::Value) -> Result<Self> {
-        let response: GraphQLResponse = response.try_into()?;
-        let errors: Vec<_> = response
-            .errors
-            .into_iter()
-            .map(|error| (
-                ProtocolError::coerce_static_from_str(&error.message),
-                error
-                    .path
-                    .first()
-                    .expect("Couldn't get path.")
-                    .clone()
-            )).collect();
+impl<T: for<'de> Deserialize<'de>> MultiResponse<T> {
+    /// Parse the response to a request of `expected` aliased calls (`response0`, `response1`,
+    /// ...) so that `responses` lines up with the requests. A call without data is rejected
+    /// with the GraphQL error reported for its alias, or with the request wide error if the
+    /// server reported one without a path.
+    pub fn from_graphql(response: serde_json::Value, expected: usize) -> Result<Self> {
+        Self::parse(response, Some(expected))
+    }
 
-        let mut responses: Vec<_> = response
-            .data
-            .into_iter()
-            .collect();
-        responses.sort_by(|(a, _), (b, _)| {
-            let a: i32 = a[8..].parse().unwrap();
-            let b: i32 = b[8..].parse().unwrap();
-            a.partial_cmp(&b).unwrap()
+    fn parse(mut response: serde_json::Value, expected: Option<usize>) -> Result<Self> {
+        // a failing mutation may come back with `"data": null`
+        if let Some(data) = response.get_mut("data") {
+            if data.is_null() {
+                *data = serde_json::Value::Object(Default::default());
+            }
+        }
+        let response: GraphQLResponse = response.try_into()?;
+        let mut data = HashMap::new();
+        for (alias, value) in response.data {
+            let index = alias_index(&alias)
+                .ok_or_else(|| ProtocolError::coerce_static_from_str(
+                    &format!("Unexpected field in response: {}", alias)
+                ))?;
+            data.insert(index, value);
+        }
+        let mut alias_errors = HashMap::new();
+        let mut request_error = None;
+        for error in response.errors {
+            match error.path.first().and_then(|alias| alias_index(alias)) {
+                Some(index) => { alias_errors.entry(index).or_insert(error.message); }
+                None => { request_error.get_or_insert(error.message); }
+            }
+        }
+        let expected = expected.unwrap_or_else(|| {
+            data.keys().chain(alias_errors.keys()).max().map(|max| max + 1).unwrap_or(0)
         });
-        let responses = responses
-            .into_iter()
-            .map(|(path, value)| {
-                if value.is_null() {
-                    Err(errors
-                        .iter()
-                        .find(|(_, error_path)| path == *error_path)
-                        .map(|(message, _)| message.clone())
-                        .unwrap_or_else(|| ProtocolError("Couldn't find error message.")))
-                } else {
-                    let response: Result<T> = serde_json::from_value(value)
-                        .map_err(|e|
-                            ProtocolError::coerce_static_from_str(
-                                &format!("Couldn't parse response: {:#?}", e)
-                            )
-                        );
-                    response
-                }
+        let responses = (0..expected)
+            .map(|index| match data.remove(&index) {
+                Some(value) if !value.is_null() => serde_json::from_value(value)
+                    .map_err(|e| ProtocolError::with_source("Couldn't parse response", e)),
+                _ => Err(alias_errors
+                    .get(&index)
+                    .or_else(|| request_error.as_ref())
+                    .map(|message| ProtocolError::coerce_static_from_str(message))
+                    .unwrap_or_else(|| ProtocolError("Couldn't find error message."))),
             })
             .collect();
         Ok(Self { responses })
     }
 }
+
+impl<T: for<'de> Deserialize<'de>> TryFrom<serde_json::Value> for MultiResponse<T> {
+    type Error = ProtocolError;
+    /// Parse without knowing how many calls were sent. Prefer `MultiResponse::from_graphql`,
+    /// which also reports calls missing from the response.
+    fn try_from(response: serde_json::Value) -> Result<Self> {
+        Self::parse(response, None)
+    }
+}
+
+/// Index of an aliased call, e.g. 3 for `response3`
+fn alias_index(alias: &str) -> Option<usize> {
+    alias.strip_prefix("response")?.parse().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{MultiRequest, MultiResponse};
+    use serde_json::json;
+
+    #[test]
+    fn chunks_keep_order() {
+        let request = MultiRequest::new((0..5).collect::<Vec<u32>>()).unwrap();
+        let chunks = request.chunks(2);
+        let sizes: Vec<usize> = chunks.iter().map(|chunk| chunk.requests.len()).collect();
+        assert_eq!(sizes, vec![2, 2, 1]);
+        assert_eq!(chunks[2].requests, vec![4]);
+    }
+
+    #[test]
+    fn partial_failure_is_aligned_with_requests() {
+        let response = json!({
+            "data": { "response0": 1, "response1": null, "response10": 3 },
+            "errors": [{ "message": "Insufficient funds", "path": ["response1"] }]
+        });
+        let parsed = MultiResponse::<u32>::from_graphql(response, 12).unwrap();
+        assert_eq!(parsed.responses.len(), 12);
+        assert_eq!(parsed.responses[0].as_ref().unwrap(), &1);
+        assert_eq!(parsed.responses[1].as_ref().unwrap_err().message(), "Insufficient funds");
+        assert!(parsed.responses[2].is_err());
+        assert_eq!(parsed.responses[10].as_ref().unwrap(), &3);
+
+        let response = json!({
+            "data": null,
+            "errors": [{ "message": "Invalid signature", "path": [] }]
+        });
+        let parsed = MultiResponse::<u32>::from_graphql(response, 2).unwrap();
+        assert!(parsed
+            .responses
+            .iter()
+            .all(|response| response.as_ref().unwrap_err().message() == "Invalid signature"));
+    }
+}
@@ -0,0 +1,39 @@
+//! Finding the operation a GraphQL document defines. Shared with `build.rs`, which names
+//! query overrides after the operation they replace.
+
+const OPERATION_KINDS: &[&str] = &["query", "mutation", "subscription"];
+
+/// Name of the operation a GraphQL document defines
+pub fn operation_name(document: &str) -> Option<String> {
+    let tokens = document
+        .lines()
+        .map(|line| line.split('#').next().unwrap_or(""))
+        .flat_map(|line| line.split(|c: char| c.is_whitespace() || "({".contains(c)))
+        .filter(|token| !token.is_empty());
+    let mut tokens = tokens.skip_while(|token| !OPERATION_KINDS.contains(token));
+    tokens.next()?;
+    tokens.next().map(|name| name.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::operation_name;
+
+    #[test]
+    fn operations_are_named_after_their_definition() {
+        assert_eq!(
+            operation_name("query ListMarkets {\n  listMarkets { name }\n}"),
+            Some("ListMarkets".to_string())
+        );
+        assert_eq!(
+            operation_name("# query Commented\nmutation PlaceLimitOrder($payload: Payload!) {}"),
+            Some("PlaceLimitOrder".to_string())
+        );
+        assert_eq!(
+            operation_name("subscription UpdatedTicker{ updatedTicker { id } }"),
+            Some("UpdatedTicker".to_string())
+        );
+        assert_eq!(operation_name("{ listMarkets { name } }"), None);
+        assert_eq!(operation_name("query"), None);
+    }
+}
@@ -0,0 +1,103 @@
+//! Order nonces. An order's timestamp in milliseconds doubles as its order nonce, so every
+//! order signed through a `State` must get a different one, also when many tasks place
+//! orders at the same millisecond. `NonceProvider` hands them out from the clock; a
+//! `NonceBlock` reserves a run of them up front for a burst of orders.
+
+use std::sync::atomic::{AtomicI64, AtomicUsize, Ordering};
+
+use crate::utils::current_time_as_i64;
+
+/// Hands out order nonces (timestamps in milliseconds) that are never reused
+#[derive(Debug, Default)]
+pub struct NonceProvider {
+    /// Last nonce handed out
+    last: AtomicI64,
+}
+
+impl NonceProvider {
+    /// Reserve `n` consecutive nonces and return the first. They start at the current
+    /// time, or right after the last nonce handed out if that is later.
+    pub fn reserve(&self, n: usize) -> i64 {
+        let n = n.max(1) as i64;
+        let now = current_time_as_i64();
+        let previous = self
+            .last
+            .fetch_update(Ordering::AcqRel, Ordering::Acquire, |last| {
+                Some(now.max(last + 1) + n - 1)
+            })
+            .expect("update closure always returns a value");
+        now.max(previous + 1)
+    }
+
+    /// Reserve `n` consecutive nonces for a burst of orders. The clock is read once; orders
+    /// placed with nonces of the block don't read it again.
+    pub fn reserve_block(&self, n: usize) -> NonceBlock {
+        let len = n.max(1);
+        NonceBlock {
+            first: self.reserve(len),
+            len,
+            taken: AtomicUsize::new(0),
+        }
+    }
+}
+
+/// Consecutive order nonces reserved with `NonceProvider::reserve_block`. Nonces are taken
+/// in order and at most once, also when the block is shared by several tasks. Nonces that
+/// are never taken are simply skipped.
+#[derive(Debug)]
+pub struct NonceBlock {
+    first: i64,
+    len: usize,
+    taken: AtomicUsize,
+}
+
+impl NonceBlock {
+    /// Take the next nonce, or `None` once the block is used up
+    pub fn next(&self) -> Option<i64> {
+        self.take(1)
+    }
+
+    /// Take the next `n` nonces and return the first, e.g. for a batch of orders, or `None`
+    /// if fewer than `n` are left
+    pub fn take(&self, n: usize) -> Option<i64> {
+        let n = n.max(1);
+        self.taken
+            .fetch_update(Ordering::AcqRel, Ordering::Acquire, |taken| {
+                Some(taken + n).filter(|taken| *taken <= self.len)
+            })
+            .ok()
+            .map(|taken| self.first + taken as i64)
+    }
+
+    pub fn remaining(&self) -> usize {
+        self.len - self.taken.load(Ordering::Acquire).min(self.len)
+    }
+
+    /// First nonce of the block, the time in milliseconds it was reserved at or just after
+    pub fn first(&self) -> i64 {
+        self.first
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::NonceProvider;
+
+    #[test]
+    fn blocks_never_overlap_other_nonces() {
+        let provider = NonceProvider::default();
+        let before = provider.reserve(1);
+        let block = provider.reserve_block(5);
+        let after = provider.reserve(1);
+        assert!(block.first() > before);
+        assert!(after >= block.first() + 5);
+
+        assert_eq!(block.next(), Some(block.first()));
+        assert_eq!(block.take(3), Some(block.first() + 1));
+        assert_eq!(block.remaining(), 1);
+        assert_eq!(block.take(2), None);
+        assert_eq!(block.next(), Some(block.first() + 4));
+        assert_eq!(block.next(), None);
+        assert_eq!(block.remaining(), 0);
+    }
+}
@@ -1,11 +1,11 @@
 use super::types::OrderbookRequest;
 use crate::graphql;
 use crate::graphql::get_orderbook;
-use graphql_client::GraphQLQuery;
+use crate::protocol::query_overrides::build_query;
 
 impl OrderbookRequest {
     pub fn make_query(&self) -> graphql_client::QueryBody<get_orderbook::Variables> {
-        graphql::GetOrderbook::build_query(get_orderbook::Variables {
+        build_query::<graphql::GetOrderbook>(get_orderbook::Variables {
             market_name: self.market.to_string()
         })
     }
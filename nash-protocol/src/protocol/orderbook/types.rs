@@ -1,5 +1,5 @@
 use crate::errors::Result;
-use crate::types::OrderbookOrder;
+use crate::types::{MarketSymbol, OrderbookOrder};
 use super::super::list_markets::ListMarketsRequest;
 use super::super::hooks::{ProtocolHook, NashProtocolRequest};
 use async_trait::async_trait;
@@ -16,6 +16,14 @@ pub struct OrderbookRequest {
     pub market: String,
 }
 
+impl From<MarketSymbol> for OrderbookRequest {
+    fn from(market: MarketSymbol) -> Self {
+        Self {
+            market: market.into(),
+        }
+    }
+}
+
 /// An order book is a list of bid and ask orders
 #[derive(Debug)]
 pub struct OrderbookResponse {
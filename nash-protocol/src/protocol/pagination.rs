@@ -0,0 +1,85 @@
+//! Cursor pagination shared by the list requests. A page of a list request is the request
+//! with a `before` cursor and a `limit`; its response carries the cursor of the next page.
+//! Other filters, like a market or a time range, stay the same from page to page.
+
+use super::NashProtocol;
+
+/// Items of one page of a list and the cursor of the next page
+#[derive(Clone, Debug)]
+pub struct Page<T> {
+    pub items: Vec<T>,
+    pub next_page: Option<String>,
+}
+
+impl<T> Page<T> {
+    /// Cursor of the next page, unless this page is the last one. Some queries return a
+    /// cursor with an empty last page, so an empty page ends the list as well.
+    pub fn next_cursor(&self) -> Option<&str> {
+        self.next_page.as_deref().filter(|_| !self.items.is_empty())
+    }
+}
+
+/// A list request paginated with a `before` cursor
+pub trait Paginated: NashProtocol + Clone {
+    type Item;
+
+    /// Cursor the request starts at, `None` for the newest page
+    fn before(&self) -> Option<&str>;
+
+    fn limit(&self) -> Option<i64>;
+
+    /// This request starting at `before`, for at most `limit` items
+    fn page(&self, before: Option<String>, limit: Option<i64>) -> Self;
+
+    fn into_page(response: Self::Response) -> Page<Self::Item>;
+
+    /// Request for the page after `page`, or `None` once the list is exhausted
+    fn next_page(&self, page: &Page<Self::Item>) -> Option<Self> {
+        page.next_cursor()
+            .map(|cursor| self.page(Some(cursor.to_string()), self.limit()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Page, Paginated};
+    use crate::protocol::list_account_movements::ListAccountMovementsRequest;
+    use crate::protocol::list_trades::ListTradesRequest;
+    use crate::types::Asset;
+
+    #[test]
+    fn pages_follow_the_cursor_until_exhausted() {
+        let page = Page {
+            items: vec![1, 2],
+            next_page: Some("cursor".to_string()),
+        };
+        assert_eq!(page.next_cursor(), Some("cursor"));
+        let last = Page {
+            items: vec![3],
+            next_page: None,
+        };
+        assert_eq!(last.next_cursor(), None);
+        let empty: Page<u32> = Page {
+            items: Vec::new(),
+            next_page: Some("cursor".to_string()),
+        };
+        assert_eq!(empty.next_cursor(), None);
+
+        let request = ListTradesRequest {
+            market: "eth_usdc".to_string(),
+            limit: Some(2),
+            before: None,
+        };
+        let next = request.page(Some("cursor".to_string()), request.limit());
+        assert_eq!((next.before(), next.limit()), (Some("cursor"), Some(2)));
+        assert_eq!(next.market, "eth_usdc");
+
+        let movements = ListAccountMovementsRequest {
+            asset: Some(Asset::ETH),
+            ..Default::default()
+        };
+        let next = movements.page(Some("movement".to_string()), Some(10));
+        assert_eq!((next.before(), next.limit()), (Some("movement"), Some(10)));
+        assert_eq!(next.asset, Some(Asset::ETH));
+    }
+}
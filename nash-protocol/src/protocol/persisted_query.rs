@@ -0,0 +1,115 @@
+//! Automatic persisted queries (APQ). Instead of the full query document, a request carries
+//! the SHA-256 hash of the document in `extensions.persistedQuery`, as understood by Apollo
+//! compatible servers. The first request for a document also carries the document so the
+//! server can register it; later requests only send the hash and variables.
+
+use std::collections::HashSet;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+
+/// Error message servers answer with when they don't know a hash
+pub const PERSISTED_QUERY_NOT_FOUND: &str = "PersistedQueryNotFound";
+
+/// Hex encoded SHA-256 of a query document
+pub fn query_hash(query: &str) -> String {
+    hex::encode(Sha256::digest(query.as_bytes()))
+}
+
+/// Whether a GraphQL response rejected a request because the hash was unknown
+pub fn is_persisted_query_not_found(response: &Value) -> bool {
+    match response.get("errors").and_then(|errors| errors.as_array()) {
+        Some(errors) => errors.iter().any(|error| {
+            error.get("message").and_then(|m| m.as_str()) == Some(PERSISTED_QUERY_NOT_FOUND)
+                || error.pointer("/extensions/code").and_then(|c| c.as_str())
+                    == Some("PERSISTED_QUERY_NOT_FOUND")
+        }),
+        None => false,
+    }
+}
+
+/// Tracks which query documents the server has registered. Disabled by default.
+#[derive(Debug, Default)]
+pub struct PersistedQueries {
+    enabled: AtomicBool,
+    registered: Mutex<HashSet<String>>,
+}
+
+impl PersistedQueries {
+    pub fn enable(&self) {
+        self.enabled.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.enabled.load(Ordering::Relaxed)
+    }
+
+    /// Body to send for a GraphQL request along with the hash of its document, or `None` if
+    /// persisted queries are disabled or the request has no document. The document is only
+    /// left in the body until the server is known to have registered it.
+    pub fn prepare(&self, request: &Value) -> Option<(Value, String)> {
+        if !self.is_enabled() {
+            return None;
+        }
+        let hash = query_hash(request.get("query")?.as_str()?);
+        let registered = self.registered.lock().unwrap().contains(&hash);
+        let mut body = with_hash(request, &hash);
+        if registered {
+            body.as_object_mut()?.remove("query");
+        }
+        Some((body, hash))
+    }
+
+    /// Body that registers the document again, after the server answered with
+    /// `PERSISTED_QUERY_NOT_FOUND` (e.g. because its cache was evicted)
+    pub fn register(&self, request: &Value, hash: &str) -> Value {
+        self.registered.lock().unwrap().remove(hash);
+        with_hash(request, hash)
+    }
+
+    /// Record the server's answer to a request sent with `hash`
+    pub fn record_response(&self, hash: String, response: &Value) {
+        if !is_persisted_query_not_found(response) {
+            self.registered.lock().unwrap().insert(hash);
+        }
+    }
+}
+
+fn with_hash(request: &Value, hash: &str) -> Value {
+    let mut body = request.clone();
+    body["extensions"] = json!({
+        "persistedQuery": {
+            "version": 1,
+            "sha256Hash": hash,
+        }
+    });
+    body
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{is_persisted_query_not_found, PersistedQueries};
+    use serde_json::json;
+
+    #[test]
+    fn document_only_sent_until_registered() {
+        let queries = PersistedQueries::default();
+        let request = json!({"query": "mutation { ping }", "variables": {}});
+        assert!(queries.prepare(&request).is_none());
+
+        queries.enable();
+        let (body, hash) = queries.prepare(&request).unwrap();
+        assert!(body.get("query").is_some());
+        assert_eq!(body["extensions"]["persistedQuery"]["sha256Hash"], json!(hash));
+        queries.record_response(hash, &json!({"data": {}}));
+
+        let (body, hash) = queries.prepare(&request).unwrap();
+        assert!(body.get("query").is_none());
+        let not_found = json!({"errors": [{"message": "PersistedQueryNotFound"}]});
+        assert!(is_persisted_query_not_found(&not_found));
+        assert!(queries.register(&request, &hash).get("query").is_some());
+        assert!(queries.prepare(&request).unwrap().0.get("query").is_some());
+    }
+}
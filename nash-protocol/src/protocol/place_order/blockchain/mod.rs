@@ -3,9 +3,27 @@ pub mod eth;
 pub mod neo;
 
 use super::super::signer::Signer;
+use super::types::PayloadNonces;
 use crate::errors::Result;
 use crate::graphql::place_limit_order;
 use crate::graphql::place_market_order;
+use crate::graphql::place_stop_limit_order;
+use crate::types::{Blockchain, Nonce};
+use nash_mpc::rust_bigint::BigInt;
+
+/// Blockchain and nonces of each fill payload of an order, in the order the exchange expects
+/// them in `blockchainSignatures`: every blockchain of the market as ordered by
+/// `Market::blockchains`, each with one payload per nonce group. Signing an order locally
+/// and offline both follow this layout, whatever the number of chains.
+pub(crate) fn fill_payload_layout(
+    blockchains: &[Blockchain],
+    nonces: &[PayloadNonces],
+) -> Vec<(Blockchain, PayloadNonces)> {
+    blockchains
+        .iter()
+        .flat_map(|&blockchain| nonces.iter().map(move |&nonce_group| (blockchain, nonce_group)))
+        .collect()
+}
 
 /// Generic representation of FillOrder payloads across blockchains. These enable
 /// Nash to settle active orders directly with the smart contract if necessary
@@ -17,6 +35,33 @@ pub enum FillOrder {
 }
 
 impl FillOrder {
+    pub fn blockchain(&self) -> Blockchain {
+        match self {
+            Self::Ethereum(_) => Blockchain::Ethereum,
+            Self::Bitcoin(_) => Blockchain::Bitcoin,
+            Self::NEO(_) => Blockchain::NEO,
+        }
+    }
+
+    /// Asset nonces (from, to) the payload was built with
+    pub fn nonces(&self) -> (Nonce, Nonce) {
+        match self {
+            Self::Ethereum(fill_order) => (fill_order.nonce_from, fill_order.nonce_to),
+            Self::Bitcoin(fill_order) => (fill_order.nonce_from, fill_order.nonce_to),
+            Self::NEO(fill_order) => (fill_order.nonce_from, fill_order.nonce_to),
+        }
+    }
+
+    /// Digest to sign with the child key of the payload's blockchain
+    pub fn digest(&self) -> Result<BigInt> {
+        match self {
+            Self::Ethereum(fill_order) => fill_order.hash(),
+            // see `btc::FillOrder::to_blockchain_signature`
+            Self::Bitcoin(_) => Ok(BigInt::from(0 as u64)),
+            Self::NEO(fill_order) => fill_order.hash(),
+        }
+    }
+
     pub fn to_hex(&self) -> Result<String> {
         match self {
             Self::Ethereum(fill_order) => fill_order.to_hex(),
@@ -36,6 +81,26 @@ impl FillOrder {
         }
     }
 
+    /// Same signature as `to_blockchain_signature`, in the type of the stop limit mutation
+    pub fn to_stop_limit_blockchain_signature(
+        &self,
+        signer: &Signer,
+    ) -> Result<place_stop_limit_order::BlockchainSignature> {
+        let signature = self.to_blockchain_signature(signer)?;
+        Ok(place_stop_limit_order::BlockchainSignature {
+            blockchain: match self.blockchain() {
+                Blockchain::Ethereum => place_stop_limit_order::Blockchain::ETH,
+                Blockchain::Bitcoin => place_stop_limit_order::Blockchain::BTC,
+                Blockchain::NEO => place_stop_limit_order::Blockchain::NEO,
+            },
+            nonce_from: signature.nonce_from,
+            nonce_to: signature.nonce_to,
+            public_key: signature.public_key,
+            signature: signature.signature,
+            r: signature.r,
+        })
+    }
+
     pub fn to_market_blockchain_signature(
         &self,
         signer: &Signer,
@@ -47,3 +112,59 @@ impl FillOrder {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::fill_payload_layout;
+    use crate::protocol::place_order::types::PayloadNonces;
+    use crate::types::{Amount, Asset, AssetAmount, Blockchain, Market, Nonce, TypedNonce};
+
+    fn market(a: Asset, b: Asset) -> Market {
+        let (a, b) = (a.with_precision(8), b.with_precision(8));
+        let min_size = |asset| AssetAmount {
+            asset,
+            amount: Amount::new("0", 8).unwrap(),
+        };
+        Market::new(a, b, min_size(a), min_size(b))
+    }
+
+    #[test]
+    fn fill_payloads_follow_exchange_layout() {
+        let nonces: Vec<PayloadNonces> = (0..2)
+            .map(|group| PayloadNonces {
+                nonce_from: TypedNonce::new(Asset::ETH, 10 + group),
+                nonce_to: TypedNonce::new(Asset::BTC, 20 + group),
+                order_nonce: Nonce::Value(30 + group),
+            })
+            .collect();
+        let layout = |market: Market| -> Vec<(Blockchain, u32)> {
+            fill_payload_layout(&market.blockchains(), &nonces)
+                .into_iter()
+                .map(|(chain, nonces)| (chain, nonces.order_nonce.into()))
+                .collect()
+        };
+        // the chain of A first, every nonce group on each chain
+        assert_eq!(
+            layout(market(Asset::ETH, Asset::BTC)),
+            vec![
+                (Blockchain::Ethereum, 30),
+                (Blockchain::Ethereum, 31),
+                (Blockchain::Bitcoin, 30),
+                (Blockchain::Bitcoin, 31),
+            ]
+        );
+        assert_eq!(
+            layout(market(Asset::NEO, Asset::ETH)),
+            vec![
+                (Blockchain::NEO, 30),
+                (Blockchain::NEO, 31),
+                (Blockchain::Ethereum, 30),
+                (Blockchain::Ethereum, 31),
+            ]
+        );
+        assert_eq!(
+            layout(market(Asset::ETH, Asset::USDC)),
+            vec![(Blockchain::Ethereum, 30), (Blockchain::Ethereum, 31)]
+        );
+    }
+}
@@ -186,7 +186,7 @@ mod tests {
             Nonce::Value(5432876),
             Amount::new("10.000000", 8).unwrap(),
             Rate::MinOrderRate,
-            OrderRate::new("17.000").unwrap().invert_rate(None).into(),
+            OrderRate::new("17.000").unwrap().invert_rate(None).unwrap().into(),
             Rate::MaxFeeRate,
             Nonce::Value(5432876),
         );
@@ -214,7 +214,7 @@ mod tests {
             Nonce::Value(5432876),
             Amount::new("10.00000000", 8).unwrap(),
             Rate::MinOrderRate,
-            OrderRate::new("0.0024").unwrap().invert_rate(None).into(),
+            OrderRate::new("0.0024").unwrap().invert_rate(None).unwrap().into(),
             Rate::MaxFeeRate,
             Nonce::Value(5432876),
         );
@@ -1,9 +1,20 @@
 //! Place orders
 
+// Order placement returns errors on bad input instead of panicking
+#![cfg_attr(not(test), deny(clippy::unwrap_used, clippy::expect_used, clippy::panic))]
+
 // TODO: is a sign that things need some restructuring
 pub(crate) mod blockchain;
-mod request;
+mod offline;
+pub mod projection;
+mod prepared;
+pub(crate) mod request;
 mod response;
 pub mod types;
 
-pub use types::{LimitOrderRequest, MarketOrderRequest, PlaceOrderResponse};
+pub use offline::{FillSignature, UnsignedFillPayload, UnsignedLimitOrder, UnsignedMarketOrder};
+pub use prepared::{PreparedLimitOrder, PreparedSubmission, PREPARED_ORDER_MAX_AGE};
+pub use projection::{OrderFields, Projected, ProjectedOrderResponse};
+pub use types::{
+    ChainSigningError, LimitOrderRequest, MarketOrderRequest, PlaceOrderResponse, RateBounds, StopLimitOrderRequest,
+};
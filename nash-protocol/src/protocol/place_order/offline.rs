@@ -0,0 +1,254 @@
+//! Two phase order placement for signing outside of the client, e.g. on an air-gapped
+//! machine or behind a policy engine. The first phase produces the exact fill payloads and
+//! canonical string to sign without touching any key, the second takes the signatures
+//! back and assembles the same mutation `signed_graphql_request` would have built.
+
+use super::super::RequestPayloadSignature;
+use super::blockchain::{fill_payload_layout, FillOrder};
+use super::request::{limit_order_canonical_string, market_order_canonical_string};
+use super::types::{LimitOrderConstructor, MarketOrderConstructor, PayloadNonces};
+use crate::errors::{ProtocolError, Result};
+use crate::graphql;
+use crate::graphql::place_limit_order;
+use crate::graphql::place_market_order;
+use crate::types::{Blockchain, Market, Nonce, PublicKey};
+use crate::protocol::query_overrides::build_query;
+use nash_mpc::rust_bigint::traits::Converter;
+
+type LimitOrderMutation = graphql_client::QueryBody<place_limit_order::Variables>;
+type MarketOrderMutation = graphql_client::QueryBody<place_market_order::Variables>;
+
+/// A blockchain fill payload waiting for a signature with the account's child key
+#[derive(Clone, Debug, PartialEq)]
+pub struct UnsignedFillPayload {
+    pub blockchain: Blockchain,
+    pub nonce_from: Nonce,
+    pub nonce_to: Nonce,
+    /// Payload bytes as uppercase hex, empty for Bitcoin
+    pub payload: String,
+    /// Hex encoded digest the child key of `blockchain` signs with the MPC protocol
+    pub digest: String,
+}
+
+/// Child key signature over the digest of an `UnsignedFillPayload`, hex encoded as
+/// produced by the MPC presignature computation
+#[derive(Clone, Debug, PartialEq)]
+pub struct FillSignature {
+    /// Child public key of the payload's blockchain
+    pub public_key: String,
+    pub signature: String,
+    pub r: String,
+}
+
+impl FillSignature {
+    // Same zero padding as `bigint_to_nash_sig` and `bigint_to_nash_r`
+    fn normalized(self) -> Result<(String, String, String)> {
+        let is_hex =
+            |value: &str| !value.is_empty() && value.chars().all(|c| c.is_ascii_hexdigit());
+        if !is_hex(&self.signature)
+            || !is_hex(&self.r)
+            || self.signature.len() > 1024
+            || self.r.len() > 66
+        {
+            return Err(ProtocolError("Fill signature and r must be hex encoded"));
+        }
+        Ok((
+            self.public_key,
+            format!("{:0>1024}", self.signature),
+            format!("{:0>66}", self.r),
+        ))
+    }
+}
+
+/// A limit order with everything but its signatures
+pub struct UnsignedLimitOrder {
+    variables: place_limit_order::Variables,
+    /// Payloads to sign, `assemble` expects their signatures in this order
+    pub fill_payloads: Vec<UnsignedFillPayload>,
+    /// String to sign with the payload signing key, see `Signer::sign_canonical_string`
+    pub canonical_string: String,
+}
+
+impl UnsignedLimitOrder {
+    /// Build the order mutation from externally produced signatures
+    pub fn assemble(
+        mut self,
+        fill_signatures: Vec<FillSignature>,
+        request_signature: RequestPayloadSignature,
+    ) -> Result<LimitOrderMutation> {
+        check_signature_count(&self.fill_payloads, &fill_signatures)?;
+        let mut signatures = Vec::new();
+        for (payload, signature) in self.fill_payloads.iter().zip(fill_signatures) {
+            let (public_key, signature, r) = signature.normalized()?;
+            signatures.push(Some(place_limit_order::BlockchainSignature {
+                blockchain: match payload.blockchain {
+                    Blockchain::Ethereum => place_limit_order::Blockchain::ETH,
+                    Blockchain::Bitcoin => place_limit_order::Blockchain::BTC,
+                    Blockchain::NEO => place_limit_order::Blockchain::NEO,
+                },
+                nonce_from: Some(payload.nonce_from.into()),
+                nonce_to: Some(payload.nonce_to.into()),
+                public_key: Some(public_key),
+                signature,
+                r: Some(r),
+            }));
+        }
+        self.variables.payload.blockchain_signatures = signatures;
+        self.variables.signature = request_signature.into();
+        Ok(build_query::<graphql::PlaceLimitOrder>(self.variables))
+    }
+}
+
+/// A market order with everything but its signatures
+pub struct UnsignedMarketOrder {
+    variables: place_market_order::Variables,
+    /// Payloads to sign, `assemble` expects their signatures in this order
+    pub fill_payloads: Vec<UnsignedFillPayload>,
+    /// String to sign with the payload signing key, see `Signer::sign_canonical_string`
+    pub canonical_string: String,
+}
+
+impl UnsignedMarketOrder {
+    /// Build the order mutation from externally produced signatures
+    pub fn assemble(
+        mut self,
+        fill_signatures: Vec<FillSignature>,
+        request_signature: RequestPayloadSignature,
+    ) -> Result<MarketOrderMutation> {
+        check_signature_count(&self.fill_payloads, &fill_signatures)?;
+        let mut signatures = Vec::new();
+        for (payload, signature) in self.fill_payloads.iter().zip(fill_signatures) {
+            let (public_key, signature, r) = signature.normalized()?;
+            signatures.push(Some(place_market_order::BlockchainSignature {
+                blockchain: match payload.blockchain {
+                    Blockchain::Ethereum => place_market_order::Blockchain::ETH,
+                    Blockchain::Bitcoin => place_market_order::Blockchain::BTC,
+                    Blockchain::NEO => place_market_order::Blockchain::NEO,
+                },
+                nonce_from: Some(payload.nonce_from.into()),
+                nonce_to: Some(payload.nonce_to.into()),
+                public_key: Some(public_key),
+                signature,
+                r: Some(r),
+            }));
+        }
+        self.variables.payload.blockchain_signatures = signatures;
+        self.variables.signature = request_signature.into();
+        Ok(build_query::<graphql::PlaceMarketOrder>(self.variables))
+    }
+}
+
+impl LimitOrderConstructor {
+    /// First phase of offline signing. `child_keys` are the account's child public keys,
+    /// one per blockchain of the market.
+    pub fn unsigned_request(
+        &self,
+        nonces: &[PayloadNonces],
+        current_time: i64,
+        affiliate: Option<String>,
+        child_keys: &[PublicKey],
+    ) -> Result<UnsignedLimitOrder> {
+        let variables = self.graphql_request(current_time, affiliate)?;
+        let fill_payloads =
+            unsigned_fill_payloads(&self.market, nonces, child_keys, |chain, key, nonces| {
+                self.make_fill_order(chain, key, nonces)
+            })?;
+        Ok(UnsignedLimitOrder {
+            canonical_string: limit_order_canonical_string(&variables)?,
+            variables,
+            fill_payloads,
+        })
+    }
+}
+
+impl MarketOrderConstructor {
+    /// First phase of offline signing. `child_keys` are the account's child public keys,
+    /// one per blockchain of the market.
+    pub fn unsigned_request(
+        &self,
+        nonces: &[PayloadNonces],
+        current_time: i64,
+        affiliate: Option<String>,
+        child_keys: &[PublicKey],
+    ) -> Result<UnsignedMarketOrder> {
+        let variables = self.graphql_request(current_time, affiliate)?;
+        let fill_payloads =
+            unsigned_fill_payloads(&self.market, nonces, child_keys, |chain, key, nonces| {
+                self.make_fill_order(chain, key, nonces)
+            })?;
+        Ok(UnsignedMarketOrder {
+            canonical_string: market_order_canonical_string(&variables)?,
+            variables,
+            fill_payloads,
+        })
+    }
+}
+
+// Laid out by `fill_payload_layout`, like locally signed payloads
+fn unsigned_fill_payloads<F>(
+    market: &Market,
+    nonces: &[PayloadNonces],
+    child_keys: &[PublicKey],
+    make_fill_order: F,
+) -> Result<Vec<UnsignedFillPayload>>
+where
+    F: Fn(Blockchain, &PublicKey, &PayloadNonces) -> Result<FillOrder>,
+{
+    let mut payloads = Vec::new();
+    for (blockchain, nonce_group) in fill_payload_layout(&market.blockchains(), nonces) {
+        let pub_key = child_keys
+            .iter()
+            .find(|key| key.blockchain() == blockchain)
+            .ok_or(ProtocolError(
+                "Missing child public key for a blockchain of the market",
+            ))?;
+        let fill_order = make_fill_order(blockchain, pub_key, &nonce_group)?;
+        let (nonce_from, nonce_to) = fill_order.nonces();
+        payloads.push(UnsignedFillPayload {
+            blockchain,
+            nonce_from,
+            nonce_to,
+            payload: fill_order.to_hex()?,
+            digest: fill_order.digest()?.to_hex(),
+        });
+    }
+    Ok(payloads)
+}
+
+fn check_signature_count(
+    payloads: &[UnsignedFillPayload],
+    signatures: &[FillSignature],
+) -> Result<()> {
+    if payloads.len() != signatures.len() {
+        return Err(ProtocolError::coerce_static_from_str(&format!(
+            "Expected {} fill signatures, got {}",
+            payloads.len(),
+            signatures.len()
+        )));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::FillSignature;
+
+    #[test]
+    fn fill_signatures_are_padded_like_local_ones() {
+        let signature = FillSignature {
+            public_key: "02ab".to_string(),
+            signature: "1f".to_string(),
+            r: "0a".to_string(),
+        };
+        let (_, signature, r) = signature.normalized().unwrap();
+        assert_eq!(signature.len(), 1024);
+        assert!(signature.ends_with("001f"));
+        assert_eq!(r.len(), 66);
+        let invalid = FillSignature {
+            public_key: "02ab".to_string(),
+            signature: "xyz".to_string(),
+            r: "0a".to_string(),
+        };
+        assert!(invalid.normalized().is_err());
+    }
+}
@@ -0,0 +1,273 @@
+//! Limit orders built and signed ahead of time, so placing them only costs the round trip.
+//! Fill payloads are signed with the current asset nonces, which change when states are
+//! signed. A prepared order whose nonces went stale, or that waited too long for its
+//! timestamp to still be recent, is signed again when it is placed.
+
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use async_trait::async_trait;
+use tokio::sync::RwLock;
+use tracing::debug;
+
+use super::types::{LimitOrderRequest, PlaceOrderResponse};
+use crate::errors::{ProtocolError, Result, ResultExt};
+use crate::protocol::{
+    serializable_to_json, ErrorResponse, NashProtocol, NonceBlock, ProtocolHook, ResponseOrError,
+    StageTimings, State, TimedNashProtocol,
+};
+use crate::types::BuyOrSell;
+use crate::utils::current_time_as_i64;
+
+/// Age after which a prepared order is signed again when placed
+pub const PREPARED_ORDER_MAX_AGE: Duration = Duration::from_secs(30);
+
+/// A limit order with its mutation signed and ready to send, from
+/// `LimitOrderRequest::prepare`. It is not `Clone`: an order is signed to be placed once.
+/// Run its `submission` to place it.
+#[derive(Debug)]
+pub struct PreparedLimitOrder {
+    request: LimitOrderRequest,
+    query: serde_json::Value,
+    /// Asset nonces of the two assets of the market the payloads were signed with
+    asset_nonces: Vec<(&'static str, Option<Vec<u32>>)>,
+    /// Timestamp and order nonce the order was signed with
+    time: i64,
+    /// How long building and signing the order took
+    pub timings: StageTimings,
+}
+
+impl LimitOrderRequest {
+    /// Look up the market, validate the order and sign its payloads now, so that placing
+    /// it later skips all of that. Asset nonces must already be known, see `run_before`.
+    pub async fn prepare(&self, state: Arc<RwLock<State>>) -> Result<PreparedLimitOrder> {
+        let time = state.read().await.reserve_order_times(1);
+        self.prepare_at(state, time).await
+    }
+
+    /// Same as `prepare`, with the next order nonce of `nonces` instead of one read from
+    /// the clock
+    pub async fn prepare_with_nonce(
+        &self,
+        state: Arc<RwLock<State>>,
+        nonces: &NonceBlock,
+    ) -> Result<PreparedLimitOrder> {
+        let time = nonces
+            .next()
+            .ok_or_else(|| ProtocolError("Order nonce block is used up"))?;
+        self.prepare_at(state, time).await
+    }
+
+    async fn prepare_at(&self, state: Arc<RwLock<State>>, time: i64) -> Result<PreparedLimitOrder> {
+        let started = Instant::now();
+        let builder = self
+            .make_constructor(state.clone())
+            .await
+            .with_context(|| self.error_context())?;
+        let nonces = builder
+            .make_payload_nonces(state.clone(), time)
+            .await
+            .with_context(|| self.error_context())?;
+        let construction = started.elapsed();
+        let started = Instant::now();
+        let state = state.read().await;
+        let affiliate = state.affiliate_code.clone();
+        let query = builder
+            .signed_graphql_request(nonces, time, affiliate, state.signer()?)
+            .with_context(|| self.error_context())?;
+        let query = serializable_to_json(&query)?;
+        let asset_nonces = [builder.market.asset_a.asset, builder.market.asset_b.asset]
+            .iter()
+            .map(|asset| (asset.name(), current_nonces(&state, asset.name())))
+            .collect();
+        Ok(PreparedLimitOrder {
+            request: self.clone(),
+            query,
+            asset_nonces,
+            time,
+            timings: StageTimings {
+                construction,
+                signing: started.elapsed(),
+                ..Default::default()
+            },
+        })
+    }
+}
+
+fn current_nonces(state: &State, asset: &str) -> Option<Vec<u32>> {
+    state.asset_nonces.as_ref()?.get(asset).cloned()
+}
+
+impl PreparedLimitOrder {
+    pub fn request(&self) -> &LimitOrderRequest {
+        &self.request
+    }
+
+    /// Time since the timestamp the order was signed with
+    pub fn age(&self) -> Duration {
+        Duration::from_millis((current_time_as_i64() - self.time).max(0) as u64)
+    }
+
+    /// Whether the signed order can still be sent as is: asset nonces are unchanged and it
+    /// is younger than `PREPARED_ORDER_MAX_AGE`
+    pub fn is_current(&self, state: &State) -> bool {
+        !state.assets_nonces_refresh
+            && self.age() < PREPARED_ORDER_MAX_AGE
+            && self
+                .asset_nonces
+                .iter()
+                .all(|(asset, nonces)| &current_nonces(state, asset) == nonces)
+    }
+
+    /// The signed mutation, or a freshly signed one if this order is no longer current
+    async fn query(&self, state: Arc<RwLock<State>>) -> Result<(serde_json::Value, StageTimings)> {
+        if self.is_current(&*state.read().await) {
+            return Ok((self.query.clone(), StageTimings::default()));
+        }
+        debug!(market = %self.request.market, age = ?self.age(), "signing stale prepared order again");
+        self.request.prepare(state).await.map(Self::into_query)
+    }
+
+    pub(super) fn into_query(self) -> (serde_json::Value, StageTimings) {
+        (self.query, self.timings)
+    }
+
+    /// The request that places the order
+    pub fn submission(self) -> PreparedSubmission {
+        PreparedSubmission(Arc::new(self))
+    }
+}
+
+/// Placement of a `PreparedLimitOrder`, from `PreparedLimitOrder::submission`. Clones share
+/// the one signed order; they only exist because running a request takes a `Clone` type.
+#[derive(Clone, Debug)]
+pub struct PreparedSubmission(Arc<PreparedLimitOrder>);
+
+#[async_trait]
+impl NashProtocol for PreparedSubmission {
+    type Response = PlaceOrderResponse;
+
+    async fn acquire_permit(
+        &self,
+        state: Arc<RwLock<State>>,
+    ) -> Option<tokio::sync::OwnedSemaphorePermit> {
+        self.0.request.acquire_permit(state).await
+    }
+
+    fn market_affinity(&self) -> Option<&str> {
+        self.0.request.market_affinity()
+    }
+
+    fn limit_price(&self) -> Option<(&str, &str)> {
+        self.0.request.limit_price()
+    }
+
+    fn limit_size(&self) -> Option<(BuyOrSell, &str)> {
+        self.0.request.limit_size()
+    }
+
+    async fn graphql(&self, state: Arc<RwLock<State>>) -> Result<serde_json::Value> {
+        self.0.query(state).await.map(|(query, _)| query)
+    }
+
+    async fn response_from_json(
+        &self,
+        response: serde_json::Value,
+        state: Arc<RwLock<State>>,
+    ) -> Result<ResponseOrError<Self::Response>> {
+        self.0.request.response_from_json(response, state).await
+    }
+
+    async fn process_response(
+        &self,
+        response: &Self::Response,
+        state: Arc<RwLock<State>>,
+    ) -> Result<()> {
+        self.0.request.process_response(response, state).await
+    }
+
+    async fn process_error(
+        &self,
+        response: &ErrorResponse,
+        graphql_request: Option<&serde_json::Value>,
+        state: Arc<RwLock<State>>,
+    ) -> Result<()> {
+        self.0
+            .request
+            .process_error(response, graphql_request, state)
+            .await
+    }
+
+    /// Same hooks as the limit order. They are usually done by the time it was prepared;
+    /// signing states changes the asset nonces, so the order is then signed again.
+    async fn run_before(&self, state: Arc<RwLock<State>>) -> Result<Option<Vec<ProtocolHook>>> {
+        self.0.request.run_before(state).await
+    }
+}
+
+/// Timings are zero unless the order had to be signed again
+#[async_trait]
+impl TimedNashProtocol for PreparedSubmission {
+    async fn graphql_timed(
+        &self,
+        state: Arc<RwLock<State>>,
+    ) -> Result<(serde_json::Value, StageTimings)> {
+        self.0.query(state).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{PreparedLimitOrder, PREPARED_ORDER_MAX_AGE};
+    use crate::protocol::place_order::LimitOrderRequest;
+    use crate::protocol::{StageTimings, State};
+    use crate::types::{BuyOrSell, OrderCancellationPolicy};
+    use crate::utils::current_time_as_i64;
+    use std::collections::HashMap;
+
+    fn prepared(time: i64) -> PreparedLimitOrder {
+        PreparedLimitOrder {
+            request: LimitOrderRequest {
+                market: "eth_usdc".to_string(),
+                client_order_id: None,
+                buy_or_sell: BuyOrSell::Buy,
+                amount: "1".to_string(),
+                price: "2000".to_string(),
+                cancellation_policy: OrderCancellationPolicy::GoodTilCancelled,
+                allow_taker: true,
+            },
+            query: serde_json::Value::Null,
+            asset_nonces: vec![("eth", Some(vec![1])), ("usdc", None)],
+            time,
+            timings: StageTimings::default(),
+        }
+    }
+
+    #[test]
+    fn prepared_orders_go_stale_with_nonces_and_age() {
+        let mut state = State::new(None);
+        let mut nonces = HashMap::new();
+        nonces.insert("eth".to_string(), vec![1]);
+        state.asset_nonces = Some(nonces);
+        let order = prepared(current_time_as_i64());
+        assert!(order.is_current(&state));
+
+        state.assets_nonces_refresh = true;
+        assert!(!order.is_current(&state));
+        state.assets_nonces_refresh = false;
+
+        // states were signed since the order was
+        let nonces = state.asset_nonces.as_mut().unwrap();
+        nonces.insert("eth".to_string(), vec![2]);
+        assert!(!order.is_current(&state));
+        let nonces = state.asset_nonces.as_mut().unwrap();
+        nonces.insert("eth".to_string(), vec![1]);
+        nonces.insert("usdc".to_string(), vec![1]);
+        assert!(!order.is_current(&state));
+        state.asset_nonces.as_mut().unwrap().remove("usdc");
+        assert!(order.is_current(&state));
+
+        let max_age = PREPARED_ORDER_MAX_AGE.as_millis() as i64;
+        assert!(!prepared(current_time_as_i64() - max_age - 1).is_current(&state));
+    }
+}
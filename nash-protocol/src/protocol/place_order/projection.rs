@@ -0,0 +1,252 @@
+//! Reduced response selections for order placement. Latency critical callers often only
+//! need the order id and status back; selecting fewer fields shrinks the response and the
+//! time spent parsing it. `ordersTillSignState` is always selected since the client relies
+//! on it to decide when states have to be signed.
+
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use serde::Deserialize;
+use tokio::sync::RwLock;
+
+use super::types::MarketName;
+use super::{LimitOrderRequest, MarketOrderRequest};
+use crate::errors::{ProtocolError, Result};
+use crate::protocol::{
+    try_response_from_json, ErrorResponse, NashProtocol, ProtocolHook, ResponseOrError, State,
+};
+use crate::types::timestamp::{self, Timestamp};
+use crate::types::{BuyOrSell, OrderStatus, OrderType};
+
+/// Optional fields to select in addition to `id`, `status` and `ordersTillSignState`
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct OrderFields {
+    pub placed_at: bool,
+    pub order_type: bool,
+    pub buy_or_sell: bool,
+    pub market: bool,
+}
+
+impl OrderFields {
+    /// Only `id`, `status` and `ordersTillSignState`
+    pub fn minimal() -> Self {
+        Self::default()
+    }
+
+    /// Same selection as the unprojected request
+    pub fn all() -> Self {
+        Self {
+            placed_at: true,
+            order_type: true,
+            buy_or_sell: true,
+            market: true,
+        }
+    }
+
+    fn selection(&self) -> String {
+        let mut selection = String::from("id status ordersTillSignState");
+        if self.placed_at {
+            selection.push_str(" placedAt");
+        }
+        if self.order_type {
+            selection.push_str(" type");
+        }
+        if self.buy_or_sell {
+            selection.push_str(" buyOrSell");
+        }
+        if self.market {
+            selection.push_str(" market { name }");
+        }
+        selection
+    }
+}
+
+/// Order requests whose response selection can be projected
+pub trait ProjectableOrder: NashProtocol + Clone {
+    /// Name of the GraphQL mutation the request is sent as
+    const MUTATION: &'static str;
+}
+
+impl ProjectableOrder for LimitOrderRequest {
+    const MUTATION: &'static str = "placeLimitOrder";
+}
+
+impl ProjectableOrder for MarketOrderRequest {
+    const MUTATION: &'static str = "placeMarketOrder";
+}
+
+/// Order request that only selects `fields` in its response
+#[derive(Clone, Debug)]
+pub struct Projected<R> {
+    pub request: R,
+    pub fields: OrderFields,
+}
+
+impl<R: ProjectableOrder> Projected<R> {
+    pub fn new(request: R, fields: OrderFields) -> Self {
+        Self { request, fields }
+    }
+}
+
+/// Response of a projected order request. Fields that were not selected are `None`.
+#[derive(Deserialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct ProjectedOrderResponse {
+    #[serde(rename = "id")]
+    pub order_id: String,
+    pub status: OrderStatus,
+    #[serde(rename = "ordersTillSignState")]
+    pub remaining_orders: u64,
+    #[serde(default, with = "timestamp::rfc3339_option")]
+    pub placed_at: Option<Timestamp>,
+    #[serde(default, rename = "type")]
+    pub order_type: Option<OrderType>,
+    #[serde(default)]
+    pub buy_or_sell: Option<BuyOrSell>,
+    #[serde(default)]
+    pub market: Option<MarketName>,
+}
+
+/// Response data of either order mutation
+#[derive(Deserialize)]
+struct ProjectedOrderData {
+    #[serde(rename = "placeLimitOrder", alias = "placeMarketOrder")]
+    order: ProjectedOrderResponse,
+}
+
+impl From<ProjectedOrderData> for ProjectedOrderResponse {
+    fn from(data: ProjectedOrderData) -> Self {
+        data.order
+    }
+}
+
+/// Replace the selection set of every `mutation(...)` call in `query` with `selection`
+pub(crate) fn project_query(query: &str, mutation: &str, selection: &str) -> Result<String> {
+    let call = format!("{}(", mutation);
+    let mut projected = String::with_capacity(query.len());
+    let mut rest = query;
+    while let Some(start) = rest.find(&call) {
+        let args_end = rest[start..]
+            .find(')')
+            .map(|i| start + i + 1)
+            .ok_or(ProtocolError("Unterminated arguments in order mutation"))?;
+        let open = args_end
+            + rest[args_end..]
+                .find('{')
+                .ok_or(ProtocolError("Order mutation has no selection set"))?;
+        let mut depth = 0;
+        let mut close = None;
+        for (i, c) in rest[open..].char_indices() {
+            match c {
+                '{' => depth += 1,
+                '}' => {
+                    depth -= 1;
+                    if depth == 0 {
+                        close = Some(open + i);
+                        break;
+                    }
+                }
+                _ => {}
+            }
+        }
+        let close = close.ok_or(ProtocolError("Unterminated selection set in order mutation"))?;
+        projected.push_str(&rest[..args_end]);
+        projected.push_str(" { ");
+        projected.push_str(selection);
+        projected.push_str(" }");
+        rest = &rest[close + 1..];
+    }
+    if projected.is_empty() {
+        return Err(ProtocolError("Query does not contain the order mutation"));
+    }
+    projected.push_str(rest);
+    Ok(projected)
+}
+
+#[async_trait]
+impl<R: ProjectableOrder> NashProtocol for Projected<R> {
+    type Response = ProjectedOrderResponse;
+
+    async fn acquire_permit(
+        &self,
+        state: Arc<RwLock<State>>,
+    ) -> Option<tokio::sync::OwnedSemaphorePermit> {
+        self.request.acquire_permit(state).await
+    }
+
+    fn market_affinity(&self) -> Option<&str> {
+        self.request.market_affinity()
+    }
+
+    fn limit_price(&self) -> Option<(&str, &str)> {
+        self.request.limit_price()
+    }
+
+    fn limit_size(&self) -> Option<(BuyOrSell, &str)> {
+        self.request.limit_size()
+    }
+
+    async fn graphql(&self, state: Arc<RwLock<State>>) -> Result<serde_json::Value> {
+        let mut graphql = self.request.graphql(state).await?;
+        let query = graphql["query"]
+            .as_str()
+            .ok_or(ProtocolError("Order request has no query document"))?;
+        let projected = project_query(query, R::MUTATION, &self.fields.selection())?;
+        graphql["query"] = serde_json::Value::String(projected);
+        Ok(graphql)
+    }
+
+    async fn response_from_json(
+        &self,
+        response: serde_json::Value,
+        _state: Arc<RwLock<State>>,
+    ) -> Result<ResponseOrError<Self::Response>> {
+        try_response_from_json::<ProjectedOrderResponse, ProjectedOrderData>(response)
+    }
+
+    /// Update the number of orders remaining before state sync
+    async fn process_response(
+        &self,
+        response: &Self::Response,
+        state: Arc<RwLock<State>>,
+    ) -> Result<()> {
+        state.read().await.set_remaining_orders(response.remaining_orders);
+        Ok(())
+    }
+
+    async fn process_error(
+        &self,
+        response: &ErrorResponse,
+        graphql_request: Option<&serde_json::Value>,
+        state: Arc<RwLock<State>>,
+    ) -> Result<()> {
+        self.request
+            .process_error(response, graphql_request, state)
+            .await
+    }
+
+    async fn run_before(&self, state: Arc<RwLock<State>>) -> Result<Option<Vec<ProtocolHook>>> {
+        self.request.run_before(state).await
+    }
+
+    async fn run_after(&self, state: Arc<RwLock<State>>) -> Result<Option<Vec<ProtocolHook>>> {
+        self.request.run_after(state).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{project_query, OrderFields};
+
+    #[test]
+    fn selection_is_replaced() {
+        let query = "mutation PlaceLimitOrder($payload: P!) {\n    placeLimitOrder(payload: $payload) {\n        id\n        market {\n            name\n        },\n        type\n    }\n}";
+        let projected =
+            project_query(query, "placeLimitOrder", &OrderFields::minimal().selection()).unwrap();
+        assert_eq!(
+            projected,
+            "mutation PlaceLimitOrder($payload: P!) {\n    placeLimitOrder(payload: $payload) { id status ordersTillSignState }\n}"
+        );
+        assert!(project_query(query, "placeMarketOrder", "id").is_err());
+    }
+}
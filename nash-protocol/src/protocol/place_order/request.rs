@@ -1,32 +1,68 @@
-use crate::errors::{ProtocolError, Result};
+use crate::errors::{ProtocolError, Result, ResultExt};
 use crate::graphql;
 use crate::graphql::place_limit_order;
 use crate::graphql::place_market_order;
+use crate::graphql::place_stop_limit_order;
 use crate::types::neo::PublicKey as NeoPublicKey;
 use crate::types::PublicKey;
 use crate::types::{
-    Asset, AssetAmount, Blockchain, BuyOrSell, Nonce, OrderCancellationPolicy, OrderRate, Rate
+    AssetAmount, Blockchain, BuyOrSell, Nonce, OrderCancellationPolicy, OrderRate, Rate,
+    RoundingMode, TypedNonce,
 };
 use crate::utils::pad_zeros;
-use graphql_client::GraphQLQuery;
+use crate::protocol::query_overrides::build_query;
+use std::collections::HashMap;
 use std::convert::TryInto;
 
 use super::super::signer::Signer;
 use super::super::{general_canonical_string, RequestPayloadSignature, State};
-use super::blockchain::{btc, eth, neo, FillOrder};
+use super::blockchain::{btc, eth, fill_payload_layout, neo, FillOrder};
 use super::types::{
-    LimitOrderConstructor, LimitOrderRequest,
+    ChainSigningError, LimitOrderConstructor, LimitOrderRequest,
     MarketOrderConstructor, MarketOrderRequest,
-    PayloadNonces
+    PayloadNonces, RateBounds, StopLimitOrderConstructor, StopLimitOrderRequest,
 };
+use bigdecimal::BigDecimal;
 
 use tokio::sync::RwLock;
 use std::sync::Arc;
 
 type LimitOrderMutation = graphql_client::QueryBody<place_limit_order::Variables>;
 type MarketOrderMutation = graphql_client::QueryBody<place_market_order::Variables>;
+type StopLimitOrderMutation = graphql_client::QueryBody<place_stop_limit_order::Variables>;
 type LimitBlockchainSignatures = Vec<Option<place_limit_order::BlockchainSignature>>;
 type MarketBlockchainSignatures = Vec<Option<place_market_order::BlockchainSignature>>;
+type StopLimitBlockchainSignatures = Vec<Option<place_stop_limit_order::BlockchainSignature>>;
+
+/// Sign the fill payloads of an order with `sign`, laid out by `fill_payload_layout`. A
+/// failure is reported as a `ChainSigningError` naming the blockchain and the payload that
+/// could not be signed.
+fn sign_fill_payloads<T>(
+    blockchains: Vec<Blockchain>,
+    signer: &Signer,
+    nonces: &[PayloadNonces],
+    sign: impl Fn(Blockchain, &PublicKey, &PayloadNonces) -> Result<T>,
+) -> Result<Vec<Option<T>>> {
+    let mut pub_keys = HashMap::new();
+    let mut order_payloads = Vec::new();
+    let layout = fill_payload_layout(&blockchains, nonces);
+    for (payload_index, (blockchain, nonce_group)) in layout.into_iter().enumerate() {
+        let failed = |error| ChainSigningError {
+            blockchain,
+            payload_index,
+            error,
+        };
+        if !pub_keys.contains_key(&blockchain) {
+            let pub_key = signer.child_public_key(blockchain).map_err(failed)?;
+            pub_keys.insert(blockchain, pub_key);
+        }
+        let payload = sign(blockchain, &pub_keys[&blockchain], &nonce_group)
+            .with_context(|| format!("nonces {:?}", nonce_group))
+            .map_err(failed)?;
+        order_payloads.push(Some(payload))
+    }
+    Ok(order_payloads)
+}
 
 impl LimitOrderRequest {
     // Buy or sell `amount` of `A` in price of `B` for an A/B market. Returns a builder struct
@@ -34,14 +70,16 @@ impl LimitOrderRequest {
     pub async fn make_constructor(&self, state: Arc<RwLock<State>>) -> Result<LimitOrderConstructor> {
         let state = state.read().await;
         let market = state.get_market(&self.market)?;
+        market.validate_order(&self.amount, Some(&self.price))?;
 
         // Amount of order always in asset A in ME. This will handle precision conversion also...
         let amount_of_a = market.asset_a.with_amount(&self.amount)?;
 
-        // Price is always in terms of asset B in ME
-        // TODO: add precision to rate and handle this better
-        let format_user_price = pad_zeros(&self.price, market.asset_b.precision)?;
-        let b_per_a: Rate = OrderRate::new(&format_user_price)?.into();
+        // Price is always in terms of asset B in ME. It was checked against the precision of
+        // B above, so rounding only pads it with zeros, but it never rounds against the trader
+        let b_per_a: Rate = OrderRate::new(&self.price)?
+            .round(market.asset_b.precision, RoundingMode::for_price(self.buy_or_sell))?
+            .into();
         
         let a_per_b = b_per_a.invert_rate(None)?;
 
@@ -69,10 +107,28 @@ impl LimitOrderRequest {
             source,
             destination,
             rate,
+            fee_rate: state.order_fee_rate(self.allow_taker)?,
         })
     }
 }
 
+impl StopLimitOrderRequest {
+    // Same as for the limit order placed once the stop price is reached, plus the stop
+    // price in terms of B
+    pub async fn make_constructor(&self, state: Arc<RwLock<State>>) -> Result<StopLimitOrderConstructor> {
+        let limit = self.limit_order().make_constructor(state).await?;
+        limit.market.validate_order(&self.amount, Some(&self.stop_price))?;
+        let stop_rate = OrderRate::new(&self.stop_price)?
+            .round(limit.market.asset_b.precision, RoundingMode::for_price(self.buy_or_sell))?;
+        if stop_rate.to_bigdecimal() <= BigDecimal::from(0) {
+            return Err(ProtocolError("Stop price must be positive"));
+        }
+        Ok(StopLimitOrderConstructor {
+            limit,
+            stop_rate: stop_rate.into(),
+        })
+    }
+}
 
 impl MarketOrderRequest {
     // Buy or sell `amount` of `A` in price of `B` for an A/B market. Returns a builder struct
@@ -80,20 +136,13 @@ impl MarketOrderRequest {
     pub async fn make_constructor(&self, state: Arc<RwLock<State>>) -> Result<MarketOrderConstructor> {
         let state = state.read().await;
 
-        let market = match state.get_market(&self.market) {
-            Ok(market) => market,
-            Err(_) => {
-                let reverse_market: Vec<&str> = self.market.split('_').rev().collect();
-                let reverse_market = reverse_market.join("_");
-                match state.get_market(&reverse_market) {
-                    Ok(market) => market.invert(),
-                    Err(err) => return Err(err)
-                }
-            }
-        };
+        // Market orders sell their amount of A, so buying is done on the reversed market
+        let market = state.resolve_market(&self.market.parse()?)?;
+        market.validate_order(&self.amount, None)?;
 
         let source = market.asset_a.with_amount(&self.amount)?;
         let destination =  market.asset_b;
+        let (min_rate, max_rate) = self.signed_rate_bounds()?;
 
         Ok(MarketOrderConstructor {
             client_order_id: self.client_order_id.clone(),
@@ -101,17 +150,45 @@ impl MarketOrderRequest {
             market: market.clone(),
             source,
             destination,
+            min_rate,
+            max_rate,
+            // market orders always take liquidity
+            fee_rate: state.order_fee_rate(true)?,
         })
     }
-}
 
-// If an asset is on another chain, convert it into a crosschain nonce
-// FIXME: maybe Nonce should also keep track of asset type to make this easier?
-fn map_crosschain(nonce: Nonce, chain: Blockchain, asset: Asset) -> Nonce {
-    if asset.blockchain() == chain {
-        nonce
-    } else {
-        Nonce::Crosschain
+    /// Rate bounds exactly as they are signed into the fill payloads of this order
+    pub fn effective_rate_bounds(&self) -> Result<RateBounds> {
+        let (min, max) = self.signed_rate_bounds()?;
+        let bound = |rate: Rate| match rate {
+            Rate::OrderRate(rate) => Some(rate.to_bigdecimal().to_string()),
+            _ => None,
+        };
+        Ok(RateBounds { min: bound(min), max: bound(max) })
+    }
+
+    // Like for limit orders, the minimum leaves room for the maximum fee
+    fn signed_rate_bounds(&self) -> Result<(Rate, Rate)> {
+        let parse = |rate: &str| -> Result<OrderRate> {
+            let rate = OrderRate::new(rate)?;
+            if rate.to_bigdecimal() <= BigDecimal::from(0) {
+                return Err(ProtocolError("Market order rate bounds must be positive"));
+            }
+            Ok(rate)
+        };
+        let min = self.rate_bounds.min.as_deref().map(parse).transpose()?;
+        let max = self.rate_bounds.max.as_deref().map(parse).transpose()?;
+        if let (Some(min), Some(max)) = (&min, &max) {
+            if min > max {
+                return Err(ProtocolError("Market order minimum rate is above its maximum rate"));
+            }
+        }
+        let min = match min {
+            Some(min) => min.subtract_fee(Rate::MaxFeeRate.to_bigdecimal()?).into(),
+            None => Rate::MinOrderRate,
+        };
+        let max = max.map(Rate::from).unwrap_or(Rate::MaxOrderRate);
+        Ok((min, max))
     }
 }
 
@@ -133,15 +210,15 @@ impl LimitOrderConstructor {
         let min_order = min_order
             .subtract_fee(Rate::MaxFeeRate.to_bigdecimal()?)?
             .into();
-        let fee_rate = Rate::MinFeeRate; // 0
+        let fee_rate = self.fee_rate.clone();
 
         match chain {
             Blockchain::Ethereum => Ok(FillOrder::Ethereum(eth::FillOrder::new(
                 pub_key.to_address()?.try_into()?,
                 self.source.asset.into(),
                 self.destination.into(),
-                map_crosschain(nonces.nonce_from, chain, self.source.asset.into()),
-                map_crosschain(nonces.nonce_to, chain, self.destination.into()),
+                nonces.nonce_from.on_chain(chain),
+                nonces.nonce_to.on_chain(chain),
                 amount,
                 min_order,
                 max_order,
@@ -149,8 +226,8 @@ impl LimitOrderConstructor {
                 nonces.order_nonce,
             ))),
             Blockchain::Bitcoin => Ok(FillOrder::Bitcoin(btc::FillOrder::new(
-                map_crosschain(nonces.nonce_from, chain, self.source.asset.into()),
-                map_crosschain(nonces.nonce_to, chain, self.destination.into()),
+                nonces.nonce_from.on_chain(chain),
+                nonces.nonce_to.on_chain(chain),
             ))),
             Blockchain::NEO => {
                 // FIXME: this can still be improved...
@@ -159,8 +236,8 @@ impl LimitOrderConstructor {
                     neo_pub_key,
                     self.source.asset.into(),
                     self.destination.into(),
-                    map_crosschain(nonces.nonce_from, chain, self.source.asset.into()),
-                    map_crosschain(nonces.nonce_to, chain, self.destination.into()),
+                    nonces.nonce_from.on_chain(chain),
+                    nonces.nonce_to.on_chain(chain),
                     amount,
                     min_order,
                     max_order,
@@ -179,16 +256,10 @@ impl LimitOrderConstructor {
         signer: &Signer,
         nonces: &[PayloadNonces],
     ) -> Result<LimitBlockchainSignatures> {
-        let mut order_payloads = Vec::new();
-        let blockchains = self.market.blockchains();
-        for blockchain in blockchains {
-            let pub_key = signer.child_public_key(blockchain)?;
-            for nonce_group in nonces {
-                let fill_order = self.make_fill_order(blockchain, &pub_key, nonce_group)?;
-                order_payloads.push(Some(fill_order.to_blockchain_signature(signer)?))
-            }
-        }
-        Ok(order_payloads)
+        sign_fill_payloads(self.market.blockchains(), signer, nonces, |blockchain, pub_key, nonces| {
+            self.make_fill_order(blockchain, pub_key, nonces)?
+                .to_blockchain_signature(signer)
+        })
     }
 
     /// Create a GraphQL request with everything filled in besides blockchain order payloads
@@ -198,10 +269,8 @@ impl LimitOrderConstructor {
         current_time: i64,
         affiliate: Option<String>,
     ) -> Result<place_limit_order::Variables> {
-        let cancel_at = match self.cancellation_policy {
-            OrderCancellationPolicy::GoodTilTime(time) => Some(format!("{:?}", time)),
-            _ => None,
-        };
+        self.cancellation_policy.check_order(self.allow_taker)?;
+        let cancel_at = self.cancellation_policy.cancel_at(current_time)?;
         let order_args = place_limit_order::Variables {
             payload: place_limit_order::PlaceLimitOrderParams {
                 client_order_id: self.client_order_id.clone(),
@@ -242,7 +311,7 @@ impl LimitOrderConstructor {
         // now compute overall request payload signature
         let canonical_string = limit_order_canonical_string(&variables)?;
         let sig: place_limit_order::Signature =
-            signer.sign_canonical_string(&canonical_string).into();
+            signer.sign_canonical_string(&canonical_string)?.into();
         variables.signature = sig;
         Ok(variables)
     }
@@ -257,7 +326,7 @@ impl LimitOrderConstructor {
         signer: &Signer,
     ) -> Result<LimitOrderMutation> {
         let request = self.sign_graphql_request(self.graphql_request(current_time, affiliate)?, nonces, signer)?;
-        Ok(graphql::PlaceLimitOrder::build_query(request))
+        Ok(build_query::<graphql::PlaceLimitOrder>(request))
     }
 
     // Construct payload nonces with source as `from` asset name and destination as
@@ -271,28 +340,22 @@ impl LimitOrderConstructor {
         let asset_nonces = state.asset_nonces.as_ref()
             .ok_or(ProtocolError("Asset nonce map does not exist"))?;
         let (from, to) = match self.buy_or_sell {
-            BuyOrSell::Buy => (
-                self.market.asset_b.asset.name(),
-                self.market.asset_a.asset.name(),
-            ),
-            BuyOrSell::Sell => (
-                self.market.asset_a.asset.name(),
-                self.market.asset_b.asset.name(),
-            ),
+            BuyOrSell::Buy => (self.market.asset_b.asset, self.market.asset_a.asset),
+            BuyOrSell::Sell => (self.market.asset_a.asset, self.market.asset_b.asset),
         };
-        let nonce_froms: Vec<Nonce> = asset_nonces
-            .get(from)
+        let nonce_froms: Vec<TypedNonce> = asset_nonces
+            .get(from.name())
             .ok_or(ProtocolError("Asset nonce for source does not exist"))?
             .iter()
-            .map(|nonce| Nonce::Value(*nonce))
+            .map(|nonce| TypedNonce::new(from, *nonce))
             .collect();
-        let nonce_tos: Vec<Nonce> = asset_nonces
-            .get(to)
+        let nonce_tos: Vec<TypedNonce> = asset_nonces
+            .get(to.name())
             .ok_or(ProtocolError(
                 "Asset nonce for destination a does not exist",
             ))?
             .iter()
-            .map(|nonce| Nonce::Value(*nonce))
+            .map(|nonce| TypedNonce::new(to, *nonce))
             .collect();
         let mut nonce_combinations = Vec::new();
         for nonce_from in &nonce_froms {
@@ -308,6 +371,93 @@ impl LimitOrderConstructor {
     }
 }
 
+impl StopLimitOrderConstructor {
+    /// Create signed blockchain payloads of the limit order in the format expected by the
+    /// stop limit mutation
+    pub fn blockchain_signatures(
+        &self,
+        signer: &Signer,
+        nonces: &[PayloadNonces],
+    ) -> Result<StopLimitBlockchainSignatures> {
+        sign_fill_payloads(self.limit.market.blockchains(), signer, nonces, |blockchain, pub_key, nonces| {
+            self.limit
+                .make_fill_order(blockchain, pub_key, nonces)?
+                .to_stop_limit_blockchain_signature(signer)
+        })
+    }
+
+    /// Create a GraphQL request with everything filled in besides blockchain order payloads
+    /// and signatures (for both the overall request and blockchain payloads)
+    pub fn graphql_request(
+        &self,
+        current_time: i64,
+        affiliate: Option<String>,
+    ) -> Result<place_stop_limit_order::Variables> {
+        let limit = &self.limit;
+        limit.cancellation_policy.check_order(limit.allow_taker)?;
+        let cancel_at = limit.cancellation_policy.cancel_at(current_time)?;
+        // prices are always in B for an A/B market, see `LimitOrderConstructor::graphql_request`
+        let price = |rate: &Rate| -> Result<place_stop_limit_order::CurrencyPriceParams> {
+            Ok(place_stop_limit_order::CurrencyPriceParams {
+                currency_a: limit.market.asset_b.asset.name().to_string(),
+                currency_b: limit.market.asset_a.asset.name().to_string(),
+                amount: rate.to_bigdecimal()?.to_string(),
+            })
+        };
+        let order_args = place_stop_limit_order::Variables {
+            payload: place_stop_limit_order::PlaceStopLimitOrderParams {
+                client_order_id: limit.client_order_id.clone(),
+                allow_taker: limit.allow_taker,
+                buy_or_sell: limit.buy_or_sell.into(),
+                cancel_at,
+                cancellation_policy: limit.cancellation_policy.into(),
+                market_name: limit.market.market_name(),
+                amount: limit.me_amount.clone().try_into()?,
+                // These two nonces are deprecated...
+                nonce_from: 1234,
+                nonce_to: 1234,
+                nonce_order: (current_time as u32) as i64,
+                timestamp: current_time,
+                limit_price: price(&limit.me_rate)?,
+                stop_price: price(&self.stop_rate)?,
+                blockchain_signatures: vec![],
+            },
+            affiliate,
+            signature: RequestPayloadSignature::empty().into(),
+        };
+        Ok(order_args)
+    }
+
+    pub fn sign_graphql_request(
+        &self,
+        mut variables: place_stop_limit_order::Variables,
+        nonces: Vec<PayloadNonces>,
+        signer: &Signer,
+    ) -> Result<place_stop_limit_order::Variables> {
+        let bc_sigs = self.blockchain_signatures(signer, &nonces)?;
+        variables.payload.blockchain_signatures = bc_sigs;
+        // the stop price is covered by the request signature, not by the fill payloads
+        let canonical_string = stop_limit_order_canonical_string(&variables)?;
+        let sig: place_stop_limit_order::Signature =
+            signer.sign_canonical_string(&canonical_string)?.into();
+        variables.signature = sig;
+        Ok(variables)
+    }
+
+    /// Create a signed GraphQL request with blockchain payloads that can be submitted
+    /// to Nash
+    pub fn signed_graphql_request(
+        &self,
+        nonces: Vec<PayloadNonces>,
+        current_time: i64,
+        affiliate: Option<String>,
+        signer: &Signer,
+    ) -> Result<StopLimitOrderMutation> {
+        let request = self.sign_graphql_request(self.graphql_request(current_time, affiliate)?, nonces, signer)?;
+        Ok(build_query::<graphql::PlaceStopLimitOrder>(request))
+    }
+}
+
 impl MarketOrderConstructor {
     /// Helper to transform a limit order into signed fillorder data on every blockchain
     pub fn make_fill_order(
@@ -316,21 +466,21 @@ impl MarketOrderConstructor {
         pub_key: &PublicKey,
         nonces: &PayloadNonces,
     ) -> Result<FillOrder> {
-        // Rate is in "dest per source", so a higher rate is always beneficial to a user
-        // Here we insure the minimum rate is the rate they specified
-        let min_order = Rate::MinOrderRate;
-        let max_order = Rate::MaxOrderRate;
+        // Rate is in "dest per source", so a higher rate is always beneficial to a user.
+        // Unbounded unless the user asked for protective bounds
+        let min_order = self.min_rate.clone();
+        let max_order = self.max_rate.clone();
         // Amount is specified in the "source" asset
         let amount = self.source.amount.clone();
-        let fee_rate = Rate::MinOrderRate; // 0
+        let fee_rate = self.fee_rate.clone();
 
         match chain {
             Blockchain::Ethereum => Ok(FillOrder::Ethereum(eth::FillOrder::new(
                 pub_key.to_address()?.try_into()?,
                 self.source.asset.into(),
                 self.destination.into(),
-                map_crosschain(nonces.nonce_from, chain, self.source.asset.into()),
-                map_crosschain(nonces.nonce_to, chain, self.destination.into()),
+                nonces.nonce_from.on_chain(chain),
+                nonces.nonce_to.on_chain(chain),
                 amount,
                 min_order,
                 max_order,
@@ -338,8 +488,8 @@ impl MarketOrderConstructor {
                 nonces.order_nonce,
             ))),
             Blockchain::Bitcoin => Ok(FillOrder::Bitcoin(btc::FillOrder::new(
-                map_crosschain(nonces.nonce_from, chain, self.source.asset.into()),
-                map_crosschain(nonces.nonce_to, chain, self.destination.into()),
+                nonces.nonce_from.on_chain(chain),
+                nonces.nonce_to.on_chain(chain),
             ))),
             Blockchain::NEO => {
                 // FIXME: this can still be improved...
@@ -348,8 +498,8 @@ impl MarketOrderConstructor {
                     neo_pub_key,
                     self.source.asset.into(),
                     self.destination.into(),
-                    map_crosschain(nonces.nonce_from, chain, self.source.asset.into()),
-                    map_crosschain(nonces.nonce_to, chain, self.destination.into()),
+                    nonces.nonce_from.on_chain(chain),
+                    nonces.nonce_to.on_chain(chain),
                     amount,
                     min_order,
                     max_order,
@@ -368,16 +518,10 @@ impl MarketOrderConstructor {
         signer: &Signer,
         nonces: &[PayloadNonces],
     ) -> Result<MarketBlockchainSignatures> {
-        let mut order_payloads = Vec::new();
-        let blockchains = self.market.blockchains();
-        for blockchain in blockchains {
-            let pub_key = signer.child_public_key(blockchain)?;
-            for nonce_group in nonces {
-                let fill_order = self.make_fill_order(blockchain, &pub_key, nonce_group)?;
-                order_payloads.push(Some(fill_order.to_market_blockchain_signature(signer)?))
-            }
-        }
-        Ok(order_payloads)
+        sign_fill_payloads(self.market.blockchains(), signer, nonces, |blockchain, pub_key, nonces| {
+            self.make_fill_order(blockchain, pub_key, nonces)?
+                .to_market_blockchain_signature(signer)
+        })
     }
 
     /// Create a GraphQL request with everything filled in besides blockchain order payloads
@@ -418,7 +562,7 @@ impl MarketOrderConstructor {
         // now compute overall request payload signature
         let canonical_string = market_order_canonical_string(&variables)?;
         let sig: place_market_order::Signature =
-            signer.sign_canonical_string(&canonical_string).into();
+            signer.sign_canonical_string(&canonical_string)?.into();
         variables.signature = sig;
         Ok(variables)
     }
@@ -433,7 +577,7 @@ impl MarketOrderConstructor {
         signer: &Signer,
     ) -> Result<MarketOrderMutation> {
         let request = self.sign_graphql_request(self.graphql_request(current_time, affiliate)?, nonces, signer)?;
-        Ok(graphql::PlaceMarketOrder::build_query(request))
+        Ok(build_query::<graphql::PlaceMarketOrder>(request))
     }
 
     // Construct payload nonces with source as `from` asset name and destination as
@@ -446,23 +590,20 @@ impl MarketOrderConstructor {
         let state = state.read().await;
         let asset_nonces = state.asset_nonces.as_ref()
             .ok_or(ProtocolError("Asset nonce map does not exist"))?;
-        let (from, to) = (
-            self.market.asset_a.asset.name(),
-            self.market.asset_b.asset.name(),
-        );
-        let nonce_froms: Vec<Nonce> = asset_nonces
-            .get(from)
+        let (from, to) = (self.market.asset_a.asset, self.market.asset_b.asset);
+        let nonce_froms: Vec<TypedNonce> = asset_nonces
+            .get(from.name())
             .ok_or(ProtocolError("Asset nonce for source does not exist"))?
             .iter()
-            .map(|nonce| Nonce::Value(*nonce))
+            .map(|nonce| TypedNonce::new(from, *nonce))
             .collect();
-        let nonce_tos: Vec<Nonce> = asset_nonces
-            .get(to)
+        let nonce_tos: Vec<TypedNonce> = asset_nonces
+            .get(to.name())
             .ok_or(ProtocolError(
                 "Asset nonce for destination a does not exist",
             ))?
             .iter()
-            .map(|nonce| Nonce::Value(*nonce))
+            .map(|nonce| TypedNonce::new(to, *nonce))
             .collect();
         let mut nonce_combinations = Vec::new();
         for nonce_from in &nonce_froms {
@@ -498,6 +639,16 @@ pub fn market_order_canonical_string(variables: &place_market_order::Variables)
     ))
 }
 
+pub fn stop_limit_order_canonical_string(variables: &place_stop_limit_order::Variables) -> Result<String> {
+    let serialized_all = serde_json::to_string(variables).map_err(|_|ProtocolError("Failed to serialize stop limit order into canonical string"))?;
+
+    Ok(general_canonical_string(
+        "place_stop_limit_order".to_string(),
+        serde_json::from_str(&serialized_all).map_err(|_|ProtocolError("Failed to deserialize stop limit order into canonical string"))?,
+        vec!["blockchain_signatures".to_string()],
+    ))
+}
+
 impl Into<place_market_order::OrderBuyOrSell> for BuyOrSell {
     fn into(self) -> place_market_order::OrderBuyOrSell {
         match self {
@@ -582,3 +733,180 @@ impl TryInto<place_limit_order::CurrencyAmountParams> for AssetAmount {
         })
     }
 }
+
+impl Into<place_stop_limit_order::OrderBuyOrSell> for BuyOrSell {
+    fn into(self) -> place_stop_limit_order::OrderBuyOrSell {
+        match self {
+            BuyOrSell::Buy => place_stop_limit_order::OrderBuyOrSell::BUY,
+            BuyOrSell::Sell => place_stop_limit_order::OrderBuyOrSell::SELL,
+        }
+    }
+}
+
+impl From<RequestPayloadSignature> for place_stop_limit_order::Signature {
+    fn from(sig: RequestPayloadSignature) -> Self {
+        place_stop_limit_order::Signature {
+            signed_digest: sig.signed_digest,
+            public_key: sig.public_key,
+        }
+    }
+}
+
+impl From<OrderCancellationPolicy> for place_stop_limit_order::OrderCancellationPolicy {
+    fn from(policy: OrderCancellationPolicy) -> Self {
+        match policy {
+            OrderCancellationPolicy::FillOrKill => {
+                place_stop_limit_order::OrderCancellationPolicy::FILL_OR_KILL
+            }
+            OrderCancellationPolicy::GoodTilCancelled => {
+                place_stop_limit_order::OrderCancellationPolicy::GOOD_TIL_CANCELLED
+            }
+            OrderCancellationPolicy::GoodTilTime(_) => {
+                place_stop_limit_order::OrderCancellationPolicy::GOOD_TIL_TIME
+            }
+            OrderCancellationPolicy::ImmediateOrCancel => {
+                place_stop_limit_order::OrderCancellationPolicy::IMMEDIATE_OR_CANCEL
+            }
+        }
+    }
+}
+
+impl TryInto<place_stop_limit_order::CurrencyAmountParams> for AssetAmount {
+    type Error = ProtocolError;
+    fn try_into(self) -> Result<place_stop_limit_order::CurrencyAmountParams> {
+        Ok(place_stop_limit_order::CurrencyAmountParams {
+            amount: pad_zeros(
+                &self.amount.to_bigdecimal().to_string(),
+                self.amount.precision,
+            )?,
+            currency: self.asset.asset.name().to_string(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{limit_order_canonical_string, stop_limit_order_canonical_string};
+    use crate::protocol::place_order::types::{LimitOrderConstructor, PayloadNonces};
+    use crate::protocol::place_order::{LimitOrderRequest, StopLimitOrderRequest};
+    use crate::protocol::State;
+    use crate::types::{
+        AccountFeeRates, Amount, Asset, AssetAmount, Blockchain, BuyOrSell, Market, Nonce,
+        OrderCancellationPolicy, PublicKey, TypedNonce,
+    };
+    use bigdecimal::BigDecimal;
+    use std::str::FromStr;
+    use std::sync::Arc;
+    use tokio::sync::RwLock;
+
+    const KEY: &str = "eyJjaGlsZF9rZXlzIjp7fSwKICAgICAgICAicGFpbGxpZXJfcGsiOnsibiI6IjU5ODdlNjIyMjYxY2FmOTZlMjU4MjZjNzBjZjMyM2IyNjE5NGZmOWNmZTY5ZTNmNDBmMzBkMzA2NTcxNjQyY2FlYThhMzE0M2QxMWZmOTRjMTM4ODM2MDQ4NjczNTdhZThjMGU2NjNiZjAzZDAwOTMwMTZkN2Y0ZDc5MGFlMjRlMjkxNzgwM2Q4MTJiNjQxYWYyZDZjMDk1NzNkMTEyZWI3Njg2NDY1MjkxY2QxNDZmZDY2MmY3N2Y1OTVlZjgzMjc3YmUxNjgwZDA0MGIxZjNjNDk5YzgxOTE3NTcyMDZlNTEwYWU1NDcyNGQ2NjdmYzA0MWEyYzdjMmZmM2QzYjY2YzM3MjlkYzI1ZTAyYzQwMTllZDNhMDEyZmQ3NWVjMGUwMzk0OGNmNzgzYWQzOTAyY2U1ZTVlNzIyMjljM2RkM2ExNGI5MzRkNjAyNjlhY2I3YmEwYmQ0MTVkMmRlMTI4ZWYxODcyMjQwMGJhZWEyZTg1MGU2ZDFmZDg3ODdhMDEzMGQ1MTYyMDZkNzE4YTQ5ZDdhMjFkNDI4YjBmYTM3NzMwNzliNjQ4NjE4MTExOTFiNTUwMDFkNGMyYzI5ZjYzMDMxNGJlMTkxY2YzY2EzZjBmOGUwOWVlMDk1NDNmZmRkYTNmOTdjZjE2OWQ1MmUwNjdjZmQ0MGNiMzAzOTQxIn0sCiAgICAgICAgInBheWxvYWRfcHVibGljX2tleSI6IjA0NjE2NDZmZGM0NTQ0ZjEwMjk0ZTIwZTk5NGNlNTZkOGMwZmY4NTI1OTZlYjZiM2FhMGJhOWQ0YjIwNzlkODZkNDJiM2I1ZTg0OTFhNDhmZjZlMTYyMDczMjU3OTgwNzkxNmVlYjA3YmViNmY5OTcwZGM1OTUyYmQ0NDQ0MDRmNzQiLAogICAgICAgICJwYXlsb2FkX3NpZ25pbmdfa2V5IjoiYmI4YmNmNTJhNWY5NDRmMzUxYzViYzg1NmI3YTRjNDFhNWYzNzBmNWNlOTlkY2UwYzhkNmYxZDQ5MWNkMzRiZiIsCiAgICAgICAgInZlcnNpb24iOjB9";
+    const NOW: i64 = 1_600_000_000_000;
+    const ETH_KEY: &str = "04be641c583207c310739a23973fb7cb7336d2b835517ede791e9fa53fa5b0fc46390ebb4dab62e8b01352f37308dbff1512615856bffd3c752db95737d3bc93a4";
+
+    fn state() -> Arc<RwLock<State>> {
+        let (a, b) = (Asset::ETH.with_precision(4), Asset::USDC.with_precision(2));
+        let min_size = |asset| AssetAmount {
+            asset,
+            amount: Amount::new("0.01", 4).unwrap(),
+        };
+        let market = Market::new(a, b, min_size(a), min_size(b));
+        let mut state = State::from_keys(KEY, "").unwrap();
+        state.set_fee_rates(
+            AccountFeeRates::new(BigDecimal::from(0), BigDecimal::from_str("0.0025").unwrap())
+                .unwrap(),
+        );
+        state.markets = Some(Arc::new(
+            vec![(market.market_name(), market)].into_iter().collect(),
+        ));
+        Arc::new(RwLock::new(state))
+    }
+
+    fn stop_limit(stop_price: &str) -> StopLimitOrderRequest {
+        let limit = LimitOrderRequest {
+            market: "eth_usdc".to_string(),
+            client_order_id: Some("Stop-1".to_string()),
+            buy_or_sell: BuyOrSell::Sell,
+            amount: "1.5".to_string(),
+            price: "180".to_string(),
+            cancellation_policy: OrderCancellationPolicy::GoodTilCancelled,
+            allow_taker: true,
+        };
+        StopLimitOrderRequest::new(limit, stop_price).unwrap()
+    }
+
+    #[tokio::test]
+    async fn stop_limit_payload_and_canonical_string() {
+        let state = state();
+        let request = stop_limit("190");
+        let constructor = request.make_constructor(state.clone()).await.unwrap();
+        let variables = constructor.graphql_request(NOW, None).unwrap();
+        let payload = &variables.payload;
+        let decimal = |amount: &str| BigDecimal::from_str(amount).unwrap();
+        assert_eq!(payload.market_name, "eth_usdc");
+        assert_eq!(payload.client_order_id.as_deref(), Some("Stop-1"));
+        assert_eq!(decimal(&payload.amount.amount), decimal("1.5"));
+        assert_eq!(payload.amount.currency, "eth");
+        // both prices are in B
+        assert_eq!(decimal(&payload.limit_price.amount), decimal("180"));
+        assert_eq!(decimal(&payload.stop_price.amount), decimal("190"));
+        assert_eq!(payload.stop_price.currency_a, "usdc");
+        assert_eq!(payload.stop_price.currency_b, "eth");
+        assert_eq!(payload.cancel_at, None);
+        assert_eq!(payload.timestamp, NOW);
+        assert_eq!(payload.nonce_order, (NOW as u32) as i64);
+        assert!(payload.blockchain_signatures.is_empty());
+
+        let canonical = stop_limit_order_canonical_string(&variables).unwrap();
+        let (operation, signed) = canonical.split_at(canonical.find(',').unwrap());
+        assert_eq!(operation, "place_stop_limit_order");
+        let signed: serde_json::Value = serde_json::from_str(&signed[1..]).unwrap();
+        // snake cased and lowercased like the limit order string, without the fill payloads
+        assert_eq!(signed["market_name"], "eth_usdc");
+        assert_eq!(signed["client_order_id"], "stop-1");
+        assert_eq!(signed["buy_or_sell"], "sell");
+        assert_eq!(signed["cancellation_policy"], "good_til_cancelled");
+        assert_eq!(signed["stop_price"]["currency_a"], "usdc");
+        assert_eq!(signed["stop_price"]["currency_b"], "eth");
+        assert_eq!(
+            decimal(signed["stop_price"]["amount"].as_str().unwrap()),
+            decimal("190")
+        );
+        assert!(signed.get("blockchain_signatures").is_none());
+        assert!(signed.get("cancel_at").is_none());
+
+        // the stop price is signed, the rest matches the limit order placed at the stop
+        let limit = request
+            .limit_order()
+            .make_constructor(state.clone())
+            .await
+            .unwrap();
+        let limit_variables = limit.graphql_request(NOW, None).unwrap();
+        let limit_canonical = limit_order_canonical_string(&limit_variables).unwrap();
+        let mut limit_signed: serde_json::Value =
+            serde_json::from_str(&limit_canonical["place_limit_order,".len()..]).unwrap();
+        limit_signed["stop_price"] = signed["stop_price"].clone();
+        assert_eq!(signed, limit_signed);
+        let other_stop = stop_limit("195").make_constructor(state).await.unwrap();
+        let other_stop = other_stop.graphql_request(NOW, None).unwrap();
+        assert_ne!(
+            stop_limit_order_canonical_string(&other_stop).unwrap(),
+            canonical
+        );
+
+        // fill payloads are those of the limit order and don't depend on the stop price
+        let key = PublicKey::new(Blockchain::Ethereum, ETH_KEY).unwrap();
+        let nonces = PayloadNonces {
+            nonce_from: TypedNonce::new(Asset::ETH, 1),
+            nonce_to: TypedNonce::new(Asset::USDC, 2),
+            order_nonce: Nonce::Value(1),
+        };
+        let fill = |constructor: &LimitOrderConstructor| {
+            constructor
+                .make_fill_order(Blockchain::Ethereum, &key, &nonces)
+                .unwrap()
+                .to_hex()
+                .unwrap()
+        };
+        assert_eq!(fill(&constructor.limit), fill(&limit));
+    }
+}
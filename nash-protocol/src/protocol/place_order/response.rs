@@ -1,107 +1,172 @@
 use super::types::PlaceOrderResponse;
+use crate::errors::{ProtocolError, Result};
 use crate::graphql::place_limit_order;
 use crate::graphql::place_market_order;
+use crate::graphql::place_stop_limit_order;
 use crate::types::{BuyOrSell, OrderStatus, OrderType};
-use chrono::{DateTime, Utc};
-use std::str::FromStr;
+use crate::types::timestamp::parse_timestamp;
 use crate::protocol::place_order::types::MarketName;
+use std::convert::{TryFrom, TryInto};
 
-impl From<place_limit_order::ResponseData> for PlaceOrderResponse {
-    fn from(response: place_limit_order::ResponseData) -> Self {
+impl TryFrom<place_limit_order::ResponseData> for PlaceOrderResponse {
+    type Error = ProtocolError;
+
+    fn try_from(response: place_limit_order::ResponseData) -> Result<Self> {
         let response = response.place_limit_order;
-        Self {
-            status: response.status.into(),
+        Ok(Self {
+            status: response.status.try_into()?,
             order_id: response.id,
             remaining_orders: response.orders_till_sign_state as u64,
-            placed_at: DateTime::<Utc>::from_str(&response.placed_at)
-                .expect("ME returned invalid placed_at DateTime for PlaceOrderResponse"),
-            order_type: response.type_.into(),
-            buy_or_sell: response.buy_or_sell.into(),
+            placed_at: parse_timestamp(&response.placed_at)?,
+            order_type: response.type_.try_into()?,
+            buy_or_sell: response.buy_or_sell.try_into()?,
             market: MarketName {
                 name: response.market.name.clone()
-            }
-        }
+            },
+            rate_bounds: None,
+        })
     }
 }
 
-impl From<place_limit_order::OrderStatus> for OrderStatus {
-    fn from(status: place_limit_order::OrderStatus) -> Self {
+impl TryFrom<place_limit_order::OrderStatus> for OrderStatus {
+    type Error = ProtocolError;
+
+    fn try_from(status: place_limit_order::OrderStatus) -> Result<Self> {
         match status {
-            place_limit_order::OrderStatus::PENDING => Self::Pending,
-            place_limit_order::OrderStatus::CANCELLED => Self::Canceled,
-            place_limit_order::OrderStatus::OPEN => Self::Open,
-            place_limit_order::OrderStatus::FILLED => Self::Filled,
+            place_limit_order::OrderStatus::PENDING => Ok(Self::Pending),
+            place_limit_order::OrderStatus::CANCELLED => Ok(Self::Canceled),
+            place_limit_order::OrderStatus::OPEN => Ok(Self::Open),
+            place_limit_order::OrderStatus::FILLED => Ok(Self::Filled),
             // This should never happen. Seems to be generated by the the rust graphql library, not the schema
             place_limit_order::OrderStatus::Other(_) => {
-                panic!("Order status set to something invalid")
+                Err(ProtocolError("Order status set to something invalid"))
             }
         }
     }
 }
 
-impl From<place_limit_order::OrderBuyOrSell> for BuyOrSell {
-    fn from(response: place_limit_order::OrderBuyOrSell) -> Self {
+impl TryFrom<place_limit_order::OrderBuyOrSell> for BuyOrSell {
+    type Error = ProtocolError;
+
+    fn try_from(response: place_limit_order::OrderBuyOrSell) -> Result<Self> {
         match response {
-            place_limit_order::OrderBuyOrSell::BUY => Self::Buy,
-            place_limit_order::OrderBuyOrSell::SELL => Self::Sell,
-            _ => panic!("Unexpected value in BuyOrSell enum"),
+            place_limit_order::OrderBuyOrSell::BUY => Ok(Self::Buy),
+            place_limit_order::OrderBuyOrSell::SELL => Ok(Self::Sell),
+            _ => Err(ProtocolError("Unexpected value in BuyOrSell enum")),
         }
     }
 }
 
-impl From<place_limit_order::OrderType> for OrderType {
-    fn from(response: place_limit_order::OrderType) -> Self {
+impl TryFrom<place_limit_order::OrderType> for OrderType {
+    type Error = ProtocolError;
+
+    fn try_from(response: place_limit_order::OrderType) -> Result<Self> {
         match response {
-            place_limit_order::OrderType::MARKET => Self::Market,
-            place_limit_order::OrderType::LIMIT => Self::Limit,
-            place_limit_order::OrderType::STOP_MARKET => Self::StopMarket,
-            place_limit_order::OrderType::STOP_LIMIT => Self::StopLimit,
-            _ => panic!("Unexpected value in OrderType enum"),
+            place_limit_order::OrderType::MARKET => Ok(Self::Market),
+            place_limit_order::OrderType::LIMIT => Ok(Self::Limit),
+            place_limit_order::OrderType::STOP_MARKET => Ok(Self::StopMarket),
+            place_limit_order::OrderType::STOP_LIMIT => Ok(Self::StopLimit),
+            _ => Err(ProtocolError("Unexpected value in OrderType enum")),
         }
     }
 }
 
 
 
-impl From<place_market_order::ResponseData> for PlaceOrderResponse {
-    fn from(response: place_market_order::ResponseData) -> Self {
+impl TryFrom<place_market_order::ResponseData> for PlaceOrderResponse {
+    type Error = ProtocolError;
+
+    fn try_from(response: place_market_order::ResponseData) -> Result<Self> {
         let response = response.place_market_order;
-        Self {
-            status: response.status.into(),
+        Ok(Self {
+            status: response.status.try_into()?,
             order_id: response.id,
             remaining_orders: response.orders_till_sign_state as u64,
-            placed_at: DateTime::<Utc>::from_str(&response.placed_at)
-                .expect("ME returned invalid placed_at DateTime for PlaceOrderResponse"),
+            placed_at: parse_timestamp(&response.placed_at)?,
             order_type: OrderType::Market,
-            buy_or_sell: response.buy_or_sell.into(),
+            buy_or_sell: response.buy_or_sell.try_into()?,
             market: MarketName {
                 name: response.market.name.clone()
             },
-        }
+            rate_bounds: None,
+        })
     }
 }
 
-impl From<place_market_order::OrderStatus> for OrderStatus {
-    fn from(status: place_market_order::OrderStatus) -> Self {
+impl TryFrom<place_market_order::OrderStatus> for OrderStatus {
+    type Error = ProtocolError;
+
+    fn try_from(status: place_market_order::OrderStatus) -> Result<Self> {
         match status {
-            place_market_order::OrderStatus::PENDING => Self::Pending,
-            place_market_order::OrderStatus::CANCELLED => Self::Canceled,
-            place_market_order::OrderStatus::OPEN => Self::Open,
-            place_market_order::OrderStatus::FILLED => Self::Filled,
+            place_market_order::OrderStatus::PENDING => Ok(Self::Pending),
+            place_market_order::OrderStatus::CANCELLED => Ok(Self::Canceled),
+            place_market_order::OrderStatus::OPEN => Ok(Self::Open),
+            place_market_order::OrderStatus::FILLED => Ok(Self::Filled),
             // This should never happen. Seems to be generated by the the rust graphql library, not the schema
             place_market_order::OrderStatus::Other(_) => {
-                panic!("Order status set to something invalid")
+                Err(ProtocolError("Order status set to something invalid"))
             }
         }
     }
 }
 
-impl From<place_market_order::OrderBuyOrSell> for BuyOrSell {
-    fn from(response: place_market_order::OrderBuyOrSell) -> Self {
+impl TryFrom<place_market_order::OrderBuyOrSell> for BuyOrSell {
+    type Error = ProtocolError;
+
+    fn try_from(response: place_market_order::OrderBuyOrSell) -> Result<Self> {
         match response {
-            place_market_order::OrderBuyOrSell::BUY => Self::Buy,
-            place_market_order::OrderBuyOrSell::SELL => Self::Sell,
-            _ => panic!("Unexpected value in BuyOrSell enum"),
+            place_market_order::OrderBuyOrSell::BUY => Ok(Self::Buy),
+            place_market_order::OrderBuyOrSell::SELL => Ok(Self::Sell),
+            _ => Err(ProtocolError("Unexpected value in BuyOrSell enum")),
         }
     }
-}
\ No newline at end of file
+}
+
+impl TryFrom<place_stop_limit_order::ResponseData> for PlaceOrderResponse {
+    type Error = ProtocolError;
+
+    fn try_from(response: place_stop_limit_order::ResponseData) -> Result<Self> {
+        let response = response.place_stop_limit_order;
+        Ok(Self {
+            status: response.status.try_into()?,
+            order_id: response.id,
+            remaining_orders: response.orders_till_sign_state as u64,
+            placed_at: parse_timestamp(&response.placed_at)?,
+            order_type: OrderType::StopLimit,
+            buy_or_sell: response.buy_or_sell.try_into()?,
+            market: MarketName {
+                name: response.market.name.clone()
+            },
+            rate_bounds: None,
+        })
+    }
+}
+
+impl TryFrom<place_stop_limit_order::OrderStatus> for OrderStatus {
+    type Error = ProtocolError;
+
+    fn try_from(status: place_stop_limit_order::OrderStatus) -> Result<Self> {
+        match status {
+            place_stop_limit_order::OrderStatus::PENDING => Ok(Self::Pending),
+            place_stop_limit_order::OrderStatus::CANCELLED => Ok(Self::Canceled),
+            place_stop_limit_order::OrderStatus::OPEN => Ok(Self::Open),
+            place_stop_limit_order::OrderStatus::FILLED => Ok(Self::Filled),
+            // This should never happen. Seems to be generated by the the rust graphql library, not the schema
+            place_stop_limit_order::OrderStatus::Other(_) => {
+                Err(ProtocolError("Order status set to something invalid"))
+            }
+        }
+    }
+}
+
+impl TryFrom<place_stop_limit_order::OrderBuyOrSell> for BuyOrSell {
+    type Error = ProtocolError;
+
+    fn try_from(response: place_stop_limit_order::OrderBuyOrSell) -> Result<Self> {
+        match response {
+            place_stop_limit_order::OrderBuyOrSell::BUY => Ok(Self::Buy),
+            place_stop_limit_order::OrderBuyOrSell::SELL => Ok(Self::Sell),
+            _ => Err(ProtocolError("Unexpected value in BuyOrSell enum")),
+        }
+    }
+}
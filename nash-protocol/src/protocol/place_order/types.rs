@@ -1,29 +1,34 @@
 use std::sync::Arc;
+use std::time::Instant;
 
 use async_trait::async_trait;
-use chrono::{DateTime, Utc};
 use tokio::sync::{Mutex, RwLock};
 
 use serde::{Serialize, Deserialize};
 
-use crate::errors::Result;
+use crate::errors::{ProtocolError, Result, ResultExt};
 use crate::graphql::place_limit_order;
 use crate::graphql::place_market_order;
+use crate::graphql::place_stop_limit_order;
 use crate::protocol::ErrorResponse;
 use crate::protocol::{
-    asset_nonces::AssetNoncesRequest, list_markets::ListMarketsRequest, serializable_to_json,
-    sign_all_states::SignAllStates, try_response_from_json, NashProtocol, NashProtocolRequest,
-    ProtocolHook, ResponseOrError, State,
+    asset_nonces::AssetNoncesRequest, get_account_fee_rates::GetAccountFeeRatesRequest,
+    list_markets::ListMarketsRequest, serializable_to_json,
+    sign_all_states::SignAllStates, try_response_from_json, DataResponse, NashProtocol,
+    NashProtocolRequest,
+    ProtocolHook, ResponseOrError, StageTimings, State, TimedNashProtocol,
 };
 use crate::types::{
-    AssetAmount, AssetofPrecision, BuyOrSell, Market, Nonce, OrderCancellationPolicy, OrderStatus,
-    OrderType, Rate,
+    AssetAmount, AssetofPrecision, Blockchain, BuyOrSell, Market, MarketSymbol, Nonce,
+    OrderCancellationPolicy, OrderStatus, OrderType, Rate, TypedNonce,
 };
-use crate::utils::current_time_as_i64;
+use crate::types::timestamp::{self, Timestamp};
+
+use super::prepared::PreparedLimitOrder;
 
 /// Request to place limit orders on Nash exchange. On an A/B market
 /// price amount will always be in terms of A and price in terms of B.
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct LimitOrderRequest {
     pub market: String,
     pub client_order_id: Option<String>,
@@ -34,16 +39,54 @@ pub struct LimitOrderRequest {
     pub allow_taker: bool,
 }
 
+/// Request to place a stop limit order: a limit order that the exchange only puts on the
+/// book once the market trades at `stop_price`. Amount is in A and both prices are in B.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct StopLimitOrderRequest {
+    pub market: String,
+    pub client_order_id: Option<String>,
+    pub buy_or_sell: BuyOrSell,
+    pub amount: String,
+    pub price: String,
+    pub stop_price: String,
+    pub cancellation_policy: OrderCancellationPolicy,
+    pub allow_taker: bool,
+}
+
 #[derive(Clone, Debug)]
 pub struct MarketOrderRequest {
     pub client_order_id: Option<String>,
     pub market: String,
     pub amount: String,
+    /// Protective bounds signed into the fill payloads. Unbounded by default.
+    pub rate_bounds: RateBounds,
+}
+
+/// Bounds on the rate an order may fill at, as the amount of destination asset per unit of
+/// source asset: the price in B for a market order on an A_B market. `None` leaves that side
+/// unbounded. Fills outside the bounds are rejected by the smart contracts.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct RateBounds {
+    pub min: Option<String>,
+    pub max: Option<String>,
+}
+
+/// Which order an error building or signing it is about
+fn order_context(kind: &str, market: &str, client_order_id: &Option<String>) -> String {
+    match client_order_id {
+        Some(id) => format!("{} order on {} with client order id {}", kind, market, id),
+        None => format!("{} order on {}", kind, market),
+    }
 }
 
 impl LimitOrderRequest {
+    pub(crate) fn error_context(&self) -> String {
+        order_context("limit", &self.market, &self.client_order_id)
+    }
+
+    /// `market` is a `MarketSymbol` or a string parsed as one
     pub fn new(
-        market: String,
+        market: impl AsRef<str>,
         buy_or_sell: BuyOrSell,
         amount_a: &str,
         price_b: &str,
@@ -52,7 +95,7 @@ impl LimitOrderRequest {
         client_order_id: Option<String>,
     ) -> Result<Self> {
         Ok(Self {
-            market,
+            market: market.as_ref().parse::<MarketSymbol>()?.into(),
             buy_or_sell,
             amount: amount_a.to_string(),
             price: price_b.to_string(),
@@ -63,14 +106,63 @@ impl LimitOrderRequest {
     }
 }
 
+impl StopLimitOrderRequest {
+    pub(crate) fn error_context(&self) -> String {
+        order_context("stop limit", &self.market, &self.client_order_id)
+    }
+
+    /// Place `limit_order` once the market trades at `stop_price_b`
+    pub fn new(limit_order: LimitOrderRequest, stop_price_b: &str) -> Result<Self> {
+        Ok(Self {
+            market: limit_order.market,
+            client_order_id: limit_order.client_order_id,
+            buy_or_sell: limit_order.buy_or_sell,
+            amount: limit_order.amount,
+            price: limit_order.price,
+            stop_price: stop_price_b.to_string(),
+            cancellation_policy: limit_order.cancellation_policy,
+            allow_taker: limit_order.allow_taker,
+        })
+    }
+
+    /// The limit order placed once the stop price is reached
+    pub fn limit_order(&self) -> LimitOrderRequest {
+        LimitOrderRequest {
+            market: self.market.clone(),
+            client_order_id: self.client_order_id.clone(),
+            buy_or_sell: self.buy_or_sell,
+            amount: self.amount.clone(),
+            price: self.price.clone(),
+            cancellation_policy: self.cancellation_policy,
+            allow_taker: self.allow_taker,
+        }
+    }
+}
+
 impl MarketOrderRequest {
-    pub fn new(market: String, amount_a: &str, client_order_id: Option<String>) -> Result<Self> {
+    pub(crate) fn error_context(&self) -> String {
+        order_context("market", &self.market, &self.client_order_id)
+    }
+
+    /// `market` is a `MarketSymbol` or a string parsed as one. To buy A with B, sell B on the
+    /// reversed market, see `MarketSymbol::reversed`.
+    pub fn new(market: impl AsRef<str>, amount_a: &str, client_order_id: Option<String>) -> Result<Self> {
         Ok(Self {
-            market,
+            market: market.as_ref().parse::<MarketSymbol>()?.into(),
             amount: amount_a.to_string(),
             client_order_id,
+            rate_bounds: RateBounds::default(),
         })
     }
+
+    /// Only fill between `min` and `max`, see `RateBounds`
+    pub fn with_rate_bounds(mut self, min: Option<&str>, max: Option<&str>) -> Self {
+        self.rate_bounds = RateBounds {
+            min: min.map(str::to_string),
+            max: max.map(str::to_string),
+        };
+        self
+    }
 }
 
 /// A helper type for constructing blockchain payloads and GraphQL requests
@@ -87,6 +179,14 @@ pub struct LimitOrderConstructor {
     pub source: AssetAmount,
     pub destination: AssetofPrecision,
     pub rate: Rate,
+    pub fee_rate: Rate,
+}
+
+/// A helper type for constructing stop limit order payloads. Fill payloads are those of the
+/// limit order; the stop price is only part of the signed GraphQL request.
+pub struct StopLimitOrderConstructor {
+    pub limit: LimitOrderConstructor,
+    pub stop_rate: Rate,
 }
 
 pub struct MarketOrderConstructor {
@@ -97,17 +197,41 @@ pub struct MarketOrderConstructor {
     // These fields are for the smart contracts
     pub source: AssetAmount,
     pub destination: AssetofPrecision,
+    pub min_rate: Rate,
+    pub max_rate: Rate,
+    pub fee_rate: Rate,
 }
 
 /// Helper type to hold all nonces for payload construction and make
-/// passing them as arguments more descriptive.
+/// passing them as arguments more descriptive. Asset nonces keep their asset, so they
+/// map to crosschain nonces on their own when signed for another blockchain.
 #[derive(Clone, Debug, Copy)]
 pub struct PayloadNonces {
-    pub nonce_from: Nonce,
-    pub nonce_to: Nonce,
+    pub nonce_from: TypedNonce,
+    pub nonce_to: TypedNonce,
     pub order_nonce: Nonce,
 }
 
+/// Signing the fill payloads of an order failed on one blockchain, e.g. because the API
+/// keys have no child key for it. Find it in the sources of the `ProtocolError` returned
+/// while placing an order with `ProtocolError::find_source`. Orders on markets that don't
+/// involve `blockchain` can still be signed.
+#[derive(Clone, Debug, thiserror::Error)]
+#[error("Could not sign fill payload {payload_index} for {blockchain:?}")]
+pub struct ChainSigningError {
+    pub blockchain: Blockchain,
+    /// Position of the payload among the order's blockchain signatures
+    pub payload_index: usize,
+    #[source]
+    pub error: ProtocolError,
+}
+
+impl From<ChainSigningError> for ProtocolError {
+    fn from(error: ChainSigningError) -> Self {
+        ProtocolError::with_source("Signing order failed", error)
+    }
+}
+
 #[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct MarketName {
     pub name: String
@@ -122,11 +246,23 @@ pub struct PlaceOrderResponse {
     #[serde(rename = "id")]
     pub order_id: String,
     pub status: OrderStatus,
-    pub placed_at: DateTime<Utc>,
+    #[serde(with = "timestamp::rfc3339")]
+    pub placed_at: Timestamp,
     #[serde(rename = "type")]
     pub order_type: OrderType,
     pub buy_or_sell: BuyOrSell,
     pub market: MarketName,
+    /// Rate bounds the fill payloads were signed with, for market orders. The minimum
+    /// includes the allowance for the maximum fee.
+    #[serde(skip)]
+    pub rate_bounds: Option<RateBounds>,
+}
+
+impl PlaceOrderResponse {
+    /// Resolve the market the order was placed on into the `Market` known to `state`
+    pub fn market(&self, state: &State) -> Result<Market> {
+        state.get_market(&self.market.name)
+    }
 }
 
 async fn get_required_hooks(state: Arc<RwLock<State>>, market: &str) -> Result<Vec<ProtocolHook>> {
@@ -142,6 +278,12 @@ async fn get_required_hooks(state: Arc<RwLock<State>>, market: &str) -> Result<V
         }
         _ => {}
     }
+    // Fee rates are signed into the order, so they must be current
+    if state.fee_rates_stale() {
+        hooks.push(ProtocolHook::Protocol(NashProtocolRequest::FeeRates(
+            GetAccountFeeRatesRequest,
+        )));
+    }
     // If have run out of r values, get more before running this pipeline
     let chains = state.get_market(market)?.blockchains();
     let fill_pool_schedules = state
@@ -193,15 +335,20 @@ impl NashProtocol for LimitOrderRequest {
             .ok()
     }
 
+    fn market_affinity(&self) -> Option<&str> {
+        Some(&self.market)
+    }
+
+    fn limit_price(&self) -> Option<(&str, &str)> {
+        Some((&self.market, &self.price))
+    }
+
+    fn limit_size(&self) -> Option<(BuyOrSell, &str)> {
+        Some((self.buy_or_sell, &self.amount))
+    }
+
     async fn graphql(&self, state: Arc<RwLock<State>>) -> Result<serde_json::Value> {
-        let builder = self.make_constructor(state.clone()).await?;
-        let time = current_time_as_i64();
-        let nonces = builder.make_payload_nonces(state.clone(), time).await?;
-        let state = state.read().await;
-        let affiliate = state.affiliate_code.clone();
-        let query = builder.signed_graphql_request(nonces, time, affiliate, state.signer()?)?;
-        let json = serializable_to_json(&query);
-        json
+        self.graphql_timed(state).await.map(|(query, _)| query)
     }
 
     async fn response_from_json(
@@ -264,14 +411,12 @@ impl NashProtocol for MarketOrderRequest {
             .ok()
     }
 
+    fn market_affinity(&self) -> Option<&str> {
+        Some(&self.market)
+    }
+
     async fn graphql(&self, state: Arc<RwLock<State>>) -> Result<serde_json::Value> {
-        let builder = self.make_constructor(state.clone()).await?;
-        let time = current_time_as_i64();
-        let nonces = builder.make_payload_nonces(state.clone(), time).await?;
-        let state = state.read().await;
-        let affiliate = state.affiliate_code.clone();
-        let query = builder.signed_graphql_request(nonces, time, affiliate, state.signer()?)?;
-        serializable_to_json(&query)
+        self.graphql_timed(state).await.map(|(query, _)| query)
     }
 
     async fn response_from_json(
@@ -279,7 +424,15 @@ impl NashProtocol for MarketOrderRequest {
         response: serde_json::Value,
         _state: Arc<RwLock<State>>,
     ) -> Result<ResponseOrError<Self::Response>> {
-        try_response_from_json::<PlaceOrderResponse, place_market_order::ResponseData>(response)
+        let response =
+            try_response_from_json::<PlaceOrderResponse, place_market_order::ResponseData>(response)?;
+        Ok(match response {
+            ResponseOrError::Response(DataResponse { mut data }) => {
+                data.rate_bounds = Some(self.effective_rate_bounds()?);
+                ResponseOrError::from_data(data)
+            }
+            error => error,
+        })
     }
 
     /// Update the number of orders remaining before state sync
@@ -310,3 +463,151 @@ impl NashProtocol for MarketOrderRequest {
         get_required_hooks(state, &self.market).await.map(Some)
     }
 }
+
+#[async_trait]
+impl NashProtocol for StopLimitOrderRequest {
+    type Response = PlaceOrderResponse;
+
+    async fn acquire_permit(
+        &self,
+        state: Arc<RwLock<State>>,
+    ) -> Option<tokio::sync::OwnedSemaphorePermit> {
+        state
+            .read()
+            .await
+            .place_order_semaphore
+            .clone()
+            .acquire_owned()
+            .await
+            .ok()
+    }
+
+    fn market_affinity(&self) -> Option<&str> {
+        Some(&self.market)
+    }
+
+    fn limit_price(&self) -> Option<(&str, &str)> {
+        Some((&self.market, &self.price))
+    }
+
+    fn limit_size(&self) -> Option<(BuyOrSell, &str)> {
+        Some((self.buy_or_sell, &self.amount))
+    }
+
+    async fn graphql(&self, state: Arc<RwLock<State>>) -> Result<serde_json::Value> {
+        self.graphql_timed(state).await.map(|(query, _)| query)
+    }
+
+    async fn response_from_json(
+        &self,
+        response: serde_json::Value,
+        _state: Arc<RwLock<State>>,
+    ) -> Result<ResponseOrError<Self::Response>> {
+        try_response_from_json::<PlaceOrderResponse, place_stop_limit_order::ResponseData>(
+            response,
+        )
+    }
+
+    /// Update the number of orders remaining before state sync
+    async fn process_response(
+        &self,
+        response: &Self::Response,
+        state: Arc<RwLock<State>>,
+    ) -> Result<()> {
+        let state = state.read().await;
+        state.set_remaining_orders(response.remaining_orders);
+        Ok(())
+    }
+
+    async fn process_error(
+        &self,
+        _response: &ErrorResponse,
+        _graphql_request: Option<&serde_json::Value>,
+        state: Arc<RwLock<State>>,
+    ) -> Result<()> {
+        state.read().await.decr_remaining_orders();
+        Ok(())
+    }
+
+    /// Potentially get more r values or sign states before placing an order
+    async fn run_before(&self, state: Arc<RwLock<State>>) -> Result<Option<Vec<ProtocolHook>>> {
+        get_required_hooks(state, &self.market).await.map(Some)
+    }
+}
+
+#[async_trait]
+impl TimedNashProtocol for LimitOrderRequest {
+    async fn graphql_timed(
+        &self,
+        state: Arc<RwLock<State>>,
+    ) -> Result<(serde_json::Value, StageTimings)> {
+        self.prepare(state).await.map(PreparedLimitOrder::into_query)
+    }
+}
+
+#[async_trait]
+impl TimedNashProtocol for MarketOrderRequest {
+    async fn graphql_timed(
+        &self,
+        state: Arc<RwLock<State>>,
+    ) -> Result<(serde_json::Value, StageTimings)> {
+        let started = Instant::now();
+        let builder = self
+            .make_constructor(state.clone())
+            .await
+            .with_context(|| self.error_context())?;
+        let time = state.read().await.reserve_order_times(1);
+        let nonces = builder
+            .make_payload_nonces(state.clone(), time)
+            .await
+            .with_context(|| self.error_context())?;
+        let construction = started.elapsed();
+        let started = Instant::now();
+        let state = state.read().await;
+        let affiliate = state.affiliate_code.clone();
+        let query = builder
+            .signed_graphql_request(nonces, time, affiliate, state.signer()?)
+            .with_context(|| self.error_context())?;
+        let json = serializable_to_json(&query)?;
+        let timings = StageTimings {
+            construction,
+            signing: started.elapsed(),
+            ..Default::default()
+        };
+        Ok((json, timings))
+    }
+}
+
+#[async_trait]
+impl TimedNashProtocol for StopLimitOrderRequest {
+    async fn graphql_timed(
+        &self,
+        state: Arc<RwLock<State>>,
+    ) -> Result<(serde_json::Value, StageTimings)> {
+        let started = Instant::now();
+        let builder = self
+            .make_constructor(state.clone())
+            .await
+            .with_context(|| self.error_context())?;
+        let time = state.read().await.reserve_order_times(1);
+        let nonces = builder
+            .limit
+            .make_payload_nonces(state.clone(), time)
+            .await
+            .with_context(|| self.error_context())?;
+        let construction = started.elapsed();
+        let started = Instant::now();
+        let state = state.read().await;
+        let affiliate = state.affiliate_code.clone();
+        let query = builder
+            .signed_graphql_request(nonces, time, affiliate, state.signer()?)
+            .with_context(|| self.error_context())?;
+        let json = serializable_to_json(&query)?;
+        let timings = StageTimings {
+            construction,
+            signing: started.elapsed(),
+            ..Default::default()
+        };
+        Ok((json, timings))
+    }
+}
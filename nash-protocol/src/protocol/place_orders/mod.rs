@@ -1,7 +1,13 @@
 //! Place multiple orders.
 
+// Order placement returns errors on bad input instead of panicking
+#![cfg_attr(not(test), deny(clippy::unwrap_used, clippy::expect_used, clippy::panic))]
+
 mod request;
 mod response;
 mod types;
 
-pub use types::{LimitOrdersRequest, MarketOrdersRequest, PlaceOrdersResponse};
+pub use types::{
+    AmendOrderRequest, AmendOrderResponse, LimitOrdersRequest, MarketOrdersRequest,
+    MixedOrder, MixedOrdersRequest, OcoOrderRequest, OcoOrderResponse, OrderPlaced, OrderRejected, PlaceOrdersResponse,
+};
@@ -1,25 +1,33 @@
-use crate::errors::Result;
+use crate::errors::{Result, ResultExt};
 use crate::graphql::place_limit_order;
 use crate::graphql::place_market_order;
 use super::super::State;
 use super::types::{
     LimitOrdersConstructor, LimitOrdersRequest,
-    MarketOrdersConstructor, MarketOrdersRequest
+    MarketOrdersConstructor, MarketOrdersRequest,
+    MixedOrder, MixedOrderConstructor, MixedOrdersConstructor, MixedOrdersRequest,
+    OcoOrderConstructor, OcoOrderRequest,
 };
 
 use tokio::sync::RwLock;
 use std::sync::Arc;
 
+use serde::Serialize;
 use std::collections::HashMap;
 use crate::protocol::multi_request::DynamicQueryBody;
+use crate::protocol::serializable_to_json;
 
 impl LimitOrdersRequest {
     // Buy or sell `amount` of `A` in price of `B` for an A/B market. Returns a builder struct
     // of `LimitOrderConstructor` that can be used to create smart contract and graphql payloads
     pub async fn make_constructor(&self, state: Arc<RwLock<State>>) -> Result<LimitOrdersConstructor> {
         let mut constructors = Vec::new();
-        for request in &self.requests {
-            constructors.push(request.make_constructor(state.clone()).await?);
+        for (index, request) in self.requests.iter().enumerate() {
+            let constructor = request
+                .make_constructor(state.clone())
+                .await
+                .with_context(|| format!("order {} in batch: {}", index, request.error_context()))?;
+            constructors.push(constructor);
         }
         Ok(LimitOrdersConstructor { constructors })
     }
@@ -30,13 +38,118 @@ impl MarketOrdersRequest {
     // of `MarketOrderConstructor` that can be used to create smart contract and graphql payloads
     pub async fn make_constructor(&self, state: Arc<RwLock<State>>) -> Result<MarketOrdersConstructor> {
         let mut constructors = Vec::new();
-        for request in &self.requests {
-            constructors.push(request.make_constructor(state.clone()).await?);
+        for (index, request) in self.requests.iter().enumerate() {
+            let constructor = request
+                .make_constructor(state.clone())
+                .await
+                .with_context(|| format!("order {} in batch: {}", index, request.error_context()))?;
+            constructors.push(constructor);
         }
         Ok(MarketOrdersConstructor { constructors })
     }
 }
 
+impl MixedOrdersRequest {
+    // Returns a builder struct holding the constructor of every order, of either type
+    pub async fn make_constructor(&self, state: Arc<RwLock<State>>) -> Result<MixedOrdersConstructor> {
+        let mut constructors = Vec::new();
+        for (index, request) in self.requests.iter().enumerate() {
+            let constructor = match request {
+                MixedOrder::Limit(request) => request
+                    .make_constructor(state.clone())
+                    .await
+                    .map(MixedOrderConstructor::Limit),
+                MixedOrder::Market(request) => request
+                    .make_constructor(state.clone())
+                    .await
+                    .map(MixedOrderConstructor::Market),
+            };
+            let constructor = constructor
+                .with_context(|| format!("order {} in batch: {}", index, request.error_context()))?;
+            constructors.push(constructor);
+        }
+        Ok(MixedOrdersConstructor { constructors })
+    }
+}
+
+impl OcoOrderRequest {
+    // Returns a builder struct holding the constructors of both orders of the pair
+    pub async fn make_constructor(&self, state: Arc<RwLock<State>>) -> Result<OcoOrderConstructor> {
+        Ok(OcoOrderConstructor {
+            take_profit: self.take_profit.make_constructor(state.clone()).await?,
+            stop: self.stop.make_constructor(state).await?,
+        })
+    }
+}
+
+/// Fields returned for every order placed by a multi-order mutation
+const PLACED_ORDER_FIELDS: &str = r#"{
+                    id
+                    status
+                    ordersTillSignState,
+                    buyOrSell,
+                    market {
+                        name
+                    },
+                    placedAt,
+                    type
+                }"#;
+
+/// Parameter declarations, aliased calls and variables of a mutation placing several
+/// orders, which can be of different types
+#[derive(Default)]
+struct OrderCalls {
+    params: Vec<String>,
+    calls: String,
+    variables: HashMap<String, serde_json::Value>,
+}
+
+impl OrderCalls {
+    /// Add a call placing a signed order as `response{index}`. `mutation` is the field
+    /// called, e.g. `placeLimitOrder`, and `params_type` the type of its payload.
+    fn push<P: Serialize, S: Serialize, A: Serialize>(
+        &mut self,
+        index: usize,
+        mutation: &str,
+        params_type: &str,
+        payload: P,
+        signature: S,
+        affiliate: A,
+    ) -> Result<()> {
+        let payload_name = format!("payload{}", index);
+        let signature_name = format!("signature{}", index);
+        let affiliate_name = format!("affiliate{}", index);
+        self.params.push(format!(
+            "${}: {}!, ${}: Signature!, ${}: AffiliateDeveloperCode",
+            payload_name, params_type, signature_name, affiliate_name
+        ));
+        self.calls = format!(r#"
+                {}
+                response{}: {}(payload: ${}, signature: ${}, affiliateDeveloperCode: ${}) {}
+                "#, self.calls, index, mutation, payload_name, signature_name, affiliate_name, PLACED_ORDER_FIELDS);
+        self.variables.insert(payload_name, serializable_to_json(&payload)?);
+        self.variables.insert(signature_name, serializable_to_json(&signature)?);
+        self.variables.insert(affiliate_name, serializable_to_json(&affiliate)?);
+        Ok(())
+    }
+
+    fn params(&self) -> String {
+        self.params.join(", ")
+    }
+
+    fn into_mutation(self, operation_name: &'static str) -> DynamicQueryBody {
+        DynamicQueryBody {
+            query: format!(r#"
+                mutation {}({}) {{
+                    {}
+                }}
+            "#, operation_name, self.params(), self.calls),
+            variables: self.variables,
+            operation_name,
+        }
+    }
+}
+
 impl LimitOrdersConstructor {
     /// Create a GraphQL request with everything filled in besides blockchain order payloads
     /// and signatures (for both the overall request and blockchain payloads)
@@ -60,50 +173,38 @@ impl LimitOrdersConstructor {
         affiliate: Option<String>,
         state: Arc<RwLock<State>>,
     ) -> Result<DynamicQueryBody> {
+        let calls = self.signed_calls(current_time, affiliate, state, 0).await?;
+        Ok(calls.into_mutation("PlaceLimitOrder"))
+    }
+
+    /// Sign every order and add its `placeLimitOrder` call. Calls are aliased
+    /// `response{first_index}`, `response{first_index + 1}`, ... so that other calls can go
+    /// before them.
+    async fn signed_calls(
+        &self,
+        current_time: i64,
+        affiliate: Option<String>,
+        state: Arc<RwLock<State>>,
+        first_index: usize,
+    ) -> Result<OrderCalls> {
         let variables = self.graphql_request(current_time, affiliate)?;
-        let mut map = HashMap::new();
-        let mut params = String::new();
-        let mut calls = String::new();
-        for (index, (variable, constructor)) in variables.into_iter().zip(self.constructors.iter()).enumerate() {
+        let mut calls = OrderCalls::default();
+        for (offset, (variable, constructor)) in variables.into_iter().zip(self.constructors.iter()).enumerate() {
             // FIXME: This current_time + index for nonces is replicated in graphql_request. We would benefit to abstract this logic somewhere.
-            let nonces = constructor.make_payload_nonces(state.clone(), current_time + index as i64).await?;
+            let nonces = constructor.make_payload_nonces(state.clone(), current_time + offset as i64).await?;
             let state = state.read().await;
             let signer = state.signer()?;
             let variable = constructor.sign_graphql_request(variable, nonces, signer)?;
-
-            // FIXME: This is also replicated in MarketOrdersConstructor::signed_graphql_request
-            let payload = format!("payload{}", index);
-            let signature = format!("signature{}", index);
-            let affiliate = format!("affiliate{}", index);
-            params = if index == 0 { params } else { format!("{}, ", params)};
-            params = format!("{}${}: PlaceLimitOrderParams!, ${}: Signature!, ${}: AffiliateDeveloperCode", params, payload, signature, affiliate);
-            calls = format!(r#"
-                {}
-                response{}: placeLimitOrder(payload: ${}, signature: ${}, affiliateDeveloperCode: ${}) {{
-                    id
-                    status
-                    ordersTillSignState,
-                    buyOrSell,
-                    market {{
-                        name
-                    }},
-                    placedAt,
-                    type
-                }}
-                "#, calls, index, payload, signature, affiliate);
-            map.insert(payload, serde_json::to_value(variable.payload).unwrap());
-            map.insert(signature, serde_json::to_value(variable.signature).unwrap());
-            map.insert(affiliate, serde_json::to_value(variable.affiliate).unwrap());
+            calls.push(
+                first_index + offset,
+                "placeLimitOrder",
+                "PlaceLimitOrderParams",
+                variable.payload,
+                variable.signature,
+                variable.affiliate,
+            )?;
         }
-        Ok(DynamicQueryBody {
-            variables: map,
-            operation_name: "PlaceLimitOrder",
-            query: format!(r#"
-                mutation PlaceLimitOrder({}) {{
-                    {}
-                }}
-            "#, params, calls)
-        })
+        Ok(calls)
     }
 }
 
@@ -131,47 +232,150 @@ impl MarketOrdersConstructor {
         state: Arc<RwLock<State>>,
     ) -> Result<DynamicQueryBody> {
         let variables = self.graphql_request(current_time, affiliate)?;
-        let mut map = HashMap::new();
-        let mut params = String::new();
-        let mut calls = String::new();
+        let mut calls = OrderCalls::default();
         for (index, (variable, constructor)) in variables.into_iter().zip(self.constructors.iter()).enumerate() {
             // FIXME: This current_time + index for nonces is replicated in graphql_request. We would benefit to abstract this logic somewhere.
             let nonces = constructor.make_payload_nonces(state.clone(), current_time + index as i64).await?;
             let state = state.read().await;
             let signer = state.signer()?;
             let variable = constructor.sign_graphql_request(variable, nonces, signer)?;
+            calls.push(
+                index,
+                "placeMarketOrder",
+                "PlaceMarketOrderParams",
+                variable.payload,
+                variable.signature,
+                variable.affiliate,
+            )?;
+        }
+        Ok(calls.into_mutation("PlaceMarketOrder"))
+    }
+}
 
-            let payload = format!("payload{}", index);
-            let signature = format!("signature{}", index);
-            let affiliate = format!("affiliate{}", index);
-            params = if index == 0 { params } else { format!("{}, ", params)};
-            params = format!("{}${}: PlaceMarketOrderParams!, ${}: Signature!, ${}: AffiliateDeveloperCode", params, payload, signature, affiliate);
-            calls = format!(r#"
-                {}
-                response{}: placeMarketOrder(payload: ${}, signature: ${}, affiliateDeveloperCode: ${}) {{
+impl MixedOrdersConstructor {
+    /// Create a signed GraphQL request placing every order, limit and market orders alike,
+    /// that can be submitted to Nash. Order `i` is `response{i}` and uses `current_time + i`
+    /// as its nonce, whatever its type.
+    pub async fn signed_graphql_request(
+        &self,
+        current_time: i64,
+        affiliate: Option<String>,
+        state: Arc<RwLock<State>>,
+    ) -> Result<DynamicQueryBody> {
+        let mut calls = OrderCalls::default();
+        for (index, constructor) in self.constructors.iter().enumerate() {
+            let order_time = current_time + index as i64;
+            match constructor {
+                MixedOrderConstructor::Limit(constructor) => {
+                    let variable = constructor.graphql_request(order_time, affiliate.clone())?;
+                    let nonces = constructor.make_payload_nonces(state.clone(), order_time).await?;
+                    let state = state.read().await;
+                    let variable = constructor.sign_graphql_request(variable, nonces, state.signer()?)?;
+                    calls.push(
+                        index,
+                        "placeLimitOrder",
+                        "PlaceLimitOrderParams",
+                        variable.payload,
+                        variable.signature,
+                        variable.affiliate,
+                    )?;
+                }
+                MixedOrderConstructor::Market(constructor) => {
+                    let variable = constructor.graphql_request(order_time, affiliate.clone())?;
+                    let nonces = constructor.make_payload_nonces(state.clone(), order_time).await?;
+                    let state = state.read().await;
+                    let variable = constructor.sign_graphql_request(variable, nonces, state.signer()?)?;
+                    calls.push(
+                        index,
+                        "placeMarketOrder",
+                        "PlaceMarketOrderParams",
+                        variable.payload,
+                        variable.signature,
+                        variable.affiliate,
+                    )?;
+                }
+            }
+        }
+        Ok(calls.into_mutation("PlaceMixedOrders"))
+    }
+}
+
+impl OcoOrderConstructor {
+    /// Create a signed GraphQL request placing both orders that can be submitted to Nash.
+    /// The take profit order is `response0` and uses `current_time` as its nonce, the stop
+    /// order is `response1` and uses `current_time + 1`.
+    pub async fn signed_graphql_request(
+        &self,
+        current_time: i64,
+        affiliate: Option<String>,
+        state: Arc<RwLock<State>>,
+    ) -> Result<DynamicQueryBody> {
+        let take_profit_nonces = self.take_profit.make_payload_nonces(state.clone(), current_time).await?;
+        let stop_nonces = self.stop.limit.make_payload_nonces(state.clone(), current_time + 1).await?;
+        let state = state.read().await;
+        let signer = state.signer()?;
+        let take_profit = self.take_profit.sign_graphql_request(
+            self.take_profit.graphql_request(current_time, affiliate.clone())?,
+            take_profit_nonces,
+            signer,
+        )?;
+        let stop = self.stop.sign_graphql_request(
+            self.stop.graphql_request(current_time + 1, affiliate)?,
+            stop_nonces,
+            signer,
+        )?;
+
+        let fields = r#"{
                     id
                     status
                     ordersTillSignState,
                     buyOrSell,
-                    market {{
+                    market {
                         name
-                    }},
+                    },
                     placedAt,
                     type
-                }}
-                "#, calls, index, payload, signature, affiliate);
-            map.insert(payload, serde_json::to_value(variable.payload).unwrap());
-            map.insert(signature, serde_json::to_value(variable.signature).unwrap());
-            map.insert(affiliate, serde_json::to_value(variable.affiliate).unwrap());
-        }
+                }"#;
+        let mut map = HashMap::new();
+        map.insert("payload0".to_string(), serializable_to_json(&take_profit.payload)?);
+        map.insert("signature0".to_string(), serializable_to_json(&take_profit.signature)?);
+        map.insert("affiliate0".to_string(), serializable_to_json(&take_profit.affiliate)?);
+        map.insert("payload1".to_string(), serializable_to_json(&stop.payload)?);
+        map.insert("signature1".to_string(), serializable_to_json(&stop.signature)?);
+        map.insert("affiliate1".to_string(), serializable_to_json(&stop.affiliate)?);
         Ok(DynamicQueryBody {
             variables: map,
-            operation_name: "PlaceMarketOrder",
+            operation_name: "PlaceOcoOrder",
             query: format!(r#"
-                mutation PlaceMarketOrder({}) {{
-                    {}
+                mutation PlaceOcoOrder(
+                    $payload0: PlaceLimitOrderParams!, $signature0: Signature!, $affiliate0: AffiliateDeveloperCode,
+                    $payload1: PlaceStopLimitOrderParams!, $signature1: Signature!, $affiliate1: AffiliateDeveloperCode
+                ) {{
+                    response0: placeLimitOrder(payload: $payload0, signature: $signature0, affiliateDeveloperCode: $affiliate0) {fields}
+                    response1: placeStopLimitOrder(payload: $payload1, signature: $signature1, affiliateDeveloperCode: $affiliate1) {fields}
                 }}
-            "#, params, calls)
+            "#, fields = fields)
         })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::OrderCalls;
+
+    #[test]
+    fn order_calls_interleave_mutations() {
+        let mut calls = OrderCalls::default();
+        calls.push(0, "placeLimitOrder", "PlaceLimitOrderParams", "limit", "signature", "").unwrap();
+        calls.push(1, "placeMarketOrder", "PlaceMarketOrderParams", "market", "signature", "").unwrap();
+        let mutation = calls.into_mutation("PlaceMixedOrders");
+        assert_eq!(mutation.variables.len(), 6);
+        assert_eq!(mutation.variables["payload1"], "market");
+        assert!(mutation.query.contains(
+            "mutation PlaceMixedOrders($payload0: PlaceLimitOrderParams!, $signature0: Signature!, \
+             $affiliate0: AffiliateDeveloperCode, $payload1: PlaceMarketOrderParams!"
+        ));
+        assert!(mutation.query.contains("response0: placeLimitOrder(payload: $payload0"));
+        assert!(mutation.query.contains("response1: placeMarketOrder(payload: $payload1"));
+    }
+}
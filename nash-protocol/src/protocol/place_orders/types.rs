@@ -1,18 +1,23 @@
 use std::sync::Arc;
+use std::time::Instant;
 
 use async_trait::async_trait;
 use tokio::sync::{Mutex, RwLock};
 
 
-use crate::errors::Result;
+use crate::errors::{ProtocolError, Result};
 use crate::protocol::ErrorResponse;
 use crate::protocol::{
-    asset_nonces::AssetNoncesRequest, list_markets::ListMarketsRequest, serializable_to_json,
+    asset_nonces::AssetNoncesRequest, get_account_fee_rates::GetAccountFeeRatesRequest,
+    list_markets::ListMarketsRequest, serializable_to_json,
     sign_all_states::SignAllStates, NashProtocol, NashProtocolRequest,
-    ProtocolHook, ResponseOrError, State,
+    ProtocolHook, ResponseOrError, StageTimings, State, TimedNashProtocol,
+};
+use crate::types::BuyOrSell;
+use crate::protocol::cancel_order::{CancelOrderRequest, CancelOrderResponse};
+use crate::protocol::place_order::{
+    LimitOrderRequest, PlaceOrderResponse, MarketOrderRequest, StopLimitOrderRequest,
 };
-use crate::utils::current_time_as_i64;
-use crate::protocol::place_order::{LimitOrderRequest, PlaceOrderResponse, MarketOrderRequest};
 
 /// Request to place limit orders on Nash exchange. On an A/B market
 /// price amount will always be in terms of A and price in terms of B.
@@ -23,14 +28,210 @@ pub type PlaceOrdersResponse = MultiResponse<PlaceOrderResponse>;
 /// price amount will always be in terms of A and price in terms of B.
 pub type MarketOrdersRequest = MultiRequest<MarketOrderRequest>;
 
-use crate::protocol::place_order::types::{LimitOrderConstructor, MarketOrderConstructor};
+use crate::protocol::place_order::types::{
+    LimitOrderConstructor, MarketOrderConstructor, StopLimitOrderConstructor,
+};
 use crate::protocol::multi_request::{MultiRequest, MultiRequestConstructor, MultiResponse};
-use std::convert::TryInto;
+
+/// An order of a batch that the exchange accepted
+pub type OrderPlaced = PlaceOrderResponse;
+
+/// An order of a batch that the exchange rejected. `index` is its position in the request.
+#[derive(Clone, Debug, PartialEq)]
+pub struct OrderRejected {
+    pub index: usize,
+    pub message: String,
+}
+
+impl std::fmt::Display for OrderRejected {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "order {} rejected: {}", self.index, self.message)
+    }
+}
+
+impl PlaceOrdersResponse {
+    /// Outcome of every order of the batch, in the same order as the requests. Orders of a
+    /// batch are accepted or rejected individually, so some may be placed while others fail.
+    pub fn outcomes(self) -> Vec<std::result::Result<OrderPlaced, OrderRejected>> {
+        self.responses
+            .into_iter()
+            .enumerate()
+            .map(|(index, response)| response.map_err(|error| OrderRejected {
+                index,
+                message: error.to_string(),
+            }))
+            .collect()
+    }
+
+    /// Accepted orders, in request order
+    pub fn placed(&self) -> impl Iterator<Item = &OrderPlaced> {
+        self.responses.iter().filter_map(|response| response.as_ref().ok())
+    }
+}
 
 pub type LimitOrdersConstructor = MultiRequestConstructor<LimitOrderConstructor>;
 pub type MarketOrdersConstructor = MultiRequestConstructor<MarketOrderConstructor>;
 
-async fn get_required_hooks(state: Arc<RwLock<State>>, market: &str) -> Result<Vec<ProtocolHook>> {
+/// An order of a batch mixing limit and market orders
+#[derive(Clone, Debug)]
+pub enum MixedOrder {
+    Limit(LimitOrderRequest),
+    Market(MarketOrderRequest),
+}
+
+impl MixedOrder {
+    pub fn market(&self) -> &str {
+        match self {
+            Self::Limit(request) => &request.market,
+            Self::Market(request) => &request.market,
+        }
+    }
+
+    pub(crate) fn error_context(&self) -> String {
+        match self {
+            Self::Limit(request) => request.error_context(),
+            Self::Market(request) => request.error_context(),
+        }
+    }
+}
+
+impl From<LimitOrderRequest> for MixedOrder {
+    fn from(request: LimitOrderRequest) -> Self {
+        Self::Limit(request)
+    }
+}
+
+impl From<MarketOrderRequest> for MixedOrder {
+    fn from(request: MarketOrderRequest) -> Self {
+        Self::Market(request)
+    }
+}
+
+/// Request to place limit and market orders in one signed mutation, e.g. to take liquidity
+/// on one market and quote on another in a single round trip. Orders are placed in the
+/// order given, and each is accepted or rejected on its own, as in `LimitOrdersRequest`.
+pub type MixedOrdersRequest = MultiRequest<MixedOrder>;
+
+/// A helper type for constructing the payload of an order of a mixed batch
+pub enum MixedOrderConstructor {
+    Limit(LimitOrderConstructor),
+    Market(MarketOrderConstructor),
+}
+
+pub type MixedOrdersConstructor = MultiRequestConstructor<MixedOrderConstructor>;
+
+/// A one-cancels-other pair closing the same position: a take-profit limit order and a
+/// protective stop limit order, placed together in one signed mutation.
+///
+/// Nash has no linked order type, so the exchange treats them as two independent orders.
+/// Cancelling the other order once one of them fills is up to the caller, see
+/// `OcoOrderResponse::cancel_sibling`.
+#[derive(Clone, Debug)]
+pub struct OcoOrderRequest {
+    pub take_profit: LimitOrderRequest,
+    pub stop: StopLimitOrderRequest,
+}
+
+impl OcoOrderRequest {
+    /// Both orders must be on the same market and on the same side
+    pub fn new(take_profit: LimitOrderRequest, stop: StopLimitOrderRequest) -> Result<Self> {
+        if take_profit.market != stop.market {
+            return Err(ProtocolError("OCO orders must be on the same market"));
+        }
+        if take_profit.buy_or_sell != stop.buy_or_sell {
+            return Err(ProtocolError("OCO orders must be on the same side"));
+        }
+        Ok(Self { take_profit, stop })
+    }
+}
+
+/// Outcome of both orders of an OCO pair. Each is accepted or rejected on its own, so one
+/// may be on the book while the other was rejected.
+#[derive(Clone, Debug)]
+pub struct OcoOrderResponse {
+    pub market: String,
+    pub take_profit: Result<PlaceOrderResponse>,
+    pub stop: Result<PlaceOrderResponse>,
+}
+
+impl OcoOrderResponse {
+    /// Ids of both orders, if both were placed
+    pub fn order_ids(&self) -> Option<(&str, &str)> {
+        match (&self.take_profit, &self.stop) {
+            (Ok(take_profit), Ok(stop)) => Some((&take_profit.order_id, &stop.order_id)),
+            _ => None,
+        }
+    }
+
+    /// Request cancelling the other order of the pair once `order_id` filled, or `None` if
+    /// `order_id` is not part of the pair or its sibling was never placed
+    pub fn cancel_sibling(&self, order_id: &str) -> Option<CancelOrderRequest> {
+        let (take_profit, stop) = (self.take_profit.as_ref().ok(), self.stop.as_ref().ok());
+        let sibling = if take_profit.map_or(false, |order| order.order_id == order_id) {
+            stop?
+        } else if stop.map_or(false, |order| order.order_id == order_id) {
+            take_profit?
+        } else {
+            return None;
+        };
+        Some(CancelOrderRequest {
+            order_id: sibling.order_id.clone(),
+            market: self.market.clone(),
+        })
+    }
+}
+
+/// A helper type for constructing the payloads of both orders of an OCO pair
+pub struct OcoOrderConstructor {
+    pub take_profit: LimitOrderConstructor,
+    pub stop: StopLimitOrderConstructor,
+}
+
+/// Replace a resting order with a new limit order, e.g. to move a quote to a new price. Run
+/// it with `Client::amend_order`: the order is cancelled first, and the replacement is only
+/// placed once the exchange confirmed the cancellation. If the order filled in the meantime
+/// nothing is placed, so an amendment never adds to what was resting. If the replacement is
+/// rejected the original order stays cancelled. `AmendOrderResponse` reports both outcomes.
+#[derive(Clone, Debug)]
+pub struct AmendOrderRequest {
+    pub cancel: CancelOrderRequest,
+    pub replacement: LimitOrderRequest,
+}
+
+impl AmendOrderRequest {
+    /// Replace `order_id` with `replacement`, on the replacement's market
+    pub fn new(order_id: &str, replacement: LimitOrderRequest) -> Self {
+        Self {
+            cancel: CancelOrderRequest {
+                order_id: order_id.to_string(),
+                market: replacement.market.clone(),
+            },
+            replacement,
+        }
+    }
+
+    /// The replacement to place once the cancellation returned `cancelled`, or `None` unless
+    /// the exchange confirmed cancelling the order being replaced
+    pub fn replacement_after(
+        &self,
+        cancelled: &Result<CancelOrderResponse>,
+    ) -> Option<&LimitOrderRequest> {
+        match cancelled {
+            Ok(response) if response.order_id == self.cancel.order_id => Some(&self.replacement),
+            _ => None,
+        }
+    }
+}
+
+/// Outcome of both calls of an amendment
+#[derive(Clone, Debug)]
+pub struct AmendOrderResponse {
+    pub cancelled: Result<CancelOrderResponse>,
+    pub replacement: Result<PlaceOrderResponse>,
+}
+
+/// Hooks to run before placing orders on `markets`
+async fn get_required_hooks(state: Arc<RwLock<State>>, markets: &[&str]) -> Result<Vec<ProtocolHook>> {
     let state = state.read().await;
 
     let mut hooks = Vec::new();
@@ -43,8 +244,22 @@ async fn get_required_hooks(state: Arc<RwLock<State>>, market: &str) -> Result<V
         }
         _ => {}
     }
-    // If have run out of r values, get more before running this pipeline
-    let chains = state.get_market(market)?.blockchains();
+    // Fee rates are signed into the order, so they must be current
+    if state.fee_rates_stale() {
+        hooks.push(ProtocolHook::Protocol(NashProtocolRequest::FeeRates(
+            GetAccountFeeRatesRequest,
+        )));
+    }
+    // If have run out of r values, get more before running this pipeline, on the chains of
+    // every market orders are placed on
+    let mut chains = Vec::new();
+    for market in markets {
+        for chain in state.get_market(market)?.blockchains() {
+            if !chains.contains(&chain) {
+                chains.push(chain);
+            }
+        }
+    }
     let fill_pool_schedules = state
         .acquire_fill_pool_schedules(Some(&chains), Some(10))
         .await?;
@@ -94,12 +309,26 @@ impl NashProtocol for LimitOrdersRequest {
             .ok()
     }
 
+    fn market_affinity(&self) -> Option<&str> {
+        self.requests.first().map(|request| request.market.as_str())
+    }
+
+    fn limit_prices(&self) -> Vec<(&str, &str)> {
+        self.requests
+            .iter()
+            .map(|request| (request.market.as_str(), request.price.as_str()))
+            .collect()
+    }
+
+    fn limit_sizes(&self) -> Vec<(BuyOrSell, &str)> {
+        self.requests
+            .iter()
+            .map(|request| (request.buy_or_sell, request.amount.as_str()))
+            .collect()
+    }
+
     async fn graphql(&self, state: Arc<RwLock<State>>) -> Result<serde_json::Value> {
-        let builder = self.make_constructor(state.clone()).await?;
-        let time = current_time_as_i64();
-        let affiliate = state.read().await.affiliate_code.clone();
-        let query = builder.signed_graphql_request(time, affiliate, state).await?;
-        serializable_to_json(&query)
+        self.graphql_timed(state).await.map(|(query, _)| query)
     }
 
     async fn response_from_json(
@@ -107,7 +336,7 @@ impl NashProtocol for LimitOrdersRequest {
         response: serde_json::Value,
         _state: Arc<RwLock<State>>,
     ) -> Result<ResponseOrError<Self::Response>> {
-        Ok(ResponseOrError::from_data(response.try_into()?))
+        Ok(ResponseOrError::from_data(MultiResponse::from_graphql(response, self.requests.len())?))
     }
 
     /// Update the number of orders remaining before state sync
@@ -141,8 +370,11 @@ impl NashProtocol for LimitOrdersRequest {
 
     /// Potentially get more r values or sign states before placing an order
     async fn run_before(&self, state: Arc<RwLock<State>>) -> Result<Option<Vec<ProtocolHook>>> {
-        let request = &self.requests[0];
-        get_required_hooks(state, &request.market).await.map(Some)
+        if self.requests.is_empty() {
+            return Err(ProtocolError("Batch has no orders"));
+        }
+        let markets: Vec<&str> = self.requests.iter().map(|request| request.market.as_str()).collect();
+        get_required_hooks(state, &markets).await.map(Some)
     }
 }
 
@@ -164,12 +396,12 @@ impl NashProtocol for MarketOrdersRequest {
             .ok()
     }
 
+    fn market_affinity(&self) -> Option<&str> {
+        self.requests.first().map(|request| request.market.as_str())
+    }
+
     async fn graphql(&self, state: Arc<RwLock<State>>) -> Result<serde_json::Value> {
-        let builder = self.make_constructor(state.clone()).await?;
-        let time = current_time_as_i64();
-        let affiliate = state.read().await.affiliate_code.clone();
-        let query = builder.signed_graphql_request(time, affiliate, state).await?;
-        serializable_to_json(&query)
+        self.graphql_timed(state).await.map(|(query, _)| query)
     }
 
     async fn response_from_json(
@@ -177,7 +409,13 @@ impl NashProtocol for MarketOrdersRequest {
         response: serde_json::Value,
         _state: Arc<RwLock<State>>,
     ) -> Result<ResponseOrError<Self::Response>> {
-        Ok(ResponseOrError::from_data(response.try_into()?))
+        let mut response: PlaceOrdersResponse = MultiResponse::from_graphql(response, self.requests.len())?;
+        for (placed, request) in response.responses.iter_mut().zip(&self.requests) {
+            if let Ok(placed) = placed {
+                placed.rate_bounds = Some(request.effective_rate_bounds()?);
+            }
+        }
+        Ok(ResponseOrError::from_data(response))
     }
 
     /// Update the number of orders remaining before state sync
@@ -207,7 +445,475 @@ impl NashProtocol for MarketOrdersRequest {
 
     /// Potentially get more r values or sign states before placing an order
     async fn run_before(&self, state: Arc<RwLock<State>>) -> Result<Option<Vec<ProtocolHook>>> {
-        let request = &self.requests[0];
-        get_required_hooks(state, &request.market).await.map(Some)
+        if self.requests.is_empty() {
+            return Err(ProtocolError("Batch has no orders"));
+        }
+        let markets: Vec<&str> = self.requests.iter().map(|request| request.market.as_str()).collect();
+        get_required_hooks(state, &markets).await.map(Some)
+    }
+}
+
+#[async_trait]
+impl NashProtocol for MixedOrdersRequest {
+    type Response = PlaceOrdersResponse;
+
+    async fn acquire_permit(
+        &self,
+        state: Arc<RwLock<State>>,
+    ) -> Option<tokio::sync::OwnedSemaphorePermit> {
+        state
+            .read()
+            .await
+            .place_order_semaphore
+            .clone()
+            .acquire_owned()
+            .await
+            .ok()
+    }
+
+    fn market_affinity(&self) -> Option<&str> {
+        self.requests.first().map(MixedOrder::market)
+    }
+
+    fn limit_prices(&self) -> Vec<(&str, &str)> {
+        self.requests
+            .iter()
+            .filter_map(|request| match request {
+                MixedOrder::Limit(request) => Some((request.market.as_str(), request.price.as_str())),
+                MixedOrder::Market(_) => None,
+            })
+            .collect()
+    }
+
+    fn limit_sizes(&self) -> Vec<(BuyOrSell, &str)> {
+        self.requests
+            .iter()
+            .filter_map(|request| match request {
+                MixedOrder::Limit(request) => Some((request.buy_or_sell, request.amount.as_str())),
+                MixedOrder::Market(_) => None,
+            })
+            .collect()
+    }
+
+    async fn graphql(&self, state: Arc<RwLock<State>>) -> Result<serde_json::Value> {
+        self.graphql_timed(state).await.map(|(query, _)| query)
+    }
+
+    async fn response_from_json(
+        &self,
+        response: serde_json::Value,
+        _state: Arc<RwLock<State>>,
+    ) -> Result<ResponseOrError<Self::Response>> {
+        let mut response: PlaceOrdersResponse = MultiResponse::from_graphql(response, self.requests.len())?;
+        for (placed, request) in response.responses.iter_mut().zip(&self.requests) {
+            if let (Ok(placed), MixedOrder::Market(request)) = (placed, request) {
+                placed.rate_bounds = Some(request.effective_rate_bounds()?);
+            }
+        }
+        Ok(ResponseOrError::from_data(response))
+    }
+
+    /// Update the number of orders remaining before state sync
+    async fn process_response(
+        &self,
+        response: &Self::Response,
+        state: Arc<RwLock<State>>,
+    ) -> Result<()> {
+        if let Some(Ok(response)) = response.responses.iter().rfind(|response| response.is_ok()) {
+            state.read().await.set_remaining_orders(response.remaining_orders);
+        }
+        Ok(())
+    }
+
+    async fn process_error(
+        &self,
+        _response: &ErrorResponse,
+        _graphql_request: Option<&serde_json::Value>,
+        state: Arc<RwLock<State>>,
+    ) -> Result<()> {
+        state.read().await.decr_n_remaining_orders(self.requests.len() as u64);
+        Ok(())
+    }
+
+    /// Potentially get more r values or sign states before placing the orders
+    async fn run_before(&self, state: Arc<RwLock<State>>) -> Result<Option<Vec<ProtocolHook>>> {
+        if self.requests.is_empty() {
+            return Err(ProtocolError("Batch has no orders"));
+        }
+        let markets: Vec<&str> = self.requests.iter().map(MixedOrder::market).collect();
+        get_required_hooks(state, &markets).await.map(Some)
+    }
+}
+
+#[async_trait]
+impl NashProtocol for OcoOrderRequest {
+    type Response = OcoOrderResponse;
+
+    async fn acquire_permit(
+        &self,
+        state: Arc<RwLock<State>>,
+    ) -> Option<tokio::sync::OwnedSemaphorePermit> {
+        state
+            .read()
+            .await
+            .place_order_semaphore
+            .clone()
+            .acquire_owned()
+            .await
+            .ok()
+    }
+
+    fn market_affinity(&self) -> Option<&str> {
+        Some(&self.take_profit.market)
+    }
+
+    fn limit_prices(&self) -> Vec<(&str, &str)> {
+        vec![
+            (&self.take_profit.market, &self.take_profit.price),
+            (&self.stop.market, &self.stop.price),
+        ]
+    }
+
+    fn limit_sizes(&self) -> Vec<(BuyOrSell, &str)> {
+        vec![
+            (self.take_profit.buy_or_sell, &self.take_profit.amount),
+            (self.stop.buy_or_sell, &self.stop.amount),
+        ]
+    }
+
+    async fn graphql(&self, state: Arc<RwLock<State>>) -> Result<serde_json::Value> {
+        self.graphql_timed(state).await.map(|(query, _)| query)
+    }
+
+    async fn response_from_json(
+        &self,
+        response: serde_json::Value,
+        _state: Arc<RwLock<State>>,
+    ) -> Result<ResponseOrError<Self::Response>> {
+        let mut responses = MultiResponse::<PlaceOrderResponse>::from_graphql(response, 2)?
+            .responses
+            .into_iter();
+        let take_profit = responses.next().ok_or(ProtocolError("Missing take profit order"))?;
+        let stop = responses.next().ok_or(ProtocolError("Missing stop order"))?;
+        Ok(ResponseOrError::from_data(OcoOrderResponse {
+            market: self.take_profit.market.clone(),
+            take_profit,
+            stop,
+        }))
+    }
+
+    /// Update the number of orders remaining before state sync
+    async fn process_response(
+        &self,
+        response: &Self::Response,
+        state: Arc<RwLock<State>>,
+    ) -> Result<()> {
+        // the stop order is signed last, so it has the lower count if both were placed
+        if let Some(placed) = response.stop.as_ref().or(response.take_profit.as_ref()).ok() {
+            state.read().await.set_remaining_orders(placed.remaining_orders);
+        }
+        Ok(())
+    }
+
+    async fn process_error(
+        &self,
+        _response: &ErrorResponse,
+        _graphql_request: Option<&serde_json::Value>,
+        state: Arc<RwLock<State>>,
+    ) -> Result<()> {
+        state.read().await.decr_n_remaining_orders(2);
+        Ok(())
+    }
+
+    /// Potentially get more r values or sign states before placing the orders
+    async fn run_before(&self, state: Arc<RwLock<State>>) -> Result<Option<Vec<ProtocolHook>>> {
+        get_required_hooks(state, &[self.take_profit.market.as_str()]).await.map(Some)
+    }
+}
+
+#[async_trait]
+impl TimedNashProtocol for LimitOrdersRequest {
+    async fn graphql_timed(
+        &self,
+        state: Arc<RwLock<State>>,
+    ) -> Result<(serde_json::Value, StageTimings)> {
+        let started = Instant::now();
+        let builder = self.make_constructor(state.clone()).await?;
+        let time = state.read().await.reserve_order_times(self.requests.len());
+        let affiliate = state.read().await.affiliate_code.clone();
+        let construction = started.elapsed();
+        // Payload nonces are computed per order while signing, so they count towards signing here
+        let started = Instant::now();
+        let query = builder.signed_graphql_request(time, affiliate, state).await?;
+        let json = serializable_to_json(&query)?;
+        let timings = StageTimings {
+            construction,
+            signing: started.elapsed(),
+            ..Default::default()
+        };
+        Ok((json, timings))
+    }
+}
+
+#[async_trait]
+impl TimedNashProtocol for MarketOrdersRequest {
+    async fn graphql_timed(
+        &self,
+        state: Arc<RwLock<State>>,
+    ) -> Result<(serde_json::Value, StageTimings)> {
+        let started = Instant::now();
+        let builder = self.make_constructor(state.clone()).await?;
+        let time = state.read().await.reserve_order_times(self.requests.len());
+        let affiliate = state.read().await.affiliate_code.clone();
+        let construction = started.elapsed();
+        // Payload nonces are computed per order while signing, so they count towards signing here
+        let started = Instant::now();
+        let query = builder.signed_graphql_request(time, affiliate, state).await?;
+        let json = serializable_to_json(&query)?;
+        let timings = StageTimings {
+            construction,
+            signing: started.elapsed(),
+            ..Default::default()
+        };
+        Ok((json, timings))
+    }
+}
+
+#[async_trait]
+impl TimedNashProtocol for MixedOrdersRequest {
+    async fn graphql_timed(
+        &self,
+        state: Arc<RwLock<State>>,
+    ) -> Result<(serde_json::Value, StageTimings)> {
+        let started = Instant::now();
+        let builder = self.make_constructor(state.clone()).await?;
+        let time = state.read().await.reserve_order_times(self.requests.len());
+        let affiliate = state.read().await.affiliate_code.clone();
+        let construction = started.elapsed();
+        // Payload nonces are computed per order while signing, so they count towards signing here
+        let started = Instant::now();
+        let query = builder.signed_graphql_request(time, affiliate, state).await?;
+        let json = serializable_to_json(&query)?;
+        let timings = StageTimings {
+            construction,
+            signing: started.elapsed(),
+            ..Default::default()
+        };
+        Ok((json, timings))
+    }
+}
+
+#[async_trait]
+impl TimedNashProtocol for OcoOrderRequest {
+    async fn graphql_timed(
+        &self,
+        state: Arc<RwLock<State>>,
+    ) -> Result<(serde_json::Value, StageTimings)> {
+        let started = Instant::now();
+        let builder = self.make_constructor(state.clone()).await?;
+        let time = state.read().await.reserve_order_times(2);
+        let affiliate = state.read().await.affiliate_code.clone();
+        let construction = started.elapsed();
+        let started = Instant::now();
+        let query = builder.signed_graphql_request(time, affiliate, state).await?;
+        let json = serializable_to_json(&query)?;
+        let timings = StageTimings {
+            construction,
+            signing: started.elapsed(),
+            ..Default::default()
+        };
+        Ok((json, timings))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{AmendOrderRequest, LimitOrdersRequest, MixedOrdersRequest, OcoOrderRequest};
+    use crate::errors::ProtocolError;
+    use crate::protocol::cancel_order::CancelOrderResponse;
+    use crate::protocol::place_order::request::{
+        limit_order_canonical_string, stop_limit_order_canonical_string,
+    };
+    use crate::protocol::place_order::types::{LimitOrderConstructor, PayloadNonces};
+    use crate::protocol::place_order::{
+        LimitOrderRequest, MarketOrderRequest, StopLimitOrderRequest,
+    };
+    use crate::protocol::{NashProtocol, NashProtocolRequest, ProtocolHook, State};
+    use crate::types::{
+        AccountFeeRates, Amount, Asset, AssetAmount, Blockchain, BuyOrSell, Market, Nonce,
+        OrderCancellationPolicy, PublicKey, TypedNonce,
+    };
+    use bigdecimal::BigDecimal;
+    use std::str::FromStr;
+    use std::sync::Arc;
+    use tokio::sync::RwLock;
+
+    const KEY: &str = "eyJjaGlsZF9rZXlzIjp7fSwKICAgICAgICAicGFpbGxpZXJfcGsiOnsibiI6IjU5ODdlNjIyMjYxY2FmOTZlMjU4MjZjNzBjZjMyM2IyNjE5NGZmOWNmZTY5ZTNmNDBmMzBkMzA2NTcxNjQyY2FlYThhMzE0M2QxMWZmOTRjMTM4ODM2MDQ4NjczNTdhZThjMGU2NjNiZjAzZDAwOTMwMTZkN2Y0ZDc5MGFlMjRlMjkxNzgwM2Q4MTJiNjQxYWYyZDZjMDk1NzNkMTEyZWI3Njg2NDY1MjkxY2QxNDZmZDY2MmY3N2Y1OTVlZjgzMjc3YmUxNjgwZDA0MGIxZjNjNDk5YzgxOTE3NTcyMDZlNTEwYWU1NDcyNGQ2NjdmYzA0MWEyYzdjMmZmM2QzYjY2YzM3MjlkYzI1ZTAyYzQwMTllZDNhMDEyZmQ3NWVjMGUwMzk0OGNmNzgzYWQzOTAyY2U1ZTVlNzIyMjljM2RkM2ExNGI5MzRkNjAyNjlhY2I3YmEwYmQ0MTVkMmRlMTI4ZWYxODcyMjQwMGJhZWEyZTg1MGU2ZDFmZDg3ODdhMDEzMGQ1MTYyMDZkNzE4YTQ5ZDdhMjFkNDI4YjBmYTM3NzMwNzliNjQ4NjE4MTExOTFiNTUwMDFkNGMyYzI5ZjYzMDMxNGJlMTkxY2YzY2EzZjBmOGUwOWVlMDk1NDNmZmRkYTNmOTdjZjE2OWQ1MmUwNjdjZmQ0MGNiMzAzOTQxIn0sCiAgICAgICAgInBheWxvYWRfcHVibGljX2tleSI6IjA0NjE2NDZmZGM0NTQ0ZjEwMjk0ZTIwZTk5NGNlNTZkOGMwZmY4NTI1OTZlYjZiM2FhMGJhOWQ0YjIwNzlkODZkNDJiM2I1ZTg0OTFhNDhmZjZlMTYyMDczMjU3OTgwNzkxNmVlYjA3YmViNmY5OTcwZGM1OTUyYmQ0NDQ0MDRmNzQiLAogICAgICAgICJwYXlsb2FkX3NpZ25pbmdfa2V5IjoiYmI4YmNmNTJhNWY5NDRmMzUxYzViYzg1NmI3YTRjNDFhNWYzNzBmNWNlOTlkY2UwYzhkNmYxZDQ5MWNkMzRiZiIsCiAgICAgICAgInZlcnNpb24iOjB9";
+    const ETH_KEY: &str = "04be641c583207c310739a23973fb7cb7336d2b835517ede791e9fa53fa5b0fc46390ebb4dab62e8b01352f37308dbff1512615856bffd3c752db95737d3bc93a4";
+
+    fn market(a: Asset, b: Asset) -> Market {
+        let (a, b) = (a.with_precision(4), b.with_precision(2));
+        let min_size = |asset| AssetAmount {
+            asset,
+            amount: Amount::new("0.01", 4).unwrap(),
+        };
+        Market::new(a, b, min_size(a), min_size(b))
+    }
+
+    fn state(markets: Vec<Market>) -> Arc<RwLock<State>> {
+        let mut state = State::from_keys(KEY, "").unwrap();
+        state.set_fee_rates(
+            AccountFeeRates::new(BigDecimal::from(0), BigDecimal::from_str("0.0025").unwrap())
+                .unwrap(),
+        );
+        state.markets = Some(Arc::new(
+            markets
+                .into_iter()
+                .map(|market| (market.market_name(), market))
+                .collect(),
+        ));
+        Arc::new(RwLock::new(state))
+    }
+
+    fn limit(market: &str, buy_or_sell: BuyOrSell, amount: &str, price: &str) -> LimitOrderRequest {
+        LimitOrderRequest {
+            market: market.to_string(),
+            client_order_id: None,
+            buy_or_sell,
+            amount: amount.to_string(),
+            price: price.to_string(),
+            cancellation_policy: OrderCancellationPolicy::GoodTilCancelled,
+            allow_taker: true,
+        }
+    }
+
+    #[test]
+    fn batch_reports_every_limit_order() {
+        let request = LimitOrdersRequest::new(vec![
+            limit("eth_usdc", BuyOrSell::Buy, "1", "200"),
+            limit("neo_usdc", BuyOrSell::Sell, "2", "10"),
+        ])
+        .unwrap();
+        assert_eq!(
+            request.limit_prices(),
+            vec![("eth_usdc", "200"), ("neo_usdc", "10")]
+        );
+        assert_eq!(
+            request.limit_sizes(),
+            vec![(BuyOrSell::Buy, "1"), (BuyOrSell::Sell, "2")]
+        );
+        assert_eq!(request.limit_price(), None);
+    }
+
+    #[tokio::test]
+    async fn oco_legs_are_a_limit_and_a_stop_limit_order() {
+        let take_profit = limit("eth_usdc", BuyOrSell::Sell, "1.5", "250");
+        let stop = StopLimitOrderRequest::new(limit("eth_usdc", BuyOrSell::Sell, "1.5", "180"), "190")
+            .unwrap();
+        let request = OcoOrderRequest::new(take_profit, stop).unwrap();
+        // both legs go through the price guard and the approval policy
+        assert_eq!(
+            request.limit_prices(),
+            vec![("eth_usdc", "250"), ("eth_usdc", "180")]
+        );
+        assert_eq!(
+            request.limit_sizes(),
+            vec![(BuyOrSell::Sell, "1.5"), (BuyOrSell::Sell, "1.5")]
+        );
+
+        let state = state(vec![market(Asset::ETH, Asset::USDC)]);
+        let constructor = request.make_constructor(state.clone()).await.unwrap();
+        let take_profit = constructor.take_profit.graphql_request(1000, None).unwrap();
+        let stop = constructor.stop.graphql_request(1001, None).unwrap();
+        let decimal = |amount: &str| BigDecimal::from_str(amount).unwrap();
+        assert_eq!(decimal(&take_profit.payload.limit_price.amount), decimal("250"));
+        assert_eq!(take_profit.payload.market_name, "eth_usdc");
+        assert_eq!(decimal(&stop.payload.limit_price.amount), decimal("180"));
+        assert_eq!(decimal(&stop.payload.stop_price.amount), decimal("190"));
+        assert_eq!(stop.payload.market_name, "eth_usdc");
+
+        let take_profit = limit_order_canonical_string(&take_profit).unwrap();
+        let stop = stop_limit_order_canonical_string(&stop).unwrap();
+        assert!(take_profit.starts_with("place_limit_order,{"));
+        assert!(!take_profit.contains("stop_price"));
+        // the stop price is only covered by the request signature
+        assert!(stop.starts_with("place_stop_limit_order,{"));
+        assert!(stop.contains(r#""stop_price":{"#));
+        assert!(!stop.contains("blockchain_signatures"));
+
+        // the stop leg signs the fill payloads of its limit order
+        let key = PublicKey::new(Blockchain::Ethereum, ETH_KEY).unwrap();
+        let nonces = PayloadNonces {
+            nonce_from: TypedNonce::new(Asset::ETH, 1),
+            nonce_to: TypedNonce::new(Asset::USDC, 2),
+            order_nonce: Nonce::Value(1001),
+        };
+        let payload = |constructor: &LimitOrderConstructor| {
+            constructor
+                .make_fill_order(Blockchain::Ethereum, &key, &nonces)
+                .unwrap()
+                .to_hex()
+                .unwrap()
+        };
+        let limit_order = request.stop.limit_order().make_constructor(state).await.unwrap();
+        assert_eq!(payload(&constructor.stop.limit), payload(&limit_order));
+        assert_ne!(payload(&constructor.stop.limit), payload(&constructor.take_profit));
+    }
+
+    #[tokio::test]
+    async fn mixed_batch_is_prepared_for_every_market() {
+        let request = MixedOrdersRequest::new(vec![
+            limit("eth_usdc", BuyOrSell::Buy, "1", "200").into(),
+            MarketOrderRequest::new("neo_eth", "5", None).unwrap().into(),
+            limit("neo_eth", BuyOrSell::Sell, "2", "0.05").into(),
+        ])
+        .unwrap();
+        // market orders have no limit price to check
+        assert_eq!(
+            request.limit_prices(),
+            vec![("eth_usdc", "200"), ("neo_eth", "0.05")]
+        );
+        assert_eq!(
+            request.limit_sizes(),
+            vec![(BuyOrSell::Buy, "1"), (BuyOrSell::Sell, "2")]
+        );
+
+        let state = state(vec![
+            market(Asset::ETH, Asset::USDC),
+            market(Asset::NEO, Asset::ETH),
+        ]);
+        let hooks = request.run_before(state).await.unwrap().unwrap();
+        let filled: Vec<Blockchain> = hooks
+            .iter()
+            .filter_map(|hook| match hook {
+                ProtocolHook::Protocol(NashProtocolRequest::DhFill(request, _)) => {
+                    Some(request.blockchain())
+                }
+                _ => None,
+            })
+            .collect();
+        // no r values yet, for the chains of both markets
+        assert_eq!(filled, vec![Blockchain::Ethereum, Blockchain::NEO]);
+    }
+
+    #[test]
+    fn amendment_replaces_only_a_confirmed_cancellation() {
+        let replacement = limit("eth_usdc", BuyOrSell::Buy, "1", "200");
+        let amend = AmendOrderRequest::new("order", replacement.clone());
+        assert_eq!(amend.cancel.market, "eth_usdc");
+
+        let confirmed = Ok(CancelOrderResponse {
+            order_id: "order".to_string(),
+        });
+        let placed = amend.replacement_after(&confirmed).unwrap();
+        assert_eq!(placed.price, replacement.price);
+
+        // e.g. the order filled before it could be cancelled
+        let failed = Err(ProtocolError("Order not found"));
+        assert!(amend.replacement_after(&failed).is_none());
+        let other = Ok(CancelOrderResponse {
+            order_id: "other".to_string(),
+        });
+        assert!(amend.replacement_after(&other).is_none());
     }
 }
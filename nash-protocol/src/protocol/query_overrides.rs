@@ -0,0 +1,70 @@
+//! GraphQL documents replacing the built in ones of the same operation, compiled in with the
+//! `custom-queries` feature from the directory named by `NASH_QUERY_OVERRIDES` (see
+//! `build.rs`). Together with a custom host in the client config this serves exchanges
+//! running an extended version of the Nash schema. Responses are still parsed into the built
+//! in types, so an override may add fields but must keep the ones the built in query selects.
+
+use graphql_client::{GraphQLQuery, QueryBody};
+
+#[cfg(feature = "custom-queries")]
+include!(concat!(env!("OUT_DIR"), "/query_overrides.rs"));
+
+#[cfg(not(feature = "custom-queries"))]
+static QUERY_OVERRIDES: &[(&str, &str)] = &[];
+
+/// Document compiled in to replace the built in one of `operation`, if any
+pub fn query_override(operation: &str) -> Option<&'static str> {
+    find_override(QUERY_OVERRIDES, operation)
+}
+
+fn find_override(overrides: &[(&str, &'static str)], operation: &str) -> Option<&'static str> {
+    overrides
+        .iter()
+        .find(|(name, _)| *name == operation)
+        .map(|(_, document)| *document)
+}
+
+/// Operations whose built in documents are replaced
+pub fn overridden_operations() -> impl Iterator<Item = &'static str> {
+    QUERY_OVERRIDES.iter().map(|(name, _)| *name)
+}
+
+/// Request body of `Q`, with the override of its operation as document if there is one.
+/// Queries are built through here rather than with `GraphQLQuery::build_query`.
+pub(crate) fn build_query<Q: GraphQLQuery>(variables: Q::Variables) -> QueryBody<Q::Variables> {
+    with_override(Q::build_query(variables), QUERY_OVERRIDES)
+}
+
+fn with_override<V>(mut body: QueryBody<V>, overrides: &[(&str, &'static str)]) -> QueryBody<V> {
+    if let Some(document) = find_override(overrides, body.operation_name) {
+        body.query = document;
+    }
+    body
+}
+
+#[cfg(test)]
+mod tests {
+    use super::with_override;
+    use crate::graphql::{list_markets, list_tickers, ListMarkets, ListTickers};
+    use graphql_client::GraphQLQuery;
+
+    const LIST_MARKETS: &str = "query ListMarkets { listMarkets { name extraField } }";
+
+    #[test]
+    fn overrides_replace_the_document_of_their_operation() {
+        let overrides = [("ListMarkets", LIST_MARKETS)];
+        let markets = with_override(
+            ListMarkets::build_query(list_markets::Variables {}),
+            &overrides,
+        );
+        assert_eq!(markets.query, LIST_MARKETS);
+        assert_eq!(markets.operation_name, "ListMarkets");
+
+        let built_in = ListTickers::build_query(list_tickers::Variables {}).query;
+        let tickers = with_override(
+            ListTickers::build_query(list_tickers::Variables {}),
+            &overrides,
+        );
+        assert_eq!(tickers.query, built_in);
+    }
+}
@@ -0,0 +1,8 @@
+//! Keep the session token alive. The exchange expires a session token some time after its
+//! last request; any request refreshes it, this one does nothing else.
+
+mod request;
+mod response;
+mod types;
+
+pub use types::{RefreshTokenRequest, RefreshTokenResponse, SESSION_TOKEN_LIFETIME};
@@ -0,0 +1,10 @@
+use super::types::RefreshTokenRequest;
+use crate::graphql;
+use crate::graphql::refresh_token;
+use crate::protocol::query_overrides::build_query;
+
+impl RefreshTokenRequest {
+    pub fn make_query(&self) -> graphql_client::QueryBody<refresh_token::Variables> {
+        build_query::<graphql::RefreshToken>(refresh_token::Variables {})
+    }
+}
@@ -0,0 +1,14 @@
+use super::types::RefreshTokenResponse;
+use crate::errors::{ProtocolError, Result};
+use crate::graphql::refresh_token;
+use std::convert::TryFrom;
+
+impl TryFrom<refresh_token::ResponseData> for RefreshTokenResponse {
+    type Error = ProtocolError;
+
+    fn try_from(response: refresh_token::ResponseData) -> Result<Self> {
+        Ok(Self {
+            message: response.refresh_token.message,
+        })
+    }
+}
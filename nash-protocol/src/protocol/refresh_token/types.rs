@@ -0,0 +1,43 @@
+use super::super::{
+    serializable_to_json, try_response_from_json, NashProtocol, ResponseOrError, State,
+};
+use crate::errors::Result;
+use crate::graphql::refresh_token;
+use async_trait::async_trait;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::RwLock;
+
+/// How long the exchange keeps a session token alive after the last request made with it
+pub const SESSION_TOKEN_LIFETIME: Duration = Duration::from_secs(15 * 60);
+
+/// Refresh the session token for another `SESSION_TOKEN_LIFETIME`
+#[derive(Clone, Debug)]
+pub struct RefreshTokenRequest;
+
+#[derive(Clone, Debug)]
+pub struct RefreshTokenResponse {
+    pub message: Option<String>,
+}
+
+/// Implement protocol bindings for RefreshTokenRequest
+#[async_trait]
+impl NashProtocol for RefreshTokenRequest {
+    type Response = RefreshTokenResponse;
+
+    async fn graphql(&self, _state: Arc<RwLock<State>>) -> Result<serde_json::Value> {
+        let query = self.make_query();
+        let mut out = serializable_to_json(&query)?;
+        // override null with an empty object, as for other requests without variables
+        *out.get_mut("variables").unwrap() = serde_json::json!({});
+        Ok(out)
+    }
+
+    async fn response_from_json(
+        &self,
+        response: serde_json::Value,
+        _state: Arc<RwLock<State>>,
+    ) -> Result<ResponseOrError<Self::Response>> {
+        try_response_from_json::<RefreshTokenResponse, refresh_token::ResponseData>(response)
+    }
+}
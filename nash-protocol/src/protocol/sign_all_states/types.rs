@@ -106,8 +106,10 @@ impl NashProtocolPipeline for SignAllStates {
         }
         Ok(Some(hooks))
     }
-    // After running this pipeline, update asset nonces
-    async fn run_after(&self, _state: Arc<RwLock<State>>) -> Result<Option<Vec<ProtocolHook>>> {
+    // After running this pipeline, update asset nonces. The signing capacity is refreshed
+    // by the next order response
+    async fn run_after(&self, state: Arc<RwLock<State>>) -> Result<Option<Vec<ProtocolHook>>> {
+        state.read().await.reset_signing_capacity();
         Ok(Some(vec![ProtocolHook::Protocol(
             NashProtocolRequest::AssetNonces(AssetNoncesRequest::new()),
         )]))
@@ -87,7 +87,10 @@ pub fn sign_state_data(state_data: &StateData, signer: &Signer) -> Result<Client
 }
 
 impl From<std::array::TryFromSliceError> for ProtocolError {
-    fn from(_error: std::array::TryFromSliceError) -> Self {
-        ProtocolError("Could not convert slice into correct number of bytes")
+    fn from(error: std::array::TryFromSliceError) -> Self {
+        ProtocolError::with_source(
+            "Could not convert slice into correct number of bytes",
+            error,
+        )
     }
 }
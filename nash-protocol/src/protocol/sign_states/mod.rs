@@ -7,4 +7,6 @@ mod response;
 mod types;
 
 pub use blockchain::StateUpdatePayload;
-pub use types::{SignStatesRequest, SignStatesResponse};
\ No newline at end of file
+pub use types::{SignStatesRequest, SignStatesResponse};
+pub(crate) use blockchain::sign_state_data;
+pub(crate) use types::{ClientSignedState, RecycledOrder, StateData};
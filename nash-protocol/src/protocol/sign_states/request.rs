@@ -6,7 +6,7 @@ use crate::graphql;
 use crate::graphql::sign_states;
 use crate::types::Blockchain;
 use crate::utils::{bigint_to_nash_r, bigint_to_nash_sig, current_time_as_i64};
-use graphql_client::GraphQLQuery;
+use crate::protocol::query_overrides::build_query;
 
 use super::super::signer::Signer;
 
@@ -57,9 +57,9 @@ impl SignStatesRequest {
             signature: RequestPayloadSignature::empty().into(),
         };
         let sig_payload = sign_states_canonical_string(&params);
-        let sig = signer.sign_canonical_string(&sig_payload);
+        let sig = signer.sign_canonical_string(&sig_payload)?;
         params.signature = sig.into();
-        Ok(graphql::SignStates::build_query(params))
+        Ok(build_query::<graphql::SignStates>(params))
     }
 }
 
@@ -1,7 +1,9 @@
-#[cfg(feature = "k256")]
+#[cfg(all(feature = "k256", not(feature = "secp256k1")))]
 use k256::ecdsa::signature::Signer as k256_Signer;
-#[cfg(feature = "k256")]
-use k256::ecdsa::{Signature, SigningKey};
+#[cfg(all(feature = "k256", not(feature = "secp256k1")))]
+use k256::ecdsa::signature::Verifier;
+#[cfg(all(feature = "k256", not(feature = "secp256k1")))]
+use k256::ecdsa::{Signature, SigningKey, VerifyingKey};
 #[cfg(feature = "secp256k1")]
 use rust_bigint::traits::Converter;
 #[cfg(feature = "secp256k1")]
@@ -13,15 +15,16 @@ use nash_mpc::client::APIchildkey;
 use nash_mpc::common::Curve;
 #[cfg(feature = "secp256k1")]
 use nash_mpc::curves::secp256_k1::get_context;
-#[cfg(feature = "k256")]
+#[cfg(all(feature = "k256", not(feature = "secp256k1")))]
 use nash_mpc::curves::secp256_k1_rust::Secp256k1Scalar;
-#[cfg(feature = "k256")]
+#[cfg(all(feature = "k256", not(feature = "secp256k1")))]
 use nash_mpc::curves::traits::ECScalar;
 use nash_mpc::paillier_common;
 use nash_mpc::rust_bigint::BigInt;
 
 use crate::errors::{ProtocolError, Result};
 use crate::protocol::RequestPayloadSignature;
+use crate::types::keys::KeyfileChildKey;
 use crate::types::ApiKeys;
 use crate::types::Blockchain;
 use crate::types::PublicKey;
@@ -37,6 +40,55 @@ pub fn chain_path(chain: Blockchain) -> &'static str {
     }
 }
 
+/// Check a DER encoded signature as produced by `Signer::sign_canonical_string` over
+/// `request`, made by the payload signing key `public_key` (hex encoded)
+#[cfg(all(feature = "rustcrypto", not(feature = "secp256k1")))]
+pub fn verify_canonical_string(public_key: &str, request: &str, signed_digest: &str) -> bool {
+    let key = match hex::decode(public_key)
+        .ok()
+        .and_then(|bytes| VerifyingKey::from_sec1_bytes(&bytes).ok())
+    {
+        Some(key) => key,
+        None => return false,
+    };
+    let signature = match hex::decode(signed_digest)
+        .ok()
+        .and_then(|der| Signature::from_asn1(&der).ok())
+    {
+        Some(signature) => signature,
+        None => return false,
+    };
+    key.verify(request.as_bytes(), &signature).is_ok()
+}
+
+/// Check a DER encoded signature as produced by `Signer::sign_canonical_string` over
+/// `request`, made by the payload signing key `public_key` (hex encoded)
+#[cfg(feature = "secp256k1")]
+pub fn verify_canonical_string(public_key: &str, request: &str, signed_digest: &str) -> bool {
+    let message_hash = hash_message(request).to_bytes();
+    let mut msg_vec = vec![0; MESSAGE_SIZE - message_hash.len()];
+    msg_vec.extend_from_slice(&message_hash);
+    let msg = match Message::from_slice(&msg_vec) {
+        Ok(msg) => msg,
+        Err(_) => return false,
+    };
+    let key = match hex::decode(public_key)
+        .ok()
+        .and_then(|bytes| secp256k1::PublicKey::from_slice(&bytes).ok())
+    {
+        Some(key) => key,
+        None => return false,
+    };
+    let signature = match hex::decode(signed_digest)
+        .ok()
+        .and_then(|der| secp256k1::Signature::from_der(&der).ok())
+    {
+        Some(signature) => signature,
+        None => return false,
+    };
+    get_context().verify(&msg, &signature, &key).is_ok()
+}
+
 #[derive(Debug)]
 pub struct Signer {
     pub api_keys: ApiKeys,
@@ -53,6 +105,14 @@ impl Signer {
         })
     }
 
+    pub fn from_keyfile_json(keyfile: &str) -> Result<Self> {
+        Ok(Self {
+            api_keys: ApiKeys::from_json(keyfile)?,
+            k1_remaining: AtomicU32::new(0),
+            r1_remaining: AtomicU32::new(0),
+        })
+    }
+
     pub fn from_data(secret: &str, session: &str) -> Result<Self> {
         Ok(Self {
             api_keys: ApiKeys::from_data(secret, session)?,
@@ -64,32 +124,41 @@ impl Signer {
     /// Sign GraphQL payload request via payload signing key
     /// The output is a hex string where signature has been DER encoded
     /// Either implemented with k256 from rustcrypto (pure rust) or secp256k1 (better performance)
-    #[cfg(feature = "rustcrypto")]
-    pub fn sign_canonical_string(&self, request: &str) -> RequestPayloadSignature {
+    #[cfg(all(feature = "rustcrypto", not(feature = "secp256k1")))]
+    pub fn sign_canonical_string(&self, request: &str) -> Result<RequestPayloadSignature> {
         let signing_key: Secp256k1Scalar =
-            ECScalar::from(&self.api_keys.keys.payload_signing_key).expect("Invalid key");
-        let key = SigningKey::from_bytes(&signing_key.to_vec()).expect("invalid secret key");
-        let sig_pre: Signature = key.try_sign(request.as_bytes()).expect("signing failed");
+            ECScalar::from(&self.api_keys.keys.payload_signing_key)
+                .map_err(|_| ProtocolError("Invalid payload signing key"))?;
+        let key = SigningKey::from_bytes(&signing_key.to_vec())
+            .map_err(|_| ProtocolError("Invalid payload signing key"))?;
+        let sig_pre: Signature = key
+            .try_sign(request.as_bytes())
+            .map_err(|_| ProtocolError("Could not sign payload"))?;
         let sig = sig_pre.to_asn1();
-        RequestPayloadSignature {
+        Ok(RequestPayloadSignature {
             signed_digest: hex::encode(sig),
             public_key: self.request_payload_public_key(),
-        }
+        })
     }
     #[cfg(feature = "secp256k1")]
-    pub fn sign_canonical_string(&self, request: &str) -> RequestPayloadSignature {
+    pub fn sign_canonical_string(&self, request: &str) -> Result<RequestPayloadSignature> {
         // create message hash
         let message_hash = hash_message(request).to_bytes();
         // add leading zeroes if necessary
         let mut msg_vec = vec![0; MESSAGE_SIZE - message_hash.len()];
         msg_vec.extend_from_slice(&message_hash);
-        let msg = Message::from_slice(&msg_vec).unwrap();
+        let msg = Message::from_slice(&msg_vec)
+            .map_err(|e| ProtocolError::with_source("Invalid payload hash", e))?;
 
         // SecretKey from BigInt
         let vec = BigInt::to_vec(&self.api_keys.keys.payload_signing_key);
+        if vec.len() > SECRET_KEY_SIZE {
+            return Err(ProtocolError("Invalid payload signing key"));
+        }
         let mut v = vec![0; SECRET_KEY_SIZE - vec.len()];
         v.extend(&vec);
-        let key = SecretKey::from_slice(&v).expect("invalid secret key");
+        let key = SecretKey::from_slice(&v)
+            .map_err(|e| ProtocolError::with_source("Invalid payload signing key", e))?;
 
         // actual signature generation (and encoding)
         let signature = get_context().sign(&msg, &key).serialize_compact();
@@ -97,10 +166,10 @@ impl Signer {
         let s = BigInt::from_bytes(&signature[COMPACT_SIGNATURE_SIZE / 2..COMPACT_SIGNATURE_SIZE]);
         let sig = der_encode_sig(&r, &s);
 
-        RequestPayloadSignature {
+        Ok(RequestPayloadSignature {
             signed_digest: hex::encode(sig),
             public_key: self.request_payload_public_key(),
-        }
+        })
     }
 
     /// Sign data hashed to `BigInt` with the MPC child key for the given `Blockchain`
@@ -112,7 +181,7 @@ impl Signer {
         if self.get_remaining_r_vals(&chain) <= 0 {
             return Err(ProtocolError("Ran out of R values"));
         }
-        let key = self.get_child_key(chain);
+        let key = self.get_child_key(chain)?;
         let curve = match chain {
             Blockchain::Ethereum | Blockchain::Bitcoin => Curve::Secp256k1,
             Blockchain::NEO => Curve::Secp256r1,
@@ -127,7 +196,7 @@ impl Signer {
 
     /// Get public key for child key on `chain`
     pub fn child_public_key(&self, chain: Blockchain) -> Result<PublicKey> {
-        PublicKey::new(chain, &self.get_child_key(chain).public_key)
+        PublicKey::new(chain, &self.get_child_key(chain)?.public_key)
     }
 
     /// Return public key for payload signing in format expected by the Nash backend service
@@ -144,21 +213,37 @@ impl Signer {
         &self.api_keys.keys.paillier_pk
     }
 
-    pub fn get_address(&self, chain: Blockchain) -> &str {
-        &self.api_keys.keys.child_keys[chain_path(chain)].address
+    pub fn get_address(&self, chain: Blockchain) -> Result<&str> {
+        Ok(&self.child_key_data(chain)?.address)
     }
 
-    pub fn get_child_key(&self, chain: Blockchain) -> APIchildkey {
-        let key = &self.api_keys.keys.child_keys[chain_path(chain)];
+    /// Child key for `chain`. Fails if the API keys have none, e.g. for a chain that was
+    /// added to the account after the keys were created.
+    pub fn get_child_key(&self, chain: Blockchain) -> Result<APIchildkey> {
+        let key = self.child_key_data(chain)?;
         // TODO: these should be unified! it was more convenient to parse the paillier_pk
         // once for all the key data from deserialization, which is why I need this atm.
         // is on list to fix once things are verified to be working
-        APIchildkey {
+        Ok(APIchildkey {
             client_secret_share: key.client_secret_share.clone(),
             paillier_pk: self.paillier_pk().clone(),
             public_key: key.public_key.clone(),
             server_secret_share_encrypted: key.server_secret_share_encrypted.clone(),
-        }
+        })
+    }
+
+    fn child_key_data(&self, chain: Blockchain) -> Result<&KeyfileChildKey> {
+        self.api_keys
+            .keys
+            .child_keys
+            .get(chain_path(chain))
+            .ok_or_else(|| {
+                ProtocolError::coerce_static_from_str(&format!(
+                    "No child key for {:?} ({})",
+                    chain,
+                    chain_path(chain)
+                ))
+            })
     }
 
     /// Get the current number of available R values for the given chain
@@ -187,13 +272,16 @@ impl Signer {
 
 #[cfg(test)]
 mod tests {
-    use super::Signer;
+    use super::{verify_canonical_string, Signer};
 
     #[test]
     fn test_signing() {
         let base64_key = "eyJjaGlsZF9rZXlzIjp7fSwKICAgICAgICAicGFpbGxpZXJfcGsiOnsibiI6IjU5ODdlNjIyMjYxY2FmOTZlMjU4MjZjNzBjZjMyM2IyNjE5NGZmOWNmZTY5ZTNmNDBmMzBkMzA2NTcxNjQyY2FlYThhMzE0M2QxMWZmOTRjMTM4ODM2MDQ4NjczNTdhZThjMGU2NjNiZjAzZDAwOTMwMTZkN2Y0ZDc5MGFlMjRlMjkxNzgwM2Q4MTJiNjQxYWYyZDZjMDk1NzNkMTEyZWI3Njg2NDY1MjkxY2QxNDZmZDY2MmY3N2Y1OTVlZjgzMjc3YmUxNjgwZDA0MGIxZjNjNDk5YzgxOTE3NTcyMDZlNTEwYWU1NDcyNGQ2NjdmYzA0MWEyYzdjMmZmM2QzYjY2YzM3MjlkYzI1ZTAyYzQwMTllZDNhMDEyZmQ3NWVjMGUwMzk0OGNmNzgzYWQzOTAyY2U1ZTVlNzIyMjljM2RkM2ExNGI5MzRkNjAyNjlhY2I3YmEwYmQ0MTVkMmRlMTI4ZWYxODcyMjQwMGJhZWEyZTg1MGU2ZDFmZDg3ODdhMDEzMGQ1MTYyMDZkNzE4YTQ5ZDdhMjFkNDI4YjBmYTM3NzMwNzliNjQ4NjE4MTExOTFiNTUwMDFkNGMyYzI5ZjYzMDMxNGJlMTkxY2YzY2EzZjBmOGUwOWVlMDk1NDNmZmRkYTNmOTdjZjE2OWQ1MmUwNjdjZmQ0MGNiMzAzOTQxIn0sCiAgICAgICAgInBheWxvYWRfcHVibGljX2tleSI6IjA0NjE2NDZmZGM0NTQ0ZjEwMjk0ZTIwZTk5NGNlNTZkOGMwZmY4NTI1OTZlYjZiM2FhMGJhOWQ0YjIwNzlkODZkNDJiM2I1ZTg0OTFhNDhmZjZlMTYyMDczMjU3OTgwNzkxNmVlYjA3YmViNmY5OTcwZGM1OTUyYmQ0NDQ0MDRmNzQiLAogICAgICAgICJwYXlsb2FkX3NpZ25pbmdfa2V5IjoiYmI4YmNmNTJhNWY5NDRmMzUxYzViYzg1NmI3YTRjNDFhNWYzNzBmNWNlOTlkY2UwYzhkNmYxZDQ5MWNkMzRiZiIsCiAgICAgICAgInZlcnNpb24iOjB9";
         let signer = Signer::from_data(&base64_key, "").unwrap();
-        let signature = signer.sign_canonical_string("hello, world!");
+        let signature = signer.sign_canonical_string("hello, world!").unwrap();
         assert_eq!(signature.signed_digest, "30440220135a79b11caa321f1548d4b86e17c9b53525ffcdeab5e559d6cca310623cc45d02205a0bb368cf79e41d4760f48c9d16ccd8351ac5e97e0a6825eac6acbe662007c4");
+        let public_key = signer.request_payload_public_key();
+        assert!(verify_canonical_string(&public_key, "hello, world!", &signature.signed_digest));
+        assert!(!verify_canonical_string(&public_key, "hello, world?", &signature.signed_digest));
     }
 }
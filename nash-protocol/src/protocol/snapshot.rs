@@ -0,0 +1,201 @@
+//! Compact binary snapshots of the cacheable parts of `State` (markets, assets and asset
+//! nonces). A snapshot lets a restarted client skip the initial `ListMarkets` and asset
+//! nonce round trips. The encoding is a 2 byte little endian schema id followed by the
+//! bincode encoded body, so older snapshots can be detected and rejected explicitly.
+
+use std::collections::HashMap;
+use std::convert::{TryFrom, TryInto};
+use std::str::FromStr;
+use std::sync::Arc;
+
+use bigdecimal::BigDecimal;
+use serde::{Deserialize, Serialize};
+
+use super::State;
+use crate::errors::{ProtocolError, Result};
+use crate::types::{Amount, Asset, AssetAmount, AssetofPrecision, Market};
+
+/// Schema id written in front of every snapshot. Bump whenever `StateSnapshot` changes shape.
+pub const STATE_SNAPSHOT_SCHEMA: u16 = 2;
+
+/// Serializable subset of `State`. Keys, r-values and semaphores are never part of a snapshot.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct StateSnapshot {
+    pub markets: Option<HashMap<String, Market>>,
+    pub assets: Option<Vec<Asset>>,
+    pub asset_nonces: Option<HashMap<String, Vec<u32>>>,
+}
+
+// bincode is not self describing, and serde only decodes `BigDecimal` from self describing
+// formats. Snapshots write markets with their decimals as strings instead.
+#[derive(Serialize, Deserialize)]
+struct SnapshotBody {
+    markets: Option<HashMap<String, MarketRecord>>,
+    assets: Option<Vec<Asset>>,
+    asset_nonces: Option<HashMap<String, Vec<u32>>>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct MarketRecord {
+    asset_a: AssetofPrecision,
+    asset_b: AssetofPrecision,
+    min_trade_size_a: AssetAmountRecord,
+    min_trade_size_b: AssetAmountRecord,
+}
+
+#[derive(Serialize, Deserialize)]
+struct AssetAmountRecord {
+    asset: AssetofPrecision,
+    precision: u32,
+    value: String,
+}
+
+impl From<&Market> for MarketRecord {
+    fn from(market: &Market) -> Self {
+        let amount = |amount: &AssetAmount| AssetAmountRecord {
+            asset: amount.asset,
+            precision: amount.amount.precision,
+            value: amount.amount.value.to_string(),
+        };
+        Self {
+            asset_a: market.asset_a,
+            asset_b: market.asset_b,
+            min_trade_size_a: amount(&market.min_trade_size_a),
+            min_trade_size_b: amount(&market.min_trade_size_b),
+        }
+    }
+}
+
+impl TryFrom<MarketRecord> for Market {
+    type Error = ProtocolError;
+
+    fn try_from(record: MarketRecord) -> Result<Self> {
+        let amount = |amount: AssetAmountRecord| -> Result<AssetAmount> {
+            let value = BigDecimal::from_str(&amount.value)
+                .map_err(|_| ProtocolError("Could not decode amount of state snapshot"))?;
+            Ok(AssetAmount {
+                asset: amount.asset,
+                amount: Amount::from_bigdecimal(value, amount.precision),
+            })
+        };
+        Ok(Market::new(
+            record.asset_a,
+            record.asset_b,
+            amount(record.min_trade_size_a)?,
+            amount(record.min_trade_size_b)?,
+        ))
+    }
+}
+
+impl StateSnapshot {
+    /// Encode snapshot as schema id + bincode body
+    pub fn to_bytes(&self) -> Result<Vec<u8>> {
+        let body = SnapshotBody {
+            markets: self.markets.as_ref().map(|markets| {
+                markets
+                    .iter()
+                    .map(|(name, market)| (name.clone(), market.into()))
+                    .collect()
+            }),
+            assets: self.assets.clone(),
+            asset_nonces: self.asset_nonces.clone(),
+        };
+        let body = bincode::serialize(&body).map_err(|e| {
+            ProtocolError::coerce_static_from_str(&format!("Could not encode state snapshot: {}", e))
+        })?;
+        let mut bytes = Vec::with_capacity(body.len() + 2);
+        bytes.extend_from_slice(&STATE_SNAPSHOT_SCHEMA.to_le_bytes());
+        bytes.extend_from_slice(&body);
+        Ok(bytes)
+    }
+
+    /// Decode a snapshot, failing if it was written with a different schema id
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        if bytes.len() < 2 {
+            return Err(ProtocolError("State snapshot is missing its schema id"));
+        }
+        let schema = u16::from_le_bytes(bytes[..2].try_into().unwrap());
+        if schema != STATE_SNAPSHOT_SCHEMA {
+            return Err(ProtocolError::coerce_static_from_str(&format!(
+                "Unsupported state snapshot schema {} (expected {})",
+                schema, STATE_SNAPSHOT_SCHEMA
+            )));
+        }
+        let body: SnapshotBody = bincode::deserialize(&bytes[2..]).map_err(|e| {
+            ProtocolError::coerce_static_from_str(&format!("Could not decode state snapshot: {}", e))
+        })?;
+        let markets = match body.markets {
+            Some(markets) => Some(
+                markets
+                    .into_iter()
+                    .map(|(name, market)| Ok((name, market.try_into()?)))
+                    .collect::<Result<_>>()?,
+            ),
+            None => None,
+        };
+        Ok(Self {
+            markets,
+            assets: body.assets,
+            asset_nonces: body.asset_nonces,
+        })
+    }
+}
+
+impl State {
+    /// Take a snapshot of market, asset and nonce data
+    pub fn snapshot(&self) -> StateSnapshot {
+        StateSnapshot {
+            markets: self.markets.as_deref().cloned(),
+            assets: self.assets.as_deref().cloned(),
+            asset_nonces: self.asset_nonces.clone(),
+        }
+    }
+
+    /// Restore market, asset and nonce data from a snapshot. Nonces may be stale, so they
+    /// are flagged for refresh before the next order is signed.
+    pub fn restore_snapshot(&mut self, snapshot: StateSnapshot) {
+        self.markets = snapshot.markets.map(Arc::new);
+        self.assets = snapshot.assets.map(Arc::new);
+        if snapshot.asset_nonces.is_some() {
+            self.asset_nonces = snapshot.asset_nonces;
+            self.assets_nonces_refresh = true;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{StateSnapshot, STATE_SNAPSHOT_SCHEMA};
+    use crate::types::{Amount, Asset, AssetAmount, Market};
+    use std::collections::HashMap;
+
+    #[test]
+    fn snapshot_roundtrip_and_schema_check() {
+        let mut nonces = HashMap::new();
+        nonces.insert("eth".to_string(), vec![3, 4]);
+        let (eth, usdc) = (Asset::ETH.with_precision(4), Asset::USDC.with_precision(2));
+        let market = Market::new(
+            eth,
+            usdc,
+            AssetAmount {
+                asset: eth,
+                amount: Amount::new("0.0125", 4).unwrap(),
+            },
+            AssetAmount {
+                asset: usdc,
+                amount: Amount::new("10", 2).unwrap(),
+            },
+        );
+        let mut markets = HashMap::new();
+        markets.insert(market.market_name(), market);
+        let snapshot = StateSnapshot {
+            markets: Some(markets),
+            assets: Some(vec![Asset::ETH, Asset::BTC]),
+            asset_nonces: Some(nonces),
+        };
+        let mut bytes = snapshot.to_bytes().unwrap();
+        assert_eq!(StateSnapshot::from_bytes(&bytes).unwrap(), snapshot);
+        bytes[..2].copy_from_slice(&(STATE_SNAPSHOT_SCHEMA + 1).to_le_bytes());
+        assert!(StateSnapshot::from_bytes(&bytes).is_err());
+    }
+}
@@ -4,20 +4,25 @@
 use std::collections::{HashMap, HashSet};
 use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Arc;
+use std::time::{Duration, Instant};
 
 use async_recursion::async_recursion;
+use tokio::sync::watch;
 use tracing::trace;
 
+use super::order_nonces::{NonceBlock, NonceProvider};
 use super::signer::Signer;
-use crate::errors::{ProtocolError, Result};
+use crate::errors::{ProtocolError, Result, ResultExt};
 use crate::protocol::dh_fill_pool::DhFillPoolRequest;
-use crate::types::{Asset, Blockchain, Market};
+use crate::types::{AccountFeeRates, Asset, Blockchain, Market, MarketSymbol, Rate};
 
 //****************************************//
 //  Protocol state representation         //
 //****************************************//
 
-/// Client state shared across the protocol.
+/// Client state shared across the protocol. Many tasks share one `State` behind a `RwLock`
+/// and mostly hold the read lock, so counters that are updated per order (remaining orders,
+/// order timestamps) are atomics and must only be changed through the methods below.
 #[derive(Debug)]
 pub struct State {
     // Inside here we will have an explicit definition of all mutable
@@ -28,18 +33,31 @@ pub struct State {
     // incrementing `asset_nonces` are used to invalidate old state in the channel
     // here we keep track of the latest nonce for each asset
     pub asset_nonces: Option<HashMap<String, Vec<u32>>>,
-    // list of markets pulled from nash
-    pub markets: Option<HashMap<String, Market>>,
+    // list of markets pulled from nash, shared with the market cache if there is one
+    pub markets: Option<Arc<HashMap<String, Market>>>,
     // list of assets supported for trading in nash
-    pub assets: Option<Vec<Asset>>,
+    pub assets: Option<Arc<Vec<Asset>>>,
+    // maker and taker fee rates of the account, signed into order payloads. Orders fetch
+    // them first when they are unknown or older than `FEE_RATES_MAX_AGE`
+    fee_rates: Option<(AccountFeeRates, Instant)>,
     // remaining orders before state signing is required
     // FIXME: move r-pool from global indexmap here
     pub remaining_orders: AtomicU64,
+    // `remaining_orders` as last reported by the exchange, published to tasks pacing their
+    // orders. `None` until an order response arrives, and again after states were signed
+    signing_capacity: watch::Sender<Option<u64>>,
+    signing_capacity_receiver: watch::Receiver<Option<u64>>,
+    // order timestamps handed out so far. Order timestamps double as order nonces, so
+    // orders signed concurrently must never share one
+    order_nonces: NonceProvider,
     // optional affiliate code, will receive a share of fees generated
     pub affiliate_code: Option<String>,
     pub assets_nonces_refresh: bool,
     pub dont_sign_states: bool, // flag only for market maker users
 
+    // optional market and asset metadata shared with other clients
+    pub market_cache: Option<Arc<MarketMetadataCache>>,
+
     pub place_order_semaphore: Arc<tokio::sync::Semaphore>,
     pub sign_all_states_semaphore: Arc<tokio::sync::Semaphore>,
     pub k1_fill_pool_semaphore: Arc<tokio::sync::Semaphore>,
@@ -48,15 +66,21 @@ pub struct State {
 
 impl State {
     pub fn new(signer: Option<Signer>) -> Self {
+        let (signing_capacity, signing_capacity_receiver) = watch::channel(None);
         Self {
             signer,
             asset_nonces: None,
             markets: None,
             assets: None,
+            fee_rates: None,
             remaining_orders: AtomicU64::new(0),
+            signing_capacity,
+            signing_capacity_receiver,
+            order_nonces: NonceProvider::default(),
             affiliate_code: None,
             assets_nonces_refresh: false,
             dont_sign_states: false,
+            market_cache: None,
             // Set these here for now
             place_order_semaphore: Arc::new(tokio::sync::Semaphore::new(1)),
             sign_all_states_semaphore: Arc::new(tokio::sync::Semaphore::new(1)),
@@ -73,17 +97,42 @@ impl State {
         Ok(Self::new(signer))
     }
 
+    /// Create state from the contents of a Nash produced .json key file
+    pub fn from_keyfile_json(keyfile: &str) -> Result<Self> {
+        let signer = Some(Signer::from_keyfile_json(keyfile)?);
+        Ok(Self::new(signer))
+    }
+
     pub fn from_keys(secret: &str, session: &str) -> Result<Self> {
         let signer = Some(Signer::from_data(secret, session)?);
         Ok(Self::new(signer))
     }
 
+    /// Share market and asset metadata with other clients through `cache`. If the cache
+    /// was already filled by another client, this state starts out with its contents
+    pub fn with_market_cache(mut self, cache: Arc<MarketMetadataCache>) -> Self {
+        if let Some(metadata) = cache.get() {
+            self.markets = Some(metadata.markets.clone());
+            self.assets = Some(metadata.assets.clone());
+        }
+        self.market_cache = Some(cache);
+        self
+    }
+
     pub fn signer(&self) -> Result<&Signer> {
         self.signer
             .as_ref()
             .ok_or(ProtocolError("Signer not initiated"))
     }
 
+    /// Market to trade on for `market`, which may be listed the other way round, e.g.
+    /// `usdc_eth` gives the inverted `eth_usdc` market
+    pub fn resolve_market(&self, market: &MarketSymbol) -> Result<Market> {
+        self.get_market(market.as_str())
+            .or_else(|_| self.get_market(market.reversed().as_str()).map(|market| market.invert()))
+            .with_context(|| format!("market {}", market))
+    }
+
     pub fn get_market(&self, market_name: &str) -> Result<Market> {
         let market_map = self
             .markets
@@ -95,20 +144,82 @@ impl State {
             .map(|m| m.clone())
     }
 
+    /// Fee rate to sign into the fill payloads of an order, see `AccountFeeRates::for_order`.
+    /// Fails while the account's fee rates are unknown rather than signing a made up rate.
+    pub fn order_fee_rate(&self, allow_taker: bool) -> Result<Rate> {
+        self.fee_rates()
+            .map(|rates| rates.for_order(allow_taker))
+            .ok_or(ProtocolError("Account fee rates are unknown"))
+    }
+
+    pub fn fee_rates(&self) -> Option<&AccountFeeRates> {
+        self.fee_rates.as_ref().map(|(rates, _)| rates)
+    }
+
+    /// Store fee rates just fetched from the exchange
+    pub fn set_fee_rates(&mut self, rates: AccountFeeRates) {
+        self.fee_rates = Some((rates, Instant::now()));
+    }
+
+    /// Whether fee rates must be fetched before signing an order: they are unknown or were
+    /// fetched more than `FEE_RATES_MAX_AGE` ago, and may have changed with trading volume
+    pub fn fee_rates_stale(&self) -> bool {
+        self.fee_rates.as_ref().map_or(true, |(_, fetched_at)| {
+            fetched_at.elapsed() >= FEE_RATES_MAX_AGE
+        })
+    }
+
     pub fn get_remaining_orders(&self) -> u64 {
         return self.remaining_orders.load(Ordering::Relaxed);
     }
 
     pub fn set_remaining_orders(&self, n: u64) {
-        return self.remaining_orders.store(n, Ordering::Relaxed);
+        self.remaining_orders.store(n, Ordering::Relaxed);
+        self.publish_signing_capacity(Some(n));
     }
 
     pub fn decr_remaining_orders(&self) {
-        self.remaining_orders.fetch_sub(1, Ordering::Relaxed);
+        self.decr_n_remaining_orders(1);
     }
 
     pub fn decr_n_remaining_orders(&self, n: u64) {
-        self.remaining_orders.fetch_sub(n, Ordering::Relaxed);
+        // a single atomic update, so concurrent decrements can't wrap below zero
+        let previous = self
+            .remaining_orders
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |remaining| {
+                Some(remaining.saturating_sub(n))
+            })
+            .expect("update closure always returns a value");
+        self.publish_signing_capacity(Some(previous.saturating_sub(n)));
+    }
+
+    /// Reserve `n` consecutive order timestamps (milliseconds) and return the first. Order
+    /// timestamps are used as order nonces, so each one is handed out at most once even
+    /// when many tasks place orders through the same state at the same millisecond.
+    pub fn reserve_order_times(&self, n: usize) -> i64 {
+        self.order_nonces.reserve(n)
+    }
+
+    /// Reserve `n` order timestamps up front for a burst of orders, see `NonceBlock`
+    pub fn reserve_order_nonce_block(&self, n: usize) -> NonceBlock {
+        self.order_nonces.reserve_block(n)
+    }
+
+    /// Forget the published signing capacity, e.g. once states were signed and the exchange
+    /// has not yet reported the new count
+    pub fn reset_signing_capacity(&self) {
+        self.publish_signing_capacity(None);
+    }
+
+    /// Watch the number of orders left before states must be signed, as reported by the
+    /// exchange on every order response. `None` while the count is unknown.
+    pub fn signing_capacity(&self) -> watch::Receiver<Option<u64>> {
+        self.signing_capacity_receiver.clone()
+    }
+
+    fn publish_signing_capacity(&self, capacity: Option<u64>) {
+        // cannot fail, `self` holds a receiver
+        let _ = self.signing_capacity.send(capacity);
     }
 
     /// Check if pools need a refill
@@ -156,11 +267,150 @@ impl State {
     }
 }
 
+/// Market and asset lists as returned by `ListMarkets`
+#[derive(Debug)]
+pub struct MarketMetadata {
+    pub markets: Arc<HashMap<String, Market>>,
+    pub assets: Arc<Vec<Asset>>,
+}
+
+/// Market metadata cache that can be shared across several clients (and their `State`)
+/// talking to the same environment, so only one of them needs to pull the market list
+#[derive(Debug, Default)]
+pub struct MarketMetadataCache {
+    inner: std::sync::RwLock<Option<Arc<MarketMetadata>>>,
+}
+
+impl MarketMetadataCache {
+    pub fn new() -> Arc<Self> {
+        Arc::new(Self::default())
+    }
+
+    /// Latest metadata stored in the cache, if any
+    pub fn get(&self) -> Option<Arc<MarketMetadata>> {
+        self.inner
+            .read()
+            .expect("Market cache lock poisoned")
+            .clone()
+    }
+
+    /// Replace cached metadata
+    pub fn set(&self, markets: Arc<HashMap<String, Market>>, assets: Arc<Vec<Asset>>) {
+        let metadata = MarketMetadata { markets, assets };
+        *self.inner.write().expect("Market cache lock poisoned") = Some(Arc::new(metadata));
+    }
+}
+
 #[derive(Hash, PartialEq, Eq)]
 enum RValPoolTypes {
     R1,
     K1,
 }
 
+/// How long fetched fee rates are signed into orders before they are fetched again
+pub const FEE_RATES_MAX_AGE: Duration = Duration::from_secs(60 * 60);
+
 pub const MAX_R_VAL_POOL_SIZE: u32 = 100;
 pub const R_VAL_FILL_POOL_THRESHOLD: u32 = 60;
+
+#[cfg(test)]
+mod tests {
+    use super::{MarketMetadataCache, State};
+    use crate::types::{AccountFeeRates, Asset};
+    use bigdecimal::BigDecimal;
+    use std::collections::{HashMap, HashSet};
+    use std::str::FromStr;
+    use std::sync::Arc;
+
+    #[test]
+    fn signing_capacity_follows_remaining_orders() {
+        let state = State::new(None);
+        let capacity = state.signing_capacity();
+        assert_eq!(*capacity.borrow(), None);
+        state.set_remaining_orders(20);
+        state.decr_n_remaining_orders(3);
+        assert_eq!(*capacity.borrow(), Some(17));
+        state.reset_signing_capacity();
+        assert_eq!(*capacity.borrow(), None);
+    }
+
+    #[test]
+    fn concurrent_decrements_saturate() {
+        let state = Arc::new(State::new(None));
+        state.set_remaining_orders(50);
+        let workers: Vec<_> = (0..8)
+            .map(|_| {
+                let state = state.clone();
+                std::thread::spawn(move || {
+                    for _ in 0..10 {
+                        state.decr_remaining_orders();
+                    }
+                })
+            })
+            .collect();
+        for worker in workers {
+            worker.join().unwrap();
+        }
+        assert_eq!(state.get_remaining_orders(), 0);
+    }
+
+    #[test]
+    fn concurrent_order_times_are_unique() {
+        let state = Arc::new(State::new(None));
+        let workers: Vec<_> = (0..8)
+            .map(|worker| {
+                let state = state.clone();
+                std::thread::spawn(move || {
+                    let mut times = Vec::new();
+                    for _ in 0..100 {
+                        // mix single orders and batches of 3
+                        let n = if worker % 2 == 0 { 1 } else { 3 };
+                        let first = state.reserve_order_times(n);
+                        times.extend((0..n as i64).map(|index| first + index));
+                    }
+                    times
+                })
+            })
+            .collect();
+        let mut seen = HashSet::new();
+        for worker in workers {
+            for time in worker.join().unwrap() {
+                assert!(seen.insert(time), "order time {} handed out twice", time);
+            }
+        }
+        assert_eq!(seen.len(), 4 * 100 + 4 * 300);
+    }
+
+    #[test]
+    fn orders_need_fetched_fee_rates() {
+        let mut state = State::new(None);
+        assert!(state.fee_rates_stale());
+        assert!(state.order_fee_rate(true).is_err());
+        let maker = BigDecimal::from_str("0.001").unwrap();
+        let taker = BigDecimal::from_str("0.002").unwrap();
+        state.set_fee_rates(AccountFeeRates::new(maker, taker.clone()).unwrap());
+        assert!(!state.fee_rates_stale());
+        let rate = state.order_fee_rate(true).unwrap();
+        assert_eq!(rate.to_bigdecimal().unwrap(), taker);
+    }
+
+    #[test]
+    fn states_share_cached_markets() {
+        let cache = MarketMetadataCache::new();
+        assert!(State::new(None)
+            .with_market_cache(cache.clone())
+            .markets
+            .is_none());
+        cache.set(Arc::new(HashMap::new()), Arc::new(vec![Asset::ETH]));
+        let first = State::new(None).with_market_cache(cache.clone());
+        let second = State::new(None).with_market_cache(cache);
+        assert!(Arc::ptr_eq(
+            first.markets.as_ref().unwrap(),
+            second.markets.as_ref().unwrap()
+        ));
+        assert!(Arc::ptr_eq(
+            first.assets.as_ref().unwrap(),
+            second.assets.as_ref().unwrap()
+        ));
+    }
+}
@@ -0,0 +1,311 @@
+//! Incremental decoding of GraphQL responses that are mostly one long list, e.g. a full page
+//! of trade history. Items are handed out as soon as they are decoded, so the response is
+//! never held in memory as a whole `serde_json::Value`.
+
+use std::fmt;
+use std::io::Read;
+use std::marker::PhantomData;
+
+use serde::de::{
+    self, DeserializeOwned, DeserializeSeed, IgnoredAny, MapAccess, SeqAccess, Visitor,
+};
+use serde::Deserializer;
+
+use super::NashProtocol;
+use crate::errors::{ProtocolError, Result};
+
+/// A request whose response can be decoded item by item
+pub trait StreamableList: NashProtocol {
+    /// Item as generated from the GraphQL query
+    type Raw: DeserializeOwned + Send;
+    type Item: Send;
+
+    /// Fields leading from `data` to the list, e.g. `["listTrades", "trades"]`
+    fn list_path(&self) -> &'static [&'static str];
+
+    fn convert(raw: Self::Raw) -> Result<Self::Item>;
+}
+
+/// What a streamed response contained besides the items of the list
+#[derive(Clone, Debug, Default)]
+pub struct ListEnvelope {
+    /// Fields next to the list, e.g. `next` for the cursor of the next page
+    pub siblings: serde_json::Map<String, serde_json::Value>,
+    /// Messages of the GraphQL errors in the response
+    pub errors: Vec<String>,
+}
+
+impl ListEnvelope {
+    /// Cursor of the next page, for the paginated list queries
+    pub fn next_page(&self) -> Option<String> {
+        self.siblings
+            .get("next")
+            .and_then(|next| next.as_str())
+            .map(str::to_string)
+    }
+}
+
+/// Decode a GraphQL response read from `reader`, passing every item of the list at `path`
+/// (below `data`) to `on_item` as soon as it is decoded. Decoding stops at the first error
+/// returned by `on_item`, which is then returned.
+pub fn decode_list<R, T, F>(reader: R, path: &[&str], on_item: F) -> Result<ListEnvelope>
+where
+    R: Read,
+    T: DeserializeOwned,
+    F: FnMut(T) -> Result<()>,
+{
+    if path.is_empty() {
+        return Err(ProtocolError("List path must not be empty"));
+    }
+    let mut decoder = Decoder {
+        on_item,
+        envelope: ListEnvelope::default(),
+        failed: None,
+        _item: PhantomData,
+    };
+    let mut deserializer = serde_json::Deserializer::from_reader(reader);
+    let decoded = RootSeed {
+        decoder: &mut decoder,
+        path,
+    }
+    .deserialize(&mut deserializer)
+    .and_then(|_| deserializer.end());
+    if let Some(error) = decoder.failed {
+        return Err(error);
+    }
+    decoded.map_err(|e| ProtocolError::with_source("Couldn't parse response", e))?;
+    Ok(decoder.envelope)
+}
+
+struct Decoder<T, F> {
+    on_item: F,
+    envelope: ListEnvelope,
+    failed: Option<ProtocolError>,
+    _item: PhantomData<T>,
+}
+
+/// The whole response: `data` and `errors`
+struct RootSeed<'a, 'p, T, F> {
+    decoder: &'a mut Decoder<T, F>,
+    path: &'p [&'p str],
+}
+
+impl<'de, 'a, 'p, T, F> DeserializeSeed<'de> for RootSeed<'a, 'p, T, F>
+where
+    T: DeserializeOwned,
+    F: FnMut(T) -> Result<()>,
+{
+    type Value = ();
+
+    fn deserialize<D: Deserializer<'de>>(
+        self,
+        deserializer: D,
+    ) -> std::result::Result<(), D::Error> {
+        deserializer.deserialize_map(self)
+    }
+}
+
+impl<'de, 'a, 'p, T, F> Visitor<'de> for RootSeed<'a, 'p, T, F>
+where
+    T: DeserializeOwned,
+    F: FnMut(T) -> Result<()>,
+{
+    type Value = ();
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a GraphQL response")
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> std::result::Result<(), A::Error> {
+        while let Some(key) = map.next_key::<String>()? {
+            match key.as_str() {
+                "data" => map.next_value_seed(PathSeed {
+                    decoder: &mut *self.decoder,
+                    path: self.path,
+                })?,
+                "errors" => {
+                    let errors: Option<Vec<serde_json::Value>> = map.next_value()?;
+                    self.decoder
+                        .envelope
+                        .errors
+                        .extend(errors.into_iter().flatten().map(
+                            |error| match error.get("message") {
+                                Some(serde_json::Value::String(message)) => message.clone(),
+                                _ => error.to_string(),
+                            },
+                        ));
+                }
+                _ => {
+                    map.next_value::<IgnoredAny>()?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// An object on the way to the list, `path` being the fields left to follow
+struct PathSeed<'a, 'p, T, F> {
+    decoder: &'a mut Decoder<T, F>,
+    path: &'p [&'p str],
+}
+
+impl<'de, 'a, 'p, T, F> DeserializeSeed<'de> for PathSeed<'a, 'p, T, F>
+where
+    T: DeserializeOwned,
+    F: FnMut(T) -> Result<()>,
+{
+    type Value = ();
+
+    fn deserialize<D: Deserializer<'de>>(
+        self,
+        deserializer: D,
+    ) -> std::result::Result<(), D::Error> {
+        deserializer.deserialize_option(self)
+    }
+}
+
+impl<'de, 'a, 'p, T, F> Visitor<'de> for PathSeed<'a, 'p, T, F>
+where
+    T: DeserializeOwned,
+    F: FnMut(T) -> Result<()>,
+{
+    type Value = ();
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        write!(formatter, "an object with field {}", self.path[0])
+    }
+
+    // a failed query comes back with `null` data
+    fn visit_none<E: de::Error>(self) -> std::result::Result<(), E> {
+        Ok(())
+    }
+
+    fn visit_unit<E: de::Error>(self) -> std::result::Result<(), E> {
+        Ok(())
+    }
+
+    fn visit_some<D: Deserializer<'de>>(
+        self,
+        deserializer: D,
+    ) -> std::result::Result<(), D::Error> {
+        deserializer.deserialize_map(self)
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> std::result::Result<(), A::Error> {
+        let (field, rest) = (self.path[0], &self.path[1..]);
+        while let Some(key) = map.next_key::<String>()? {
+            if key == field && rest.is_empty() {
+                map.next_value_seed(ListSeed {
+                    decoder: &mut *self.decoder,
+                })?;
+            } else if key == field {
+                map.next_value_seed(PathSeed {
+                    decoder: &mut *self.decoder,
+                    path: rest,
+                })?;
+            } else if rest.is_empty() {
+                let value = map.next_value()?;
+                self.decoder.envelope.siblings.insert(key, value);
+            } else {
+                map.next_value::<IgnoredAny>()?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The list itself
+struct ListSeed<'a, T, F> {
+    decoder: &'a mut Decoder<T, F>,
+}
+
+impl<'de, 'a, T, F> DeserializeSeed<'de> for ListSeed<'a, T, F>
+where
+    T: DeserializeOwned,
+    F: FnMut(T) -> Result<()>,
+{
+    type Value = ();
+
+    fn deserialize<D: Deserializer<'de>>(
+        self,
+        deserializer: D,
+    ) -> std::result::Result<(), D::Error> {
+        deserializer.deserialize_option(self)
+    }
+}
+
+impl<'de, 'a, T, F> Visitor<'de> for ListSeed<'a, T, F>
+where
+    T: DeserializeOwned,
+    F: FnMut(T) -> Result<()>,
+{
+    type Value = ();
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a list")
+    }
+
+    fn visit_none<E: de::Error>(self) -> std::result::Result<(), E> {
+        Ok(())
+    }
+
+    fn visit_unit<E: de::Error>(self) -> std::result::Result<(), E> {
+        Ok(())
+    }
+
+    fn visit_some<D: Deserializer<'de>>(
+        self,
+        deserializer: D,
+    ) -> std::result::Result<(), D::Error> {
+        deserializer.deserialize_seq(self)
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> std::result::Result<(), A::Error> {
+        while let Some(item) = seq.next_element::<T>()? {
+            if let Err(error) = (self.decoder.on_item)(item) {
+                self.decoder.failed = Some(error);
+                // abandon the rest of the response
+                return Err(de::Error::custom("item rejected"));
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::decode_list;
+    use crate::errors::ProtocolError;
+
+    #[test]
+    fn decodes_items_and_siblings() {
+        let response = br#"{
+            "data": { "listTrades": { "trades": [1, 2, 3], "next": "cursor" }, "other": [4] },
+            "errors": [{ "message": "partial", "path": ["other"] }]
+        }"#;
+        let mut items = Vec::new();
+        let envelope = decode_list(&response[..], &["listTrades", "trades"], |item: u32| {
+            items.push(item);
+            Ok(())
+        })
+        .unwrap();
+        assert_eq!(items, vec![1, 2, 3]);
+        assert_eq!(envelope.next_page().as_deref(), Some("cursor"));
+        assert_eq!(envelope.errors, vec!["partial".to_string()]);
+
+        let result = decode_list(&response[..], &["listTrades", "trades"], |item: u32| {
+            if item == 2 {
+                Err(ProtocolError("stop"))
+            } else {
+                Ok(())
+            }
+        });
+        assert_eq!(result.unwrap_err().message(), "stop");
+
+        let failed = br#"{ "data": null, "errors": [{ "message": "Not allowed" }] }"#;
+        let envelope =
+            decode_list(&failed[..], &["listTrades", "trades"], |_: u32| Ok(())).unwrap();
+        assert_eq!(envelope.errors, vec!["Not allowed".to_string()]);
+    }
+}
@@ -1,6 +1,6 @@
 use crate::graphql;
 use graphql::new_account_trades;
-use graphql_client::GraphQLQuery;
+use crate::protocol::query_overrides::build_query;
 
 /// Initiate subscription to get new trades
 #[derive(Clone, Debug)]
@@ -10,7 +10,7 @@ pub struct SubscribeAccountTrades {
 
 impl SubscribeAccountTrades {
     pub fn make_query(&self) -> graphql_client::QueryBody<new_account_trades::Variables> {
-        graphql::NewAccountTrades::build_query(new_account_trades::Variables {
+        build_query::<graphql::NewAccountTrades>(new_account_trades::Variables {
             payload: new_account_trades::NewAccountTradesParams {
                 market_name: self.market_name.clone(),
             }
@@ -1,6 +1,7 @@
 use crate::graphql;
 use graphql::subscribe_trades;
-use graphql_client::GraphQLQuery;
+use crate::protocol::query_overrides::build_query;
+use crate::types::MarketSymbol;
 
 /// Initiate subscription to get new trades
 #[derive(Clone, Debug)]
@@ -8,9 +9,17 @@ pub struct SubscribeTrades {
     pub market: String,
 }
 
+impl From<MarketSymbol> for SubscribeTrades {
+    fn from(market: MarketSymbol) -> Self {
+        Self {
+            market: market.into(),
+        }
+    }
+}
+
 impl SubscribeTrades {
     pub fn make_query(&self) -> graphql_client::QueryBody<subscribe_trades::Variables> {
-        graphql::SubscribeTrades::build_query(subscribe_trades::Variables {
+        build_query::<graphql::SubscribeTrades>(subscribe_trades::Variables {
             market_name: self.market.clone(),
         })
     }
@@ -1,10 +1,10 @@
 use super::super::super::ResponseOrError;
 use super::request::SubscribeTrades;
-use crate::errors::{ProtocolError, Result};
+use crate::errors::Result;
 use crate::graphql;
 use crate::types::{BuyOrSell, Trade, AccountTradeSide};
 use graphql::subscribe_trades;
-use chrono::{DateTime, Utc};
+use crate::types::timestamp::parse_timestamp;
 use bigdecimal::BigDecimal;
 use std::str::FromStr;
 use crate::protocol::traits::TryFromState;
@@ -42,8 +42,7 @@ impl TryFromState<subscribe_trades::ResponseData> for Vec<Trade> {
                 maker_order_id: trade_data.maker_order_id.clone(),
                 account_side: trade_data.account_side.into(),
                 id: trade_data.id,
-                executed_at: DateTime::<Utc>::from_str(&trade_data.executed_at)
-                    .map_err(|_| ProtocolError("Could not convert value to DateTime"))?,
+                executed_at: parse_timestamp(&trade_data.executed_at)?,
                 limit_price,
                 direction: trade_data.direction.into(),
             })
@@ -1,4 +1,4 @@
-//! Subscription to get new trades
+//! Subscription to changes of the account balances
 
 mod request;
 mod response;
@@ -1,6 +1,6 @@
 use crate::graphql;
 use graphql::updated_account_balances;
-use graphql_client::GraphQLQuery;
+use crate::protocol::query_overrides::build_query;
 
 /// Initiate subscription to new account *trading* balances
 #[derive(Clone, Debug)]
@@ -11,7 +11,7 @@ pub struct SubscribeAccountBalances {
 
 impl SubscribeAccountBalances {
     pub fn make_query(&self) -> graphql_client::QueryBody<updated_account_balances::Variables> {
-        graphql::UpdatedAccountBalances::build_query(updated_account_balances::Variables {
+        build_query::<graphql::UpdatedAccountBalances>(updated_account_balances::Variables {
             payload: updated_account_balances::UpdatedAccountBalancesParams {
                 currency: self.symbol.clone(),
             }
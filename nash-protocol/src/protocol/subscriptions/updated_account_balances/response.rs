@@ -13,28 +13,69 @@ use std::sync::Arc;
 use tokio::sync::RwLock;
 use async_trait::async_trait;
 
-/// List of new incoming trades for a market via subscription.
-#[derive(Clone, Debug)]
+/// Balances of the assets that changed, in the same breakdown as `ListAccountBalancesResponse`.
+/// An asset is only in a map if the update carried that part of its balance.
+#[derive(Clone, Debug, Default)]
 pub struct AccountBalancesResponse {
+    /// Funds available in the state channel
     pub balances: HashMap<Asset, BigDecimal>,
+    /// Funds in open orders
+    pub in_orders: HashMap<Asset, BigDecimal>,
+    /// Funds pending in the state channel, e.g. deposits not yet confirmed
+    pub pending: HashMap<Asset, BigDecimal>,
+    /// Funds in the personal wallet
+    pub personal: HashMap<Asset, BigDecimal>,
 }
 
-#[async_trait]
-impl TryFromState<updated_account_balances::ResponseData> for HashMap<Asset, BigDecimal> {
-    async fn from(response: updated_account_balances::ResponseData, _state: Arc<RwLock<State>>) -> Result<HashMap<Asset, BigDecimal>> {
-        let mut balances = HashMap::new();
-        for balance in response.updated_account_balances {
-            let symbol = balance.asset.unwrap().symbol;
-            if let Ok(asset) = Asset::from_str(&symbol) {
-                let state_channel_amount = BigDecimal::from_str(&balance.available.unwrap().amount).unwrap();
-                balances.insert(asset, state_channel_amount);
+fn insert_amount(
+    map: &mut HashMap<Asset, BigDecimal>,
+    asset: Asset,
+    amount: Option<&str>,
+) -> Result<()> {
+    if let Some(amount) = amount {
+        map.insert(asset, BigDecimal::from_str(amount)?);
+    }
+    Ok(())
+}
+
+impl AccountBalancesResponse {
+    /// Assets with any part of their balance in this update
+    pub fn assets(&self) -> Vec<Asset> {
+        let mut assets: Vec<Asset> = Vec::new();
+        for map in &[&self.balances, &self.in_orders, &self.pending, &self.personal] {
+            for asset in map.keys() {
+                if !assets.contains(asset) {
+                    assets.push(*asset);
+                }
             }
         }
+        assets
+    }
 
+    fn from_data(response: updated_account_balances::ResponseData) -> Result<Self> {
+        let mut balances = Self::default();
+        for balance in response.updated_account_balances {
+            // assets unknown to this client are skipped, as when listing balances
+            let asset = match balance.asset.map(|asset| Asset::from_str(&asset.symbol)) {
+                Some(Ok(asset)) => asset,
+                _ => continue,
+            };
+            insert_amount(&mut balances.balances, asset, balance.available.as_ref().map(|b| b.amount.as_str()))?;
+            insert_amount(&mut balances.in_orders, asset, balance.in_orders.as_ref().map(|b| b.amount.as_str()))?;
+            insert_amount(&mut balances.pending, asset, balance.pending.as_ref().map(|b| b.amount.as_str()))?;
+            insert_amount(&mut balances.personal, asset, balance.personal.as_ref().map(|b| b.amount.as_str()))?;
+        }
         Ok(balances)
     }
 }
 
+#[async_trait]
+impl TryFromState<updated_account_balances::ResponseData> for AccountBalancesResponse {
+    async fn from(response: updated_account_balances::ResponseData, _state: Arc<RwLock<State>>) -> Result<AccountBalancesResponse> {
+        Self::from_data(response)
+    }
+}
+
 impl SubscribeAccountBalances {
     pub async fn response_from_graphql(
         &self,
@@ -43,12 +84,48 @@ impl SubscribeAccountBalances {
     ) -> Result<ResponseOrError<AccountBalancesResponse>> {
         Ok(match response {
             ResponseOrError::Response(data) => {
-                let response = data.data;
-                ResponseOrError::from_data(AccountBalancesResponse {
-                    balances: TryFromState::from(response, state).await?,
-                })
+                ResponseOrError::from_data(TryFromState::from(data.data, state).await?)
             }
             ResponseOrError::Error(error) => ResponseOrError::Error(error),
         })
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::AccountBalancesResponse;
+    use crate::graphql::updated_account_balances;
+    use crate::types::Asset;
+    use bigdecimal::BigDecimal;
+    use std::str::FromStr;
+
+    #[test]
+    fn updates_carry_every_part_of_a_balance() {
+        let data: updated_account_balances::ResponseData = serde_json::from_value(serde_json::json!({
+            "updatedAccountBalances": [
+                {
+                    "asset": { "name": "Ethereum", "symbol": "eth" },
+                    "available": { "amount": "1.5" },
+                    "inOrders": { "amount": "0.5" },
+                    "pending": { "amount": "0" },
+                    "personal": null
+                },
+                {
+                    "asset": { "name": "Unknown", "symbol": "not_an_asset" },
+                    "available": { "amount": "1" },
+                    "inOrders": null,
+                    "pending": null,
+                    "personal": null
+                }
+            ]
+        }))
+        .unwrap();
+        let balances = AccountBalancesResponse::from_data(data).unwrap();
+        let amount = |value: &str| BigDecimal::from_str(value).unwrap();
+        assert_eq!(balances.balances.get(&Asset::ETH), Some(&amount("1.5")));
+        assert_eq!(balances.in_orders.get(&Asset::ETH), Some(&amount("0.5")));
+        assert_eq!(balances.pending.get(&Asset::ETH), Some(&amount("0")));
+        assert!(balances.personal.is_empty());
+        assert_eq!(balances.assets(), vec![Asset::ETH]);
+    }
+}
@@ -1,6 +1,7 @@
+use crate::types::timestamp::format_timestamp;
 use crate::graphql;
 use graphql::updated_account_orders;
-use graphql_client::GraphQLQuery;
+use crate::protocol::query_overrides::build_query;
 use crate::types::{BuyOrSell, OrderStatus, OrderType, DateTimeRange};
 
 /// Initiate subscription to get new orders for an account
@@ -46,7 +47,7 @@ impl From<OrderType> for updated_account_orders::OrderType {
 
 impl SubscribeAccountOrders {
     pub fn make_query(&self) -> graphql_client::QueryBody<updated_account_orders::Variables> {
-        graphql::UpdatedAccountOrders::build_query(updated_account_orders::Variables {
+        build_query::<graphql::UpdatedAccountOrders>(updated_account_orders::Variables {
             payload: updated_account_orders::UpdatedAccountOrdersParams {
                 market_name: self.market.clone(),
                 buy_or_sell: self.buy_or_sell.map(|x| x.into()),
@@ -58,8 +59,8 @@ impl SubscribeAccountOrders {
                     .order_type
                     .as_ref()
                     .map(|x| x.iter().map(|x| Some(x.clone().into())).collect()),
-                range_start: self.range.as_ref().map(|x| format!("{:?}", x.start)),
-                range_stop: self.range.as_ref().map(|x| format!("{:?}", x.stop)),
+                range_start: self.range.as_ref().map(|x| format_timestamp(&x.start)),
+                range_stop: self.range.as_ref().map(|x| format_timestamp(&x.stop)),
             }
         })
     }
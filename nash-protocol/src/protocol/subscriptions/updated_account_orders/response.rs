@@ -1,6 +1,7 @@
 use super::super::super::{DataResponse, ResponseOrError};
 use super::request::SubscribeAccountOrders;
 use crate::errors::{ProtocolError, Result};
+use std::convert::{TryFrom, TryInto};
 use crate::types::{
     BuyOrSell, Order, OrderCancellationPolicy, OrderCancellationReason,
     OrderStatus, OrderType,
@@ -10,7 +11,7 @@ use crate::protocol::state::State;
 use std::sync::Arc;
 use tokio::sync::RwLock;
 use bigdecimal::BigDecimal;
-use chrono::{DateTime, Utc};
+use crate::types::timestamp::parse_timestamp;
 use std::str::FromStr;
 
 /// Order book updates pushed over a subscription consist of a list of bid orders and
@@ -45,8 +46,7 @@ impl SubscribeAccountOrders {
                         Some(price) => Some(BigDecimal::from_str(&price.amount)?),
                         None => None,
                     };
-                    let placed_at = DateTime::<Utc>::from_str(&order_data.placed_at)
-                        .map_err(|_| ProtocolError("Could not convert value to DateTime"))?;
+                    let placed_at = parse_timestamp(&order_data.placed_at)?;
                     orders.push(Order {
                         id: order_data.id.clone(),
                         client_order_id: order_data.client_order_id.clone(),
@@ -58,7 +58,7 @@ impl SubscribeAccountOrders {
                         stop_price,
                         placed_at,
                         trades: Vec::new(),
-                        cancellation_policy: (&order_data).into(),
+                        cancellation_policy: (&order_data).try_into()?,
                         cancellation_reason: order_data.cancellation_reason.as_ref().map(|x| x.into()),
                         buy_or_sell: order_data.buy_or_sell.into(),
                         order_type: order_data.type_.into(),
@@ -131,12 +131,14 @@ impl From<&updated_account_orders::OrderCancellationReason> for OrderCancellatio
     }
 }
 
-impl From<&updated_account_orders::UpdatedAccountOrdersUpdatedAccountOrders>
+impl TryFrom<&updated_account_orders::UpdatedAccountOrdersUpdatedAccountOrders>
     for Option<OrderCancellationPolicy>
 {
-    fn from(order: &updated_account_orders::UpdatedAccountOrdersUpdatedAccountOrders) -> Self {
+    type Error = ProtocolError;
+
+    fn try_from(order: &updated_account_orders::UpdatedAccountOrdersUpdatedAccountOrders) -> Result<Self> {
         if let Some(cancellation_policy) = &order.cancellation_policy {
-            Some(match cancellation_policy {
+            Ok(Some(match cancellation_policy {
                 updated_account_orders::OrderCancellationPolicy::FILL_OR_KILL => {
                     OrderCancellationPolicy::FillOrKill
                 }
@@ -144,18 +146,20 @@ impl From<&updated_account_orders::UpdatedAccountOrdersUpdatedAccountOrders>
                     OrderCancellationPolicy::GoodTilCancelled
                 }
                 updated_account_orders::OrderCancellationPolicy::GOOD_TIL_TIME => {
-                    // This unwrap is safe
-                    let cancel_at =
-                        DateTime::<Utc>::from_str(&order.cancel_at.as_ref().unwrap()).unwrap();
+                    let cancel_at = order
+                        .cancel_at
+                        .as_ref()
+                        .ok_or(ProtocolError("GoodTilTime order has no cancellation time"))?;
+                    let cancel_at = parse_timestamp(cancel_at)?;
                     OrderCancellationPolicy::GoodTilTime(cancel_at)
                 }
                 updated_account_orders::OrderCancellationPolicy::IMMEDIATE_OR_CANCEL => {
                     OrderCancellationPolicy::ImmediateOrCancel
                 }
                 _ => panic!("Unsupported OrderCancellationPolicy"),
-            })
+            }))
         } else {
-            None
+            Ok(None)
         }
     }
 }
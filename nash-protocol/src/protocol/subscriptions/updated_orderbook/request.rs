@@ -1,6 +1,7 @@
 use crate::graphql;
 use graphql::updated_orderbook;
-use graphql_client::GraphQLQuery;
+use crate::protocol::query_overrides::build_query;
+use crate::types::MarketSymbol;
 
 // Subscribe to order book updates on `Market`.
 #[derive(Clone, Debug)]
@@ -8,9 +9,17 @@ pub struct SubscribeOrderbook {
     pub market: String,
 }
 
+impl From<MarketSymbol> for SubscribeOrderbook {
+    fn from(market: MarketSymbol) -> Self {
+        Self {
+            market: market.into(),
+        }
+    }
+}
+
 impl SubscribeOrderbook {
     pub fn make_query(&self) -> graphql_client::QueryBody<updated_orderbook::Variables> {
-        graphql::UpdatedOrderbook::build_query(updated_orderbook::Variables {
+        build_query::<graphql::UpdatedOrderbook>(updated_orderbook::Variables {
             market_name: self.market.clone(),
         })
     }
@@ -1,6 +1,7 @@
 use crate::graphql;
 use graphql::updated_ticker;
-use graphql_client::GraphQLQuery;
+use crate::protocol::query_overrides::build_query;
+use crate::types::MarketSymbol;
 
 // Subscribe to ticker updates on `Market`.
 #[derive(Clone, Debug)]
@@ -8,9 +9,17 @@ pub struct SubscribeTicker {
     pub market: String,
 }
 
+impl From<MarketSymbol> for SubscribeTicker {
+    fn from(market: MarketSymbol) -> Self {
+        Self {
+            market: market.into(),
+        }
+    }
+}
+
 impl SubscribeTicker {
     pub fn make_query(&self) -> graphql_client::QueryBody<updated_ticker::Variables> {
-        graphql::UpdatedTicker::build_query(updated_ticker::Variables {
+        build_query::<graphql::UpdatedTicker>(updated_ticker::Variables {
             market_name: self.market.clone(),
         })
     }
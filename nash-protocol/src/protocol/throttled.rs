@@ -0,0 +1,133 @@
+//! Recognizing GraphQL errors with which the exchange refuses requests while it enforces
+//! its rate limits, e.g. "Too many requests, retry after 2s". The exchange has no error
+//! code for these, so they are recognized by their message. The phrases are kept narrow, as
+//! validation errors such as "too many decimals" must not pause the whole client.
+
+use std::time::Duration;
+
+use super::graphql::ErrorResponse;
+
+/// Phrases of throttling errors, lowercase
+const THROTTLE_PHRASES: &[&str] = &["too many requests", "rate limit exceeded", "throttled"];
+/// Phrases that precede the time to wait, lowercase
+const RETRY_AFTER_PHRASES: &[&str] = &["retry after", "retry in", "try again in"];
+/// Longest wait taken from a throttling error, whatever the exchange asks for
+const MAX_RETRY_AFTER: Duration = Duration::from_secs(300);
+
+/// The exchange refused a request because too many were sent. Find it in the sources of
+/// the `ProtocolError` returned by `ResponseOrError::response_or_error` with
+/// `ProtocolError::find_source`.
+#[derive(Debug, thiserror::Error)]
+#[error("Throttled by the exchange")]
+pub struct Throttled {
+    /// How long the exchange asked to wait, if it said
+    pub retry_after: Option<Duration>,
+    #[source]
+    pub response: ErrorResponse,
+}
+
+impl ErrorResponse {
+    /// Whether the exchange refused the request because too many were sent
+    pub fn is_throttled(&self) -> bool {
+        self.errors.iter().any(|error| {
+            let message = error.message.to_lowercase();
+            THROTTLE_PHRASES
+                .iter()
+                .any(|phrase| message.contains(phrase))
+        })
+    }
+
+    /// Time to wait the exchange asked for in a throttling error, if any
+    pub fn retry_after(&self) -> Option<Duration> {
+        self.errors
+            .iter()
+            .filter_map(|error| parse_retry_after(&error.message))
+            .max()
+    }
+}
+
+impl Throttled {
+    /// `response` as a `Throttled` error, or back if it isn't one
+    pub fn from_response(response: ErrorResponse) -> Result<Self, ErrorResponse> {
+        if !response.is_throttled() {
+            return Err(response);
+        }
+        Ok(Self {
+            retry_after: response.retry_after(),
+            response,
+        })
+    }
+}
+
+/// Wait in e.g. "retry after 1.5s", "retry in 500 ms" or "try again in 2 seconds". A number
+/// without a unit is in seconds. Capped at `MAX_RETRY_AFTER`.
+fn parse_retry_after(message: &str) -> Option<Duration> {
+    let message = message.to_lowercase();
+    let rest = RETRY_AFTER_PHRASES.iter().find_map(|phrase| {
+        message
+            .find(phrase)
+            .map(|at| message[at + phrase.len()..].trim_start())
+    })?;
+    let number_len = rest
+        .find(|c: char| !c.is_ascii_digit() && c != '.')
+        .unwrap_or_else(|| rest.len());
+    let value: f64 = rest[..number_len].parse().ok()?;
+    let unit: String = rest[number_len..]
+        .trim_start()
+        .chars()
+        .take_while(|c| c.is_ascii_alphabetic())
+        .collect();
+    let seconds = match unit.as_str() {
+        "ms" | "millisecond" | "milliseconds" => value / 1000.0,
+        "m" | "min" | "mins" | "minute" | "minutes" => value * 60.0,
+        _ => value,
+    };
+    if !seconds.is_finite() || seconds < 0.0 {
+        return None;
+    }
+    Some(Duration::from_secs_f64(
+        seconds.min(MAX_RETRY_AFTER.as_secs_f64()),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Throttled, MAX_RETRY_AFTER};
+    use crate::protocol::graphql::{Error, ErrorResponse};
+    use std::time::Duration;
+
+    fn response(message: &str) -> ErrorResponse {
+        ErrorResponse {
+            errors: vec![Error {
+                message: message.to_string(),
+                path: Vec::new(),
+            }],
+        }
+    }
+
+    #[test]
+    fn throttling_errors_carry_their_retry_after() {
+        let throttled =
+            Throttled::from_response(response("Too many requests, retry after 2s")).unwrap();
+        assert_eq!(throttled.retry_after, Some(Duration::from_secs(2)));
+
+        let throttled =
+            Throttled::from_response(response("Rate limit exceeded. Try again in 250 ms")).unwrap();
+        assert_eq!(throttled.retry_after, Some(Duration::from_millis(250)));
+
+        let throttled = Throttled::from_response(response("Request throttled")).unwrap();
+        assert_eq!(throttled.retry_after, None);
+
+        assert!(Throttled::from_response(response("Invalid blockchain signature")).is_err());
+        assert!(Throttled::from_response(response("Price has too many decimals")).is_err());
+    }
+
+    #[test]
+    fn huge_retry_after_is_capped() {
+        let throttled = Throttled::from_response(response(
+            "Too many requests, retry after 99999999999999999999",
+        ))
+        .unwrap();
+        assert_eq!(throttled.retry_after, Some(MAX_RETRY_AFTER));
+    }
+}
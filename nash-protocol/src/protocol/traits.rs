@@ -1,5 +1,6 @@
 //! These traits describe the high level behavior of the Nash protocol. Clients can use them
 //! to provide a generic implementation across requests
+use super::latency::StageTimings;
 use super::subscriptions::SubscriptionResponse;
 use super::{ProtocolHook, ResponseOrError, State};
 use crate::errors::ProtocolError;
@@ -9,6 +10,7 @@ use tokio::sync::RwLock;
 use std::fmt::Debug;
 use std::sync::Arc;
 use crate::protocol::ErrorResponse;
+use crate::types::BuyOrSell;
 
 //****************************************//
 //  Nash protocol trait                   //
@@ -23,6 +25,30 @@ pub trait NashProtocol: Debug + Send + Sync {
     async fn acquire_permit(&self, _state: Arc<RwLock<State>>) -> Option<tokio::sync::OwnedSemaphorePermit> {
         None
     }
+    /// Market this request is bound to, if any. Transports that pool connections use this
+    /// to route every request on the same market over the same connection, in order
+    fn market_affinity(&self) -> Option<&str> {
+        None
+    }
+    /// Market and price of the limit order this request places, if any. Clients use this to
+    /// check prices against a fair value before submitting
+    fn limit_price(&self) -> Option<(&str, &str)> {
+        None
+    }
+    /// Side and amount of the limit order this request places, if any
+    fn limit_size(&self) -> Option<(BuyOrSell, &str)> {
+        None
+    }
+    /// Market and price of every limit order this request places. Requests placing several
+    /// orders at once override this; the default is the order of `limit_price`
+    fn limit_prices(&self) -> Vec<(&str, &str)> {
+        self.limit_price().into_iter().collect()
+    }
+    /// Side and amount of every limit order this request places, in the order of
+    /// `limit_prices`
+    fn limit_sizes(&self) -> Vec<(BuyOrSell, &str)> {
+        self.limit_size().into_iter().collect()
+    }
     /// Convert the protocol request to GraphQL from communication with Nash server
     async fn graphql(&self, state: Arc<RwLock<State>>) -> Result<serde_json::Value>;
     /// Convert JSON response to request to the protocol's associated type
@@ -60,6 +86,17 @@ pub trait NashProtocol: Debug + Send + Sync {
     }
 }
 
+/// Order requests that can report how long payload construction and signing took. Used
+/// by clients to enforce a `LatencyBudget`
+#[async_trait]
+pub trait TimedNashProtocol: NashProtocol {
+    /// Same as `NashProtocol::graphql`, with construction and signing stages timed
+    async fn graphql_timed(
+        &self,
+        state: Arc<RwLock<State>>,
+    ) -> Result<(serde_json::Value, StageTimings)>;
+}
+
 //****************************************//
 //  Nash protocol pipeline trait          //
 //****************************************//
@@ -77,6 +114,23 @@ pub trait NashProtocolPipeline: Debug + Send + Sync {
     async fn acquire_permit(&self, _state: Arc<RwLock<State>>) -> Option<tokio::sync::OwnedSemaphorePermit> {
         None
     }
+    /// Market and price of the limit order this pipeline places, if any
+    fn limit_price(&self) -> Option<(&str, &str)> {
+        None
+    }
+    /// Side and amount of the limit order this pipeline places, if any
+    fn limit_size(&self) -> Option<(BuyOrSell, &str)> {
+        None
+    }
+    /// Market and price of every limit order this pipeline places
+    fn limit_prices(&self) -> Vec<(&str, &str)> {
+        self.limit_price().into_iter().collect()
+    }
+    /// Side and amount of every limit order this pipeline places, in the order of
+    /// `limit_prices`
+    fn limit_sizes(&self) -> Vec<(BuyOrSell, &str)> {
+        self.limit_size().into_iter().collect()
+    }
     /// Create initial state for the pipeline
     async fn init_state(&self, state: Arc<RwLock<State>>) -> Self::PipelineState;
     /// Give next action to take or return `None` if pipeline is finished. `&State` needs
@@ -123,6 +177,18 @@ where
     async fn acquire_permit(&self, state: Arc<RwLock<State>>) -> Option<tokio::sync::OwnedSemaphorePermit> {
         self.acquire_permit(state).await
     }
+    fn limit_price(&self) -> Option<(&str, &str)> {
+        NashProtocol::limit_price(self)
+    }
+    fn limit_size(&self) -> Option<(BuyOrSell, &str)> {
+        NashProtocol::limit_size(self)
+    }
+    fn limit_prices(&self) -> Vec<(&str, &str)> {
+        NashProtocol::limit_prices(self)
+    }
+    fn limit_sizes(&self) -> Vec<(BuyOrSell, &str)> {
+        NashProtocol::limit_sizes(self)
+    }
     // This begins as `None` but will be set to a wrapped T::Response
     async fn init_state(&self, _state: Arc<RwLock<State>>) -> Self::PipelineState {
         None
@@ -0,0 +1,182 @@
+//! Blockchain payloads of withdrawals. The exchange builds them; before signing, payloads
+//! that can be decoded are rebuilt from what was asked for and compared.
+
+use super::super::sign_states::StateData;
+use super::types::PreparedWithdrawal;
+use crate::errors::{ProtocolError, Result};
+use crate::types::blockchain::{bigdecimal_to_nash_prec, eth::Address, Prefix};
+use crate::types::{Amount, Asset, Blockchain, Nonce};
+use bigdecimal::BigDecimal;
+use std::convert::TryInto;
+
+/// Movement payload of the Ethereum settlement contract. It has the layout of a state
+/// update, with the movement's prefix.
+#[derive(Clone, Debug, PartialEq)]
+pub struct MovementPayloadEth {
+    pub prefix: Prefix,   // 1 byte
+    pub asset_id: Asset,  // 2 bytes
+    pub amount: Amount,   // 8 bytes
+    pub nonce: Nonce,     // 4 bytes
+    pub address: Address, // 20 bytes
+}
+
+impl MovementPayloadEth {
+    /// Payload withdrawing `amount` of `asset` from the state channel of `address`
+    pub fn withdrawal(
+        asset: Asset,
+        amount: &BigDecimal,
+        nonce: u32,
+        address: Address,
+    ) -> Result<Self> {
+        Ok(Self {
+            prefix: Prefix::Withdrawal,
+            asset_id: asset,
+            // SC always encodes at 8 precision
+            amount: Amount::from_bigdecimal(bigdecimal_to_nash_prec(amount, 8)?, 8),
+            nonce: Nonce::Value(nonce),
+            address,
+        })
+    }
+
+    pub fn from_hex(hex_str: &str) -> Result<Self> {
+        let bytes = hex::decode(hex_str)
+            .map_err(|_| ProtocolError("Could not decode movement hex to bytes"))?;
+        if bytes.len() != 35 {
+            return Err(ProtocolError("Movement payload must be 35 bytes"));
+        }
+        Ok(Self {
+            prefix: Prefix::from_bytes(bytes[..1].try_into()?)?,
+            asset_id: Asset::from_eth_bytes(bytes[1..3].try_into()?)?,
+            amount: Amount::from_bytes(bytes[3..11].try_into()?, 8)?,
+            nonce: Nonce::from_be_bytes(bytes[11..15].try_into()?)?,
+            address: Address::from_bytes(bytes[15..35].try_into()?)?,
+        })
+    }
+
+    pub fn to_bytes(&self) -> Result<Vec<u8>> {
+        Ok([
+            &self.prefix.to_bytes()[..],
+            &self.asset_id.to_eth_bytes()[..],
+            &self.amount.to_be_bytes()?[..],
+            &self.nonce.to_be_bytes()[..],
+            &self.address.to_bytes()[..],
+        ]
+        .concat())
+    }
+
+    pub fn to_hex(&self) -> Result<String> {
+        Ok(hex::encode(self.to_bytes()?))
+    }
+}
+
+/// Fail unless movement payloads of `blockchain` can be checked before signing. There is no
+/// local encoding of NEO and BTC movement payloads yet, and signing them unchecked would
+/// sign whatever the exchange sent.
+pub(super) fn check_supported(blockchain: Blockchain) -> Result<()> {
+    if blockchain != Blockchain::Ethereum {
+        return Err(ProtocolError(
+            "Withdrawals are only supported on Ethereum, NEO and BTC payloads can't be checked yet",
+        ));
+    }
+    Ok(())
+}
+
+impl PreparedWithdrawal {
+    /// Check a payload the exchange asks to sign withdraws what was asked for. The payload
+    /// is rebuilt from the request, not from the quantity the exchange prepared, and
+    /// compared.
+    pub(super) fn verify(&self, element: &StateData) -> Result<()> {
+        let blockchain = self.request.blockchain();
+        check_supported(blockchain)?;
+        if element.blockchain != blockchain {
+            return Err(ProtocolError(
+                "Withdrawal payload is for another blockchain. Refusing to sign",
+            ));
+        }
+        // SC always encodes at 8 precision
+        if self.quantity != bigdecimal_to_nash_prec(&self.request.amount, 8)? {
+            return Err(ProtocolError(
+                "Prepared withdrawal quantity differs from the requested amount. Refusing to sign",
+            ));
+        }
+        let nonce: u32 = self
+            .nonce
+            .try_into()
+            .map_err(|_| ProtocolError("Withdrawal nonce does not fit into u32"))?;
+        let address = Address::new(self.address.trim_start_matches("0x"))?;
+        let expected = MovementPayloadEth::withdrawal(
+            self.request.asset,
+            &self.request.amount,
+            nonce,
+            address,
+        )?;
+        if MovementPayloadEth::from_hex(&element.payload)? != expected {
+            return Err(ProtocolError(
+                "Withdrawal payload does not match the withdrawal. Refusing to sign",
+            ));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::types::{PreparedWithdrawal, WithdrawRequest};
+    use super::{Address, MovementPayloadEth, StateData};
+    use crate::types::{Asset, Blockchain};
+    use bigdecimal::BigDecimal;
+    use std::str::FromStr;
+
+    const ADDRESS: &str = "D58547F100B67BB99BBE8E94523B6BB4FDA76954";
+
+    fn prepared(asset: Asset, requested: &str, quantity: &str) -> PreparedWithdrawal {
+        PreparedWithdrawal {
+            request: WithdrawRequest::new(asset, BigDecimal::from_str(requested).unwrap()),
+            address: format!("0x{}", ADDRESS),
+            nonce: 44,
+            quantity: BigDecimal::from_str(quantity).unwrap(),
+            fee: BigDecimal::from(0),
+            transaction_elements: Vec::new(),
+            recycled_orders: Vec::new(),
+        }
+    }
+
+    fn element(blockchain: Blockchain, amount: &str) -> StateData {
+        let address = Address::new(ADDRESS).unwrap();
+        let amount = BigDecimal::from_str(amount).unwrap();
+        let payload = MovementPayloadEth::withdrawal(Asset::USDC, &amount, 44, address).unwrap();
+        StateData {
+            payload: payload.to_hex().unwrap(),
+            payload_hash: String::new(),
+            blockchain,
+        }
+    }
+
+    #[test]
+    fn only_payloads_of_the_requested_withdrawal_are_signed() {
+        let withdrawal = prepared(Asset::USDC, "1.5", "1.5");
+        assert!(withdrawal.verify(&element(Blockchain::Ethereum, "1.5")).is_ok());
+        // the exchange's payload withdraws more than requested
+        assert!(withdrawal.verify(&element(Blockchain::Ethereum, "15")).is_err());
+        // payload and prepared quantity agree with each other, but not with the request
+        let inflated = prepared(Asset::USDC, "1.5", "15");
+        assert!(inflated.verify(&element(Blockchain::Ethereum, "15")).is_err());
+        assert!(withdrawal.verify(&element(Blockchain::NEO, "1.5")).is_err());
+        // no local encoding of NEO payloads to check them against
+        let neo = prepared(Asset::NEO, "1.5", "1.5");
+        assert!(neo.verify(&element(Blockchain::NEO, "1.5")).is_err());
+    }
+
+    #[test]
+    fn eth_withdrawal_payload_round_trips() {
+        let address = Address::new("D58547F100B67BB99BBE8E94523B6BB4FDA76954").unwrap();
+        let amount = BigDecimal::from_str("1.5").unwrap();
+        let payload = MovementPayloadEth::withdrawal(Asset::USDC, &amount, 44, address).unwrap();
+        let hex = payload.to_hex().unwrap();
+        assert_eq!(
+            hex,
+            "0300030000000008f0d1800000002cd58547f100b67bb99bbe8e94523b6bb4fda76954"
+        );
+        assert_eq!(MovementPayloadEth::from_hex(&hex).unwrap(), payload);
+    }
+}
@@ -0,0 +1,12 @@
+//! Withdrawals of funds from the state channel to an address on the asset's blockchain.
+//! Running a `WithdrawRequest` has the exchange prepare the movement. Running the resulting
+//! `PreparedWithdrawal` checks the blockchain payloads the exchange built for it, signs them
+//! with the account's child keys like order payloads, and adds the signed movement.
+
+mod blockchain;
+mod request;
+mod response;
+mod types;
+
+pub use blockchain::MovementPayloadEth;
+pub use types::{MovementStatus, PreparedWithdrawal, WithdrawRequest, WithdrawResponse};
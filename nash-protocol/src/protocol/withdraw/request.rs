@@ -0,0 +1,155 @@
+use super::super::sign_states::{sign_state_data, ClientSignedState};
+use super::super::{general_canonical_string, RequestPayloadSignature};
+use super::blockchain::check_supported;
+use super::types::{PreparedWithdrawal, WithdrawRequest};
+use crate::errors::{ProtocolError, Result};
+use crate::graphql;
+use crate::graphql::{add_movement, prepare_movement};
+use crate::types::blockchain::bigdecimal_to_nash_prec;
+use crate::types::Blockchain;
+use crate::utils::{bigint_to_nash_r, bigint_to_nash_sig, current_time_as_i64};
+use crate::protocol::query_overrides::build_query;
+
+use super::super::signer::Signer;
+
+impl WithdrawRequest {
+    /// Create PrepareMovement GraphQL request
+    pub fn make_query(
+        &self,
+        signer: &Signer,
+    ) -> Result<graphql_client::QueryBody<prepare_movement::Variables>> {
+        check_supported(self.blockchain())?;
+        let mut params = prepare_movement::Variables {
+            payload: prepare_movement::PrepareMovementParams {
+                address: signer.get_address(self.blockchain())?.to_string(),
+                cap_quantity_to_maximum: None,
+                gas_price: None,
+                quantity: prepare_movement::CurrencyAmountParams {
+                    // SC always encodes at 8 precision
+                    amount: bigdecimal_to_nash_prec(&self.amount, 8)?.to_string(),
+                    currency: self.asset.name().to_string(),
+                },
+                target_address: self.target_address.clone(),
+                timestamp: current_time_as_i64(),
+                type_: prepare_movement::MovementType::WITHDRAWAL,
+            },
+            signature: RequestPayloadSignature::empty().into(),
+        };
+        let sig_payload = prepare_movement_canonical_string(&params)?;
+        params.signature = signer.sign_canonical_string(&sig_payload)?.into();
+        Ok(build_query::<graphql::PrepareMovement>(params))
+    }
+}
+
+impl PreparedWithdrawal {
+    /// Create AddMovement GraphQL request, signing the payloads of the prepared withdrawal
+    pub fn make_query(
+        &self,
+        signer: &Signer,
+    ) -> Result<graphql_client::QueryBody<add_movement::Variables>> {
+        let mut signed_elements = Vec::new();
+        for element in &self.transaction_elements {
+            self.verify(element)?;
+            signed_elements.push(sign_state_data(element, signer)?);
+        }
+        let mut resigned_orders = Vec::new();
+        for order in &self.recycled_orders {
+            if !order.verify() {
+                return Err(ProtocolError(
+                    "Recycled order payload failed to verify. Refusing to sign",
+                ));
+            }
+            resigned_orders.push(sign_state_data(order.state(), signer)?);
+        }
+        let mut params = add_movement::Variables {
+            payload: add_movement::AddMovementParams {
+                address: self.address.clone(),
+                nonce: self.nonce,
+                quantity: add_movement::CurrencyAmountParams {
+                    amount: self.quantity.to_string(),
+                    currency: self.request.asset.name().to_string(),
+                },
+                resigned_orders: Some(resigned_orders.iter().map(|x| Some(x.into())).collect()),
+                signed_transaction_elements: Some(
+                    signed_elements.iter().map(|x| Some(x.into())).collect(),
+                ),
+                target_address: self.request.target_address.clone(),
+                timestamp: current_time_as_i64(),
+                transaction_hash: None,
+                transaction_payload: None,
+                type_: add_movement::MovementType::WITHDRAWAL,
+            },
+            signature: RequestPayloadSignature::empty().into(),
+        };
+        let sig_payload = add_movement_canonical_string(&params)?;
+        params.signature = signer.sign_canonical_string(&sig_payload)?.into();
+        Ok(build_query::<graphql::AddMovement>(params))
+    }
+}
+
+impl From<RequestPayloadSignature> for prepare_movement::Signature {
+    fn from(sig: RequestPayloadSignature) -> Self {
+        prepare_movement::Signature {
+            signed_digest: sig.signed_digest,
+            public_key: sig.public_key,
+        }
+    }
+}
+
+impl From<RequestPayloadSignature> for add_movement::Signature {
+    fn from(sig: RequestPayloadSignature) -> Self {
+        add_movement::Signature {
+            signed_digest: sig.signed_digest,
+            public_key: sig.public_key,
+        }
+    }
+}
+
+impl From<&ClientSignedState> for add_movement::ClientSignedMessage {
+    fn from(signed_state: &ClientSignedState) -> Self {
+        Self {
+            message: Some(signed_state.message.clone()),
+            blockchain: Some(signed_state.blockchain.into()),
+            r: Some(bigint_to_nash_r(signed_state.r.clone())),
+            signature: Some(bigint_to_nash_sig(signed_state.signature.clone())),
+        }
+    }
+}
+
+impl From<Blockchain> for add_movement::Blockchain {
+    fn from(chain: Blockchain) -> Self {
+        match chain {
+            Blockchain::Ethereum => Self::ETH,
+            Blockchain::NEO => Self::NEO,
+            Blockchain::Bitcoin => Self::BTC,
+        }
+    }
+}
+
+/// Generate canonical payload string for prepare movement GraphQL request
+pub fn prepare_movement_canonical_string(
+    variables: &prepare_movement::Variables,
+) -> Result<String> {
+    let serialized = serde_json::to_value(variables)
+        .map_err(|_| ProtocolError("Failed to serialize movement into canonical string"))?;
+    Ok(general_canonical_string(
+        "prepare_movement".to_string(),
+        serialized,
+        vec![],
+    ))
+}
+
+/// Generate canonical payload string for add movement GraphQL request. Signed payloads are
+/// left out, as with sign states.
+pub fn add_movement_canonical_string(variables: &add_movement::Variables) -> Result<String> {
+    let serialized = serde_json::to_value(variables)
+        .map_err(|_| ProtocolError("Failed to serialize movement into canonical string"))?;
+    Ok(general_canonical_string(
+        "add_movement".to_string(),
+        serialized,
+        vec![
+            "resigned_orders".to_string(),
+            "signed_transaction_elements".to_string(),
+        ],
+    ))
+}
@@ -0,0 +1,121 @@
+use super::super::sign_states::{RecycledOrder, StateData};
+use super::super::ResponseOrError;
+use super::types::{MovementStatus, PreparedWithdrawal, WithdrawRequest, WithdrawResponse};
+use crate::errors::{ProtocolError, Result};
+use crate::graphql::{add_movement, prepare_movement};
+use crate::types::{Asset, Blockchain};
+use bigdecimal::BigDecimal;
+use std::convert::{TryFrom, TryInto};
+use std::str::FromStr;
+
+impl WithdrawRequest {
+    pub fn response_from_graphql(
+        &self,
+        response: ResponseOrError<prepare_movement::ResponseData>,
+        address: String,
+    ) -> Result<ResponseOrError<PreparedWithdrawal>> {
+        match response {
+            ResponseOrError::Response(data) => {
+                let prepared = data.data.prepare_movement;
+                let transaction_elements = prepared
+                    .transaction_elements
+                    .iter()
+                    .map(|element| StateData {
+                        payload: element.payload.clone(),
+                        payload_hash: element.payload_hash.clone(),
+                        blockchain: (&element.blockchain).into(),
+                    })
+                    .collect();
+                let recycled_orders = prepared
+                    .recycled_orders
+                    .iter()
+                    .map(|order| {
+                        RecycledOrder(StateData {
+                            payload: order.payload.clone(),
+                            payload_hash: order.payload_hash.clone(),
+                            blockchain: (&order.blockchain).into(),
+                        })
+                    })
+                    .collect();
+                Ok(ResponseOrError::from_data(PreparedWithdrawal {
+                    request: self.clone(),
+                    address,
+                    nonce: prepared.nonce,
+                    quantity: BigDecimal::from_str(&prepared.quantity.amount)?,
+                    fee: BigDecimal::from_str(&prepared.fees.amount)?,
+                    transaction_elements,
+                    recycled_orders,
+                }))
+            }
+            ResponseOrError::Error(error) => Ok(ResponseOrError::Error(error)),
+        }
+    }
+}
+
+impl PreparedWithdrawal {
+    pub fn response_from_graphql(
+        &self,
+        response: ResponseOrError<add_movement::ResponseData>,
+    ) -> Result<ResponseOrError<WithdrawResponse>> {
+        match response {
+            ResponseOrError::Response(data) => {
+                let movement = data.data.add_movement;
+                let fee = match &movement.fee {
+                    Some(fee) => Some(BigDecimal::from_str(fee)?),
+                    None => None,
+                };
+                Ok(ResponseOrError::from_data(WithdrawResponse {
+                    movement_id: movement.id,
+                    asset: Asset::from_str(&movement.currency)?,
+                    blockchain: (&movement.blockchain).into(),
+                    quantity: BigDecimal::from_str(&movement.quantity.amount)?,
+                    fee,
+                    status: (&movement.status).try_into()?,
+                    target_address: movement.target_address,
+                    transaction_hash: movement.transaction_hash,
+                }))
+            }
+            ResponseOrError::Error(error) => Ok(ResponseOrError::Error(error)),
+        }
+    }
+}
+
+impl From<&prepare_movement::Blockchain> for Blockchain {
+    fn from(chain: &prepare_movement::Blockchain) -> Self {
+        match chain {
+            prepare_movement::Blockchain::ETH => Self::Ethereum,
+            prepare_movement::Blockchain::NEO => Self::NEO,
+            prepare_movement::Blockchain::BTC => Self::Bitcoin,
+            prepare_movement::Blockchain::Other(_) => {
+                panic!("Nash API is serving non-supported blockchain")
+            }
+        }
+    }
+}
+
+impl From<&add_movement::Blockchain> for Blockchain {
+    fn from(chain: &add_movement::Blockchain) -> Self {
+        match chain {
+            add_movement::Blockchain::ETH => Self::Ethereum,
+            add_movement::Blockchain::NEO => Self::NEO,
+            add_movement::Blockchain::BTC => Self::Bitcoin,
+            add_movement::Blockchain::Other(_) => {
+                panic!("Nash API is serving non-supported blockchain")
+            }
+        }
+    }
+}
+
+impl TryFrom<&add_movement::MovementStatus> for MovementStatus {
+    type Error = ProtocolError;
+
+    fn try_from(status: &add_movement::MovementStatus) -> Result<Self> {
+        match status {
+            add_movement::MovementStatus::CREATED => Ok(Self::Created),
+            add_movement::MovementStatus::PENDING => Ok(Self::Pending),
+            add_movement::MovementStatus::COMPLETED => Ok(Self::Completed),
+            add_movement::MovementStatus::FAILED => Ok(Self::Failed),
+            add_movement::MovementStatus::Other(_) => Err(ProtocolError("Unknown movement status")),
+        }
+    }
+}
@@ -0,0 +1,124 @@
+use super::super::sign_states::{RecycledOrder, StateData};
+use super::super::{
+    json_to_type_or_error, serializable_to_json, NashProtocol, ResponseOrError, State,
+};
+use crate::errors::Result;
+use crate::types::{Address, Asset, Blockchain};
+use async_trait::async_trait;
+use bigdecimal::BigDecimal;
+use std::sync::Arc;
+use tokio::sync::RwLock;
+
+/// Withdraw `amount` of `asset` to `target_address`, or to the account's own address on the
+/// asset's blockchain if there is none. Running it only prepares the withdrawal: run the
+/// `PreparedWithdrawal` it returns to sign and submit it.
+#[derive(Clone, Debug)]
+pub struct WithdrawRequest {
+    pub asset: Asset,
+    pub amount: BigDecimal,
+    pub target_address: Option<String>,
+}
+
+impl WithdrawRequest {
+    pub fn new(asset: Asset, amount: BigDecimal) -> Self {
+        Self {
+            asset,
+            amount,
+            target_address: None,
+        }
+    }
+
+    /// Send the funds to `address` instead of the account's own address. The address is
+    /// checked to be one of the asset's blockchain.
+    pub fn to_address(mut self, address: &str) -> Result<Self> {
+        Address::parse(self.asset.blockchain(), address)?;
+        self.target_address = Some(address.to_string());
+        Ok(self)
+    }
+
+    pub fn blockchain(&self) -> Blockchain {
+        self.asset.blockchain()
+    }
+}
+
+/// A withdrawal the exchange prepared, with the blockchain payloads to sign for it
+#[derive(Clone, Debug)]
+pub struct PreparedWithdrawal {
+    pub request: WithdrawRequest,
+    /// The account's own address on the asset's blockchain
+    pub address: String,
+    /// Nonce of the movement on the asset's blockchain
+    pub nonce: i64,
+    /// Amount that will be withdrawn
+    pub quantity: BigDecimal,
+    pub fee: BigDecimal,
+    pub(super) transaction_elements: Vec<StateData>,
+    pub(super) recycled_orders: Vec<RecycledOrder>,
+}
+
+/// Status of a movement on the exchange
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MovementStatus {
+    Created,
+    Pending,
+    Completed,
+    Failed,
+}
+
+/// A withdrawal the exchange accepted
+#[derive(Clone, Debug)]
+pub struct WithdrawResponse {
+    pub movement_id: String,
+    pub asset: Asset,
+    pub blockchain: Blockchain,
+    pub quantity: BigDecimal,
+    pub fee: Option<BigDecimal>,
+    pub status: MovementStatus,
+    pub target_address: Option<String>,
+    /// Hash of the blockchain transaction, once the exchange sent it
+    pub transaction_hash: Option<String>,
+}
+
+/// Implement protocol bindings for WithdrawRequest
+#[async_trait]
+impl NashProtocol for WithdrawRequest {
+    type Response = PreparedWithdrawal;
+
+    async fn graphql(&self, state: Arc<RwLock<State>>) -> Result<serde_json::Value> {
+        let state = state.read().await;
+        let query = self.make_query(state.signer()?)?;
+        serializable_to_json(&query)
+    }
+
+    async fn response_from_json(
+        &self,
+        response: serde_json::Value,
+        state: Arc<RwLock<State>>,
+    ) -> Result<ResponseOrError<Self::Response>> {
+        let as_graphql = json_to_type_or_error(response)?;
+        let state = state.read().await;
+        let address = state.signer()?.get_address(self.blockchain())?.to_string();
+        self.response_from_graphql(as_graphql, address)
+    }
+}
+
+/// Implement protocol bindings for PreparedWithdrawal
+#[async_trait]
+impl NashProtocol for PreparedWithdrawal {
+    type Response = WithdrawResponse;
+
+    async fn graphql(&self, state: Arc<RwLock<State>>) -> Result<serde_json::Value> {
+        let state = state.read().await;
+        let query = self.make_query(state.signer()?)?;
+        serializable_to_json(&query)
+    }
+
+    async fn response_from_json(
+        &self,
+        response: serde_json::Value,
+        _state: Arc<RwLock<State>>,
+    ) -> Result<ResponseOrError<Self::Response>> {
+        let as_graphql = json_to_type_or_error(response)?;
+        self.response_from_graphql(as_graphql)
+    }
+}
@@ -6,7 +6,7 @@ use crate::errors::{ProtocolError, Result};
 use byteorder::{BigEndian, ReadBytesExt};
 #[cfg(feature = "secp256k1")]
 use nash_mpc::curves::secp256_k1::Secp256k1Point;
-#[cfg(feature = "k256")]
+#[cfg(all(feature = "k256", not(feature = "secp256k1")))]
 use nash_mpc::curves::secp256_k1_rust::Secp256k1Point;
 use nash_mpc::curves::traits::ECPoint;
 use sha3::{Digest, Keccak256};
@@ -42,7 +42,7 @@ impl OrderRate {
     /// Create OrderRate from big endian bytes in Ethereum FillOrder payload
     pub fn from_be_bytes(bytes: [u8; 8]) -> Result<Self> {
         let num = u64::from_be_bytes(bytes);
-        let big_num = nash_u64_to_bigdecimal(num, 8);
+        let big_num = nash_u64_to_bigdecimal(num, 8)?;
         Ok(OrderRate::from_bigdecimal(big_num))
     }
 }
@@ -60,7 +60,7 @@ impl Amount {
                 .read_u64::<BigEndian>()
                 .map_err(|_| ProtocolError("Could not convert bytes to u64"))?,
             precision,
-        );
+        )?;
         Ok(Self { value, precision })
     }
 }
@@ -14,26 +14,38 @@ use std::convert::TryFrom;
 /// Convert a bigdecimal `num` to `u64` for serialization in the protocol using the
 /// precision scheme defined by the Nash ME
 pub fn bigdecimal_to_nash_u64(num: &BigDecimal, precision: u32) -> Result<u64> {
-    let num = bigdecimal_to_nash_prec(num, precision);
-    let multiplier = BigDecimal::from(u64::pow(10, precision));
+    let num = bigdecimal_to_nash_prec(num, precision)?;
+    let multiplier = ten_to_the(precision)?;
     (num * multiplier)
         .with_scale(0)
         .to_u64()
         .ok_or(ProtocolError("Result does not fit into u64."))
 }
 
-pub fn nash_u64_to_bigdecimal(num: u64, precision: u32) -> BigDecimal {
+pub fn nash_u64_to_bigdecimal(num: u64, precision: u32) -> Result<BigDecimal> {
     let num = BigDecimal::from(num);
-    let divider = BigDecimal::from(u64::pow(10, precision));
-    num / divider
+    let divider = ten_to_the(precision)?;
+    Ok(num / divider)
 }
 
 /// Convert a bigdecimal to precision expected by the Nash ME
 /// Nash ME defines precision as only digits right of decimal point
-pub fn bigdecimal_to_nash_prec(num: &BigDecimal, precision: u32) -> BigDecimal {
-    let scale = BigDecimal::from(u64::pow(10, precision));
+pub fn bigdecimal_to_nash_prec(num: &BigDecimal, precision: u32) -> Result<BigDecimal> {
+    let scale = ten_to_the(precision)?;
     let scaled = (num * &scale).with_scale(0);
-    &scaled / &scale
+    Ok(&scaled / &scale)
+}
+
+/// `10^precision`, failing for a precision too large to scale a `u64` by
+pub(crate) fn ten_to_the(precision: u32) -> Result<BigDecimal> {
+    u64::checked_pow(10, precision)
+        .map(BigDecimal::from)
+        .ok_or_else(|| {
+            ProtocolError::coerce_static_from_str(&format!(
+                "Precision {} is too large, at most 19 decimals are supported",
+                precision
+            ))
+        })
 }
 
 /// The type prefix indicates what operation this data represents. This is
@@ -84,6 +96,15 @@ impl Address {
             Blockchain::NEO => Ok(Self::NEO(neo::Address::new(hex_str)?)),
         }
     }
+
+    /// Parse an address as the exchange and wallets show it. Unlike `new`, Ethereum
+    /// addresses may be 0x prefixed.
+    pub fn parse(chain: Blockchain, address: &str) -> Result<Self> {
+        match chain {
+            Blockchain::Ethereum => Self::new(chain, address.trim_start_matches("0x")),
+            _ => Self::new(chain, address),
+        }
+    }
 }
 
 impl TryFrom<Address> for eth::Address {
@@ -150,6 +171,14 @@ impl PublicKey {
         })
     }
 
+    pub fn blockchain(&self) -> Blockchain {
+        match self {
+            Self::Bitcoin(_) => Blockchain::Bitcoin,
+            Self::Ethereum(_) => Blockchain::Ethereum,
+            Self::NEO(_) => Blockchain::NEO,
+        }
+    }
+
     pub fn to_hex_str(&self) -> String {
         match self {
             Self::Bitcoin(key) => key.to_hex(),
@@ -232,8 +261,11 @@ mod tests {
     #[test]
     fn nash_precision() {
         let num = BigDecimal::from_str("1.55555").unwrap();
-        let prec_num = bigdecimal_to_nash_prec(&num, 4);
+        let prec_num = bigdecimal_to_nash_prec(&num, 4).unwrap();
         assert_eq!(prec_num.to_string(), "1.5555");
+        assert!(bigdecimal_to_nash_prec(&num, 19).is_ok());
+        assert!(bigdecimal_to_nash_prec(&num, 20).is_err());
+        assert!(bigdecimal_to_nash_u64(&num, 20).is_err());
     }
 
     #[test]
@@ -242,8 +274,8 @@ mod tests {
         let bd_2 = BigDecimal::from_str("0.03454").unwrap();
         println!("Good: {}", bd_1.inverse());
         println!("Bad: {}", bd_2.inverse());
-        let step1_1 = bigdecimal_to_nash_prec(&bd_1.inverse(), 8);
-        let step1_2 = bigdecimal_to_nash_prec(&bd_2.inverse(), 8);
+        let step1_1 = bigdecimal_to_nash_prec(&bd_1.inverse(), 8).unwrap();
+        let step1_2 = bigdecimal_to_nash_prec(&bd_2.inverse(), 8).unwrap();
         println!("Good: {}", step1_1);
         println!("Bad: {}", step1_2);
         let converted_1 = bigdecimal_to_nash_u64(&bd_1.inverse(), 8).unwrap();
@@ -3,11 +3,12 @@
 //! module. For example `protocol::place_order`.
 
 use crate::errors::{ProtocolError, Result};
-use bigdecimal::BigDecimal;
+use bigdecimal::{BigDecimal, Signed};
+use std::convert::TryFrom;
 use std::str::FromStr;
-use chrono::{DateTime, Utc};
+use super::timestamp::{self, Timestamp};
 use serde::{Deserialize, Serialize};
-use super::blockchain::bigdecimal_to_nash_prec;
+use super::blockchain::{bigdecimal_to_nash_prec, ten_to_the};
 use lazy_static::lazy_static;
 
 /// Representation of blockchains to help navigate encoding issues
@@ -169,7 +170,12 @@ impl AssetofPrecision {
     /// Starting with an asset of some precision, create a new asset that holds
     /// a specific amount of value
     pub fn with_amount(&self, amount_str: &str) -> Result<AssetAmount> {
-        let amount = Amount::new(amount_str, self.precision)?;
+        self.with_amount_rounded(amount_str, RoundingMode::Floor)
+    }
+
+    /// Same as `with_amount`, bringing the amount to the asset's precision with `rounding`
+    pub fn with_amount_rounded(&self, amount_str: &str, rounding: RoundingMode) -> Result<AssetAmount> {
+        let amount = Amount::new_rounded(amount_str, self.precision, rounding)?;
         Ok(AssetAmount {
             asset: *self,
             amount,
@@ -204,6 +210,73 @@ impl AssetAmount {
             amount: Amount::from_bigdecimal(new_amount, into_asset.precision),
         })
     }
+
+    /// Value of `self` in `into_asset` at `rate`, truncated to the precision of `into_asset`
+    /// like the exchange does. E.g. an amount of ETH times an ETH/USDC price gives USDC.
+    pub fn mul_rate(&self, rate: &Rate, into_asset: AssetofPrecision) -> Result<AssetAmount> {
+        self.mul_rate_rounded(rate, into_asset, RoundingMode::Floor)
+    }
+
+    /// Same as `mul_rate`, bringing the value to the precision of `into_asset` with `rounding`
+    pub fn mul_rate_rounded(
+        &self,
+        rate: &Rate,
+        into_asset: AssetofPrecision,
+        rounding: RoundingMode,
+    ) -> Result<AssetAmount> {
+        let value = self.amount.to_bigdecimal() * rate.to_bigdecimal()?;
+        Ok(AssetAmount {
+            asset: into_asset,
+            amount: Amount::from_bigdecimal(
+                rounding.round(&value, into_asset.precision)?,
+                into_asset.precision,
+            ),
+        })
+    }
+
+    /// Sum of two amounts of the same asset, at the precision of `self`
+    pub fn add(&self, other: &AssetAmount) -> Result<AssetAmount> {
+        self.check_same_asset(other)?;
+        self.with_value(&self.amount.value + &other.amount.value)
+    }
+
+    /// Difference of two amounts of the same asset, at the precision of `self`. Fails
+    /// instead of going negative.
+    pub fn sub(&self, other: &AssetAmount) -> Result<AssetAmount> {
+        self.check_same_asset(other)?;
+        let value = &self.amount.value - &other.amount.value;
+        if value < BigDecimal::from(0) {
+            return Err(ProtocolError("AssetAmount subtraction would be negative"));
+        }
+        self.with_value(value)
+    }
+
+    fn check_same_asset(&self, other: &AssetAmount) -> Result<()> {
+        if self.asset.asset != other.asset.asset {
+            return Err(ProtocolError("Cannot combine amounts of different assets"));
+        }
+        Ok(())
+    }
+
+    fn with_value(&self, value: BigDecimal) -> Result<AssetAmount> {
+        Ok(AssetAmount {
+            asset: self.asset,
+            amount: Amount::from_bigdecimal(
+                bigdecimal_to_nash_prec(&value, self.asset.precision)?,
+                self.asset.precision,
+            ),
+        })
+    }
+}
+
+/// Amounts of different assets are not comparable
+impl PartialOrd for AssetAmount {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        if self.asset.asset != other.asset.asset {
+            return None;
+        }
+        self.amount.partial_cmp(&other.amount)
+    }
 }
 
 /// This type encodes all the information necessary for a client operating
@@ -246,16 +319,17 @@ impl Market {
         )
     }
 
-    /// Get list of blockchains associated with this market. There will always be
-    /// either one or two blockchains associated with a market.
+    /// Get list of blockchains associated with this market, one for each distinct chain of
+    /// its assets, in the order orders on it are signed for them: the chain of A first.
     pub fn blockchains(&self) -> Vec<Blockchain> {
-        let chain_a = self.asset_a.asset.blockchain();
-        let chain_b = self.asset_b.asset.blockchain();
-        if chain_a == chain_b {
-            vec![chain_a]
-        } else {
-            vec![chain_a, chain_b]
+        let mut chains = Vec::new();
+        for asset in &[self.asset_a, self.asset_b] {
+            let chain = asset.asset.blockchain();
+            if !chains.contains(&chain) {
+                chains.push(chain);
+            }
         }
+        chains
     }
 
     /// Get market asset by string name
@@ -269,6 +343,39 @@ impl Market {
         }
     }
 
+    /// Check an order for `amount` of A, at `price` in B for orders that have a limit price,
+    /// against the precision and minimum trade sizes of the market. This catches orders the
+    /// matching engine would reject before they are signed. Nash doesn't publish a maximum
+    /// trade size, so that is left to the matching engine.
+    pub fn validate_order(&self, amount: &str, price: Option<&str>) -> Result<()> {
+        let amount = parse_order_number("amount", amount)?;
+        check_precision("Amount", &amount, &self.asset_a)?;
+        if amount < self.min_trade_size_a.amount.value {
+            return Err(ProtocolError::coerce_static_from_str(&format!(
+                "Amount {} {} is below the minimum trade size of {} on {}",
+                amount,
+                self.asset_a.asset.name(),
+                self.min_trade_size_a.amount.value,
+                self.market_name()
+            )));
+        }
+        if let Some(price) = price {
+            let price = parse_order_number("price", price)?;
+            check_precision("Price", &price, &self.asset_b)?;
+            let value = &amount * &price;
+            if value < self.min_trade_size_b.amount.value {
+                return Err(ProtocolError::coerce_static_from_str(&format!(
+                    "Order value {} {} is below the minimum trade size of {} on {}",
+                    value,
+                    self.asset_b.asset.name(),
+                    self.min_trade_size_b.amount.value,
+                    self.market_name()
+                )));
+            }
+        }
+        Ok(())
+    }
+
     pub fn invert(&self) -> Market {
         Market::new(
             self.asset_b.clone(),
@@ -279,6 +386,33 @@ impl Market {
     }
 }
 
+fn parse_order_number(name: &str, value: &str) -> Result<BigDecimal> {
+    let number = BigDecimal::from_str(value).map_err(|e| {
+        ProtocolError::with_source(format!("Invalid order {} {:?}", name, value), e)
+    })?;
+    if !number.is_positive() {
+        return Err(ProtocolError::coerce_static_from_str(&format!(
+            "Order {} must be positive, got {}",
+            name, value
+        )));
+    }
+    Ok(number)
+}
+
+/// Fail rather than truncate a number with more decimals than `asset` has
+fn check_precision(name: &str, value: &BigDecimal, asset: &AssetofPrecision) -> Result<()> {
+    if value.with_scale(asset.precision as i64) != *value {
+        return Err(ProtocolError::coerce_static_from_str(&format!(
+            "{} {} has more than the {} decimals allowed for {}",
+            name,
+            value,
+            asset.precision,
+            asset.asset.name()
+        )));
+    }
+    Ok(())
+}
+
 /// Buy or sell type for Nash protocol. We don't use the one generated automatically
 /// from the GraphQL schema as it does not implement necessary traits like Clone
 #[derive(Clone, Debug, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
@@ -288,6 +422,63 @@ pub enum BuyOrSell {
     Sell,
 }
 
+/// How a value with more decimals than a precision allows is brought to that precision
+#[derive(Clone, Debug, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum RoundingMode {
+    /// Toward negative infinity. For positive values this is the truncation the ME applies.
+    Floor,
+    /// Toward positive infinity
+    Ceil,
+    /// To the nearest value, ties to the one with an even last digit
+    HalfEven,
+}
+
+impl RoundingMode {
+    /// Rounding that never makes a limit price worse for the side placing the order: buy
+    /// prices are rounded down and sell prices up
+    pub fn for_price(buy_or_sell: BuyOrSell) -> Self {
+        match buy_or_sell {
+            BuyOrSell::Buy => Self::Floor,
+            BuyOrSell::Sell => Self::Ceil,
+        }
+    }
+
+    /// `value` with exactly `precision` decimals
+    pub fn round(&self, value: &BigDecimal, precision: u32) -> Result<BigDecimal> {
+        let scale = precision as i64;
+        let ten_to_the_precision = ten_to_the(precision)?;
+        // `with_scale` drops decimals, i.e. rounds toward zero
+        let truncated = value.with_scale(scale);
+        if truncated == *value {
+            return Ok(truncated);
+        }
+        let step = BigDecimal::from(1) / &ten_to_the_precision;
+        let away_from_zero = if value.is_negative() {
+            &truncated - &step
+        } else {
+            &truncated + &step
+        };
+        let rounded = match self {
+            Self::Floor if value.is_negative() => away_from_zero,
+            Self::Ceil if value.is_positive() => away_from_zero,
+            Self::Floor | Self::Ceil => truncated,
+            Self::HalfEven => {
+                let twice_remainder = (value - &truncated).abs() * BigDecimal::from(2);
+                let last_digit_odd = {
+                    let units = &truncated * &ten_to_the_precision;
+                    !(units / BigDecimal::from(2)).is_integer()
+                };
+                if twice_remainder > step || (twice_remainder == step && last_digit_odd) {
+                    away_from_zero
+                } else {
+                    truncated
+                }
+            }
+        };
+        rounded.with_scale(scale)
+    }
+}
+
 /// Type of order execution in Nash ME
 #[derive(Clone, Debug, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
 #[serde(rename_all = "SCREAMING_SNAKE_CASE")]
@@ -326,7 +517,36 @@ impl From<OrderRate> for Rate {
     }
 }
 
+/// Order rates compare with order rates and fee rates with fee rates. The min and max
+/// variants are bounds: below and above any rate of their kind, not a value.
+impl PartialOrd for Rate {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        let (kind, bound, rate) = self.ordering_key();
+        let (other_kind, other_bound, other_rate) = other.ordering_key();
+        if kind != other_kind {
+            return None;
+        }
+        match (rate, other_rate) {
+            (Some(rate), Some(other_rate)) => rate.partial_cmp(other_rate),
+            _ => bound.partial_cmp(&other_bound),
+        }
+    }
+}
+
 impl Rate {
+    /// Whether this is a fee rate, where it sits among rates of its kind (-1 for the min
+    /// bound, 1 for the max bound) and its value if it has one
+    fn ordering_key(&self) -> (bool, i8, Option<&BigDecimal>) {
+        match self {
+            Self::OrderRate(rate) => (false, 0, Some(&rate.inner)),
+            Self::MinOrderRate => (false, -1, None),
+            Self::MaxOrderRate => (false, 1, None),
+            Self::FeeRate(rate) => (true, 0, Some(&rate.inner)),
+            Self::MinFeeRate => (true, -1, None),
+            Self::MaxFeeRate => (true, 1, None),
+        }
+    }
+
     /// Return new bigdecimal inner value based on Rate
     pub fn to_bigdecimal(&self) -> Result<BigDecimal> {
         let num = match self {
@@ -343,7 +563,7 @@ impl Rate {
 
     pub fn invert_rate(&self, precision: Option<u32>) -> Result<Self> {
         match self {
-            Self::OrderRate(rate) => Ok(Self::OrderRate(rate.invert_rate(precision))),
+            Self::OrderRate(rate) => Ok(Self::OrderRate(rate.invert_rate(precision)?)),
             _ => Err(ProtocolError(
                 "Cannot invert a Rate that is not an OrderRate",
             )),
@@ -389,14 +609,23 @@ impl OrderRate {
     }
 
     /// Invert the price to units of the other market pair. For example, if price is
-    /// in terms of ETH in an ETH/USDC market, this will convert it to terms of USDC
-    pub fn invert_rate(&self, precision: Option<u32>) -> Self {
-        let mut inverse = self.inner.inverse();
-        if let Some(precision) = precision {
-            let scale_num = BigDecimal::from(u64::pow(10, precision));
-            inverse = (&self.inner * &scale_num).with_scale(0) / scale_num;
+    /// in terms of ETH in an ETH/USDC market, this will convert it to terms of USDC.
+    /// With a `precision`, the inverse is rounded down to it.
+    pub fn invert_rate(&self, precision: Option<u32>) -> Result<Self> {
+        let inverse = Self {
+            inner: self.inner.inverse(),
+        };
+        match precision {
+            Some(precision) => inverse.round(precision, RoundingMode::Floor),
+            None => Ok(inverse),
         }
-        Self { inner: inverse }
+    }
+
+    /// The rate with exactly `precision` decimals, rounded with `rounding`
+    pub fn round(&self, precision: u32, rounding: RoundingMode) -> Result<Self> {
+        Ok(Self {
+            inner: rounding.round(&self.inner, precision)?,
+        })
     }
 
     /// Return a new `BigDecimal` based on `OrderRate`
@@ -414,8 +643,50 @@ impl OrderRate {
     }
 }
 
+impl PartialOrd for OrderRate {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        self.inner.partial_cmp(&other.inner)
+    }
+}
+
 type FeeRate = OrderRate;
 
+/// Trading fee rates of an account, as fractions of the traded amount (e.g. 0.0025 for 0.25%).
+/// Nash derives them from 30 day volume, so they change over time.
+#[derive(Clone, Debug, PartialEq)]
+pub struct AccountFeeRates {
+    pub maker: BigDecimal,
+    pub taker: BigDecimal,
+}
+
+impl AccountFeeRates {
+    /// Fee rates must lie between zero and `Rate::MaxFeeRate`, the most a fill payload
+    /// lets the exchange charge
+    pub fn new(maker: BigDecimal, taker: BigDecimal) -> Result<Self> {
+        let max = Rate::MaxFeeRate.to_bigdecimal()?;
+        for rate in &[&maker, &taker] {
+            if **rate < BigDecimal::from(0) || **rate > max {
+                return Err(ProtocolError::coerce_static_from_str(&format!(
+                    "Fee rate {} is outside of [0, {}]",
+                    rate, max
+                )));
+            }
+        }
+        Ok(Self { maker, taker })
+    }
+
+    /// Fee rate to sign into the payloads of an order. An order that may take liquidity
+    /// can be charged the taker rate, so it gets the higher of both.
+    pub fn for_order(&self, allow_taker: bool) -> Rate {
+        let rate = if allow_taker && self.taker > self.maker {
+            &self.taker
+        } else {
+            &self.maker
+        };
+        Rate::FeeRate(FeeRate::from_bigdecimal(rate.clone()))
+    }
+}
+
 /// Amount encodes the amount of asset being bought or sold in an order
 /// It is encoded with a precision that depends on the market and asset
 /// being traded. For example, in the ETH/USD market, ETH has a precision
@@ -428,12 +699,16 @@ pub struct Amount {
 }
 
 impl Amount {
-    /// Construct a new Amount from string and precision
+    /// Construct a new Amount from string and precision, dropping decimals beyond it
     pub fn new(str_num: &str, precision: u32) -> Result<Self> {
+        Self::new_rounded(str_num, precision, RoundingMode::Floor)
+    }
+
+    /// Construct a new Amount from string and precision, rounded with `rounding`
+    pub fn new_rounded(str_num: &str, precision: u32, rounding: RoundingMode) -> Result<Self> {
         let value = BigDecimal::from_str(str_num)
             .map_err(|_| ProtocolError("String to BigDecimal failed in creating Amount"))?;
-        let adjust_precision = bigdecimal_to_nash_prec(&value, precision);
-        Ok(Self { value: adjust_precision, precision })
+        Ok(Self { value: rounding.round(&value, precision)?, precision })
     }
 
     pub fn from_bigdecimal(value: BigDecimal, precision: u32) -> Self {
@@ -446,6 +721,17 @@ impl Amount {
     }
 }
 
+/// Amounts compare by value, then by precision, so that like `PartialEq` only amounts of
+/// the same value and precision are equal
+impl PartialOrd for Amount {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        match self.value.partial_cmp(&other.value)? {
+            std::cmp::Ordering::Equal => self.precision.partial_cmp(&other.precision),
+            ordering => Some(ordering),
+        }
+    }
+}
+
 /// Nonces are 32 bit integers. They increment over time such that data
 /// with lower nonces that has already been observed are rejected by the
 /// matching engine and smart contract.
@@ -501,6 +787,45 @@ impl From<&u32> for Nonce {
     }
 }
 
+/// A nonce together with the asset it belongs to. Fill payloads are signed once per
+/// blockchain of a market, and a nonce of an asset living on another chain has to be
+/// signed as `Nonce::Crosschain` there. Keeping the asset with the nonce means that
+/// mapping can't be done against the wrong asset.
+#[derive(Clone, Debug, Copy, PartialEq, Eq, Hash)]
+pub struct TypedNonce {
+    asset: Asset,
+    nonce: Nonce,
+}
+
+impl TypedNonce {
+    pub fn new(asset: Asset, nonce: u32) -> Self {
+        Self {
+            asset,
+            nonce: nonce.into(),
+        }
+    }
+
+    pub fn asset(&self) -> Asset {
+        self.asset
+    }
+
+    /// Nonce as signed into a payload for `chain`
+    pub fn on_chain(&self, chain: Blockchain) -> Nonce {
+        if self.asset.blockchain() == chain {
+            self.nonce
+        } else {
+            Nonce::Crosschain
+        }
+    }
+}
+
+/// The nonce as tracked for its asset, regardless of the chain it is signed for
+impl From<TypedNonce> for Nonce {
+    fn from(nonce: TypedNonce) -> Self {
+        nonce.nonce
+    }
+}
+
 #[derive(Clone, Debug, PartialEq)]
 pub enum CandleInterval {
     FifteenMinute,
@@ -526,12 +851,12 @@ pub struct Candle {
     pub low_price: BigDecimal,
     pub open_price: BigDecimal,
     pub interval: CandleInterval,
-    pub interval_start: DateTime<Utc>,
+    pub interval_start: Timestamp,
 }
 #[derive(Clone, Copy, Debug)]
 pub struct DateTimeRange {
-    pub start: DateTime<Utc>,
-    pub stop: DateTime<Utc>,
+    pub start: Timestamp,
+    pub stop: Timestamp,
 }
 
 /// Status of an order on Nash
@@ -559,7 +884,7 @@ pub struct Trade {
     pub taker_order_id: String,
     pub maker_order_id: String,
     pub amount: BigDecimal,
-    pub executed_at: DateTime<Utc>,
+    pub executed_at: Timestamp,
     pub account_side: AccountTradeSide,
     pub maker_fee: BigDecimal,
     pub taker_fee: BigDecimal,
@@ -570,14 +895,127 @@ pub struct Trade {
     pub limit_price: BigDecimal,
 }
 
-#[derive(Clone, Copy, Debug, PartialEq)]
+/// Account centric view of a `Trade`: the side this account took, the fee it paid and
+/// what it received on a single execution
+#[derive(Clone, Debug)]
+pub struct Fill {
+    pub trade_id: String,
+    pub order_id: String,
+    pub market: String,
+    pub buy_or_sell: BuyOrSell,
+    // whether the account was maker or taker
+    pub liquidity: AccountTradeSide,
+    pub amount: BigDecimal,
+    pub price: BigDecimal,
+    pub fee: BigDecimal,
+    pub received: BigDecimal,
+    pub executed_at: Timestamp,
+}
+
+impl TryFrom<&Trade> for Fill {
+    type Error = ProtocolError;
+    fn try_from(trade: &Trade) -> Result<Self> {
+        // `direction` is the taker's side, the maker took the opposite one
+        let (order_id, buy_or_sell, fee, received) = match trade.account_side {
+            AccountTradeSide::Taker => (
+                &trade.taker_order_id,
+                trade.direction,
+                &trade.taker_fee,
+                &trade.taker_recieved,
+            ),
+            AccountTradeSide::Maker => (
+                &trade.maker_order_id,
+                match trade.direction {
+                    BuyOrSell::Buy => BuyOrSell::Sell,
+                    BuyOrSell::Sell => BuyOrSell::Buy,
+                },
+                &trade.maker_fee,
+                &trade.maker_recieved,
+            ),
+            AccountTradeSide::None => {
+                return Err(ProtocolError("Trade is not associated with this account"))
+            }
+        };
+        Ok(Self {
+            trade_id: trade.id.clone(),
+            order_id: order_id.clone(),
+            market: trade.market.clone(),
+            buy_or_sell,
+            liquidity: trade.account_side.clone(),
+            amount: trade.amount.clone(),
+            price: trade.limit_price.clone(),
+            fee: fee.clone(),
+            received: received.clone(),
+            executed_at: trade.executed_at,
+        })
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
 pub enum OrderCancellationPolicy {
     FillOrKill,
     GoodTilCancelled,
-    GoodTilTime(DateTime<Utc>),
+    GoodTilTime(#[serde(with = "timestamp::rfc3339")] Timestamp),
     ImmediateOrCancel,
 }
 
+/// Latest expiry accepted for good til time orders, relative to the time the order is placed
+pub const MAX_GOOD_TIL_TIME_MILLIS: i64 = 365 * 24 * 60 * 60 * 1000;
+
+/// Reasons a good til time expiry is refused before the order is sent
+#[derive(Clone, Copy, Debug, PartialEq, thiserror::Error)]
+pub enum GoodTilTimeError {
+    #[error("Good til time expiry {expires_at} is not after the order time {now}")]
+    NotInFuture { expires_at: i64, now: i64 },
+    #[error("Good til time expiry {expires_at} is more than {max} ms after the order time {now}")]
+    TooFarInFuture { expires_at: i64, now: i64, max: i64 },
+}
+
+impl From<GoodTilTimeError> for ProtocolError {
+    fn from(error: GoodTilTimeError) -> Self {
+        ProtocolError::with_source("Invalid good til time expiry", error)
+    }
+}
+
+impl OrderCancellationPolicy {
+    /// RFC3339 UTC expiry to send as `cancelAt` for an order placed at `now` (unix millis).
+    /// `None` for policies without an expiry.
+    pub fn cancel_at(&self, now: i64) -> std::result::Result<Option<String>, GoodTilTimeError> {
+        let time = match self {
+            Self::GoodTilTime(time) => time,
+            _ => return Ok(None),
+        };
+        let expires_at = timestamp::unix_millis(time);
+        if expires_at <= now {
+            return Err(GoodTilTimeError::NotInFuture { expires_at, now });
+        }
+        if expires_at - now > MAX_GOOD_TIL_TIME_MILLIS {
+            return Err(GoodTilTimeError::TooFarInFuture {
+                expires_at,
+                now,
+                max: MAX_GOOD_TIL_TIME_MILLIS,
+            });
+        }
+        Ok(Some(timestamp::format_timestamp(time)))
+    }
+
+    /// Orders that execute on arrival, in part (IOC) or in full (FOK), and never rest on the book
+    pub fn is_immediate(&self) -> bool {
+        matches!(self, Self::ImmediateOrCancel | Self::FillOrKill)
+    }
+
+    /// Check the policy against the other order parameters before the order is signed. An
+    /// immediate order that may not take liquidity could only ever be cancelled.
+    pub fn check_order(&self, allow_taker: bool) -> Result<()> {
+        if self.is_immediate() && !allow_taker {
+            return Err(ProtocolError(
+                "Immediate or cancel and fill or kill orders must be allowed to take liquidity",
+            ));
+        }
+        Ok(())
+    }
+}
+
 #[derive(Clone, Copy, Debug, PartialEq)]
 pub enum OrderCancellationReason {
     AdminCancelled,
@@ -599,7 +1037,7 @@ pub struct Order {
     pub amount_executed: BigDecimal,
     pub limit_price: Option<BigDecimal>,
     pub stop_price: Option<BigDecimal>,
-    pub placed_at: DateTime<Utc>,
+    pub placed_at: Timestamp,
     pub buy_or_sell: BuyOrSell,
     pub cancellation_policy: Option<OrderCancellationPolicy>,
     pub cancellation_reason: Option<OrderCancellationReason>,
@@ -609,6 +1047,16 @@ pub struct Order {
     pub trades: Vec<Trade>,
 }
 
+impl Order {
+    /// Fills of this order from the account's point of view
+    pub fn fills(&self) -> Vec<Fill> {
+        self.trades
+            .iter()
+            .filter_map(|trade| Fill::try_from(trade).ok())
+            .collect()
+    }
+}
+
 /// Compressed representation for Order as returned by Orderbook queries and subscriptions
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct OrderbookOrder {
@@ -618,11 +1066,178 @@ pub struct OrderbookOrder {
 
 #[cfg(test)]
 mod tests {
-    use super::{BigDecimal, FromStr, OrderRate};
+    use super::{AccountTradeSide, BigDecimal, BuyOrSell, Fill, FromStr, OrderRate, Trade, TryFrom};
+    use super::{AccountFeeRates, Asset, AssetAmount, GoodTilTimeError, OrderCancellationPolicy, Rate};
+    use super::{Blockchain, Nonce, TypedNonce};
+    use crate::types::timestamp;
+
+    #[test]
+    fn asset_amount_arithmetic_keeps_precision() {
+        let eth = Asset::ETH.with_precision(4);
+        let usdc = Asset::USDC.with_precision(2);
+        let amount = |asset, value: &str| AssetAmount {
+            asset,
+            amount: super::Amount::new(value, 4).unwrap(),
+        };
+        let a = amount(eth, "1.2345");
+        let b = amount(eth, "0.0005");
+        assert_eq!(a.add(&b).unwrap().amount.value, BigDecimal::from_str("1.235").unwrap());
+        assert_eq!(a.sub(&b).unwrap().amount.value, BigDecimal::from_str("1.234").unwrap());
+        assert!(b.sub(&a).is_err());
+        assert!(a > b);
+        let dollars = amount(usdc, "1");
+        assert!(a.add(&dollars).is_err());
+        assert_eq!(a.partial_cmp(&dollars), None);
+        let price: Rate = OrderRate::new("2000.123").unwrap().into();
+        let value = a.mul_rate(&price, usdc).unwrap();
+        // 2469.1518435 truncated to cents
+        assert_eq!(value.amount.value, BigDecimal::from_str("2469.15").unwrap());
+        assert!(price > Rate::MinOrderRate);
+    }
+
+    #[test]
+    fn rate_bounds_are_below_and_above_any_rate() {
+        let price: Rate = OrderRate::new("2000").unwrap().into();
+        let tiny: Rate = OrderRate::new("0.0001").unwrap().into();
+        assert!(price < Rate::MaxOrderRate);
+        assert!(tiny < Rate::MaxOrderRate);
+        assert!(tiny > Rate::MinOrderRate);
+        assert!(Rate::MinOrderRate < Rate::MaxOrderRate);
+        assert_eq!(
+            Rate::MaxOrderRate.partial_cmp(&Rate::MaxOrderRate),
+            Some(std::cmp::Ordering::Equal)
+        );
+        let fee = Rate::FeeRate(OrderRate::new("0.0025").unwrap());
+        assert!(fee < Rate::MaxFeeRate);
+        assert_eq!(price.partial_cmp(&fee), None);
+        assert_eq!(Rate::MaxOrderRate.partial_cmp(&Rate::MaxFeeRate), None);
+    }
+
+    #[test]
+    fn amounts_only_compare_equal_when_equal() {
+        let four = super::Amount::new("1.5", 4).unwrap();
+        let two = super::Amount::new("1.5", 2).unwrap();
+        assert_ne!(four, two);
+        assert_ne!(four.partial_cmp(&two), Some(std::cmp::Ordering::Equal));
+        assert!(two < four);
+        assert!(super::Amount::new("1.4", 8).unwrap() < two);
+    }
+
+    #[test]
+    fn orders_are_checked_against_market() {
+        let eth = Asset::ETH.with_precision(4);
+        let usdc = Asset::USDC.with_precision(2);
+        let min_size = |asset, value: &str| AssetAmount {
+            asset,
+            amount: super::Amount::new(value, 4).unwrap(),
+        };
+        let market = super::Market::new(eth, usdc, min_size(eth, "0.01"), min_size(usdc, "5"));
+        assert!(market.validate_order("0.0125", Some("400.5")).is_ok());
+        assert!(market.validate_order("0.01", None).is_ok());
+        let error = |amount, price| market.validate_order(amount, price).unwrap_err().to_string();
+        assert_eq!(error("0.00001", None), "Amount 0.00001 has more than the 4 decimals allowed for eth");
+        assert_eq!(error("0.005", None), "Amount 0.005 eth is below the minimum trade size of 0.0100 on eth_usdc");
+        assert_eq!(error("1", Some("200.001")), "Price 200.001 has more than the 2 decimals allowed for usdc");
+        assert!(error("0.02", Some("200")).starts_with("Order value 4.00 usdc is below"));
+        assert!(error("-1", None).contains("must be positive"));
+        assert!(error("1", Some("abc")).starts_with("Invalid order price"));
+    }
+
+    #[test]
+    fn rounding_modes() {
+        use super::RoundingMode::{Ceil, Floor, HalfEven};
+        let round = |mode: super::RoundingMode, value: &str| {
+            mode.round(&BigDecimal::from_str(value).unwrap(), 2).unwrap().to_string()
+        };
+        assert_eq!(round(Floor, "1.239"), "1.23");
+        assert_eq!(round(Ceil, "1.231"), "1.24");
+        assert_eq!(round(Floor, "-1.231"), "-1.24");
+        assert_eq!(round(Ceil, "-1.239"), "-1.23");
+        assert_eq!(round(HalfEven, "1.235"), "1.24");
+        assert_eq!(round(HalfEven, "1.245"), "1.24");
+        assert_eq!(round(HalfEven, "1.2451"), "1.25");
+        assert_eq!(round(HalfEven, "-1.235"), "-1.24");
+        assert_eq!(round(Ceil, "7"), "7.00");
+        assert_eq!(super::RoundingMode::for_price(BuyOrSell::Sell), Ceil);
+        let rate = OrderRate::new("3").unwrap().invert_rate(Some(4)).unwrap();
+        assert_eq!(rate.to_bigdecimal().to_string(), "0.3333");
+    }
+
+    #[test]
+    fn good_til_time_is_utc_and_validated() {
+        let expiry = timestamp::parse_timestamp("2021-03-01T12:00:00+02:00").unwrap();
+        let policy = OrderCancellationPolicy::GoodTilTime(expiry);
+        let now = timestamp::unix_millis(&expiry) - 60_000;
+        let cancel_at = policy.cancel_at(now).unwrap().unwrap();
+        assert!(cancel_at.starts_with("2021-03-01T10:00:00"));
+        assert!(cancel_at.ends_with('Z'));
+        assert!(matches!(
+            policy.cancel_at(now + 120_000),
+            Err(GoodTilTimeError::NotInFuture { .. })
+        ));
+        assert!(matches!(
+            policy.cancel_at(now - super::MAX_GOOD_TIL_TIME_MILLIS),
+            Err(GoodTilTimeError::TooFarInFuture { .. })
+        ));
+        assert_eq!(OrderCancellationPolicy::GoodTilCancelled.cancel_at(now), Ok(None));
+    }
+
+    #[test]
+    fn immediate_policies_must_allow_taker() {
+        for policy in &[OrderCancellationPolicy::ImmediateOrCancel, OrderCancellationPolicy::FillOrKill] {
+            assert!(policy.is_immediate());
+            assert!(policy.check_order(true).is_ok());
+            assert!(policy.check_order(false).is_err());
+        }
+        assert!(OrderCancellationPolicy::GoodTilCancelled.check_order(false).is_ok());
+    }
+
+    #[test]
+    fn maker_fill_takes_opposite_side() {
+        let trade = Trade {
+            id: "trade".to_string(),
+            taker_order_id: "taker".to_string(),
+            maker_order_id: "maker".to_string(),
+            amount: BigDecimal::from_str("1").unwrap(),
+            executed_at: timestamp::now(),
+            account_side: AccountTradeSide::Maker,
+            maker_fee: BigDecimal::from_str("0.1").unwrap(),
+            taker_fee: BigDecimal::from_str("0.2").unwrap(),
+            maker_recieved: BigDecimal::from_str("3").unwrap(),
+            taker_recieved: BigDecimal::from_str("4").unwrap(),
+            market: "eth_usdc".to_string(),
+            direction: BuyOrSell::Buy,
+            limit_price: BigDecimal::from_str("150").unwrap(),
+        };
+        let fill = Fill::try_from(&trade).unwrap();
+        assert_eq!(fill.order_id, "maker");
+        assert_eq!(fill.buy_or_sell, BuyOrSell::Sell);
+        assert_eq!(fill.fee, trade.maker_fee);
+    }
+
+    #[test]
+    fn typed_nonce_is_crosschain_on_other_chains() {
+        let nonce = TypedNonce::new(Asset::ETH, 42);
+        assert_eq!(nonce.on_chain(Blockchain::Ethereum), Nonce::Value(42));
+        assert_eq!(nonce.on_chain(Blockchain::NEO), Nonce::Crosschain);
+        assert_eq!(Nonce::from(nonce), Nonce::Value(42));
+    }
+
+    #[test]
+    fn account_fee_rates_are_bounded() {
+        let maker = BigDecimal::from_str("0.001").unwrap();
+        let taker = BigDecimal::from_str("0.002").unwrap();
+        let rates = AccountFeeRates::new(maker.clone(), taker.clone()).unwrap();
+        assert_eq!(rates.for_order(false).to_bigdecimal().unwrap(), maker);
+        assert_eq!(rates.for_order(true).to_bigdecimal().unwrap(), taker);
+        assert!(AccountFeeRates::new(maker.clone(), BigDecimal::from_str("0.003").unwrap()).is_err());
+        assert!(AccountFeeRates::new(BigDecimal::from(-1), taker).is_err());
+    }
+
     #[test]
     fn fee_rate_conversion_precision() {
         let rate = OrderRate::new("150").unwrap();
-        let inverted_rate = rate.invert_rate(None);
+        let inverted_rate = rate.invert_rate(None).unwrap();
         let minus_fee = inverted_rate.subtract_fee(BigDecimal::from_str("0.0025").unwrap());
         let payload = minus_fee.to_be_bytes().unwrap();
         assert_eq!(665000, u64::from_be_bytes(payload));
@@ -0,0 +1,134 @@
+//! Market identifiers. Requests send the market as a string (e.g. `eth_usdc`), which
+//! `MarketSymbol` parses and normalizes, so that the reverse market and the assets of a
+//! market are worked out in one place.
+
+use std::convert::TryFrom;
+use std::fmt;
+use std::str::FromStr;
+
+use crate::errors::{ProtocolError, Result};
+
+/// Symbol of an A/B market, e.g. `eth_usdc` where A is ETH and B is USDC. Parsing accepts
+/// any case, surrounding whitespace and `/` or `-` as separator (`ETH/USDC`), and
+/// normalizes to the lowercase `a_b` form the exchange uses.
+#[derive(Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct MarketSymbol {
+    name: String,
+    /// Position of the `_` in `name`
+    separator: usize,
+}
+
+impl MarketSymbol {
+    /// Symbol of the market trading `base` (A) in `quote` (B)
+    pub fn new(base: &str, quote: &str) -> Result<Self> {
+        let base = base.trim().to_lowercase();
+        let quote = quote.trim().to_lowercase();
+        let valid = |asset: &str| !asset.is_empty() && asset.chars().all(char::is_alphanumeric);
+        if !valid(&base) || !valid(&quote) {
+            return Err(ProtocolError::coerce_static_from_str(&format!(
+                "Invalid market {}_{}: assets must be non-empty and alphanumeric",
+                base, quote
+            )));
+        }
+        if base == quote {
+            return Err(ProtocolError::coerce_static_from_str(&format!(
+                "Invalid market {}_{}: both assets are the same",
+                base, quote
+            )));
+        }
+        Ok(Self {
+            separator: base.len(),
+            name: format!("{}_{}", base, quote),
+        })
+    }
+
+    /// Asset A, in which order amounts are given
+    pub fn base(&self) -> &str {
+        &self.name[..self.separator]
+    }
+
+    /// Asset B, in which prices are given
+    pub fn quote(&self) -> &str {
+        &self.name[self.separator + 1..]
+    }
+
+    /// The B/A market, e.g. `usdc_eth` for `eth_usdc`
+    pub fn reversed(&self) -> Self {
+        Self {
+            separator: self.name.len() - self.separator - 1,
+            name: format!("{}_{}", self.quote(), self.base()),
+        }
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.name
+    }
+}
+
+impl FromStr for MarketSymbol {
+    type Err = ProtocolError;
+
+    fn from_str(market: &str) -> Result<Self> {
+        let mut assets = market.trim().split(|c| c == '_' || c == '/' || c == '-');
+        match (assets.next(), assets.next(), assets.next()) {
+            (Some(base), Some(quote), None) => Self::new(base, quote),
+            _ => Err(ProtocolError::coerce_static_from_str(&format!(
+                "Invalid market {:?}: expected two assets, as in eth_usdc",
+                market
+            ))),
+        }
+    }
+}
+
+impl TryFrom<&str> for MarketSymbol {
+    type Error = ProtocolError;
+
+    fn try_from(market: &str) -> Result<Self> {
+        market.parse()
+    }
+}
+
+impl TryFrom<String> for MarketSymbol {
+    type Error = ProtocolError;
+
+    fn try_from(market: String) -> Result<Self> {
+        market.parse()
+    }
+}
+
+impl AsRef<str> for MarketSymbol {
+    fn as_ref(&self) -> &str {
+        &self.name
+    }
+}
+
+impl fmt::Display for MarketSymbol {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.name)
+    }
+}
+
+impl From<MarketSymbol> for String {
+    fn from(symbol: MarketSymbol) -> Self {
+        symbol.name
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::MarketSymbol;
+
+    #[test]
+    fn symbols_are_normalized_and_reversed() {
+        let symbol: MarketSymbol = " ETH/usdc ".parse().unwrap();
+        assert_eq!(symbol.as_str(), "eth_usdc");
+        assert_eq!((symbol.base(), symbol.quote()), ("eth", "usdc"));
+        let reversed = symbol.reversed();
+        assert_eq!(reversed, "usdc-eth".parse().unwrap());
+        assert_eq!((reversed.base(), reversed.quote()), ("usdc", "eth"));
+        assert_eq!(reversed.reversed(), symbol);
+        for invalid in &["eth", "eth_", "eth_usdc_btc", "eth_eth", "e th_usdc"] {
+            assert!(invalid.parse::<MarketSymbol>().is_err(), "{}", invalid);
+        }
+    }
+}
@@ -3,9 +3,12 @@
 pub mod blockchain;
 pub mod exchange;
 pub mod keys;
+mod market_symbol;
+pub mod timestamp;
 
-pub use blockchain::{eth, neo, AssetOrCrosschain, Prefix, PublicKey};
+pub use blockchain::{eth, neo, Address, AssetOrCrosschain, Prefix, PublicKey};
 pub use exchange::{
+    AccountFeeRates,
     AccountTradeSide,
     Amount,
     Asset,
@@ -16,6 +19,8 @@ pub use exchange::{
     Candle,
     CandleInterval,
     DateTimeRange,
+    Fill,
+    GoodTilTimeError,
     Market,
     Nonce,
     Order,
@@ -26,6 +31,10 @@ pub use exchange::{
     OrderType,
     OrderbookOrder,
     Rate,
+    RoundingMode,
     Trade,
+    TypedNonce,
 };
-pub use keys::ApiKeys;
+pub use market_symbol::MarketSymbol;
+pub use keys::{migrate_keyfile, probe_keyfile, ApiKeys, KeyfileFormat};
+pub use timestamp::Timestamp;
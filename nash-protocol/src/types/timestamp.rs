@@ -0,0 +1,124 @@
+//! Timestamp type used across protocol types. `chrono` is used by default; building with
+//! the `time` feature and without `chrono` switches `Timestamp` to `time::OffsetDateTime`.
+//! Protocol code only parses and formats timestamps through the helpers in this module.
+
+use crate::errors::{ProtocolError, Result};
+
+#[cfg(not(any(feature = "chrono", feature = "time")))]
+compile_error!("nash-protocol requires either the `chrono` or the `time` feature");
+
+#[cfg(feature = "chrono")]
+pub type Timestamp = chrono::DateTime<chrono::Utc>;
+
+#[cfg(all(feature = "time", not(feature = "chrono")))]
+pub type Timestamp = time::OffsetDateTime;
+
+/// Parse an RFC3339 timestamp as returned by the Nash API
+#[cfg(feature = "chrono")]
+pub fn parse_timestamp(value: &str) -> Result<Timestamp> {
+    chrono::DateTime::parse_from_rfc3339(value)
+        .map(|time| time.with_timezone(&chrono::Utc))
+        .map_err(|_| ProtocolError("Could not convert value to DateTime"))
+}
+
+/// Parse an RFC3339 timestamp as returned by the Nash API
+#[cfg(all(feature = "time", not(feature = "chrono")))]
+pub fn parse_timestamp(value: &str) -> Result<Timestamp> {
+    time::OffsetDateTime::parse(value, &time::format_description::well_known::Rfc3339)
+        .map(|time| time.to_offset(time::UtcOffset::UTC))
+        .map_err(|_| ProtocolError("Could not convert value to DateTime"))
+}
+
+/// Format a timestamp as RFC3339 in UTC, the form the Nash API expects
+#[cfg(feature = "chrono")]
+pub fn format_timestamp(timestamp: &Timestamp) -> String {
+    timestamp.to_rfc3339_opts(chrono::SecondsFormat::AutoSi, true)
+}
+
+/// Format a timestamp as RFC3339 in UTC, the form the Nash API expects
+#[cfg(all(feature = "time", not(feature = "chrono")))]
+pub fn format_timestamp(timestamp: &Timestamp) -> String {
+    timestamp
+        .to_offset(time::UtcOffset::UTC)
+        .format(&time::format_description::well_known::Rfc3339)
+        .expect("UTC timestamps can always be formatted as RFC3339")
+}
+
+/// Current time
+#[cfg(feature = "chrono")]
+pub fn now() -> Timestamp {
+    chrono::Utc::now()
+}
+
+/// Current time
+#[cfg(all(feature = "time", not(feature = "chrono")))]
+pub fn now() -> Timestamp {
+    time::OffsetDateTime::now_utc()
+}
+
+/// Milliseconds since the unix epoch
+#[cfg(feature = "chrono")]
+pub fn unix_millis(timestamp: &Timestamp) -> i64 {
+    timestamp.timestamp_millis()
+}
+
+/// Milliseconds since the unix epoch
+#[cfg(all(feature = "time", not(feature = "chrono")))]
+pub fn unix_millis(timestamp: &Timestamp) -> i64 {
+    (timestamp.unix_timestamp_nanos() / 1_000_000) as i64
+}
+
+/// Serde adapter so `Timestamp` fields (de)serialize as RFC3339 strings with either backend
+pub mod rfc3339 {
+    use super::{format_timestamp, parse_timestamp, Timestamp};
+    use serde::{de, Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(timestamp: &Timestamp, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&format_timestamp(timestamp))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Timestamp, D::Error> {
+        let value = String::deserialize(deserializer)?;
+        parse_timestamp(&value).map_err(de::Error::custom)
+    }
+}
+
+/// Serde adapter for optional `Timestamp` fields
+pub mod rfc3339_option {
+    use super::{format_timestamp, parse_timestamp, Timestamp};
+    use serde::{de, Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(
+        timestamp: &Option<Timestamp>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        match timestamp {
+            Some(timestamp) => serializer.serialize_some(&format_timestamp(timestamp)),
+            None => serializer.serialize_none(),
+        }
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Option<Timestamp>, D::Error> {
+        match Option::<String>::deserialize(deserializer)? {
+            Some(value) => parse_timestamp(&value).map(Some).map_err(de::Error::custom),
+            None => Ok(None),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{format_timestamp, parse_timestamp};
+
+    #[test]
+    fn rfc3339_roundtrip() {
+        let parsed = parse_timestamp("2020-09-12T10:20:30.5+02:00").unwrap();
+        let formatted = format_timestamp(&parsed);
+        assert!(formatted.starts_with("2020-09-12T08:20:30.5"));
+        assert!(formatted.ends_with('Z'));
+        assert_eq!(parse_timestamp(&formatted).unwrap(), parsed);
+        assert!(parse_timestamp("12/09/2020").is_err());
+    }
+}